@@ -1,8 +1,12 @@
 //! Data types, functions and traits for `sys` runtime's `Instance` implementation.
 
 use crate::{
-    Extern, error::InstantiationError, exports::Exports, imports::Imports, module::Module,
-    store::AsStoreMut,
+    Extern, InstanceSnapshot, SnapshotError,
+    error::InstantiationError,
+    exports::Exports,
+    imports::Imports,
+    module::Module,
+    store::{AsStoreMut, AsStoreRef},
 };
 use wasmer_vm::{StoreHandle, VMInstance};
 
@@ -79,6 +83,25 @@ impl Instance {
         Ok((instance, exports))
     }
 
+    pub(crate) fn snapshot(
+        &self,
+        store: &impl AsStoreRef,
+    ) -> Result<InstanceSnapshot, SnapshotError> {
+        self._handle
+            .get(store.as_store_ref().objects().as_sys())
+            .snapshot()
+    }
+
+    pub(crate) fn restore(
+        &self,
+        store: &mut impl AsStoreMut,
+        snapshot: &InstanceSnapshot,
+    ) -> Result<(), SnapshotError> {
+        self._handle
+            .get_mut(store.as_store_mut().objects_mut().as_sys_mut())
+            .restore(snapshot)
+    }
+
     fn get_exports(
         store: &mut impl AsStoreMut,
         module: &Module,
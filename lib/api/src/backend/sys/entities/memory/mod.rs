@@ -7,7 +7,7 @@ use std::{
 };
 
 use tracing::warn;
-use wasmer_types::{MemoryType, Pages};
+use wasmer_types::{DirtyPageTracking, DirtyRanges, MemoryEpoch, MemoryType, Pages};
 use wasmer_vm::{LinearMemory, MemoryError, StoreHandle, ThreadConditionsHandle, VMMemory};
 
 use crate::{
@@ -67,9 +67,13 @@ impl Memory {
     where
         IntoPages: Into<Pages>,
     {
-        self.handle
-            .get_mut(store.objects_mut().as_sys_mut())
-            .grow(delta.into())
+        let objects = store.objects_mut().as_sys_mut();
+        assert_eq!(
+            self.handle.store_id(),
+            objects.id(),
+            "object used with the wrong context"
+        );
+        objects.grow_memory(self.handle.internal_handle(), delta.into())
     }
 
     pub(crate) fn grow_at_least(
@@ -77,9 +81,13 @@ impl Memory {
         store: &mut impl AsStoreMut,
         min_size: u64,
     ) -> Result<(), MemoryError> {
-        self.handle
-            .get_mut(store.objects_mut().as_sys_mut())
-            .grow_at_least(min_size)
+        let objects = store.objects_mut().as_sys_mut();
+        assert_eq!(
+            self.handle.store_id(),
+            objects.id(),
+            "object used with the wrong context"
+        );
+        objects.grow_memory_at_least(self.handle.internal_handle(), min_size)
     }
 
     pub(crate) fn reset(&self, store: &mut impl AsStoreMut) -> Result<(), MemoryError> {
@@ -89,6 +97,25 @@ impl Memory {
         Ok(())
     }
 
+    pub(crate) fn enable_dirty_tracking(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<DirtyPageTracking, MemoryError> {
+        self.handle
+            .get_mut(store.as_store_mut().objects_mut().as_sys_mut())
+            .enable_dirty_tracking()
+    }
+
+    pub(crate) fn dirty_ranges_since(
+        &self,
+        store: &mut impl AsStoreMut,
+        epoch: MemoryEpoch,
+    ) -> Result<DirtyRanges, MemoryError> {
+        self.handle
+            .get_mut(store.as_store_mut().objects_mut().as_sys_mut())
+            .dirty_ranges_since(epoch)
+    }
+
     pub(crate) fn from_vm_extern(store: &impl AsStoreRef, vm_extern: VMExternMemory) -> Self {
         Self {
             handle: unsafe {
@@ -125,10 +125,14 @@ impl Table {
     ) -> Result<u32, RuntimeError> {
         let item = value_to_table_element(store, init)?;
         let obj_mut = store.objects_mut().as_sys_mut();
-
-        self.handle
-            .get_mut(obj_mut)
-            .grow(delta, item)
+        assert_eq!(
+            self.handle.store_id(),
+            obj_mut.id(),
+            "object used with the wrong context"
+        );
+
+        obj_mut
+            .grow_table(self.handle.internal_handle(), delta, item)
             .ok_or_else(|| RuntimeError::new(format!("failed to grow table by `{delta}`")))
     }
 
@@ -11,10 +11,13 @@ pub use tunables::*;
 
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{
-    CompilerConfig, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware, wasmparser,
+    CompilerConfig, FunctionBodyData, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware,
+    wasmparser,
 };
 
 pub use wasmer_compiler::{Artifact, EngineBuilder, Features, Tunables};
+pub use wasmer_types::{MemoryIndex, TableIndex};
+pub use wasmer_vm::{LimitedMemory, LimitedTable, ResourceLimiter};
 
 pub use wasmer_types::MiddlewareError;
 pub use wasmer_types::target::{Architecture, CpuFeature, OperatingSystem, Target, Triple};
@@ -1,4 +1,5 @@
-pub use wasmer_compiler::BaseTunables;
+pub use wasmer_compiler::{BaseTunables, PoolingTunables};
+pub use wasmer_vm::PoolingConfig;
 
 // All BaseTunable definition now is in wasmer_compile crate
 // Tests are still here
@@ -305,6 +306,78 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn check_pooling_tunables() -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(feature = "wat")]
+        use crate::wat2wasm;
+        use crate::{Engine, Instance, Module, Store, imports};
+
+        let wasm_bytes = wat2wasm(
+            br#"(module
+            (memory (export "memory") 1 4)
+            (table 2 funcref)
+          )"#,
+        )?;
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "singlepass")] {
+                let compiler =  wasmer_compiler_singlepass::Singlepass::default();
+            } else if #[cfg(feature = "llvm")] {
+                let compiler =  wasmer_compiler_llvm::LLVM::default();
+            } else {
+                let compiler =  wasmer_compiler_cranelift::Cranelift::default();
+            }
+        }
+
+        let tunables = PoolingTunables::new(PoolingConfig {
+            max_instances: 2,
+            max_memory_pages: Pages(4),
+            ..Default::default()
+        })?;
+        let allocator = tunables.allocator().clone();
+        #[allow(deprecated)]
+        let mut engine = Engine::new(compiler.into(), Default::default(), Default::default());
+        engine.set_tunables(tunables);
+        let mut store = Store::new(engine.clone());
+        let module = Module::new(&store, wasm_bytes)?;
+
+        let first = Instance::new(&mut store, &module, &imports! {})?;
+        let second = Instance::new(&mut store, &module, &imports! {})?;
+        assert_eq!(allocator.available_slots(), 0);
+        assert!(Instance::new(&mut store, &module, &imports! {}).is_err());
+
+        for instance in [&first, &second] {
+            let memory = instance.exports.get_memory("memory")?;
+            memory.view(&store).write_u8(0, 42)?;
+        }
+        let memory = second.exports.get_memory("memory")?;
+        assert_eq!(memory.grow(&mut store, 3)?, Pages(1));
+        assert!(memory.grow(&mut store, 1).is_err());
+        memory
+            .view(&store)
+            .write_u8(WASM_PAGE_SIZE as u64 * 4 - 1, 42)?;
+
+        // The slots are given back, zeroed, once the store is dropped.
+        drop(store);
+        assert_eq!(allocator.available_slots(), 2);
+        let mut store = Store::new(engine);
+        let instances = [
+            Instance::new(&mut store, &module, &imports! {})?,
+            Instance::new(&mut store, &module, &imports! {})?,
+        ];
+        for instance in &instances {
+            let memory = instance.exports.get_memory("memory")?;
+            assert_eq!(memory.view(&store).read_u8(0)?, 0);
+            assert_eq!(memory.grow(&mut store, 3)?, Pages(1));
+            assert_eq!(
+                memory.view(&store).read_u8(WASM_PAGE_SIZE as u64 * 4 - 1)?,
+                0
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(all(
         feature = "singlepass",
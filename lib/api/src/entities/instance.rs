@@ -1,6 +1,11 @@
 use crate::{
-    Extern, error::InstantiationError, exports::Exports, imports::Imports,
-    macros::backend::gen_rt_ty, module::Module, store::AsStoreMut,
+    Extern, InstanceSnapshot, SnapshotError,
+    error::InstantiationError,
+    exports::Exports,
+    imports::Imports,
+    macros::backend::gen_rt_ty,
+    module::Module,
+    store::{AsStoreMut, AsStoreRef},
 };
 
 /// A WebAssembly Instance is a stateful, executable
@@ -168,6 +173,71 @@ impl Instance {
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    /// Captures the mutable state of this instance so it can be restored
+    /// into a fresh instance of the same [`Module`] with [`Self::restore`].
+    ///
+    /// The snapshot holds the memories, mutable globals and tables defined
+    /// by the module, whether they are exported or not, along with the
+    /// passive segments that were dropped. Table elements and function
+    /// references in globals are recorded as function indices, so they
+    /// must be null or a function of this instance. Imported memories,
+    /// tables and globals are not part of the snapshot.
+    ///
+    /// Only the `sys` backend supports snapshots.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{imports, Instance, InstanceSnapshot, Module, Store, TypedFunction};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let module = Module::new(&store, r#"
+    ///     (module
+    ///       (global $counter (mut i32) (i32.const 0))
+    ///       (func (export "bump") (result i32)
+    ///         (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    ///         (global.get $counter)))
+    /// "#)?;
+    /// let instance = Instance::new(&mut store, &module, &imports! {})?;
+    /// let bump: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "bump")?;
+    /// bump.call(&mut store)?;
+    ///
+    /// let bytes = instance.snapshot(&store)?.serialize()?;
+    ///
+    /// let warm = Instance::new(&mut store, &module, &imports! {})?;
+    /// warm.restore(&mut store, &InstanceSnapshot::deserialize(&bytes)?)?;
+    /// let bump: TypedFunction<(), i32> = warm.exports.get_typed_function(&store, "bump")?;
+    /// assert_eq!(bump.call(&mut store)?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self, store: &impl AsStoreRef) -> Result<InstanceSnapshot, SnapshotError> {
+        match &self._inner {
+            #[cfg(feature = "sys")]
+            crate::BackendInstance::Sys(instance) => instance.snapshot(store),
+            _ => Err(SnapshotError::Unsupported),
+        }
+    }
+
+    /// Restores a snapshot taken by [`Self::snapshot`] into this instance,
+    /// which should be a fresh instance of the same [`Module`].
+    ///
+    /// Memories and tables are grown to the size they had when the snapshot
+    /// was taken. [`SnapshotError::Incompatible`] is returned, leaving the
+    /// instance untouched, if the snapshot was taken from another module or
+    /// if the instance grew past it.
+    pub fn restore(
+        &self,
+        store: &mut impl AsStoreMut,
+        snapshot: &InstanceSnapshot,
+    ) -> Result<(), SnapshotError> {
+        match &self._inner {
+            #[cfg(feature = "sys")]
+            crate::BackendInstance::Sys(instance) => instance.restore(store, snapshot),
+            _ => Err(SnapshotError::Unsupported),
+        }
+    }
 }
 
 impl std::fmt::Debug for Instance {
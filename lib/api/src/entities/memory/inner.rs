@@ -1,5 +1,5 @@
 use super::{shared::SharedMemory, view::*};
-use wasmer_types::{MemoryError, MemoryType, Pages};
+use wasmer_types::{DirtyPageTracking, DirtyRanges, MemoryEpoch, MemoryError, MemoryType, Pages};
 
 use crate::{
     AsStoreMut, AsStoreRef, ExportError, Exportable, Extern, StoreMut, StoreRef,
@@ -202,6 +202,37 @@ impl BackendMemory {
         })
     }
 
+    /// Starts tracking the pages of the memory that are written to
+    #[inline]
+    pub fn enable_dirty_tracking(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<DirtyPageTracking, MemoryError> {
+        match self {
+            #[cfg(feature = "sys")]
+            Self::Sys(s) => s.enable_dirty_tracking(store),
+            _ => Err(MemoryError::UnsupportedOperation {
+                message: "dirty page tracking is only supported by the sys backend".to_string(),
+            }),
+        }
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`
+    #[inline]
+    pub fn dirty_ranges_since(
+        &self,
+        store: &mut impl AsStoreMut,
+        epoch: MemoryEpoch,
+    ) -> Result<DirtyRanges, MemoryError> {
+        match self {
+            #[cfg(feature = "sys")]
+            Self::Sys(s) => s.dirty_ranges_since(store, epoch),
+            _ => Err(MemoryError::UnsupportedOperation {
+                message: "dirty page tracking is only supported by the sys backend".to_string(),
+            }),
+        }
+    }
+
     /// Attempts to duplicate this memory (if its clonable) in a new store
     /// (copied memory)
     #[inline]
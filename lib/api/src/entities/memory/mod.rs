@@ -1,5 +1,5 @@
 pub use shared::SharedMemory;
-use wasmer_types::{MemoryError, MemoryType, Pages};
+use wasmer_types::{DirtyPageTracking, DirtyRanges, MemoryEpoch, MemoryError, MemoryType, Pages};
 
 use crate::{
     AsStoreMut, AsStoreRef, ExportError, Exportable, Extern, StoreMut, StoreRef,
@@ -151,6 +151,51 @@ impl Memory {
         self.0.reset(store)
     }
 
+    /// Starts tracking which pages of the memory are written to, so that
+    /// [`Memory::dirty_ranges_since`] can tell which parts of the memory
+    /// changed. Calling it again has no effect.
+    ///
+    /// On Linux the kernel records the written pages through `userfaultfd`
+    /// write protection when it supports it, otherwise every page is hashed
+    /// when the dirty ranges are requested. Only the `sys` backend supports
+    /// dirty page tracking.
+    pub fn enable_dirty_tracking(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<DirtyPageTracking, MemoryError> {
+        self.0.enable_dirty_tracking(store)
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`,
+    /// along with the epoch to pass next time to only get newer writes.
+    ///
+    /// Passing [`MemoryEpoch::START`] returns the whole memory. Every
+    /// consumer can keep its own epoch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryEpoch, MemoryType, Store};
+    /// # let mut store = Store::default();
+    /// #
+    /// let m = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+    /// m.enable_dirty_tracking(&mut store).unwrap();
+    /// let all = m.dirty_ranges_since(&mut store, MemoryEpoch::START).unwrap();
+    /// assert_eq!(all.ranges, vec![0..65536]);
+    ///
+    /// m.view(&store).write(100, b"hello").unwrap();
+    /// let dirty = m.dirty_ranges_since(&mut store, all.epoch).unwrap();
+    /// assert_eq!(dirty.ranges.len(), 1);
+    /// assert!(dirty.ranges[0].contains(&100));
+    /// ```
+    pub fn dirty_ranges_since(
+        &self,
+        store: &mut impl AsStoreMut,
+        epoch: MemoryEpoch,
+    ) -> Result<DirtyRanges, MemoryError> {
+        self.0.dirty_ranges_since(store, epoch)
+    }
+
     /// Attempts to duplicate this memory (if its clonable) in a new store
     /// (copied memory)
     pub fn copy_to_store(
@@ -16,7 +16,7 @@ pub(crate) use inner::*;
 use wasmer_types::StoreId;
 
 #[cfg(feature = "sys")]
-use wasmer_vm::TrapHandlerFn;
+use wasmer_vm::{ResourceLimiter, TrapHandlerFn};
 
 /// The store represents all global state that can be manipulated by
 /// WebAssembly programs. It consists of the runtime representation
@@ -88,6 +88,21 @@ impl Store {
         }
     }
 
+    #[cfg(feature = "sys")]
+    /// Set the [`ResourceLimiter`] consulted when the memories and tables of
+    /// this store grow, including when they are created by a new instance.
+    ///
+    /// # Note
+    ///
+    /// Only the `sys` stores support resource limiters. In other stores, this
+    /// function has no effect.
+    pub fn set_resource_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        #[allow(irrefutable_let_patterns)]
+        if let StoreObjects::Sys(ref mut objects) = self.inner.objects {
+            objects.set_limiter(limiter)
+        }
+    }
+
     /// Returns the [`Engine`].
     pub fn engine(&self) -> &Engine {
         self.inner.store.engine()
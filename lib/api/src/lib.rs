@@ -439,9 +439,10 @@ pub use backend::*;
 mod vm;
 
 pub use wasmer_types::{
-    Bytes, CompileError, DeserializeError, ExportIndex, ExportType, ExternType, FrameInfo,
-    FunctionType, GlobalInit, GlobalType, ImportType, LocalFunctionIndex, MemoryError, MemoryStyle,
-    MemoryType, Mutability, OnCalledAction, Pages, ParseCpuFeatureError, SerializeError,
+    Bytes, CompileError, DeserializeError, DirtyPageTracking, DirtyRanges, ExportIndex, ExportType,
+    ExternType, FrameInfo, FunctionType, GlobalInit, GlobalType, ImportType, InstanceSnapshot,
+    LocalFunctionIndex, MemoryEpoch, MemoryError, MemoryStyle, MemoryType, Mutability,
+    OnCalledAction, Pages, ParseCpuFeatureError, SerializeError, SnapshotError, SnapshotValue,
     TableStyle, TableType, TagKind, TagType, Type, ValueType, WASM_MAX_PAGES, WASM_MIN_PAGES,
     WASM_PAGE_SIZE, WasmError, WasmResult, is_wasm,
 };
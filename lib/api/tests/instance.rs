@@ -82,3 +82,102 @@ fn unit_native_function_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn snapshot_restores_into_fresh_instance() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (memory 1)
+  (table 2 funcref)
+  (global $g (mut i64) (i64.const 0))
+  (func $one (result i32) i32.const 1)
+  (func $two (result i32) i32.const 2)
+  (elem declare func $one $two)
+  (data $d "hello")
+  (func (export "mutate")
+    (drop (memory.grow (i32.const 1)))
+    (memory.init $d (i32.const 65536) (i32.const 0) (i32.const 5))
+    (data.drop $d)
+    (global.set $g (i64.const 42))
+    (table.set (i32.const 1) (ref.func $two))
+    (drop (table.grow (ref.func $one) (i32.const 1))))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (result i32) (local.get 0)))
+  (func (export "global") (result i64) global.get $g)
+  (func (export "size") (result i32) memory.size)
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "init") (memory.init $d (i32.const 0) (i32.const 0) (i32.const 1))))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let mutate: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&store, "mutate")
+        .map_err(|e| format!("{e:?}"))?;
+    mutate.call(&mut store).map_err(|e| format!("{e:?}"))?;
+
+    let bytes = instance
+        .snapshot(&store)
+        .map_err(|e| format!("{e:?}"))?
+        .serialize()
+        .map_err(|e| format!("{e:?}"))?;
+    let snapshot = InstanceSnapshot::deserialize(&bytes).map_err(|e| format!("{e:?}"))?;
+
+    let warm = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    warm.restore(&mut store, &snapshot)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let exports = &warm.exports;
+    let call: TypedFunction<i32, i32> = exports
+        .get_typed_function(&store, "call")
+        .map_err(|e| format!("{e:?}"))?;
+    let global: TypedFunction<(), i64> = exports
+        .get_typed_function(&store, "global")
+        .map_err(|e| format!("{e:?}"))?;
+    let size: TypedFunction<(), i32> = exports
+        .get_typed_function(&store, "size")
+        .map_err(|e| format!("{e:?}"))?;
+    let load: TypedFunction<i32, i32> = exports
+        .get_typed_function(&store, "load")
+        .map_err(|e| format!("{e:?}"))?;
+    let init: TypedFunction<(), ()> = exports
+        .get_typed_function(&store, "init")
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(global.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+    assert_eq!(size.call(&mut store).map_err(|e| format!("{e:?}"))?, 2);
+    assert_eq!(
+        load.call(&mut store, 65536 + 4)
+            .map_err(|e| format!("{e:?}"))?,
+        i32::from(b'o')
+    );
+    assert_eq!(call.call(&mut store, 1).map_err(|e| format!("{e:?}"))?, 2);
+    assert_eq!(call.call(&mut store, 2).map_err(|e| format!("{e:?}"))?, 1);
+    assert!(call.call(&mut store, 0).is_err());
+    // The data segment was dropped before the snapshot.
+    assert!(init.call(&mut store).is_err());
+
+    // A restored instance cannot take the snapshot of a smaller one.
+    let cold = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let cold_snapshot = cold.snapshot(&store).map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        warm.restore(&mut store, &cold_snapshot),
+        Err(SnapshotError::Incompatible(_))
+    ));
+
+    let other = Module::new(&store, "(module (memory 1))").map_err(|e| format!("{e:?}"))?;
+    let other = Instance::new(&mut store, &other, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        other.restore(&mut store, &snapshot),
+        Err(SnapshotError::Incompatible(_))
+    ));
+
+    Ok(())
+}
@@ -143,3 +143,201 @@ fn test_wasm_memory_size() {
         assert_eq!(memory.size(&store).0, 11);
     }
 }
+
+#[test]
+#[cfg(feature = "sys")]
+fn test_resource_limiter_denies_memory_growth() {
+    use std::sync::atomic::AtomicU32;
+    use wasmer::{
+        Pages, TypedFunction,
+        sys::{LimitedMemory, LimitedTable, ResourceLimiter},
+    };
+
+    /// Allows at most `max_pages` pages per memory, and counts the denials.
+    #[derive(Debug)]
+    struct Limiter {
+        max_pages: u32,
+        denied: Arc<AtomicU32>,
+    }
+
+    impl ResourceLimiter for Limiter {
+        fn memory_growing(
+            &mut self,
+            _memory: LimitedMemory,
+            _current: Pages,
+            desired: Pages,
+            _: Option<Pages>,
+        ) -> bool {
+            let allowed = desired.0 <= self.max_pages;
+            if !allowed {
+                self.denied.fetch_add(1, Ordering::SeqCst);
+            }
+            allowed
+        }
+
+        fn table_growing(&mut self, _: LimitedTable, _: u32, _: u32, _: Option<u32>) -> bool {
+            true
+        }
+    }
+
+    let mut store = Store::default();
+    let denied = Arc::new(AtomicU32::new(0));
+    store.set_resource_limiter(Some(Box::new(Limiter {
+        max_pages: 3,
+        denied: denied.clone(),
+    })));
+
+    let wat = r#"(module
+        (memory (export "memory") 1)
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0))))"#;
+    let module = Module::new(&store, wat).unwrap();
+    let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+    let grow: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "grow").unwrap();
+
+    assert_eq!(grow.call(&mut store, 2).unwrap(), 1);
+    assert_eq!(grow.call(&mut store, 1).unwrap(), -1);
+    assert_eq!(denied.load(Ordering::SeqCst), 1);
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    assert_eq!(memory.size(&store), Pages(3));
+    assert!(memory.grow(&mut store, Pages(1)).is_err());
+    assert_eq!(denied.load(Ordering::SeqCst), 2);
+
+    let wat = r#"(module (memory 4))"#;
+    let module = Module::new(&store, wat).unwrap();
+    assert!(Instance::new(&mut store, &module, &imports! {}).is_err());
+    assert_eq!(denied.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+#[cfg(feature = "sys")]
+fn test_resource_limiter_identifies_memories_and_tables() {
+    use std::sync::Mutex;
+    use wasmer::{
+        Pages, TypedFunction,
+        sys::{LimitedMemory, LimitedTable, MemoryIndex, ResourceLimiter, TableIndex},
+    };
+
+    /// Records the memories and tables it is consulted for.
+    #[derive(Debug, Default)]
+    struct Limiter {
+        memories: Arc<Mutex<Vec<LimitedMemory>>>,
+        tables: Arc<Mutex<Vec<LimitedTable>>>,
+    }
+
+    impl ResourceLimiter for Limiter {
+        fn memory_growing(
+            &mut self,
+            memory: LimitedMemory,
+            _: Pages,
+            _: Pages,
+            _: Option<Pages>,
+        ) -> bool {
+            self.memories.lock().unwrap().push(memory);
+            true
+        }
+
+        fn table_growing(&mut self, table: LimitedTable, _: u32, _: u32, _: Option<u32>) -> bool {
+            self.tables.lock().unwrap().push(table);
+            true
+        }
+    }
+
+    let limiter = Limiter::default();
+    let memories = limiter.memories.clone();
+    let tables = limiter.tables.clone();
+    let mut store = Store::default();
+    store.set_resource_limiter(Some(Box::new(limiter)));
+
+    let wat = r#"(module
+        (memory 1)
+        (table 1 funcref)
+        (func (export "grow") (result i32)
+            (drop (table.grow (ref.null func) (i32.const 1)))
+            (memory.grow (i32.const 1))))"#;
+    let module = Module::new(&store, wat).unwrap();
+    let grow_instance = |store: &mut Store| {
+        let instance = Instance::new(store, &module, &imports! {}).unwrap();
+        let grow: TypedFunction<(), i32> =
+            instance.exports.get_typed_function(store, "grow").unwrap();
+        assert_eq!(grow.call(store).unwrap(), 1);
+    };
+    grow_instance(&mut store);
+    grow_instance(&mut store);
+
+    // The initial sizes are given by module index, and the growths by
+    // store handle, which differ between the instances.
+    let memories = memories.lock().unwrap().clone();
+    assert_eq!(memories.len(), 4);
+    assert_eq!(
+        memories[0],
+        LimitedMemory::Instantiating(MemoryIndex::from_u32(0))
+    );
+    assert_eq!(
+        memories[2],
+        LimitedMemory::Instantiating(MemoryIndex::from_u32(0))
+    );
+    assert!(matches!(memories[1], LimitedMemory::Store(_)));
+    assert!(matches!(memories[3], LimitedMemory::Store(_)));
+    assert_ne!(memories[1], memories[3]);
+
+    let tables = tables.lock().unwrap().clone();
+    assert_eq!(tables.len(), 4);
+    assert_eq!(
+        tables[0],
+        LimitedTable::Instantiating(TableIndex::from_u32(0))
+    );
+    assert!(matches!(tables[1], LimitedTable::Store(_)));
+    assert_ne!(tables[1], tables[3]);
+}
+
+#[test]
+#[cfg(feature = "sys")]
+fn test_memory_images_are_private_to_each_instance() {
+    let mut store = Store::default();
+    let wat = r#"(module
+        (memory (export "memory") 3)
+        (data (i32.const 10) "hello")
+        (data (i32.const 70000) "world")
+        (data (i32.const 12) "LL"))"#;
+    let module = Module::new(&store, wat).unwrap();
+
+    let first = Instance::new(&mut store, &module, &imports! {}).unwrap();
+    let memory = first.exports.get_memory("memory").unwrap();
+    let mut buffer = [0u8; 5];
+    memory.view(&store).read(10, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"heLLo");
+    memory.view(&store).read(70000, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"world");
+    memory.view(&store).write(10, b"HELLO").unwrap();
+    memory.view(&store).write_u8(0, 1).unwrap();
+
+    let second = Instance::new(&mut store, &module, &imports! {}).unwrap();
+    let memory = second.exports.get_memory("memory").unwrap();
+    memory.view(&store).read(10, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"heLLo");
+    assert_eq!(memory.view(&store).read_u8(0).unwrap(), 0);
+
+    // Segments with a dynamic offset are still copied.
+    let wat = r#"(module
+        (global $base (import "env" "base") i32)
+        (memory (export "memory") 1)
+        (data (i32.const 10) "static")
+        (data (global.get $base) "dynamic"))"#;
+    let module = Module::new(&store, wat).unwrap();
+    let import_object = imports! {
+        "env" => { "base" => wasmer::Global::new(&mut store, wasmer::Value::I32(100)) },
+    };
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut buffer = [0u8; 7];
+    memory.view(&store).read(100, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"dynamic");
+
+    // Segments which do not fit still fail the instantiation.
+    let wat = r#"(module (memory 1) (data (i32.const 65534) "abc"))"#;
+    let module = Module::new(&store, wat).unwrap();
+    assert!(Instance::new(&mut store, &module, &imports! {}).is_err());
+}
@@ -4,6 +4,7 @@
 
 use crate::hash::Hash;
 use std::error::Error;
+use std::future::{self, Future};
 use wasmer::{AsEngineRef, Module};
 
 /// A generic cache for storing and loading compiled wasm modules.
@@ -26,3 +27,87 @@ pub trait Cache {
     /// Store a [`Module`] into the cache with the given [`crate::Hash`].
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError>;
 }
+
+/// A generic cache for storing and loading compiled wasm modules
+/// asynchronously, for example from a remote store.
+///
+/// Every [`Cache`] is an `AsyncCache`, completing its operations
+/// synchronously.
+pub trait AsyncCache {
+    /// The serialization error for the implementation
+    type SerializeError: Error + Send + Sync;
+    /// The deserialization error for the implementation
+    type DeserializeError: Error + Send + Sync;
+
+    /// Loads a module using the provided [`wasmer::Engine`] and [`crate::Hash`].
+    ///
+    /// # Safety
+    /// This function is unsafe as the cache store could be tampered with.
+    unsafe fn load(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+    ) -> impl Future<Output = Result<Module, Self::DeserializeError>> + Send;
+
+    /// Store a [`Module`] into the cache with the given [`crate::Hash`].
+    fn store(
+        &mut self,
+        key: Hash,
+        module: &Module,
+    ) -> impl Future<Output = Result<(), Self::SerializeError>> + Send;
+}
+
+impl<C: Cache> AsyncCache for C {
+    type SerializeError = C::SerializeError;
+    type DeserializeError = C::DeserializeError;
+
+    unsafe fn load(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+    ) -> impl Future<Output = Result<Module, Self::DeserializeError>> + Send {
+        future::ready(unsafe { Cache::load(self, engine, key) })
+    }
+
+    fn store(
+        &mut self,
+        key: Hash,
+        module: &Module,
+    ) -> impl Future<Output = Result<(), Self::SerializeError>> + Send {
+        future::ready(Cache::store(self, key, module))
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::FileSystemCache;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Polls a future which completes synchronously.
+    fn now<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future is pending"),
+        }
+    }
+
+    async fn roundtrip<C: AsyncCache>(cache: &mut C, engine: &wasmer::Engine) -> Module {
+        let wat = r#"(module (func (export "f") (result i32) (i32.const 42)))"#;
+        let module = Module::new(engine, wat).unwrap();
+        let key = Hash::generate(wat.as_bytes());
+        cache.store(key, &module).await.unwrap();
+        unsafe { cache.load(engine, key) }.await.unwrap()
+    }
+
+    #[test]
+    fn test_caches_are_async_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = wasmer::Engine::default();
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+
+        let module = now(roundtrip(&mut cache, &engine));
+        assert!(module.exports().functions().any(|f| f.name() == "f"));
+    }
+}
@@ -18,11 +18,15 @@
 mod cache;
 mod filesystem;
 mod hash;
+#[cfg(feature = "filesystem")]
+mod lru;
 
-pub use crate::cache::Cache;
+pub use crate::cache::{AsyncCache, Cache};
 #[cfg(feature = "filesystem")]
 pub use crate::filesystem::FileSystemCache;
 pub use crate::hash::Hash;
+#[cfg(feature = "filesystem")]
+pub use crate::lru::{CacheEntryMetadata, LruFileSystemCache};
 
 // We re-export those for convinience of users
 pub use wasmer::{DeserializeError, SerializeError};
@@ -0,0 +1,438 @@
+use crate::cache::Cache;
+use crate::hash::Hash;
+use std::fs::{self, create_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wasmer::{AsEngineRef, DeserializeError, Module, SerializeError};
+
+/// Extension of the files holding the serialized modules.
+const MODULE_EXTENSION: &str = "wasmu";
+/// Extension of the files holding the metadata of the entries.
+const METADATA_EXTENSION: &str = "meta";
+/// Extension of the files being written.
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// A directory of compiled wasm artifacts, bounded in size, evicting the
+/// least recently used entries first.
+///
+/// Next to every serialized module, the cache stores the metadata of the
+/// entry: the engine it was compiled with, its size, the time it was last
+/// loaded, and a checksum of its contents. The checksum is verified
+/// before the module is deserialized, so that truncated or altered
+/// entries are discarded rather than loaded. Entries are written to a
+/// temporary file first, so that a directory shared by several processes
+/// never exposes partially written entries.
+///
+/// The size of the cache is kept under its limit every time a module is
+/// stored. Use [`LruFileSystemCache::prune`] to enforce it explicitly,
+/// for example on a directory populated by other processes.
+///
+/// # Usage
+///
+/// ```
+/// use wasmer::{Engine, Module, SerializeError};
+/// use wasmer_cache::{Cache, Hash, LruFileSystemCache};
+///
+/// fn store_module(engine: &Engine, module: &Module, bytes: &[u8]) -> Result<(), SerializeError> {
+///     // Create a cache of at most 512 MiB.
+///     let mut cache = LruFileSystemCache::new("some/directory/goes/here", 512 << 20, engine)?;
+///
+///     // Store a module into the cache, evicting old entries if needed.
+///     cache.store(Hash::generate(bytes), module)?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LruFileSystemCache {
+    path: PathBuf,
+    max_size: u64,
+    engine_id: String,
+}
+
+/// The metadata of an entry of a [`LruFileSystemCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntryMetadata {
+    /// The key the module was stored with.
+    pub key: Hash,
+    /// The deterministic id of the engine the module was compiled with.
+    pub engine_id: String,
+    /// The size of the serialized module, in bytes.
+    pub size: u64,
+    /// The checksum of the serialized module.
+    pub checksum: Hash,
+    /// The last time the module was stored or loaded.
+    pub last_access: SystemTime,
+}
+
+impl CacheEntryMetadata {
+    fn to_text(&self) -> String {
+        let last_access = self
+            .last_access
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        format!(
+            "key={}\nengine={}\nsize={}\nchecksum={}\nlast_access={}\n",
+            self.key, self.engine_id, self.size, self.checksum, last_access
+        )
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let mut key = None;
+        let mut engine_id = None;
+        let mut size = None;
+        let mut checksum = None;
+        let mut last_access = None;
+        for line in text.lines() {
+            let (name, value) = line.split_once('=')?;
+            match name {
+                "key" => key = value.parse().ok(),
+                "engine" => engine_id = Some(value.to_string()),
+                "size" => size = value.parse().ok(),
+                "checksum" => checksum = value.parse().ok(),
+                "last_access" => {
+                    last_access = value
+                        .parse()
+                        .ok()
+                        .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+                }
+                _ => {}
+            }
+        }
+        Some(Self {
+            key: key?,
+            engine_id: engine_id?,
+            size: size?,
+            checksum: checksum?,
+            last_access: last_access?,
+        })
+    }
+}
+
+impl LruFileSystemCache {
+    /// Construct a new `LruFileSystemCache` around the specified
+    /// directory, holding at most `max_size` bytes of modules compiled
+    /// with `engine`.
+    ///
+    /// The directory can be shared by caches of different engines: the
+    /// modules of each engine are stored separately, but all count
+    /// towards the size limit.
+    pub fn new<P: Into<PathBuf>>(
+        path: P,
+        max_size: u64,
+        engine: &impl AsEngineRef,
+    ) -> io::Result<Self> {
+        let path: PathBuf = path.into();
+        create_dir_all(&path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("failed to create cache directory {}: {e}", path.display()),
+            )
+        })?;
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the supplied path already points to a file: {}",
+                    path.display()
+                ),
+            ));
+        }
+        Ok(Self {
+            path,
+            max_size,
+            engine_id: engine.as_engine_ref().engine().deterministic_id(),
+        })
+    }
+
+    /// Returns the maximum size of the cache, in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Sets the maximum size of the cache, in bytes.
+    ///
+    /// The cache is not pruned until the next module is stored, or until
+    /// [`Self::prune`] is called.
+    pub fn set_max_size(&mut self, max_size: u64) {
+        self.max_size = max_size;
+    }
+
+    /// Returns the metadata of all the entries of the cache, of all
+    /// engines, from the least to the most recently used.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntryMetadata>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.path)? {
+            let path = dir_entry?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == METADATA_EXTENSION)
+            {
+                if let Some(metadata) = read_metadata(&path) {
+                    entries.push(metadata);
+                }
+            }
+        }
+        entries.sort_by_key(|entry| entry.last_access);
+        Ok(entries)
+    }
+
+    /// Returns the total size of the modules in the cache, in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|entry| entry.size).sum())
+    }
+
+    /// Removes the entry stored with `key` for the engine of this cache.
+    ///
+    /// Returns whether there was such an entry.
+    pub fn remove(&self, key: Hash) -> io::Result<bool> {
+        self.remove_entry(key, &self.engine_id)
+    }
+
+    /// Evicts the least recently used entries, of all engines, until the
+    /// cache fits in its maximum size. Files which do not belong to a
+    /// valid entry, such as leftovers of interrupted writes or entries
+    /// with unreadable metadata, are removed as well.
+    ///
+    /// Returns the number of bytes freed.
+    pub fn prune(&self) -> io::Result<u64> {
+        let mut freed = 0;
+        for dir_entry in fs::read_dir(&self.path)? {
+            let path = dir_entry?.path();
+            let orphan = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(METADATA_EXTENSION) => {
+                    read_metadata(&path).is_none()
+                        || !path.with_extension(MODULE_EXTENSION).exists()
+                }
+                // The metadata of a module is written right after it.
+                Some(MODULE_EXTENSION) => {
+                    !path.with_extension(METADATA_EXTENSION).exists() && is_stale(&path)
+                }
+                Some(TEMPORARY_EXTENSION) => is_stale(&path),
+                _ => false,
+            };
+            if orphan {
+                freed += path.metadata().map(|m| m.len()).unwrap_or(0);
+                remove_file_if_exists(&path)?;
+            }
+        }
+
+        let entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|entry| entry.size).sum();
+        for entry in entries {
+            if size <= self.max_size {
+                break;
+            }
+            if self.remove_entry(entry.key, &entry.engine_id)? {
+                size -= entry.size;
+                freed += entry.size;
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Path of the files of an entry, without extension.
+    fn entry_path(&self, key: Hash, engine_id: &str) -> PathBuf {
+        // Engine ids are arbitrary strings: only keep what is safe in a file name.
+        let engine_id: String = engine_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.path.join(format!("{key}-{engine_id}"))
+    }
+
+    fn remove_entry(&self, key: Hash, engine_id: &str) -> io::Result<bool> {
+        let path = self.entry_path(key, engine_id);
+        // The metadata goes first, so that the entry is never seen without its module.
+        let removed = remove_file_if_exists(&path.with_extension(METADATA_EXTENSION))?;
+        remove_file_if_exists(&path.with_extension(MODULE_EXTENSION))?;
+        Ok(removed)
+    }
+}
+
+impl Cache for LruFileSystemCache {
+    type DeserializeError = DeserializeError;
+    type SerializeError = SerializeError;
+
+    unsafe fn load(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+    ) -> Result<Module, Self::DeserializeError> {
+        let engine_id = engine.as_engine_ref().engine().deterministic_id();
+        let path = self.entry_path(key, &engine_id);
+        let metadata_path = path.with_extension(METADATA_EXTENSION);
+        let Some(mut metadata) = read_metadata(&metadata_path) else {
+            return Err(DeserializeError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no cache entry for {key}"),
+            )));
+        };
+
+        let bytes = match fs::read(path.with_extension(MODULE_EXTENSION)) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = self.remove_entry(key, &engine_id);
+                return Err(e.into());
+            }
+        };
+        if bytes.len() as u64 != metadata.size || Hash::generate(&bytes) != metadata.checksum {
+            // The entry was truncated or altered, we can not trust it anymore.
+            let _ = self.remove_entry(key, &engine_id);
+            return Err(DeserializeError::CorruptedBinary(format!(
+                "the checksum of the cache entry for {key} does not match"
+            )));
+        }
+
+        let ret = unsafe { Module::deserialize(engine, bytes) };
+        match ret {
+            Ok(_) => {
+                // Failing to record the access only makes the entry older.
+                metadata.last_access = SystemTime::now();
+                let _ = write_atomically(&metadata_path, metadata.to_text().as_bytes());
+            }
+            Err(_) => {
+                let _ = self.remove_entry(key, &engine_id);
+            }
+        }
+        ret
+    }
+
+    fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        let buffer = module.serialize()?;
+        let path = self.entry_path(key, &self.engine_id);
+        let metadata = CacheEntryMetadata {
+            key,
+            engine_id: self.engine_id.clone(),
+            size: buffer.len() as u64,
+            checksum: Hash::generate(&buffer),
+            last_access: SystemTime::now(),
+        };
+
+        write_atomically(&path.with_extension(MODULE_EXTENSION), &buffer)?;
+        write_atomically(
+            &path.with_extension(METADATA_EXTENSION),
+            metadata.to_text().as_bytes(),
+        )?;
+        self.prune()?;
+
+        Ok(())
+    }
+}
+
+fn read_metadata(path: &Path) -> Option<CacheEntryMetadata> {
+    CacheEntryMetadata::from_text(&fs::read_to_string(path).ok()?)
+}
+
+/// Writes `contents` to a temporary file, then moves it to `path`.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.{TEMPORARY_EXTENSION}", std::process::id()));
+    let temporary = PathBuf::from(temporary);
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+/// Whether a file is old enough to be a leftover of an interrupted
+/// write rather than a write in progress.
+fn is_stale(path: &Path) -> bool {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > Duration::from_secs(60 * 60))
+}
+
+fn remove_file_if_exists(path: &Path) -> io::Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(engine: &wasmer::Engine, index: u32) -> (Hash, Module) {
+        let wat = format!(r#"(module (func (export "f") (result i32) (i32.const {index})))"#);
+        let module = Module::new(engine, &wat).unwrap();
+        (Hash::generate(wat.as_bytes()), module)
+    }
+
+    #[test]
+    fn test_lru_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = wasmer::Engine::default();
+        let mut cache = LruFileSystemCache::new(dir.path(), u64::MAX, &engine).unwrap();
+
+        let (key_a, module_a) = module(&engine, 1);
+        let (key_b, module_b) = module(&engine, 2);
+        let (key_c, module_c) = module(&engine, 3);
+        cache.store(key_a, &module_a).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        cache.store(key_b, &module_b).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        // Loading `a` makes `b` the least recently used entry.
+        let _restored = unsafe { cache.load(&engine, key_a).unwrap() };
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.key).collect::<Vec<_>>(),
+            [key_b, key_a]
+        );
+        assert!(
+            entries
+                .iter()
+                .all(|e| e.engine_id == engine.deterministic_id())
+        );
+
+        // Leave room for two entries only.
+        cache.set_max_size(cache.size().unwrap() + entries[0].size / 2);
+        std::thread::sleep(Duration::from_millis(5));
+        cache.store(key_c, &module_c).unwrap();
+        assert!(cache.size().unwrap() <= cache.max_size());
+        assert!(unsafe { cache.load(&engine, key_b) }.is_err());
+        let _restored = unsafe { cache.load(&engine, key_a).unwrap() };
+        let _restored = unsafe { cache.load(&engine, key_c).unwrap() };
+
+        cache.set_max_size(0);
+        assert!(cache.prune().unwrap() > 0);
+        assert!(cache.entries().unwrap().is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_lru_cache_rejects_corrupted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = wasmer::Engine::default();
+        let mut cache = LruFileSystemCache::new(dir.path(), u64::MAX, &engine).unwrap();
+
+        let (key, module) = module(&engine, 1);
+        cache.store(key, &module).unwrap();
+
+        // Truncate the serialized module.
+        let path = cache
+            .entry_path(key, &engine.deterministic_id())
+            .with_extension(MODULE_EXTENSION);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+
+        let error = unsafe { cache.load(&engine, key) }.unwrap_err();
+        assert!(matches!(error, DeserializeError::CorruptedBinary(_)));
+        // The corrupted entry is discarded.
+        assert!(!path.exists());
+        assert!(cache.entries().unwrap().is_empty());
+    }
+}
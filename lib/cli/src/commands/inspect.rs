@@ -1,25 +1,30 @@
-use std::path::PathBuf;
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write as _, path::PathBuf};
 
-use crate::backend::RuntimeOptions;
+use crate::{backend::RuntimeOptions, opts::ItemFormatOpts, utils::render::CliRender};
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
 use clap::Parser;
+use serde::Serialize;
 use wasmer::*;
-use wasmer_types::target::Target;
+use wasmer_types::{Features, target::Target};
+use wasmparser::{DataKind, ElementItems, ElementKind, KnownCustom, Name, Payload, TypeRef};
 
 #[derive(Debug, Parser)]
-/// The options for the `wasmer validate` subcommand
+/// The options for the `wasmer inspect` subcommand
 pub struct Inspect {
-    /// File to validate as WebAssembly
+    /// File to inspect as WebAssembly
     #[clap(name = "FILE")]
     path: PathBuf,
 
+    #[clap(flatten)]
+    fmt: ItemFormatOpts,
+
     #[clap(flatten)]
     rt: RuntimeOptions,
 }
 
 impl Inspect {
-    /// Runs logic for the `validate` subcommand
+    /// Runs logic for the `inspect` subcommand
     pub fn execute(&self) -> Result<()> {
         self.inner_execute()
             .context(format!("failed to inspect `{}`", self.path.display()))
@@ -32,49 +37,512 @@ impl Inspect {
             .get_engine_for_module(&module_contents, &Target::default())?;
 
         let iswasm = is_wasm(&module_contents);
-        let module_len = module_contents.len();
-        let module = Module::new(&engine, module_contents)?;
-        println!(
-            "Backend used to parse the module: {}",
-            engine.deterministic_id()
-        );
-
-        println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
-        println!("Size: {}", ByteSize(module_len as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        let wasm = to_wasm(&module_contents)?;
+        let module = Module::new(&engine, &wasm)?;
+
+        let report = ModuleReport {
+            backend: engine.deterministic_id().to_string(),
+            kind: if !iswasm { "wat" } else { "wasm" },
+            size: module_contents.len() as u64,
+            imports: ExternsReport {
+                functions: module
+                    .imports()
+                    .functions()
+                    .map(|f| ExternReport::import(f.module(), f.name(), f.ty()))
+                    .collect(),
+                memories: module
+                    .imports()
+                    .memories()
+                    .map(|f| ExternReport::import(f.module(), f.name(), f.ty()))
+                    .collect(),
+                tables: module
+                    .imports()
+                    .tables()
+                    .map(|f| ExternReport::import(f.module(), f.name(), f.ty()))
+                    .collect(),
+                globals: module
+                    .imports()
+                    .globals()
+                    .map(|f| ExternReport::import(f.module(), f.name(), f.ty()))
+                    .collect(),
+            },
+            exports: ExternsReport {
+                functions: module
+                    .exports()
+                    .functions()
+                    .map(|f| ExternReport::export(f.name(), f.ty()))
+                    .collect(),
+                memories: module
+                    .exports()
+                    .memories()
+                    .map(|f| ExternReport::export(f.name(), f.ty()))
+                    .collect(),
+                tables: module
+                    .exports()
+                    .tables()
+                    .map(|f| ExternReport::export(f.name(), f.ty()))
+                    .collect(),
+                globals: module
+                    .exports()
+                    .globals()
+                    .map(|f| ExternReport::export(f.name(), f.ty()))
+                    .collect(),
+            },
+            features: Features::detect_from_wasm(&wasm)?,
+            sections: SectionsReport::parse(&wasm)?,
+        };
+
+        println!("{}", self.fmt.get().render(&report));
+        Ok(())
+    }
+}
+
+/// Converts the contents of a module to the WebAssembly binary format.
+fn to_wasm(contents: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if is_wasm(contents) {
+        return Ok(Cow::Borrowed(contents));
+    }
+
+    #[cfg(feature = "wat")]
+    {
+        Ok(wat2wasm(contents)?)
+    }
+
+    #[cfg(not(feature = "wat"))]
+    {
+        anyhow::bail!("the file is not a WebAssembly binary and WAT support is disabled")
+    }
+}
+
+/// Everything `wasmer inspect` knows about a module.
+#[derive(Debug, Serialize)]
+struct ModuleReport {
+    backend: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: u64,
+    imports: ExternsReport,
+    exports: ExternsReport,
+    features: Features,
+    #[serde(flatten)]
+    sections: SectionsReport,
+}
+
+#[derive(Debug, Serialize)]
+struct ExternsReport {
+    functions: Vec<ExternReport>,
+    memories: Vec<ExternReport>,
+    tables: Vec<ExternReport>,
+    globals: Vec<ExternReport>,
+}
+
+/// An imported or exported item.
+#[derive(Debug, Serialize)]
+struct ExternReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl ExternReport {
+    fn import(module: &str, name: &str, ty: impl std::fmt::Display) -> Self {
+        Self {
+            module: Some(module.to_string()),
+            name: name.to_string(),
+            ty: ty.to_string(),
         }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+
+    fn export(name: &str, ty: impl std::fmt::Display) -> Self {
+        Self {
+            module: None,
+            name: name.to_string(),
+            ty: ty.to_string(),
         }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+}
+
+/// Details read directly from the sections of the module binary.
+#[derive(Debug, Default, Serialize)]
+struct SectionsReport {
+    start_function: Option<u32>,
+    custom_sections: Vec<CustomSectionReport>,
+    module_name: Option<String>,
+    producers: BTreeMap<String, Vec<ProducerReport>>,
+    target_features: Vec<String>,
+    has_dwarf: bool,
+    data_segments: Vec<SegmentReport>,
+    element_segments: Vec<SegmentReport>,
+    functions: Vec<FunctionReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomSectionReport {
+    name: String,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ProducerReport {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SegmentKind {
+    Active,
+    Passive,
+    Declared,
+}
+
+impl std::fmt::Display for SegmentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Active => write!(f, "active"),
+            Self::Passive => write!(f, "passive"),
+            Self::Declared => write!(f, "declared"),
         }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+    }
+}
+
+/// A data or element segment. The size is in bytes for data segments, and
+/// in elements for element segments.
+#[derive(Debug, Serialize)]
+struct SegmentReport {
+    kind: SegmentKind,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionReport {
+    index: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    body_size: u64,
+}
+
+impl SectionsReport {
+    fn parse(wasm: &[u8]) -> Result<Self> {
+        let mut report = Self::default();
+        let mut imported_functions = 0;
+        let mut function_names = BTreeMap::new();
+
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::ImportSection(imports) => {
+                    for import in imports {
+                        if let TypeRef::Func(_) = import?.ty {
+                            imported_functions += 1;
+                        }
+                    }
+                }
+                Payload::StartSection { func, .. } => report.start_function = Some(func),
+                Payload::DataSection(data) => {
+                    for segment in data {
+                        let segment = segment?;
+                        report.data_segments.push(SegmentReport {
+                            kind: match segment.kind {
+                                DataKind::Active { .. } => SegmentKind::Active,
+                                DataKind::Passive => SegmentKind::Passive,
+                            },
+                            size: segment.data.len() as u64,
+                        });
+                    }
+                }
+                Payload::ElementSection(elements) => {
+                    for segment in elements {
+                        let segment = segment?;
+                        report.element_segments.push(SegmentReport {
+                            kind: match segment.kind {
+                                ElementKind::Active { .. } => SegmentKind::Active,
+                                ElementKind::Passive => SegmentKind::Passive,
+                                ElementKind::Declared => SegmentKind::Declared,
+                            },
+                            size: match segment.items {
+                                ElementItems::Functions(items) => items.count(),
+                                ElementItems::Expressions(_, items) => items.count(),
+                            } as u64,
+                        });
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    report.functions.push(FunctionReport {
+                        index: imported_functions + report.functions.len() as u32,
+                        name: None,
+                        body_size: body.range().len() as u64,
+                    });
+                }
+                Payload::CustomSection(section) => {
+                    report.custom_sections.push(CustomSectionReport {
+                        name: section.name().to_string(),
+                        size: section.data().len() as u64,
+                    });
+                    if section.name().starts_with(".debug_") {
+                        report.has_dwarf = true;
+                    }
+
+                    match section.as_known() {
+                        KnownCustom::Name(names) => {
+                            for name in names {
+                                match name? {
+                                    Name::Module { name, .. } => {
+                                        report.module_name = Some(name.to_string());
+                                    }
+                                    Name::Function(map) => {
+                                        for naming in map {
+                                            let naming = naming?;
+                                            function_names
+                                                .insert(naming.index, naming.name.to_string());
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        KnownCustom::Producers(producers) => {
+                            for field in producers {
+                                let field = field?;
+                                let values = field
+                                    .values
+                                    .into_iter()
+                                    .map(|value| {
+                                        value.map(|value| ProducerReport {
+                                            name: value.name.to_string(),
+                                            version: value.version.to_string(),
+                                        })
+                                    })
+                                    .collect::<Result<_, _>>()?;
+                                report.producers.insert(field.name.to_string(), values);
+                            }
+                        }
+                        _ if section.name() == "target_features" => {
+                            report.target_features = parse_target_features(section.data())?;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for function in &mut report.functions {
+            function.name = function_names.remove(&function.index);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Parses the `target_features` custom section emitted by LLVM, made of a
+/// list of feature names each prefixed with `+`, `-` or `=`.
+fn parse_target_features(data: &[u8]) -> Result<Vec<String>> {
+    let mut reader = wasmparser::BinaryReader::new(data, 0);
+    let count = reader.read_var_u32()?;
+    (0..count)
+        .map(|_| {
+            let prefix = reader.read_u8()? as char;
+            let name = reader.read_string()?;
+            Ok(format!("{prefix}{name}"))
+        })
+        .collect()
+}
+
+/// The names of the features enabled in `features`.
+fn feature_names(features: &Features) -> Vec<&'static str> {
+    [
+        ("threads", features.threads),
+        ("reference-types", features.reference_types),
+        ("simd", features.simd),
+        ("bulk-memory", features.bulk_memory),
+        ("multi-value", features.multi_value),
+        ("tail-call", features.tail_call),
+        ("module-linking", features.module_linking),
+        ("multi-memory", features.multi_memory),
+        ("memory64", features.memory64),
+        ("exceptions", features.exceptions),
+        ("relaxed-simd", features.relaxed_simd),
+        ("extended-const", features.extended_const),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+impl CliRender for ModuleReport {
+    fn render_item_table(&self) -> String {
+        let mut out = String::new();
+        let _ = self.write_table(&mut out);
+        out.trim_end().to_string()
+    }
+
+    fn render_list_table(items: &[Self]) -> String {
+        items
+            .iter()
+            .map(|item| item.render_item_table())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl ModuleReport {
+    fn write_table(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(out, "Backend used to parse the module: {}", self.backend)?;
+        writeln!(out, "Type: {}", self.kind)?;
+        writeln!(out, "Size: {}", ByteSize(self.size))?;
+        writeln!(out, "Imports:")?;
+        self.imports.write_table(out)?;
+        writeln!(out, "Exports:")?;
+        self.exports.write_table(out)?;
+
+        let sections = &self.sections;
+        match sections.start_function {
+            Some(index) => writeln!(out, "Start function: {index}")?,
+            None => writeln!(out, "Start function: none")?,
+        }
+        writeln!(
+            out,
+            "Features: {}",
+            feature_names(&self.features).join(", ")
+        )?;
+        writeln!(out, "Custom sections:")?;
+        for section in &sections.custom_sections {
+            writeln!(out, "  \"{}\": {}", section.name, ByteSize(section.size))?;
+        }
+        if let Some(name) = &sections.module_name {
+            writeln!(out, "Module name: {name}")?;
+        }
+        if !sections.producers.is_empty() {
+            writeln!(out, "Producers:")?;
+            for (field, values) in &sections.producers {
+                let values = values
+                    .iter()
+                    .map(|value| format!("{} {}", value.name, value.version))
+                    .collect::<Vec<_>>();
+                writeln!(out, "  {field}: {}", values.join(", "))?;
+            }
         }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        if !sections.target_features.is_empty() {
+            writeln!(
+                out,
+                "Target features: {}",
+                sections.target_features.join(", ")
+            )?;
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        writeln!(
+            out,
+            "DWARF debug info: {}",
+            if sections.has_dwarf { "yes" } else { "no" }
+        )?;
+        writeln!(out, "Data segments:")?;
+        for (index, segment) in sections.data_segments.iter().enumerate() {
+            writeln!(
+                out,
+                "  {index}: {}, {}",
+                segment.kind,
+                ByteSize(segment.size)
+            )?;
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        writeln!(out, "Element segments:")?;
+        for (index, segment) in sections.element_segments.iter().enumerate() {
+            writeln!(
+                out,
+                "  {index}: {}, {} elements",
+                segment.kind, segment.size
+            )?;
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        writeln!(out, "Functions:")?;
+        for function in &sections.functions {
+            match &function.name {
+                Some(name) => writeln!(
+                    out,
+                    "  {} ({name}): {}",
+                    function.index,
+                    ByteSize(function.body_size)
+                )?,
+                None => writeln!(
+                    out,
+                    "  {}: {}",
+                    function.index,
+                    ByteSize(function.body_size)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExternsReport {
+    fn write_table(&self, out: &mut String) -> std::fmt::Result {
+        for (title, externs) in [
+            ("Functions", &self.functions),
+            ("Memories", &self.memories),
+            ("Tables", &self.tables),
+            ("Globals", &self.globals),
+        ] {
+            writeln!(out, "  {title}:")?;
+            for item in externs {
+                match &item.module {
+                    Some(module) => {
+                        writeln!(out, "    \"{module}\".\"{}\": {}", item.name, item.ty)?
+                    }
+                    None => writeln!(out, "    \"{}\": {}", item.name, item.ty)?,
+                }
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "wat"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_report_describes_segments_and_functions() {
+        let wasm = wat2wasm(
+            br#"(module $example
+                (import "env" "log" (func $log (param i32)))
+                (memory 1)
+                (table 2 funcref)
+                (func $first (call $log (i32.const 1)))
+                (func $second)
+                (start $second)
+                (elem (i32.const 0) $first $second)
+                (data (i32.const 0) "hello")
+                (data "passive"))"#,
+        )
+        .unwrap();
+        let report = SectionsReport::parse(&wasm).unwrap();
+
+        assert_eq!(report.start_function, Some(2));
+        assert_eq!(report.module_name.as_deref(), Some("example"));
+        assert!(!report.has_dwarf);
+
+        let data = report
+            .data_segments
+            .iter()
+            .map(|segment| (segment.kind, segment.size))
+            .collect::<Vec<_>>();
+        assert_eq!(data, [(SegmentKind::Active, 5), (SegmentKind::Passive, 7)]);
+        assert_eq!(report.element_segments.len(), 1);
+        assert_eq!(report.element_segments[0].size, 2);
+
+        let functions = report
+            .functions
+            .iter()
+            .map(|function| (function.index, function.name.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(functions, [(1, Some("first")), (2, Some("second"))]);
+        assert!(report.functions[0].body_size > report.functions[1].body_size);
+    }
+
+    #[test]
+    fn target_features_are_parsed_with_their_prefix() {
+        let data = [2, b'+', 4, b's', b'i', b'm', b'd', b'-', 2, b'm', b'v'];
+        assert_eq!(parse_target_features(&data).unwrap(), ["+simd", "-mv"]);
+    }
+}
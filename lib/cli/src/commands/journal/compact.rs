@@ -2,9 +2,11 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use wasmer_wasix::journal::{
-    CompactingLogFileJournal, LogFileJournal, PrintingJournal, copy_journal,
+    CompactingJournal, CompactingLogFileJournal, CountingJournal, EncryptedJournal, JournalKey,
+    LogFileJournal, PrintingJournal, copy_journal,
 };
 
+use super::JournalKeyOpts;
 use crate::commands::CliCommand;
 
 /// Compacts a journal by removing duplicate or redundant
@@ -14,16 +16,59 @@ pub struct CmdJournalCompact {
     /// Path to the journal that will be compacted
     #[clap(index = 1)]
     journal_path: PathBuf,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
+}
+
+impl CmdJournalCompact {
+    /// Encrypted journals are decrypted, compacted and then encrypted
+    /// again with the first key into a staging file that replaces the
+    /// original one
+    fn compact_encrypted(&self, key: JournalKey) -> anyhow::Result<()> {
+        let source = self.keys.open(&self.journal_path)?;
+
+        // The compactor first learns all the records
+        let mut compacting = CompactingJournal::new(CountingJournal::default())?;
+        copy_journal(&source, &compacting)?;
+        compacting.replace_inner(source);
+
+        let mut temp_filename = self
+            .journal_path
+            .file_name()
+            .ok_or_else(|| {
+                anyhow::format_err!(
+                    "The path is not a valid filename - {}",
+                    self.journal_path.to_string_lossy()
+                )
+            })?
+            .to_string_lossy()
+            .to_string();
+        temp_filename.insert_str(0, ".compacting.");
+        let temp_path = self.journal_path.with_file_name(&temp_filename);
+
+        std::fs::remove_file(&temp_path).ok();
+        let target = EncryptedJournal::new(LogFileJournal::new(&temp_path)?, key)?;
+        compacting.compact_to(target)?;
+        std::fs::rename(&temp_path, &self.journal_path)?;
+        Ok(())
+    }
 }
 
 impl CliCommand for CmdJournalCompact {
     type Output = ();
 
     fn run(self) -> Result<(), anyhow::Error> {
-        let compactor = CompactingLogFileJournal::new(&self.journal_path)?.with_compact_on_drop();
-        drop(compactor);
+        match self.keys.keys()?.into_iter().next() {
+            Some(key) => self.compact_encrypted(key)?,
+            None => {
+                let compactor =
+                    CompactingLogFileJournal::new(&self.journal_path)?.with_compact_on_drop();
+                drop(compactor);
+            }
+        }
 
-        let journal = LogFileJournal::new(&self.journal_path)?;
+        let journal = self.keys.open(&self.journal_path)?;
         let printer = PrintingJournal::default();
         copy_journal(&journal, &printer)?;
         Ok(())
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use wasmer_wasix::journal::{JournalState, copy_journal_until};
+
+use super::JournalKeyOpts;
+use crate::commands::CliCommand;
+
+/// Compares the state of the files, memory, threads and sockets of two
+/// journals, or of the same journal at two different records
+#[derive(Debug, Parser)]
+pub struct CmdJournalDiff {
+    /// Path to the journal that is compared
+    #[clap(index = 1)]
+    journal_path: PathBuf,
+    /// Path to the journal it is compared against (defaults to the
+    /// first journal)
+    #[clap(index = 2)]
+    other_path: Option<PathBuf>,
+    /// Index of the last record of the first journal that is included
+    /// in the comparison (defaults to all of them)
+    #[clap(long)]
+    from: Option<u64>,
+    /// Index of the last record of the second journal that is included
+    /// in the comparison (defaults to all of them)
+    #[clap(long)]
+    to: Option<u64>,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
+}
+
+impl CliCommand for CmdJournalDiff {
+    type Output = ();
+
+    fn run(self) -> Result<(), anyhow::Error> {
+        let other_path = self.other_path.as_ref().unwrap_or(&self.journal_path);
+
+        let before = JournalState::new();
+        copy_journal_until(&self.keys.open(&self.journal_path)?, &before, self.from)?;
+        let after = JournalState::new();
+        copy_journal_until(&self.keys.open(other_path)?, &after, self.to)?;
+
+        let changes = before.diff(&after);
+        if changes.is_empty() {
+            println!("no differences");
+            return Ok(());
+        }
+
+        // The changes are already ordered by section
+        let mut section = "";
+        for change in changes {
+            if change.section() != section {
+                section = change.section();
+                println!("{section}:");
+            }
+            println!("  {change}");
+        }
+        Ok(())
+    }
+}
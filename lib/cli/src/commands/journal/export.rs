@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use wasmer_wasix::journal::{JournalPrintingMode, LogFileJournal, PrintingJournal, copy_journal};
+use wasmer_wasix::journal::{JournalPrintingMode, PrintingJournal, copy_journal};
 
+use super::JournalKeyOpts;
 use crate::commands::CliCommand;
 
 /// Exports all the events in a journal to STDOUT as JSON data
@@ -11,13 +12,16 @@ pub struct CmdJournalExport {
     /// Path to the journal that will be printed
     #[clap(index = 1)]
     journal_path: PathBuf,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
 }
 
 impl CliCommand for CmdJournalExport {
     type Output = ();
 
     fn run(self) -> Result<(), anyhow::Error> {
-        let journal = LogFileJournal::new(self.journal_path)?;
+        let journal = self.keys.open(&self.journal_path)?;
         let printer = PrintingJournal::new(JournalPrintingMode::Json);
         copy_journal(&journal, &printer)?;
         Ok(())
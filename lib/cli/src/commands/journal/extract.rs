@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use wasmer_wasix::journal::{LogFileJournal, copy_journal};
+use wasmer_wasix::journal::copy_journal_until;
 
+use super::JournalKeyOpts;
 use crate::commands::CliCommand;
 
 #[derive(Debug, Parser)]
@@ -25,6 +26,14 @@ pub struct CmdJournalExtract {
     #[clap(index = 1)]
     journal_path: PathBuf,
 
+    /// Index of the last record that is applied, which extracts the state
+    /// as it was at that record (defaults to all the records)
+    #[clap(long)]
+    at: Option<u64>,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
+
     #[clap(subcommand)]
     what: CmdExtractWhat,
 }
@@ -33,13 +42,13 @@ impl CliCommand for CmdJournalExtract {
     type Output = ();
 
     fn run(self) -> Result<(), anyhow::Error> {
-        let journal = LogFileJournal::new(&self.journal_path)?;
+        let journal = self.keys.open(&self.journal_path)?;
 
         match self.what {
             CmdExtractWhat::Memory(cmd) => {
                 let memory_file =
                     wasmer_wasix::journal::MemFileJournal::new(&cmd.memory_file_path)?;
-                copy_journal(&journal, &memory_file)?;
+                copy_journal_until(&journal, &memory_file, self.at)?;
             }
         }
         Ok(())
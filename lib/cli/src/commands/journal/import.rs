@@ -1,8 +1,9 @@
 use std::{io::ErrorKind, path::PathBuf};
 
 use clap::Parser;
-use wasmer_wasix::journal::{JournalEntry, LogFileJournal, WritableJournal};
+use wasmer_wasix::journal::{JournalEntry, WritableJournal};
 
+use super::JournalKeyOpts;
 use crate::commands::CliCommand;
 
 /// Imports events into a journal file. Events are streamed as JSON
@@ -12,6 +13,9 @@ pub struct CmdJournalImport {
     /// Path to the journal that will be printed
     #[clap(index = 1)]
     journal_path: PathBuf,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
 }
 
 impl CliCommand for CmdJournalImport {
@@ -22,7 +26,7 @@ impl CliCommand for CmdJournalImport {
         if self.journal_path.exists() {
             std::fs::remove_file(&self.journal_path)?;
         }
-        let journal = LogFileJournal::new(self.journal_path)?;
+        let journal = self.keys.open(&self.journal_path)?;
 
         // Read all the events from `stdin`, deserialize them and save them to the journal
         let stdin = std::io::stdin();
@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use wasmer_wasix::journal::{LogFileJournal, PrintingJournal, copy_journal};
+use wasmer_wasix::journal::{PrintingJournal, copy_journal};
 
+use super::JournalKeyOpts;
 use crate::commands::CliCommand;
 
 /// Prints a summarized version of contents of a journal to stdout
@@ -11,13 +12,16 @@ pub struct CmdJournalInspect {
     /// Path to the journal that will be printed
     #[clap(index = 1)]
     journal_path: PathBuf,
+
+    #[clap(flatten)]
+    keys: JournalKeyOpts,
 }
 
 impl CliCommand for CmdJournalInspect {
     type Output = ();
 
     fn run(self) -> Result<(), anyhow::Error> {
-        let journal = LogFileJournal::new(self.journal_path)?;
+        let journal = self.keys.open(&self.journal_path)?;
         let printer = PrintingJournal::default();
         copy_journal(&journal, &printer)?;
         Ok(())
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use wasmer_wasix::journal::{DynJournal, EncryptedJournal, JournalKey, LogFileJournal};
+
+/// Keys used to read and write encrypted journals
+#[derive(Debug, Default, clap::Parser)]
+pub struct JournalKeyOpts {
+    /// Key used to decrypt the journal, in the form `<id>:<hex>` where the
+    /// key itself is 32 bytes encoded as hex. The first key that is given
+    /// is also used to encrypt any records that are written.
+    ///
+    /// The argument can be passed multiple times to supply older keys.
+    #[clap(
+        long = "key",
+        value_name = "ID:HEX",
+        env = "WASMER_JOURNAL_KEY",
+        hide_env_values = true,
+        value_parser = parse_journal_key
+    )]
+    keys: Vec<JournalKey>,
+
+    /// File that holds keys in the same form as `--key`, one per line
+    #[clap(long = "key-file", value_name = "PATH")]
+    key_files: Vec<PathBuf>,
+}
+
+impl JournalKeyOpts {
+    /// Returns all the keys that were supplied, with the key used to
+    /// encrypt first
+    pub fn keys(&self) -> anyhow::Result<Vec<JournalKey>> {
+        let mut keys = self.keys.clone();
+        for path in self.key_files.iter() {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("unable to read the key file {}", path.display()))?;
+            for line in contents.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let key = parse_journal_key(line).map_err(|err| {
+                    anyhow::format_err!("invalid key in {} - {err}", path.display())
+                })?;
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Opens the journal at `path`, decrypting it when keys were supplied
+    pub fn open(&self, path: &Path) -> anyhow::Result<Box<DynJournal>> {
+        let journal = LogFileJournal::new(path)?;
+        let mut keys = self.keys()?.into_iter();
+        let Some(key) = keys.next() else {
+            return Ok(Box::new(journal));
+        };
+        let journal = keys.fold(EncryptedJournal::new(journal, key)?, |journal, key| {
+            journal.with_decryption_key(key)
+        });
+        Ok(Box::new(journal))
+    }
+}
+
+fn parse_journal_key(s: &str) -> Result<JournalKey, String> {
+    let (id, key) = s
+        .split_once(':')
+        .ok_or_else(|| "expected a key in the form `<id>:<hex>`".to_string())?;
+    let key = hex::decode(key.trim()).map_err(|err| format!("the key is not valid hex - {err}"))?;
+    JournalKey::new(id.trim(), &key).map_err(|err| err.to_string())
+}
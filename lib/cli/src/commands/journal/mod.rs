@@ -1,20 +1,24 @@
 use crate::commands::CliCommand;
 
 mod compact;
+mod diff;
 mod export;
 mod extract;
 mod filter;
 mod import;
 mod inspect;
+mod key;
 #[cfg(feature = "fuse")]
 mod mount;
 
 pub use compact::*;
+pub use diff::*;
 pub use export::*;
 pub use extract::*;
 pub use filter::*;
 pub use import::*;
 pub use inspect::*;
+pub use key::*;
 #[cfg(feature = "fuse")]
 pub use mount::*;
 
@@ -23,6 +27,8 @@ pub use mount::*;
 pub enum CmdJournal {
     /// Compacts a journal into a smaller size by removed redundant or duplicate events
     Compact(CmdJournalCompact),
+    /// Compares the state of two journals, or of a journal at two records
+    Diff(CmdJournalDiff),
     /// Exports the contents of a journal to stdout as JSON objects
     Export(CmdJournalExport),
     /// Imports the events into a journal as JSON objects
@@ -44,6 +50,7 @@ impl CliCommand for CmdJournal {
     fn run(self) -> Result<(), anyhow::Error> {
         match self {
             Self::Compact(cmd) => cmd.run(),
+            Self::Diff(cmd) => cmd.run(),
             Self::Import(cmd) => cmd.run(),
             Self::Export(cmd) => cmd.run(),
             Self::Inspect(cmd) => cmd.run(),
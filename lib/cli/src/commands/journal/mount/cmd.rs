@@ -15,6 +15,10 @@ pub struct CmdJournalMount {
     /// Path to the directory where the file system will be mounted
     #[clap(index = 2)]
     mount_path: PathBuf,
+    /// Index of the last record that is applied, which mounts the file
+    /// system as it was at that record (defaults to all the records)
+    #[clap(long)]
+    at: Option<u64>,
 }
 
 impl CliCommand for CmdJournalMount {
@@ -33,6 +37,7 @@ impl CliCommand for CmdJournalMount {
         let fs = JournalFileSystemBuilder::new(&self.journal_path)
             .with_fd_seed(WasiFdSeed::default())
             .with_progress_bar(false)
+            .with_at(self.at)
             .build()?;
 
         // Mounts the journal file system at a path
@@ -28,7 +28,7 @@ use wasmer_wasix::{
     journal::{
         ArchivedJournalEntry, ArchivedJournalEntryFileDescriptorWriteV1, Journal, JournalEntry,
         JournalEntryFileDescriptorWriteV1, LogFileJournal, LogWriteResult, ReadableJournal,
-        WritableJournal, copy_journal,
+        WritableJournal, copy_journal, copy_journal_until,
     },
     types::Oflags,
     wasmer_wasix_types::wasi,
@@ -57,6 +57,7 @@ pub struct JournalFileSystemBuilder {
     path: PathBuf,
     fd_seed: WasiFdSeed,
     progress_bar: bool,
+    at: Option<u64>,
 }
 
 impl JournalFileSystemBuilder {
@@ -65,6 +66,7 @@ impl JournalFileSystemBuilder {
             path: path.to_path_buf(),
             fd_seed: WasiFdSeed::default(),
             progress_bar: false,
+            at: None,
         }
     }
 
@@ -78,6 +80,12 @@ impl JournalFileSystemBuilder {
         self
     }
 
+    /// Only applies the records up to and including the record at `at`
+    pub fn with_at(mut self, at: Option<u64>) -> Self {
+        self.at = at;
+        self
+    }
+
     // Opens the journal and copies all its contents into
     // and memory file system
     pub fn build(self) -> anyhow::Result<JournalFileSystem> {
@@ -111,9 +119,9 @@ impl JournalFileSystemBuilder {
 
         tokio::task::block_in_place(|| {
             if let Some(progress) = progress {
-                copy_journal_with_progress(&journal, &state, progress)
+                copy_journal_with_progress(&journal, &state, progress, self.at)
             } else {
-                copy_journal(&journal, &state)
+                copy_journal_until(&journal, &state, self.at).map(|_| ())
             }
         })?;
 
@@ -131,10 +139,16 @@ pub fn copy_journal_with_progress<R: ReadableJournal, W: WritableJournal>(
     from: &R,
     to: &W,
     mut progress: ProgressBar,
+    at: Option<u64>,
 ) -> anyhow::Result<()> {
+    let mut index = 0;
     while let Some(record) = from.read()? {
         progress.set_position(record.record_end);
         to.write(record.into_inner())?;
+        if at == Some(index) {
+            break;
+        }
+        index += 1;
     }
     progress.finish_and_clear();
     println!("Journal is mounted");
@@ -1,7 +1,7 @@
 use std::{
     fmt::Display,
     net::{IpAddr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::OnceLock,
     time::Duration,
@@ -14,7 +14,7 @@ use dialoguer::theme::ColorfulTheme;
 use virtual_net::{
     DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, StreamSecurity,
     UnsupportedVirtualNetworking, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualUnixDatagramSocket,
 };
 
 /// A custom implementation of the [`virtual_net::VirtualNetwork`] that asks users if they want to
@@ -296,6 +296,24 @@ impl VirtualNetworking for AskingNetworking {
         call!(self, connect_tcp, addr, peer);
     }
 
+    /// Listens for connections on a Unix stream socket bound to a path
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        call!(self, listen_unix, path);
+    }
+
+    /// Opens a connection to the Unix stream socket bound to a path
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        call!(self, connect_unix, path);
+    }
+
+    /// Opens a Unix datagram socket bound to a path, or an unnamed one
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        call!(self, bind_unix_datagram, path);
+    }
+
     /// Performs DNS resolution for a specific hostname
     async fn resolve(
         &self,
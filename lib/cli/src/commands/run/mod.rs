@@ -581,6 +581,9 @@ impl Run {
                 runner.with_writable_journal(journal);
             }
             runner.with_skip_stdio_during_bootstrap(self.wasi.skip_stdio_during_bootstrap);
+            if let Some(replay) = self.wasi.build_non_deterministic_replay()? {
+                runner.with_non_deterministic_replay(replay);
+            }
         }
 
         Ok(runner)
@@ -1001,6 +1004,10 @@ impl<R: wasmer_wasix::Runtime + Send + Sync> wasmer_wasix::Runtime for Monitorin
         self.runtime.networking()
     }
 
+    fn network_meter(&self) -> Option<&virtual_net::NetworkMeter> {
+        self.runtime.network_meter()
+    }
+
     fn task_manager(&self) -> &Arc<dyn wasmer_wasix::VirtualTaskManager> {
         self.runtime.task_manager()
     }
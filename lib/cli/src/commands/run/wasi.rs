@@ -1,5 +1,6 @@
 use std::{
     collections::{BTreeSet, HashMap},
+    net::SocketAddr,
     path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, mpsc::Sender},
@@ -12,12 +13,17 @@ use clap::Parser;
 use tokio::runtime::Handle;
 use url::Url;
 use virtual_fs::{DeviceFile, FileSystem, PassthruFileSystem, RootFileSystemBuilder};
-use virtual_net::ruleset::Ruleset;
+use virtual_net::{
+    CapturingNetworking, DynVirtualNetworking, FaultRule, FaultyNetworking, NetworkFaults,
+    UnixNetworking, ruleset::Ruleset,
+};
 use wasmer::{Engine, Function, Instance, Memory32, Memory64, Module, RuntimeError, Store, Value};
 use wasmer_config::package::PackageSource as PackageSpecifier;
 use wasmer_types::ModuleHash;
 #[cfg(feature = "journal")]
-use wasmer_wasix::journal::{LogFileJournal, SnapshotTrigger};
+use wasmer_wasix::journal::{
+    LogFileJournal, NonDeterministicReplay, RemoteJournal, RemoteJournalReceiver, SnapshotTrigger,
+};
 use wasmer_wasix::{
     PluggableRuntime, RewindState, Runtime, WasiEnv, WasiEnvBuilder, WasiError, WasiFunctionEnv,
     WasiVersion,
@@ -25,7 +31,7 @@ use wasmer_wasix::{
     capabilities::Capabilities,
     default_fs_backing, get_wasi_versions,
     http::HttpClient,
-    journal::{CompactingLogFileJournal, DynJournal, DynReadableJournal},
+    journal::{ChunkedMemoryJournal, CompactingLogFileJournal, DynJournal, DynReadableJournal},
     os::{TtyBridge, tty_sys::SysTty},
     rewind_ext,
     runners::MAPPED_CURRENT_DIR_DEFAULT_PATH,
@@ -72,6 +78,15 @@ pub struct Wasi {
     )]
     pub(crate) mapped_dirs: Vec<MappedDirectory>,
 
+    /// Map a host directory for Unix domain sockets, the sockets the Wasm
+    /// module binds under the guest directory are created in the host one
+    #[clap(
+        long = "map-unix-sockets",
+        value_name = "GUEST_DIR:HOST_DIR",
+        value_parser=parse_mapdir,
+    )]
+    pub(crate) unix_socket_dirs: Vec<MappedDirectory>,
+
     /// Set the module's initial CWD to this path; does not work with
     /// WASI preview 1 modules.
     #[clap(long = "cwd")]
@@ -125,6 +140,36 @@ pub struct Wasi {
     // and when --net=<ruleset> is specified, the inner Option will be initialized: Some(Some(ruleset))
     pub networking: Option<Option<String>>,
 
+    /// Records the network traffic of the Wasm module into a pcapng file,
+    /// which can be opened with Wireshark or tcpdump
+    #[clap(long = "net-capture", value_name = "FILE")]
+    pub(crate) net_capture: Option<PathBuf>,
+
+    /// Injects faults into the network traffic, such as latency, packet
+    /// loss, connection resets or partitions.
+    ///
+    /// Rule Syntax:
+    ///
+    /// <fault>,<fault>,...[@<ruleset>]
+    ///
+    /// where the ruleset uses the syntax of --net and selects the traffic
+    /// the rule applies to. The first matching rule is used.
+    ///
+    /// Examples:
+    ///
+    ///  - Slow down all the traffic: latency=100ms,jitter=20ms,bandwidth=256k
+    ///
+    ///  - Drop UDP datagrams and reset connections: loss=0.1,reset=0.01@ipv4:allow=10.0.0.0/8:*
+    ///
+    ///  - Fail some DNS queries: dns-fail=0.5@dns:allow=*.example.com:*
+    #[clap(long = "net-fault", value_name = "RULE")]
+    pub(crate) net_faults: Vec<FaultRule>,
+
+    /// Seeds the random decisions of --net-fault, runs with the same seed
+    /// make the same decisions
+    #[clap(long = "net-fault-seed", value_name = "SEED", default_value_t = 0)]
+    pub(crate) net_fault_seed: u64,
+
     /// Disables the TTY bridge
     #[clap(long = "no-tty")]
     pub no_tty: bool,
@@ -182,6 +227,23 @@ pub struct Wasi {
     #[clap(long = "with-compact-on-growth", default_value = "0.15")]
     pub with_compact_on_growth: f32,
 
+    /// Stores the memory written to the journals as chunks that are
+    /// compressed with zstd and only written once, so that snapshots only
+    /// grow the journal by the memory that actually changed
+    #[cfg(feature = "journal")]
+    #[clap(long = "journal-chunk-memory")]
+    pub journal_chunk_memory: bool,
+
+    /// zstd compression level (1 to 22) of the memory chunks written to
+    /// the journals
+    #[cfg(feature = "journal")]
+    #[clap(
+        long = "journal-compression-level",
+        default_value = "3",
+        requires = "journal_chunk_memory"
+    )]
+    pub journal_compression_level: i32,
+
     /// Indicates what events will cause a snapshot to be taken
     /// and written to the journal file.
     ///
@@ -214,6 +276,70 @@ pub struct Wasi {
     #[clap(long = "skip-journal-stdio")]
     pub skip_stdio_during_bootstrap: bool,
 
+    /// Replays a run that was recorded with `--snapshot-on non-deterministic-call`
+    /// by feeding the results of its non-deterministic calls (random numbers,
+    /// clocks, stdin, sockets and polling) back to the process from this journal.
+    ///
+    /// As soon as the process makes a call that was not recorded it carries on
+    /// with the real calls.
+    #[cfg(feature = "journal")]
+    #[clap(long = "journal-replay")]
+    pub replay_journal: Option<PathBuf>,
+
+    /// Streams the events written to the last writable journal to a warm
+    /// standby that was started with `--journal-follow` on this address.
+    ///
+    /// The standby is sent any events it is missing whenever it (re)connects,
+    /// after authenticating with `--journal-token`.
+    #[cfg(feature = "journal")]
+    #[clap(
+        long = "journal-replicate",
+        value_name = "ADDR",
+        conflicts_with = "enable_compaction"
+    )]
+    pub journal_replicate: Option<SocketAddr>,
+
+    /// Runs as a warm standby of a process started with `--journal-replicate`.
+    ///
+    /// The events it sends to this address are written to the last writable
+    /// journal and applied as they arrive. The WASM process only starts
+    /// running once the other process closes its journal, or once it has
+    /// been disconnected for longer than `--journal-failover-timeout`.
+    ///
+    /// Only processes which know `--journal-token` are accepted, but the
+    /// events are sent unencrypted: this port must not be exposed outside
+    /// of a trusted network.
+    #[cfg(feature = "journal")]
+    #[clap(
+        long = "journal-follow",
+        value_name = "ADDR",
+        conflicts_with_all = ["enable_compaction", "journal_replicate"]
+    )]
+    pub journal_follow: Option<SocketAddr>,
+
+    /// Secret shared by a process started with `--journal-replicate` and
+    /// its standby started with `--journal-follow`
+    #[cfg(feature = "journal")]
+    #[clap(
+        long = "journal-token",
+        env = "WASMER_JOURNAL_TOKEN",
+        value_name = "TOKEN",
+        hide_env_values = true
+    )]
+    pub journal_token: Option<String>,
+
+    /// Number of milli-seconds the process that is followed can be
+    /// disconnected before the standby takes over
+    #[cfg(feature = "journal")]
+    #[clap(long = "journal-failover-timeout", requires = "journal_follow")]
+    pub journal_failover_timeout: Option<u64>,
+
+    /// The journal that replicates or follows another one, which is only
+    /// set up once even though the journals are built more than once
+    #[cfg(feature = "journal")]
+    #[clap(skip)]
+    pub replicated_journal: Arc<std::sync::OnceLock<Arc<DynJournal>>>,
+
     /// Allow instances to send http requests.
     ///
     /// Access to domains is granted by default.
@@ -451,6 +577,9 @@ impl Wasi {
                 builder.add_writable_journal(journal);
             }
             builder.with_skip_stdio_during_bootstrap(self.skip_stdio_during_bootstrap);
+            if let Some(replay) = self.build_non_deterministic_replay()? {
+                builder.with_non_deterministic_replay(replay);
+            }
         }
 
         Ok(builder)
@@ -474,7 +603,7 @@ impl Wasi {
 
         let mut writable = Vec::new();
         for journal in self.writable_journals.clone() {
-            if self.enable_compaction {
+            let journal: Arc<DynJournal> = if self.enable_compaction {
                 let mut journal = CompactingLogFileJournal::new(journal)?;
                 if !self.without_compact_on_drop {
                     journal = journal.with_compact_on_drop()
@@ -482,14 +611,73 @@ impl Wasi {
                 if self.with_compact_on_growth.is_normal() && self.with_compact_on_growth != 0f32 {
                     journal = journal.with_compact_on_factor_size(self.with_compact_on_growth);
                 }
+                Arc::new(journal)
+            } else {
+                Arc::new(LogFileJournal::new(journal)?)
+            };
+            if self.journal_chunk_memory {
+                let journal = ChunkedMemoryJournal::new(journal)?
+                    .with_compression_level(self.journal_compression_level);
                 writable.push(Arc::new(journal) as Arc<DynJournal>);
             } else {
-                writable.push(Arc::new(LogFileJournal::new(journal)?));
+                writable.push(journal);
+            }
+        }
+
+        // Replication wraps the journal that events are written to
+        if self.journal_replicate.is_some() || self.journal_follow.is_some() {
+            let Some(journal) = writable.pop() else {
+                bail!("Replicating or following a journal requires a writable journal");
+            };
+            if let Some(journal) = self.replicated_journal.get() {
+                writable.push(journal.clone());
+                return Ok((readable, writable));
+            }
+            let Some(token) = self.journal_token.clone() else {
+                bail!("Replicating or following a journal requires a --journal-token");
+            };
+            if let Some(addr) = self.journal_replicate {
+                let journal: Arc<DynJournal> = Arc::new(RemoteJournal::new(journal, addr, token)?);
+                self.replicated_journal.set(journal.clone()).ok();
+                writable.push(journal);
+            } else if let Some(addr) = self.journal_follow {
+                let mut receiver = RemoteJournalReceiver::new(journal, true, token)?;
+                if let Some(timeout) = self.journal_failover_timeout {
+                    receiver = receiver.with_failover_timeout(Duration::from_millis(timeout));
+                }
+                let listener = std::net::TcpListener::bind(addr)
+                    .with_context(|| format!("Unable to listen on {addr}"))?;
+                let addr = receiver.listen(listener)?;
+                tracing::info!(%addr, "following the journal");
+                let journal: Arc<DynJournal> = Arc::new(receiver);
+                self.replicated_journal.set(journal.clone()).ok();
+                writable.push(journal);
             }
         }
         Ok((readable, writable))
     }
 
+    #[cfg(feature = "journal")]
+    pub fn build_non_deterministic_replay(
+        &self,
+    ) -> anyhow::Result<Option<Arc<NonDeterministicReplay>>> {
+        let Some(path) = self.replay_journal.as_ref() else {
+            return Ok(None);
+        };
+        if matches!(std::fs::metadata(path), Err(e) if e.kind() == std::io::ErrorKind::NotFound) {
+            bail!("Replay journal file does not exist: {path:?}");
+        }
+
+        let journal = LogFileJournal::new_readonly(path)?;
+        let replay = NonDeterministicReplay::from_journal(&journal)
+            .with_context(|| format!("Unable to read the replay journal {path:?}"))?;
+        tracing::debug!(
+            calls = replay.remaining(),
+            "loaded the non-deterministic calls to replay"
+        );
+        Ok(Some(Arc::new(replay)))
+    }
+
     #[cfg(not(feature = "journal"))]
     pub fn build_journals(&self) -> anyhow::Result<Vec<Arc<DynJournal>>> {
         Ok(Vec::new())
@@ -658,16 +846,49 @@ impl Wasi {
             virtual_net::host::LocalNetworking::default()
         };
 
-        if has_networking {
-            rt.set_networking_implementation(network);
+        let network: DynVirtualNetworking = if has_networking {
+            Arc::new(network)
         } else {
-            let net = super::capabilities::net::AskingNetworking::new(
+            Arc::new(super::capabilities::net::AskingNetworking::new(
                 pkg_cache_path.to_path_buf(),
                 Arc::new(network),
-            );
+            ))
+        };
+        let network: DynVirtualNetworking = match &self.net_capture {
+            Some(path) => Arc::new(
+                CapturingNetworking::create(network, path)
+                    .with_context(|| format!("Unable to create the network capture {path:?}"))?,
+            ),
+            None => network,
+        };
+        let network: DynVirtualNetworking = if self.net_faults.is_empty() {
+            network
+        } else {
+            let faults = self
+                .net_faults
+                .iter()
+                .cloned()
+                .fold(NetworkFaults::new(), NetworkFaults::with_rule)
+                .with_seed(self.net_fault_seed);
+            Arc::new(FaultyNetworking::new(network, faults))
+        };
 
-            rt.set_networking_implementation(net);
+        // Unix sockets between the instances of this runtime do not need the
+        // networking capability, only the mapped host directories reach out.
+        #[allow(unused_mut)]
+        let mut network = UnixNetworking::new(network);
+        #[cfg(unix)]
+        {
+            let _guard = tokio_task_manager.runtime_handle().enter();
+            for MappedDirectory { guest, host } in &self.unix_socket_dirs {
+                network = network.with_host_dir(guest, host);
+            }
+        }
+        #[cfg(not(unix))]
+        if !self.unix_socket_dirs.is_empty() {
+            bail!("--map-unix-sockets is only supported on Unix hosts");
         }
+        rt.set_networking_implementation(network);
 
         #[cfg(feature = "journal")]
         {
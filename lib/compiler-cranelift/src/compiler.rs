@@ -13,7 +13,7 @@ use crate::{
         FunctionBuilderContext, make_trampoline_dynamic_function, make_trampoline_function_call,
     },
     translator::{
-        FuncTranslator, compiled_function_unwind_info, irlibcall_to_libcall,
+        FuncTranslator, StructResults, compiled_function_unwind_info, irlibcall_to_libcall,
         irreloc_to_relocationkind, signature_to_cranelift_ir,
     },
 };
@@ -91,8 +91,13 @@ impl CraneliftCompiler {
         let signatures = module
             .signatures
             .iter()
-            .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, frontend_config))
+            .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, &*isa))
             .collect::<PrimaryMap<SignatureIndex, ir::Signature>>();
+        let struct_results = module
+            .signatures
+            .iter()
+            .map(|(_sig_index, func_type)| StructResults::new(func_type.results(), &*isa))
+            .collect::<PrimaryMap<SignatureIndex, _>>();
 
         // Generate the frametable
         #[cfg(feature = "unwind")]
@@ -136,6 +141,7 @@ impl CraneliftCompiler {
                     isa.frontend_config(),
                     module,
                     &signatures,
+                    &struct_results,
                     &memory_styles,
                     table_styles,
                 );
@@ -250,6 +256,7 @@ impl CraneliftCompiler {
                     isa.frontend_config(),
                     module,
                     &signatures,
+                    &struct_results,
                     memory_styles,
                     table_styles,
                 );
@@ -555,6 +562,8 @@ fn translate_ir_trapcode(trap: ir::TrapCode) -> TrapCode {
         TrapCode::BadConversionToInteger
     } else if trap == crate::TRAP_UNREACHABLE {
         TrapCode::UnreachableCodeReached
+    } else if trap == crate::TRAP_CALL_DEPTH_EXCEEDED {
+        TrapCode::CallDepthExceeded
     } else if trap == crate::TRAP_INTERRUPT {
         unimplemented!("Interrupts not supported")
     } else if trap == crate::TRAP_NULL_REFERENCE || trap == crate::TRAP_NULL_I31_REF {
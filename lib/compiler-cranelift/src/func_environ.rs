@@ -4,7 +4,9 @@
 use crate::{
     heap::{Heap, HeapData, HeapStyle},
     table::{TableData, TableSize},
-    translator::{FuncEnvironment as BaseFuncEnvironment, GlobalVariable, TargetEnvironment},
+    translator::{
+        FuncEnvironment as BaseFuncEnvironment, GlobalVariable, StructResults, TargetEnvironment,
+    },
 };
 use cranelift_codegen::{
     cursor::FuncCursor,
@@ -37,6 +39,19 @@ pub fn type_of_vmtable_definition_current_elements(vmoffsets: &VMOffsets) -> ir:
     ir::Type::int(u16::from(vmoffsets.size_of_vmtable_definition_current_elements()) * 8).unwrap()
 }
 
+/// Reads the WebAssembly results of a call, from the slot given by
+/// [`FuncEnvironment::prepare_call`] if the callee returns them like a struct.
+fn call_results(
+    builder: &mut FunctionBuilder,
+    call: ir::Inst,
+    struct_results: Option<(&StructResults, ir::StackSlot)>,
+) -> Vec<ir::Value> {
+    match struct_results {
+        Some((results, slot)) => results.call_results(builder, call, slot),
+        None => builder.inst_results(call).to_vec(),
+    }
+}
+
 /// The `FuncEnvironment` implementation for use by the `ModuleEnvironment`.
 pub struct FuncEnvironment<'module_environment> {
     /// Target-specified configuration.
@@ -51,6 +66,10 @@ pub struct FuncEnvironment<'module_environment> {
     /// The module function signatures
     signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
 
+    /// How the module function signatures return their results, if they return them like a
+    /// struct
+    struct_results: &'module_environment PrimaryMap<SignatureIndex, Option<StructResults>>,
+
     /// Heaps implementing WebAssembly linear memories.
     heaps: PrimaryMap<Heap, HeapData>,
 
@@ -135,6 +154,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         target_config: TargetFrontendConfig,
         module: &'module_environment ModuleInfo,
         signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
+        struct_results: &'module_environment PrimaryMap<SignatureIndex, Option<StructResults>>,
         memory_styles: &'module_environment PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
     ) -> Self {
@@ -142,6 +162,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             target_config,
             module,
             signatures,
+            struct_results,
             type_stack: vec![],
             heaps: PrimaryMap::new(),
             vmctx: None,
@@ -248,6 +269,21 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         });
     }
 
+    /// Prepares a call to a function with the given signature, returning where to read its
+    /// results from if it returns them like a struct.
+    fn prepare_call(
+        &self,
+        builder: &mut FunctionBuilder,
+        sig_index: SignatureIndex,
+        args: &mut Vec<ir::Value>,
+    ) -> Option<(&'module_environment StructResults, ir::StackSlot)> {
+        let results = self.struct_results[sig_index].as_ref()?;
+        Some((
+            results,
+            results.prepare_call(builder, self.pointer_type(), args),
+        ))
+    }
+
     fn vmctx(&mut self, func: &mut Function) -> ir::GlobalValue {
         self.vmctx.unwrap_or_else(|| {
             let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
@@ -962,9 +998,9 @@ impl TargetEnvironment for FuncEnvironment<'_> {
 }
 
 impl BaseFuncEnvironment for FuncEnvironment<'_> {
-    fn is_wasm_parameter(&self, _signature: &ir::Signature, index: usize) -> bool {
-        // The first parameter is the vmctx. The rest are the wasm parameters.
-        index >= 1
+    fn struct_results(&self, index: LocalFunctionIndex) -> Option<&StructResults> {
+        let sig_index = self.module.functions[self.module.func_index(index)];
+        self.struct_results[sig_index].as_ref()
     }
 
     fn translate_table_grow(
@@ -1267,7 +1303,7 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
         sig_ref: ir::SigRef,
         callee: ir::Value,
         call_args: &[ir::Value],
-    ) -> WasmResult<ir::Inst> {
+    ) -> WasmResult<Vec<ir::Value>> {
         let pointer_type = self.pointer_type();
 
         // Get the anyfunc pointer (the funcref) from the table.
@@ -1320,7 +1356,7 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
             }
         }
 
-        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
+        let mut real_call_args = Vec::with_capacity(call_args.len() + 3);
 
         // First append the callee vmctx address.
         let vmctx = builder.ins().load(
@@ -1334,9 +1370,11 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
         // Then append the regular call arguments.
         real_call_args.extend_from_slice(call_args);
 
-        Ok(builder
+        let struct_results = self.prepare_call(builder, sig_index, &mut real_call_args);
+        let call = builder
             .ins()
-            .call_indirect(sig_ref, func_addr, &real_call_args))
+            .call_indirect(sig_ref, func_addr, &real_call_args);
+        Ok(call_results(builder, call, struct_results))
     }
 
     fn translate_call(
@@ -1345,8 +1383,9 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
         callee_index: FunctionIndex,
         callee: ir::FuncRef,
         call_args: &[ir::Value],
-    ) -> WasmResult<ir::Inst> {
-        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
+    ) -> WasmResult<Vec<ir::Value>> {
+        let mut real_call_args = Vec::with_capacity(call_args.len() + 3);
+        let sig_index = self.module.functions[callee_index];
 
         // Handle direct calls to locally-defined functions.
         if !self.module.is_imported_function(callee_index) {
@@ -1362,7 +1401,9 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
             // Then append the regular call arguments.
             real_call_args.extend_from_slice(call_args);
 
-            return Ok(builder.ins().call(callee, &real_call_args));
+            let struct_results = self.prepare_call(builder, sig_index, &mut real_call_args);
+            let call = builder.ins().call(callee, &real_call_args);
+            return Ok(call_results(builder, call, struct_results));
         }
 
         // Handle direct calls to imported functions. We use an indirect call
@@ -1392,9 +1433,11 @@ impl BaseFuncEnvironment for FuncEnvironment<'_> {
         // Then append the regular call arguments.
         real_call_args.extend_from_slice(call_args);
 
-        Ok(builder
+        let struct_results = self.prepare_call(builder, sig_index, &mut real_call_args);
+        let call = builder
             .ins()
-            .call_indirect(sig_ref, func_addr, &real_call_args))
+            .call_indirect(sig_ref, func_addr, &real_call_args);
+        Ok(call_results(builder, call, struct_results))
     }
 
     fn translate_memory_grow(
@@ -79,3 +79,5 @@ pub const TRAP_NULL_REFERENCE: TrapCode = TrapCode::unwrap_user(TRAP_USER_OFFSET
 pub const TRAP_NULL_I31_REF: TrapCode = TrapCode::unwrap_user(TRAP_USER_OFFSET + 6);
 /// Trap reported for interrupts (not currently supported).
 pub const TRAP_INTERRUPT: TrapCode = TrapCode::unwrap_user(TRAP_USER_OFFSET + 7);
+/// Trap reported when a middleware-enforced call depth limit is exceeded.
+pub const TRAP_CALL_DEPTH_EXCEEDED: TrapCode = TrapCode::unwrap_user(TRAP_USER_OFFSET + 8);
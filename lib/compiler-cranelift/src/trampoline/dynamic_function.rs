@@ -3,7 +3,7 @@
 
 //! A trampoline generator for calling dynamic host functions from Wasm.
 
-use crate::translator::{compiled_function_unwind_info, signature_to_cranelift_ir};
+use crate::translator::{StructResults, compiled_function_unwind_info, signature_to_cranelift_ir};
 use cranelift_codegen::{
     Context,
     ir::{self, Function, InstBuilder, MemFlags, StackSlotData, StackSlotKind, UserFuncName},
@@ -23,7 +23,8 @@ pub fn make_trampoline_dynamic_function(
 ) -> Result<FunctionBody, CompileError> {
     let pointer_type = isa.pointer_type();
     let frontend_config = isa.frontend_config();
-    let signature = signature_to_cranelift_ir(func_type, frontend_config);
+    let signature = signature_to_cranelift_ir(func_type, isa);
+    let struct_results = StructResults::new(func_type.results(), isa);
     let mut stub_sig = ir::Signature::new(frontend_config.default_call_conv);
     // Add the caller `vmctx` parameter.
     stub_sig.params.push(ir::AbiParam::special(
@@ -37,7 +38,7 @@ pub fn make_trampoline_dynamic_function(
     // Compute the size of the values vector. The vmctx and caller vmctx are passed separately.
     let value_size = mem::size_of::<u128>();
     let values_vec_len =
-        (value_size * cmp::max(signature.params.len() - 1, signature.returns.len())) as u32;
+        (value_size * cmp::max(func_type.params().len(), func_type.results().len())) as u32;
 
     let mut context = Context::new();
     context.func = Function::with_name_signature(UserFuncName::user(0, 0), signature.clone());
@@ -58,19 +59,23 @@ pub fn make_trampoline_dynamic_function(
 
         let values_vec_ptr_val = builder.ins().stack_addr(pointer_type, ss, 0);
         let mflags = MemFlags::trusted();
-        // We only get the non-vmctx arguments
-        for i in 1..signature.params.len() {
-            let val = builder.func.dfg.block_params(block0)[i];
-            builder.ins().store(
-                mflags,
-                val,
-                values_vec_ptr_val,
-                ((i - 1) * value_size) as i32,
-            );
+        // We only get the wasm arguments, not the vmctx or the struct return pointer
+        let block_params = builder.func.dfg.block_params(block0).to_vec();
+        let args = signature
+            .params
+            .iter()
+            .zip(block_params)
+            .filter(|(param, _)| param.purpose == ir::ArgumentPurpose::Normal);
+        for (i, (_, val)) in args.enumerate() {
+            builder
+                .ins()
+                .store(mflags, val, values_vec_ptr_val, (i * value_size) as i32);
         }
 
-        let block_params = builder.func.dfg.block_params(block0);
-        let vmctx_ptr_val = block_params[0];
+        let vmctx_ptr_val = builder
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .unwrap();
         let callee_args = vec![vmctx_ptr_val, values_vec_ptr_val];
 
         let new_sig = builder.import_signature(stub_sig);
@@ -88,17 +93,24 @@ pub fn make_trampoline_dynamic_function(
             .call_indirect(new_sig, callee_value, &callee_args);
 
         let mflags = MemFlags::trusted();
+        let result_types = match &struct_results {
+            Some(results) => results.types().to_vec(),
+            None => signature.returns.iter().map(|r| r.value_type).collect(),
+        };
         let mut results = Vec::new();
-        for (i, r) in signature.returns.iter().enumerate() {
-            let load = builder.ins().load(
-                r.value_type,
-                mflags,
-                values_vec_ptr_val,
-                (i * value_size) as i32,
-            );
+        for (i, ty) in result_types.into_iter().enumerate() {
+            let load = builder
+                .ins()
+                .load(ty, mflags, values_vec_ptr_val, (i * value_size) as i32);
             results.push(load);
         }
-        builder.ins().return_(&results);
+        match &struct_results {
+            Some(struct_results) => {
+                let returns = struct_results.returned(&mut builder, &results);
+                builder.ins().return_(&returns)
+            }
+            None => builder.ins().return_(&results),
+        };
         builder.finalize()
     }
 
@@ -8,7 +8,7 @@
 //! let my_func = instance.exports.get("func");
 //! my_func.call([1, 2])
 //! ```
-use crate::translator::{compiled_function_unwind_info, signature_to_cranelift_ir};
+use crate::translator::{StructResults, compiled_function_unwind_info, signature_to_cranelift_ir};
 use cranelift_codegen::{
     Context,
     ir::{self, InstBuilder},
@@ -27,7 +27,8 @@ pub fn make_trampoline_function_call(
 ) -> Result<FunctionBody, CompileError> {
     let pointer_type = isa.pointer_type();
     let frontend_config = isa.frontend_config();
-    let signature = signature_to_cranelift_ir(func_type, frontend_config);
+    let signature = signature_to_cranelift_ir(func_type, isa);
+    let struct_results = StructResults::new(func_type.results(), isa);
     let mut wrapper_sig = ir::Signature::new(frontend_config.default_call_conv);
 
     // Add the callee `vmctx` parameter.
@@ -61,26 +62,25 @@ pub fn make_trampoline_function_call(
 
         // Load the argument values out of `values_vec`.
         let mflags = ir::MemFlags::trusted();
-        let callee_args = signature
-            .params
-            .iter()
-            .enumerate()
-            .map(|(i, r)| {
-                match i {
-                    0 => vmctx_ptr_val,
-                    _ =>
-                    // i - 1 because vmctx is not passed through `values_vec`.
-                    {
-                        builder.ins().load(
-                            r.value_type,
-                            mflags,
-                            values_vec_ptr_val,
-                            ((i - 1) * value_size) as i32,
-                        )
-                    }
-                }
-            })
-            .collect::<Vec<_>>();
+        let mut callee_args = vec![vmctx_ptr_val];
+        callee_args.extend(
+            signature
+                .params
+                .iter()
+                .filter(|r| r.purpose == ir::ArgumentPurpose::Normal)
+                .enumerate()
+                .map(|(i, r)| {
+                    builder.ins().load(
+                        r.value_type,
+                        mflags,
+                        values_vec_ptr_val,
+                        (i * value_size) as i32,
+                    )
+                }),
+        );
+        let slot = struct_results
+            .as_ref()
+            .map(|results| results.prepare_call(&mut builder, pointer_type, &mut callee_args));
 
         let new_sig = builder.import_signature(signature);
 
@@ -88,7 +88,10 @@ pub fn make_trampoline_function_call(
             .ins()
             .call_indirect(new_sig, callee_value, &callee_args);
 
-        let results = builder.func.dfg.inst_results(call).to_vec();
+        let results = match (&struct_results, slot) {
+            (Some(results), Some(slot)) => results.call_results(&mut builder, call, slot),
+            _ => builder.func.dfg.inst_results(call).to_vec(),
+        };
 
         // Store the return values into `values_vec`.
         let mflags = ir::MemFlags::trusted();
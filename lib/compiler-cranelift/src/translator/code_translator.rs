@@ -78,7 +78,9 @@ mod bounds_checks;
 
 use super::func_environ::{FuncEnvironment, GlobalVariable};
 use super::func_state::{ControlStackFrame, ElseData, FuncTranslationState};
-use super::translation_utils::{block_with_params, f32_translation, f64_translation};
+use super::translation_utils::{
+    StructResults, block_with_params, f32_translation, f64_translation,
+};
 use crate::{HashMap, hash_map};
 use core::convert::TryFrom;
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
@@ -589,10 +591,10 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 frame.num_return_values()
             };
             {
-                let return_args = state.peekn_mut(return_count);
+                let return_start = state.stack.len() - return_count;
+                let return_args = &mut state.stack[return_start..];
                 environ.handle_before_return(return_args, builder);
-                bitcast_wasm_returns(environ, return_args, builder);
-                builder.ins().return_(return_args);
+                return_wasm_values(environ, state.struct_results.as_ref(), return_args, builder);
             }
             state.popn(return_count);
             state.reachable = false;
@@ -627,22 +629,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 builder,
             );
 
-            let call = environ.translate_call(
+            let results = environ.translate_call(
                 builder,
                 FunctionIndex::from_u32(*function_index),
                 fref,
                 args,
             )?;
-            let inst_results = builder.inst_results(call);
-            debug_assert_eq!(
-                inst_results.len(),
-                builder.func.dfg.signatures[builder.func.dfg.ext_funcs[fref].signature]
-                    .returns
-                    .len(),
-                "translate_call results should match the call signature"
-            );
             state.popn(num_args);
-            state.pushn(inst_results);
+            state.pushn(&results);
         }
         Operator::CallIndirect {
             type_index,
@@ -659,7 +653,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let args = state.peekn_mut(num_args);
             bitcast_wasm_params(environ, sigref, args, builder);
 
-            let call = environ.translate_call_indirect(
+            let results = environ.translate_call_indirect(
                 builder,
                 TableIndex::from_u32(*table_index),
                 SignatureIndex::from_u32(*type_index),
@@ -667,14 +661,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 callee,
                 state.peekn(num_args),
             )?;
-            let inst_results = builder.inst_results(call);
-            debug_assert_eq!(
-                inst_results.len(),
-                builder.func.dfg.signatures[sigref].returns.len(),
-                "translate_call_indirect results should match the call signature"
-            );
             state.popn(num_args);
-            state.pushn(inst_results);
+            state.pushn(&results);
         }
         /******************************* Memory management ***********************************
          * Memory management is handled by environment. It is usually translated into calls to
@@ -3401,6 +3389,26 @@ pub fn bitcast_wasm_returns<FE: FuncEnvironment + ?Sized>(
     }
 }
 
+/// Returns `values` from the function currently being built, like a struct if that's how it
+/// returns its results.
+pub fn return_wasm_values<FE: FuncEnvironment + ?Sized>(
+    environ: &mut FE,
+    struct_results: Option<&StructResults>,
+    values: &mut [Value],
+    builder: &mut FunctionBuilder,
+) {
+    match struct_results {
+        Some(results) => {
+            let returns = results.returned(builder, values);
+            builder.ins().return_(&returns);
+        }
+        None => {
+            bitcast_wasm_returns(environ, values, builder);
+            builder.ins().return_(values);
+        }
+    }
+}
+
 /// Like `bitcast_wasm_returns`, but for the parameters being passed to a specified callee.
 pub fn bitcast_wasm_params<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
@@ -5,7 +5,7 @@
 //! traits `FunctionEnvMutironment`.
 
 use super::func_state::FuncTranslationState;
-use super::translation_utils::reference_type;
+use super::translation_utils::{StructResults, reference_type};
 use crate::heap::{Heap, HeapData};
 use core::convert::From;
 use cranelift_codegen::cursor::FuncCursor;
@@ -94,6 +94,12 @@ pub trait FuncEnvironment: TargetEnvironment {
         signature.params[index].purpose == ir::ArgumentPurpose::Normal
     }
 
+    /// How the given local function returns its results, if it returns them like a struct
+    /// rather than as the returns of its signature.
+    fn struct_results(&self, _index: LocalFunctionIndex) -> Option<&StructResults> {
+        None
+    }
+
     /// Is the given return of the given function a wasm-level parameter, as
     /// opposed to a hidden parameter added for use by the implementation?
     fn is_wasm_return(&self, signature: &ir::Signature, index: usize) -> bool {
@@ -182,15 +188,16 @@ pub trait FuncEnvironment: TargetEnvironment {
     ///
     /// The function reference `callee` was previously created by `make_direct_func()`.
     ///
-    /// Return the call instruction whose results are the WebAssembly return values.
+    /// Return the WebAssembly return values of the call.
     fn translate_call(
         &mut self,
         builder: &mut FunctionBuilder,
         _callee_index: FunctionIndex,
         callee: ir::FuncRef,
         call_args: &[ir::Value],
-    ) -> WasmResult<ir::Inst> {
-        Ok(builder.ins().call(callee, call_args))
+    ) -> WasmResult<Vec<ir::Value>> {
+        let call = builder.ins().call(callee, call_args);
+        Ok(builder.inst_results(call).to_vec())
     }
 
     /// Translate a `call_indirect` WebAssembly instruction at `pos`.
@@ -201,9 +208,7 @@ pub trait FuncEnvironment: TargetEnvironment {
     ///
     /// The signature `sig_ref` was previously created by `make_indirect_sig()`.
     ///
-    /// Return the call instruction whose results are the WebAssembly return values.
-    /// Returns `None` if this statically traps instead of creating a call
-    /// instruction.
+    /// Return the WebAssembly return values of the call.
     fn translate_call_indirect(
         &mut self,
         builder: &mut FunctionBuilder,
@@ -212,7 +217,7 @@ pub trait FuncEnvironment: TargetEnvironment {
         sig_ref: ir::SigRef,
         callee: ir::Value,
         call_args: &[ir::Value],
-    ) -> WasmResult<ir::Inst>;
+    ) -> WasmResult<Vec<ir::Value>>;
 
     /// Translate a `memory.grow` WebAssembly instruction.
     ///
@@ -10,6 +10,7 @@
 //! value and control stacks during the translation of a single function.
 
 use super::func_environ::{FuncEnvironment, GlobalVariable};
+use super::translation_utils::StructResults;
 use crate::heap::Heap;
 use crate::{HashMap, Occupied, Vacant};
 use cranelift_codegen::ir::{self, Block, Inst, Value};
@@ -230,6 +231,8 @@ pub struct FuncTranslationState {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub(crate) reachable: bool,
+    /// How the function returns its results, if it returns them like a struct.
+    pub(crate) struct_results: Option<StructResults>,
 
     // Map of global variables that have already been created by `FuncEnvironment::make_global`.
     globals: HashMap<GlobalIndex, GlobalVariable>,
@@ -267,6 +270,7 @@ impl FuncTranslationState {
             //metadata_stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            struct_results: None,
             globals: HashMap::new(),
             heaps: HashMap::new(),
             signatures: HashMap::new(),
@@ -288,16 +292,23 @@ impl FuncTranslationState {
     ///
     /// This resets the state to containing only a single block representing the whole function.
     /// The exit block is the last block in the function which will contain the return instruction.
-    pub(crate) fn initialize(&mut self, sig: &ir::Signature, exit_block: Block) {
+    pub(crate) fn initialize(
+        &mut self,
+        sig: &ir::Signature,
+        exit_block: Block,
+        struct_results: Option<StructResults>,
+    ) {
         self.clear();
-        self.push_block(
-            exit_block,
-            0,
-            sig.returns
+        let num_returns = match &struct_results {
+            Some(results) => results.types().len(),
+            None => sig
+                .returns
                 .iter()
                 .filter(|arg| arg.purpose == ir::ArgumentPurpose::Normal)
                 .count(),
-        );
+        };
+        self.push_block(exit_block, 0, num_returns);
+        self.struct_results = struct_results;
     }
 
     /// Push a value.
@@ -10,8 +10,8 @@
 use super::code_translator::translate_operator;
 use super::func_environ::{FuncEnvironment, ReturnMode};
 use super::func_state::FuncTranslationState;
-use super::translation_utils::get_vmctx_value_label;
-use crate::translator::code_translator::bitcast_wasm_returns;
+use super::translation_utils::{StructResults, get_vmctx_value_label};
+use crate::translator::code_translator::return_wasm_values;
 use core::convert::TryFrom;
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::{self, Block, InstBuilder, ValueLabel};
@@ -19,7 +19,7 @@ use cranelift_codegen::timing;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use wasmer_compiler::{FunctionBinaryReader, ModuleTranslationState, wptype_to_type};
 use wasmer_compiler::{wasm_unsupported, wasmparser};
-use wasmer_types::{LocalFunctionIndex, WasmResult};
+use wasmer_types::{LocalFunctionIndex, TrapCode, WasmResult};
 
 /// WebAssembly to Cranelift IR function translator.
 ///
@@ -67,16 +67,27 @@ impl FuncTranslator {
         local_function_index: LocalFunctionIndex,
     ) -> WasmResult<()> {
         environ.push_params_on_stack(local_function_index);
-        self.translate_from_reader(module_translation_state, reader, func, environ)
+        let struct_results = environ.struct_results(local_function_index).cloned();
+        self.translate_from_reader(
+            module_translation_state,
+            reader,
+            func,
+            environ,
+            struct_results,
+        )
     }
 
     /// Translate a binary WebAssembly function from a `FunctionBinaryReader`.
+    ///
+    /// `struct_results` describes how the function returns its results if it returns them like
+    /// a struct rather than as the returns of its signature.
     pub fn translate_from_reader<FE: FuncEnvironment + ?Sized>(
         &mut self,
         module_translation_state: &ModuleTranslationState,
         reader: &mut dyn FunctionBinaryReader,
         func: &mut ir::Function,
         environ: &mut FE,
+        struct_results: Option<StructResults>,
     ) -> WasmResult<()> {
         let _tt = timing::wasm_translate_function();
         tracing::trace!(
@@ -105,8 +116,18 @@ impl FuncTranslator {
         // Set up the translation state with a single pushed control block representing the whole
         // function and its return values.
         let exit_block = builder.create_block();
-        builder.append_block_params_for_function_returns(exit_block);
-        self.state.initialize(&builder.func.signature, exit_block);
+        match &struct_results {
+            Some(results) => {
+                for &ty in results.types() {
+                    builder.append_block_param(exit_block, ty);
+                }
+            }
+            None => {
+                builder.append_block_params_for_function_returns(exit_block);
+            }
+        }
+        self.state
+            .initialize(&builder.func.signature, exit_block, struct_results);
 
         parse_local_decls(reader, &mut builder, num_params, environ)?;
         parse_function_body(
@@ -241,6 +262,17 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
         builder.set_srcloc(cur_srcloc(reader));
         let op = reader.read_operator()?;
         environ.before_translate_operator(&op, builder, state)?;
+        if let (wasmparser::Operator::Unreachable, TrapCode::CallDepthExceeded) =
+            (&op, reader.trap_code())
+        {
+            // A trap pushed by a middleware, lowered with its own trap code.
+            if state.reachable {
+                builder.ins().trap(crate::TRAP_CALL_DEPTH_EXCEEDED);
+                state.reachable = false;
+            }
+            environ.after_translate_operator(&op, builder, state)?;
+            continue;
+        }
         translate_operator(module_translation_state, &op, builder, state, environ)?;
         environ.after_translate_operator(&op, builder, state)?;
     }
@@ -254,10 +286,12 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
         //debug_assert!(builder.is_pristine());
         if !builder.is_unreachable() {
             match environ.return_mode() {
-                ReturnMode::NormalReturns => {
-                    bitcast_wasm_returns(environ, &mut state.stack, builder);
-                    builder.ins().return_(&state.stack)
-                }
+                ReturnMode::NormalReturns => return_wasm_values(
+                    environ,
+                    state.struct_results.as_ref(),
+                    &mut state.stack,
+                    builder,
+                ),
             };
         }
     }
@@ -10,7 +10,7 @@ mod unwind;
 pub use self::func_environ::{FuncEnvironment, GlobalVariable, TargetEnvironment};
 pub use self::func_translator::FuncTranslator;
 pub use self::translation_utils::{
-    irlibcall_to_libcall, irreloc_to_relocationkind, signature_to_cranelift_ir,
+    StructResults, irlibcall_to_libcall, irreloc_to_relocationkind, signature_to_cranelift_ir,
 };
 #[cfg(feature = "unwind")]
 pub(crate) use self::unwind::CraneliftUnwindInfo;
@@ -3,37 +3,233 @@
 use super::func_environ::TargetEnvironment;
 use cranelift_codegen::{
     binemit::Reloc,
-    ir::{self, AbiParam},
-    isa::TargetFrontendConfig,
+    ir::{self, AbiParam, InstBuilder},
+    isa::{CallConv, TargetFrontendConfig, TargetIsa},
 };
 use cranelift_frontend::FunctionBuilder;
 use wasmer_compiler::{types::relocation::RelocationKind, wasm_unsupported, wasmparser};
-use wasmer_types::{FunctionType, LibCall, Type, WasmError, WasmResult};
+use wasmer_types::{FunctionType, LibCall, Type, WasmError, WasmResult, target::Architecture};
 
 /// Helper function translate a Function signature into Cranelift Ir
-pub fn signature_to_cranelift_ir(
-    signature: &FunctionType,
-    target_config: TargetFrontendConfig,
-) -> ir::Signature {
+pub fn signature_to_cranelift_ir(signature: &FunctionType, isa: &dyn TargetIsa) -> ir::Signature {
+    let target_config = isa.frontend_config();
     let mut sig = ir::Signature::new(target_config.default_call_conv);
     sig.params.extend(signature.params().iter().map(|&ty| {
         let cret_arg: ir::Type = type_to_irtype(ty, target_config)
             .expect("only numeric types are supported in function signatures");
         AbiParam::new(cret_arg)
     }));
-    sig.returns.extend(signature.results().iter().map(|&ty| {
-        let cret_arg: ir::Type = type_to_irtype(ty, target_config)
-            .expect("only numeric types are supported in function signatures");
-        AbiParam::new(cret_arg)
-    }));
-    // The Vmctx signature
+    match StructResults::new(signature.results(), isa) {
+        Some(results) => match &results.parts {
+            Some(parts) => sig
+                .returns
+                .extend(parts.iter().map(|&(ty, _)| AbiParam::new(ty))),
+            None => sig.params.insert(
+                0,
+                AbiParam::special(
+                    target_config.pointer_type(),
+                    ir::ArgumentPurpose::StructReturn,
+                ),
+            ),
+        },
+        None => sig.returns.extend(signature.results().iter().map(|&ty| {
+            let cret_arg: ir::Type = type_to_irtype(ty, target_config)
+                .expect("only numeric types are supported in function signatures");
+            AbiParam::new(cret_arg)
+        })),
+    }
+    // The Vmctx signature, after the struct return pointer if there is one
+    let vmctx_index = sig.uses_struct_return_param() as usize;
     sig.params.insert(
-        0,
+        vmctx_index,
         AbiParam::special(target_config.pointer_type(), ir::ArgumentPurpose::VMContext),
     );
     sig
 }
 
+/// The layout of the results of a function returning more than one value.
+///
+/// Host functions return several values as a `#[repr(C)]` struct, and imported functions and
+/// table entries point straight at them, so every function with more than one result returns
+/// them that way: in the registers the C ABI of the target uses for such a struct, or in memory
+/// pointed to by a struct return parameter.
+#[derive(Debug, Clone)]
+pub struct StructResults {
+    /// The types of the results.
+    types: Vec<ir::Type>,
+    /// The offset of each result in the struct.
+    offsets: Vec<u32>,
+    /// The size of the struct, rounded up to a multiple of 8.
+    size: u32,
+    /// The alignment of the struct.
+    align: u32,
+    /// The registers the struct is returned in, as their type and the offset they are loaded
+    /// from, or `None` if it is returned in memory.
+    parts: Option<Vec<(ir::Type, u32)>>,
+}
+
+impl StructResults {
+    /// Computes the layout of the given results, or `None` if there are less than two of them.
+    pub fn new(results: &[Type], isa: &dyn TargetIsa) -> Option<Self> {
+        if results.len() < 2 {
+            return None;
+        }
+        let target_config = isa.frontend_config();
+        let types = results
+            .iter()
+            .map(|&ty| {
+                type_to_irtype(ty, target_config)
+                    .expect("only numeric types are supported in function signatures")
+            })
+            .collect::<Vec<_>>();
+        let mut offsets = Vec::with_capacity(types.len());
+        let mut size = 0u32;
+        let mut align = 1;
+        for ty in &types {
+            let field_size = ty.bytes();
+            size = size.next_multiple_of(field_size);
+            offsets.push(size);
+            size += field_size;
+            align = align.max(field_size);
+        }
+        let size = size.next_multiple_of(align.max(8));
+        let mut results = Self {
+            types,
+            offsets,
+            size,
+            align,
+            parts: None,
+        };
+        results.parts = results.registers(isa);
+        Some(results)
+    }
+
+    /// The types of the results.
+    pub fn types(&self) -> &[ir::Type] {
+        &self.types
+    }
+
+    /// The registers the C ABI of the target returns this struct in, if any.
+    fn registers(&self, isa: &dyn TargetIsa) -> Option<Vec<(ir::Type, u32)>> {
+        use ir::types::{F64, I64};
+        let eightbytes = || (0..self.size).step_by(8);
+        match isa.triple().architecture {
+            Architecture::X86_64 if isa.default_call_conv() == CallConv::WindowsFastcall => {
+                (self.size == 8).then(|| vec![(I64, 0)])
+            }
+            Architecture::X86_64 if self.size <= 16 => Some(
+                eightbytes()
+                    .map(|offset| {
+                        // An eightbyte is returned in an SSE register if it only holds floats.
+                        let only_floats = self.fields().all(|(ty, field)| {
+                            field + ty.bytes() <= offset || field >= offset + 8 || ty.is_float()
+                        });
+                        (if only_floats { F64 } else { I64 }, offset)
+                    })
+                    .collect(),
+            ),
+            Architecture::Aarch64(_) => {
+                // Homogeneous floating-point aggregates are returned in one register per field.
+                let first = self.types[0];
+                if first.is_float()
+                    && self.types.len() <= 4
+                    && self.types.iter().all(|&ty| ty == first)
+                {
+                    Some(self.fields().collect())
+                } else if self.size <= 16 {
+                    Some(eightbytes().map(|offset| (I64, offset)).collect())
+                } else {
+                    None
+                }
+            }
+            Architecture::Riscv64(_) if self.size <= 16 => {
+                // Two fields with at least one float are returned in one register each.
+                if self.types.len() == 2 && self.types.iter().any(|ty| ty.is_float()) {
+                    Some(self.fields().collect())
+                } else {
+                    Some(eightbytes().map(|offset| (I64, offset)).collect())
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (ir::Type, u32)> + '_ {
+        self.types.iter().copied().zip(self.offsets.iter().copied())
+    }
+
+    fn create_slot(&self, builder: &mut FunctionBuilder) -> ir::StackSlot {
+        builder.create_sized_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            self.size,
+            self.align.trailing_zeros() as u8,
+        ))
+    }
+
+    /// Turns the results of the function being built into the values it returns, storing them
+    /// through its struct return parameter if it has one.
+    pub fn returned(&self, builder: &mut FunctionBuilder, values: &[ir::Value]) -> Vec<ir::Value> {
+        match &self.parts {
+            Some(parts) => {
+                let slot = self.create_slot(builder);
+                for (&value, &offset) in values.iter().zip(&self.offsets) {
+                    builder.ins().stack_store(value, slot, offset as i32);
+                }
+                parts
+                    .iter()
+                    .map(|&(ty, offset)| builder.ins().stack_load(ty, slot, offset as i32))
+                    .collect()
+            }
+            None => {
+                let sret = builder
+                    .func
+                    .special_param(ir::ArgumentPurpose::StructReturn)
+                    .expect("functions returning a struct in memory take a struct return pointer");
+                for (&value, &offset) in values.iter().zip(&self.offsets) {
+                    builder
+                        .ins()
+                        .store(ir::MemFlags::trusted(), value, sret, offset as i32);
+                }
+                vec![]
+            }
+        }
+    }
+
+    /// Prepares a call to a function returning these results, passing it a struct return pointer
+    /// in `args` if it takes one. Returns the slot the results are read from after the call.
+    pub fn prepare_call(
+        &self,
+        builder: &mut FunctionBuilder,
+        pointer_type: ir::Type,
+        args: &mut Vec<ir::Value>,
+    ) -> ir::StackSlot {
+        let slot = self.create_slot(builder);
+        if self.parts.is_none() {
+            let sret = builder.ins().stack_addr(pointer_type, slot, 0);
+            args.insert(0, sret);
+        }
+        slot
+    }
+
+    /// Reads the results of a call prepared with `prepare_call`.
+    pub fn call_results(
+        &self,
+        builder: &mut FunctionBuilder,
+        call: ir::Inst,
+        slot: ir::StackSlot,
+    ) -> Vec<ir::Value> {
+        if let Some(parts) = &self.parts {
+            let values = builder.inst_results(call).to_vec();
+            for (value, &(_, offset)) in values.into_iter().zip(parts) {
+                builder.ins().stack_store(value, slot, offset as i32);
+            }
+        }
+        self.fields()
+            .map(|(ty, offset)| builder.ins().stack_load(ty, slot, offset as i32))
+            .collect()
+    }
+}
+
 /// Helper function translating wasmparser types to Cranelift types when possible.
 pub fn reference_type(target_config: TargetFrontendConfig) -> WasmResult<ir::Type> {
     Ok(target_config.pointer_type())
@@ -44,7 +44,7 @@ use wasmer_compiler::{
 };
 use wasmer_types::{
     CompileError, FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex,
-    ModuleInfo, SignatureIndex, TableIndex, Type,
+    ModuleInfo, SignatureIndex, TableIndex, TrapCode, Type,
 };
 use wasmer_types::{TagIndex, entity::PrimaryMap};
 use wasmer_vm::{MemoryStyle, TableStyle, VMOffsets};
@@ -293,6 +293,7 @@ impl FuncTranslator {
             locals: params_locals,
             ctx: CtxType::new(wasm_module, &func, &cache_builder, &*self.abi, config),
             unreachable_depth: 0,
+            unreachable_trap_code: TrapCode::UnreachableCodeReached,
             memory_styles,
             _table_styles,
             module: &module,
@@ -318,6 +319,7 @@ impl FuncTranslator {
         while fcg.state.has_control_frames() {
             let pos = reader.current_position() as u32;
             let op = reader.read_operator()?;
+            fcg.unreachable_trap_code = reader.trap_code();
             fcg.translate_operator(op, pos)?;
         }
 
@@ -1958,6 +1960,7 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     locals: Vec<(BasicTypeEnum<'ctx>, PointerValue<'ctx>)>, // Contains params and locals
     ctx: CtxType<'ctx, 'a>,
     unreachable_depth: usize,
+    unreachable_trap_code: TrapCode,
     memory_styles: &'a PrimaryMap<MemoryIndex, MemoryStyle>,
     _table_styles: &'a PrimaryMap<TableIndex, TableStyle>,
 
@@ -2423,9 +2426,13 @@ impl<'ctx> LLVMFunctionCodeGenerator<'ctx, '_> {
             }
 
             Operator::Unreachable => {
+                let trap_code = match self.unreachable_trap_code {
+                    TrapCode::CallDepthExceeded => self.intrinsics.trap_call_depth_exceeded,
+                    _ => self.intrinsics.trap_unreachable,
+                };
                 err!(self.builder.build_call(
                     self.intrinsics.throw_trap,
-                    &[self.intrinsics.trap_unreachable.into()],
+                    &[trap_code.into()],
                     "throw",
                 ));
                 err!(self.builder.build_unreachable());
@@ -7,7 +7,10 @@ use crate::{
     common_decl::*,
     config::Singlepass,
     location::{Location, Reg},
-    machine::{Label, Machine, MachineStackOffset, NATIVE_PAGE_SIZE, UnsignedCondition},
+    machine::{
+        Label, Machine, MachineStackOffset, NATIVE_PAGE_SIZE, ResultsLayout, ResultsRegisters,
+        UnsignedCondition,
+    },
     unwind::UnwindFrame,
 };
 #[cfg(feature = "unwind")]
@@ -71,6 +74,12 @@ pub struct FuncGen<'a, M: Machine> {
     /// Metadata about floating point values on the stack.
     fp_stack: Vec<FloatValue>,
 
+    /// Depths in the value stack of the `v128` values.
+    v128_stack: Vec<usize>,
+
+    /// Stack offsets of the 16-byte slots holding `v128` values of the value stack.
+    v128_slots: Vec<usize>,
+
     /// A list of frames describing the current control stack.
     control_stack: Vec<ControlFrame>,
 
@@ -78,6 +87,19 @@ pub struct FuncGen<'a, M: Machine> {
 
     save_area_offset: Option<MachineStackOffset>,
 
+    /// Stack slots carrying all but the first of the values passed by a branch,
+    /// in ascending address order. They also receive the struct holding the
+    /// results of callees returning more than one word.
+    multi_value_slots: Vec<Location<M::GPR, M::SIMD>>,
+
+    /// Stack slot holding the address of the memory receiving the struct of
+    /// results, if the function returns it through memory.
+    return_area_slot: Option<Location<M::GPR, M::SIMD>>,
+
+    /// 16-byte stack area where the struct of results is assembled, if the
+    /// function returns it in registers.
+    return_buffer: Option<Location<M::GPR, M::SIMD>>,
+
     state: MachineState,
 
     track_state: bool,
@@ -88,6 +110,9 @@ pub struct FuncGen<'a, M: Machine> {
     /// Nesting level of unreachable code.
     unreachable_depth: usize,
 
+    /// Trap code used when lowering the next `unreachable` operator.
+    unreachable_trap_code: TrapCode,
+
     /// Function state map. Not yet used in the reborn version but let's keep it.
     fsm: FunctionStateMap,
 
@@ -228,6 +253,7 @@ pub struct ControlFrame {
     pub label: Label,
     pub loop_like: bool,
     pub if_else: IfElseState,
+    pub params: SmallVec<[WpType; 8]>,
     pub returns: SmallVec<[WpType; 1]>,
     pub value_stack_depth: usize,
     pub fp_stack_depth: usize,
@@ -235,6 +261,18 @@ pub struct ControlFrame {
     pub state_diff_id: usize,
 }
 
+impl ControlFrame {
+    /// Types of the values carried by a branch to this frame: the parameters
+    /// of a loop, or the results of any other block.
+    fn branch_types(&self) -> &[WpType] {
+        if self.loop_like {
+            &self.params
+        } else {
+            &self.returns
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum IfElseState {
     None,
@@ -255,6 +293,19 @@ fn type_to_wp_type(ty: Type) -> WpType {
     }
 }
 
+/// Offsets in the multi-value slots of all but the first of values of the given types.
+fn multi_value_offsets(tys: &[WpType]) -> SmallVec<[usize; 8]> {
+    let mut offset = 0;
+    tys.iter()
+        .skip(1)
+        .map(|ty| {
+            let value_offset = offset;
+            offset += if *ty == WpType::V128 { 16 } else { 8 };
+            value_offset
+        })
+        .collect()
+}
+
 /// Abstraction for a 2-input, 1-output operator. Can be an integer/floating-point
 /// binop/cmpop.
 struct I2O1<R: Reg, S: Reg> {
@@ -283,7 +334,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
 
         for (ty, mv) in tys {
             let loc = match *ty {
-                WpType::F32 | WpType::F64 => self.machine.pick_simd().map(Location::SIMD),
+                WpType::F32 | WpType::F64 | WpType::V128 => {
+                    self.machine.pick_simd().map(Location::SIMD)
+                }
                 WpType::I32 | WpType::I64 => self.machine.pick_gpr().map(Location::GPR),
                 WpType::Ref(ty) if ty.is_extern_ref() || ty.is_func_ref() => {
                     self.machine.pick_gpr().map(Location::GPR)
@@ -294,8 +347,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             let loc = if let Some(x) = loc {
                 x
             } else {
-                self.stack_offset.0 += 8;
-                delta_stack_offset += 8;
+                let size = if *ty == WpType::V128 { 16 } else { 8 };
+                self.stack_offset.0 += size;
+                delta_stack_offset += size;
+                if *ty == WpType::V128 {
+                    self.v128_slots.push(self.stack_offset.0);
+                }
                 self.machine.local_on_stack(self.stack_offset.0 as i32)
             };
             if let Location::GPR(x) = loc {
@@ -316,8 +373,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             self.machine.adjust_stack(delta_stack_offset as u32)?;
         }
         if zeroed {
-            for i in 0..tys.len() {
-                self.machine.zero_location(Size::S64, ret[i])?;
+            for (i, (ty, _)) in tys.iter().enumerate() {
+                if *ty == WpType::V128 {
+                    self.machine.v128_const(0, ret[i])?;
+                } else {
+                    self.machine.zero_location(Size::S64, ret[i])?;
+                }
             }
         }
         Ok(ret)
@@ -355,8 +416,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                                 self.stack_offset.0
                             );
                         }
-                        self.stack_offset.0 -= 8;
-                        delta_stack_offset += 8;
+                        let size = self.pop_v128_slot(offset);
+                        self.stack_offset.0 -= size;
+                        delta_stack_offset += size;
                         self.state
                             .stack_values
                             .pop()
@@ -379,7 +441,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
     /// Releases locations used for stack value.
     fn release_locations_value(&mut self, stack_depth: usize) -> Result<(), CompileError> {
         let mut delta_stack_offset: usize = 0;
-        let locs: &[Location<M::GPR, M::SIMD>] = &self.value_stack[stack_depth..];
+        let locs = self.value_stack[stack_depth..].to_vec();
 
         for loc in locs.iter().rev() {
             match *loc {
@@ -406,8 +468,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                                 self.stack_offset.0
                             );
                         }
-                        self.stack_offset.0 -= 8;
-                        delta_stack_offset += 8;
+                        let size = self.pop_v128_slot(offset);
+                        self.stack_offset.0 -= size;
+                        delta_stack_offset += size;
                         self.state.stack_values.pop().ok_or_else(|| {
                             CompileError::Codegen("Pop with values stack empty".to_owned())
                         })?;
@@ -467,8 +530,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     if offset != self.stack_offset.0 {
                         codegen_error!("Invalid memory offset {}!={}", offset, self.stack_offset.0);
                     }
-                    self.stack_offset.0 -= 8;
-                    delta_stack_offset += 8;
+                    let size = self.pop_v128_slot(offset);
+                    self.stack_offset.0 -= size;
+                    delta_stack_offset += size;
                     self.state.stack_values.pop().ok_or_else(|| {
                         CompileError::Codegen("Pop on empty value stack".to_owned())
                     })?;
@@ -498,6 +562,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
     fn release_locations_keep_state(&mut self, stack_depth: usize) -> Result<(), CompileError> {
         let mut delta_stack_offset: usize = 0;
         let mut stack_offset = self.stack_offset.0;
+        let mut v128_slots = self.v128_slots.iter().rev().peekable();
         let locs = &self.value_stack[stack_depth..];
 
         for loc in locs.iter().rev() {
@@ -510,8 +575,13 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     if offset != stack_offset {
                         codegen_error!("Invalid memory offset {}!={}", offset, self.stack_offset.0);
                     }
-                    stack_offset -= 8;
-                    delta_stack_offset += 8;
+                    let size = if v128_slots.next_if_eq(&&offset).is_some() {
+                        16
+                    } else {
+                        8
+                    };
+                    stack_offset -= size;
+                    delta_stack_offset += size;
                 }
             }
         }
@@ -523,6 +593,16 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         Ok(())
     }
 
+    /// Size of the value stack slot at `offset`, forgetting it if it holds a `v128`.
+    fn pop_v128_slot(&mut self, offset: usize) -> usize {
+        if self.v128_slots.last() == Some(&offset) {
+            self.v128_slots.pop();
+            16
+        } else {
+            8
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     fn init_locals(
         &mut self,
@@ -530,8 +610,13 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         sig: FunctionType,
         calling_convention: CallingConvention,
     ) -> Result<Vec<Location<M::GPR, M::SIMD>>, CompileError> {
+        // Scalar locals are numbered apart from the `v128` ones, which always live
+        // on the stack.
+        let local_types = self.local_types[..n].to_vec();
+        let num_scalars = local_types.iter().filter(|ty| **ty != WpType::V128).count();
+
         // How many machine stack slots will all the locals use?
-        let num_mem_slots = (0..n)
+        let num_mem_slots = (0..num_scalars)
             .filter(|&x| self.machine.is_local_on_stack(x))
             .count();
 
@@ -541,7 +626,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
 
         // Callee-saved registers used for locals.
         // Keep this consistent with the "Save callee-saved registers" code below.
-        for i in 0..n {
+        for i in 0..num_scalars {
             // If a local is not stored on stack, then it is allocated to a callee-saved register.
             if !self.machine.is_local_on_stack(i) {
                 static_area_size += 8;
@@ -557,13 +642,86 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         // Total size of callee saved registers.
         let callee_saved_regs_size = static_area_size;
 
-        // Now we can determine concrete locations for locals.
-        let locations: Vec<Location<M::GPR, M::SIMD>> = (0..n)
-            .map(|i| self.machine.get_local_location(i, callee_saved_regs_size))
+        // Now we can determine concrete locations for locals. The `v128` locals take
+        // 16-byte slots after the ones of the scalar locals.
+        let scalar_area_size = static_area_size + num_mem_slots * 8;
+        let mut num_scalars = 0;
+        let mut num_v128s = 0;
+        let locations: Vec<Location<M::GPR, M::SIMD>> = local_types
+            .iter()
+            .map(|ty| {
+                if *ty == WpType::V128 {
+                    num_v128s += 1;
+                    self.machine
+                        .local_on_stack((scalar_area_size + num_v128s * 16) as i32)
+                } else {
+                    num_scalars += 1;
+                    self.machine
+                        .get_local_location(num_scalars - 1, callee_saved_regs_size)
+                }
+            })
             .collect();
 
         // Add size of locals on stack.
-        static_area_size += num_mem_slots * 8;
+        static_area_size = scalar_area_size + num_v128s * 16;
+
+        // Slots used to pass all but the first of multiple values across branches, and
+        // to receive the results of calls. No block or function type can carry more
+        // values than the largest signature of the module.
+        let num_multi_value_slots = self
+            .module
+            .signatures
+            .values()
+            .map(|sig| {
+                let values_size = |tys: &[Type]| {
+                    tys.iter()
+                        .skip(1)
+                        .map(|ty| if *ty == Type::V128 { 16 } else { 8 })
+                        .sum::<usize>()
+                };
+                let struct_size = ResultsLayout::new(sig.results()).map_or(0, |layout| layout.size);
+                cmp::max(
+                    cmp::max(values_size(sig.params()), values_size(sig.results())),
+                    struct_size,
+                ) / 8
+            })
+            .fold(1, cmp::max);
+        self.multi_value_slots = (0..num_multi_value_slots)
+            .map(|i| {
+                self.machine
+                    .local_on_stack((static_area_size + (num_multi_value_slots - i) * 8) as i32)
+            })
+            .collect();
+        static_area_size += num_multi_value_slots * 8;
+
+        // Space to return a struct of results, or to hold the address of the memory
+        // receiving it.
+        if let Some(layout) = ResultsLayout::new(sig.results()) {
+            if self
+                .machine
+                .get_results_registers(sig.results(), &layout, calling_convention)
+                .is_some()
+            {
+                static_area_size += 16;
+                self.return_buffer = Some(self.machine.local_on_stack(static_area_size as i32));
+            } else {
+                static_area_size += 8;
+                self.return_area_slot = Some(self.machine.local_on_stack(static_area_size as i32));
+            }
+        }
+        // The address of that memory may come in a temporary register, keep it until saved.
+        let results_address = self
+            .return_area_slot
+            .map(|_| self.machine.get_results_address_reg(calling_convention));
+        if let Some(gpr) = results_address {
+            self.machine.reserve_unused_temp_gpr(gpr);
+        }
+        let first_param =
+            if results_address.is_some() && self.machine.results_address_is_first_param() {
+                2
+            } else {
+                1
+            };
 
         // Allocate save area, without actually writing to it.
         static_area_size = self.machine.round_stack_adjust(static_area_size);
@@ -572,11 +730,24 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         //
         // `rep stosq` writes data from low address to high address and may skip the stack guard page.
         // so here we probe it explicitly when needed.
-        for i in (sig.params().len()..n)
+        let mut probed_locals: Vec<_> = locations[sig.params().len()..]
+            .iter()
+            .filter(|loc| matches!(loc, Location::Memory(_, _)))
+            .cloned()
+            .collect();
+        probed_locals.sort_by(|a, b| b.cmp(a));
+        for loc in probed_locals.iter().step_by(NATIVE_PAGE_SIZE / 16).skip(1) {
+            self.machine.zero_location(Size::S64, *loc)?;
+        }
+        for slot in self
+            .multi_value_slots
+            .clone()
+            .iter()
+            .rev()
             .step_by(NATIVE_PAGE_SIZE / 8)
             .skip(1)
         {
-            self.machine.zero_location(Size::S64, locations[i])?;
+            self.machine.zero_location(Size::S64, *slot)?;
         }
 
         self.machine.adjust_stack(static_area_size as _)?;
@@ -630,39 +801,62 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         // Locals are allocated on the stack from higher address to lower address,
         // so we won't skip the stack guard page here.
         let mut stack_offset: usize = 0;
+        let mut num_scalar_params = 0;
+        let mut num_v128_params = 0;
         for (i, param) in sig.params().iter().enumerate() {
             let sz = match *param {
                 Type::I32 | Type::F32 => Size::S32,
                 Type::I64 | Type::F64 => Size::S64,
                 Type::ExternRef | Type::FuncRef => Size::S64,
+                Type::V128 => {
+                    let loc = self.machine.get_simd_call_param_location(
+                        num_v128_params,
+                        &mut stack_offset,
+                        calling_convention,
+                    );
+                    num_v128_params += 1;
+                    self.machine.move_v128(loc, locations[i])?;
+                    continue;
+                }
                 _ => codegen_error!("singlepass init_local unimplemented"),
             };
             let loc = self.machine.get_call_param_location(
-                i + 1,
+                num_scalar_params + first_param,
                 sz,
                 &mut stack_offset,
                 calling_convention,
             );
+            num_scalar_params += 1;
             self.machine
                 .move_location_extend(sz, false, loc, Size::S64, locations[i])?;
         }
 
+        // Save the address of the memory receiving the results.
+        if let (Some(gpr), Some(slot)) = (results_address, self.return_area_slot) {
+            self.machine
+                .move_location(Size::S64, Location::GPR(gpr), slot)?;
+            self.machine.release_gpr(gpr);
+        }
+
         // Load vmctx into it's GPR.
         self.machine.move_location(
             Size::S64,
             self.machine
-                .get_simple_param_location(0, calling_convention),
+                .get_simple_param_location(first_param - 1, calling_convention),
             Location::GPR(self.machine.get_vmctx_reg()),
         )?;
 
-        // Initialize all normal locals to zero.
-        let mut init_stack_loc_cnt = 0;
-        let mut last_stack_loc = Location::Memory(self.machine.local_pointer(), i32::MAX);
-        for location in locations.iter().take(n).skip(sig.params().len()) {
+        // Initialize all normal locals to zero. The slots of the scalar and of the
+        // `v128` locals are contiguous apart from each other.
+        let mut init_stack_locs =
+            [(0, Location::Memory(self.machine.local_pointer(), i32::MAX)); 2];
+        for (location, ty) in locations.iter().zip(&local_types).skip(sig.params().len()) {
             match location {
                 Location::Memory(_, _) => {
-                    init_stack_loc_cnt += 1;
-                    last_stack_loc = cmp::min(last_stack_loc, *location);
+                    let (init_stack_loc_cnt, last_stack_loc) =
+                        &mut init_stack_locs[(*ty == WpType::V128) as usize];
+                    *init_stack_loc_cnt += if *ty == WpType::V128 { 2 } else { 1 };
+                    *last_stack_loc = cmp::min(*last_stack_loc, *location);
                 }
                 Location::GPR(_) => {
                     self.machine.zero_location(Size::S64, *location)?;
@@ -670,9 +864,11 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 _ => codegen_error!("singlepass init_local unreachable"),
             }
         }
-        if init_stack_loc_cnt > 0 {
-            self.machine
-                .init_stack_loc(init_stack_loc_cnt, last_stack_loc)?;
+        for (init_stack_loc_cnt, last_stack_loc) in init_stack_locs {
+            if init_stack_loc_cnt > 0 {
+                self.machine
+                    .init_stack_loc(init_stack_loc_cnt, last_stack_loc)?;
+            }
         }
 
         // Add the size of all locals allocated to stack.
@@ -712,6 +908,11 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         self.machine.set_srcloc(offset);
     }
 
+    /// Set the trap code used if the next operator fed is an `unreachable`.
+    pub fn set_unreachable_trap_code(&mut self, code: TrapCode) {
+        self.unreachable_trap_code = code;
+    }
+
     fn get_location_released(
         &mut self,
         loc: Location<M::GPR, M::SIMD>,
@@ -727,6 +928,332 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         self.get_location_released(loc)
     }
 
+    /// Pops the `n` topmost entries off the value stack and releases their locations.
+    fn pop_values_released(&mut self, n: usize) -> Result<(), CompileError> {
+        let depth = self.value_stack.len() - n;
+        let released = self.value_stack.split_off(depth);
+        self.release_locations(&released)?;
+        while self.fp_stack.last().is_some_and(|fp| fp.depth >= depth) {
+            self.fp_stack.pop();
+        }
+        self.truncate_v128_stack(depth);
+        Ok(())
+    }
+
+    /// Forgets the `v128` values at or above `depth` in the value stack.
+    fn truncate_v128_stack(&mut self, depth: usize) {
+        while self.v128_stack.last().is_some_and(|&d| d >= depth) {
+            self.v128_stack.pop();
+        }
+    }
+
+    /// Whether the value right below the `n` topmost values of the value stack is a `v128`.
+    fn is_v128_below(&self, n: usize) -> bool {
+        self.v128_stack.last().map(|&d| d + 1 + n) == Some(self.value_stack.len())
+    }
+
+    /// Pushes a `v128` value onto the value stack, returning its location.
+    fn push_v128(&mut self) -> Result<Location<M::GPR, M::SIMD>, CompileError> {
+        let depth = self.value_stack.len();
+        let loc =
+            self.acquire_locations(&[(WpType::V128, MachineValue::WasmStack(depth))], false)?[0];
+        self.value_stack.push(loc);
+        self.v128_stack.push(depth);
+        Ok(loc)
+    }
+
+    /// Performs the pending NaN canonicalization of the float on top of the value stack,
+    /// if it has to be, and pops it off the FP stack.
+    fn canonicalize_float_operand(&mut self) -> Result<(), CompileError> {
+        let depth = self.value_stack.len() - 1;
+        if let Some(canonicalization) = self.pending_canonicalization(depth) {
+            let loc = self.value_stack[depth];
+            self.machine
+                .canonicalize_nan(canonicalization.to_size(), loc, loc)?;
+        }
+        self.fp_stack.pop1()?;
+        Ok(())
+    }
+
+    /// Pops a `v128` value off the value stack and releases its location.
+    fn pop_v128_released(&mut self) -> Result<Location<M::GPR, M::SIMD>, CompileError> {
+        self.v128_stack.pop1()?;
+        self.pop_value_released()
+    }
+
+    /// Returns the parameter and result types of a block.
+    #[allow(clippy::type_complexity)]
+    fn block_signature(
+        &self,
+        blockty: WpTypeOrFuncType,
+    ) -> (SmallVec<[WpType; 8]>, SmallVec<[WpType; 1]>) {
+        match blockty {
+            WpTypeOrFuncType::Empty => (smallvec![], smallvec![]),
+            WpTypeOrFuncType::Type(inner_ty) => (smallvec![], smallvec![inner_ty]),
+            WpTypeOrFuncType::FuncType(sig_index) => {
+                let sig = &self.module.signatures[SignatureIndex::from_u32(sig_index)];
+                (
+                    sig.params().iter().cloned().map(type_to_wp_type).collect(),
+                    sig.results().iter().cloned().map(type_to_wp_type).collect(),
+                )
+            }
+        }
+    }
+
+    /// Pending NaN canonicalization of the value at `depth` in the value stack, if it
+    /// has to be performed.
+    fn pending_canonicalization(&self, depth: usize) -> Option<CanonicalizeType> {
+        if !self.machine.arch_supports_canonicalize_nan()
+            || !self.config.enable_nan_canonicalization
+        {
+            return None;
+        }
+        self.fp_stack
+            .iter()
+            .rfind(|fp| fp.depth == depth)
+            .and_then(|fp| fp.canonicalization)
+    }
+
+    /// Moves the values starting at `depth` in the value stack to where a branch target
+    /// or the caller expects them: the first one to the return register, and the other
+    /// ones to the multi-value slots.
+    fn emit_values_out(&mut self, tys: &[WpType], depth: usize) -> Result<(), CompileError> {
+        // Fill the multi-value slots first, memory to memory moves may use the
+        // return register as a temporary.
+        for (i, offset) in multi_value_offsets(tys).into_iter().enumerate() {
+            let ty = tys[i + 1];
+            let loc = self.value_stack[depth + i + 1];
+            let slot = self.multi_value_slot_at(offset)?;
+            match self.pending_canonicalization(depth + i + 1) {
+                _ if ty == WpType::V128 => self.machine.move_v128(loc, slot)?,
+                Some(canonicalization) if ty.is_float() => {
+                    self.machine
+                        .canonicalize_nan(canonicalization.to_size(), loc, slot)?;
+                }
+                _ => self.machine.emit_relaxed_mov(Size::S64, loc, slot)?,
+            }
+        }
+        if let Some(&ty) = tys.first() {
+            let loc = self.value_stack[depth];
+            let canonicalize = ty.is_float() && self.pending_canonicalization(depth).is_some();
+            self.machine
+                .emit_function_return_value(ty, canonicalize, loc)?;
+        }
+        Ok(())
+    }
+
+    /// Pushes values passed by a branch or returned by a call onto the value stack.
+    /// The first value is read from `first`, or from the SIMD return register if it is
+    /// a `v128`, and the other ones from the multi-value slots.
+    fn push_values_in(
+        &mut self,
+        tys: &[WpType],
+        first: Location<M::GPR, M::SIMD>,
+    ) -> Result<(), CompileError> {
+        let depth = self.value_stack.len();
+        let values: SmallVec<[_; 8]> = tys
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| (*ty, MachineValue::WasmStack(depth + i)))
+            .collect();
+        let locs = self.acquire_locations(&values, false)?;
+        let offsets = multi_value_offsets(tys);
+        for (i, (ty, loc)) in tys.iter().zip(locs).enumerate() {
+            let src = if i == 0 && *ty == WpType::V128 {
+                Location::SIMD(self.machine.get_simd_for_ret())
+            } else if i == 0 {
+                first
+            } else {
+                self.multi_value_slot_at(offsets[i - 1])?
+            };
+            if *ty == WpType::V128 {
+                self.machine.move_v128(src, loc)?;
+                self.v128_stack.push(depth + i);
+            } else if i == 0 {
+                self.machine.move_location(Size::S64, src, loc)?;
+            } else {
+                self.machine.emit_relaxed_mov(Size::S64, src, loc)?;
+            }
+            self.value_stack.push(loc);
+            if ty.is_float() {
+                self.fp_stack.push(FloatValue::new(depth + i));
+            }
+        }
+        Ok(())
+    }
+
+    /// Acquires a location for the address of the multi-value slots, passed to callees
+    /// returning their results through memory.
+    fn acquire_return_area_param(&mut self) -> Result<Location<M::GPR, M::SIMD>, CompileError> {
+        Ok(self.acquire_locations(&[(WpType::I64, MachineValue::Undefined)], false)?[0])
+    }
+
+    /// Writes the address of the multi-value slots to `dest`, using `tmp` as scratch.
+    fn emit_return_area_address(
+        &mut self,
+        tmp: M::GPR,
+        dest: Location<M::GPR, M::SIMD>,
+    ) -> Result<(), CompileError> {
+        self.machine
+            .location_address(Size::S64, self.multi_value_slots[0], Location::GPR(tmp))?;
+        self.machine
+            .move_location(Size::S64, Location::GPR(tmp), dest)
+    }
+
+    /// Returns the results of the function, whose first value is in the return register
+    /// and the other ones in the multi-value slots, as a struct following `layout`. It
+    /// goes to registers if the calling convention allows it, or else to the memory
+    /// provided by the caller.
+    fn emit_results_struct(&mut self, layout: &ResultsLayout) -> Result<(), CompileError> {
+        let ret = self
+            .machine
+            .reserve_unused_temp_gpr(self.machine.get_gpr_for_ret());
+        let area = self.machine.acquire_temp_gpr().unwrap();
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        match (self.return_area_slot, self.return_buffer) {
+            (Some(slot), _) => {
+                self.machine
+                    .move_location(Size::S64, slot, Location::GPR(area))?;
+            }
+            (None, Some(buffer)) => {
+                self.machine
+                    .location_address(Size::S64, buffer, Location::GPR(area))?;
+            }
+            (None, None) => codegen_error!("singlepass emit_results_struct without return area"),
+        }
+
+        let results = self.signature.results();
+        let offsets = multi_value_offsets(
+            &results
+                .iter()
+                .cloned()
+                .map(type_to_wp_type)
+                .collect::<SmallVec<[_; 8]>>(),
+        );
+        for (i, (ty, offset)) in results.iter().zip(&layout.offsets).enumerate() {
+            if i == 0 && *ty == Type::V128 {
+                self.machine.move_v128(
+                    Location::SIMD(self.machine.get_simd_for_ret()),
+                    Location::Memory(area, *offset as i32),
+                )?;
+                continue;
+            }
+            let (sz, words) = match ty {
+                Type::I32 | Type::F32 => (Size::S32, 1),
+                Type::V128 => (Size::S64, 2),
+                _ => (Size::S64, 1),
+            };
+            for word in 0..words {
+                let src = if i == 0 {
+                    Location::GPR(ret)
+                } else {
+                    self.multi_value_slot_at(offsets[i - 1] + word * 8)?
+                };
+                self.machine.move_location(sz, src, Location::GPR(tmp))?;
+                self.machine.move_location(
+                    sz,
+                    Location::GPR(tmp),
+                    Location::Memory(area, (offset + word * 8) as i32),
+                )?;
+            }
+        }
+
+        match self.machine.get_results_registers(
+            self.signature.results(),
+            layout,
+            self.calling_convention,
+        ) {
+            Some(parts) => {
+                for (reg, offset, sz) in parts {
+                    self.machine
+                        .move_location(sz, Location::Memory(area, offset as i32), reg)?;
+                }
+            }
+            // The x86-64 conventions also return the address of the struct.
+            None => {
+                self.machine
+                    .move_location(Size::S64, Location::GPR(area), Location::GPR(ret))?;
+            }
+        }
+        self.machine.release_gpr(tmp);
+        self.machine.release_gpr(area);
+        self.machine.release_gpr(ret);
+        Ok(())
+    }
+
+    /// Location `offset` bytes into the multi-value slots.
+    fn multi_value_slot_at(
+        &self,
+        offset: usize,
+    ) -> Result<Location<M::GPR, M::SIMD>, CompileError> {
+        match self.multi_value_slots[0] {
+            Location::Memory(base, disp) => Ok(Location::Memory(base, disp + offset as i32)),
+            _ => codegen_error!("singlepass multi_value_slot_at unreachable"),
+        }
+    }
+
+    /// Pushes the values returned by a call onto the value stack. More than one result
+    /// is returned as a struct following `layout`, read from `registers` or else from
+    /// the multi-value slots, whose address was passed to the callee.
+    fn push_call_results_in(
+        &mut self,
+        tys: &[WpType],
+        layout: Option<ResultsLayout>,
+        registers: Option<ResultsRegisters<M::GPR, M::SIMD>>,
+    ) -> Result<(), CompileError> {
+        let Some(layout) = layout else {
+            if let Some(ty) = tys.first() {
+                let ret = if ty.is_float() || *ty == WpType::V128 {
+                    Location::SIMD(self.machine.get_simd_for_ret())
+                } else {
+                    Location::GPR(self.machine.get_gpr_for_ret())
+                };
+                self.push_values_in(tys, ret)?;
+            }
+            return Ok(());
+        };
+        for (reg, offset, sz) in registers.into_iter().flatten() {
+            let slot = self.multi_value_slot_at(offset)?;
+            self.machine.move_location(sz, reg, slot)?;
+        }
+
+        let depth = self.value_stack.len();
+        let values: SmallVec<[_; 8]> = tys
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| (*ty, MachineValue::WasmStack(depth + i)))
+            .collect();
+        let locs = self.acquire_locations(&values, false)?;
+        for (i, ((ty, loc), offset)) in tys.iter().zip(locs).zip(&layout.offsets).enumerate() {
+            let slot = self.multi_value_slot_at(*offset)?;
+            match ty {
+                WpType::V128 => {
+                    self.machine.move_v128(slot, loc)?;
+                    self.v128_stack.push(depth + i);
+                }
+                WpType::I32 | WpType::F32 => self.machine.emit_relaxed_mov(Size::S32, slot, loc)?,
+                _ => self.machine.emit_relaxed_mov(Size::S64, slot, loc)?,
+            }
+            self.value_stack.push(loc);
+            if ty.is_float() {
+                self.fp_stack.push(FloatValue::new(depth + i));
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits a branch to the frame `relative_depth` levels up the control stack.
+    fn emit_branch(&mut self, relative_depth: u32) -> Result<(), CompileError> {
+        let frame = &self.control_stack[self.control_stack.len() - 1 - relative_depth as usize];
+        let tys: SmallVec<[WpType; 8]> = frame.branch_types().into();
+        let frame_depth = frame.value_stack_depth;
+        let label = frame.label;
+
+        self.emit_values_out(&tys, self.value_stack.len() - tys.len())?;
+        self.release_locations_keep_state(frame_depth)?;
+        self.machine.jmp_unconditionnal(label)
+    }
+
     /// Prepare data for binary operator with 2 inputs and 1 output.
     fn i2o1_prepare(&mut self, ty: WpType) -> Result<I2O1<M::GPR, M::SIMD>, CompileError> {
         let loc_b = self.pop_value_released()?;
@@ -785,18 +1312,89 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         params: I,
         params_type: J,
     ) -> Result<(), CompileError> {
-        // Values pushed in this function are above the shadow region.
-        self.state.stack_values.push(MachineValue::ExplicitShadow);
+        self.emit_call_native_with_results_address(cb, params, params_type, None)
+    }
 
-        let params: Vec<_> = params.collect();
+    /// Like `emit_call_native`, also passing the address of the memory receiving the
+    /// results of a callee returning them through memory.
+    fn emit_call_native_with_results_address<
+        I: Iterator<Item = Location<M::GPR, M::SIMD>>,
+        J: Iterator<Item = WpType>,
+        F: FnOnce(&mut Self) -> Result<(), CompileError>,
+    >(
+        &mut self,
+        cb: F,
+        params: I,
+        params_type: J,
+        results_address: Option<Location<M::GPR, M::SIMD>>,
+    ) -> Result<(), CompileError> {
+        let mut params: Vec<_> = params.collect();
+        let params_type: Vec<_> = params_type.collect();
         let params_size: Vec<_> = params_type
+            .iter()
             .map(|x| match x {
                 WpType::F32 | WpType::I32 => Size::S32,
-                WpType::V128 => unimplemented!(),
                 _ => Size::S64,
             })
             .collect();
 
+        let calling_convention = self.calling_convention;
+
+        // The address of the memory receiving the results may come before the vmctx.
+        let vmctx_param =
+            if results_address.is_some() && self.machine.results_address_is_first_param() {
+                1
+            } else {
+                0
+            };
+
+        // Calculate stack offset.
+        let mut stack_offset: usize = 0;
+        let mut args: Vec<Location<M::GPR, M::SIMD>> = vec![];
+        let mut num_scalar_args = vmctx_param + 1;
+        let mut num_v128_args = 0;
+        for (i, ty) in params_type.iter().enumerate() {
+            if *ty == WpType::V128 {
+                args.push(self.machine.get_simd_param_location(
+                    num_v128_args,
+                    &mut stack_offset,
+                    calling_convention,
+                ));
+                num_v128_args += 1;
+            } else {
+                args.push(self.machine.get_param_location(
+                    num_scalar_args,
+                    params_size[i],
+                    &mut stack_offset,
+                    calling_convention,
+                ));
+                num_scalar_args += 1;
+            }
+        }
+
+        // `v128` arguments going from a SIMD register to another one could overwrite
+        // each other, so they are first copied to stack slots.
+        let mut staged_v128s = 0;
+        for (param, arg) in params.iter_mut().zip(&args) {
+            if let (Location::SIMD(_), Location::SIMD(_)) = (*param, *arg) {
+                if *param == *arg {
+                    continue;
+                }
+                self.stack_offset.0 += 16;
+                self.machine
+                    .adjust_stack(self.machine.round_stack_adjust(16) as u32)?;
+                self.v128_slots.push(self.stack_offset.0);
+                self.state.stack_values.push(MachineValue::Undefined);
+                let slot = self.machine.local_on_stack(self.stack_offset.0 as i32);
+                self.machine.move_v128(*param, slot)?;
+                *param = slot;
+                staged_v128s += 1;
+            }
+        }
+
+        // Values pushed in this function are above the shadow region.
+        self.state.stack_values.push(MachineValue::ExplicitShadow);
+
         // Save used GPRs. Preserve correct stack alignment
         let used_gprs = self.machine.get_used_gprs();
         let mut used_stack = self.machine.push_used_gpr(&used_gprs)?;
@@ -830,25 +1428,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         self.machine
             .reserve_unused_temp_gpr(self.machine.get_grp_for_call());
 
-        let calling_convention = self.calling_convention;
-
         let stack_padding: usize = match calling_convention {
             CallingConvention::WindowsFastcall => 32,
             _ => 0,
         };
 
-        let mut stack_offset: usize = 0;
-        let mut args: Vec<Location<M::GPR, M::SIMD>> = vec![];
         let mut pushed_args: usize = 0;
-        // Calculate stack offset.
-        for (i, _param) in params.iter().enumerate() {
-            args.push(self.machine.get_param_location(
-                1 + i,
-                params_size[i],
-                &mut stack_offset,
-                calling_convention,
-            ));
-        }
 
         // Align stack to 16 bytes.
         let stack_unaligned =
@@ -861,6 +1446,8 @@ impl<'a, M: Machine> FuncGen<'a, M> {
 
         #[allow(clippy::type_complexity)]
         let mut call_movs: Vec<(Location<M::GPR, M::SIMD>, M::GPR)> = vec![];
+        #[allow(clippy::type_complexity)]
+        let mut simd_movs: Vec<(Location<M::GPR, M::SIMD>, M::SIMD)> = vec![];
         // Prepare register & stack parameters.
         for (i, param) in params.iter().enumerate().rev() {
             let loc = args[i];
@@ -868,6 +1455,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 Location::GPR(x) => {
                     call_movs.push((*param, x));
                 }
+                Location::SIMD(x) => {
+                    simd_movs.push((*param, x));
+                }
                 Location::Memory(_, _) => {
                     pushed_args += 1;
                     match *param {
@@ -903,8 +1493,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             self.state.stack_values.push(MachineValue::Undefined);
                         }
                     }
-                    self.machine
-                        .move_location_for_native(params_size[i], *param, loc)?;
+                    if params_type[i] == WpType::V128 {
+                        self.machine.move_v128(*param, loc)?;
+                    } else {
+                        self.machine
+                            .move_location_for_native(params_size[i], *param, loc)?;
+                    }
                 }
                 _ => {
                     return Err(CompileError::Codegen(
@@ -914,6 +1508,13 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             }
         }
 
+        if let Some(loc) = results_address {
+            call_movs.push((
+                loc,
+                self.machine.get_results_address_reg(calling_convention),
+            ));
+        }
+
         // Sort register moves so that register are not overwritten before read.
         Self::sort_call_movs(&mut call_movs);
 
@@ -925,14 +1526,22 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             }
         }
 
-        // Put vmctx as the first parameter.
+        // Put vmctx as the first parameter, after the results address if it comes first.
         self.machine.move_location(
             Size::S64,
             Location::GPR(self.machine.get_vmctx_reg()),
             self.machine
-                .get_simple_param_location(0, calling_convention),
+                .get_simple_param_location(vmctx_param, calling_convention),
         )?; // vmctx
 
+        // Load the `v128` arguments passed in registers, none of which is read from
+        // another argument register.
+        for (loc, simd) in simd_movs {
+            if loc != Location::SIMD(simd) {
+                self.machine.move_v128(loc, Location::SIMD(simd))?;
+            }
+        }
+
         if stack_padding > 0 {
             self.machine.adjust_stack(stack_padding as u32)?;
         }
@@ -1008,6 +1617,20 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 "emit_call_native: Popped value is not ExplicitShadow".to_owned(),
             ));
         }
+
+        // Release the slots of the staged `v128` arguments.
+        if staged_v128s > 0 {
+            for _ in 0..staged_v128s {
+                self.v128_slots.pop();
+                self.state
+                    .stack_values
+                    .pop()
+                    .ok_or_else(|| CompileError::Codegen("Pop an empty value stack".to_owned()))?;
+            }
+            self.stack_offset.0 -= 16 * staged_v128s;
+            self.machine
+                .restore_stack(self.machine.round_stack_adjust(16 * staged_v128s) as u32)?;
+        }
         Ok(())
     }
 
@@ -1098,6 +1721,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             label: self.machine.get_label(),
             loop_like: false,
             if_else: IfElseState::None,
+            params: smallvec![],
             returns: self
                 .signature
                 .results()
@@ -1178,13 +1802,19 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             local_types,
             value_stack: vec![],
             fp_stack: vec![],
+            v128_stack: vec![],
+            v128_slots: vec![],
             control_stack: vec![],
             stack_offset: MachineStackOffset(0),
             save_area_offset: None,
+            multi_value_slots: vec![],
+            return_area_slot: None,
+            return_buffer: None,
             state: machine.new_machine_state(),
             track_state: true,
             machine,
             unreachable_depth: 0,
+            unreachable_trap_code: TrapCode::UnreachableCodeReached,
             fsm,
             relocations: vec![],
             special_labels,
@@ -1242,6 +1872,8 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let ty = type_to_wp_type(self.module.globals[global_index].ty);
                 if ty.is_float() {
                     self.fp_stack.push(FloatValue::new(self.value_stack.len()));
+                } else if ty == WpType::V128 {
+                    self.v128_stack.push(self.value_stack.len());
                 }
                 let loc = self.acquire_locations(
                     &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
@@ -1251,30 +1883,26 @@ impl<'a, M: Machine> FuncGen<'a, M> {
 
                 let tmp = self.machine.acquire_temp_gpr().unwrap();
 
-                let src = if let Some(local_global_index) =
+                let offset = if let Some(local_global_index) =
                     self.module.local_global_index(global_index)
                 {
-                    let offset = self.vmoffsets.vmctx_vmglobal_definition(local_global_index);
-                    self.machine.emit_relaxed_mov(
-                        Size::S64,
-                        Location::Memory(self.machine.get_vmctx_reg(), offset as i32),
-                        Location::GPR(tmp),
-                    )?;
-                    Location::Memory(tmp, 0)
+                    self.vmoffsets.vmctx_vmglobal_definition(local_global_index)
                 } else {
                     // Imported globals require one level of indirection.
-                    let offset = self
-                        .vmoffsets
-                        .vmctx_vmglobal_import_definition(global_index);
-                    self.machine.emit_relaxed_mov(
-                        Size::S64,
-                        Location::Memory(self.machine.get_vmctx_reg(), offset as i32),
-                        Location::GPR(tmp),
-                    )?;
-                    Location::Memory(tmp, 0)
+                    self.vmoffsets
+                        .vmctx_vmglobal_import_definition(global_index)
                 };
-
-                self.machine.emit_relaxed_mov(Size::S64, src, loc)?;
+                self.machine.emit_relaxed_mov(
+                    Size::S64,
+                    Location::Memory(self.machine.get_vmctx_reg(), offset as i32),
+                    Location::GPR(tmp),
+                )?;
+                if ty == WpType::V128 {
+                    self.machine.move_v128(Location::Memory(tmp, 0), loc)?;
+                } else {
+                    self.machine
+                        .emit_relaxed_mov(Size::S64, Location::Memory(tmp, 0), loc)?;
+                }
 
                 self.machine.release_gpr(tmp);
             }
@@ -1304,6 +1932,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     Location::Memory(tmp, 0)
                 };
                 let ty = type_to_wp_type(self.module.globals[global_index].ty);
+                if ty == WpType::V128 {
+                    let loc = self.pop_v128_released()?;
+                    self.machine.move_v128(loc, dst)?;
+                    self.machine.release_gpr(tmp);
+                    return Ok(());
+                }
                 let loc = self.pop_value_released()?;
                 if ty.is_float() {
                     let fp = self.fp_stack.pop1()?;
@@ -1328,6 +1962,27 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 }
                 self.machine.release_gpr(tmp);
             }
+            Operator::LocalGet { local_index }
+                if self.local_types[local_index as usize] == WpType::V128 =>
+            {
+                let local = self.locals[local_index as usize];
+                let ret = self.push_v128()?;
+                self.machine.move_v128(local, ret)?;
+            }
+            Operator::LocalSet { local_index }
+                if self.local_types[local_index as usize] == WpType::V128 =>
+            {
+                let local = self.locals[local_index as usize];
+                let loc = self.pop_v128_released()?;
+                self.machine.move_v128(loc, local)?;
+            }
+            Operator::LocalTee { local_index }
+                if self.local_types[local_index as usize] == WpType::V128 =>
+            {
+                let local = self.locals[local_index as usize];
+                let loc = *self.value_stack.last().unwrap();
+                self.machine.move_v128(loc, local)?;
+            }
             Operator::LocalGet { local_index } => {
                 let local_index = local_index as usize;
                 let ret = self.acquire_locations(
@@ -2644,11 +3299,31 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     sig.params().iter().cloned().map(type_to_wp_type).collect();
                 let return_types: SmallVec<[WpType; 1]> =
                     sig.results().iter().cloned().map(type_to_wp_type).collect();
+                let results_layout = ResultsLayout::new(sig.results());
+                let results_registers = results_layout.as_ref().and_then(|layout| {
+                    self.machine.get_results_registers(
+                        sig.results(),
+                        layout,
+                        self.calling_convention,
+                    )
+                });
 
-                let params: SmallVec<[_; 8]> = self
+                let mut params: SmallVec<[_; 8]> = self
                     .value_stack
                     .drain(self.value_stack.len() - param_types.len()..)
                     .collect();
+                self.truncate_v128_stack(self.value_stack.len());
+                // Results returned through memory are written to the multi-value slots.
+                let return_area = if results_layout.is_some() && results_registers.is_none() {
+                    let return_area = self.acquire_return_area_param()?;
+                    let tmp = self.machine.acquire_temp_gpr().unwrap();
+                    self.emit_return_area_address(tmp, return_area)?;
+                    self.machine.release_gpr(tmp);
+                    params.push(return_area);
+                    Some(return_area)
+                } else {
+                    None
+                };
                 self.release_locations_only_regs(&params)?;
 
                 self.release_locations_only_osr_state(params.len())?;
@@ -2684,7 +3359,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 };
                 let calling_convention = self.calling_convention;
 
-                self.emit_call_native(
+                self.emit_call_native_with_results_address(
                     |this| {
                         let offset = this
                             .machine
@@ -2696,37 +3371,14 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                         this.relocations.append(&mut relocations);
                         Ok(())
                     },
-                    params.iter().copied(),
+                    params.iter().take(param_types.len()).copied(),
                     param_types.iter().copied(),
+                    return_area,
                 )?;
 
                 self.release_locations_only_stack(&params)?;
 
-                if !return_types.is_empty() {
-                    let ret = self.acquire_locations(
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )?[0];
-                    self.value_stack.push(ret);
-                    if return_types[0].is_float() {
-                        self.machine.move_location(
-                            Size::S64,
-                            Location::SIMD(self.machine.get_simd_for_ret()),
-                            ret,
-                        )?;
-                        self.fp_stack
-                            .push(FloatValue::new(self.value_stack.len() - 1));
-                    } else {
-                        self.machine.move_location(
-                            Size::S64,
-                            Location::GPR(self.machine.get_gpr_for_ret()),
-                            ret,
-                        )?;
-                    }
-                }
+                self.push_call_results_in(&return_types, results_layout, results_registers)?;
             }
             Operator::CallIndirect {
                 type_index,
@@ -2741,13 +3393,32 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     sig.params().iter().cloned().map(type_to_wp_type).collect();
                 let return_types: SmallVec<[WpType; 1]> =
                     sig.results().iter().cloned().map(type_to_wp_type).collect();
+                let results_layout = ResultsLayout::new(sig.results());
+                let results_registers = results_layout.as_ref().and_then(|layout| {
+                    self.machine.get_results_registers(
+                        sig.results(),
+                        layout,
+                        self.calling_convention,
+                    )
+                });
 
                 let func_index = self.pop_value_released()?;
 
-                let params: SmallVec<[_; 8]> = self
+                let mut params: SmallVec<[_; 8]> = self
                     .value_stack
                     .drain(self.value_stack.len() - param_types.len()..)
                     .collect();
+                self.truncate_v128_stack(self.value_stack.len());
+                // Results returned through memory are written to the multi-value slots.
+                // The location of their address may reuse the location of `func_index`,
+                // so it is only written once the callee has been looked up.
+                let return_area = if results_layout.is_some() && results_registers.is_none() {
+                    let return_area = self.acquire_return_area_param()?;
+                    params.push(return_area);
+                    Some(return_area)
+                } else {
+                    None
+                };
                 self.release_locations_only_regs(&params)?;
 
                 // Pop arguments off the FP stack and canonicalize them if needed.
@@ -2873,6 +3544,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     ),
                     self.special_labels.bad_signature,
                 )?;
+                if let Some(return_area) = return_area {
+                    self.emit_return_area_address(sigidx, return_area)?;
+                }
                 self.machine.release_gpr(sigidx);
                 self.machine.release_gpr(table_count);
                 self.machine.release_gpr(table_base);
@@ -2893,8 +3567,14 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let vmcaller_checked_anyfunc_vmctx =
                     self.vmoffsets.vmcaller_checked_anyfunc_vmctx() as usize;
                 let calling_convention = self.calling_convention;
+                let vmctx_param =
+                    if return_area.is_some() && self.machine.results_address_is_first_param() {
+                        1
+                    } else {
+                        0
+                    };
 
-                self.emit_call_native(
+                self.emit_call_native_with_results_address(
                     |this| {
                         if this.machine.arch_requires_indirect_call_trampoline() {
                             this.machine
@@ -2915,7 +3595,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                                     vmcaller_checked_anyfunc_vmctx as i32,
                                 ),
                                 this.machine
-                                    .get_simple_param_location(0, calling_convention),
+                                    .get_simple_param_location(vmctx_param, calling_convention),
                             )?;
 
                             this.machine.emit_call_location(Location::Memory(
@@ -2926,57 +3606,30 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             Ok(())
                         }
                     },
-                    params.iter().copied(),
+                    params.iter().take(param_types.len()).copied(),
                     param_types.iter().copied(),
+                    return_area,
                 )?;
 
                 self.release_locations_only_stack(&params)?;
 
-                if !return_types.is_empty() {
-                    let ret = self.acquire_locations(
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )?[0];
-                    self.value_stack.push(ret);
-                    if return_types[0].is_float() {
-                        self.machine.move_location(
-                            Size::S64,
-                            Location::SIMD(self.machine.get_simd_for_ret()),
-                            ret,
-                        )?;
-                        self.fp_stack
-                            .push(FloatValue::new(self.value_stack.len() - 1));
-                    } else {
-                        self.machine.move_location(
-                            Size::S64,
-                            Location::GPR(self.machine.get_gpr_for_ret()),
-                            ret,
-                        )?;
-                    }
-                }
+                self.push_call_results_in(&return_types, results_layout, results_registers)?;
             }
             Operator::If { blockty } => {
                 let label_end = self.machine.get_label();
                 let label_else = self.machine.get_label();
-
+                let (params, returns) = self.block_signature(blockty);
+                // Block parameters are passed to both branches like branch values.
+                self.emit_values_out(&params, self.value_stack.len() - 1 - params.len())?;
                 let cond = self.pop_value_released()?;
+                self.pop_values_released(params.len())?;
 
                 let frame = ControlFrame {
                     label: label_end,
                     loop_like: false,
                     if_else: IfElseState::If(label_else),
-                    returns: match blockty {
-                        WpTypeOrFuncType::Empty => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CompileError::Codegen(
-                                "If: multi-value returns not yet implemented".to_owned(),
-                            ));
-                        }
-                    },
+                    params,
+                    returns,
                     value_stack_depth: self.value_stack.len(),
                     fp_stack_depth: self.fp_stack.len(),
                     state: self.state.clone(),
@@ -2990,23 +3643,17 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     cond,
                     label_else,
                 )?;
+
+                let params = self.control_stack.last().unwrap().params.clone();
+                let ret = Location::GPR(self.machine.get_gpr_for_ret());
+                self.push_values_in(&params, ret)?;
             }
             Operator::Else => {
-                let frame = self.control_stack.last_mut().unwrap();
+                let frame = self.control_stack.last().unwrap();
 
                 if !was_unreachable && !frame.returns.is_empty() {
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    let canonicalize = if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        self.machine.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                    } else {
-                        false
-                    };
-                    self.machine
-                        .emit_function_return_value(first_return, canonicalize, loc)?;
+                    let returns = frame.returns.clone();
+                    self.emit_values_out(&returns, self.value_stack.len() - returns.len())?;
                 }
 
                 let frame = &self.control_stack.last_mut().unwrap();
@@ -3015,6 +3662,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 self.release_locations_value(stack_depth)?;
                 self.value_stack.truncate(stack_depth);
                 self.fp_stack.truncate(fp_depth);
+                self.truncate_v128_stack(stack_depth);
                 let frame = &mut self.control_stack.last_mut().unwrap();
 
                 match frame.if_else {
@@ -3029,6 +3677,32 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                         ));
                     }
                 }
+
+                let params = self.control_stack.last().unwrap().params.clone();
+                let ret = Location::GPR(self.machine.get_gpr_for_ret());
+                self.push_values_in(&params, ret)?;
+            }
+            Operator::TypedSelect { .. } | Operator::Select if self.is_v128_below(1) => {
+                let cond = self.pop_value_released()?;
+                let v_b = self.pop_v128_released()?;
+                let v_a = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+
+                let end_label = self.machine.get_label();
+                let zero_label = self.machine.get_label();
+
+                self.machine.jmp_on_condition(
+                    UnsignedCondition::Equal,
+                    Size::S32,
+                    Location::Imm32(0),
+                    cond,
+                    zero_label,
+                )?;
+                self.machine.move_v128(v_a, ret)?;
+                self.machine.jmp_unconditionnal(end_label)?;
+                self.machine.emit_label(zero_label)?;
+                self.machine.move_v128(v_b, ret)?;
+                self.machine.emit_label(end_label)?;
             }
             // `TypedSelect` must be used for extern refs so ref counting should
             // be done with TypedSelect. But otherwise they're the same.
@@ -3095,27 +3769,34 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 self.machine.emit_label(end_label)?;
             }
             Operator::Block { blockty } => {
+                let (params, returns) = self.block_signature(blockty);
+                // Block parameters stay in place, as part of the block's own values.
+                let value_stack_depth = self.value_stack.len() - params.len();
+                let fp_stack_depth = self
+                    .fp_stack
+                    .iter()
+                    .take_while(|fp| fp.depth < value_stack_depth)
+                    .count();
                 let frame = ControlFrame {
                     label: self.machine.get_label(),
                     loop_like: false,
                     if_else: IfElseState::None,
-                    returns: match blockty {
-                        WpTypeOrFuncType::Empty => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CompileError::Codegen(
-                                "Block: multi-value returns not yet implemented".to_owned(),
-                            ));
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
-                    fp_stack_depth: self.fp_stack.len(),
+                    params,
+                    returns,
+                    value_stack_depth,
+                    fp_stack_depth,
                     state: self.state.clone(),
                     state_diff_id: self.get_state_diff(),
                 };
                 self.control_stack.push(frame);
             }
             Operator::Loop { blockty } => {
+                let (params, returns) = self.block_signature(blockty);
+
+                // Loop parameters are passed to the loop header like branch values.
+                self.emit_values_out(&params, self.value_stack.len() - params.len())?;
+                self.pop_values_released(params.len())?;
+
                 self.machine.align_for_loop()?;
                 let label = self.machine.get_label();
                 let state_diff_id = self.get_state_diff();
@@ -3125,15 +3806,8 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     label,
                     loop_like: true,
                     if_else: IfElseState::None,
-                    returns: match blockty {
-                        WpTypeOrFuncType::Empty => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CompileError::Codegen(
-                                "Loop: multi-value returns not yet implemented".to_owned(),
-                            ));
-                        }
-                    },
+                    params: params.clone(),
+                    returns,
                     value_stack_depth: self.value_stack.len(),
                     fp_stack_depth: self.fp_stack.len(),
                     state: self.state.clone(),
@@ -3141,6 +3815,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 });
                 self.machine.emit_label(label)?;
 
+                let ret = Location::GPR(self.machine.get_gpr_for_ret());
+                self.push_values_in(&params, ret)?;
+
                 // TODO: Re-enable interrupt signal check without branching
             }
             Operator::Nop => {}
@@ -3995,67 +4672,15 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             }
             Operator::Unreachable => {
                 self.mark_trappable();
-                self.machine
-                    .emit_illegal_op(TrapCode::UnreachableCodeReached)?;
+                self.machine.emit_illegal_op(self.unreachable_trap_code)?;
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
-                let frame = &self.control_stack[0];
-                if !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CompileError::Codegen(
-                            "Return: incorrect frame.returns".to_owned(),
-                        ));
-                    }
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    let canonicalize = if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        self.machine.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                    } else {
-                        false
-                    };
-                    self.machine
-                        .emit_function_return_value(first_return, canonicalize, loc)?;
-                }
-                let frame = &self.control_stack[0];
-                let frame_depth = frame.value_stack_depth;
-                let label = frame.label;
-                self.release_locations_keep_state(frame_depth)?;
-                self.machine.jmp_unconditionnal(label)?;
+                self.emit_branch(self.control_stack.len() as u32 - 1)?;
                 self.unreachable_depth = 1;
             }
             Operator::Br { relative_depth } => {
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CompileError::Codegen(
-                            "Br: incorrect frame.returns".to_owned(),
-                        ));
-                    }
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    let canonicalize = if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        self.machine.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                    } else {
-                        false
-                    };
-                    self.machine
-                        .emit_function_return_value(first_return, canonicalize, loc)?;
-                }
-                let stack_len = self.control_stack.len();
-                let frame = &mut self.control_stack[stack_len - 1 - (relative_depth as usize)];
-                let frame_depth = frame.value_stack_depth;
-                let label = frame.label;
-
-                self.release_locations_keep_state(frame_depth)?;
-                self.machine.jmp_unconditionnal(label)?;
+                self.emit_branch(relative_depth)?;
                 self.unreachable_depth = 1;
             }
             Operator::BrIf { relative_depth } => {
@@ -4069,34 +4694,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     after,
                 )?;
 
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CompileError::Codegen(
-                            "BrIf: incorrect frame.returns".to_owned(),
-                        ));
-                    }
-
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    let canonicalize = if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        self.machine.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                    } else {
-                        false
-                    };
-                    self.machine
-                        .emit_function_return_value(first_return, canonicalize, loc)?;
-                }
-                let stack_len = self.control_stack.len();
-                let frame = &mut self.control_stack[stack_len - 1 - (relative_depth as usize)];
-                let stack_depth = frame.value_stack_depth;
-                let label = frame.label;
-                self.release_locations_keep_state(stack_depth)?;
-                self.machine.jmp_unconditionnal(label)?;
+                self.emit_branch(relative_depth)?;
 
                 self.machine.emit_label(after)?;
             }
@@ -4124,67 +4722,11 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     let label = self.machine.get_label();
                     self.machine.emit_label(label)?;
                     table.push(label);
-                    let frame =
-                        &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    if !frame.loop_like && !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CompileError::Codegen(format!(
-                                "BrTable: incorrect frame.returns for {target:?}",
-                            )));
-                        }
-
-                        let first_return = frame.returns[0];
-                        let loc = *self.value_stack.last().unwrap();
-                        let canonicalize = if first_return.is_float() {
-                            let fp = self.fp_stack.peek1()?;
-                            self.machine.arch_supports_canonicalize_nan()
-                                && self.config.enable_nan_canonicalization
-                                && fp.canonicalization.is_some()
-                        } else {
-                            false
-                        };
-                        self.machine
-                            .emit_function_return_value(first_return, canonicalize, loc)?;
-                    }
-                    let frame =
-                        &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    let stack_depth = frame.value_stack_depth;
-                    let label = frame.label;
-                    self.release_locations_keep_state(stack_depth)?;
-                    self.machine.jmp_unconditionnal(label)?;
+                    self.emit_branch(*target)?;
                 }
                 self.machine.emit_label(default_br)?;
 
-                {
-                    let frame = &self.control_stack
-                        [self.control_stack.len() - 1 - (default_target as usize)];
-                    if !frame.loop_like && !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CompileError::Codegen(
-                                "BrTable: incorrect frame.returns".to_owned(),
-                            ));
-                        }
-
-                        let first_return = frame.returns[0];
-                        let loc = *self.value_stack.last().unwrap();
-                        let canonicalize = if first_return.is_float() {
-                            let fp = self.fp_stack.peek1()?;
-                            self.machine.arch_supports_canonicalize_nan()
-                                && self.config.enable_nan_canonicalization
-                                && fp.canonicalization.is_some()
-                        } else {
-                            false
-                        };
-                        self.machine
-                            .emit_function_return_value(first_return, canonicalize, loc)?;
-                    }
-                    let frame = &self.control_stack
-                        [self.control_stack.len() - 1 - (default_target as usize)];
-                    let stack_depth = frame.value_stack_depth;
-                    let label = frame.label;
-                    self.release_locations_keep_state(stack_depth)?;
-                    self.machine.jmp_unconditionnal(label)?;
-                }
+                self.emit_branch(default_target)?;
 
                 self.machine.emit_label(table_label)?;
                 for x in table {
@@ -4192,6 +4734,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 }
                 self.unreachable_depth = 1;
             }
+            Operator::Drop if self.is_v128_below(0) => {
+                self.pop_v128_released()?;
+            }
             Operator::Drop => {
                 self.pop_value_released()?;
                 if let Some(x) = self.fp_stack.last() {
@@ -4204,21 +4749,19 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let frame = self.control_stack.pop().unwrap();
 
                 if !was_unreachable && !frame.returns.is_empty() {
-                    let loc = *self.value_stack.last().unwrap();
-                    let canonicalize = if frame.returns[0].is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        self.machine.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                    } else {
-                        false
-                    };
-                    self.machine
-                        .emit_function_return_value(frame.returns[0], canonicalize, loc)?;
+                    self.emit_values_out(
+                        &frame.returns,
+                        self.value_stack.len() - frame.returns.len(),
+                    )?;
                 }
 
                 if self.control_stack.is_empty() {
                     self.machine.emit_label(frame.label)?;
+
+                    if let Some(layout) = ResultsLayout::new(self.signature.results()) {
+                        self.emit_results_struct(&layout)?;
+                    }
+
                     self.finalize_locals(self.calling_convention)?;
                     self.machine.emit_function_epilog()?;
 
@@ -4235,6 +4778,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     self.release_locations(released)?;
                     self.value_stack.truncate(frame.value_stack_depth);
                     self.fp_stack.truncate(frame.fp_stack_depth);
+                    self.truncate_v128_stack(frame.value_stack_depth);
 
                     if !frame.loop_like {
                         self.machine.emit_label(frame.label)?;
@@ -4244,31 +4788,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                         self.machine.emit_label(label)?;
                     }
 
-                    if !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CompileError::Codegen(
-                                "End: incorrect frame.returns".to_owned(),
-                            ));
-                        }
-                        let loc = self.acquire_locations(
-                            &[(
-                                frame.returns[0],
-                                MachineValue::WasmStack(self.value_stack.len()),
-                            )],
-                            false,
-                        )?[0];
-                        self.machine.move_location(
-                            Size::S64,
-                            Location::GPR(self.machine.get_gpr_for_ret()),
-                            loc,
-                        )?;
-                        self.value_stack.push(loc);
-                        if frame.returns[0].is_float() {
-                            self.fp_stack
-                                .push(FloatValue::new(self.value_stack.len() - 1));
-                            // we already canonicalized at the `Br*` instruction or here previously.
-                        }
-                    }
+                    // The values were already canonicalized at the `Br*` instruction or here previously.
+                    let ret = Location::GPR(self.machine.get_gpr_for_ret());
+                    self.push_values_in(&frame.returns, ret)?;
                 }
             }
             Operator::AtomicFence => {
@@ -6631,6 +7153,382 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     ret,
                 )?;
             }
+            Operator::V128Const { value } => {
+                let ret = self.push_v128()?;
+                self.machine.v128_const(value.i128() as u128, ret)?;
+            }
+            Operator::V128Load { ref memarg }
+            | Operator::V128Load8x8S { ref memarg }
+            | Operator::V128Load8x8U { ref memarg }
+            | Operator::V128Load16x4S { ref memarg }
+            | Operator::V128Load16x4U { ref memarg }
+            | Operator::V128Load32x2S { ref memarg }
+            | Operator::V128Load32x2U { ref memarg }
+            | Operator::V128Load8Splat { ref memarg }
+            | Operator::V128Load16Splat { ref memarg }
+            | Operator::V128Load32Splat { ref memarg }
+            | Operator::V128Load64Splat { ref memarg }
+            | Operator::V128Load32Zero { ref memarg }
+            | Operator::V128Load64Zero { ref memarg } => {
+                let target = self.pop_value_released()?;
+                let ret = self.push_v128()?;
+                self.op_memory(
+                    |this,
+                     need_check,
+                     imported_memories,
+                     offset,
+                     heap_access_oob,
+                     unaligned_atomic| {
+                        this.machine.v128_load(
+                            &op,
+                            target,
+                            memarg,
+                            ret,
+                            need_check,
+                            imported_memories,
+                            offset,
+                            heap_access_oob,
+                            unaligned_atomic,
+                        )
+                    },
+                )?;
+            }
+            Operator::V128Load8Lane { ref memarg, .. }
+            | Operator::V128Load16Lane { ref memarg, .. }
+            | Operator::V128Load32Lane { ref memarg, .. }
+            | Operator::V128Load64Lane { ref memarg, .. } => {
+                let loc = self.pop_v128_released()?;
+                let target = self.pop_value_released()?;
+                let ret = self.push_v128()?;
+                self.op_memory(
+                    |this,
+                     need_check,
+                     imported_memories,
+                     offset,
+                     heap_access_oob,
+                     unaligned_atomic| {
+                        this.machine.v128_load_lane(
+                            &op,
+                            target,
+                            memarg,
+                            loc,
+                            ret,
+                            need_check,
+                            imported_memories,
+                            offset,
+                            heap_access_oob,
+                            unaligned_atomic,
+                        )
+                    },
+                )?;
+            }
+            Operator::V128Store { ref memarg }
+            | Operator::V128Store8Lane { ref memarg, .. }
+            | Operator::V128Store16Lane { ref memarg, .. }
+            | Operator::V128Store32Lane { ref memarg, .. }
+            | Operator::V128Store64Lane { ref memarg, .. } => {
+                let target_value = self.pop_v128_released()?;
+                let target_addr = self.pop_value_released()?;
+                self.op_memory(
+                    |this,
+                     need_check,
+                     imported_memories,
+                     offset,
+                     heap_access_oob,
+                     unaligned_atomic| {
+                        this.machine.v128_save(
+                            &op,
+                            target_value,
+                            memarg,
+                            target_addr,
+                            need_check,
+                            imported_memories,
+                            offset,
+                            heap_access_oob,
+                            unaligned_atomic,
+                        )
+                    },
+                )?;
+            }
+            Operator::V128Not
+            | Operator::I8x16Abs
+            | Operator::I8x16Neg
+            | Operator::I8x16Popcnt
+            | Operator::I16x8Abs
+            | Operator::I16x8Neg
+            | Operator::I32x4Abs
+            | Operator::I32x4Neg
+            | Operator::I64x2Abs
+            | Operator::I64x2Neg
+            | Operator::I16x8ExtAddPairwiseI8x16S
+            | Operator::I16x8ExtAddPairwiseI8x16U
+            | Operator::I32x4ExtAddPairwiseI16x8S
+            | Operator::I32x4ExtAddPairwiseI16x8U
+            | Operator::I16x8ExtendLowI8x16S
+            | Operator::I16x8ExtendHighI8x16S
+            | Operator::I16x8ExtendLowI8x16U
+            | Operator::I16x8ExtendHighI8x16U
+            | Operator::I32x4ExtendLowI16x8S
+            | Operator::I32x4ExtendHighI16x8S
+            | Operator::I32x4ExtendLowI16x8U
+            | Operator::I32x4ExtendHighI16x8U
+            | Operator::I64x2ExtendLowI32x4S
+            | Operator::I64x2ExtendHighI32x4S
+            | Operator::I64x2ExtendLowI32x4U
+            | Operator::I64x2ExtendHighI32x4U
+            | Operator::F32x4Ceil
+            | Operator::F32x4Floor
+            | Operator::F32x4Trunc
+            | Operator::F32x4Nearest
+            | Operator::F32x4Abs
+            | Operator::F32x4Neg
+            | Operator::F32x4Sqrt
+            | Operator::F64x2Ceil
+            | Operator::F64x2Floor
+            | Operator::F64x2Trunc
+            | Operator::F64x2Nearest
+            | Operator::F64x2Abs
+            | Operator::F64x2Neg
+            | Operator::F64x2Sqrt
+            | Operator::I32x4TruncSatF32x4S
+            | Operator::I32x4TruncSatF32x4U
+            | Operator::F32x4ConvertI32x4S
+            | Operator::F32x4ConvertI32x4U
+            | Operator::I32x4TruncSatF64x2SZero
+            | Operator::I32x4TruncSatF64x2UZero
+            | Operator::F64x2ConvertLowI32x4S
+            | Operator::F64x2ConvertLowI32x4U
+            | Operator::F32x4DemoteF64x2Zero
+            | Operator::F64x2PromoteLowF32x4 => {
+                let loc = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_unop(&op, loc, ret)?;
+            }
+            Operator::V128And
+            | Operator::V128AndNot
+            | Operator::V128Or
+            | Operator::V128Xor
+            | Operator::I8x16Shuffle { .. }
+            | Operator::I8x16Swizzle
+            | Operator::I8x16Eq
+            | Operator::I8x16Ne
+            | Operator::I8x16LtS
+            | Operator::I8x16LtU
+            | Operator::I8x16GtS
+            | Operator::I8x16GtU
+            | Operator::I8x16LeS
+            | Operator::I8x16LeU
+            | Operator::I8x16GeS
+            | Operator::I8x16GeU
+            | Operator::I16x8Eq
+            | Operator::I16x8Ne
+            | Operator::I16x8LtS
+            | Operator::I16x8LtU
+            | Operator::I16x8GtS
+            | Operator::I16x8GtU
+            | Operator::I16x8LeS
+            | Operator::I16x8LeU
+            | Operator::I16x8GeS
+            | Operator::I16x8GeU
+            | Operator::I32x4Eq
+            | Operator::I32x4Ne
+            | Operator::I32x4LtS
+            | Operator::I32x4LtU
+            | Operator::I32x4GtS
+            | Operator::I32x4GtU
+            | Operator::I32x4LeS
+            | Operator::I32x4LeU
+            | Operator::I32x4GeS
+            | Operator::I32x4GeU
+            | Operator::I64x2Eq
+            | Operator::I64x2Ne
+            | Operator::I64x2LtS
+            | Operator::I64x2GtS
+            | Operator::I64x2LeS
+            | Operator::I64x2GeS
+            | Operator::F32x4Eq
+            | Operator::F32x4Ne
+            | Operator::F32x4Lt
+            | Operator::F32x4Gt
+            | Operator::F32x4Le
+            | Operator::F32x4Ge
+            | Operator::F64x2Eq
+            | Operator::F64x2Ne
+            | Operator::F64x2Lt
+            | Operator::F64x2Gt
+            | Operator::F64x2Le
+            | Operator::F64x2Ge
+            | Operator::I8x16NarrowI16x8S
+            | Operator::I8x16NarrowI16x8U
+            | Operator::I8x16Add
+            | Operator::I8x16AddSatS
+            | Operator::I8x16AddSatU
+            | Operator::I8x16Sub
+            | Operator::I8x16SubSatS
+            | Operator::I8x16SubSatU
+            | Operator::I8x16MinS
+            | Operator::I8x16MinU
+            | Operator::I8x16MaxS
+            | Operator::I8x16MaxU
+            | Operator::I8x16AvgrU
+            | Operator::I16x8Q15MulrSatS
+            | Operator::I16x8NarrowI32x4S
+            | Operator::I16x8NarrowI32x4U
+            | Operator::I16x8Add
+            | Operator::I16x8AddSatS
+            | Operator::I16x8AddSatU
+            | Operator::I16x8Sub
+            | Operator::I16x8SubSatS
+            | Operator::I16x8SubSatU
+            | Operator::I16x8Mul
+            | Operator::I16x8MinS
+            | Operator::I16x8MinU
+            | Operator::I16x8MaxS
+            | Operator::I16x8MaxU
+            | Operator::I16x8AvgrU
+            | Operator::I16x8ExtMulLowI8x16S
+            | Operator::I16x8ExtMulHighI8x16S
+            | Operator::I16x8ExtMulLowI8x16U
+            | Operator::I16x8ExtMulHighI8x16U
+            | Operator::I32x4Add
+            | Operator::I32x4Sub
+            | Operator::I32x4Mul
+            | Operator::I32x4MinS
+            | Operator::I32x4MinU
+            | Operator::I32x4MaxS
+            | Operator::I32x4MaxU
+            | Operator::I32x4DotI16x8S
+            | Operator::I32x4ExtMulLowI16x8S
+            | Operator::I32x4ExtMulHighI16x8S
+            | Operator::I32x4ExtMulLowI16x8U
+            | Operator::I32x4ExtMulHighI16x8U
+            | Operator::I64x2Add
+            | Operator::I64x2Sub
+            | Operator::I64x2Mul
+            | Operator::I64x2ExtMulLowI32x4S
+            | Operator::I64x2ExtMulHighI32x4S
+            | Operator::I64x2ExtMulLowI32x4U
+            | Operator::I64x2ExtMulHighI32x4U
+            | Operator::F32x4Add
+            | Operator::F32x4Sub
+            | Operator::F32x4Mul
+            | Operator::F32x4Div
+            | Operator::F32x4Min
+            | Operator::F32x4Max
+            | Operator::F32x4PMin
+            | Operator::F32x4PMax
+            | Operator::F64x2Add
+            | Operator::F64x2Sub
+            | Operator::F64x2Mul
+            | Operator::F64x2Div
+            | Operator::F64x2Min
+            | Operator::F64x2Max
+            | Operator::F64x2PMin
+            | Operator::F64x2PMax => {
+                let loc_b = self.pop_v128_released()?;
+                let loc_a = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_binop(&op, loc_a, loc_b, ret)?;
+            }
+            Operator::V128Bitselect => {
+                let mask = self.pop_v128_released()?;
+                let loc_b = self.pop_v128_released()?;
+                let loc_a = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_bitselect(loc_a, loc_b, mask, ret)?;
+            }
+            Operator::I8x16Shl
+            | Operator::I8x16ShrS
+            | Operator::I8x16ShrU
+            | Operator::I16x8Shl
+            | Operator::I16x8ShrS
+            | Operator::I16x8ShrU
+            | Operator::I32x4Shl
+            | Operator::I32x4ShrS
+            | Operator::I32x4ShrU
+            | Operator::I64x2Shl
+            | Operator::I64x2ShrS
+            | Operator::I64x2ShrU => {
+                let count = self.pop_value_released()?;
+                let loc = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_shift(&op, loc, count, ret)?;
+            }
+            Operator::V128AnyTrue
+            | Operator::I8x16AllTrue
+            | Operator::I8x16Bitmask
+            | Operator::I16x8AllTrue
+            | Operator::I16x8Bitmask
+            | Operator::I32x4AllTrue
+            | Operator::I32x4Bitmask
+            | Operator::I64x2AllTrue
+            | Operator::I64x2Bitmask => {
+                let loc = self.pop_v128_released()?;
+                let ret = self.acquire_locations(
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )?[0];
+                self.value_stack.push(ret);
+                self.machine.v128_test(&op, loc, ret)?;
+            }
+            Operator::I8x16Splat
+            | Operator::I16x8Splat
+            | Operator::I32x4Splat
+            | Operator::I64x2Splat => {
+                let loc = self.pop_value_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_splat(&op, loc, ret)?;
+            }
+            Operator::F32x4Splat | Operator::F64x2Splat => {
+                self.canonicalize_float_operand()?;
+                let loc = self.pop_value_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_splat(&op, loc, ret)?;
+            }
+            Operator::I8x16ExtractLaneS { .. }
+            | Operator::I8x16ExtractLaneU { .. }
+            | Operator::I16x8ExtractLaneS { .. }
+            | Operator::I16x8ExtractLaneU { .. }
+            | Operator::I32x4ExtractLane { .. }
+            | Operator::I64x2ExtractLane { .. }
+            | Operator::F32x4ExtractLane { .. }
+            | Operator::F64x2ExtractLane { .. } => {
+                let ty = match op {
+                    Operator::I64x2ExtractLane { .. } => WpType::I64,
+                    Operator::F32x4ExtractLane { .. } => WpType::F32,
+                    Operator::F64x2ExtractLane { .. } => WpType::F64,
+                    _ => WpType::I32,
+                };
+                let loc = self.pop_v128_released()?;
+                let ret = self.acquire_locations(
+                    &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )?[0];
+                self.value_stack.push(ret);
+                if ty.is_float() {
+                    self.fp_stack
+                        .push(FloatValue::new(self.value_stack.len() - 1));
+                }
+                self.machine.v128_extract_lane(&op, loc, ret)?;
+            }
+            Operator::I8x16ReplaceLane { .. }
+            | Operator::I16x8ReplaceLane { .. }
+            | Operator::I32x4ReplaceLane { .. }
+            | Operator::I64x2ReplaceLane { .. }
+            | Operator::F32x4ReplaceLane { .. }
+            | Operator::F64x2ReplaceLane { .. } => {
+                if matches!(
+                    op,
+                    Operator::F32x4ReplaceLane { .. } | Operator::F64x2ReplaceLane { .. }
+                ) {
+                    self.canonicalize_float_operand()?;
+                }
+                let value = self.pop_value_released()?;
+                let loc = self.pop_v128_released()?;
+                let ret = self.push_v128()?;
+                self.machine.v128_replace_lane(&op, loc, value, ret)?;
+            }
+
             _ => {
                 return Err(CompileError::Codegen(format!(
                     "not yet implemented: {op:?}"
@@ -184,6 +184,7 @@ impl Compiler for SinglepassCompiler {
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
+                            generator.set_unreachable_trap_code(reader.trap_code());
                             generator.feed_operator(op)?;
                         }
 
@@ -205,6 +206,7 @@ impl Compiler for SinglepassCompiler {
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
+                            generator.set_unreachable_trap_code(reader.trap_code());
                             generator.feed_operator(op)?;
                         }
 
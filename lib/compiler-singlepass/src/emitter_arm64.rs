@@ -1,7 +1,7 @@
 pub use crate::{
     arm64_decl::{ARM64Register, ArgumentRegisterAllocator, GPR, NEON},
     location::{Multiplier, Reg},
-    machine::{Label, Offset},
+    machine::{Label, Offset, ResultsLayout, ResultsRegisters},
 };
 use crate::{codegen_error, common_decl::Size, location::Location as AbstractLocation};
 use dynasm::dynasm;
@@ -485,6 +485,57 @@ pub trait EmitterARM64 {
     ) -> Result<(), CompileError>;
     fn emit_cnt(&mut self, src: NEON, dst: NEON) -> Result<(), CompileError>;
     fn emit_addv(&mut self, src: NEON, dst: NEON) -> Result<(), CompileError>;
+
+    // Vector (`v128`) instructions, operating on whole NEON registers. Memory is
+    // accessed with the `Q` forms of `ldr` and `str`.
+    fn emit_ldrq(&mut self, reg: NEON, addr: Location) -> Result<(), CompileError>;
+    fn emit_strq(&mut self, reg: NEON, addr: Location) -> Result<(), CompileError>;
+    fn emit_ldurq(&mut self, reg: NEON, addr: GPR, offset: i32) -> Result<(), CompileError>;
+    fn emit_sturq(&mut self, reg: NEON, addr: GPR, offset: i32) -> Result<(), CompileError>;
+    fn emit_neon(
+        &mut self,
+        op: NeonOp,
+        src1: NEON,
+        src2: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError>;
+    fn emit_neon_unop(&mut self, op: NeonUnop, src: NEON, dst: NEON) -> Result<(), CompileError>;
+    fn emit_neon_shift(
+        &mut self,
+        op: NeonShift,
+        src: NEON,
+        imm: u32,
+        dst: NEON,
+    ) -> Result<(), CompileError>;
+    fn emit_neon_reduce(
+        &mut self,
+        op: NeonReduce,
+        src: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError>;
+    /// `tbl`, or `tbx` to leave the lanes with out of range indices unchanged.
+    fn emit_tbl(
+        &mut self,
+        keep: bool,
+        table: NEON,
+        indices: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError>;
+    /// `ext`, extracting 16 bytes from the concatenation of `src2` and `src1`, from byte `imm`.
+    fn emit_ext(&mut self, src1: NEON, src2: NEON, imm: u32, dst: NEON)
+    -> Result<(), CompileError>;
+    fn emit_dup(&mut self, sz: Size, src: GPR, dst: NEON) -> Result<(), CompileError>;
+    fn emit_ins(&mut self, sz: Size, src: GPR, lane: u8, dst: NEON) -> Result<(), CompileError>;
+    /// `smov` for signed lanes, `umov` otherwise.
+    fn emit_vmov_lane(
+        &mut self,
+        sz: Size,
+        signed: bool,
+        src: NEON,
+        lane: u8,
+        dst: GPR,
+    ) -> Result<(), CompileError>;
+
     fn emit_read_fpcr(&mut self, reg: GPR) -> Result<(), CompileError>;
     fn emit_write_fpcr(&mut self, reg: GPR) -> Result<(), CompileError>;
     fn emit_read_fpsr(&mut self, reg: GPR) -> Result<(), CompileError>;
@@ -506,6 +557,256 @@ pub trait EmitterARM64 {
     }
 }
 
+/// Defines an enum of NEON vector instructions, and how to emit them.
+///
+/// Each instruction is given with its mnemonic and the arrangements of its operands,
+/// destination first.
+macro_rules! neon_ops {
+    ($(#[$attr:meta])* enum $name:ident { $($op:ident => $mnemonic:ident $dst:ident, $src1:ident, $src2:ident;)* }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $($op,)*
+        }
+
+        impl $name {
+            fn emit(self, a: &mut Assembler, dst: NEON, src1: NEON, src2: NEON) {
+                match self {
+                    $($name::$op => dynasm!(a ; $mnemonic V(dst).$dst, V(src1).$src1, V(src2).$src2),)*
+                }
+            }
+        }
+    };
+}
+
+/// Like `neon_ops`, for instructions with a single operand.
+macro_rules! neon_unops {
+    ($(#[$attr:meta])* enum $name:ident { $($op:ident => $mnemonic:ident $dst:ident, $src:ident;)* }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $($op,)*
+        }
+
+        impl $name {
+            fn emit(self, a: &mut Assembler, dst: NEON, src: NEON) {
+                match self {
+                    $($name::$op => dynasm!(a ; $mnemonic V(dst).$dst, V(src).$src),)*
+                }
+            }
+        }
+    };
+}
+
+neon_ops! {
+    /// Vector instructions computing `dst = src1 op src2`.
+    enum NeonOp {
+        AddB => add B16, B16, B16;
+        AddH => add H8, H8, H8;
+        AddS => add S4, S4, S4;
+        AddD => add D2, D2, D2;
+        SubB => sub B16, B16, B16;
+        SubH => sub H8, H8, H8;
+        SubS => sub S4, S4, S4;
+        SubD => sub D2, D2, D2;
+        MulH => mul H8, H8, H8;
+        MulS => mul S4, S4, S4;
+        SqaddB => sqadd B16, B16, B16;
+        SqaddH => sqadd H8, H8, H8;
+        UqaddB => uqadd B16, B16, B16;
+        UqaddH => uqadd H8, H8, H8;
+        SqsubB => sqsub B16, B16, B16;
+        SqsubH => sqsub H8, H8, H8;
+        UqsubB => uqsub B16, B16, B16;
+        UqsubH => uqsub H8, H8, H8;
+        SminB => smin B16, B16, B16;
+        SminH => smin H8, H8, H8;
+        SminS => smin S4, S4, S4;
+        UminB => umin B16, B16, B16;
+        UminH => umin H8, H8, H8;
+        UminS => umin S4, S4, S4;
+        SmaxB => smax B16, B16, B16;
+        SmaxH => smax H8, H8, H8;
+        SmaxS => smax S4, S4, S4;
+        UmaxB => umax B16, B16, B16;
+        UmaxH => umax H8, H8, H8;
+        UmaxS => umax S4, S4, S4;
+        UrhaddB => urhadd B16, B16, B16;
+        UrhaddH => urhadd H8, H8, H8;
+        SqrdmulhH => sqrdmulh H8, H8, H8;
+        AddpS => addp S4, S4, S4;
+        CmeqB => cmeq B16, B16, B16;
+        CmeqH => cmeq H8, H8, H8;
+        CmeqS => cmeq S4, S4, S4;
+        CmeqD => cmeq D2, D2, D2;
+        CmgtB => cmgt B16, B16, B16;
+        CmgtH => cmgt H8, H8, H8;
+        CmgtS => cmgt S4, S4, S4;
+        CmgtD => cmgt D2, D2, D2;
+        CmgeB => cmge B16, B16, B16;
+        CmgeH => cmge H8, H8, H8;
+        CmgeS => cmge S4, S4, S4;
+        CmgeD => cmge D2, D2, D2;
+        CmhiB => cmhi B16, B16, B16;
+        CmhiH => cmhi H8, H8, H8;
+        CmhiS => cmhi S4, S4, S4;
+        CmhsB => cmhs B16, B16, B16;
+        CmhsH => cmhs H8, H8, H8;
+        CmhsS => cmhs S4, S4, S4;
+        SshlB => sshl B16, B16, B16;
+        SshlH => sshl H8, H8, H8;
+        SshlS => sshl S4, S4, S4;
+        SshlD => sshl D2, D2, D2;
+        UshlB => ushl B16, B16, B16;
+        UshlH => ushl H8, H8, H8;
+        UshlS => ushl S4, S4, S4;
+        UshlD => ushl D2, D2, D2;
+        And => and B16, B16, B16;
+        Orr => orr B16, B16, B16;
+        Eor => eor B16, B16, B16;
+        Bic => bic B16, B16, B16;
+        // Reads `dst` as well: `dst = (dst & src1) | (!dst & src2)`.
+        Bsl => bsl B16, B16, B16;
+        Zip1B => zip1 B16, B16, B16;
+        FaddS => fadd S4, S4, S4;
+        FaddD => fadd D2, D2, D2;
+        FsubS => fsub S4, S4, S4;
+        FsubD => fsub D2, D2, D2;
+        FmulS => fmul S4, S4, S4;
+        FmulD => fmul D2, D2, D2;
+        FdivS => fdiv S4, S4, S4;
+        FdivD => fdiv D2, D2, D2;
+        FminS => fmin S4, S4, S4;
+        FminD => fmin D2, D2, D2;
+        FmaxS => fmax S4, S4, S4;
+        FmaxD => fmax D2, D2, D2;
+        FcmeqS => fcmeq S4, S4, S4;
+        FcmeqD => fcmeq D2, D2, D2;
+        FcmgtS => fcmgt S4, S4, S4;
+        FcmgtD => fcmgt D2, D2, D2;
+        FcmgeS => fcmge S4, S4, S4;
+        FcmgeD => fcmge D2, D2, D2;
+        // Widening multiplications of the low halves, or of the high ones for the `2` forms.
+        SmullB => smull H8, B8, B8;
+        Smull2B => smull2 H8, B16, B16;
+        SmullH => smull S4, H4, H4;
+        Smull2H => smull2 S4, H8, H8;
+        SmullS => smull D2, S2, S2;
+        Smull2S => smull2 D2, S4, S4;
+        UmullB => umull H8, B8, B8;
+        Umull2B => umull2 H8, B16, B16;
+        UmullH => umull S4, H4, H4;
+        Umull2H => umull2 S4, H8, H8;
+        UmullS => umull D2, S2, S2;
+        Umull2S => umull2 D2, S4, S4;
+        // Reads `dst` as well: `dst += src1 * src2`.
+        UmlalS => umlal D2, S2, S2;
+    }
+}
+
+neon_unops! {
+    /// Vector instructions computing `dst = op src`.
+    enum NeonUnop {
+        Mov => mov B16, B16;
+        Not => not B16, B16;
+        CntB => cnt B16, B16;
+        AbsB => abs B16, B16;
+        AbsH => abs H8, H8;
+        AbsS => abs S4, S4;
+        AbsD => abs D2, D2;
+        NegB => neg B16, B16;
+        NegH => neg H8, H8;
+        NegS => neg S4, S4;
+        NegD => neg D2, D2;
+        Rev64S => rev64 S4, S4;
+        FabsS => fabs S4, S4;
+        FabsD => fabs D2, D2;
+        FnegS => fneg S4, S4;
+        FnegD => fneg D2, D2;
+        FsqrtS => fsqrt S4, S4;
+        FsqrtD => fsqrt D2, D2;
+        FrintnS => frintn S4, S4;
+        FrintnD => frintn D2, D2;
+        FrintmS => frintm S4, S4;
+        FrintmD => frintm D2, D2;
+        FrintpS => frintp S4, S4;
+        FrintpD => frintp D2, D2;
+        FrintzS => frintz S4, S4;
+        FrintzD => frintz D2, D2;
+        ScvtfS => scvtf S4, S4;
+        ScvtfD => scvtf D2, D2;
+        UcvtfS => ucvtf S4, S4;
+        UcvtfD => ucvtf D2, D2;
+        // The float to integer conversions saturate, and convert NaNs to 0.
+        FcvtzsS => fcvtzs S4, S4;
+        FcvtzsD => fcvtzs D2, D2;
+        FcvtzuS => fcvtzu S4, S4;
+        FcvtzuD => fcvtzu D2, D2;
+        Fcvtl => fcvtl D2, S2;
+        Fcvtn => fcvtn S2, D2;
+        // Widening of the low halves, or of the high ones for the `2` forms.
+        SxtlB => sxtl H8, B8;
+        Sxtl2B => sxtl2 H8, B16;
+        SxtlH => sxtl S4, H4;
+        Sxtl2H => sxtl2 S4, H8;
+        SxtlS => sxtl D2, S2;
+        Sxtl2S => sxtl2 D2, S4;
+        UxtlB => uxtl H8, B8;
+        Uxtl2B => uxtl2 H8, B16;
+        UxtlH => uxtl S4, H4;
+        Uxtl2H => uxtl2 S4, H8;
+        UxtlS => uxtl D2, S2;
+        Uxtl2S => uxtl2 D2, S4;
+        SaddlpB => saddlp H8, B16;
+        SaddlpH => saddlp S4, H8;
+        UaddlpB => uaddlp H8, B16;
+        UaddlpH => uaddlp S4, H8;
+        UaddlpS => uaddlp D2, S4;
+        // Narrowing to the low half, zeroing the high one, or to the high half, keeping
+        // the low one, for the `2` forms.
+        XtnD => xtn S2, D2;
+        SqxtnH => sqxtn B8, H8;
+        Sqxtn2H => sqxtn2 B16, H8;
+        SqxtnS => sqxtn H4, S4;
+        Sqxtn2S => sqxtn2 H8, S4;
+        SqxtnD => sqxtn S2, D2;
+        SqxtunH => sqxtun B8, H8;
+        Sqxtun2H => sqxtun2 B16, H8;
+        SqxtunS => sqxtun H4, S4;
+        Sqxtun2S => sqxtun2 H8, S4;
+        UqxtnD => uqxtn S2, D2;
+    }
+}
+
+/// Vector shifts by an immediate count, computing `dst = src op imm`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NeonShift {
+    ShlB,
+    ShlH,
+    ShlS,
+    ShlD,
+    SshrB,
+    SshrH,
+    SshrS,
+    SshrD,
+    UshrB,
+    UshrH,
+    UshrS,
+    UshrD,
+}
+
+/// Reductions of the lanes of a vector to a scalar, in the low lane of `dst`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NeonReduce {
+    UminvB,
+    UminvH,
+    UminvS,
+    UmaxvS,
+    AddvH,
+    AddvS,
+    AddpD,
+}
+
 impl EmitterARM64 for Assembler {
     fn get_label(&mut self) -> DynamicLabel {
         self.new_dynamic_label()
@@ -2782,6 +3083,163 @@ impl EmitterARM64 for Assembler {
         Ok(())
     }
 
+    fn emit_ldrq(&mut self, reg: NEON, addr: Location) -> Result<(), CompileError> {
+        match addr {
+            Location::Memory(addr, disp) => {
+                let disp = disp as u32;
+                assert!((disp & 0xf) == 0 && (disp < 0x10000));
+                dynasm!(self ; ldr Q(reg), [X(addr), disp]);
+            }
+            Location::Memory2(addr, r2, Multiplier::One, 0) => {
+                dynasm!(self ; ldr Q(reg), [X(addr), X(r2)]);
+            }
+            _ => codegen_error!("singlepass can't emit LDR Q{:?}, {:?}", reg, addr),
+        }
+        Ok(())
+    }
+    fn emit_strq(&mut self, reg: NEON, addr: Location) -> Result<(), CompileError> {
+        match addr {
+            Location::Memory(addr, disp) => {
+                let disp = disp as u32;
+                assert!((disp & 0xf) == 0 && (disp < 0x10000));
+                dynasm!(self ; str Q(reg), [X(addr), disp]);
+            }
+            Location::Memory2(addr, r2, Multiplier::One, 0) => {
+                dynasm!(self ; str Q(reg), [X(addr), X(r2)]);
+            }
+            _ => codegen_error!("singlepass can't emit STR Q{:?}, {:?}", reg, addr),
+        }
+        Ok(())
+    }
+    fn emit_ldurq(&mut self, reg: NEON, addr: GPR, offset: i32) -> Result<(), CompileError> {
+        assert!((-255..=255).contains(&offset));
+        dynasm!(self ; ldur Q(reg), [X(addr), offset]);
+        Ok(())
+    }
+    fn emit_sturq(&mut self, reg: NEON, addr: GPR, offset: i32) -> Result<(), CompileError> {
+        assert!((-255..=255).contains(&offset));
+        dynasm!(self ; stur Q(reg), [X(addr), offset]);
+        Ok(())
+    }
+
+    fn emit_neon(
+        &mut self,
+        op: NeonOp,
+        src1: NEON,
+        src2: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError> {
+        op.emit(self, dst, src1, src2);
+        Ok(())
+    }
+    fn emit_neon_unop(&mut self, op: NeonUnop, src: NEON, dst: NEON) -> Result<(), CompileError> {
+        op.emit(self, dst, src);
+        Ok(())
+    }
+    fn emit_neon_shift(
+        &mut self,
+        op: NeonShift,
+        src: NEON,
+        imm: u32,
+        dst: NEON,
+    ) -> Result<(), CompileError> {
+        match op {
+            NeonShift::ShlB => dynasm!(self ; shl V(dst).B16, V(src).B16, imm),
+            NeonShift::ShlH => dynasm!(self ; shl V(dst).H8, V(src).H8, imm),
+            NeonShift::ShlS => dynasm!(self ; shl V(dst).S4, V(src).S4, imm),
+            NeonShift::ShlD => dynasm!(self ; shl V(dst).D2, V(src).D2, imm),
+            NeonShift::SshrB => dynasm!(self ; sshr V(dst).B16, V(src).B16, imm),
+            NeonShift::SshrH => dynasm!(self ; sshr V(dst).H8, V(src).H8, imm),
+            NeonShift::SshrS => dynasm!(self ; sshr V(dst).S4, V(src).S4, imm),
+            NeonShift::SshrD => dynasm!(self ; sshr V(dst).D2, V(src).D2, imm),
+            NeonShift::UshrB => dynasm!(self ; ushr V(dst).B16, V(src).B16, imm),
+            NeonShift::UshrH => dynasm!(self ; ushr V(dst).H8, V(src).H8, imm),
+            NeonShift::UshrS => dynasm!(self ; ushr V(dst).S4, V(src).S4, imm),
+            NeonShift::UshrD => dynasm!(self ; ushr V(dst).D2, V(src).D2, imm),
+        }
+        Ok(())
+    }
+    fn emit_neon_reduce(
+        &mut self,
+        op: NeonReduce,
+        src: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError> {
+        match op {
+            NeonReduce::UminvB => dynasm!(self ; uminv B(dst), V(src).B16),
+            NeonReduce::UminvH => dynasm!(self ; uminv H(dst), V(src).H8),
+            NeonReduce::UminvS => dynasm!(self ; uminv S(dst), V(src).S4),
+            NeonReduce::UmaxvS => dynasm!(self ; umaxv S(dst), V(src).S4),
+            NeonReduce::AddvH => dynasm!(self ; addv H(dst), V(src).H8),
+            NeonReduce::AddvS => dynasm!(self ; addv S(dst), V(src).S4),
+            NeonReduce::AddpD => dynasm!(self ; addp D(dst), V(src).D2),
+        }
+        Ok(())
+    }
+    fn emit_tbl(
+        &mut self,
+        keep: bool,
+        table: NEON,
+        indices: NEON,
+        dst: NEON,
+    ) -> Result<(), CompileError> {
+        if keep {
+            dynasm!(self ; tbx V(dst).B16, {V(table).B16 * 1}, V(indices).B16);
+        } else {
+            dynasm!(self ; tbl V(dst).B16, {V(table).B16 * 1}, V(indices).B16);
+        }
+        Ok(())
+    }
+    fn emit_ext(
+        &mut self,
+        src1: NEON,
+        src2: NEON,
+        imm: u32,
+        dst: NEON,
+    ) -> Result<(), CompileError> {
+        dynasm!(self ; ext V(dst).B16, V(src1).B16, V(src2).B16, imm);
+        Ok(())
+    }
+    fn emit_dup(&mut self, sz: Size, src: GPR, dst: NEON) -> Result<(), CompileError> {
+        match sz {
+            Size::S8 => dynasm!(self ; dup V(dst).B16, W(src)),
+            Size::S16 => dynasm!(self ; dup V(dst).H8, W(src)),
+            Size::S32 => dynasm!(self ; dup V(dst).S4, W(src)),
+            Size::S64 => dynasm!(self ; dup V(dst).D2, X(src)),
+        }
+        Ok(())
+    }
+    fn emit_ins(&mut self, sz: Size, src: GPR, lane: u8, dst: NEON) -> Result<(), CompileError> {
+        let lane = lane as u32;
+        match sz {
+            Size::S8 => dynasm!(self ; ins V(dst).B[lane], W(src)),
+            Size::S16 => dynasm!(self ; ins V(dst).H[lane], W(src)),
+            Size::S32 => dynasm!(self ; ins V(dst).S[lane], W(src)),
+            Size::S64 => dynasm!(self ; ins V(dst).D[lane], X(src)),
+        }
+        Ok(())
+    }
+    fn emit_vmov_lane(
+        &mut self,
+        sz: Size,
+        signed: bool,
+        src: NEON,
+        lane: u8,
+        dst: GPR,
+    ) -> Result<(), CompileError> {
+        let lane = lane as u32;
+        match (sz, signed) {
+            (Size::S8, false) => dynasm!(self ; umov W(dst), V(src).B[lane]),
+            (Size::S16, false) => dynasm!(self ; umov W(dst), V(src).H[lane]),
+            (Size::S32, false) => dynasm!(self ; umov W(dst), V(src).S[lane]),
+            (Size::S64, false) => dynasm!(self ; umov X(dst), V(src).D[lane]),
+            (Size::S8, true) => dynasm!(self ; smov W(dst), V(src).B[lane]),
+            (Size::S16, true) => dynasm!(self ; smov W(dst), V(src).H[lane]),
+            _ => codegen_error!("singlepass can't emit SMOV {:?}", sz),
+        }
+        Ok(())
+    }
+
     #[allow(clippy::unit_arg)]
     fn emit_fmov(
         &mut self,
@@ -2831,6 +3289,42 @@ impl EmitterARM64 for Assembler {
     }
 }
 
+/// Registers holding results returned like a struct following `layout`, each with the
+/// offset and size of the part of the struct it holds, or `None` if the struct is
+/// returned through the memory pointed to by X8.
+pub fn results_registers_arm64(
+    tys: &[Type],
+    layout: &ResultsLayout,
+) -> Option<ResultsRegisters<GPR, NEON>> {
+    // Homogeneous aggregates of up to four floats are returned in one register per field.
+    let f32s = tys.iter().all(|ty| *ty == Type::F32);
+    if (f32s || tys.iter().all(|ty| *ty == Type::F64)) && tys.len() <= 4 {
+        let sz = if f32s { Size::S32 } else { Size::S64 };
+        let parts = layout
+            .offsets
+            .iter()
+            .enumerate()
+            .map(|(i, offset)| (Location::SIMD(NEON::from_index(i).unwrap()), *offset, sz))
+            .collect();
+        return Some(parts);
+    }
+    // Other structs of up to 16 bytes are returned in X0 and X1.
+    if layout.size > 16 {
+        return None;
+    }
+    let parts = (0..layout.size)
+        .step_by(8)
+        .map(|offset| {
+            (
+                Location::GPR(GPR::from_index(offset / 8).unwrap()),
+                offset,
+                Size::S64,
+            )
+        })
+        .collect();
+    Some(parts)
+}
+
 pub fn gen_std_trampoline_arm64(
     sig: &FunctionType,
     calling_convention: CallingConvention,
@@ -2849,89 +3343,140 @@ pub fn gen_std_trampoline_arm64(
         ; mov X(args), x2
     );
 
-    let stack_args = sig.params().len().saturating_sub(7); //1st arg is ctx, not an actual arg
-    let mut stack_offset = stack_args as u32 * 8;
-    if stack_args > 0 {
-        if stack_offset % 16 != 0 {
-            stack_offset += 8;
-            assert!(stack_offset % 16 == 0);
-        }
-        dynasm!(a ; sub sp, sp, stack_offset);
+    let results_layout = ResultsLayout::new(sig.results());
+    let results_registers = results_layout
+        .as_ref()
+        .and_then(|layout| results_registers_arm64(sig.results(), layout));
+
+    // Area receiving the struct of results.
+    let return_area_size = results_layout
+        .as_ref()
+        .map_or(0, |layout| layout.size.next_multiple_of(16) as u32);
+    if return_area_size > 0 {
+        dynasm!(a ; sub sp, sp, return_area_size);
     }
 
-    // Move arguments to their locations.
-    // `callee_vmctx` is already in the first argument register, so no need to move.
+    // Calculate the locations of the arguments: the scalars go to X1-X7 (1st arg is ctx,
+    // not an actual arg), the `v128` ones to V0-V7, and the others to the stack.
+    let (mut n_scalars, mut n_simds) = (0, 0);
     let mut caller_stack_offset: i32 = 0;
-    for (i, param) in sig.params().iter().enumerate() {
+    let mut param_locations = Vec::with_capacity(sig.params().len());
+    for param in sig.params() {
         let sz = match *param {
             Type::I32 | Type::F32 => Size::S32,
             Type::I64 | Type::F64 => Size::S64,
             Type::ExternRef => Size::S64,
             Type::FuncRef => Size::S64,
+            Type::V128 => Size::S64,
             _ => codegen_error!(
                 "singlepass unsupported param type for trampoline {:?}",
                 *param
             ),
         };
-        match i {
-            0..=6 => {
-                a.emit_ldr(
-                    sz,
-                    Location::GPR(GPR::from_index(i + 1).unwrap()),
-                    Location::Memory(args, (i * 16) as i32),
-                )?;
+        let loc = if *param == Type::V128 {
+            n_simds += 1;
+            if n_simds <= 8 {
+                Location::SIMD(NEON::from_index(n_simds - 1).unwrap())
+            } else {
+                caller_stack_offset = (caller_stack_offset + 15) & !15;
+                let loc = Location::Memory(GPR::XzrSp, caller_stack_offset);
+                caller_stack_offset += 16;
+                loc
             }
-            _ => {
-                #[allow(clippy::single_match)]
-                match calling_convention {
-                    CallingConvention::AppleAarch64 => {
-                        let sz = 1
-                            << match sz {
-                                Size::S8 => 0,
-                                Size::S16 => 1,
-                                Size::S32 => 2,
-                                Size::S64 => 3,
-                            };
-                        // align first
-                        if sz > 1 && caller_stack_offset & (sz - 1) != 0 {
-                            caller_stack_offset = (caller_stack_offset + (sz - 1)) & !(sz - 1);
-                        }
-                    }
-                    _ => (),
+        } else {
+            n_scalars += 1;
+            if n_scalars <= 7 {
+                Location::GPR(GPR::from_index(n_scalars).unwrap())
+            } else {
+                let sz = match calling_convention {
+                    CallingConvention::AppleAarch64 => match sz {
+                        Size::S32 => 4,
+                        _ => 8,
+                    },
+                    _ => 8,
                 };
+                // align first
+                if caller_stack_offset & (sz - 1) != 0 {
+                    caller_stack_offset = (caller_stack_offset + (sz - 1)) & !(sz - 1);
+                }
+                let loc = Location::Memory(GPR::XzrSp, caller_stack_offset);
+                caller_stack_offset += sz;
+                loc
+            }
+        };
+        param_locations.push((sz, loc));
+    }
+    let stack_offset = ((caller_stack_offset + 15) & !15) as u32;
+    if stack_offset > 0 {
+        dynasm!(a ; sub sp, sp, stack_offset);
+    }
+
+    // Move arguments to their locations.
+    // `callee_vmctx` is already in the first argument register, so no need to move.
+    for (i, (param, (sz, loc))) in sig.params().iter().zip(param_locations).enumerate() {
+        let src = Location::Memory(args, (i * 16) as i32);
+        match loc {
+            Location::SIMD(neon) => a.emit_ldrq(neon, src)?,
+            Location::Memory(_, _) if *param == Type::V128 => {
+                // using V16 as scratch reg
+                a.emit_ldrq(NEON::V16, src)?;
+                a.emit_strq(NEON::V16, loc)?;
+            }
+            Location::Memory(_, _) => {
                 // using X16 as scratch reg
+                a.emit_ldr(sz, Location::GPR(GPR::X16), src)?;
+                a.emit_str(sz, Location::GPR(GPR::X16), loc)?;
+            }
+            _ => a.emit_ldr(sz, loc, src)?,
+        }
+    }
+
+    // The struct of results is returned through the memory pointed to by X8.
+    if results_layout.is_some() && results_registers.is_none() {
+        a.emit_add(
+            Size::S64,
+            Location::GPR(GPR::XzrSp),
+            Location::Imm32(stack_offset),
+            Location::GPR(GPR::X8),
+        )?;
+    }
+
+    dynasm!(a  ; blr X(fptr));
+
+    // Write return values.
+    if let Some(layout) = &results_layout {
+        for (reg, offset, sz) in results_registers.iter().flatten() {
+            a.emit_str(
+                *sz,
+                *reg,
+                Location::Memory(GPR::XzrSp, (stack_offset as usize + offset) as i32),
+            )?;
+        }
+        for (i, (ty, offset)) in sig.results().iter().zip(&layout.offsets).enumerate() {
+            let (sz, words) = match ty {
+                Type::I32 | Type::F32 => (Size::S32, 1),
+                Type::V128 => (Size::S64, 2),
+                _ => (Size::S64, 1),
+            };
+            for word in 0..words {
                 a.emit_ldr(
                     sz,
                     Location::GPR(GPR::X16),
-                    Location::Memory(args, (i * 16) as i32),
+                    Location::Memory(
+                        GPR::XzrSp,
+                        (stack_offset as usize + offset + word * 8) as i32,
+                    ),
                 )?;
                 a.emit_str(
                     sz,
                     Location::GPR(GPR::X16),
-                    Location::Memory(GPR::XzrSp, caller_stack_offset),
+                    Location::Memory(args, (i * 16 + word * 8) as i32),
                 )?;
-                match calling_convention {
-                    CallingConvention::AppleAarch64 => {
-                        caller_stack_offset += 1
-                            << match sz {
-                                Size::S8 => 0,
-                                Size::S16 => 1,
-                                Size::S32 => 2,
-                                Size::S64 => 3,
-                            };
-                    }
-                    _ => {
-                        caller_stack_offset += 8;
-                    }
-                }
             }
         }
-    }
-
-    dynasm!(a  ; blr X(fptr));
-
-    // Write return value.
-    if !sig.results().is_empty() {
+    } else if sig.results() == [Type::V128] {
+        a.emit_strq(NEON::V0, Location::Memory(args, 0))?;
+    } else if !sig.results().is_empty() {
         a.emit_str(Size::S64, Location::GPR(GPR::X0), Location::Memory(args, 0))?;
     }
 
@@ -2939,7 +3484,7 @@ pub fn gen_std_trampoline_arm64(
     dynasm!(a
         ; ldp X(fptr), X(args), [x29, 16]
         ; ldp x29, x30, [x29]
-        ; add sp, sp, 32 + stack_offset as u32
+        ; add sp, sp, 32 + stack_offset as u32 + return_area_size
         ; ret
     );
 
@@ -2957,8 +3502,18 @@ pub fn gen_std_dynamic_import_trampoline_arm64(
     calling_convention: CallingConvention,
 ) -> Result<FunctionBody, CompileError> {
     let mut a = Assembler::new(0);
+    let results_layout = ResultsLayout::new(sig.results());
+    let results_registers = results_layout
+        .as_ref()
+        .and_then(|layout| results_registers_arm64(sig.results(), layout));
+    let results_address = results_layout.is_some() && results_registers.is_none();
+
     // Allocate argument array.
-    let stack_offset: usize = 16 * std::cmp::max(sig.params().len(), sig.results().len());
+    let values_size = 16 * std::cmp::max(sig.params().len(), sig.results().len());
+    // Right above the values array, 16 bytes to assemble the struct of results if it
+    // is returned in registers, or to save the address of the memory receiving it.
+    let return_area_slot = if results_layout.is_some() { 16 } else { 0 };
+    let stack_offset: usize = values_size + return_area_slot;
     // Save LR and X26, as scratch register
     a.emit_stpdb(
         Size::S64,
@@ -2987,6 +3542,15 @@ pub fn gen_std_dynamic_import_trampoline_arm64(
         }
     }
 
+    if results_address {
+        // Save the address of the memory receiving the results.
+        a.emit_str(
+            Size::S64,
+            Location::GPR(GPR::X8),
+            Location::Memory(GPR::XzrSp, values_size as _),
+        )?;
+    }
+
     // Copy arguments.
     if !sig.params().is_empty() {
         let mut argalloc = ArgumentRegisterAllocator::default();
@@ -2996,8 +3560,32 @@ pub fn gen_std_dynamic_import_trampoline_arm64(
 
         for (i, ty) in sig.params().iter().enumerate() {
             let source_loc = match argalloc.next(*ty, calling_convention)? {
+                Some(ARM64Register::NEON(neon)) if *ty == Type::V128 => {
+                    a.emit_strq(neon, Location::Memory(GPR::XzrSp, (i * 16) as _))?;
+                    continue;
+                }
                 Some(ARM64Register::GPR(gpr)) => Location::GPR(gpr),
                 Some(ARM64Register::NEON(neon)) => Location::SIMD(neon),
+                None if *ty == Type::V128 => {
+                    stack_param_count = stack_param_count.next_multiple_of(16);
+                    for word in 0..2 {
+                        a.emit_ldr(
+                            Size::S64,
+                            Location::GPR(GPR::X26),
+                            Location::Memory(
+                                GPR::XzrSp,
+                                (stack_offset + 16 + stack_param_count) as _,
+                            ),
+                        )?;
+                        a.emit_str(
+                            Size::S64,
+                            Location::GPR(GPR::X26),
+                            Location::Memory(GPR::XzrSp, (i * 16 + word * 8) as _),
+                        )?;
+                        stack_param_count += 8;
+                    }
+                    continue;
+                }
                 None => {
                     let sz = match calling_convention {
                         CallingConvention::AppleAarch64 => match *ty {
@@ -3060,14 +3648,61 @@ pub fn gen_std_dynamic_import_trampoline_arm64(
     // Call target.
     a.emit_call_register(GPR::X26)?;
 
-    // Fetch return value.
-    if !sig.results().is_empty() {
-        assert_eq!(sig.results().len(), 1);
+    if let Some(layout) = &results_layout {
+        // Write the struct of results.
+        let (area, area_offset) = if results_address {
+            a.emit_ldr(
+                Size::S64,
+                Location::GPR(GPR::X1),
+                Location::Memory(GPR::XzrSp, values_size as _),
+            )?;
+            (GPR::X1, 0)
+        } else {
+            (GPR::XzrSp, values_size)
+        };
+        for (i, (ty, offset)) in sig.results().iter().zip(&layout.offsets).enumerate() {
+            let (sz, words) = match ty {
+                Type::I32 | Type::F32 => (Size::S32, 1),
+                Type::V128 => (Size::S64, 2),
+                _ => (Size::S64, 1),
+            };
+            for word in 0..words {
+                a.emit_ldr(
+                    sz,
+                    Location::GPR(GPR::X2),
+                    Location::Memory(GPR::XzrSp, (i * 16 + word * 8) as _),
+                )?;
+                a.emit_str(
+                    sz,
+                    Location::GPR(GPR::X2),
+                    Location::Memory(area, (area_offset + offset + word * 8) as _),
+                )?;
+            }
+        }
+        for (reg, offset, sz) in results_registers.iter().flatten() {
+            a.emit_ldr(
+                *sz,
+                *reg,
+                Location::Memory(area, (area_offset + offset) as _),
+            )?;
+        }
+    } else if sig.results() == [Type::V128] {
+        a.emit_ldrq(NEON::V0, Location::Memory(GPR::XzrSp, 0))?;
+    } else if let Some(ty) = sig.results().first() {
+        // Fetch return value.
         a.emit_ldr(
             Size::S64,
             Location::GPR(GPR::X0),
             Location::Memory(GPR::XzrSp, 0),
         )?;
+        // Make a copy of a float in V0, where callers read it from.
+        if *ty == Type::F32 || *ty == Type::F64 {
+            a.emit_ldr(
+                Size::S64,
+                Location::SIMD(NEON::V0),
+                Location::Memory(GPR::XzrSp, 0),
+            )?;
+        }
     }
 
     // Release values array.
@@ -3115,30 +3750,38 @@ pub fn gen_import_call_trampoline_arm64(
     calling_convention: CallingConvention,
 ) -> Result<CustomSection, CompileError> {
     let mut a = Assembler::new(0);
+    let params = sig.params();
+    let has_v128 = params.contains(&Type::V128);
 
-    // Singlepass internally treats all arguments as integers
+    // Singlepass internally treats all arguments as integers, except `v128` ones
+    // which are passed in NEON registers like the native calling convention does.
     // For the standard System V calling convention requires
     //  floating point arguments to be passed in NEON registers.
     //  Translation is expensive, so only do it if needed.
-    if sig
-        .params()
-        .iter()
-        .any(|&x| x == Type::F32 || x == Type::F64)
-    {
+    if params.iter().any(|&x| x == Type::F32 || x == Type::F64) {
         #[allow(clippy::match_single_binding)]
         match calling_convention {
             _ => {
+                let n_scalars = params.iter().filter(|&&ty| ty != Type::V128).count();
+                let n_simds = params.len() - n_scalars;
+                if n_simds > 8 {
+                    codegen_error!(
+                        "singlepass can't pass more than 8 v128 arguments to imported functions"
+                    );
+                }
+
                 // Allocate stack space for arguments.
-                let stack_offset: i32 = if sig.params().len() > 7 {
+                let scalars_size: i32 = if n_scalars > 7 {
                     7 * 8
                 } else {
-                    (sig.params().len() as i32) * 8
+                    (n_scalars as i32) * 8
                 };
-                let stack_offset = if stack_offset & 15 != 0 {
-                    stack_offset + 8
+                let scalars_size = if scalars_size & 15 != 0 {
+                    scalars_size + 8
                 } else {
-                    stack_offset
+                    scalars_size
                 };
+                let stack_offset = scalars_size + (n_simds as i32) * 16;
                 if stack_offset > 0 {
                     if stack_offset < 0x1000 {
                         a.emit_sub(
@@ -3169,16 +3812,24 @@ pub fn gen_import_call_trampoline_arm64(
                     GPR::X7,
                 ];
                 let mut param_locations = vec![];
-                /* Clippy is wrong about using `i` to index `PARAM_REGS` here. */
-                #[allow(clippy::needless_range_loop)]
-                for i in 0..sig.params().len() {
-                    let loc = match i {
-                        0..=6 => {
-                            let loc = Location::Memory(GPR::XzrSp, (i * 8) as i32);
-                            a.emit_str(Size::S64, Location::GPR(PARAM_REGS[i]), loc)?;
-                            loc
+                let (mut scalar_idx, mut simd_idx) = (0, 0);
+                for ty in params {
+                    let loc = if *ty == Type::V128 {
+                        let loc = Location::Memory(GPR::XzrSp, scalars_size + simd_idx * 16);
+                        a.emit_strq(NEON::from_index(simd_idx as usize).unwrap(), loc)?;
+                        simd_idx += 1;
+                        loc
+                    } else {
+                        let i = scalar_idx;
+                        scalar_idx += 1;
+                        match i {
+                            0..=6 => {
+                                let loc = Location::Memory(GPR::XzrSp, (i * 8) as i32);
+                                a.emit_str(Size::S64, Location::GPR(PARAM_REGS[i]), loc)?;
+                                loc
+                            }
+                            _ => Location::Memory(GPR::XzrSp, stack_offset + ((i - 7) * 8) as i32),
                         }
-                        _ => Location::Memory(GPR::XzrSp, stack_offset + ((i - 7) * 8) as i32),
                     };
                     param_locations.push(loc);
                 }
@@ -3187,11 +3838,23 @@ pub fn gen_import_call_trampoline_arm64(
                 let mut caller_stack_offset: i32 = 0;
                 let mut argalloc = ArgumentRegisterAllocator::default();
                 argalloc.next(Type::I64, calling_convention).unwrap(); // skip VMContext
-                for (i, ty) in sig.params().iter().enumerate() {
+                for (i, ty) in params.iter().enumerate() {
                     let prev_loc = param_locations[i];
                     let targ = match argalloc.next(*ty, calling_convention)? {
+                        Some(ARM64Register::NEON(neon)) if *ty == Type::V128 => {
+                            a.emit_ldrq(neon, prev_loc)?;
+                            continue;
+                        }
                         Some(ARM64Register::GPR(gpr)) => Location::GPR(gpr),
                         Some(ARM64Register::NEON(neon)) => Location::SIMD(neon),
+                        None if has_v128 => {
+                            // The floats taking the NEON registers of the `v128`
+                            // arguments could need more stack than the caller reserved.
+                            codegen_error!(
+                                "singlepass can't pass {} arguments on the stack to imported functions taking v128 arguments",
+                                ty
+                            );
+                        }
                         None => {
                             // No register can be allocated. Put this argument on the stack.
                             a.emit_ldr(Size::S64, Location::GPR(GPR::X16), prev_loc)?;
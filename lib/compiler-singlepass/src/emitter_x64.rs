@@ -345,6 +345,45 @@ pub trait EmitterX64 {
         dst: XMM,
     ) -> Result<(), CompileError>;
 
+    // Packed (`v128`) instructions. They only take register operands, as `v128` stack
+    // slots are not 16-byte aligned; memory is accessed with `emit_movdqu`.
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory) -> Result<(), CompileError>;
+    fn emit_packed(
+        &mut self,
+        op: PackedOp,
+        src1: XMM,
+        src2: XMM,
+        dst: XMM,
+    ) -> Result<(), CompileError>;
+    fn emit_packed_imm(
+        &mut self,
+        op: PackedImmOp,
+        src1: XMM,
+        src2: XMM,
+        imm: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError>;
+    fn emit_packed_unop(&mut self, op: PackedUnop, src: XMM, dst: XMM) -> Result<(), CompileError>;
+    fn emit_packed_unop_imm(
+        &mut self,
+        op: PackedUnopImm,
+        src: XMM,
+        imm: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError>;
+    fn emit_pinsr(
+        &mut self,
+        sz: Size,
+        src1: XMM,
+        src2: GPR,
+        lane: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError>;
+    fn emit_pextr(&mut self, sz: Size, src: XMM, lane: u8, dst: GPR) -> Result<(), CompileError>;
+    fn emit_ptest(&mut self, src1: XMM, src2: XMM) -> Result<(), CompileError>;
+    /// `pmovmskb` for `Size::S8` lanes, `movmskps` for `Size::S32` and `movmskpd` for `Size::S64`.
+    fn emit_movmsk(&mut self, sz: Size, src: XMM, dst: GPR) -> Result<(), CompileError>;
+
     fn emit_test_gpr_64(&mut self, reg: GPR) -> Result<(), CompileError>;
 
     fn emit_ud2(&mut self) -> Result<(), CompileError>;
@@ -907,6 +946,200 @@ macro_rules! sse_round_fn {
     }
 }
 
+/// Defines an enum of packed instructions, and how to emit them.
+///
+/// Each instruction is given with its SSE mnemonic and its AVX (VEX encoded) one.
+/// The SSE forms overwrite their first operand, so two operand instructions are
+/// emitted as a move of `src1` to `dst` followed by `op dst, src2`.
+macro_rules! packed_ops {
+    ($(#[$attr:meta])* enum $name:ident { $($op:ident => $sse:ident, $avx:ident;)* }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $($op,)*
+        }
+
+        impl $name {
+            fn emit_sse(self, a: &mut AssemblerX64, dst: XMM, src: XMM) {
+                match self {
+                    $($name::$op => dynasm!(a ; $sse Rx(dst), Rx(src)),)*
+                }
+            }
+
+            fn emit_avx(self, a: &mut AssemblerX64, dst: XMM, src1: XMM, src2: XMM) {
+                match self {
+                    $($name::$op => dynasm!(a ; $avx Rx(dst), Rx(src1), Rx(src2)),)*
+                }
+            }
+        }
+    };
+}
+
+/// Like `packed_ops`, for instructions taking an 8-bit immediate.
+macro_rules! packed_imm_ops {
+    ($(#[$attr:meta])* enum $name:ident { $($op:ident => $sse:ident, $avx:ident;)* }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $($op,)*
+        }
+
+        impl $name {
+            fn emit_sse(self, a: &mut AssemblerX64, dst: XMM, src: XMM, imm: u8) {
+                match self {
+                    $($name::$op => dynasm!(a ; $sse Rx(dst), Rx(src), imm as i8),)*
+                }
+            }
+
+            fn emit_avx(self, a: &mut AssemblerX64, dst: XMM, src1: XMM, src2: XMM, imm: u8) {
+                // dynasm encodes the register-only VEX forms with an immediate as
+                // `ENC_MR`, which swaps the two source operands.
+                match self {
+                    $($name::$op => dynasm!(a ; $avx Rx(dst), Rx(src2), Rx(src1), imm as i8),)*
+                }
+            }
+        }
+    };
+}
+
+packed_ops! {
+    /// Packed instructions computing `dst = src1 op src2`.
+    enum PackedOp {
+        Paddb => paddb, vpaddb;
+        Paddw => paddw, vpaddw;
+        Paddd => paddd, vpaddd;
+        Paddq => paddq, vpaddq;
+        Psubb => psubb, vpsubb;
+        Psubw => psubw, vpsubw;
+        Psubd => psubd, vpsubd;
+        Psubq => psubq, vpsubq;
+        Pmullw => pmullw, vpmullw;
+        Pmulld => pmulld, vpmulld;
+        Pmuludq => pmuludq, vpmuludq;
+        Pmuldq => pmuldq, vpmuldq;
+        Pmaddwd => pmaddwd, vpmaddwd;
+        Pmaddubsw => pmaddubsw, vpmaddubsw;
+        Pmulhrsw => pmulhrsw, vpmulhrsw;
+        Paddsb => paddsb, vpaddsb;
+        Paddsw => paddsw, vpaddsw;
+        Paddusb => paddusb, vpaddusb;
+        Paddusw => paddusw, vpaddusw;
+        Psubsb => psubsb, vpsubsb;
+        Psubsw => psubsw, vpsubsw;
+        Psubusb => psubusb, vpsubusb;
+        Psubusw => psubusw, vpsubusw;
+        Pminsb => pminsb, vpminsb;
+        Pminsw => pminsw, vpminsw;
+        Pminsd => pminsd, vpminsd;
+        Pminub => pminub, vpminub;
+        Pminuw => pminuw, vpminuw;
+        Pminud => pminud, vpminud;
+        Pmaxsb => pmaxsb, vpmaxsb;
+        Pmaxsw => pmaxsw, vpmaxsw;
+        Pmaxsd => pmaxsd, vpmaxsd;
+        Pmaxub => pmaxub, vpmaxub;
+        Pmaxuw => pmaxuw, vpmaxuw;
+        Pmaxud => pmaxud, vpmaxud;
+        Pavgb => pavgb, vpavgb;
+        Pavgw => pavgw, vpavgw;
+        Pcmpeqb => pcmpeqb, vpcmpeqb;
+        Pcmpeqw => pcmpeqw, vpcmpeqw;
+        Pcmpeqd => pcmpeqd, vpcmpeqd;
+        Pcmpeqq => pcmpeqq, vpcmpeqq;
+        Pcmpgtb => pcmpgtb, vpcmpgtb;
+        Pcmpgtw => pcmpgtw, vpcmpgtw;
+        Pcmpgtd => pcmpgtd, vpcmpgtd;
+        Pcmpgtq => pcmpgtq, vpcmpgtq;
+        Pand => pand, vpand;
+        Pandn => pandn, vpandn;
+        Por => por, vpor;
+        Pxor => pxor, vpxor;
+        Packsswb => packsswb, vpacksswb;
+        Packuswb => packuswb, vpackuswb;
+        Packssdw => packssdw, vpackssdw;
+        Packusdw => packusdw, vpackusdw;
+        Punpcklbw => punpcklbw, vpunpcklbw;
+        Punpckhbw => punpckhbw, vpunpckhbw;
+        Punpcklqdq => punpcklqdq, vpunpcklqdq;
+        Pshufb => pshufb, vpshufb;
+        Psllw => psllw, vpsllw;
+        Pslld => pslld, vpslld;
+        Psllq => psllq, vpsllq;
+        Psrlw => psrlw, vpsrlw;
+        Psrld => psrld, vpsrld;
+        Psrlq => psrlq, vpsrlq;
+        Psraw => psraw, vpsraw;
+        Psrad => psrad, vpsrad;
+        Addps => addps, vaddps;
+        Addpd => addpd, vaddpd;
+        Subps => subps, vsubps;
+        Subpd => subpd, vsubpd;
+        Mulps => mulps, vmulps;
+        Mulpd => mulpd, vmulpd;
+        Divps => divps, vdivps;
+        Divpd => divpd, vdivpd;
+        Minps => minps, vminps;
+        Minpd => minpd, vminpd;
+        Maxps => maxps, vmaxps;
+        Maxpd => maxpd, vmaxpd;
+        Andps => andps, vandps;
+        Andnps => andnps, vandnps;
+        Orps => orps, vorps;
+        Xorps => xorps, vxorps;
+        Unpcklps => unpcklps, vunpcklps;
+    }
+}
+
+packed_imm_ops! {
+    /// Packed instructions computing `dst = src1 op src2` according to an immediate.
+    enum PackedImmOp {
+        Shufps => shufps, vshufps;
+        Pblendw => pblendw, vpblendw;
+        Cmpps => cmpps, vcmpps;
+        Cmppd => cmppd, vcmppd;
+    }
+}
+
+/// Packed instructions computing `dst = op src`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PackedUnop {
+    Pabsb,
+    Pabsw,
+    Pabsd,
+    Pmovsxbw,
+    Pmovzxbw,
+    Pmovsxwd,
+    Pmovzxwd,
+    Pmovsxdq,
+    Pmovzxdq,
+    Cvtdq2ps,
+    Cvtdq2pd,
+    Cvttps2dq,
+    Cvttpd2dq,
+    Cvtps2pd,
+    Cvtpd2ps,
+    Sqrtps,
+    Sqrtpd,
+}
+
+/// Packed instructions computing `dst = op src` according to an immediate.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PackedUnopImm {
+    Pshufd,
+    Pshuflw,
+    Roundps,
+    Roundpd,
+    // Shifts by an immediate count.
+    Psllw,
+    Pslld,
+    Psllq,
+    Psrlw,
+    Psrld,
+    Psrlq,
+    Psraw,
+    Psrad,
+}
+
 impl EmitterX64 for AssemblerX64 {
     fn get_simd_arch(&self) -> Option<&CpuFeature> {
         self.simd_arch.as_ref()
@@ -2371,6 +2604,237 @@ impl EmitterX64 for AssemblerX64 {
         Ok(())
     }
 
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory) -> Result<(), CompileError> {
+        match (src, dst) {
+            (XMMOrMemory::XMM(src), XMMOrMemory::XMM(dst)) => {
+                if src != dst {
+                    dynasm!(self ; movdqa Rx(dst), Rx(src))
+                }
+            }
+            (XMMOrMemory::Memory(base, disp), XMMOrMemory::XMM(dst)) => {
+                dynasm!(self ; movdqu Rx(dst), [Rq(base) + disp])
+            }
+            (XMMOrMemory::XMM(src), XMMOrMemory::Memory(base, disp)) => {
+                dynasm!(self ; movdqu [Rq(base) + disp], Rx(src))
+            }
+            _ => codegen_error!("singlepass can't emit MOVDQU {:?} {:?}", src, dst),
+        };
+        Ok(())
+    }
+
+    fn emit_packed(
+        &mut self,
+        op: PackedOp,
+        src1: XMM,
+        src2: XMM,
+        dst: XMM,
+    ) -> Result<(), CompileError> {
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => op.emit_avx(self, dst, src1, src2),
+            Some(CpuFeature::SSE42) => {
+                if src2 == dst && src1 != dst {
+                    codegen_error!(
+                        "singlepass can't emit {:?} overwriting its second operand",
+                        op
+                    );
+                }
+                self.emit_movdqu(XMMOrMemory::XMM(src1), XMMOrMemory::XMM(dst))?;
+                op.emit_sse(self, dst, src2);
+            }
+            _ => codegen_error!("singlepass can't emit {:?} without SSE 4.2", op),
+        }
+        Ok(())
+    }
+
+    fn emit_packed_imm(
+        &mut self,
+        op: PackedImmOp,
+        src1: XMM,
+        src2: XMM,
+        imm: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError> {
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => op.emit_avx(self, dst, src1, src2, imm),
+            Some(CpuFeature::SSE42) => {
+                if src2 == dst && src1 != dst {
+                    codegen_error!(
+                        "singlepass can't emit {:?} overwriting its second operand",
+                        op
+                    );
+                }
+                self.emit_movdqu(XMMOrMemory::XMM(src1), XMMOrMemory::XMM(dst))?;
+                op.emit_sse(self, dst, src2, imm);
+            }
+            _ => codegen_error!("singlepass can't emit {:?} without SSE 4.2", op),
+        }
+        Ok(())
+    }
+
+    fn emit_packed_unop(&mut self, op: PackedUnop, src: XMM, dst: XMM) -> Result<(), CompileError> {
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => match op {
+                PackedUnop::Pabsb => dynasm!(self ; vpabsb Rx(dst), Rx(src)),
+                PackedUnop::Pabsw => dynasm!(self ; vpabsw Rx(dst), Rx(src)),
+                PackedUnop::Pabsd => dynasm!(self ; vpabsd Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxbw => dynasm!(self ; vpmovsxbw Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxbw => dynasm!(self ; vpmovzxbw Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxwd => dynasm!(self ; vpmovsxwd Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxwd => dynasm!(self ; vpmovzxwd Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxdq => dynasm!(self ; vpmovsxdq Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxdq => dynasm!(self ; vpmovzxdq Rx(dst), Rx(src)),
+                PackedUnop::Cvtdq2ps => dynasm!(self ; vcvtdq2ps Rx(dst), Rx(src)),
+                PackedUnop::Cvtdq2pd => dynasm!(self ; vcvtdq2pd Rx(dst), Rx(src)),
+                PackedUnop::Cvttps2dq => dynasm!(self ; vcvttps2dq Rx(dst), Rx(src)),
+                PackedUnop::Cvttpd2dq => dynasm!(self ; vcvttpd2dq Rx(dst), Rx(src)),
+                PackedUnop::Cvtps2pd => dynasm!(self ; vcvtps2pd Rx(dst), Rx(src)),
+                PackedUnop::Cvtpd2ps => dynasm!(self ; vcvtpd2ps Rx(dst), Rx(src)),
+                PackedUnop::Sqrtps => dynasm!(self ; vsqrtps Rx(dst), Rx(src)),
+                PackedUnop::Sqrtpd => dynasm!(self ; vsqrtpd Rx(dst), Rx(src)),
+            },
+            Some(CpuFeature::SSE42) => match op {
+                PackedUnop::Pabsb => dynasm!(self ; pabsb Rx(dst), Rx(src)),
+                PackedUnop::Pabsw => dynasm!(self ; pabsw Rx(dst), Rx(src)),
+                PackedUnop::Pabsd => dynasm!(self ; pabsd Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxbw => dynasm!(self ; pmovsxbw Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxbw => dynasm!(self ; pmovzxbw Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxwd => dynasm!(self ; pmovsxwd Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxwd => dynasm!(self ; pmovzxwd Rx(dst), Rx(src)),
+                PackedUnop::Pmovsxdq => dynasm!(self ; pmovsxdq Rx(dst), Rx(src)),
+                PackedUnop::Pmovzxdq => dynasm!(self ; pmovzxdq Rx(dst), Rx(src)),
+                PackedUnop::Cvtdq2ps => dynasm!(self ; cvtdq2ps Rx(dst), Rx(src)),
+                PackedUnop::Cvtdq2pd => dynasm!(self ; cvtdq2pd Rx(dst), Rx(src)),
+                PackedUnop::Cvttps2dq => dynasm!(self ; cvttps2dq Rx(dst), Rx(src)),
+                PackedUnop::Cvttpd2dq => dynasm!(self ; cvttpd2dq Rx(dst), Rx(src)),
+                PackedUnop::Cvtps2pd => dynasm!(self ; cvtps2pd Rx(dst), Rx(src)),
+                PackedUnop::Cvtpd2ps => dynasm!(self ; cvtpd2ps Rx(dst), Rx(src)),
+                PackedUnop::Sqrtps => dynasm!(self ; sqrtps Rx(dst), Rx(src)),
+                PackedUnop::Sqrtpd => dynasm!(self ; sqrtpd Rx(dst), Rx(src)),
+            },
+            _ => codegen_error!("singlepass can't emit {:?} without SSE 4.2", op),
+        }
+        Ok(())
+    }
+
+    fn emit_packed_unop_imm(
+        &mut self,
+        op: PackedUnopImm,
+        src: XMM,
+        imm: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError> {
+        let imm = imm as i8;
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => match op {
+                PackedUnopImm::Pshufd => dynasm!(self ; vpshufd Rx(dst), Rx(src), imm),
+                PackedUnopImm::Pshuflw => dynasm!(self ; vpshuflw Rx(dst), Rx(src), imm),
+                PackedUnopImm::Roundps => dynasm!(self ; vroundps Rx(dst), Rx(src), imm),
+                PackedUnopImm::Roundpd => dynasm!(self ; vroundpd Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psllw => dynasm!(self ; vpsllw Rx(dst), Rx(src), imm),
+                PackedUnopImm::Pslld => dynasm!(self ; vpslld Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psllq => dynasm!(self ; vpsllq Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psrlw => dynasm!(self ; vpsrlw Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psrld => dynasm!(self ; vpsrld Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psrlq => dynasm!(self ; vpsrlq Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psraw => dynasm!(self ; vpsraw Rx(dst), Rx(src), imm),
+                PackedUnopImm::Psrad => dynasm!(self ; vpsrad Rx(dst), Rx(src), imm),
+            },
+            Some(CpuFeature::SSE42) => match op {
+                PackedUnopImm::Pshufd => dynasm!(self ; pshufd Rx(dst), Rx(src), imm),
+                PackedUnopImm::Pshuflw => dynasm!(self ; pshuflw Rx(dst), Rx(src), imm),
+                PackedUnopImm::Roundps => dynasm!(self ; roundps Rx(dst), Rx(src), imm),
+                PackedUnopImm::Roundpd => dynasm!(self ; roundpd Rx(dst), Rx(src), imm),
+                // The shifts operate in place.
+                _ => {
+                    self.emit_movdqu(XMMOrMemory::XMM(src), XMMOrMemory::XMM(dst))?;
+                    match op {
+                        PackedUnopImm::Psllw => dynasm!(self ; psllw Rx(dst), imm),
+                        PackedUnopImm::Pslld => dynasm!(self ; pslld Rx(dst), imm),
+                        PackedUnopImm::Psllq => dynasm!(self ; psllq Rx(dst), imm),
+                        PackedUnopImm::Psrlw => dynasm!(self ; psrlw Rx(dst), imm),
+                        PackedUnopImm::Psrld => dynasm!(self ; psrld Rx(dst), imm),
+                        PackedUnopImm::Psrlq => dynasm!(self ; psrlq Rx(dst), imm),
+                        PackedUnopImm::Psraw => dynasm!(self ; psraw Rx(dst), imm),
+                        PackedUnopImm::Psrad => dynasm!(self ; psrad Rx(dst), imm),
+                        _ => unreachable!(),
+                    }
+                }
+            },
+            _ => codegen_error!("singlepass can't emit {:?} without SSE 4.2", op),
+        }
+        Ok(())
+    }
+
+    fn emit_pinsr(
+        &mut self,
+        sz: Size,
+        src1: XMM,
+        src2: GPR,
+        lane: u8,
+        dst: XMM,
+    ) -> Result<(), CompileError> {
+        let lane = lane as i8;
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => match sz {
+                Size::S8 => dynasm!(self ; vpinsrb Rx(dst), Rx(src1), Rd(src2), lane),
+                Size::S16 => dynasm!(self ; vpinsrw Rx(dst), Rx(src1), Rd(src2), lane),
+                Size::S32 => dynasm!(self ; vpinsrd Rx(dst), Rx(src1), Rd(src2), lane),
+                Size::S64 => dynasm!(self ; vpinsrq Rx(dst), Rx(src1), Rq(src2), lane),
+            },
+            Some(CpuFeature::SSE42) => {
+                self.emit_movdqu(XMMOrMemory::XMM(src1), XMMOrMemory::XMM(dst))?;
+                match sz {
+                    Size::S8 => dynasm!(self ; pinsrb Rx(dst), Rd(src2), lane),
+                    Size::S16 => dynasm!(self ; pinsrw Rx(dst), Rd(src2), lane),
+                    Size::S32 => dynasm!(self ; pinsrd Rx(dst), Rd(src2), lane),
+                    Size::S64 => dynasm!(self ; pinsrq Rx(dst), Rq(src2), lane),
+                }
+            }
+            _ => codegen_error!("singlepass can't emit PINSR without SSE 4.2"),
+        }
+        Ok(())
+    }
+
+    fn emit_pextr(&mut self, sz: Size, src: XMM, lane: u8, dst: GPR) -> Result<(), CompileError> {
+        let lane = lane as i8;
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => match sz {
+                Size::S8 => dynasm!(self ; vpextrb Rd(dst), Rx(src), lane),
+                Size::S16 => dynasm!(self ; vpextrw Rd(dst), Rx(src), lane),
+                Size::S32 => dynasm!(self ; vpextrd Rd(dst), Rx(src), lane),
+                Size::S64 => dynasm!(self ; vpextrq Rq(dst), Rx(src), lane),
+            },
+            Some(CpuFeature::SSE42) => match sz {
+                Size::S8 => dynasm!(self ; pextrb Rd(dst), Rx(src), lane),
+                Size::S16 => dynasm!(self ; pextrw Rd(dst), Rx(src), lane),
+                Size::S32 => dynasm!(self ; pextrd Rd(dst), Rx(src), lane),
+                Size::S64 => dynasm!(self ; pextrq Rq(dst), Rx(src), lane),
+            },
+            _ => codegen_error!("singlepass can't emit PEXTR without SSE 4.2"),
+        }
+        Ok(())
+    }
+
+    fn emit_ptest(&mut self, src1: XMM, src2: XMM) -> Result<(), CompileError> {
+        match self.get_simd_arch() {
+            Some(CpuFeature::AVX) => dynasm!(self ; vptest Rx(src1), Rx(src2)),
+            Some(CpuFeature::SSE42) => dynasm!(self ; ptest Rx(src1), Rx(src2)),
+            _ => codegen_error!("singlepass can't emit PTEST without SSE 4.2"),
+        }
+        Ok(())
+    }
+
+    fn emit_movmsk(&mut self, sz: Size, src: XMM, dst: GPR) -> Result<(), CompileError> {
+        // The legacy SSE encoding is used with AVX as well, `dynasm` can't encode the VEX one.
+        match sz {
+            Size::S8 => dynasm!(self ; pmovmskb Rd(dst), Rx(src)),
+            Size::S32 => dynasm!(self ; movmskps Rd(dst), Rx(src)),
+            Size::S64 => dynasm!(self ; movmskpd Rd(dst), Rx(src)),
+            _ => codegen_error!("singlepass can't emit MOVMSK {:?}", sz),
+        }
+        Ok(())
+    }
+
     fn emit_test_gpr_64(&mut self, reg: GPR) -> Result<(), CompileError> {
         dynasm!(self ; test Rq(reg), Rq(reg));
         Ok(())
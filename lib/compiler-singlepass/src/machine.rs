@@ -14,10 +14,10 @@ use wasmer_compiler::{
         relocation::{Relocation, RelocationTarget},
         section::CustomSection,
     },
-    wasmparser::{MemArg, ValType as WpType},
+    wasmparser::{MemArg, Operator, ValType as WpType},
 };
 use wasmer_types::{
-    CompileError, FunctionIndex, FunctionType, TrapCode, TrapInformation, VMOffsets,
+    CompileError, FunctionIndex, FunctionType, TrapCode, TrapInformation, Type, VMOffsets,
     target::{Architecture, CallingConvention, Target},
 };
 pub type Label = DynamicLabel;
@@ -311,6 +311,19 @@ pub trait Machine {
     fn get_gpr_for_ret(&self) -> Self::GPR;
     /// get the simd for the return of float/double values
     fn get_simd_for_ret(&self) -> Self::SIMD;
+    /// Registers holding results returned like a struct following `layout`, each with the
+    /// offset and size of the part of the struct it holds, or `None` if the struct is
+    /// returned through memory.
+    fn get_results_registers(
+        &self,
+        tys: &[Type],
+        layout: &ResultsLayout,
+        calling_convention: CallingConvention,
+    ) -> Option<ResultsRegisters<Self::GPR, Self::SIMD>>;
+    /// get the gpr receiving the address of the memory results are returned through
+    fn get_results_address_reg(&self, calling_convention: CallingConvention) -> Self::GPR;
+    /// Is that gpr the first parameter, with the vmctx and the other parameters shifted by one
+    fn results_address_is_first_param(&self) -> bool;
 
     /// Emit a debug breakpoint
     fn emit_debug_breakpoint(&mut self) -> Result<(), CompileError>;
@@ -2382,6 +2395,139 @@ pub trait Machine {
         ret: Location<Self::GPR, Self::SIMD>,
     ) -> Result<(), CompileError>;
 
+    /// Location of the `idx`-th `v128` parameter passed to a callee, counting only the
+    /// `v128` parameters. MUST be called in order with `get_param_location`, as the
+    /// parameters passed on the stack share `stack_location`.
+    fn get_simd_param_location(
+        &self,
+        idx: usize,
+        stack_location: &mut usize,
+        calling_convention: CallingConvention,
+    ) -> Location<Self::GPR, Self::SIMD>;
+    /// Location of the `idx`-th `v128` parameter, as seen by the callee. MUST be called
+    /// in order with `get_call_param_location`.
+    fn get_simd_call_param_location(
+        &self,
+        idx: usize,
+        stack_location: &mut usize,
+        calling_convention: CallingConvention,
+    ) -> Location<Self::GPR, Self::SIMD>;
+    /// Move a `v128` value between SIMD registers and 16-byte stack slots
+    fn move_v128(
+        &mut self,
+        src: Location<Self::GPR, Self::SIMD>,
+        dst: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Materialize a `v128` constant
+    fn v128_const(
+        &mut self,
+        value: u128,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower a SIMD operator with one `v128` operand and a `v128` result
+    fn v128_unop(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower a SIMD operator with two `v128` operands and a `v128` result
+    fn v128_binop(
+        &mut self,
+        op: &Operator,
+        loc_a: Location<Self::GPR, Self::SIMD>,
+        loc_b: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower `v128.bitselect`
+    fn v128_bitselect(
+        &mut self,
+        loc_a: Location<Self::GPR, Self::SIMD>,
+        loc_b: Location<Self::GPR, Self::SIMD>,
+        mask: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower a shift of the lanes of a `v128` by an `i32` count
+    fn v128_shift(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        count: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower `v128.any_true`, the `all_true` and the `bitmask` operators, with an `i32` result
+    fn v128_test(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower a splat of a scalar to all the lanes of a `v128`
+    fn v128_splat(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower an `extract_lane` operator
+    fn v128_extract_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower a `replace_lane` operator
+    fn v128_replace_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location<Self::GPR, Self::SIMD>,
+        value: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+    ) -> Result<(), CompileError>;
+    /// Lower `v128.load` and the extending, splatting and zeroing loads
+    #[allow(clippy::too_many_arguments)]
+    fn v128_load(
+        &mut self,
+        op: &Operator,
+        addr: Location<Self::GPR, Self::SIMD>,
+        memarg: &MemArg,
+        ret: Location<Self::GPR, Self::SIMD>,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError>;
+    /// Lower the `v128.load*_lane` loads, replacing a lane of `loc`
+    #[allow(clippy::too_many_arguments)]
+    fn v128_load_lane(
+        &mut self,
+        op: &Operator,
+        addr: Location<Self::GPR, Self::SIMD>,
+        memarg: &MemArg,
+        loc: Location<Self::GPR, Self::SIMD>,
+        ret: Location<Self::GPR, Self::SIMD>,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError>;
+    /// Lower `v128.store` and the `v128.store*_lane` stores
+    #[allow(clippy::too_many_arguments)]
+    fn v128_save(
+        &mut self,
+        op: &Operator,
+        value: Location<Self::GPR, Self::SIMD>,
+        memarg: &MemArg,
+        addr: Location<Self::GPR, Self::SIMD>,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError>;
+
     /// Standard function Trampoline generation
     fn gen_std_trampoline(
         &self,
@@ -2409,6 +2555,56 @@ pub trait Machine {
     fn gen_windows_unwind_info(&mut self, code_len: usize) -> Option<Vec<u8>>;
 }
 
+/// Registers holding a struct of results, each with the offset and size of the part of
+/// the struct it holds.
+pub type ResultsRegisters<R, S> = Vec<(Location<R, S>, usize, Size)>;
+
+/// Layout of the results of a function returning more than one value.
+///
+/// Such results are returned like a `#[repr(C)]` struct holding one field per result,
+/// which is how host functions return their `WasmTypeList::CStruct`: in the registers
+/// given by `Machine::get_results_registers` when the calling convention allows it, or
+/// else through memory whose address is passed by the caller in
+/// `Machine::get_results_address_reg`.
+pub struct ResultsLayout {
+    /// Offset of each result in the struct.
+    pub offsets: Vec<usize>,
+    /// Size of the struct, rounded up to its alignment.
+    pub size: usize,
+}
+
+impl ResultsLayout {
+    /// Layout of the given results, or `None` if there is at most one.
+    pub fn new(tys: &[Type]) -> Option<Self> {
+        if tys.len() < 2 {
+            return None;
+        }
+        let mut offsets = Vec::with_capacity(tys.len());
+        let mut size: usize = 0;
+        let mut align: usize = 8;
+        for ty in tys {
+            let field_size = Self::field_size(*ty);
+            size = size.next_multiple_of(field_size);
+            offsets.push(size);
+            size += field_size;
+            align = align.max(field_size);
+        }
+        Some(Self {
+            offsets,
+            size: size.next_multiple_of(align),
+        })
+    }
+
+    /// Size, and alignment, of the struct field holding a value of the given type.
+    pub fn field_size(ty: Type) -> usize {
+        match ty {
+            Type::I32 | Type::F32 => 4,
+            Type::V128 => 16,
+            _ => 8,
+        }
+    }
+}
+
 /// Standard entry trampoline generation
 pub fn gen_std_trampoline(
     sig: &FunctionType,
@@ -9,10 +9,11 @@ use wasmer_compiler::{
         relocation::{Relocation, RelocationKind, RelocationTarget},
         section::CustomSection,
     },
-    wasmparser::{MemArg, ValType as WpType},
+    wasmparser::{MemArg, Operator, ValType as WpType},
 };
 use wasmer_types::{
-    CompileError, FunctionIndex, FunctionType, SourceLoc, TrapCode, TrapInformation, VMOffsets,
+    CompileError, FunctionIndex, FunctionType, SourceLoc, TrapCode, TrapInformation, Type,
+    VMOffsets,
     target::{CallingConvention, CpuFeature, Target},
 };
 
@@ -65,6 +66,7 @@ enum ImmType {
     OffsetHWord,
     OffsetWord,
     OffsetDWord,
+    OffsetQWord,
 }
 
 #[allow(dead_code)]
@@ -107,6 +109,7 @@ impl MachineARM64 {
             ImmType::OffsetHWord => (imm & 1 == 0) && (0..0x2000).contains(&imm),
             ImmType::OffsetWord => (imm & 3 == 0) && (0..0x4000).contains(&imm),
             ImmType::OffsetDWord => (imm & 7 == 0) && (0..0x8000).contains(&imm),
+            ImmType::OffsetQWord => (imm & 15 == 0) && (0..0x10000).contains(&imm),
         }
     }
 
@@ -1311,6 +1314,157 @@ impl MachineARM64 {
         self.restore_fpcr(old_fpcr)
     }
 
+    fn emit_relaxed_ldrq(&mut self, dst: NEON, src: Location) -> Result<(), CompileError> {
+        match src {
+            Location::Memory(addr, offset) => {
+                if self.compatible_imm(offset as i64, ImmType::OffsetQWord) {
+                    self.assembler.emit_ldrq(dst, src)
+                } else if self.compatible_imm(offset as i64, ImmType::UnscaledOffset) {
+                    self.assembler.emit_ldurq(dst, addr, offset)
+                } else {
+                    // Not a temporary GPR, which could hold an argument of a call.
+                    let tmp = GPR::X17;
+                    self.assembler
+                        .emit_mov_imm(Location::GPR(tmp), (offset as i64) as u64)?;
+                    self.assembler
+                        .emit_ldrq(dst, Location::Memory2(addr, tmp, Multiplier::One, 0))
+                }
+            }
+            _ => codegen_error!("singlepass emit_relaxed_ldrq unreachable"),
+        }
+    }
+    fn emit_relaxed_strq(&mut self, src: NEON, dst: Location) -> Result<(), CompileError> {
+        match dst {
+            Location::Memory(addr, offset) => {
+                if self.compatible_imm(offset as i64, ImmType::OffsetQWord) {
+                    self.assembler.emit_strq(src, dst)
+                } else if self.compatible_imm(offset as i64, ImmType::UnscaledOffset) {
+                    self.assembler.emit_sturq(src, addr, offset)
+                } else {
+                    // Not a temporary GPR, which could hold an argument of a call.
+                    let tmp = GPR::X17;
+                    self.assembler
+                        .emit_mov_imm(Location::GPR(tmp), (offset as i64) as u64)?;
+                    self.assembler
+                        .emit_strq(src, Location::Memory2(addr, tmp, Multiplier::One, 0))
+                }
+            }
+            _ => codegen_error!("singlepass emit_relaxed_strq unreachable"),
+        }
+    }
+
+    /// Acquires a NEON register for the lowering of a `v128` operator.
+    fn acquire_v128_temp(&mut self, temps: &mut Vec<NEON>) -> Result<NEON, CompileError> {
+        let tmp = self.acquire_temp_simd().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp simd for v128".to_owned())
+        })?;
+        temps.push(tmp);
+        Ok(tmp)
+    }
+
+    /// Lowers an operator producing a `v128` to `ret` from the `v128` operands in `srcs`.
+    ///
+    /// `f` gets the operands in NEON registers, which it must not modify, the register
+    /// receiving the result, which is none of the operands, and `n_scratch` scratch
+    /// registers.
+    fn emit_v128<F>(
+        &mut self,
+        srcs: &[Location],
+        ret: Location,
+        n_scratch: usize,
+        f: F,
+    ) -> Result<(), CompileError>
+    where
+        F: FnOnce(&mut Self, &[NEON], NEON, &[NEON]) -> Result<(), CompileError>,
+    {
+        let mut temps = vec![];
+        let regs = self.v128_operands(srcs, &mut temps)?;
+        let out = match ret {
+            Location::SIMD(x) if !regs.contains(&x) => x,
+            _ => self.acquire_v128_temp(&mut temps)?,
+        };
+        let mut scratch = Vec::with_capacity(n_scratch);
+        for _ in 0..n_scratch {
+            scratch.push(self.acquire_v128_temp(&mut temps)?);
+        }
+        f(self, &regs, out, &scratch)?;
+        self.move_v128(Location::SIMD(out), ret)?;
+        for tmp in temps {
+            self.release_simd(tmp);
+        }
+        Ok(())
+    }
+
+    /// Like `emit_v128`, for operators only reading `v128` values.
+    fn emit_v128_read<F>(
+        &mut self,
+        srcs: &[Location],
+        n_scratch: usize,
+        f: F,
+    ) -> Result<(), CompileError>
+    where
+        F: FnOnce(&mut Self, &[NEON], &[NEON]) -> Result<(), CompileError>,
+    {
+        let mut temps = vec![];
+        let regs = self.v128_operands(srcs, &mut temps)?;
+        let mut scratch = Vec::with_capacity(n_scratch);
+        for _ in 0..n_scratch {
+            scratch.push(self.acquire_v128_temp(&mut temps)?);
+        }
+        f(self, &regs, &scratch)?;
+        for tmp in temps {
+            self.release_simd(tmp);
+        }
+        Ok(())
+    }
+
+    /// Puts the `v128` operands in `srcs` in NEON registers, loading those in memory
+    /// into temporary registers.
+    fn v128_operands(
+        &mut self,
+        srcs: &[Location],
+        temps: &mut Vec<NEON>,
+    ) -> Result<Vec<NEON>, CompileError> {
+        let mut regs = Vec::with_capacity(srcs.len());
+        for src in srcs {
+            let reg = match *src {
+                Location::SIMD(x) => x,
+                Location::Memory(_, _) => {
+                    let tmp = self.acquire_v128_temp(temps)?;
+                    self.emit_relaxed_ldrq(tmp, *src)?;
+                    tmp
+                }
+                _ => codegen_error!("singlepass can't use {:?} as a v128 operand", src),
+            };
+            regs.push(reg);
+        }
+        Ok(regs)
+    }
+
+    /// Loads a `v128` constant into `dst`.
+    fn v128_load_const(&mut self, value: u128, dst: NEON) -> Result<(), CompileError> {
+        if value == 0 {
+            return self.assembler.emit_neon(NeonOp::Eor, dst, dst, dst);
+        }
+        if value == u128::MAX {
+            return self.assembler.emit_neon(NeonOp::CmeqB, dst, dst, dst);
+        }
+        let (low, high) = (value as u64, (value >> 64) as u64);
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.assembler.emit_mov_imm(Location::GPR(tmp), low)?;
+        if high == low {
+            self.assembler.emit_dup(Size::S64, tmp, dst)?;
+        } else {
+            self.assembler.emit_ins(Size::S64, tmp, 0, dst)?;
+            self.assembler.emit_mov_imm(Location::GPR(tmp), high)?;
+            self.assembler.emit_ins(Size::S64, tmp, 1, dst)?;
+        }
+        self.release_gpr(tmp);
+        Ok(())
+    }
+
     fn used_gprs_contains(&self, r: &GPR) -> bool {
         self.used_gprs & (1 << r.into_index()) != 0
     }
@@ -1479,35 +1633,22 @@ impl Machine for MachineARM64 {
     }
 
     fn push_used_simd(&mut self, used_neons: &[NEON]) -> Result<usize, CompileError> {
-        let stack_adjust = if used_neons.len() & 1 == 1 {
-            (used_neons.len() * 8) as u32 + 8
-        } else {
-            (used_neons.len() * 8) as u32
-        };
+        // The registers may hold `v128` values, so they are saved whole.
+        let stack_adjust = (used_neons.len() * 16) as u32;
         self.adjust_stack(stack_adjust)?;
 
         for (i, r) in used_neons.iter().enumerate() {
-            self.assembler.emit_str(
-                Size::S64,
-                Location::SIMD(*r),
-                Location::Memory(GPR::XzrSp, (i * 8) as i32),
-            )?;
+            self.assembler
+                .emit_strq(*r, Location::Memory(GPR::XzrSp, (i * 16) as i32))?;
         }
         Ok(stack_adjust as usize)
     }
     fn pop_used_simd(&mut self, used_neons: &[NEON]) -> Result<(), CompileError> {
         for (i, r) in used_neons.iter().enumerate() {
-            self.assembler.emit_ldr(
-                Size::S64,
-                Location::SIMD(*r),
-                Location::Memory(GPR::XzrSp, (i * 8) as i32),
-            )?;
+            self.assembler
+                .emit_ldrq(*r, Location::Memory(GPR::XzrSp, (i * 16) as i32))?;
         }
-        let stack_adjust = if used_neons.len() & 1 == 1 {
-            (used_neons.len() * 8) as u32 + 8
-        } else {
-            (used_neons.len() * 8) as u32
-        };
+        let stack_adjust = (used_neons.len() * 16) as u32;
         self.assembler.emit_add(
             Size::S64,
             Location::GPR(GPR::XzrSp),
@@ -2289,7 +2430,9 @@ impl Machine for MachineARM64 {
         canonicalize: bool,
         loc: Location,
     ) -> Result<(), CompileError> {
-        if canonicalize {
+        if ty == WpType::V128 {
+            self.move_v128(loc, Location::SIMD(NEON::V0))?;
+        } else if canonicalize {
             self.canonicalize_nan(
                 match ty {
                     WpType::F32 => Size::S32,
@@ -2389,6 +2532,23 @@ impl Machine for MachineARM64 {
         NEON::V0
     }
 
+    fn get_results_registers(
+        &self,
+        tys: &[Type],
+        layout: &ResultsLayout,
+        _calling_convention: CallingConvention,
+    ) -> Option<ResultsRegisters<GPR, NEON>> {
+        results_registers_arm64(tys, layout)
+    }
+
+    fn get_results_address_reg(&self, _calling_convention: CallingConvention) -> GPR {
+        GPR::X8
+    }
+
+    fn results_address_is_first_param(&self) -> bool {
+        false
+    }
+
     fn arch_requires_indirect_call_trampoline(&self) -> bool {
         self.assembler.arch_requires_indirect_call_trampoline()
     }
@@ -2428,10 +2588,43 @@ impl Machine for MachineARM64 {
     fn location_address(
         &mut self,
         _size: Size,
-        _source: Location,
-        _dest: Location,
+        source: Location,
+        dest: Location,
     ) -> Result<(), CompileError> {
-        codegen_error!("singlepass location_address not implemented")
+        match (source, dest) {
+            (Location::Memory(addr, offset), Location::GPR(_)) => {
+                if self.compatible_imm(offset as i64, ImmType::Bits12) {
+                    self.assembler.emit_add(
+                        Size::S64,
+                        Location::GPR(addr),
+                        Location::Imm32(offset as u32),
+                        dest,
+                    )
+                } else if self.compatible_imm(-(offset as i64), ImmType::Bits12) {
+                    self.assembler.emit_sub(
+                        Size::S64,
+                        Location::GPR(addr),
+                        Location::Imm32(-offset as u32),
+                        dest,
+                    )
+                } else {
+                    let tmp = GPR::X17;
+                    self.assembler
+                        .emit_mov_imm(Location::GPR(tmp), offset as i64 as u64)?;
+                    self.assembler.emit_add(
+                        Size::S64,
+                        Location::GPR(addr),
+                        Location::GPR(tmp),
+                        dest,
+                    )
+                }
+            }
+            _ => codegen_error!(
+                "singlepass can't emit location_address {:?} {:?}",
+                source,
+                dest
+            ),
+        }
     }
     // logic
     fn location_and(
@@ -8415,6 +8608,861 @@ impl Machine for MachineARM64 {
         )
     }
 
+    fn get_simd_param_location(
+        &self,
+        idx: usize,
+        stack_location: &mut usize,
+        _calling_convention: CallingConvention,
+    ) -> Location {
+        if idx < 8 {
+            Location::SIMD(NEON::from_index(idx).unwrap())
+        } else {
+            *stack_location = stack_location.next_multiple_of(16);
+            let loc = Location::Memory(GPR::XzrSp, *stack_location as i32);
+            *stack_location += 16;
+            loc
+        }
+    }
+    fn get_simd_call_param_location(
+        &self,
+        idx: usize,
+        stack_location: &mut usize,
+        _calling_convention: CallingConvention,
+    ) -> Location {
+        if idx < 8 {
+            Location::SIMD(NEON::from_index(idx).unwrap())
+        } else {
+            *stack_location = stack_location.next_multiple_of(16);
+            let loc = Location::Memory(GPR::X29, 16 * 2 + *stack_location as i32);
+            *stack_location += 16;
+            loc
+        }
+    }
+    fn move_v128(&mut self, src: Location, dst: Location) -> Result<(), CompileError> {
+        match (src, dst) {
+            (Location::SIMD(x), Location::SIMD(y)) => {
+                if x != y {
+                    self.assembler.emit_neon_unop(NeonUnop::Mov, x, y)?;
+                }
+                Ok(())
+            }
+            (Location::Memory(_, _), Location::SIMD(y)) => self.emit_relaxed_ldrq(y, src),
+            (Location::SIMD(x), Location::Memory(_, _)) => self.emit_relaxed_strq(x, dst),
+            (Location::Memory(_, _), Location::Memory(_, _)) => {
+                if src != dst {
+                    let tmp = self.acquire_temp_simd().ok_or_else(|| {
+                        CompileError::Codegen("singlepass cannot acquire temp simd".to_owned())
+                    })?;
+                    self.emit_relaxed_ldrq(tmp, src)?;
+                    self.emit_relaxed_strq(tmp, dst)?;
+                    self.release_simd(tmp);
+                }
+                Ok(())
+            }
+            _ => codegen_error!("singlepass can't move v128 {:?} to {:?}", src, dst),
+        }
+    }
+    fn v128_const(&mut self, value: u128, ret: Location) -> Result<(), CompileError> {
+        self.emit_v128(&[], ret, 0, |this, _, out, _| {
+            this.v128_load_const(value, out)
+        })
+    }
+    fn v128_unop(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let unop = match op {
+            Operator::V128Not => NeonUnop::Not,
+            Operator::I8x16Abs => NeonUnop::AbsB,
+            Operator::I16x8Abs => NeonUnop::AbsH,
+            Operator::I32x4Abs => NeonUnop::AbsS,
+            Operator::I64x2Abs => NeonUnop::AbsD,
+            Operator::I8x16Neg => NeonUnop::NegB,
+            Operator::I16x8Neg => NeonUnop::NegH,
+            Operator::I32x4Neg => NeonUnop::NegS,
+            Operator::I64x2Neg => NeonUnop::NegD,
+            Operator::I8x16Popcnt => NeonUnop::CntB,
+            Operator::I16x8ExtAddPairwiseI8x16S => NeonUnop::SaddlpB,
+            Operator::I16x8ExtAddPairwiseI8x16U => NeonUnop::UaddlpB,
+            Operator::I32x4ExtAddPairwiseI16x8S => NeonUnop::SaddlpH,
+            Operator::I32x4ExtAddPairwiseI16x8U => NeonUnop::UaddlpH,
+            Operator::I16x8ExtendLowI8x16S => NeonUnop::SxtlB,
+            Operator::I16x8ExtendHighI8x16S => NeonUnop::Sxtl2B,
+            Operator::I16x8ExtendLowI8x16U => NeonUnop::UxtlB,
+            Operator::I16x8ExtendHighI8x16U => NeonUnop::Uxtl2B,
+            Operator::I32x4ExtendLowI16x8S => NeonUnop::SxtlH,
+            Operator::I32x4ExtendHighI16x8S => NeonUnop::Sxtl2H,
+            Operator::I32x4ExtendLowI16x8U => NeonUnop::UxtlH,
+            Operator::I32x4ExtendHighI16x8U => NeonUnop::Uxtl2H,
+            Operator::I64x2ExtendLowI32x4S => NeonUnop::SxtlS,
+            Operator::I64x2ExtendHighI32x4S => NeonUnop::Sxtl2S,
+            Operator::I64x2ExtendLowI32x4U => NeonUnop::UxtlS,
+            Operator::I64x2ExtendHighI32x4U => NeonUnop::Uxtl2S,
+            Operator::F32x4Abs => NeonUnop::FabsS,
+            Operator::F64x2Abs => NeonUnop::FabsD,
+            Operator::F32x4Neg => NeonUnop::FnegS,
+            Operator::F64x2Neg => NeonUnop::FnegD,
+            Operator::F32x4Sqrt => NeonUnop::FsqrtS,
+            Operator::F64x2Sqrt => NeonUnop::FsqrtD,
+            Operator::F32x4Nearest => NeonUnop::FrintnS,
+            Operator::F64x2Nearest => NeonUnop::FrintnD,
+            Operator::F32x4Floor => NeonUnop::FrintmS,
+            Operator::F64x2Floor => NeonUnop::FrintmD,
+            Operator::F32x4Ceil => NeonUnop::FrintpS,
+            Operator::F64x2Ceil => NeonUnop::FrintpD,
+            Operator::F32x4Trunc => NeonUnop::FrintzS,
+            Operator::F64x2Trunc => NeonUnop::FrintzD,
+            Operator::F32x4ConvertI32x4S => NeonUnop::ScvtfS,
+            Operator::F32x4ConvertI32x4U => NeonUnop::UcvtfS,
+            Operator::I32x4TruncSatF32x4S => NeonUnop::FcvtzsS,
+            Operator::I32x4TruncSatF32x4U => NeonUnop::FcvtzuS,
+            Operator::F32x4DemoteF64x2Zero => NeonUnop::Fcvtn,
+            Operator::F64x2PromoteLowF32x4 => NeonUnop::Fcvtl,
+            // Widened or converted to 64-bit lanes first, then converted or narrowed.
+            Operator::F64x2ConvertLowI32x4S => {
+                return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_neon_unop(NeonUnop::SxtlS, r[0], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::ScvtfD, out, out)
+                });
+            }
+            Operator::F64x2ConvertLowI32x4U => {
+                return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_neon_unop(NeonUnop::UxtlS, r[0], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::UcvtfD, out, out)
+                });
+            }
+            Operator::I32x4TruncSatF64x2SZero => {
+                return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler
+                        .emit_neon_unop(NeonUnop::FcvtzsD, r[0], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::SqxtnD, out, out)
+                });
+            }
+            Operator::I32x4TruncSatF64x2UZero => {
+                return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler
+                        .emit_neon_unop(NeonUnop::FcvtzuD, r[0], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::UqxtnD, out, out)
+                });
+            }
+            _ => codegen_error!("singlepass v128_unop unimplemented for {:?}", op),
+        };
+        self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+            this.assembler.emit_neon_unop(unop, r[0], out)
+        })
+    }
+    fn v128_binop(
+        &mut self,
+        op: &Operator,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        // Operators lowered to a single instruction, possibly with swapped operands.
+        let simple = match op {
+            Operator::I8x16Add => Some((NeonOp::AddB, false)),
+            Operator::I16x8Add => Some((NeonOp::AddH, false)),
+            Operator::I32x4Add => Some((NeonOp::AddS, false)),
+            Operator::I64x2Add => Some((NeonOp::AddD, false)),
+            Operator::I8x16Sub => Some((NeonOp::SubB, false)),
+            Operator::I16x8Sub => Some((NeonOp::SubH, false)),
+            Operator::I32x4Sub => Some((NeonOp::SubS, false)),
+            Operator::I64x2Sub => Some((NeonOp::SubD, false)),
+            Operator::I16x8Mul => Some((NeonOp::MulH, false)),
+            Operator::I32x4Mul => Some((NeonOp::MulS, false)),
+            Operator::I8x16AddSatS => Some((NeonOp::SqaddB, false)),
+            Operator::I8x16AddSatU => Some((NeonOp::UqaddB, false)),
+            Operator::I16x8AddSatS => Some((NeonOp::SqaddH, false)),
+            Operator::I16x8AddSatU => Some((NeonOp::UqaddH, false)),
+            Operator::I8x16SubSatS => Some((NeonOp::SqsubB, false)),
+            Operator::I8x16SubSatU => Some((NeonOp::UqsubB, false)),
+            Operator::I16x8SubSatS => Some((NeonOp::SqsubH, false)),
+            Operator::I16x8SubSatU => Some((NeonOp::UqsubH, false)),
+            Operator::I8x16MinS => Some((NeonOp::SminB, false)),
+            Operator::I8x16MinU => Some((NeonOp::UminB, false)),
+            Operator::I8x16MaxS => Some((NeonOp::SmaxB, false)),
+            Operator::I8x16MaxU => Some((NeonOp::UmaxB, false)),
+            Operator::I16x8MinS => Some((NeonOp::SminH, false)),
+            Operator::I16x8MinU => Some((NeonOp::UminH, false)),
+            Operator::I16x8MaxS => Some((NeonOp::SmaxH, false)),
+            Operator::I16x8MaxU => Some((NeonOp::UmaxH, false)),
+            Operator::I32x4MinS => Some((NeonOp::SminS, false)),
+            Operator::I32x4MinU => Some((NeonOp::UminS, false)),
+            Operator::I32x4MaxS => Some((NeonOp::SmaxS, false)),
+            Operator::I32x4MaxU => Some((NeonOp::UmaxS, false)),
+            Operator::I8x16AvgrU => Some((NeonOp::UrhaddB, false)),
+            Operator::I16x8AvgrU => Some((NeonOp::UrhaddH, false)),
+            Operator::I16x8Q15MulrSatS => Some((NeonOp::SqrdmulhH, false)),
+            Operator::I8x16Eq => Some((NeonOp::CmeqB, false)),
+            Operator::I16x8Eq => Some((NeonOp::CmeqH, false)),
+            Operator::I32x4Eq => Some((NeonOp::CmeqS, false)),
+            Operator::I64x2Eq => Some((NeonOp::CmeqD, false)),
+            Operator::I8x16GtS => Some((NeonOp::CmgtB, false)),
+            Operator::I16x8GtS => Some((NeonOp::CmgtH, false)),
+            Operator::I32x4GtS => Some((NeonOp::CmgtS, false)),
+            Operator::I64x2GtS => Some((NeonOp::CmgtD, false)),
+            Operator::I8x16GeS => Some((NeonOp::CmgeB, false)),
+            Operator::I16x8GeS => Some((NeonOp::CmgeH, false)),
+            Operator::I32x4GeS => Some((NeonOp::CmgeS, false)),
+            Operator::I64x2GeS => Some((NeonOp::CmgeD, false)),
+            Operator::I8x16LtS => Some((NeonOp::CmgtB, true)),
+            Operator::I16x8LtS => Some((NeonOp::CmgtH, true)),
+            Operator::I32x4LtS => Some((NeonOp::CmgtS, true)),
+            Operator::I64x2LtS => Some((NeonOp::CmgtD, true)),
+            Operator::I8x16LeS => Some((NeonOp::CmgeB, true)),
+            Operator::I16x8LeS => Some((NeonOp::CmgeH, true)),
+            Operator::I32x4LeS => Some((NeonOp::CmgeS, true)),
+            Operator::I64x2LeS => Some((NeonOp::CmgeD, true)),
+            Operator::I8x16GtU => Some((NeonOp::CmhiB, false)),
+            Operator::I16x8GtU => Some((NeonOp::CmhiH, false)),
+            Operator::I32x4GtU => Some((NeonOp::CmhiS, false)),
+            Operator::I8x16GeU => Some((NeonOp::CmhsB, false)),
+            Operator::I16x8GeU => Some((NeonOp::CmhsH, false)),
+            Operator::I32x4GeU => Some((NeonOp::CmhsS, false)),
+            Operator::I8x16LtU => Some((NeonOp::CmhiB, true)),
+            Operator::I16x8LtU => Some((NeonOp::CmhiH, true)),
+            Operator::I32x4LtU => Some((NeonOp::CmhiS, true)),
+            Operator::I8x16LeU => Some((NeonOp::CmhsB, true)),
+            Operator::I16x8LeU => Some((NeonOp::CmhsH, true)),
+            Operator::I32x4LeU => Some((NeonOp::CmhsS, true)),
+            Operator::V128And => Some((NeonOp::And, false)),
+            Operator::V128Or => Some((NeonOp::Orr, false)),
+            Operator::V128Xor => Some((NeonOp::Eor, false)),
+            // `bic` computes `a & !b`.
+            Operator::V128AndNot => Some((NeonOp::Bic, false)),
+            Operator::F32x4Add => Some((NeonOp::FaddS, false)),
+            Operator::F64x2Add => Some((NeonOp::FaddD, false)),
+            Operator::F32x4Sub => Some((NeonOp::FsubS, false)),
+            Operator::F64x2Sub => Some((NeonOp::FsubD, false)),
+            Operator::F32x4Mul => Some((NeonOp::FmulS, false)),
+            Operator::F64x2Mul => Some((NeonOp::FmulD, false)),
+            Operator::F32x4Div => Some((NeonOp::FdivS, false)),
+            Operator::F64x2Div => Some((NeonOp::FdivD, false)),
+            // `fmin` and `fmax` propagate NaNs and order -0 below 0, as wasm does.
+            Operator::F32x4Min => Some((NeonOp::FminS, false)),
+            Operator::F64x2Min => Some((NeonOp::FminD, false)),
+            Operator::F32x4Max => Some((NeonOp::FmaxS, false)),
+            Operator::F64x2Max => Some((NeonOp::FmaxD, false)),
+            Operator::F32x4Eq => Some((NeonOp::FcmeqS, false)),
+            Operator::F64x2Eq => Some((NeonOp::FcmeqD, false)),
+            Operator::F32x4Gt => Some((NeonOp::FcmgtS, false)),
+            Operator::F64x2Gt => Some((NeonOp::FcmgtD, false)),
+            Operator::F32x4Ge => Some((NeonOp::FcmgeS, false)),
+            Operator::F64x2Ge => Some((NeonOp::FcmgeD, false)),
+            Operator::F32x4Lt => Some((NeonOp::FcmgtS, true)),
+            Operator::F64x2Lt => Some((NeonOp::FcmgtD, true)),
+            Operator::F32x4Le => Some((NeonOp::FcmgeS, true)),
+            Operator::F64x2Le => Some((NeonOp::FcmgeD, true)),
+            _ => None,
+        };
+        if let Some((neon, swap)) = simple {
+            return self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                let (a, b) = if swap { (r[1], r[0]) } else { (r[0], r[1]) };
+                this.assembler.emit_neon(neon, a, b, out)
+            });
+        }
+
+        // Widening multiplications of the low or high halves.
+        let ext_mul = match op {
+            Operator::I16x8ExtMulLowI8x16S => Some(NeonOp::SmullB),
+            Operator::I16x8ExtMulHighI8x16S => Some(NeonOp::Smull2B),
+            Operator::I16x8ExtMulLowI8x16U => Some(NeonOp::UmullB),
+            Operator::I16x8ExtMulHighI8x16U => Some(NeonOp::Umull2B),
+            Operator::I32x4ExtMulLowI16x8S => Some(NeonOp::SmullH),
+            Operator::I32x4ExtMulHighI16x8S => Some(NeonOp::Smull2H),
+            Operator::I32x4ExtMulLowI16x8U => Some(NeonOp::UmullH),
+            Operator::I32x4ExtMulHighI16x8U => Some(NeonOp::Umull2H),
+            Operator::I64x2ExtMulLowI32x4S => Some(NeonOp::SmullS),
+            Operator::I64x2ExtMulHighI32x4S => Some(NeonOp::Smull2S),
+            Operator::I64x2ExtMulLowI32x4U => Some(NeonOp::UmullS),
+            Operator::I64x2ExtMulHighI32x4U => Some(NeonOp::Umull2S),
+            _ => None,
+        };
+        if let Some(mul) = ext_mul {
+            return self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                this.assembler.emit_neon(mul, r[0], r[1], out)
+            });
+        }
+
+        // Narrowing of `a` to the low half and of `b` to the high half.
+        let narrow = match op {
+            Operator::I8x16NarrowI16x8S => Some((NeonUnop::SqxtnH, NeonUnop::Sqxtn2H)),
+            Operator::I8x16NarrowI16x8U => Some((NeonUnop::SqxtunH, NeonUnop::Sqxtun2H)),
+            Operator::I16x8NarrowI32x4S => Some((NeonUnop::SqxtnS, NeonUnop::Sqxtn2S)),
+            Operator::I16x8NarrowI32x4U => Some((NeonUnop::SqxtunS, NeonUnop::Sqxtun2S)),
+            _ => None,
+        };
+        if let Some((low, high)) = narrow {
+            return self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                this.assembler.emit_neon_unop(low, r[0], out)?;
+                this.assembler.emit_neon_unop(high, r[1], out)
+            });
+        }
+
+        match op {
+            Operator::I8x16Ne | Operator::I16x8Ne | Operator::I32x4Ne | Operator::I64x2Ne => {
+                let eq = match op {
+                    Operator::I8x16Ne => NeonOp::CmeqB,
+                    Operator::I16x8Ne => NeonOp::CmeqH,
+                    Operator::I32x4Ne => NeonOp::CmeqS,
+                    _ => NeonOp::CmeqD,
+                };
+                self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_neon(eq, r[0], r[1], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::Not, out, out)
+                })
+            }
+            Operator::F32x4Ne | Operator::F64x2Ne => {
+                let eq = if *op == Operator::F32x4Ne {
+                    NeonOp::FcmeqS
+                } else {
+                    NeonOp::FcmeqD
+                };
+                self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_neon(eq, r[0], r[1], out)?;
+                    this.assembler.emit_neon_unop(NeonUnop::Not, out, out)
+                })
+            }
+            Operator::F32x4PMin
+            | Operator::F64x2PMin
+            | Operator::F32x4PMax
+            | Operator::F64x2PMax => {
+                // `pmin` is `b < a ? b : a`, `pmax` is `a < b ? b : a`.
+                let gt = match op {
+                    Operator::F32x4PMin | Operator::F32x4PMax => NeonOp::FcmgtS,
+                    _ => NeonOp::FcmgtD,
+                };
+                let min = matches!(op, Operator::F32x4PMin | Operator::F64x2PMin);
+                self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                    let (a, b) = if min { (r[0], r[1]) } else { (r[1], r[0]) };
+                    this.assembler.emit_neon(gt, a, b, out)?;
+                    this.assembler.emit_neon(NeonOp::Bsl, r[1], r[0], out)
+                })
+            }
+            Operator::I32x4DotI16x8S => {
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    this.assembler.emit_neon(NeonOp::SmullH, r[0], r[1], s[0])?;
+                    this.assembler.emit_neon(NeonOp::Smull2H, r[0], r[1], out)?;
+                    this.assembler.emit_neon(NeonOp::AddpS, s[0], out, out)
+                })
+            }
+            Operator::I64x2Mul => self.emit_v128(&[loc_a, loc_b], ret, 2, |this, r, out, s| {
+                // The sum of the cross products of the 32-bit halves, shifted, plus the
+                // product of the low halves.
+                this.assembler.emit_neon_unop(NeonUnop::Rev64S, r[1], out)?;
+                this.assembler.emit_neon(NeonOp::MulS, out, r[0], out)?;
+                this.assembler.emit_neon_unop(NeonUnop::UaddlpS, out, out)?;
+                this.assembler
+                    .emit_neon_shift(NeonShift::ShlD, out, 32, out)?;
+                this.assembler.emit_neon_unop(NeonUnop::XtnD, r[0], s[0])?;
+                this.assembler.emit_neon_unop(NeonUnop::XtnD, r[1], s[1])?;
+                this.assembler.emit_neon(NeonOp::UmlalS, s[0], s[1], out)
+            }),
+            // `tbl` zeroes the lanes whose index is out of range.
+            Operator::I8x16Swizzle => self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                this.assembler.emit_tbl(false, r[0], r[1], out)
+            }),
+            Operator::I8x16Shuffle { lanes } => {
+                // Look the lanes of `a` up with `tbl`, which zeroes the lanes out of
+                // range, then those of `b` with `tbx`, which keeps them.
+                let mask = |from_b: bool| {
+                    u128::from_le_bytes(lanes.map(|lane| match (lane >= 16, from_b) {
+                        (false, false) => lane,
+                        (true, true) => lane - 16,
+                        _ => 0xFF,
+                    }))
+                };
+                let (mask_a, mask_b) = (mask(false), mask(true));
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    this.v128_load_const(mask_a, s[0])?;
+                    this.assembler.emit_tbl(false, r[0], s[0], out)?;
+                    if lanes.iter().any(|&lane| lane >= 16) {
+                        this.v128_load_const(mask_b, s[0])?;
+                        this.assembler.emit_tbl(true, r[1], s[0], out)?;
+                    }
+                    Ok(())
+                })
+            }
+            _ => codegen_error!("singlepass v128_binop unimplemented for {:?}", op),
+        }
+    }
+    fn v128_bitselect(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        mask: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        self.emit_v128(&[loc_a, loc_b, mask], ret, 0, |this, r, out, _| {
+            this.assembler.emit_neon_unop(NeonUnop::Mov, r[2], out)?;
+            this.assembler.emit_neon(NeonOp::Bsl, r[0], r[1], out)
+        })
+    }
+    fn v128_shift(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        count: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        // The shift by an immediate, and the one by a vector, whose right shifts are
+        // left shifts by a negative count.
+        let (sz, imm, by_vector, neg) = match op {
+            Operator::I8x16Shl => (Size::S8, NeonShift::ShlB, NeonOp::UshlB, None),
+            Operator::I16x8Shl => (Size::S16, NeonShift::ShlH, NeonOp::UshlH, None),
+            Operator::I32x4Shl => (Size::S32, NeonShift::ShlS, NeonOp::UshlS, None),
+            Operator::I64x2Shl => (Size::S64, NeonShift::ShlD, NeonOp::UshlD, None),
+            Operator::I8x16ShrS => (
+                Size::S8,
+                NeonShift::SshrB,
+                NeonOp::SshlB,
+                Some(NeonUnop::NegB),
+            ),
+            Operator::I16x8ShrS => (
+                Size::S16,
+                NeonShift::SshrH,
+                NeonOp::SshlH,
+                Some(NeonUnop::NegH),
+            ),
+            Operator::I32x4ShrS => (
+                Size::S32,
+                NeonShift::SshrS,
+                NeonOp::SshlS,
+                Some(NeonUnop::NegS),
+            ),
+            Operator::I64x2ShrS => (
+                Size::S64,
+                NeonShift::SshrD,
+                NeonOp::SshlD,
+                Some(NeonUnop::NegD),
+            ),
+            Operator::I8x16ShrU => (
+                Size::S8,
+                NeonShift::UshrB,
+                NeonOp::UshlB,
+                Some(NeonUnop::NegB),
+            ),
+            Operator::I16x8ShrU => (
+                Size::S16,
+                NeonShift::UshrH,
+                NeonOp::UshlH,
+                Some(NeonUnop::NegH),
+            ),
+            Operator::I32x4ShrU => (
+                Size::S32,
+                NeonShift::UshrS,
+                NeonOp::UshlS,
+                Some(NeonUnop::NegS),
+            ),
+            Operator::I64x2ShrU => (
+                Size::S64,
+                NeonShift::UshrD,
+                NeonOp::UshlD,
+                Some(NeonUnop::NegD),
+            ),
+            _ => codegen_error!("singlepass v128_shift unimplemented for {:?}", op),
+        };
+        let lane_bits = match sz {
+            Size::S8 => 8,
+            Size::S16 => 16,
+            Size::S32 => 32,
+            Size::S64 => 64,
+        };
+
+        if let Location::Imm32(n) = count {
+            let n = n % lane_bits;
+            return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                if n == 0 {
+                    this.assembler.emit_neon_unop(NeonUnop::Mov, r[0], out)
+                } else {
+                    this.assembler.emit_neon_shift(imm, r[0], n, out)
+                }
+            });
+        }
+
+        // The count goes to all the lanes of a register, masked to the lane width.
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(Size::S32, count, Location::GPR(tmp))?;
+        self.assembler.emit_and(
+            Size::S32,
+            Location::GPR(tmp),
+            Location::Imm32(lane_bits - 1),
+            Location::GPR(tmp),
+        )?;
+        self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+            this.assembler.emit_dup(sz, tmp, s[0])?;
+            if let Some(neg) = neg {
+                this.assembler.emit_neon_unop(neg, s[0], s[0])?;
+            }
+            this.assembler.emit_neon(by_vector, r[0], s[0], out)
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_test(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.emit_v128_read(&[loc], 2, |this, r, s| {
+            let a = r[0];
+            match op {
+                Operator::V128AnyTrue
+                | Operator::I8x16AllTrue
+                | Operator::I16x8AllTrue
+                | Operator::I32x4AllTrue
+                | Operator::I64x2AllTrue => {
+                    // Any true when the largest lane isn't zero, all true when the
+                    // smallest one isn't. 64-bit lanes are compared to zero first.
+                    let (reduce, cond) = match op {
+                        Operator::V128AnyTrue => (NeonReduce::UmaxvS, Condition::Ne),
+                        Operator::I8x16AllTrue => (NeonReduce::UminvB, Condition::Ne),
+                        Operator::I16x8AllTrue => (NeonReduce::UminvH, Condition::Ne),
+                        Operator::I32x4AllTrue => (NeonReduce::UminvS, Condition::Ne),
+                        _ => {
+                            this.v128_load_const(0, s[0])?;
+                            this.assembler.emit_neon(NeonOp::CmeqD, a, s[0], s[0])?;
+                            (NeonReduce::UmaxvS, Condition::Eq)
+                        }
+                    };
+                    let src = if *op == Operator::I64x2AllTrue {
+                        s[0]
+                    } else {
+                        a
+                    };
+                    this.assembler.emit_neon_reduce(reduce, src, s[1])?;
+                    this.assembler
+                        .emit_vmov_lane(Size::S32, false, s[1], 0, tmp)?;
+                    this.assembler
+                        .emit_cmp(Size::S32, Location::Imm8(0), Location::GPR(tmp))?;
+                    this.assembler
+                        .emit_cset(Size::S32, Location::GPR(tmp), cond)
+                }
+                Operator::I8x16Bitmask
+                | Operator::I16x8Bitmask
+                | Operator::I32x4Bitmask
+                | Operator::I64x2Bitmask => {
+                    // Spread the sign of each lane to all of its bits, keep a different
+                    // bit of each lane, and add the lanes up.
+                    let (sign, bits) = match op {
+                        Operator::I8x16Bitmask => {
+                            (NeonShift::SshrB, 0x8040_2010_0804_0201_8040_2010_0804_0201)
+                        }
+                        Operator::I16x8Bitmask => {
+                            (NeonShift::SshrH, 0x0080_0040_0020_0010_0008_0004_0002_0001)
+                        }
+                        Operator::I32x4Bitmask => {
+                            (NeonShift::SshrS, 0x0000_0008_0000_0004_0000_0002_0000_0001)
+                        }
+                        _ => (NeonShift::SshrD, 0x0000_0000_0000_0002_0000_0000_0000_0001),
+                    };
+                    let lane_bits = match op {
+                        Operator::I8x16Bitmask => 7,
+                        Operator::I16x8Bitmask => 15,
+                        Operator::I32x4Bitmask => 31,
+                        _ => 63,
+                    };
+                    this.assembler.emit_neon_shift(sign, a, lane_bits, s[0])?;
+                    this.v128_load_const(bits, s[1])?;
+                    this.assembler.emit_neon(NeonOp::And, s[0], s[1], s[0])?;
+                    let reduce = match op {
+                        Operator::I8x16Bitmask => {
+                            // Interleave the low and high halves, for the bits of the
+                            // high one to be added in the high bytes of 16-bit lanes.
+                            this.assembler.emit_ext(s[0], s[0], 8, s[1])?;
+                            this.assembler.emit_neon(NeonOp::Zip1B, s[0], s[1], s[0])?;
+                            NeonReduce::AddvH
+                        }
+                        Operator::I16x8Bitmask => NeonReduce::AddvH,
+                        Operator::I32x4Bitmask => NeonReduce::AddvS,
+                        _ => NeonReduce::AddpD,
+                    };
+                    this.assembler.emit_neon_reduce(reduce, s[0], s[0])?;
+                    this.assembler
+                        .emit_vmov_lane(Size::S32, false, s[0], 0, tmp)
+                }
+                _ => codegen_error!("singlepass v128_test unimplemented for {:?}", op),
+            }
+        })?;
+        self.move_location(Size::S32, Location::GPR(tmp), ret)?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_splat(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let sz = match op {
+            Operator::I8x16Splat => Size::S8,
+            Operator::I16x8Splat => Size::S16,
+            Operator::I32x4Splat | Operator::F32x4Splat => Size::S32,
+            Operator::I64x2Splat | Operator::F64x2Splat => Size::S64,
+            _ => codegen_error!("singlepass v128_splat unimplemented for {:?}", op),
+        };
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(word, loc, Location::GPR(tmp))?;
+        self.emit_v128(&[], ret, 0, |this, _, out, _| {
+            this.assembler.emit_dup(sz, tmp, out)
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_extract_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let (sz, lane, signed) = match *op {
+            Operator::I8x16ExtractLaneS { lane } => (Size::S8, lane, true),
+            Operator::I8x16ExtractLaneU { lane } => (Size::S8, lane, false),
+            Operator::I16x8ExtractLaneS { lane } => (Size::S16, lane, true),
+            Operator::I16x8ExtractLaneU { lane } => (Size::S16, lane, false),
+            Operator::I32x4ExtractLane { lane } | Operator::F32x4ExtractLane { lane } => {
+                (Size::S32, lane, false)
+            }
+            Operator::I64x2ExtractLane { lane } | Operator::F64x2ExtractLane { lane } => {
+                (Size::S64, lane, false)
+            }
+            _ => codegen_error!("singlepass v128_extract_lane unimplemented for {:?}", op),
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.emit_v128_read(&[loc], 0, |this, r, _| {
+            this.assembler.emit_vmov_lane(sz, signed, r[0], lane, tmp)
+        })?;
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        self.move_location(word, Location::GPR(tmp), ret)?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_replace_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        value: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let (sz, lane) = match *op {
+            Operator::I8x16ReplaceLane { lane } => (Size::S8, lane),
+            Operator::I16x8ReplaceLane { lane } => (Size::S16, lane),
+            Operator::I32x4ReplaceLane { lane } | Operator::F32x4ReplaceLane { lane } => {
+                (Size::S32, lane)
+            }
+            Operator::I64x2ReplaceLane { lane } | Operator::F64x2ReplaceLane { lane } => {
+                (Size::S64, lane)
+            }
+            _ => codegen_error!("singlepass v128_replace_lane unimplemented for {:?}", op),
+        };
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(word, value, Location::GPR(tmp))?;
+        self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+            this.assembler.emit_neon_unop(NeonUnop::Mov, r[0], out)?;
+            this.assembler.emit_ins(sz, tmp, lane, out)
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_load(
+        &mut self,
+        op: &Operator,
+        addr: Location,
+        memarg: &MemArg,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let value_size = match op {
+            Operator::V128Load { .. } => 16,
+            Operator::V128Load8x8S { .. }
+            | Operator::V128Load8x8U { .. }
+            | Operator::V128Load16x4S { .. }
+            | Operator::V128Load16x4U { .. }
+            | Operator::V128Load32x2S { .. }
+            | Operator::V128Load32x2U { .. }
+            | Operator::V128Load64Splat { .. }
+            | Operator::V128Load64Zero { .. } => 8,
+            Operator::V128Load8Splat { .. } => 1,
+            Operator::V128Load16Splat { .. } => 2,
+            Operator::V128Load32Splat { .. } | Operator::V128Load32Zero { .. } => 4,
+            _ => codegen_error!("singlepass v128_load unimplemented for {:?}", op),
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                this.emit_v128(&[], ret, 0, |this, _, out, _| {
+                    let mem = Location::Memory(addr, 0);
+                    let extend = match op {
+                        Operator::V128Load { .. } => return this.assembler.emit_ldrq(out, mem),
+                        // Loading the low lane of a register zeroes the others.
+                        Operator::V128Load32Zero { .. } => {
+                            return this.assembler.emit_ldr(Size::S32, Location::SIMD(out), mem);
+                        }
+                        Operator::V128Load64Zero { .. } => {
+                            return this.assembler.emit_ldr(Size::S64, Location::SIMD(out), mem);
+                        }
+                        Operator::V128Load8Splat { .. }
+                        | Operator::V128Load16Splat { .. }
+                        | Operator::V128Load32Splat { .. }
+                        | Operator::V128Load64Splat { .. } => {
+                            let sz = match value_size {
+                                1 => Size::S8,
+                                2 => Size::S16,
+                                4 => Size::S32,
+                                _ => Size::S64,
+                            };
+                            this.assembler.emit_ldr(sz, Location::GPR(addr), mem)?;
+                            return this.assembler.emit_dup(sz, addr, out);
+                        }
+                        Operator::V128Load8x8S { .. } => NeonUnop::SxtlB,
+                        Operator::V128Load8x8U { .. } => NeonUnop::UxtlB,
+                        Operator::V128Load16x4S { .. } => NeonUnop::SxtlH,
+                        Operator::V128Load16x4U { .. } => NeonUnop::UxtlH,
+                        Operator::V128Load32x2S { .. } => NeonUnop::SxtlS,
+                        _ => NeonUnop::UxtlS,
+                    };
+                    this.assembler
+                        .emit_ldr(Size::S64, Location::SIMD(out), mem)?;
+                    this.assembler.emit_neon_unop(extend, out, out)
+                })
+            },
+        )
+    }
+    fn v128_load_lane(
+        &mut self,
+        op: &Operator,
+        addr: Location,
+        memarg: &MemArg,
+        loc: Location,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let (sz, lane, value_size) = match *op {
+            Operator::V128Load8Lane { lane, .. } => (Size::S8, lane, 1),
+            Operator::V128Load16Lane { lane, .. } => (Size::S16, lane, 2),
+            Operator::V128Load32Lane { lane, .. } => (Size::S32, lane, 4),
+            Operator::V128Load64Lane { lane, .. } => (Size::S64, lane, 8),
+            _ => codegen_error!("singlepass v128_load_lane unimplemented for {:?}", op),
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                this.assembler
+                    .emit_ldr(sz, Location::GPR(addr), Location::Memory(addr, 0))?;
+                this.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_neon_unop(NeonUnop::Mov, r[0], out)?;
+                    this.assembler.emit_ins(sz, addr, lane, out)
+                })
+            },
+        )
+    }
+    fn v128_save(
+        &mut self,
+        op: &Operator,
+        value: Location,
+        memarg: &MemArg,
+        addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let lane = match *op {
+            Operator::V128Store { .. } => None,
+            Operator::V128Store8Lane { lane, .. } => Some((Size::S8, lane)),
+            Operator::V128Store16Lane { lane, .. } => Some((Size::S16, lane)),
+            Operator::V128Store32Lane { lane, .. } => Some((Size::S32, lane)),
+            Operator::V128Store64Lane { lane, .. } => Some((Size::S64, lane)),
+            _ => codegen_error!("singlepass v128_save unimplemented for {:?}", op),
+        };
+        let value_size = match lane {
+            None => 16,
+            Some((Size::S8, _)) => 1,
+            Some((Size::S16, _)) => 2,
+            Some((Size::S32, _)) => 4,
+            Some((Size::S64, _)) => 8,
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                this.emit_v128_read(&[value], 0, |this, r, _| {
+                    let mem = Location::Memory(addr, 0);
+                    let Some((sz, lane)) = lane else {
+                        return this.assembler.emit_strq(r[0], mem);
+                    };
+                    let tmp = this.acquire_temp_gpr().ok_or_else(|| {
+                        CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+                    })?;
+                    this.assembler.emit_vmov_lane(sz, false, r[0], lane, tmp)?;
+                    this.assembler.emit_str(sz, Location::GPR(tmp), mem)?;
+                    this.release_gpr(tmp);
+                    Ok(())
+                })
+            },
+        )
+    }
+
     fn gen_std_trampoline(
         &self,
         sig: &FunctionType,
@@ -8792,6 +9840,72 @@ mod test {
         Ok(())
     }
 
+    fn test_v128_binop(machine: &mut MachineARM64, op: &Operator) -> Result<(), CompileError> {
+        for (loc_a, loc_b, ret) in [
+            (NEON::V8, NEON::V9, NEON::V10),
+            (NEON::V8, NEON::V9, NEON::V8),
+            (NEON::V8, NEON::V9, NEON::V9),
+            (NEON::V8, NEON::V8, NEON::V8),
+        ] {
+            machine.v128_binop(
+                op,
+                Location::SIMD(loc_a),
+                Location::SIMD(loc_b),
+                Location::SIMD(ret),
+            )?;
+        }
+        machine.v128_binop(
+            op,
+            Location::SIMD(NEON::V8),
+            Location::Memory(GPR::X29, -16),
+            Location::SIMD(NEON::V9),
+        )?;
+        machine.v128_binop(
+            op,
+            Location::Memory(GPR::X29, -16),
+            Location::Memory(GPR::X29, -1024),
+            Location::Memory(GPR::X29, -48),
+        )?;
+        machine.v128_binop(
+            op,
+            Location::Memory(GPR::X29, 32),
+            Location::SIMD(NEON::V8),
+            Location::Memory(GPR::XzrSp, 0x10000),
+        )?;
+        assert!(machine.get_used_simd().is_empty());
+        assert!(machine.get_used_gprs().is_empty());
+
+        Ok(())
+    }
+
+    fn test_v128_unop(machine: &mut MachineARM64, op: &Operator) -> Result<(), CompileError> {
+        for (loc, ret) in [
+            (Location::SIMD(NEON::V8), Location::SIMD(NEON::V9)),
+            (Location::SIMD(NEON::V8), Location::SIMD(NEON::V8)),
+            (Location::Memory(GPR::X29, -16), Location::SIMD(NEON::V8)),
+            (Location::SIMD(NEON::V8), Location::Memory(GPR::X29, -1024)),
+        ] {
+            machine.v128_unop(op, loc, ret)?;
+        }
+        assert!(machine.get_used_simd().is_empty());
+        assert!(machine.get_used_gprs().is_empty());
+
+        Ok(())
+    }
+
+    /// Returns the code emitted by `f` on a new machine.
+    fn v128_code(
+        f: impl FnOnce(&mut MachineARM64) -> Result<(), CompileError>,
+    ) -> Result<Vec<u32>, CompileError> {
+        let mut machine = MachineARM64::new(None);
+        f(&mut machine)?;
+        Ok(machine
+            .assembler_finalize()?
+            .chunks(4)
+            .map(|insn| u32::from_le_bytes(insn.try_into().unwrap()))
+            .collect())
+    }
+
     #[test]
     fn tests_arm64() -> Result<(), CompileError> {
         let mut machine = MachineARM64::new(None);
@@ -8826,4 +9940,104 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn tests_arm64_v128() -> Result<(), CompileError> {
+        let mut machine = MachineARM64::new(None);
+
+        for op in [
+            Operator::I8x16Add,
+            Operator::I16x8Sub,
+            Operator::I32x4Mul,
+            Operator::I64x2Mul,
+            Operator::I8x16AddSatU,
+            Operator::I16x8Q15MulrSatS,
+            Operator::I32x4MinS,
+            Operator::I8x16AvgrU,
+            Operator::I16x8Ne,
+            Operator::I32x4LtU,
+            Operator::I64x2LeS,
+            Operator::V128AndNot,
+            Operator::I8x16NarrowI16x8U,
+            Operator::I32x4DotI16x8S,
+            Operator::I64x2ExtMulHighI32x4S,
+            Operator::I8x16Swizzle,
+            Operator::I8x16Shuffle {
+                lanes: [0, 17, 2, 19, 4, 21, 6, 23, 8, 25, 10, 27, 12, 29, 14, 31],
+            },
+            Operator::F32x4Div,
+            Operator::F64x2Max,
+            Operator::F32x4PMin,
+            Operator::F64x2PMax,
+            Operator::F32x4Le,
+            Operator::F64x2Ne,
+        ] {
+            test_v128_binop(&mut machine, &op)?;
+        }
+        for op in [
+            Operator::V128Not,
+            Operator::I8x16Popcnt,
+            Operator::I64x2Abs,
+            Operator::I16x8ExtAddPairwiseI8x16U,
+            Operator::I32x4ExtendHighI16x8S,
+            Operator::F32x4Nearest,
+            Operator::F64x2ConvertLowI32x4U,
+            Operator::I32x4TruncSatF64x2SZero,
+            Operator::F32x4DemoteF64x2Zero,
+        ] {
+            test_v128_unop(&mut machine, &op)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn tests_arm64_v128_in_registers() -> Result<(), CompileError> {
+        // add v10.4s, v8.4s, v9.4s
+        let code = v128_code(|m| {
+            m.v128_binop(
+                &Operator::I32x4Add,
+                Location::SIMD(NEON::V8),
+                Location::SIMD(NEON::V9),
+                Location::SIMD(NEON::V10),
+            )
+        })?;
+        assert_eq!(code, [0x4ea9850a]);
+
+        // The result overwriting an operand goes through a temporary register:
+        // fcmgt v0.2d, v9.2d, v8.2d; mov v8.16b, v0.16b
+        let code = v128_code(|m| {
+            m.v128_binop(
+                &Operator::F64x2Lt,
+                Location::SIMD(NEON::V8),
+                Location::SIMD(NEON::V9),
+                Location::SIMD(NEON::V8),
+            )
+        })?;
+        assert_eq!(code, [0x6ee8e520, 0x4ea01c08]);
+
+        // mov v11.16b, v10.16b; bsl v11.16b, v8.16b, v9.16b
+        let code = v128_code(|m| {
+            m.v128_bitselect(
+                Location::SIMD(NEON::V8),
+                Location::SIMD(NEON::V9),
+                Location::SIMD(NEON::V10),
+                Location::SIMD(NEON::V11),
+            )
+        })?;
+        assert_eq!(code, [0x4eaa1d4b, 0x6e691d0b]);
+
+        // ldr q0, [x29, #32]; sshr v1.4s, v0.4s, #3; str q1, [sp, #16]
+        let code = v128_code(|m| {
+            m.v128_shift(
+                &Operator::I32x4ShrS,
+                Location::Memory(GPR::X29, 32),
+                Location::Imm32(35),
+                Location::Memory(GPR::XzrSp, 16),
+            )
+        })?;
+        assert_eq!(code, [0x3dc00ba0, 0x4f3d0401, 0x3d8007e1]);
+
+        Ok(())
+    }
 }
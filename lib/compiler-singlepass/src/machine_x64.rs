@@ -20,7 +20,7 @@ use wasmer_compiler::{
         relocation::{Relocation, RelocationKind, RelocationTarget},
         section::{CustomSection, CustomSectionProtection, SectionBody},
     },
-    wasmparser::{MemArg, ValType as WpType},
+    wasmparser::{MemArg, Operator, ValType as WpType},
 };
 use wasmer_types::{
     CompileError, FunctionIndex, FunctionType, SourceLoc, TrapCode, TrapInformation, Type,
@@ -1856,6 +1856,200 @@ impl MachineX86_64 {
         self.emit_relaxed_binop(AssemblerX64::emit_xchg, sz, src, dst)
     }
 
+    /// Acquires an XMM register for the lowering of a `v128` operator, which is not
+    /// one of `busy`. The temporary registers are used first, then any unused one.
+    /// When none is left, a value register is spilled to the native stack until
+    /// `release_v128_temps` is called.
+    fn acquire_v128_temp(
+        &mut self,
+        busy: &[XMM],
+        temps: &mut Vec<(XMM, bool)>,
+    ) -> Result<XMM, CompileError> {
+        use XMM::*;
+        static REGS: &[XMM] = &[XMM0, XMM1, XMM2, XMM3, XMM4, XMM5, XMM6, XMM7];
+        if let Some(x) = REGS
+            .iter()
+            .find(|x| !self.used_simd_contains(x) && !busy.contains(x))
+        {
+            self.used_simd_insert(*x);
+            temps.push((*x, false));
+            return Ok(*x);
+        }
+        let Some(x) = REGS
+            .iter()
+            .find(|x| !busy.contains(x) && !temps.iter().any(|(t, _)| t == *x))
+        else {
+            codegen_error!("singlepass cannot acquire temp simd for v128");
+        };
+        self.assembler
+            .emit_sub(Size::S64, Location::Imm32(16), Location::GPR(GPR::RSP))?;
+        self.assembler
+            .emit_movdqu(XMMOrMemory::XMM(*x), XMMOrMemory::Memory(GPR::RSP, 0))?;
+        temps.push((*x, true));
+        Ok(*x)
+    }
+
+    /// Releases the registers acquired by `acquire_v128_temp`, restoring the spilled ones.
+    fn release_v128_temps(&mut self, temps: Vec<(XMM, bool)>) -> Result<(), CompileError> {
+        for (x, spilled) in temps.into_iter().rev() {
+            if spilled {
+                self.assembler
+                    .emit_movdqu(XMMOrMemory::Memory(GPR::RSP, 0), XMMOrMemory::XMM(x))?;
+                self.assembler
+                    .emit_add(Size::S64, Location::Imm32(16), Location::GPR(GPR::RSP))?;
+            } else {
+                self.release_simd(x);
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the `v128` operands in `srcs` in XMM registers, loading those in memory
+    /// into temporary registers.
+    fn v128_operands(
+        &mut self,
+        srcs: &[Location],
+        busy: &mut Vec<XMM>,
+        temps: &mut Vec<(XMM, bool)>,
+    ) -> Result<Vec<XMM>, CompileError> {
+        busy.extend(srcs.iter().filter_map(|loc| match loc {
+            Location::SIMD(x) => Some(*x),
+            _ => None,
+        }));
+        let mut regs = Vec::with_capacity(srcs.len());
+        for src in srcs {
+            let reg = match *src {
+                Location::SIMD(x) => x,
+                Location::Memory(_, _) => {
+                    let tmp = self.acquire_v128_temp(busy, temps)?;
+                    busy.push(tmp);
+                    self.move_v128(*src, Location::SIMD(tmp))?;
+                    tmp
+                }
+                _ => codegen_error!("singlepass can't use {:?} as a v128 operand", src),
+            };
+            regs.push(reg);
+        }
+        Ok(regs)
+    }
+
+    /// Acquires `n` scratch XMM registers.
+    fn v128_scratch(
+        &mut self,
+        n: usize,
+        busy: &mut Vec<XMM>,
+        temps: &mut Vec<(XMM, bool)>,
+    ) -> Result<Vec<XMM>, CompileError> {
+        let mut scratch = Vec::with_capacity(n);
+        for _ in 0..n {
+            let tmp = self.acquire_v128_temp(busy, temps)?;
+            busy.push(tmp);
+            scratch.push(tmp);
+        }
+        Ok(scratch)
+    }
+
+    /// Lowers an operator producing a `v128` to `ret` from the `v128` operands in `srcs`.
+    ///
+    /// `f` gets the operands in XMM registers, which it must not modify, the register
+    /// receiving the result, which is none of the operands, and `n_scratch` scratch
+    /// registers.
+    fn emit_v128<F>(
+        &mut self,
+        srcs: &[Location],
+        ret: Location,
+        n_scratch: usize,
+        f: F,
+    ) -> Result<(), CompileError>
+    where
+        F: FnOnce(&mut Self, &[XMM], XMM, &[XMM]) -> Result<(), CompileError>,
+    {
+        let mut busy = vec![];
+        if let Location::SIMD(x) = ret {
+            busy.push(x);
+        }
+        let mut temps = vec![];
+        let regs = self.v128_operands(srcs, &mut busy, &mut temps)?;
+        let out = match ret {
+            Location::SIMD(x) if !regs.contains(&x) => x,
+            _ => {
+                let tmp = self.acquire_v128_temp(&busy, &mut temps)?;
+                busy.push(tmp);
+                tmp
+            }
+        };
+        let scratch = self.v128_scratch(n_scratch, &mut busy, &mut temps)?;
+        f(self, &regs, out, &scratch)?;
+        self.move_v128(Location::SIMD(out), ret)?;
+        self.release_v128_temps(temps)
+    }
+
+    /// Like `emit_v128`, for operators only reading `v128` values.
+    fn emit_v128_read<F>(
+        &mut self,
+        srcs: &[Location],
+        n_scratch: usize,
+        f: F,
+    ) -> Result<(), CompileError>
+    where
+        F: FnOnce(&mut Self, &[XMM], &[XMM]) -> Result<(), CompileError>,
+    {
+        let mut busy = vec![];
+        let mut temps = vec![];
+        let regs = self.v128_operands(srcs, &mut busy, &mut temps)?;
+        let scratch = self.v128_scratch(n_scratch, &mut busy, &mut temps)?;
+        f(self, &regs, &scratch)?;
+        self.release_v128_temps(temps)
+    }
+
+    /// Loads a `v128` constant into `dst`.
+    fn v128_load_const(&mut self, value: u128, dst: XMM) -> Result<(), CompileError> {
+        if value == 0 {
+            return self.assembler.emit_packed(PackedOp::Pxor, dst, dst, dst);
+        }
+        if value == u128::MAX {
+            return self.assembler.emit_packed(PackedOp::Pcmpeqd, dst, dst, dst);
+        }
+        let (low, high) = (value as u64, (value >> 64) as u64);
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.assembler
+            .emit_mov(Size::S64, Location::Imm64(low), Location::GPR(tmp))?;
+        self.assembler
+            .emit_mov(Size::S64, Location::GPR(tmp), Location::SIMD(dst))?;
+        if high == low {
+            self.assembler
+                .emit_packed(PackedOp::Punpcklqdq, dst, dst, dst)?;
+        } else {
+            self.assembler
+                .emit_mov(Size::S64, Location::Imm64(high), Location::GPR(tmp))?;
+            self.assembler.emit_pinsr(Size::S64, dst, tmp, 1, dst)?;
+        }
+        self.release_gpr(tmp);
+        Ok(())
+    }
+
+    /// Copies the lowest lane of `x` to all of its lanes. `tmp` is clobbered.
+    fn v128_broadcast(&mut self, sz: Size, x: XMM, tmp: XMM) -> Result<(), CompileError> {
+        match sz {
+            Size::S8 => {
+                self.assembler.emit_packed(PackedOp::Pxor, tmp, tmp, tmp)?;
+                self.assembler.emit_packed(PackedOp::Pshufb, x, tmp, x)
+            }
+            Size::S16 => {
+                self.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Pshuflw, x, 0, x)?;
+                self.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Pshufd, x, 0, x)
+            }
+            Size::S32 => self
+                .assembler
+                .emit_packed_unop_imm(PackedUnopImm::Pshufd, x, 0, x),
+            Size::S64 => self.assembler.emit_packed(PackedOp::Punpcklqdq, x, x, x),
+        }
+    }
+
     fn used_gprs_contains(&self, r: &GPR) -> bool {
         self.used_gprs & (1 << r.into_index()) != 0
     }
@@ -2020,29 +2214,28 @@ impl Machine for MachineX86_64 {
     }
 
     fn push_used_simd(&mut self, used_xmms: &[XMM]) -> Result<usize, CompileError> {
-        self.adjust_stack((used_xmms.len() * 8) as u32)?;
+        // The registers may hold `v128` values, so they are saved whole.
+        self.adjust_stack((used_xmms.len() * 16) as u32)?;
 
         for (i, r) in used_xmms.iter().enumerate() {
-            self.move_location(
-                Size::S64,
-                Location::SIMD(*r),
-                Location::Memory(GPR::RSP, (i * 8) as i32),
+            self.assembler.emit_movdqu(
+                XMMOrMemory::XMM(*r),
+                XMMOrMemory::Memory(GPR::RSP, (i * 16) as i32),
             )?;
         }
 
-        Ok(used_xmms.len() * 8)
+        Ok(used_xmms.len() * 16)
     }
     fn pop_used_simd(&mut self, used_xmms: &[XMM]) -> Result<(), CompileError> {
         for (i, r) in used_xmms.iter().enumerate() {
-            self.move_location(
-                Size::S64,
-                Location::Memory(GPR::RSP, (i * 8) as i32),
-                Location::SIMD(*r),
+            self.assembler.emit_movdqu(
+                XMMOrMemory::Memory(GPR::RSP, (i * 16) as i32),
+                XMMOrMemory::XMM(*r),
             )?;
         }
         self.assembler.emit_add(
             Size::S64,
-            Location::Imm32((used_xmms.len() * 8) as u32),
+            Location::Imm32((used_xmms.len() * 16) as u32),
             Location::GPR(GPR::RSP),
         )
     }
@@ -2262,7 +2455,7 @@ impl Machine for MachineX86_64 {
         &self,
         idx: usize,
         _sz: Size,
-        _stack_location: &mut usize,
+        stack_location: &mut usize,
         calling_convention: CallingConvention,
     ) -> Location {
         match calling_convention {
@@ -2271,7 +2464,11 @@ impl Machine for MachineX86_64 {
                 1 => Location::GPR(GPR::RDX),
                 2 => Location::GPR(GPR::R8),
                 3 => Location::GPR(GPR::R9),
-                _ => Location::Memory(GPR::RBP, (32 + 16 + (idx - 4) * 8) as i32),
+                _ => {
+                    let loc = Location::Memory(GPR::RBP, (32 + 16 + *stack_location) as i32);
+                    *stack_location += 8;
+                    loc
+                }
             },
             _ => match idx {
                 0 => Location::GPR(GPR::RDI),
@@ -2280,7 +2477,11 @@ impl Machine for MachineX86_64 {
                 3 => Location::GPR(GPR::RCX),
                 4 => Location::GPR(GPR::R8),
                 5 => Location::GPR(GPR::R9),
-                _ => Location::Memory(GPR::RBP, (16 + (idx - 6) * 8) as i32),
+                _ => {
+                    let loc = Location::Memory(GPR::RBP, (16 + *stack_location) as i32);
+                    *stack_location += 8;
+                    loc
+                }
             },
         }
     }
@@ -2379,6 +2580,13 @@ impl Machine for MachineX86_64 {
             | Location::Memory2(_, _, _, _)
             | Location::Imm32(_)
             | Location::Imm64(_) => match size_val {
+                Size::S32
+                    if signed
+                        && size_op == Size::S64
+                        && matches!(source, Location::GPR(_) | Location::Memory(_, _)) =>
+                {
+                    self.assembler.emit_movsx(size_val, source, size_op, dst)
+                }
                 Size::S32 | Size::S64 => self.assembler.emit_mov(size_val, source, dst),
                 Size::S16 | Size::S8 => {
                     if signed {
@@ -2493,7 +2701,9 @@ impl Machine for MachineX86_64 {
         canonicalize: bool,
         loc: Location,
     ) -> Result<(), CompileError> {
-        if canonicalize {
+        if ty == WpType::V128 {
+            self.move_v128(loc, Location::SIMD(XMM::XMM0))
+        } else if canonicalize {
             self.canonicalize_nan(
                 match ty {
                     WpType::F32 => Size::S32,
@@ -2617,6 +2827,58 @@ impl Machine for MachineX86_64 {
         XMM::XMM0
     }
 
+    fn get_results_registers(
+        &self,
+        tys: &[Type],
+        layout: &ResultsLayout,
+        calling_convention: CallingConvention,
+    ) -> Option<ResultsRegisters<GPR, XMM>> {
+        match calling_convention {
+            // Only structs of 1, 2, 4 or 8 bytes are returned in RAX.
+            CallingConvention::WindowsFastcall => {
+                (layout.size == 8).then(|| vec![(Location::GPR(GPR::RAX), 0, Size::S64)])
+            }
+            // Each eightbyte of a struct of up to 16 bytes goes to the next XMM register
+            // if it only holds floats, or else to the next general purpose one.
+            _ => {
+                if layout.size > 16 {
+                    return None;
+                }
+                let mut gprs = [GPR::RAX, GPR::RDX].into_iter();
+                let mut xmms = [XMM::XMM0, XMM::XMM1].into_iter();
+                let parts = (0..layout.size)
+                    .step_by(8)
+                    .map(|offset| {
+                        let only_floats = tys.iter().zip(&layout.offsets).all(|(ty, field)| {
+                            *field + ResultsLayout::field_size(*ty) <= offset
+                                || *field >= offset + 8
+                                || *ty == Type::F32
+                                || *ty == Type::F64
+                        });
+                        let reg = if only_floats {
+                            Location::SIMD(xmms.next().unwrap())
+                        } else {
+                            Location::GPR(gprs.next().unwrap())
+                        };
+                        (reg, offset, Size::S64)
+                    })
+                    .collect();
+                Some(parts)
+            }
+        }
+    }
+
+    fn get_results_address_reg(&self, calling_convention: CallingConvention) -> GPR {
+        match calling_convention {
+            CallingConvention::WindowsFastcall => GPR::RCX,
+            _ => GPR::RDI,
+        }
+    }
+
+    fn results_address_is_first_param(&self) -> bool {
+        true
+    }
+
     fn arch_requires_indirect_call_trampoline(&self) -> bool {
         self.assembler.arch_requires_indirect_call_trampoline()
     }
@@ -7790,189 +8052,1542 @@ impl Machine for MachineX86_64 {
         self.emit_relaxed_avx(AssemblerX64::emit_vdivss, loc_a, loc_b, ret)
     }
 
-    fn gen_std_trampoline(
+    fn get_simd_param_location(
         &self,
-        sig: &FunctionType,
+        idx: usize,
+        stack_location: &mut usize,
         calling_convention: CallingConvention,
-    ) -> Result<FunctionBody, CompileError> {
-        // the cpu feature here is irrelevant
-        let mut a = AssemblerX64::new(0, None)?;
-
-        // Calculate stack offset.
-        let mut stack_offset: u32 = 0;
-        for (i, _param) in sig.params().iter().enumerate() {
-            if let Location::Memory(_, _) =
-                self.get_simple_param_location(1 + i, calling_convention)
-            {
-                stack_offset += 8;
-            }
+    ) -> Location {
+        let regs = match calling_convention {
+            CallingConvention::WindowsFastcall => 4,
+            _ => 8,
+        };
+        if idx < regs {
+            Location::SIMD(XMM::from_index(idx).unwrap())
+        } else {
+            *stack_location = stack_location.next_multiple_of(16);
+            let loc = Location::Memory(GPR::RSP, *stack_location as i32);
+            *stack_location += 16;
+            loc
         }
-        let stack_padding: u32 = match calling_convention {
-            CallingConvention::WindowsFastcall => 32,
-            _ => 0,
+    }
+    fn get_simd_call_param_location(
+        &self,
+        idx: usize,
+        stack_location: &mut usize,
+        calling_convention: CallingConvention,
+    ) -> Location {
+        let (regs, base) = match calling_convention {
+            CallingConvention::WindowsFastcall => (4, 32 + 16),
+            _ => (8, 16),
         };
-
-        // Align to 16 bytes. We push two 8-byte registers below, so here we need to ensure stack_offset % 16 == 8.
-        if stack_offset % 16 != 8 {
-            stack_offset += 8;
+        if idx < regs {
+            Location::SIMD(XMM::from_index(idx).unwrap())
+        } else {
+            *stack_location = stack_location.next_multiple_of(16);
+            let loc = Location::Memory(GPR::RBP, (base + *stack_location) as i32);
+            *stack_location += 16;
+            loc
         }
-
-        // Used callee-saved registers
-        a.emit_push(Size::S64, Location::GPR(GPR::R15))?;
-        a.emit_push(Size::S64, Location::GPR(GPR::R14))?;
-
-        // Prepare stack space.
-        a.emit_sub(
-            Size::S64,
-            Location::Imm32(stack_offset + stack_padding),
-            Location::GPR(GPR::RSP),
-        )?;
-
-        // Arguments
-        a.emit_mov(
-            Size::S64,
-            self.get_simple_param_location(1, calling_convention),
-            Location::GPR(GPR::R15),
-        )?; // func_ptr
-        a.emit_mov(
-            Size::S64,
-            self.get_simple_param_location(2, calling_convention),
-            Location::GPR(GPR::R14),
-        )?; // args_rets
-
-        // Move arguments to their locations.
-        // `callee_vmctx` is already in the first argument register, so no need to move.
-        {
-            let mut n_stack_args: usize = 0;
-            for (i, _param) in sig.params().iter().enumerate() {
-                let src_loc = Location::Memory(GPR::R14, (i * 16) as _); // args_rets[i]
-                let dst_loc = self.get_simple_param_location(1 + i, calling_convention);
-
-                match dst_loc {
-                    Location::GPR(_) => {
-                        a.emit_mov(Size::S64, src_loc, dst_loc)?;
-                    }
-                    Location::Memory(_, _) => {
-                        // This location is for reading arguments but we are writing arguments here.
-                        // So recalculate it.
-                        a.emit_mov(Size::S64, src_loc, Location::GPR(GPR::RAX))?;
-                        a.emit_mov(
+    }
+    fn move_v128(&mut self, src: Location, dst: Location) -> Result<(), CompileError> {
+        match (src, dst) {
+            (Location::SIMD(x), Location::SIMD(y)) => self
+                .assembler
+                .emit_movdqu(XMMOrMemory::XMM(x), XMMOrMemory::XMM(y)),
+            (Location::Memory(base, disp), Location::SIMD(y)) => self
+                .assembler
+                .emit_movdqu(XMMOrMemory::Memory(base, disp), XMMOrMemory::XMM(y)),
+            (Location::SIMD(x), Location::Memory(base, disp)) => self
+                .assembler
+                .emit_movdqu(XMMOrMemory::XMM(x), XMMOrMemory::Memory(base, disp)),
+            (Location::Memory(src_base, src_disp), Location::Memory(dst_base, dst_disp)) => {
+                if src != dst {
+                    let tmp = self.pick_temp_gpr().ok_or_else(|| {
+                        CompileError::Codegen("singlepass can't pick a temp gpr".to_owned())
+                    })?;
+                    for half in [0, 8] {
+                        self.assembler.emit_mov(
                             Size::S64,
-                            Location::GPR(GPR::RAX),
-                            Location::Memory(
-                                GPR::RSP,
-                                (stack_padding as usize + n_stack_args * 8) as _,
-                            ),
+                            Location::Memory(src_base, src_disp + half),
+                            Location::GPR(tmp),
+                        )?;
+                        self.assembler.emit_mov(
+                            Size::S64,
+                            Location::GPR(tmp),
+                            Location::Memory(dst_base, dst_disp + half),
                         )?;
-                        n_stack_args += 1;
                     }
-                    _ => codegen_error!("singlepass gen_std_trampoline unreachable"),
                 }
+                Ok(())
             }
+            _ => codegen_error!("singlepass can't move v128 {:?} to {:?}", src, dst),
         }
-
-        // Call.
-        a.emit_call_location(Location::GPR(GPR::R15))?;
-
-        // Restore stack.
-        a.emit_add(
-            Size::S64,
-            Location::Imm32(stack_offset + stack_padding),
-            Location::GPR(GPR::RSP),
-        )?;
-
-        // Write return value.
-        if !sig.results().is_empty() {
-            a.emit_mov(
-                Size::S64,
-                Location::GPR(GPR::RAX),
-                Location::Memory(GPR::R14, 0),
-            )?;
-        }
-
-        // Restore callee-saved registers.
-        a.emit_pop(Size::S64, Location::GPR(GPR::R14))?;
-        a.emit_pop(Size::S64, Location::GPR(GPR::R15))?;
-
-        a.emit_ret()?;
-
-        let mut body = a.finalize().unwrap();
-        body.shrink_to_fit();
-        Ok(FunctionBody {
-            body,
-            unwind_info: None,
+    }
+    fn v128_const(&mut self, value: u128, ret: Location) -> Result<(), CompileError> {
+        self.emit_v128(&[], ret, 0, |this, _, out, _| {
+            this.v128_load_const(value, out)
         })
     }
-    // Generates dynamic import function call trampoline for a function type.
-    fn gen_std_dynamic_import_trampoline(
-        &self,
-        vmoffsets: &VMOffsets,
-        sig: &FunctionType,
-        calling_convention: CallingConvention,
-    ) -> Result<FunctionBody, CompileError> {
-        // the cpu feature here is irrelevant
-        let mut a = AssemblerX64::new(0, None)?;
-
-        // Allocate argument array.
-        let stack_offset: usize = 16 * std::cmp::max(sig.params().len(), sig.results().len()) + 8; // 16 bytes each + 8 bytes sysv call padding
-        let stack_padding: usize = match calling_convention {
-            CallingConvention::WindowsFastcall => 32,
-            _ => 0,
+    fn v128_unop(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let unop = match op {
+            Operator::I8x16Abs => Some(PackedUnop::Pabsb),
+            Operator::I16x8Abs => Some(PackedUnop::Pabsw),
+            Operator::I32x4Abs => Some(PackedUnop::Pabsd),
+            Operator::I16x8ExtendLowI8x16S | Operator::I16x8ExtendHighI8x16S => {
+                Some(PackedUnop::Pmovsxbw)
+            }
+            Operator::I16x8ExtendLowI8x16U | Operator::I16x8ExtendHighI8x16U => {
+                Some(PackedUnop::Pmovzxbw)
+            }
+            Operator::I32x4ExtendLowI16x8S | Operator::I32x4ExtendHighI16x8S => {
+                Some(PackedUnop::Pmovsxwd)
+            }
+            Operator::I32x4ExtendLowI16x8U | Operator::I32x4ExtendHighI16x8U => {
+                Some(PackedUnop::Pmovzxwd)
+            }
+            Operator::I64x2ExtendLowI32x4S | Operator::I64x2ExtendHighI32x4S => {
+                Some(PackedUnop::Pmovsxdq)
+            }
+            Operator::I64x2ExtendLowI32x4U | Operator::I64x2ExtendHighI32x4U => {
+                Some(PackedUnop::Pmovzxdq)
+            }
+            Operator::F32x4Sqrt => Some(PackedUnop::Sqrtps),
+            Operator::F64x2Sqrt => Some(PackedUnop::Sqrtpd),
+            Operator::F32x4ConvertI32x4S => Some(PackedUnop::Cvtdq2ps),
+            Operator::F64x2ConvertLowI32x4S => Some(PackedUnop::Cvtdq2pd),
+            Operator::F32x4DemoteF64x2Zero => Some(PackedUnop::Cvtpd2ps),
+            Operator::F64x2PromoteLowF32x4 => Some(PackedUnop::Cvtps2pd),
+            _ => None,
         };
-        a.emit_sub(
-            Size::S64,
-            Location::Imm32((stack_offset + stack_padding) as _),
-            Location::GPR(GPR::RSP),
-        )?;
-
-        // Copy arguments.
-        if !sig.params().is_empty() {
-            let mut argalloc = ArgumentRegisterAllocator::default();
-            argalloc.next(Type::I64, calling_convention).unwrap(); // skip VMContext
+        if let Some(unop) = unop {
+            let high = matches!(
+                op,
+                Operator::I16x8ExtendHighI8x16S
+                    | Operator::I16x8ExtendHighI8x16U
+                    | Operator::I32x4ExtendHighI16x8S
+                    | Operator::I32x4ExtendHighI16x8U
+                    | Operator::I64x2ExtendHighI32x4S
+                    | Operator::I64x2ExtendHighI32x4U
+            );
+            return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                if high {
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Pshufd, r[0], 0xEE, out)?;
+                    this.assembler.emit_packed_unop(unop, out, out)
+                } else {
+                    this.assembler.emit_packed_unop(unop, r[0], out)
+                }
+            });
+        }
 
-            let mut stack_param_count: usize = 0;
+        // Rounding mode of `roundps` and `roundpd`.
+        let round = match op {
+            Operator::F32x4Nearest | Operator::F64x2Nearest => Some(0),
+            Operator::F32x4Floor | Operator::F64x2Floor => Some(1),
+            Operator::F32x4Ceil | Operator::F64x2Ceil => Some(2),
+            Operator::F32x4Trunc | Operator::F64x2Trunc => Some(3),
+            _ => None,
+        };
+        if let Some(mode) = round {
+            let unop = match op {
+                Operator::F32x4Nearest
+                | Operator::F32x4Floor
+                | Operator::F32x4Ceil
+                | Operator::F32x4Trunc => PackedUnopImm::Roundps,
+                _ => PackedUnopImm::Roundpd,
+            };
+            return self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                this.assembler.emit_packed_unop_imm(unop, r[0], mode, out)
+            });
+        }
 
-            for (i, ty) in sig.params().iter().enumerate() {
-                let source_loc = match argalloc.next(*ty, calling_convention)? {
-                    Some(X64Register::GPR(gpr)) => Location::GPR(gpr),
-                    Some(X64Register::XMM(xmm)) => Location::SIMD(xmm),
-                    None => {
-                        a.emit_mov(
-                            Size::S64,
-                            Location::Memory(
-                                GPR::RSP,
-                                (stack_padding * 2 + stack_offset + 8 + stack_param_count * 8) as _,
-                            ),
-                            Location::GPR(GPR::RAX),
-                        )?;
-                        stack_param_count += 1;
-                        Location::GPR(GPR::RAX)
-                    }
+        match op {
+            Operator::V128Not => self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                this.v128_load_const(u128::MAX, out)?;
+                this.assembler.emit_packed(PackedOp::Pxor, out, r[0], out)
+            }),
+            Operator::I8x16Neg | Operator::I16x8Neg | Operator::I32x4Neg | Operator::I64x2Neg => {
+                let sub = match op {
+                    Operator::I8x16Neg => PackedOp::Psubb,
+                    Operator::I16x8Neg => PackedOp::Psubw,
+                    Operator::I32x4Neg => PackedOp::Psubd,
+                    _ => PackedOp::Psubq,
                 };
-                a.emit_mov(
-                    Size::S64,
-                    source_loc,
-                    Location::Memory(GPR::RSP, (stack_padding + i * 16) as _),
-                )?;
-
-                // Zero upper 64 bits.
-                a.emit_mov(
-                    Size::S64,
-                    Location::Imm32(0),
-                    Location::Memory(GPR::RSP, (stack_padding + i * 16 + 8) as _),
-                )?;
+                self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.v128_load_const(0, out)?;
+                    this.assembler.emit_packed(sub, out, r[0], out)
+                })
             }
-        }
-
-        match calling_convention {
-            CallingConvention::WindowsFastcall => {
-                // Load target address.
-                a.emit_mov(
-                    Size::S64,
-                    Location::Memory(
-                        GPR::RCX,
-                        vmoffsets.vmdynamicfunction_import_context_address() as i32,
-                    ),
+            Operator::I64x2Abs => self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                // Sign of each lane, from its high half.
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Pshufd, r[0], 0xF5, s[0])?;
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psrad, s[0], 31, s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pxor, r[0], s[0], out)?;
+                this.assembler.emit_packed(PackedOp::Psubq, out, s[0], out)
+            }),
+            Operator::I8x16Popcnt => self.emit_v128(&[loc], ret, 2, |this, r, out, s| {
+                // Look the population count of each nibble up in a table.
+                let table = u128::from_le_bytes([0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4]);
+                this.v128_load_const(u128::from_le_bytes([0x0F; 16]), s[0])?;
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psrlw, r[0], 4, s[1])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pand, s[1], s[0], s[1])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pand, s[0], r[0], s[0])?;
+                this.v128_load_const(table, out)?;
+                this.assembler
+                    .emit_packed(PackedOp::Pshufb, out, s[0], out)?;
+                this.v128_load_const(table, s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pshufb, s[0], s[1], s[0])?;
+                this.assembler.emit_packed(PackedOp::Paddb, out, s[0], out)
+            }),
+            Operator::I16x8ExtAddPairwiseI8x16S => {
+                self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.v128_load_const(u128::from_le_bytes([1; 16]), out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pmaddubsw, out, r[0], out)
+                })
+            }
+            Operator::I16x8ExtAddPairwiseI8x16U => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    this.v128_load_const(u128::from_le_bytes([1; 16]), s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pmaddubsw, r[0], s[0], out)
+                })
+            }
+            Operator::I32x4ExtAddPairwiseI16x8S => {
+                self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.v128_load_const(0x0001_0001_0001_0001_0001_0001_0001_0001, out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pmaddwd, out, r[0], out)
+                })
+            }
+            Operator::I32x4ExtAddPairwiseI16x8U => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler
+                        .emit_packed_imm(PackedImmOp::Pblendw, r[0], s[0], 0xAA, out)?;
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Psrld, r[0], 16, s[0])?;
+                    this.assembler.emit_packed(PackedOp::Paddd, out, s[0], out)
+                })
+            }
+            Operator::F32x4Abs | Operator::F64x2Abs => {
+                let (shift, and) = if *op == Operator::F32x4Abs {
+                    (PackedUnopImm::Psrld, PackedOp::Andps)
+                } else {
+                    (PackedUnopImm::Psrlq, PackedOp::Andps)
+                };
+                self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.v128_load_const(u128::MAX, out)?;
+                    this.assembler.emit_packed_unop_imm(shift, out, 1, out)?;
+                    this.assembler.emit_packed(and, out, r[0], out)
+                })
+            }
+            Operator::F32x4Neg | Operator::F64x2Neg => {
+                let (shift, bits) = if *op == Operator::F32x4Neg {
+                    (PackedUnopImm::Pslld, 31)
+                } else {
+                    (PackedUnopImm::Psllq, 63)
+                };
+                self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.v128_load_const(u128::MAX, out)?;
+                    this.assembler.emit_packed_unop_imm(shift, out, bits, out)?;
+                    this.assembler.emit_packed(PackedOp::Xorps, out, r[0], out)
+                })
+            }
+            Operator::F32x4ConvertI32x4U => self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                // Convert the low 16 bits exactly, and the high ones halved, as the
+                // conversion is signed.
+                this.v128_load_const(0, s[0])?;
+                this.assembler
+                    .emit_packed_imm(PackedImmOp::Pblendw, s[0], r[0], 0x55, s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Psubd, r[0], s[0], out)?;
+                this.assembler
+                    .emit_packed_unop(PackedUnop::Cvtdq2ps, s[0], s[0])?;
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psrld, out, 1, out)?;
+                this.assembler
+                    .emit_packed_unop(PackedUnop::Cvtdq2ps, out, out)?;
+                this.assembler.emit_packed(PackedOp::Addps, out, out, out)?;
+                this.assembler.emit_packed(PackedOp::Addps, out, s[0], out)
+            }),
+            Operator::F64x2ConvertLowI32x4U => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    // Build the doubles 2^52 + x, and subtract 2^52.
+                    this.v128_load_const(0x43300000_43300000_43300000_43300000, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Unpcklps, r[0], s[0], out)?;
+                    this.v128_load_const(0x43300000_00000000_43300000_00000000, s[0])?;
+                    this.assembler.emit_packed(PackedOp::Subpd, out, s[0], out)
+                })
+            }
+            Operator::I32x4TruncSatF32x4S => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    // Zero the NaN lanes, and fix the lanes overflowing to i32::MIN
+                    // which should be i32::MAX.
+                    this.assembler
+                        .emit_packed_imm(PackedImmOp::Cmpps, r[0], r[0], 0, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Andps, r[0], s[0], out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pxor, s[0], out, s[0])?;
+                    this.assembler
+                        .emit_packed_unop(PackedUnop::Cvttps2dq, out, out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pand, s[0], out, s[0])?;
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Psrad, s[0], 31, s[0])?;
+                    this.assembler.emit_packed(PackedOp::Pxor, out, s[0], out)
+                })
+            }
+            Operator::I32x4TruncSatF32x4U => {
+                self.emit_v128(&[loc], ret, 2, |this, r, out, s| {
+                    // Clamp the negative and NaN lanes to 0.
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Maxps, r[0], s[0], out)?;
+                    // 2^31
+                    this.v128_load_const(0x4F000000_4F000000_4F000000_4F000000, s[0])?;
+                    // The lanes at or above 2^31 are converted minus 2^31, saturated to
+                    // u32::MAX if still too large, and added back.
+                    this.assembler
+                        .emit_packed(PackedOp::Subps, out, s[0], s[1])?;
+                    this.assembler
+                        .emit_packed_imm(PackedImmOp::Cmpps, s[0], s[1], 2, s[0])?;
+                    this.assembler
+                        .emit_packed_unop(PackedUnop::Cvttps2dq, s[1], s[1])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pxor, s[1], s[0], s[1])?;
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pmaxsd, s[1], s[0], s[1])?;
+                    this.assembler
+                        .emit_packed_unop(PackedUnop::Cvttps2dq, out, out)?;
+                    this.assembler.emit_packed(PackedOp::Paddd, out, s[1], out)
+                })
+            }
+            Operator::I32x4TruncSatF64x2SZero => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    // Clamp to i32::MAX, NaN lanes to 0.
+                    this.v128_load_const(0x41DFFFFF_FFC00000_41DFFFFF_FFC00000, out)?;
+                    this.assembler
+                        .emit_packed_imm(PackedImmOp::Cmppd, r[0], r[0], 0, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Andps, s[0], out, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Minpd, r[0], s[0], out)?;
+                    this.assembler
+                        .emit_packed_unop(PackedUnop::Cvttpd2dq, out, out)
+                })
+            }
+            Operator::I32x4TruncSatF64x2UZero => {
+                self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                    // Clamp to [0, u32::MAX], NaN lanes to 0, truncate, and take the
+                    // low half of the doubles 2^52 + x.
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Maxpd, r[0], s[0], out)?;
+                    this.v128_load_const(0x41EFFFFF_FFE00000_41EFFFFF_FFE00000, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Minpd, out, s[0], out)?;
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Roundpd, out, 3, out)?;
+                    this.v128_load_const(0x43300000_00000000_43300000_00000000, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Addpd, out, s[0], out)?;
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler
+                        .emit_packed_imm(PackedImmOp::Shufps, out, s[0], 0x88, out)
+                })
+            }
+            _ => codegen_error!("singlepass v128_unop unimplemented for {:?}", op),
+        }
+    }
+    fn v128_binop(
+        &mut self,
+        op: &Operator,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        // Operators lowered to a single instruction, possibly with swapped operands.
+        let simple = match op {
+            Operator::I8x16Add => Some((PackedOp::Paddb, false)),
+            Operator::I16x8Add => Some((PackedOp::Paddw, false)),
+            Operator::I32x4Add => Some((PackedOp::Paddd, false)),
+            Operator::I64x2Add => Some((PackedOp::Paddq, false)),
+            Operator::I8x16Sub => Some((PackedOp::Psubb, false)),
+            Operator::I16x8Sub => Some((PackedOp::Psubw, false)),
+            Operator::I32x4Sub => Some((PackedOp::Psubd, false)),
+            Operator::I64x2Sub => Some((PackedOp::Psubq, false)),
+            Operator::I16x8Mul => Some((PackedOp::Pmullw, false)),
+            Operator::I32x4Mul => Some((PackedOp::Pmulld, false)),
+            Operator::I8x16AddSatS => Some((PackedOp::Paddsb, false)),
+            Operator::I8x16AddSatU => Some((PackedOp::Paddusb, false)),
+            Operator::I16x8AddSatS => Some((PackedOp::Paddsw, false)),
+            Operator::I16x8AddSatU => Some((PackedOp::Paddusw, false)),
+            Operator::I8x16SubSatS => Some((PackedOp::Psubsb, false)),
+            Operator::I8x16SubSatU => Some((PackedOp::Psubusb, false)),
+            Operator::I16x8SubSatS => Some((PackedOp::Psubsw, false)),
+            Operator::I16x8SubSatU => Some((PackedOp::Psubusw, false)),
+            Operator::I8x16MinS => Some((PackedOp::Pminsb, false)),
+            Operator::I8x16MinU => Some((PackedOp::Pminub, false)),
+            Operator::I8x16MaxS => Some((PackedOp::Pmaxsb, false)),
+            Operator::I8x16MaxU => Some((PackedOp::Pmaxub, false)),
+            Operator::I16x8MinS => Some((PackedOp::Pminsw, false)),
+            Operator::I16x8MinU => Some((PackedOp::Pminuw, false)),
+            Operator::I16x8MaxS => Some((PackedOp::Pmaxsw, false)),
+            Operator::I16x8MaxU => Some((PackedOp::Pmaxuw, false)),
+            Operator::I32x4MinS => Some((PackedOp::Pminsd, false)),
+            Operator::I32x4MinU => Some((PackedOp::Pminud, false)),
+            Operator::I32x4MaxS => Some((PackedOp::Pmaxsd, false)),
+            Operator::I32x4MaxU => Some((PackedOp::Pmaxud, false)),
+            Operator::I8x16AvgrU => Some((PackedOp::Pavgb, false)),
+            Operator::I16x8AvgrU => Some((PackedOp::Pavgw, false)),
+            Operator::I8x16Eq => Some((PackedOp::Pcmpeqb, false)),
+            Operator::I16x8Eq => Some((PackedOp::Pcmpeqw, false)),
+            Operator::I32x4Eq => Some((PackedOp::Pcmpeqd, false)),
+            Operator::I64x2Eq => Some((PackedOp::Pcmpeqq, false)),
+            Operator::I8x16GtS => Some((PackedOp::Pcmpgtb, false)),
+            Operator::I16x8GtS => Some((PackedOp::Pcmpgtw, false)),
+            Operator::I32x4GtS => Some((PackedOp::Pcmpgtd, false)),
+            Operator::I64x2GtS => Some((PackedOp::Pcmpgtq, false)),
+            Operator::I8x16LtS => Some((PackedOp::Pcmpgtb, true)),
+            Operator::I16x8LtS => Some((PackedOp::Pcmpgtw, true)),
+            Operator::I32x4LtS => Some((PackedOp::Pcmpgtd, true)),
+            Operator::I64x2LtS => Some((PackedOp::Pcmpgtq, true)),
+            Operator::V128And => Some((PackedOp::Pand, false)),
+            Operator::V128Or => Some((PackedOp::Por, false)),
+            Operator::V128Xor => Some((PackedOp::Pxor, false)),
+            // `pandn` computes `!a & b`.
+            Operator::V128AndNot => Some((PackedOp::Pandn, true)),
+            Operator::I8x16NarrowI16x8S => Some((PackedOp::Packsswb, false)),
+            Operator::I8x16NarrowI16x8U => Some((PackedOp::Packuswb, false)),
+            Operator::I16x8NarrowI32x4S => Some((PackedOp::Packssdw, false)),
+            Operator::I16x8NarrowI32x4U => Some((PackedOp::Packusdw, false)),
+            Operator::I32x4DotI16x8S => Some((PackedOp::Pmaddwd, false)),
+            Operator::F32x4Add => Some((PackedOp::Addps, false)),
+            Operator::F64x2Add => Some((PackedOp::Addpd, false)),
+            Operator::F32x4Sub => Some((PackedOp::Subps, false)),
+            Operator::F64x2Sub => Some((PackedOp::Subpd, false)),
+            Operator::F32x4Mul => Some((PackedOp::Mulps, false)),
+            Operator::F64x2Mul => Some((PackedOp::Mulpd, false)),
+            Operator::F32x4Div => Some((PackedOp::Divps, false)),
+            Operator::F64x2Div => Some((PackedOp::Divpd, false)),
+            // `pmin` is `b < a ? b : a`, which is what `minps b, a` computes, `pmax` alike.
+            Operator::F32x4PMin => Some((PackedOp::Minps, true)),
+            Operator::F64x2PMin => Some((PackedOp::Minpd, true)),
+            Operator::F32x4PMax => Some((PackedOp::Maxps, true)),
+            Operator::F64x2PMax => Some((PackedOp::Maxpd, true)),
+            _ => None,
+        };
+        if let Some((packed, swap)) = simple {
+            return self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                let (a, b) = if swap { (r[1], r[0]) } else { (r[0], r[1]) };
+                this.assembler.emit_packed(packed, a, b, out)
+            });
+        }
+
+        // Integer comparisons lowered to a comparison instruction whose result is
+        // inverted, possibly with swapped operands.
+        let inverted = match op {
+            Operator::I8x16Ne => Some((PackedOp::Pcmpeqb, false)),
+            Operator::I16x8Ne => Some((PackedOp::Pcmpeqw, false)),
+            Operator::I32x4Ne => Some((PackedOp::Pcmpeqd, false)),
+            Operator::I64x2Ne => Some((PackedOp::Pcmpeqq, false)),
+            Operator::I8x16LeS => Some((PackedOp::Pcmpgtb, false)),
+            Operator::I16x8LeS => Some((PackedOp::Pcmpgtw, false)),
+            Operator::I32x4LeS => Some((PackedOp::Pcmpgtd, false)),
+            Operator::I64x2LeS => Some((PackedOp::Pcmpgtq, false)),
+            Operator::I8x16GeS => Some((PackedOp::Pcmpgtb, true)),
+            Operator::I16x8GeS => Some((PackedOp::Pcmpgtw, true)),
+            Operator::I32x4GeS => Some((PackedOp::Pcmpgtd, true)),
+            Operator::I64x2GeS => Some((PackedOp::Pcmpgtq, true)),
+            _ => None,
+        };
+        if let Some((packed, swap)) = inverted {
+            return self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                let (a, b) = if swap { (r[1], r[0]) } else { (r[0], r[1]) };
+                this.assembler.emit_packed(packed, a, b, out)?;
+                this.v128_load_const(u128::MAX, s[0])?;
+                this.assembler.emit_packed(PackedOp::Pxor, out, s[0], out)
+            });
+        }
+
+        // Unsigned comparisons: `a >= b` when `max(a, b) == a`, and `a <= b` when
+        // `min(a, b) == a`. The strict ones are their inverse.
+        let unsigned = match op {
+            Operator::I8x16GeU => Some((PackedOp::Pmaxub, PackedOp::Pcmpeqb, false)),
+            Operator::I16x8GeU => Some((PackedOp::Pmaxuw, PackedOp::Pcmpeqw, false)),
+            Operator::I32x4GeU => Some((PackedOp::Pmaxud, PackedOp::Pcmpeqd, false)),
+            Operator::I8x16LeU => Some((PackedOp::Pminub, PackedOp::Pcmpeqb, false)),
+            Operator::I16x8LeU => Some((PackedOp::Pminuw, PackedOp::Pcmpeqw, false)),
+            Operator::I32x4LeU => Some((PackedOp::Pminud, PackedOp::Pcmpeqd, false)),
+            Operator::I8x16LtU => Some((PackedOp::Pmaxub, PackedOp::Pcmpeqb, true)),
+            Operator::I16x8LtU => Some((PackedOp::Pmaxuw, PackedOp::Pcmpeqw, true)),
+            Operator::I32x4LtU => Some((PackedOp::Pmaxud, PackedOp::Pcmpeqd, true)),
+            Operator::I8x16GtU => Some((PackedOp::Pminub, PackedOp::Pcmpeqb, true)),
+            Operator::I16x8GtU => Some((PackedOp::Pminuw, PackedOp::Pcmpeqw, true)),
+            Operator::I32x4GtU => Some((PackedOp::Pminud, PackedOp::Pcmpeqd, true)),
+            _ => None,
+        };
+        if let Some((minmax, eq, invert)) = unsigned {
+            return self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                this.assembler.emit_packed(minmax, r[0], r[1], out)?;
+                this.assembler.emit_packed(eq, out, r[0], out)?;
+                if invert {
+                    this.v128_load_const(u128::MAX, s[0])?;
+                    this.assembler.emit_packed(PackedOp::Pxor, out, s[0], out)?;
+                }
+                Ok(())
+            });
+        }
+
+        // Float comparisons, with the predicate of `cmpps` and `cmppd`.
+        let float_cmp = match op {
+            Operator::F32x4Eq => Some((PackedImmOp::Cmpps, 0, false)),
+            Operator::F32x4Ne => Some((PackedImmOp::Cmpps, 4, false)),
+            Operator::F32x4Lt => Some((PackedImmOp::Cmpps, 1, false)),
+            Operator::F32x4Le => Some((PackedImmOp::Cmpps, 2, false)),
+            Operator::F32x4Gt => Some((PackedImmOp::Cmpps, 1, true)),
+            Operator::F32x4Ge => Some((PackedImmOp::Cmpps, 2, true)),
+            Operator::F64x2Eq => Some((PackedImmOp::Cmppd, 0, false)),
+            Operator::F64x2Ne => Some((PackedImmOp::Cmppd, 4, false)),
+            Operator::F64x2Lt => Some((PackedImmOp::Cmppd, 1, false)),
+            Operator::F64x2Le => Some((PackedImmOp::Cmppd, 2, false)),
+            Operator::F64x2Gt => Some((PackedImmOp::Cmppd, 1, true)),
+            Operator::F64x2Ge => Some((PackedImmOp::Cmppd, 2, true)),
+            _ => None,
+        };
+        if let Some((cmp, predicate, swap)) = float_cmp {
+            return self.emit_v128(&[loc_a, loc_b], ret, 0, |this, r, out, _| {
+                let (a, b) = if swap { (r[1], r[0]) } else { (r[0], r[1]) };
+                this.assembler.emit_packed_imm(cmp, a, b, predicate, out)
+            });
+        }
+
+        match op {
+            Operator::F32x4Min | Operator::F64x2Min => {
+                let (min, cmp, shift) = if *op == Operator::F32x4Min {
+                    (
+                        PackedOp::Minps,
+                        PackedImmOp::Cmpps,
+                        (PackedUnopImm::Psrld, 10),
+                    )
+                } else {
+                    (
+                        PackedOp::Minpd,
+                        PackedImmOp::Cmppd,
+                        (PackedUnopImm::Psrlq, 13),
+                    )
+                };
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    // `minps` returns its second operand when either is NaN or both
+                    // are zeros: compute it both ways to propagate NaNs and -0.
+                    this.assembler.emit_packed(min, r[0], r[1], s[0])?;
+                    this.assembler.emit_packed(min, r[1], r[0], out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Orps, s[0], out, s[0])?;
+                    // Canonicalize NaNs.
+                    this.assembler.emit_packed_imm(cmp, out, s[0], 3, out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Orps, s[0], out, s[0])?;
+                    this.assembler
+                        .emit_packed_unop_imm(shift.0, out, shift.1, out)?;
+                    this.assembler.emit_packed(PackedOp::Andnps, out, s[0], out)
+                })
+            }
+            Operator::F32x4Max | Operator::F64x2Max => {
+                let (max, sub, cmp, shift) = if *op == Operator::F32x4Max {
+                    (
+                        PackedOp::Maxps,
+                        PackedOp::Subps,
+                        PackedImmOp::Cmpps,
+                        (PackedUnopImm::Psrld, 10),
+                    )
+                } else {
+                    (
+                        PackedOp::Maxpd,
+                        PackedOp::Subpd,
+                        PackedImmOp::Cmppd,
+                        (PackedUnopImm::Psrlq, 13),
+                    )
+                };
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    this.assembler.emit_packed(max, r[0], r[1], s[0])?;
+                    this.assembler.emit_packed(max, r[1], r[0], out)?;
+                    // Propagate the NaNs and the sign of zeros where both ways differ.
+                    this.assembler
+                        .emit_packed(PackedOp::Xorps, out, s[0], out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Orps, s[0], out, s[0])?;
+                    this.assembler.emit_packed(sub, s[0], out, s[0])?;
+                    // Canonicalize NaNs.
+                    this.assembler.emit_packed_imm(cmp, out, s[0], 3, out)?;
+                    this.assembler
+                        .emit_packed_unop_imm(shift.0, out, shift.1, out)?;
+                    this.assembler.emit_packed(PackedOp::Andnps, out, s[0], out)
+                })
+            }
+            Operator::I64x2Mul => self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                // The sum of the cross products of the 32-bit halves, shifted, plus the
+                // product of the low halves.
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psrlq, r[0], 32, out)?;
+                this.assembler
+                    .emit_packed(PackedOp::Pmuludq, out, r[1], out)?;
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psrlq, r[1], 32, s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pmuludq, s[0], r[0], s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Paddq, out, s[0], out)?;
+                this.assembler
+                    .emit_packed_unop_imm(PackedUnopImm::Psllq, out, 32, out)?;
+                this.assembler
+                    .emit_packed(PackedOp::Pmuludq, r[0], r[1], s[0])?;
+                this.assembler.emit_packed(PackedOp::Paddq, out, s[0], out)
+            }),
+            Operator::I16x8Q15MulrSatS => {
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    // `pmulhrsw` only overflows to 0x8000 on 0x8000 * 0x8000.
+                    this.assembler
+                        .emit_packed(PackedOp::Pmulhrsw, r[0], r[1], out)?;
+                    this.v128_load_const(0x8000_8000_8000_8000_8000_8000_8000_8000, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pcmpeqw, s[0], out, s[0])?;
+                    this.assembler.emit_packed(PackedOp::Pxor, out, s[0], out)
+                })
+            }
+            Operator::I16x8ExtMulLowI8x16S
+            | Operator::I16x8ExtMulHighI8x16S
+            | Operator::I16x8ExtMulLowI8x16U
+            | Operator::I16x8ExtMulHighI8x16U
+            | Operator::I32x4ExtMulLowI16x8S
+            | Operator::I32x4ExtMulHighI16x8S
+            | Operator::I32x4ExtMulLowI16x8U
+            | Operator::I32x4ExtMulHighI16x8U => {
+                let (extend, mul) = match op {
+                    Operator::I16x8ExtMulLowI8x16S | Operator::I16x8ExtMulHighI8x16S => {
+                        (PackedUnop::Pmovsxbw, PackedOp::Pmullw)
+                    }
+                    Operator::I16x8ExtMulLowI8x16U | Operator::I16x8ExtMulHighI8x16U => {
+                        (PackedUnop::Pmovzxbw, PackedOp::Pmullw)
+                    }
+                    Operator::I32x4ExtMulLowI16x8S | Operator::I32x4ExtMulHighI16x8S => {
+                        (PackedUnop::Pmovsxwd, PackedOp::Pmulld)
+                    }
+                    _ => (PackedUnop::Pmovzxwd, PackedOp::Pmulld),
+                };
+                let high = matches!(
+                    op,
+                    Operator::I16x8ExtMulHighI8x16S
+                        | Operator::I16x8ExtMulHighI8x16U
+                        | Operator::I32x4ExtMulHighI16x8S
+                        | Operator::I32x4ExtMulHighI16x8U
+                );
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    for (src, dst) in [(r[0], out), (r[1], s[0])] {
+                        if high {
+                            this.assembler.emit_packed_unop_imm(
+                                PackedUnopImm::Pshufd,
+                                src,
+                                0xEE,
+                                dst,
+                            )?;
+                            this.assembler.emit_packed_unop(extend, dst, dst)?;
+                        } else {
+                            this.assembler.emit_packed_unop(extend, src, dst)?;
+                        }
+                    }
+                    this.assembler.emit_packed(mul, out, s[0], out)
+                })
+            }
+            Operator::I64x2ExtMulLowI32x4S
+            | Operator::I64x2ExtMulHighI32x4S
+            | Operator::I64x2ExtMulLowI32x4U
+            | Operator::I64x2ExtMulHighI32x4U => {
+                // Move the multiplied lanes to the low halves of the 64-bit lanes.
+                let shuffle = match op {
+                    Operator::I64x2ExtMulLowI32x4S | Operator::I64x2ExtMulLowI32x4U => 0x50,
+                    _ => 0xFA,
+                };
+                let mul = match op {
+                    Operator::I64x2ExtMulLowI32x4S | Operator::I64x2ExtMulHighI32x4S => {
+                        PackedOp::Pmuldq
+                    }
+                    _ => PackedOp::Pmuludq,
+                };
+                self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                    this.assembler.emit_packed_unop_imm(
+                        PackedUnopImm::Pshufd,
+                        r[0],
+                        shuffle,
+                        out,
+                    )?;
+                    this.assembler.emit_packed_unop_imm(
+                        PackedUnopImm::Pshufd,
+                        r[1],
+                        shuffle,
+                        s[0],
+                    )?;
+                    this.assembler.emit_packed(mul, out, s[0], out)
+                })
+            }
+            Operator::I8x16Swizzle => self.emit_v128(&[loc_a, loc_b], ret, 1, |this, r, out, s| {
+                // Set the high bit of the out of range indices, for `pshufb` to zero them.
+                this.v128_load_const(u128::from_le_bytes([0x70; 16]), s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Paddusb, s[0], r[1], s[0])?;
+                this.assembler
+                    .emit_packed(PackedOp::Pshufb, r[0], s[0], out)
+            }),
+            Operator::I8x16Shuffle { lanes } => {
+                // Select the lanes of each operand with `pshufb`, zeroing the others.
+                let mask = |from_b: bool| {
+                    u128::from_le_bytes(lanes.map(|lane| match (lane >= 16, from_b) {
+                        (false, false) => lane,
+                        (true, true) => lane - 16,
+                        _ => 0x80,
+                    }))
+                };
+                let (mask_a, mask_b) = (mask(false), mask(true));
+                self.emit_v128(&[loc_a, loc_b], ret, 2, |this, r, out, s| {
+                    this.v128_load_const(mask_a, s[0])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Pshufb, r[0], s[0], out)?;
+                    if lanes.iter().any(|&lane| lane >= 16) {
+                        this.v128_load_const(mask_b, s[0])?;
+                        this.assembler
+                            .emit_packed(PackedOp::Pshufb, r[1], s[0], s[1])?;
+                        this.assembler.emit_packed(PackedOp::Por, out, s[1], out)?;
+                    }
+                    Ok(())
+                })
+            }
+            _ => codegen_error!("singlepass v128_binop unimplemented for {:?}", op),
+        }
+    }
+    fn v128_bitselect(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        mask: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        self.emit_v128(&[loc_a, loc_b, mask], ret, 1, |this, r, out, s| {
+            this.assembler
+                .emit_packed(PackedOp::Pand, r[0], r[2], out)?;
+            this.assembler
+                .emit_packed(PackedOp::Pandn, r[2], r[1], s[0])?;
+            this.assembler.emit_packed(PackedOp::Por, out, s[0], out)
+        })
+    }
+    fn v128_shift(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        count: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let lane_bits = match op {
+            Operator::I8x16Shl | Operator::I8x16ShrS | Operator::I8x16ShrU => 8,
+            Operator::I16x8Shl | Operator::I16x8ShrS | Operator::I16x8ShrU => 16,
+            Operator::I32x4Shl | Operator::I32x4ShrS | Operator::I32x4ShrU => 32,
+            Operator::I64x2Shl | Operator::I64x2ShrS | Operator::I64x2ShrU => 64,
+            _ => codegen_error!("singlepass v128_shift unimplemented for {:?}", op),
+        };
+        // The bytes are shifted as the high halves of 16-bit lanes, whose low halves
+        // are then dropped.
+        let byte_shr = matches!(op, Operator::I8x16ShrS | Operator::I8x16ShrU);
+        let extra = if byte_shr { 8 } else { 0 };
+
+        if let Location::Imm32(n) = count {
+            let n = (n % lane_bits) as u8;
+            return self.emit_v128(&[loc], ret, 1, |this, r, out, s| {
+                let a = r[0];
+                match op {
+                    Operator::I8x16Shl => {
+                        this.assembler
+                            .emit_packed_unop_imm(PackedUnopImm::Psllw, a, n, out)?;
+                        this.v128_load_const(u128::from_le_bytes([0xFF << n; 16]), s[0])?;
+                        this.assembler.emit_packed(PackedOp::Pand, out, s[0], out)
+                    }
+                    Operator::I8x16ShrS | Operator::I8x16ShrU => {
+                        let (shift, pack) = if *op == Operator::I8x16ShrS {
+                            (PackedUnopImm::Psraw, PackedOp::Packsswb)
+                        } else {
+                            (PackedUnopImm::Psrlw, PackedOp::Packuswb)
+                        };
+                        this.assembler.emit_packed(PackedOp::Punpcklbw, a, a, out)?;
+                        this.assembler
+                            .emit_packed_unop_imm(shift, out, n + extra, out)?;
+                        this.assembler
+                            .emit_packed(PackedOp::Punpckhbw, a, a, s[0])?;
+                        this.assembler
+                            .emit_packed_unop_imm(shift, s[0], n + extra, s[0])?;
+                        this.assembler.emit_packed(pack, out, s[0], out)
+                    }
+                    Operator::I64x2ShrS => {
+                        // `(a >>> n ^ m) - m` with `m = 1 << (63 - n)` sign extends.
+                        this.assembler
+                            .emit_packed_unop_imm(PackedUnopImm::Psrlq, a, n, out)?;
+                        let m = (1u64 << 63) >> n;
+                        this.v128_load_const(((m as u128) << 64) | m as u128, s[0])?;
+                        this.assembler.emit_packed(PackedOp::Pxor, out, s[0], out)?;
+                        this.assembler.emit_packed(PackedOp::Psubq, out, s[0], out)
+                    }
+                    _ => {
+                        let shift = match op {
+                            Operator::I16x8Shl => PackedUnopImm::Psllw,
+                            Operator::I16x8ShrS => PackedUnopImm::Psraw,
+                            Operator::I16x8ShrU => PackedUnopImm::Psrlw,
+                            Operator::I32x4Shl => PackedUnopImm::Pslld,
+                            Operator::I32x4ShrS => PackedUnopImm::Psrad,
+                            Operator::I32x4ShrU => PackedUnopImm::Psrld,
+                            Operator::I64x2Shl => PackedUnopImm::Psllq,
+                            _ => PackedUnopImm::Psrlq,
+                        };
+                        this.assembler.emit_packed_unop_imm(shift, a, n, out)
+                    }
+                }
+            });
+        }
+
+        // The count goes to the low lane of a register, masked to the lane width.
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(Size::S32, count, Location::GPR(tmp))?;
+        self.assembler.emit_and(
+            Size::S32,
+            Location::Imm32(lane_bits - 1),
+            Location::GPR(tmp),
+        )?;
+        if extra != 0 {
+            self.assembler.emit_add(
+                Size::S32,
+                Location::Imm32(extra as u32),
+                Location::GPR(tmp),
+            )?;
+        }
+        self.emit_v128(&[loc], ret, 2, |this, r, out, s| {
+            let (a, n) = (r[0], s[0]);
+            this.assembler
+                .emit_mov(Size::S32, Location::GPR(tmp), Location::SIMD(n))?;
+            match op {
+                Operator::I8x16Shl => {
+                    // The mask of the bits staying in each byte: 0xFF00 << n >> 8.
+                    this.v128_load_const(u128::MAX, s[1])?;
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Psllw, s[1], 8, s[1])?;
+                    this.assembler.emit_packed(PackedOp::Psllw, s[1], n, s[1])?;
+                    this.assembler
+                        .emit_packed_unop_imm(PackedUnopImm::Psrlw, s[1], 8, s[1])?;
+                    this.assembler
+                        .emit_packed(PackedOp::Packuswb, s[1], s[1], s[1])?;
+                    this.assembler.emit_packed(PackedOp::Psllw, a, n, out)?;
+                    this.assembler.emit_packed(PackedOp::Pand, out, s[1], out)
+                }
+                Operator::I8x16ShrS | Operator::I8x16ShrU => {
+                    let (shift, pack) = if *op == Operator::I8x16ShrS {
+                        (PackedOp::Psraw, PackedOp::Packsswb)
+                    } else {
+                        (PackedOp::Psrlw, PackedOp::Packuswb)
+                    };
+                    this.assembler.emit_packed(PackedOp::Punpcklbw, a, a, out)?;
+                    this.assembler.emit_packed(shift, out, n, out)?;
+                    this.assembler
+                        .emit_packed(PackedOp::Punpckhbw, a, a, s[1])?;
+                    this.assembler.emit_packed(shift, s[1], n, s[1])?;
+                    this.assembler.emit_packed(pack, out, s[1], out)
+                }
+                Operator::I64x2ShrS => {
+                    this.assembler.emit_packed(PackedOp::Psrlq, a, n, out)?;
+                    this.v128_load_const(0x8000_0000_0000_0000_8000_0000_0000_0000, s[1])?;
+                    this.assembler.emit_packed(PackedOp::Psrlq, s[1], n, s[1])?;
+                    this.assembler.emit_packed(PackedOp::Pxor, out, s[1], out)?;
+                    this.assembler.emit_packed(PackedOp::Psubq, out, s[1], out)
+                }
+                _ => {
+                    let shift = match op {
+                        Operator::I16x8Shl => PackedOp::Psllw,
+                        Operator::I16x8ShrS => PackedOp::Psraw,
+                        Operator::I16x8ShrU => PackedOp::Psrlw,
+                        Operator::I32x4Shl => PackedOp::Pslld,
+                        Operator::I32x4ShrS => PackedOp::Psrad,
+                        Operator::I32x4ShrU => PackedOp::Psrld,
+                        Operator::I64x2Shl => PackedOp::Psllq,
+                        _ => PackedOp::Psrlq,
+                    };
+                    this.assembler.emit_packed(shift, a, n, out)
+                }
+            }
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_test(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.emit_v128_read(&[loc], 1, |this, r, s| {
+            let a = r[0];
+            match op {
+                Operator::V128AnyTrue => {
+                    this.assembler
+                        .emit_xor(Size::S32, Location::GPR(tmp), Location::GPR(tmp))?;
+                    this.assembler.emit_ptest(a, a)?;
+                    this.assembler.emit_set(Condition::NotEqual, tmp)
+                }
+                Operator::I8x16AllTrue
+                | Operator::I16x8AllTrue
+                | Operator::I32x4AllTrue
+                | Operator::I64x2AllTrue => {
+                    let eq = match op {
+                        Operator::I8x16AllTrue => PackedOp::Pcmpeqb,
+                        Operator::I16x8AllTrue => PackedOp::Pcmpeqw,
+                        Operator::I32x4AllTrue => PackedOp::Pcmpeqd,
+                        _ => PackedOp::Pcmpeqq,
+                    };
+                    // All true when no lane is equal to zero.
+                    this.v128_load_const(0, s[0])?;
+                    this.assembler.emit_packed(eq, s[0], a, s[0])?;
+                    this.assembler
+                        .emit_xor(Size::S32, Location::GPR(tmp), Location::GPR(tmp))?;
+                    this.assembler.emit_ptest(s[0], s[0])?;
+                    this.assembler.emit_set(Condition::Equal, tmp)
+                }
+                Operator::I8x16Bitmask => this.assembler.emit_movmsk(Size::S8, a, tmp),
+                Operator::I16x8Bitmask => {
+                    this.assembler.emit_packed(PackedOp::Packsswb, a, a, s[0])?;
+                    this.assembler.emit_movmsk(Size::S8, s[0], tmp)?;
+                    this.assembler
+                        .emit_and(Size::S32, Location::Imm32(0xFF), Location::GPR(tmp))
+                }
+                Operator::I32x4Bitmask => this.assembler.emit_movmsk(Size::S32, a, tmp),
+                Operator::I64x2Bitmask => this.assembler.emit_movmsk(Size::S64, a, tmp),
+                _ => codegen_error!("singlepass v128_test unimplemented for {:?}", op),
+            }
+        })?;
+        self.move_location(Size::S32, Location::GPR(tmp), ret)?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_splat(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let sz = match op {
+            Operator::I8x16Splat => Size::S8,
+            Operator::I16x8Splat => Size::S16,
+            Operator::I32x4Splat | Operator::F32x4Splat => Size::S32,
+            Operator::I64x2Splat | Operator::F64x2Splat => Size::S64,
+            _ => codegen_error!("singlepass v128_splat unimplemented for {:?}", op),
+        };
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(word, loc, Location::GPR(tmp))?;
+        self.emit_v128(&[], ret, 1, |this, _, out, s| {
+            this.assembler
+                .emit_mov(word, Location::GPR(tmp), Location::SIMD(out))?;
+            this.v128_broadcast(sz, out, s[0])
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_extract_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let (sz, lane, signed) = match *op {
+            Operator::I8x16ExtractLaneS { lane } => (Size::S8, lane, true),
+            Operator::I8x16ExtractLaneU { lane } => (Size::S8, lane, false),
+            Operator::I16x8ExtractLaneS { lane } => (Size::S16, lane, true),
+            Operator::I16x8ExtractLaneU { lane } => (Size::S16, lane, false),
+            Operator::I32x4ExtractLane { lane } | Operator::F32x4ExtractLane { lane } => {
+                (Size::S32, lane, false)
+            }
+            Operator::I64x2ExtractLane { lane } | Operator::F64x2ExtractLane { lane } => {
+                (Size::S64, lane, false)
+            }
+            _ => codegen_error!("singlepass v128_extract_lane unimplemented for {:?}", op),
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.emit_v128_read(&[loc], 0, |this, r, _| {
+            this.assembler.emit_pextr(sz, r[0], lane, tmp)
+        })?;
+        if signed {
+            self.assembler
+                .emit_movsx(sz, Location::GPR(tmp), Size::S32, Location::GPR(tmp))?;
+        }
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        self.move_location(word, Location::GPR(tmp), ret)?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_replace_lane(
+        &mut self,
+        op: &Operator,
+        loc: Location,
+        value: Location,
+        ret: Location,
+    ) -> Result<(), CompileError> {
+        let (sz, lane) = match *op {
+            Operator::I8x16ReplaceLane { lane } => (Size::S8, lane),
+            Operator::I16x8ReplaceLane { lane } => (Size::S16, lane),
+            Operator::I32x4ReplaceLane { lane } | Operator::F32x4ReplaceLane { lane } => {
+                (Size::S32, lane)
+            }
+            Operator::I64x2ReplaceLane { lane } | Operator::F64x2ReplaceLane { lane } => {
+                (Size::S64, lane)
+            }
+            _ => codegen_error!("singlepass v128_replace_lane unimplemented for {:?}", op),
+        };
+        let word = if sz == Size::S64 {
+            Size::S64
+        } else {
+            Size::S32
+        };
+        let tmp = self.acquire_temp_gpr().ok_or_else(|| {
+            CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+        })?;
+        self.move_location(word, value, Location::GPR(tmp))?;
+        self.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+            this.assembler.emit_pinsr(sz, r[0], tmp, lane, out)
+        })?;
+        self.release_gpr(tmp);
+        Ok(())
+    }
+    fn v128_load(
+        &mut self,
+        op: &Operator,
+        addr: Location,
+        memarg: &MemArg,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let value_size = match op {
+            Operator::V128Load { .. } => 16,
+            Operator::V128Load8x8S { .. }
+            | Operator::V128Load8x8U { .. }
+            | Operator::V128Load16x4S { .. }
+            | Operator::V128Load16x4U { .. }
+            | Operator::V128Load32x2S { .. }
+            | Operator::V128Load32x2U { .. }
+            | Operator::V128Load64Splat { .. }
+            | Operator::V128Load64Zero { .. } => 8,
+            Operator::V128Load8Splat { .. } => 1,
+            Operator::V128Load16Splat { .. } => 2,
+            Operator::V128Load32Splat { .. } | Operator::V128Load32Zero { .. } => 4,
+            _ => codegen_error!("singlepass v128_load unimplemented for {:?}", op),
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                this.emit_v128(&[], ret, 1, |this, _, out, s| {
+                    let mem = Location::Memory(addr, 0);
+                    let extend = match op {
+                        Operator::V128Load { .. } => {
+                            return this
+                                .assembler
+                                .emit_movdqu(XMMOrMemory::Memory(addr, 0), XMMOrMemory::XMM(out));
+                        }
+                        Operator::V128Load32Zero { .. } => {
+                            return this.assembler.emit_mov(Size::S32, mem, Location::SIMD(out));
+                        }
+                        Operator::V128Load64Zero { .. } => {
+                            return this.assembler.emit_mov(Size::S64, mem, Location::SIMD(out));
+                        }
+                        Operator::V128Load8Splat { .. } | Operator::V128Load16Splat { .. } => {
+                            let sz = if value_size == 1 { Size::S8 } else { Size::S16 };
+                            this.assembler
+                                .emit_movzx(sz, mem, Size::S32, Location::GPR(addr))?;
+                            this.assembler.emit_mov(
+                                Size::S32,
+                                Location::GPR(addr),
+                                Location::SIMD(out),
+                            )?;
+                            return this.v128_broadcast(sz, out, s[0]);
+                        }
+                        Operator::V128Load32Splat { .. } => {
+                            this.assembler
+                                .emit_mov(Size::S32, mem, Location::SIMD(out))?;
+                            return this.v128_broadcast(Size::S32, out, s[0]);
+                        }
+                        Operator::V128Load64Splat { .. } => {
+                            this.assembler
+                                .emit_mov(Size::S64, mem, Location::SIMD(out))?;
+                            return this.v128_broadcast(Size::S64, out, s[0]);
+                        }
+                        Operator::V128Load8x8S { .. } => PackedUnop::Pmovsxbw,
+                        Operator::V128Load8x8U { .. } => PackedUnop::Pmovzxbw,
+                        Operator::V128Load16x4S { .. } => PackedUnop::Pmovsxwd,
+                        Operator::V128Load16x4U { .. } => PackedUnop::Pmovzxwd,
+                        Operator::V128Load32x2S { .. } => PackedUnop::Pmovsxdq,
+                        _ => PackedUnop::Pmovzxdq,
+                    };
+                    this.assembler
+                        .emit_mov(Size::S64, mem, Location::SIMD(out))?;
+                    this.assembler.emit_packed_unop(extend, out, out)
+                })
+            },
+        )
+    }
+    fn v128_load_lane(
+        &mut self,
+        op: &Operator,
+        addr: Location,
+        memarg: &MemArg,
+        loc: Location,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let (sz, lane, value_size) = match *op {
+            Operator::V128Load8Lane { lane, .. } => (Size::S8, lane, 1),
+            Operator::V128Load16Lane { lane, .. } => (Size::S16, lane, 2),
+            Operator::V128Load32Lane { lane, .. } => (Size::S32, lane, 4),
+            Operator::V128Load64Lane { lane, .. } => (Size::S64, lane, 8),
+            _ => codegen_error!("singlepass v128_load_lane unimplemented for {:?}", op),
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                let mem = Location::Memory(addr, 0);
+                match sz {
+                    Size::S8 | Size::S16 => {
+                        this.assembler
+                            .emit_movzx(sz, mem, Size::S32, Location::GPR(addr))?
+                    }
+                    _ => this.assembler.emit_mov(sz, mem, Location::GPR(addr))?,
+                }
+                this.emit_v128(&[loc], ret, 0, |this, r, out, _| {
+                    this.assembler.emit_pinsr(sz, r[0], addr, lane, out)
+                })
+            },
+        )
+    }
+    fn v128_save(
+        &mut self,
+        op: &Operator,
+        value: Location,
+        memarg: &MemArg,
+        addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+        unaligned_atomic: Label,
+    ) -> Result<(), CompileError> {
+        let lane = match *op {
+            Operator::V128Store { .. } => None,
+            Operator::V128Store8Lane { lane, .. } => Some((Size::S8, lane)),
+            Operator::V128Store16Lane { lane, .. } => Some((Size::S16, lane)),
+            Operator::V128Store32Lane { lane, .. } => Some((Size::S32, lane)),
+            Operator::V128Store64Lane { lane, .. } => Some((Size::S64, lane)),
+            _ => codegen_error!("singlepass v128_save unimplemented for {:?}", op),
+        };
+        let value_size = match lane {
+            None => 16,
+            Some((Size::S8, _)) => 1,
+            Some((Size::S16, _)) => 2,
+            Some((Size::S32, _)) => 4,
+            Some((Size::S64, _)) => 8,
+        };
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            value_size,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            unaligned_atomic,
+            |this, addr| {
+                this.emit_v128_read(&[value], 0, |this, r, _| {
+                    let Some((sz, lane)) = lane else {
+                        return this
+                            .assembler
+                            .emit_movdqu(XMMOrMemory::XMM(r[0]), XMMOrMemory::Memory(addr, 0));
+                    };
+                    let tmp = this.acquire_temp_gpr().ok_or_else(|| {
+                        CompileError::Codegen("singlepass cannot acquire temp gpr".to_owned())
+                    })?;
+                    this.assembler.emit_pextr(sz, r[0], lane, tmp)?;
+                    this.assembler
+                        .emit_mov(sz, Location::GPR(tmp), Location::Memory(addr, 0))?;
+                    this.release_gpr(tmp);
+                    Ok(())
+                })
+            },
+        )
+    }
+
+    fn gen_std_trampoline(
+        &self,
+        sig: &FunctionType,
+        calling_convention: CallingConvention,
+    ) -> Result<FunctionBody, CompileError> {
+        // the cpu feature here is irrelevant
+        let mut a = AssemblerX64::new(0, None)?;
+        let results_layout = ResultsLayout::new(sig.results());
+        let results_registers = results_layout.as_ref().and_then(|layout| {
+            self.get_results_registers(sig.results(), layout, calling_convention)
+        });
+        // The address of the memory receiving the results comes before the vmctx.
+        let first_param = if results_layout.is_some() && results_registers.is_none() {
+            2
+        } else {
+            1
+        };
+
+        // Calculate the locations of the arguments, and the stack offset.
+        let mut stack_args: usize = 0;
+        let (mut n_scalars, mut n_simds) = (0, 0);
+        let mut param_locations = Vec::with_capacity(sig.params().len());
+        for ty in sig.params() {
+            let loc = if *ty == Type::V128 {
+                n_simds += 1;
+                self.get_simd_param_location(n_simds - 1, &mut stack_args, calling_convention)
+            } else {
+                n_scalars += 1;
+                self.get_param_location(
+                    first_param + n_scalars - 1,
+                    Size::S64,
+                    &mut stack_args,
+                    calling_convention,
+                )
+            };
+            param_locations.push(loc);
+        }
+        let mut stack_offset = stack_args as u32;
+        let stack_padding: u32 = match calling_convention {
+            CallingConvention::WindowsFastcall => 32,
+            _ => 0,
+        };
+
+        // Area receiving the struct of results, right above the stack arguments.
+        let return_area_offset = stack_padding + stack_offset;
+        if let Some(layout) = &results_layout {
+            stack_offset += layout.size as u32;
+        }
+
+        // Align to 16 bytes. We push two 8-byte registers below, so here we need to ensure stack_offset % 16 == 8.
+        if stack_offset % 16 != 8 {
+            stack_offset += 8;
+        }
+
+        // Used callee-saved registers
+        a.emit_push(Size::S64, Location::GPR(GPR::R15))?;
+        a.emit_push(Size::S64, Location::GPR(GPR::R14))?;
+
+        // Prepare stack space.
+        a.emit_sub(
+            Size::S64,
+            Location::Imm32(stack_offset + stack_padding),
+            Location::GPR(GPR::RSP),
+        )?;
+
+        // Arguments
+        a.emit_mov(
+            Size::S64,
+            self.get_simple_param_location(1, calling_convention),
+            Location::GPR(GPR::R15),
+        )?; // func_ptr
+        a.emit_mov(
+            Size::S64,
+            self.get_simple_param_location(2, calling_convention),
+            Location::GPR(GPR::R14),
+        )?; // args_rets
+
+        // `callee_vmctx` is already in the first argument register, unless the address
+        // of the area receiving the results goes there.
+        if first_param == 2 {
+            a.emit_mov(
+                Size::S64,
+                self.get_simple_param_location(0, calling_convention),
+                self.get_simple_param_location(1, calling_convention),
+            )?;
+            a.emit_lea(
+                Size::S64,
+                Location::Memory(GPR::RSP, return_area_offset as _),
+                self.get_simple_param_location(0, calling_convention),
+            )?;
+        }
+
+        // Move arguments to their locations.
+        for (i, (ty, dst_loc)) in sig.params().iter().zip(&param_locations).enumerate() {
+            let src_offset = (i * 16) as i32; // args_rets[i]
+            match *dst_loc {
+                Location::GPR(_) => {
+                    a.emit_mov(Size::S64, Location::Memory(GPR::R14, src_offset), *dst_loc)?;
+                }
+                Location::SIMD(x) => {
+                    a.emit_movdqu(
+                        XMMOrMemory::Memory(GPR::R14, src_offset),
+                        XMMOrMemory::XMM(x),
+                    )?;
+                }
+                Location::Memory(_, offset) => {
+                    let words = if *ty == Type::V128 { 2 } else { 1 };
+                    for word in 0..words {
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(GPR::R14, src_offset + word * 8),
+                            Location::GPR(GPR::RAX),
+                        )?;
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RAX),
+                            Location::Memory(GPR::RSP, stack_padding as i32 + offset + word * 8),
+                        )?;
+                    }
+                }
+                _ => codegen_error!("singlepass gen_std_trampoline unreachable"),
+            }
+        }
+
+        // Call.
+        a.emit_call_location(Location::GPR(GPR::R15))?;
+
+        // Write return values.
+        if let Some(layout) = &results_layout {
+            for (reg, offset, sz) in results_registers.iter().flatten() {
+                a.emit_mov(
+                    *sz,
+                    *reg,
+                    Location::Memory(GPR::RSP, (return_area_offset as usize + offset) as _),
+                )?;
+            }
+            for (i, (ty, offset)) in sig.results().iter().zip(&layout.offsets).enumerate() {
+                let (sz, words) = match ty {
+                    Type::I32 | Type::F32 => (Size::S32, 1),
+                    Type::V128 => (Size::S64, 2),
+                    _ => (Size::S64, 1),
+                };
+                for word in 0..words {
+                    a.emit_mov(
+                        sz,
+                        Location::Memory(
+                            GPR::RSP,
+                            (return_area_offset as usize + offset + word * 8) as _,
+                        ),
+                        Location::GPR(GPR::RAX),
+                    )?;
+                    a.emit_mov(
+                        sz,
+                        Location::GPR(GPR::RAX),
+                        Location::Memory(GPR::R14, (i * 16 + word * 8) as _),
+                    )?;
+                }
+            }
+        } else if sig.results() == [Type::V128] {
+            a.emit_movdqu(
+                XMMOrMemory::XMM(XMM::XMM0),
+                XMMOrMemory::Memory(GPR::R14, 0),
+            )?;
+        } else if !sig.results().is_empty() {
+            a.emit_mov(
+                Size::S64,
+                Location::GPR(GPR::RAX),
+                Location::Memory(GPR::R14, 0),
+            )?;
+        }
+
+        // Restore stack.
+        a.emit_add(
+            Size::S64,
+            Location::Imm32(stack_offset + stack_padding),
+            Location::GPR(GPR::RSP),
+        )?;
+
+        // Restore callee-saved registers.
+        a.emit_pop(Size::S64, Location::GPR(GPR::R14))?;
+        a.emit_pop(Size::S64, Location::GPR(GPR::R15))?;
+
+        a.emit_ret()?;
+
+        let mut body = a.finalize().unwrap();
+        body.shrink_to_fit();
+        Ok(FunctionBody {
+            body,
+            unwind_info: None,
+        })
+    }
+    // Generates dynamic import function call trampoline for a function type.
+    fn gen_std_dynamic_import_trampoline(
+        &self,
+        vmoffsets: &VMOffsets,
+        sig: &FunctionType,
+        calling_convention: CallingConvention,
+    ) -> Result<FunctionBody, CompileError> {
+        // the cpu feature here is irrelevant
+        let mut a = AssemblerX64::new(0, None)?;
+
+        let results_layout = ResultsLayout::new(sig.results());
+        let results_registers = results_layout.as_ref().and_then(|layout| {
+            self.get_results_registers(sig.results(), layout, calling_convention)
+        });
+        let results_address = results_layout.is_some() && results_registers.is_none();
+
+        // Allocate argument array.
+        let values_size = 16 * std::cmp::max(sig.params().len(), sig.results().len());
+        // Right above the values array, 16 bytes to assemble the struct of results if it
+        // is returned in registers, or to save the address of the memory receiving it.
+        let return_area_slot = if results_layout.is_some() { 16 } else { 0 };
+        let stack_offset: usize = values_size + return_area_slot + 8; // 16 bytes each + 8 bytes sysv call padding
+        let stack_padding: usize = match calling_convention {
+            CallingConvention::WindowsFastcall => 32,
+            _ => 0,
+        };
+        a.emit_sub(
+            Size::S64,
+            Location::Imm32((stack_offset + stack_padding) as _),
+            Location::GPR(GPR::RSP),
+        )?;
+
+        // Copy arguments.
+        let mut argalloc = ArgumentRegisterAllocator::default();
+        if results_address {
+            // Save the address of the memory receiving the results.
+            let Some(X64Register::GPR(gpr)) = argalloc.next(Type::I64, calling_convention)? else {
+                codegen_error!("singlepass gen_std_dynamic_import_trampoline unreachable");
+            };
+            a.emit_mov(
+                Size::S64,
+                Location::GPR(gpr),
+                Location::Memory(GPR::RSP, (stack_padding + values_size) as _),
+            )?;
+        }
+        let Some(X64Register::GPR(vmctx)) = argalloc.next(Type::I64, calling_convention)? else {
+            codegen_error!("singlepass gen_std_dynamic_import_trampoline unreachable");
+        };
+
+        let mut stack_param_offset: usize = 0;
+        for (i, ty) in sig.params().iter().enumerate() {
+            let offset = stack_padding + i * 16;
+            match argalloc.next(*ty, calling_convention)? {
+                Some(X64Register::XMM(xmm)) if *ty == Type::V128 => a.emit_movdqu(
+                    XMMOrMemory::XMM(xmm),
+                    XMMOrMemory::Memory(GPR::RSP, offset as _),
+                )?,
+                Some(X64Register::GPR(gpr)) => a.emit_mov(
+                    Size::S64,
+                    Location::GPR(gpr),
+                    Location::Memory(GPR::RSP, offset as _),
+                )?,
+                Some(X64Register::XMM(xmm)) => a.emit_mov(
+                    Size::S64,
+                    Location::SIMD(xmm),
+                    Location::Memory(GPR::RSP, offset as _),
+                )?,
+                None => {
+                    let words = if *ty == Type::V128 {
+                        stack_param_offset = stack_param_offset.next_multiple_of(16);
+                        2
+                    } else {
+                        1
+                    };
+                    for word in 0..words {
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                GPR::RSP,
+                                (stack_padding * 2 + stack_offset + 8 + stack_param_offset) as _,
+                            ),
+                            Location::GPR(GPR::RAX),
+                        )?;
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RAX),
+                            Location::Memory(GPR::RSP, (offset + word * 8) as _),
+                        )?;
+                        stack_param_offset += 8;
+                    }
+                }
+            }
+
+            // Zero upper 64 bits.
+            if *ty != Type::V128 {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm32(0),
+                    Location::Memory(GPR::RSP, (offset + 8) as _),
+                )?;
+            }
+        }
+
+        match calling_convention {
+            CallingConvention::WindowsFastcall => {
+                if vmctx != GPR::RCX {
+                    a.emit_mov(Size::S64, Location::GPR(vmctx), Location::GPR(GPR::RCX))?;
+                }
+                // Load target address.
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(
+                        GPR::RCX,
+                        vmoffsets.vmdynamicfunction_import_context_address() as i32,
+                    ),
                     Location::GPR(GPR::RAX),
                 )?;
                 // Load values array.
@@ -7983,6 +9598,9 @@ impl Machine for MachineX86_64 {
                 )?;
             }
             _ => {
+                if vmctx != GPR::RDI {
+                    a.emit_mov(Size::S64, Location::GPR(vmctx), Location::GPR(GPR::RDI))?;
+                }
                 // Load target address.
                 a.emit_mov(
                     Size::S64,
@@ -8000,14 +9618,70 @@ impl Machine for MachineX86_64 {
         // Call target.
         a.emit_call_location(Location::GPR(GPR::RAX))?;
 
-        // Fetch return value.
-        if !sig.results().is_empty() {
-            assert_eq!(sig.results().len(), 1);
+        if let Some(layout) = &results_layout {
+            // Write the struct of results.
+            let (area, area_offset) = if results_address {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(GPR::RSP, (stack_padding + values_size) as _),
+                    Location::GPR(GPR::RCX),
+                )?;
+                (GPR::RCX, 0)
+            } else {
+                (GPR::RSP, stack_padding + values_size)
+            };
+            for (i, (ty, offset)) in sig.results().iter().zip(&layout.offsets).enumerate() {
+                let (sz, words) = match ty {
+                    Type::I32 | Type::F32 => (Size::S32, 1),
+                    Type::V128 => (Size::S64, 2),
+                    _ => (Size::S64, 1),
+                };
+                for word in 0..words {
+                    a.emit_mov(
+                        sz,
+                        Location::Memory(GPR::RSP, (stack_padding + i * 16 + word * 8) as _),
+                        Location::GPR(GPR::RDX),
+                    )?;
+                    a.emit_mov(
+                        sz,
+                        Location::GPR(GPR::RDX),
+                        Location::Memory(area, (area_offset + offset + word * 8) as _),
+                    )?;
+                }
+            }
+            match &results_registers {
+                Some(parts) => {
+                    for (reg, offset, sz) in parts {
+                        a.emit_mov(
+                            *sz,
+                            Location::Memory(area, (area_offset + offset) as _),
+                            *reg,
+                        )?;
+                    }
+                }
+                // The address of the memory is returned as well.
+                None => a.emit_mov(Size::S64, Location::GPR(GPR::RCX), Location::GPR(GPR::RAX))?,
+            }
+        } else if sig.results() == [Type::V128] {
+            a.emit_movdqu(
+                XMMOrMemory::Memory(GPR::RSP, stack_padding as i32),
+                XMMOrMemory::XMM(XMM::XMM0),
+            )?;
+        } else if let Some(ty) = sig.results().first() {
+            // Fetch return value.
             a.emit_mov(
                 Size::S64,
                 Location::Memory(GPR::RSP, stack_padding as i32),
                 Location::GPR(GPR::RAX),
             )?;
+            // Make a copy of a float in XMM0, where callers read it from.
+            if *ty == Type::F32 || *ty == Type::F64 {
+                a.emit_mov(
+                    Size::S64,
+                    Location::GPR(GPR::RAX),
+                    Location::SIMD(XMM::XMM0),
+                )?;
+            }
         }
 
         // Release values array.
@@ -8039,54 +9713,70 @@ impl Machine for MachineX86_64 {
         let mut a = AssemblerX64::new(0, None)?;
 
         // TODO: ARM entry trampoline is not emitted.
+        let params = sig.params();
+        // The address of the memory receiving the results comes before the vmctx, and is
+        // passed through untouched.
+        let first_param = match ResultsLayout::new(sig.results()) {
+            Some(layout)
+                if self
+                    .get_results_registers(sig.results(), &layout, calling_convention)
+                    .is_none() =>
+            {
+                2
+            }
+            _ => 1,
+        };
+        let has_v128 = params.contains(&Type::V128);
+        if has_v128 && calling_convention == CallingConvention::WindowsFastcall {
+            // `v128` arguments are passed by reference there.
+            codegen_error!(
+                "singlepass can't pass v128 arguments to imported functions with {:?}",
+                calling_convention
+            );
+        }
 
-        // Singlepass internally treats all arguments as integers
+        // Singlepass internally treats all arguments as integers, except `v128` ones
+        // which are passed in XMM registers like the native calling conventions do.
         // For the standard Windows calling convention requires
         //  floating point arguments to be passed in XMM registers for the 4 first arguments only
         //  That's the only change to do, other arguments are not to be changed
         // For the standard System V calling convention requires
         //  floating point arguments to be passed in XMM registers.
         //  Translation is expensive, so only do it if needed.
-        if sig
-            .params()
-            .iter()
-            .any(|&x| x == Type::F32 || x == Type::F64)
-        {
+        if params.iter().any(|&x| x == Type::F32 || x == Type::F64) {
             match calling_convention {
                 CallingConvention::WindowsFastcall => {
-                    let mut param_locations: Vec<Location> = vec![];
-                    static PARAM_REGS: &[GPR] = &[GPR::RDX, GPR::R8, GPR::R9];
-                    #[allow(clippy::needless_range_loop)]
-                    for i in 0..sig.params().len() {
-                        let loc = match i {
-                            0..=2 => Location::GPR(PARAM_REGS[i]),
-                            _ => Location::Memory(GPR::RSP, 32 + 8 + ((i - 3) * 8) as i32), // will not be used anyway
-                        };
-                        param_locations.push(loc);
-                    }
+                    static PARAM_REGS: &[GPR] = &[GPR::RCX, GPR::RDX, GPR::R8, GPR::R9];
+                    let param_regs = &PARAM_REGS[first_param..];
 
                     // Copy Float arguments to XMM from GPR.
                     let mut argalloc = ArgumentRegisterAllocator::default();
-                    for (i, ty) in sig.params().iter().enumerate() {
-                        let prev_loc = param_locations[i];
+                    for _ in 0..first_param {
+                        argalloc.next(Type::I64, calling_convention)?.unwrap(); // skip VMContext
+                    }
+                    for (ty, prev_reg) in params.iter().zip(param_regs) {
                         match argalloc.next(*ty, calling_convention)? {
                             Some(X64Register::GPR(_gpr)) => continue,
-                            Some(X64Register::XMM(xmm)) => {
-                                a.emit_mov(Size::S64, prev_loc, Location::SIMD(xmm))?
-                            }
+                            Some(X64Register::XMM(xmm)) => a.emit_mov(
+                                Size::S64,
+                                Location::GPR(*prev_reg),
+                                Location::SIMD(xmm),
+                            )?,
                             None => continue,
                         };
                     }
                 }
                 _ => {
                     let mut param_locations = vec![];
+                    static PARAM_REGS: &[GPR] =
+                        &[GPR::RDI, GPR::RSI, GPR::RDX, GPR::RCX, GPR::R8, GPR::R9];
+                    let param_regs = &PARAM_REGS[first_param..];
+                    let n_scalars = params.iter().filter(|&&ty| ty != Type::V128).count();
+                    let n_simds = params.len() - n_scalars;
 
                     // Allocate stack space for arguments.
-                    let stack_offset: i32 = if sig.params().len() > 5 {
-                        5 * 8
-                    } else {
-                        (sig.params().len() as i32) * 8
-                    };
+                    let scalars_size = (n_scalars.min(param_regs.len()) as i32) * 8;
+                    let stack_offset: i32 = scalars_size + (n_simds.min(8) as i32) * 16;
                     if stack_offset > 0 {
                         a.emit_sub(
                             Size::S64,
@@ -8096,17 +9786,34 @@ impl Machine for MachineX86_64 {
                     }
 
                     // Store all arguments to the stack to prevent overwrite.
-                    static PARAM_REGS: &[GPR] = &[GPR::RSI, GPR::RDX, GPR::RCX, GPR::R8, GPR::R9];
-                    #[allow(clippy::needless_range_loop)]
-                    for i in 0..sig.params().len() {
-                        let loc = match i {
-                            0..=4 => {
-                                let loc = Location::Memory(GPR::RSP, (i * 8) as i32);
-                                a.emit_mov(Size::S64, Location::GPR(PARAM_REGS[i]), loc)?;
-                                loc
+                    let (mut scalar_idx, mut simd_idx) = (0, 0);
+                    for ty in params {
+                        let loc = if *ty == Type::V128 {
+                            if simd_idx >= 8 {
+                                codegen_error!(
+                                    "singlepass can't pass more than 8 v128 arguments to imported functions"
+                                );
                             }
-                            _ => {
-                                Location::Memory(GPR::RSP, stack_offset + 8 + ((i - 5) * 8) as i32)
+                            let loc = Location::Memory(GPR::RSP, scalars_size + simd_idx * 16);
+                            a.emit_movdqu(
+                                XMMOrMemory::XMM(XMM::from_index(simd_idx as usize).unwrap()),
+                                XMMOrMemory::Memory(GPR::RSP, scalars_size + simd_idx * 16),
+                            )?;
+                            simd_idx += 1;
+                            loc
+                        } else {
+                            let i = scalar_idx;
+                            scalar_idx += 1;
+                            match param_regs.get(i) {
+                                Some(reg) => {
+                                    let loc = Location::Memory(GPR::RSP, (i * 8) as i32);
+                                    a.emit_mov(Size::S64, Location::GPR(*reg), loc)?;
+                                    loc
+                                }
+                                None => Location::Memory(
+                                    GPR::RSP,
+                                    stack_offset + 8 + ((i - param_regs.len()) * 8) as i32,
+                                ),
                             }
                         };
                         param_locations.push(loc);
@@ -8114,13 +9821,35 @@ impl Machine for MachineX86_64 {
 
                     // Copy arguments.
                     let mut argalloc = ArgumentRegisterAllocator::default();
-                    argalloc.next(Type::I64, calling_convention)?.unwrap(); // skip VMContext
+                    for _ in 0..first_param {
+                        argalloc.next(Type::I64, calling_convention)?.unwrap(); // skip VMContext
+                    }
                     let mut caller_stack_offset: i32 = 0;
-                    for (i, ty) in sig.params().iter().enumerate() {
+                    for (i, ty) in params.iter().enumerate() {
                         let prev_loc = param_locations[i];
                         let targ = match argalloc.next(*ty, calling_convention)? {
+                            Some(X64Register::XMM(xmm)) if *ty == Type::V128 => {
+                                let Location::Memory(base, disp) = prev_loc else {
+                                    codegen_error!(
+                                        "singlepass gen_import_call_trampoline unreachable"
+                                    );
+                                };
+                                a.emit_movdqu(
+                                    XMMOrMemory::Memory(base, disp),
+                                    XMMOrMemory::XMM(xmm),
+                                )?;
+                                continue;
+                            }
                             Some(X64Register::GPR(gpr)) => Location::GPR(gpr),
                             Some(X64Register::XMM(xmm)) => Location::SIMD(xmm),
+                            None if has_v128 => {
+                                // The floats taking the XMM registers of the `v128`
+                                // arguments could need more stack than the caller reserved.
+                                codegen_error!(
+                                    "singlepass can't pass {} arguments on the stack to imported functions taking v128 arguments",
+                                    ty
+                                );
+                            }
                             None => {
                                 // No register can be allocated. Put this argument on the stack.
                                 //
@@ -8159,33 +9888,22 @@ impl Machine for MachineX86_64 {
         // from Ctx and jumps to it.
 
         let offset = vmoffsets.vmctx_vmfunction_import(index);
+        let Location::GPR(vmctx) =
+            self.get_simple_param_location(first_param - 1, calling_convention)
+        else {
+            codegen_error!("singlepass gen_import_call_trampoline unreachable");
+        };
 
-        match calling_convention {
-            CallingConvention::WindowsFastcall => {
-                a.emit_mov(
-                    Size::S64,
-                    Location::Memory(GPR::RCX, offset as i32), // function pointer
-                    Location::GPR(GPR::RAX),
-                )?;
-                a.emit_mov(
-                    Size::S64,
-                    Location::Memory(GPR::RCX, offset as i32 + 8), // target vmctx
-                    Location::GPR(GPR::RCX),
-                )?;
-            }
-            _ => {
-                a.emit_mov(
-                    Size::S64,
-                    Location::Memory(GPR::RDI, offset as i32), // function pointer
-                    Location::GPR(GPR::RAX),
-                )?;
-                a.emit_mov(
-                    Size::S64,
-                    Location::Memory(GPR::RDI, offset as i32 + 8), // target vmctx
-                    Location::GPR(GPR::RDI),
-                )?;
-            }
-        }
+        a.emit_mov(
+            Size::S64,
+            Location::Memory(vmctx, offset as i32), // function pointer
+            Location::GPR(GPR::RAX),
+        )?;
+        a.emit_mov(
+            Size::S64,
+            Location::Memory(vmctx, offset as i32 + 8), // target vmctx
+            Location::GPR(vmctx),
+        )?;
         a.emit_host_redirection(GPR::RAX)?;
 
         let mut contents = a.finalize().unwrap();
@@ -8260,7 +9978,7 @@ impl Machine for MachineX86_64 {
 #[cfg(test)]
 mod test {
     use super::*;
-    use enumset::enum_set;
+    use enumset::{EnumSet, enum_set};
     use std::str::FromStr;
     use wasmer_types::target::{CpuFeature, Target, Triple};
 
@@ -8601,6 +10319,120 @@ mod test {
         Ok(())
     }
 
+    fn test_v128_binop(machine: &mut MachineX86_64, op: &Operator) -> Result<(), CompileError> {
+        for (loc_a, loc_b, ret) in [
+            (XMM::XMM3, XMM::XMM4, XMM::XMM5),
+            (XMM::XMM3, XMM::XMM4, XMM::XMM3),
+            (XMM::XMM3, XMM::XMM4, XMM::XMM4),
+            (XMM::XMM3, XMM::XMM3, XMM::XMM3),
+        ] {
+            machine.v128_binop(
+                op,
+                Location::SIMD(loc_a),
+                Location::SIMD(loc_b),
+                Location::SIMD(ret),
+            )?;
+        }
+        machine.v128_binop(
+            op,
+            Location::SIMD(XMM::XMM3),
+            Location::Memory(GPR::RBP, -16),
+            Location::SIMD(XMM::XMM4),
+        )?;
+        machine.v128_binop(
+            op,
+            Location::Memory(GPR::RBP, -16),
+            Location::Memory(GPR::RBP, -1024),
+            Location::Memory(GPR::RBP, -48),
+        )?;
+        machine.v128_binop(
+            op,
+            Location::Memory(GPR::RBP, 32),
+            Location::SIMD(XMM::XMM3),
+            Location::Memory(GPR::RSP, 0x10000),
+        )?;
+        assert!(machine.get_used_simd().is_empty());
+        assert!(machine.get_used_gprs().is_empty());
+
+        Ok(())
+    }
+
+    fn test_v128_unop(machine: &mut MachineX86_64, op: &Operator) -> Result<(), CompileError> {
+        for (loc, ret) in [
+            (Location::SIMD(XMM::XMM3), Location::SIMD(XMM::XMM4)),
+            (Location::SIMD(XMM::XMM3), Location::SIMD(XMM::XMM3)),
+            (Location::Memory(GPR::RBP, -16), Location::SIMD(XMM::XMM3)),
+            (Location::SIMD(XMM::XMM3), Location::Memory(GPR::RBP, -1024)),
+        ] {
+            machine.v128_unop(op, loc, ret)?;
+        }
+        assert!(machine.get_used_simd().is_empty());
+        assert!(machine.get_used_gprs().is_empty());
+
+        Ok(())
+    }
+
+    fn test_v128(machine: &mut MachineX86_64) -> Result<(), CompileError> {
+        for op in [
+            Operator::I8x16Add,
+            Operator::I16x8Sub,
+            Operator::I32x4Mul,
+            Operator::I64x2Mul,
+            Operator::I8x16AddSatU,
+            Operator::I16x8Q15MulrSatS,
+            Operator::I32x4MinS,
+            Operator::I8x16AvgrU,
+            Operator::I16x8Ne,
+            Operator::I32x4LtU,
+            Operator::I64x2LeS,
+            Operator::V128AndNot,
+            Operator::I8x16NarrowI16x8U,
+            Operator::I32x4DotI16x8S,
+            Operator::I64x2ExtMulHighI32x4S,
+            Operator::I8x16Swizzle,
+            Operator::I8x16Shuffle {
+                lanes: [0, 17, 2, 19, 4, 21, 6, 23, 8, 25, 10, 27, 12, 29, 14, 31],
+            },
+            Operator::F32x4Div,
+            Operator::F64x2Max,
+            Operator::F32x4PMin,
+            Operator::F64x2PMax,
+            Operator::F32x4Le,
+            Operator::F64x2Ne,
+        ] {
+            test_v128_binop(machine, &op)?;
+        }
+        for op in [
+            Operator::V128Not,
+            Operator::I8x16Popcnt,
+            Operator::I64x2Abs,
+            Operator::I16x8ExtAddPairwiseI8x16U,
+            Operator::I32x4ExtAddPairwiseI16x8U,
+            Operator::I32x4ExtendHighI16x8S,
+            Operator::F32x4Nearest,
+            Operator::F32x4ConvertI32x4U,
+            Operator::F64x2ConvertLowI32x4U,
+            Operator::I32x4TruncSatF32x4U,
+            Operator::I32x4TruncSatF64x2UZero,
+            Operator::F32x4DemoteF64x2Zero,
+        ] {
+            test_v128_unop(machine, &op)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the code emitted by `f` on a new machine with the given features.
+    fn v128_code(
+        set: EnumSet<CpuFeature>,
+        f: impl FnOnce(&mut MachineX86_64) -> Result<(), CompileError>,
+    ) -> Result<Vec<u8>, CompileError> {
+        let target = Target::new(Triple::from_str("x86_64-linux-gnu").unwrap(), set);
+        let mut machine = MachineX86_64::new(Some(target))?;
+        f(&mut machine)?;
+        machine.assembler_finalize()
+    }
+
     #[test]
     fn tests_avx() -> Result<(), CompileError> {
         let set = enum_set!(CpuFeature::AVX);
@@ -8633,6 +10465,7 @@ mod test {
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_eq)?;
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_lt)?;
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_le)?;
+        test_v128(&mut machine)?;
 
         Ok(())
     }
@@ -8669,6 +10502,98 @@ mod test {
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_eq)?;
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_lt)?;
         test_float_cmp_op(&mut machine, MachineX86_64::f32_cmp_le)?;
+        test_v128(&mut machine)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn tests_v128_in_registers() -> Result<(), CompileError> {
+        let avx = enum_set!(CpuFeature::AVX);
+        let sse42 = enum_set!(CpuFeature::SSE42);
+        let add = |m: &mut MachineX86_64| {
+            m.v128_binop(
+                &Operator::I32x4Add,
+                Location::SIMD(XMM::XMM3),
+                Location::SIMD(XMM::XMM4),
+                Location::SIMD(XMM::XMM5),
+            )
+        };
+        // vpaddd xmm5, xmm3, xmm4
+        assert_eq!(v128_code(avx, add)?, [0xc4, 0xe1, 0x61, 0xfe, 0xec]);
+        // movdqa xmm5, xmm3; paddd xmm5, xmm4
+        assert_eq!(
+            v128_code(sse42, add)?,
+            [0x66, 0x40, 0x0f, 0x6f, 0xeb, 0x66, 0x40, 0x0f, 0xfe, 0xec]
+        );
+
+        // The result overwriting an operand goes through a temporary register:
+        // vcmpltps xmm0, xmm3, xmm4; movdqa xmm3, xmm0
+        let code = v128_code(avx, |m| {
+            m.v128_binop(
+                &Operator::F32x4Lt,
+                Location::SIMD(XMM::XMM3),
+                Location::SIMD(XMM::XMM4),
+                Location::SIMD(XMM::XMM3),
+            )
+        })?;
+        assert_eq!(
+            code,
+            [
+                0xc4, 0xe1, 0x60, 0xc2, 0xc4, 0x01, 0x66, 0x40, 0x0f, 0x6f, 0xd8
+            ]
+        );
+
+        // vpand xmm6, xmm3, xmm5; vpandn xmm0, xmm5, xmm4; vpor xmm6, xmm6, xmm0
+        let code = v128_code(avx, |m| {
+            m.v128_bitselect(
+                Location::SIMD(XMM::XMM3),
+                Location::SIMD(XMM::XMM4),
+                Location::SIMD(XMM::XMM5),
+                Location::SIMD(XMM::XMM6),
+            )
+        })?;
+        assert_eq!(
+            code,
+            [
+                0xc4, 0xe1, 0x61, 0xdb, 0xf5, 0xc4, 0xe1, 0x51, 0xdf, 0xc4, 0xc4, 0xe1, 0x49, 0xeb,
+                0xf0
+            ]
+        );
+
+        // vpxor xmm0, xmm0, xmm0; vpblendw xmm5, xmm3, xmm0, 0xaa;
+        // vpsrld xmm0, xmm3, 16; vpaddd xmm5, xmm5, xmm0
+        let code = v128_code(avx, |m| {
+            m.v128_unop(
+                &Operator::I32x4ExtAddPairwiseI16x8U,
+                Location::SIMD(XMM::XMM3),
+                Location::SIMD(XMM::XMM5),
+            )
+        })?;
+        assert_eq!(
+            code,
+            [
+                0xc4, 0xe1, 0x79, 0xef, 0xc0, 0xc4, 0xe3, 0x61, 0x0e, 0xe8, 0xaa, 0xc4, 0xe1, 0x79,
+                0x72, 0xd3, 0x10, 0xc4, 0xe1, 0x51, 0xfe, 0xe8
+            ]
+        );
+
+        // movdqu xmm0, [rbp + 32]; vpsrad xmm1, xmm0, 3; movdqu [rsp + 16], xmm1
+        let code = v128_code(avx, |m| {
+            m.v128_shift(
+                &Operator::I32x4ShrS,
+                Location::Memory(GPR::RBP, 32),
+                Location::Imm32(35),
+                Location::Memory(GPR::RSP, 16),
+            )
+        })?;
+        assert_eq!(
+            code,
+            [
+                0xf3, 0x40, 0x0f, 0x6f, 0x84, 0x25, 0x20, 0x00, 0x00, 0x00, 0xc4, 0xe1, 0x71, 0x72,
+                0xe0, 0x03, 0xf3, 0x40, 0x0f, 0x7f, 0x8c, 0x24, 0x10, 0x00, 0x00, 0x00
+            ]
+        );
 
         Ok(())
     }
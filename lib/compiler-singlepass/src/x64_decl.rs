@@ -326,7 +326,7 @@ impl ArgumentRegisterAllocator {
                             None
                         }
                     }
-                    Type::F32 | Type::F64 => {
+                    Type::F32 | Type::F64 | Type::V128 => {
                         if self.n_xmms < XMM_SEQ.len() {
                             let xmm = XMM_SEQ[self.n_xmms];
                             self.n_xmms += 1;
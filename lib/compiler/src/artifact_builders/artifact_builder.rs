@@ -76,9 +76,16 @@ impl ArtifactBuild {
         let mut module = translation.module;
         let middlewares = compiler.get_middlewares();
         middlewares
-            .apply_on_module_info(&mut module)
+            .apply_on_module(&mut module, &translation.function_body_inputs)
             .map_err(|err| CompileError::MiddlewareError(err.to_string()))?;
 
+        // Middlewares may append tables to the module, which can only have the
+        // default table style.
+        let mut table_styles = table_styles;
+        while table_styles.len() < module.tables.len() {
+            table_styles.push(TableStyle::CallerChecksSignature);
+        }
+
         if let Some(hash_algorithm) = hash_algorithm {
             let hash = match hash_algorithm {
                 HashAlgorithm::Sha256 => ModuleHash::sha256(data),
@@ -2,7 +2,7 @@
 //! to allow compiling and instantiating to be done as separate steps.
 
 use std::sync::{
-    Arc,
+    Arc, OnceLock,
     atomic::{AtomicUsize, Ordering::SeqCst},
 };
 
@@ -10,7 +10,8 @@ use std::sync::{
 use crate::ModuleEnvironment;
 use crate::{
     ArtifactBuild, ArtifactBuildFromArchive, ArtifactCreate, Engine, EngineInner, Features,
-    FrameInfosVariant, FunctionExtent, GlobalFrameInfoRegistration, InstantiationError, Tunables,
+    FrameInfosVariant, FunctionExtent, GlobalFrameInfoRegistration, InstantiationError, LinkError,
+    Tunables,
     engine::{link::link_module, resolver::resolve_tags},
     lib::std::vec::IntoIter,
     register_frame_info, resolve_imports,
@@ -38,15 +39,16 @@ use wasmer_types::HashAlgorithm;
 use wasmer_types::{
     ArchivedDataInitializerLocation, ArchivedOwnedDataInitializer, CompileError, DataInitializer,
     DataInitializerLike, DataInitializerLocation, DataInitializerLocationLike, DeserializeError,
-    FunctionIndex, LocalFunctionIndex, MemoryIndex, ModuleInfo, OwnedDataInitializer,
-    SerializeError, SignatureIndex, TableIndex,
+    FunctionIndex, LocalFunctionIndex, LocalMemoryIndex, MemoryIndex, ModuleInfo,
+    OwnedDataInitializer, Pages, SerializeError, SignatureIndex, TableIndex,
     entity::{BoxedSlice, PrimaryMap},
     target::{CpuFeature, Target},
 };
 
 use wasmer_vm::{
-    FunctionBodyPtr, InstanceAllocator, MemoryStyle, StoreObjects, TableStyle, TrapHandlerFn,
-    VMConfig, VMExtern, VMInstance, VMSharedSignatureIndex, VMTrampoline,
+    FunctionBodyPtr, InstanceAllocator, LimitedMemory, LimitedTable, MemoryImage, MemoryStyle,
+    StoreObjects, TableStyle, TrapHandlerFn, VMConfig, VMExtern, VMInstance,
+    VMSharedSignatureIndex, VMTrampoline,
 };
 
 #[cfg_attr(feature = "artifact-size", derive(loupe::MemoryUsage))]
@@ -67,6 +69,10 @@ pub struct AllocatedArtifact {
     finished_dynamic_function_trampolines: BoxedSlice<FunctionIndex, FunctionBodyPtr>,
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    // The copy-on-write images of the local memories, built on the first
+    // instantiation.
+    #[cfg_attr(feature = "artifact-size", loupe(skip))]
+    memory_images: OnceLock<Vec<(LocalMemoryIndex, MemoryImage)>>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -491,6 +497,7 @@ impl Artifact {
                 finished_dynamic_function_trampolines,
                 signatures,
                 finished_function_lengths,
+                memory_images: OnceLock::new(),
             }),
         };
 
@@ -863,29 +870,54 @@ impl Artifact {
             )
             .map_err(InstantiationError::Link)?;
 
+            // Let the resource limiter of the store deny the initial size of the
+            // local memories and tables, as a growth from zero.
+            if let Some(limiter) = context.limiter_mut() {
+                for (index, ty) in module.memories.iter().skip(module.num_imported_memories) {
+                    if !limiter.memory_growing(
+                        LimitedMemory::Instantiating(index),
+                        Pages(0),
+                        ty.minimum,
+                        ty.maximum,
+                    ) {
+                        return Err(InstantiationError::Link(LinkError::Resource(format!(
+                            "memory of {} pages denied by the resource limiter",
+                            ty.minimum.0
+                        ))));
+                    }
+                }
+                for (index, ty) in module.tables.iter().skip(module.num_imported_tables) {
+                    if !limiter.table_growing(
+                        LimitedTable::Instantiating(index),
+                        0,
+                        ty.minimum,
+                        ty.maximum,
+                    ) {
+                        return Err(InstantiationError::Link(LinkError::Resource(format!(
+                            "table of {} elements denied by the resource limiter",
+                            ty.minimum
+                        ))));
+                    }
+                }
+            }
+
             // Get pointers to where metadata about local memories should live in VM memory.
             // Get pointers to where metadata about local tables should live in VM memory.
 
             let (allocator, memory_definition_locations, table_definition_locations) =
                 InstanceAllocator::new(&module);
-            let finished_memories = tunables
-                .create_memories(
+            let (finished_memories, finished_tables) = tunables
+                .create_memories_and_tables(
                     context,
                     &module,
                     self.memory_styles(),
                     &memory_definition_locations,
-                )
-                .map_err(InstantiationError::Link)?
-                .into_boxed_slice();
-            let finished_tables = tunables
-                .create_tables(
-                    context,
-                    &module,
                     self.table_styles(),
                     &table_definition_locations,
                 )
-                .map_err(InstantiationError::Link)?
-                .into_boxed_slice();
+                .map_err(InstantiationError::Link)?;
+            let finished_memories = finished_memories.into_boxed_slice();
+            let finished_tables = finished_tables.into_boxed_slice();
             let finished_globals = tunables
                 .create_globals(context, &module)
                 .map_err(InstantiationError::Link)?
@@ -909,6 +941,61 @@ impl Artifact {
         }
     }
 
+    /// Returns the copy-on-write images of the local memories of this
+    /// artifact, building them on first use.
+    fn memory_images(&self) -> &[(LocalMemoryIndex, MemoryImage)] {
+        match &self.allocated {
+            Some(allocated) => allocated
+                .memory_images
+                .get_or_init(|| self.build_memory_images()),
+            None => &[],
+        }
+    }
+
+    /// Builds an image of every local memory whose initial contents are
+    /// fully known ahead of instantiation: the memory is not shared, and
+    /// all its data initializers have constant offsets and fit in its
+    /// minimum size. The other memories keep copying their data segments
+    /// at instantiation, which also reports out of bounds segments.
+    #[allow(clippy::type_complexity)]
+    fn build_memory_images(&self) -> Vec<(LocalMemoryIndex, MemoryImage)> {
+        let module = self.module_info();
+        let mut segments: PrimaryMap<LocalMemoryIndex, Option<Vec<(usize, &[u8])>>> = module
+            .memories
+            .values()
+            .skip(module.num_imported_memories)
+            .map(|ty| if ty.shared { None } else { Some(Vec::new()) })
+            .collect();
+
+        for init in self.data_initializers() {
+            let location = init.location();
+            let Some(index) = module.local_memory_index(location.memory_index()) else {
+                continue;
+            };
+            let minimum = module.memories[location.memory_index()].minimum.bytes().0;
+            let data = init.data();
+            let fits = location.base().is_none()
+                && location
+                    .offset()
+                    .checked_add(data.len())
+                    .is_some_and(|end| end <= minimum);
+            match &mut segments[index] {
+                Some(memory_segments) if fits => memory_segments.push((location.offset(), data)),
+                memory_segments => *memory_segments = None,
+            }
+        }
+
+        segments
+            .into_iter()
+            .filter_map(|(index, memory_segments)| {
+                // Failing to build an image is not an error: the data
+                // segments are copied instead.
+                let image = MemoryImage::new(&memory_segments?).ok()??;
+                Some((index, image))
+            })
+            .collect()
+    }
+
     /// Finishes the instantiation of a just created `VMInstance`.
     ///
     /// # Safety
@@ -922,8 +1009,21 @@ impl Artifact {
         handle: &mut VMInstance,
     ) -> Result<(), InstantiationError> {
         unsafe {
+            // Map the memory images first: the data initializers of the
+            // memories they were applied to are then skipped.
+            let mut imaged_memories = Vec::new();
+            for (index, image) in self.memory_images() {
+                if handle
+                    .initialize_memory_with_image(*index, image)
+                    .map_err(|e| InstantiationError::Link(LinkError::Resource(e.to_string())))?
+                {
+                    imaged_memories.push(self.module_info().memory_index(*index));
+                }
+            }
+
             let data_initializers = self
                 .data_initializers()
+                .filter(|init| !imaged_memories.contains(&init.location().memory_index()))
                 .map(|init| DataInitializer {
                     location: init.location().clone_to_plain(),
                     data: init.data(),
@@ -960,7 +1060,7 @@ impl Artifact {
         let mut module = translation.module;
         let middlewares = compiler.get_middlewares();
         middlewares
-            .apply_on_module_info(&mut module)
+            .apply_on_module(&mut module, &translation.function_body_inputs)
             .map_err(|e| CompileError::MiddlewareError(e.to_string()))?;
 
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
@@ -1291,6 +1391,7 @@ impl Artifact {
                         .into_boxed_slice(),
                     signatures: signatures.into_boxed_slice(),
                     finished_function_lengths,
+                    memory_images: OnceLock::new(),
                 }),
             })
         }
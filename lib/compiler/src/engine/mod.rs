@@ -25,7 +25,7 @@ pub use self::resolver::resolve_imports;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::trap::*;
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::tunables::{BaseTunables, Tunables};
+pub use self::tunables::{BaseTunables, PoolingTunables, Tunables};
 
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::artifact::Artifact;
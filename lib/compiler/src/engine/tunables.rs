@@ -6,7 +6,10 @@ use wasmer_types::{
     entity::{EntityRef, PrimaryMap},
     target::{PointerWidth, Target},
 };
-use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects, VMTag};
+use wasmer_vm::{
+    InstanceSlot, InternalStoreHandle, MemoryError, PoolingAllocator, PoolingConfig, StoreObjects,
+    VMTag,
+};
 use wasmer_vm::{MemoryStyle, TableStyle};
 use wasmer_vm::{VMConfig, VMGlobal, VMMemory, VMTable};
 use wasmer_vm::{VMMemoryDefinition, VMTableDefinition};
@@ -134,6 +137,41 @@ pub trait Tunables {
         }
     }
 
+    /// Allocate memory for the memories and the tables of the current
+    /// module.
+    ///
+    /// Implementors allocating memories and tables together, for example
+    /// from a pool, override this rather than `create_memories` and
+    /// `create_tables`.
+    ///
+    /// # Safety
+    /// - `memory_definition_locations` and `table_definition_locations`
+    ///   must point to valid locations in VM memory.
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    unsafe fn create_memories_and_tables(
+        &self,
+        context: &mut StoreObjects,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        memory_definition_locations: &[NonNull<VMMemoryDefinition>],
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        table_definition_locations: &[NonNull<VMTableDefinition>],
+    ) -> Result<
+        (
+            PrimaryMap<LocalMemoryIndex, InternalStoreHandle<VMMemory>>,
+            PrimaryMap<LocalTableIndex, InternalStoreHandle<VMTable>>,
+        ),
+        LinkError,
+    > {
+        unsafe {
+            let memories =
+                self.create_memories(context, module, memory_styles, memory_definition_locations)?;
+            let tables =
+                self.create_tables(context, module, table_styles, table_definition_locations)?;
+            Ok((memories, tables))
+        }
+    }
+
     /// Allocate memory for just the globals of the current module,
     /// with initializers applied.
     #[allow(clippy::result_large_err)]
@@ -294,6 +332,208 @@ impl Tunables for BaseTunables {
     }
 }
 
+/// Tunables allocating the memories and tables of instances from a
+/// [`PoolingAllocator`].
+///
+/// The address space of all the memories is reserved when the tunables
+/// are created. Every instance takes an instance slot of the pool, and
+/// each of its memories and tables a slot of their kind. The slots are
+/// given back to the pool, with the pages of the memories released to the
+/// OS, when the instance is dropped. Instantiating
+/// more than [`PoolingConfig::max_instances`] instances at the same time
+/// fails, which bounds the memory used by a host running many instances.
+///
+/// The memories of an instance cannot grow past
+/// [`PoolingConfig::max_memory_pages`]. Unless that is the whole 4 GiB
+/// of a 32-bit memory, memories use the dynamic style, with explicit
+/// bounds checks. Shared memories, and memories or tables created by the
+/// host, are not allocated from the pool.
+///
+/// Instances without local memories or tables do not take a slot.
+#[derive(Clone, Debug)]
+pub struct PoolingTunables {
+    allocator: PoolingAllocator,
+}
+
+impl PoolingTunables {
+    /// Creates the tunables, reserving the address space of the pool.
+    pub fn new(config: PoolingConfig) -> Result<Self, String> {
+        Ok(Self {
+            allocator: PoolingAllocator::new(config)?,
+        })
+    }
+
+    /// Returns the allocator memories and tables are allocated from.
+    pub fn allocator(&self) -> &PoolingAllocator {
+        &self.allocator
+    }
+
+    /// Creates a VM memory in a memory slot of the pool, for `slot` if
+    /// any, unless the memory is shared.
+    unsafe fn create_pooled_memory(
+        &self,
+        slot: Option<&InstanceSlot>,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        unsafe {
+            match slot {
+                _ if ty.shared => VMMemory::from_definition(ty, style, vm_definition_location),
+                Some(slot) => slot.memory_from_definition(ty, style, vm_definition_location),
+                None => self
+                    .allocator
+                    .memory_from_definition(ty, style, vm_definition_location),
+            }
+        }
+    }
+}
+
+impl Tunables for PoolingTunables {
+    /// Get a [`MemoryStyle`] for the provided [`MemoryType`].
+    fn memory_style(&self, _memory: &MemoryType) -> MemoryStyle {
+        let config = self.allocator.config();
+        if config.max_memory_pages >= Pages::max_value() {
+            MemoryStyle::Static {
+                bound: config.max_memory_pages,
+                offset_guard_size: config.memory_offset_guard_size,
+            }
+        } else {
+            MemoryStyle::Dynamic {
+                offset_guard_size: config.memory_offset_guard_size,
+            }
+        }
+    }
+
+    /// Get a [`TableStyle`] for the provided [`TableType`].
+    fn table_style(&self, _table: &TableType) -> TableStyle {
+        TableStyle::CallerChecksSignature
+    }
+
+    /// Create a memory owned by the host given a [`MemoryType`] and a [`MemoryStyle`].
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        VMMemory::new(ty, style)
+    }
+
+    /// Create a memory owned by the VM in a memory slot of the pool,
+    /// without taking an instance slot.
+    ///
+    /// # Safety
+    /// - `vm_definition_location` must point to a valid, owned `VMMemoryDefinition`,
+    ///   for example in `VMContext`.
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        unsafe { self.create_pooled_memory(None, ty, style, vm_definition_location) }
+    }
+
+    /// Create a table owned by the host given a [`TableType`] and a [`TableStyle`].
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        VMTable::new(ty, style)
+    }
+
+    /// Create a table owned by the VM in a table slot of the pool, without
+    /// taking an instance slot.
+    ///
+    /// # Safety
+    /// - `vm_definition_location` must point to a valid, owned `VMTableDefinition`,
+    ///   for example in `VMContext`.
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        unsafe {
+            self.allocator
+                .table_from_definition(ty, style, vm_definition_location)
+        }
+    }
+
+    /// Allocate the memories and the tables of the current module from a
+    /// single slot of the pool.
+    ///
+    /// # Safety
+    /// - `memory_definition_locations` and `table_definition_locations`
+    ///   must point to valid locations in VM memory.
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    unsafe fn create_memories_and_tables(
+        &self,
+        context: &mut StoreObjects,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        memory_definition_locations: &[NonNull<VMMemoryDefinition>],
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        table_definition_locations: &[NonNull<VMTableDefinition>],
+    ) -> Result<
+        (
+            PrimaryMap<LocalMemoryIndex, InternalStoreHandle<VMMemory>>,
+            PrimaryMap<LocalTableIndex, InternalStoreHandle<VMTable>>,
+        ),
+        LinkError,
+    > {
+        let num_imported_memories = module.num_imported_memories;
+        let num_imported_tables = module.num_imported_tables;
+        let mut memories: PrimaryMap<LocalMemoryIndex, _> =
+            PrimaryMap::with_capacity(module.memories.len() - num_imported_memories);
+        let mut tables: PrimaryMap<LocalTableIndex, _> =
+            PrimaryMap::with_capacity(module.tables.len() - num_imported_tables);
+        if module.memories.len() == num_imported_memories
+            && module.tables.len() == num_imported_tables
+        {
+            return Ok((memories, tables));
+        }
+
+        // The memories and tables keep the slot in use until they are all dropped.
+        let slot = self
+            .allocator
+            .allocate_instance()
+            .map_err(LinkError::Resource)?;
+        unsafe {
+            for (index, mdl) in memory_definition_locations
+                .iter()
+                .enumerate()
+                .take(module.memories.len())
+                .skip(num_imported_memories)
+            {
+                let mi = MemoryIndex::new(index);
+                let ty = &module.memories[mi];
+                let style = &memory_styles[mi];
+                memories.push(InternalStoreHandle::new(
+                    context,
+                    self.create_pooled_memory(Some(&slot), ty, style, *mdl)
+                        .map_err(|e| {
+                            LinkError::Resource(format!("Failed to create memory: {e}"))
+                        })?,
+                ));
+            }
+            for (index, tdl) in table_definition_locations
+                .iter()
+                .enumerate()
+                .take(module.tables.len())
+                .skip(num_imported_tables)
+            {
+                let ti = TableIndex::new(index);
+                let ty = &module.tables[ti];
+                let style = &table_styles[ti];
+                tables.push(InternalStoreHandle::new(
+                    context,
+                    slot.table_from_definition(ty, style, *tdl)
+                        .map_err(LinkError::Resource)?,
+                ));
+            }
+        }
+        Ok((memories, tables))
+    }
+}
+
 impl Tunables for Box<dyn Tunables + Send + Sync> {
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
         self.as_ref().memory_style(memory)
@@ -338,6 +578,34 @@ impl Tunables for Box<dyn Tunables + Send + Sync> {
                 .create_vm_table(ty, style, vm_definition_location)
         }
     }
+
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    unsafe fn create_memories_and_tables(
+        &self,
+        context: &mut StoreObjects,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        memory_definition_locations: &[NonNull<VMMemoryDefinition>],
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        table_definition_locations: &[NonNull<VMTableDefinition>],
+    ) -> Result<
+        (
+            PrimaryMap<LocalMemoryIndex, InternalStoreHandle<VMMemory>>,
+            PrimaryMap<LocalTableIndex, InternalStoreHandle<VMTable>>,
+        ),
+        LinkError,
+    > {
+        unsafe {
+            self.as_ref().create_memories_and_tables(
+                context,
+                module,
+                memory_styles,
+                memory_definition_locations,
+                table_styles,
+                table_definition_locations,
+            )
+        }
+    }
 }
 
 impl Tunables for std::sync::Arc<dyn Tunables + Send + Sync> {
@@ -384,4 +652,32 @@ impl Tunables for std::sync::Arc<dyn Tunables + Send + Sync> {
                 .create_vm_table(ty, style, vm_definition_location)
         }
     }
+
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    unsafe fn create_memories_and_tables(
+        &self,
+        context: &mut StoreObjects,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        memory_definition_locations: &[NonNull<VMMemoryDefinition>],
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        table_definition_locations: &[NonNull<VMTableDefinition>],
+    ) -> Result<
+        (
+            PrimaryMap<LocalMemoryIndex, InternalStoreHandle<VMMemory>>,
+            PrimaryMap<LocalTableIndex, InternalStoreHandle<VMTable>>,
+        ),
+        LinkError,
+    > {
+        unsafe {
+            self.as_ref().create_memories_and_tables(
+                context,
+                module,
+                memory_styles,
+                memory_definition_locations,
+                table_styles,
+                table_definition_locations,
+            )
+        }
+    }
 }
@@ -15,7 +15,7 @@ use wasmer_types::{
     LocalFunctionIndex, MemoryIndex, MemoryType, ModuleInfo, SignatureIndex, TableIndex,
     TableInitializer, TableType,
 };
-use wasmer_types::{TagIndex, WasmResult};
+use wasmer_types::{TagIndex, TrapCode, WasmResult};
 
 /// Contains function data: bytecode and its offset in the module.
 #[derive(Hash)]
@@ -38,6 +38,12 @@ pub trait FunctionBinaryReader<'a> {
     /// Reads the next available `Operator`.
     fn read_operator(&mut self) -> WasmResult<Operator<'a>>;
 
+    /// Returns the trap code to use if the last operator returned by
+    /// `read_operator` was an `unreachable`.
+    fn trap_code(&self) -> TrapCode {
+        TrapCode::UnreachableCodeReached
+    }
+
     /// Returns the current position.
     fn current_position(&self) -> usize;
 
@@ -5,11 +5,12 @@ use smallvec::SmallVec;
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::{Deref, Range};
-use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, WasmResult};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, TrapCode, WasmResult};
 use wasmparser::{BinaryReader, Operator, ValType};
 
 use super::error::from_binaryreadererror_wasmerror;
-use crate::translator::environ::FunctionBinaryReader;
+use crate::translator::environ::{FunctionBinaryReader, FunctionBodyData};
 
 /// A shared builder for function middlewares.
 pub trait ModuleMiddleware: Debug + Send + Sync {
@@ -27,6 +28,18 @@ pub trait ModuleMiddleware: Debug + Send + Sync {
     fn transform_module_info(&self, _: &mut ModuleInfo) -> Result<(), MiddlewareError> {
         Ok(())
     }
+
+    /// Transforms a `ModuleInfo` struct in-place, with the original bodies of
+    /// the local functions at hand, for middlewares which need to look at the
+    /// whole module first. This is called before application on functions
+    /// begins, and calls `transform_module_info` by default.
+    fn transform_module(
+        &self,
+        module_info: &mut ModuleInfo,
+        _function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<(), MiddlewareError> {
+        self.transform_module_info(module_info)
+    }
 }
 
 /// A function middleware specialized for a single function.
@@ -50,6 +63,9 @@ pub struct MiddlewareBinaryReader<'a> {
 
     /// The backing middleware chain for this reader.
     chain: Vec<Box<dyn FunctionMiddleware>>,
+
+    /// The trap code attached to the last operator returned by `read_operator`.
+    trap_code: TrapCode,
 }
 
 /// The state of the binary reader. Exposed to middlewares to push their outputs.
@@ -58,8 +74,9 @@ pub struct MiddlewareReaderState<'a> {
     /// Raw binary reader.
     inner: BinaryReader<'a>,
 
-    /// The pending operations added by the middleware.
-    pending_operations: VecDeque<Operator<'a>>,
+    /// The pending operations added by the middleware, each with the trap
+    /// code it was pushed with (see `push_trap`).
+    pending_operations: VecDeque<(Operator<'a>, Option<TrapCode>)>,
 }
 
 /// Trait for generating middleware chains from "prototype" (generator) chains.
@@ -72,6 +89,14 @@ pub trait ModuleMiddlewareChain {
 
     /// Applies the chain on a `ModuleInfo` struct.
     fn apply_on_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError>;
+
+    /// Applies the chain on a `ModuleInfo` struct, given the bodies of its
+    /// local functions.
+    fn apply_on_module(
+        &self,
+        module_info: &mut ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<(), MiddlewareError>;
 }
 
 impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
@@ -92,24 +117,49 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
         }
         Ok(())
     }
+
+    /// Applies the chain on a `ModuleInfo` struct, given the bodies of its
+    /// local functions.
+    fn apply_on_module(
+        &self,
+        module_info: &mut ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<(), MiddlewareError> {
+        for item in self {
+            item.transform_module(module_info, function_bodies)?;
+        }
+        Ok(())
+    }
 }
 
 impl<'a> MiddlewareReaderState<'a> {
     /// Push an operator.
     pub fn push_operator(&mut self, operator: Operator<'a>) {
-        self.pending_operations.push_back(operator);
+        self.pending_operations.push_back((operator, None));
+    }
+
+    /// Push a trap with the given code.
+    ///
+    /// The trap is emitted as an `unreachable` operator that the compiler
+    /// lowers with `code` instead of `TrapCode::UnreachableCodeReached`.
+    /// Later stages of the middleware chain do not see it.
+    pub fn push_trap(&mut self, code: TrapCode) {
+        self.pending_operations
+            .push_back((Operator::Unreachable, Some(code)));
     }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter);
+        self.pending_operations
+            .extend(iter.into_iter().map(|op| (op, None)));
     }
 }
 
 impl<'a: 'b, 'b> Extend<&'b Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = &'b Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter.into_iter().cloned());
+        self.pending_operations
+            .extend(iter.into_iter().map(|op| (op.clone(), None)));
     }
 }
 
@@ -123,6 +173,7 @@ impl<'a> MiddlewareBinaryReader<'a> {
                 pending_operations: VecDeque::new(),
             },
             chain: vec![],
+            trap_code: TrapCode::UnreachableCodeReached,
         }
     }
 
@@ -173,22 +224,32 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
                 .map_err(from_binaryreadererror_wasmerror)?;
 
             // Fill the initial raw operator into pending buffer.
-            self.state.pending_operations.push_back(raw_op);
+            self.state.pending_operations.push_back((raw_op, None));
 
             // Run the operator through each stage.
             for stage in &mut self.chain {
                 // Take the outputs from the previous stage.
-                let pending: SmallVec<[Operator<'a>; 2]> =
+                let pending: SmallVec<[(Operator<'a>, Option<TrapCode>); 2]> =
                     self.state.pending_operations.drain(0..).collect();
 
-                // ...and feed them into the current stage.
-                for pending_op in pending {
-                    stage.feed(pending_op, &mut self.state)?;
+                // ...and feed them into the current stage. Traps pushed by an
+                // earlier stage are passed through untouched.
+                for (pending_op, trap_code) in pending {
+                    match trap_code {
+                        Some(code) => self.state.push_trap(code),
+                        None => stage.feed(pending_op, &mut self.state)?,
+                    }
                 }
             }
         }
 
-        Ok(self.state.pending_operations.pop_front().unwrap())
+        let (op, trap_code) = self.state.pending_operations.pop_front().unwrap();
+        self.trap_code = trap_code.unwrap_or(TrapCode::UnreachableCodeReached);
+        Ok(op)
+    }
+
+    fn trap_code(&self) -> TrapCode {
+        self.trap_code
     }
 
     fn current_position(&self) -> usize {
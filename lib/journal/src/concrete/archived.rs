@@ -4,6 +4,7 @@ use rkyv::ser::{Allocator, Writer};
 use rkyv::{
     Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize, api::serialize_using,
 };
+use std::borrow::Cow;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::{Duration, SystemTime};
 
@@ -90,6 +91,10 @@ pub enum JournalEntryRecordType {
     DuplicateFileDescriptorV2 = 62,
     FileDescriptorSetFdFlagsV1 = 63,
     SocketPairV1 = 64,
+    NonDeterministicCallV1 = 65,
+    EncryptedV1 = 66,
+    MemoryChunkV1 = 67,
+    UpdateMemoryRegionChunkedV1 = 68,
 }
 
 impl JournalEntryRecordType {
@@ -311,6 +316,22 @@ impl JournalEntryRecordType {
             JournalEntryRecordType::SnapshotV1 => {
                 ArchivedJournalEntry::SnapshotV1(unsafe { rkyv::access_unchecked(data) })
             }
+            JournalEntryRecordType::NonDeterministicCallV1 => {
+                ArchivedJournalEntry::NonDeterministicCallV1(unsafe {
+                    rkyv::access_unchecked(data)
+                })
+            }
+            JournalEntryRecordType::EncryptedV1 => {
+                ArchivedJournalEntry::EncryptedV1(unsafe { rkyv::access_unchecked(data) })
+            }
+            JournalEntryRecordType::MemoryChunkV1 => {
+                ArchivedJournalEntry::MemoryChunkV1(unsafe { rkyv::access_unchecked(data) })
+            }
+            JournalEntryRecordType::UpdateMemoryRegionChunkedV1 => {
+                ArchivedJournalEntry::UpdateMemoryRegionChunkedV1(unsafe {
+                    rkyv::access_unchecked(data)
+                })
+            }
         }
         .try_into()
     }
@@ -406,6 +427,12 @@ impl JournalEntry<'_> {
             Self::SocketSetOptTimeV1 { .. } => JournalEntryRecordType::SocketSetOptTimeV1,
             Self::SocketShutdownV1 { .. } => JournalEntryRecordType::SocketShutdownV1,
             Self::SnapshotV1 { .. } => JournalEntryRecordType::SnapshotV1,
+            Self::NonDeterministicCallV1 { .. } => JournalEntryRecordType::NonDeterministicCallV1,
+            Self::EncryptedV1 { .. } => JournalEntryRecordType::EncryptedV1,
+            Self::MemoryChunkV1 { .. } => JournalEntryRecordType::MemoryChunkV1,
+            Self::UpdateMemoryRegionChunkedV1 { .. } => {
+                JournalEntryRecordType::UpdateMemoryRegionChunkedV1
+            }
         }
     }
 
@@ -987,6 +1014,57 @@ impl JournalEntry<'_> {
                 },
                 serializer,
             ),
+            JournalEntry::NonDeterministicCallV1 {
+                call,
+                errno,
+                result,
+            } => serialize_using(
+                &JournalEntryNonDeterministicCallV1 {
+                    call: call.into(),
+                    errno: errno as u16,
+                    result: result.into(),
+                },
+                serializer,
+            ),
+            JournalEntry::EncryptedV1 {
+                key_id,
+                sequence,
+                nonce,
+                ciphertext,
+            } => serialize_using(
+                &JournalEntryEncryptedV1 {
+                    key_id: key_id.into(),
+                    sequence,
+                    nonce,
+                    ciphertext: ciphertext.into(),
+                },
+                serializer,
+            ),
+            JournalEntry::MemoryChunkV1 {
+                hash,
+                compressed_data,
+            } => serialize_using(
+                &JournalEntryMemoryChunkV1 {
+                    hash,
+                    compressed_data: compressed_data.into(),
+                },
+                serializer,
+            ),
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                // The hashes of the chunks are archived as one run of bytes
+                let chunks: Cow<'_, [u8]> = match chunks {
+                    Cow::Borrowed(chunks) => Cow::Borrowed(chunks.as_flattened()),
+                    Cow::Owned(chunks) => Cow::Owned(chunks.into_flattened()),
+                };
+                serialize_using(
+                    &JournalEntryUpdateMemoryRegionChunkedV1 {
+                        chunks: chunks.into(),
+                        start: region.start,
+                        end: region.end,
+                    },
+                    serializer,
+                )
+            }
         }
         .map_err(|err| anyhow::format_err!("failed to serialize journal record - {err}"))?;
         Ok(amt)
@@ -1070,6 +1148,10 @@ pub enum ArchivedJournalEntry<'a> {
     SocketSetOptTimeV1(&'a ArchivedJournalEntrySocketSetOptTimeV1),
     SocketShutdownV1(&'a ArchivedJournalEntrySocketShutdownV1),
     SnapshotV1(&'a ArchivedJournalEntrySnapshotV1),
+    NonDeterministicCallV1(&'a ArchivedJournalEntryNonDeterministicCallV1<'a>),
+    EncryptedV1(&'a ArchivedJournalEntryEncryptedV1<'a>),
+    MemoryChunkV1(&'a ArchivedJournalEntryMemoryChunkV1<'a>),
+    UpdateMemoryRegionChunkedV1(&'a ArchivedJournalEntryUpdateMemoryRegionChunkedV1<'a>),
 }
 
 #[repr(C)]
@@ -1670,6 +1752,46 @@ pub struct JournalEntrySnapshotV1 {
     pub trigger: JournalSnapshotTriggerV1,
 }
 
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryNonDeterministicCallV1<'a> {
+    pub call: JournalNonDeterministicCallV1,
+    pub errno: u16,
+    pub result: AlignedCowVec<'a, u8>,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryEncryptedV1<'a> {
+    pub key_id: AlignedCowStr<'a>,
+    pub sequence: u64,
+    pub nonce: [u8; 12],
+    pub ciphertext: AlignedCowVec<'a, u8>,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryMemoryChunkV1<'a> {
+    pub hash: [u8; 32],
+    pub compressed_data: AlignedCowVec<'a, u8>,
+}
+
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(attr(repr(align(8))))]
+pub struct JournalEntryUpdateMemoryRegionChunkedV1<'a> {
+    pub chunks: AlignedCowVec<'a, u8>,
+    pub start: u64,
+    pub end: u64,
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
 #[rkyv(derive(Debug))]
@@ -1965,6 +2087,18 @@ pub enum JournalThreadStartTypeV1 {
     ThreadSpawn { start_ptr: u64 },
 }
 
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[rkyv(derive(Debug), attr(repr(align(8))))]
+pub enum JournalNonDeterministicCallV1 {
+    RandomGet,
+    ClockTimeGet { clock_id: JournalSnapshot0ClockidV1 },
+    FdRead { fd: u32 },
+    SockRecv { fd: u32 },
+    PollOneoff,
+}
+
 #[repr(C)]
 #[repr(align(8))]
 #[derive(Debug, Clone, Copy, RkyvSerialize, RkyvDeserialize, Archive, PartialEq, Eq, Hash)]
@@ -607,6 +607,42 @@ impl From<ThreadStartType> for JournalThreadStartTypeV1 {
     }
 }
 
+impl From<NonDeterministicCall> for JournalNonDeterministicCallV1 {
+    fn from(value: NonDeterministicCall) -> Self {
+        match value {
+            NonDeterministicCall::RandomGet => JournalNonDeterministicCallV1::RandomGet,
+            NonDeterministicCall::ClockTimeGet { clock_id } => {
+                JournalNonDeterministicCallV1::ClockTimeGet {
+                    clock_id: clock_id.into(),
+                }
+            }
+            NonDeterministicCall::FdRead { fd } => JournalNonDeterministicCallV1::FdRead { fd },
+            NonDeterministicCall::SockRecv { fd } => JournalNonDeterministicCallV1::SockRecv { fd },
+            NonDeterministicCall::PollOneoff => JournalNonDeterministicCallV1::PollOneoff,
+        }
+    }
+}
+
+impl From<&'_ ArchivedJournalNonDeterministicCallV1> for NonDeterministicCall {
+    fn from(value: &'_ ArchivedJournalNonDeterministicCallV1) -> Self {
+        match value {
+            ArchivedJournalNonDeterministicCallV1::RandomGet => NonDeterministicCall::RandomGet,
+            ArchivedJournalNonDeterministicCallV1::ClockTimeGet { clock_id } => {
+                NonDeterministicCall::ClockTimeGet {
+                    clock_id: clock_id.into(),
+                }
+            }
+            ArchivedJournalNonDeterministicCallV1::FdRead { fd } => {
+                NonDeterministicCall::FdRead { fd: fd.to_native() }
+            }
+            ArchivedJournalNonDeterministicCallV1::SockRecv { fd } => {
+                NonDeterministicCall::SockRecv { fd: fd.to_native() }
+            }
+            ArchivedJournalNonDeterministicCallV1::PollOneoff => NonDeterministicCall::PollOneoff,
+        }
+    }
+}
+
 impl From<JournalWasiMemoryLayout> for WasiMemoryLayout {
     fn from(value: JournalWasiMemoryLayout) -> Self {
         Self {
@@ -798,6 +834,41 @@ impl<'a> TryFrom<ArchivedJournalEntry<'a>> for JournalEntry<'a> {
                     .unwrap_or(SystemTime::UNIX_EPOCH),
                 trigger: trigger.into(),
             },
+            ArchivedJournalEntry::NonDeterministicCallV1(
+                ArchivedJournalEntryNonDeterministicCallV1 {
+                    call,
+                    errno,
+                    result,
+                },
+            ) => Self::NonDeterministicCallV1 {
+                call: call.into(),
+                errno: wasi::Errno::try_from(errno.to_native()).unwrap_or(wasi::Errno::Io),
+                result: result.as_ref().into(),
+            },
+            ArchivedJournalEntry::EncryptedV1(ArchivedJournalEntryEncryptedV1 {
+                key_id,
+                sequence,
+                nonce,
+                ciphertext,
+            }) => Self::EncryptedV1 {
+                key_id: String::from_utf8_lossy(key_id.as_ref()),
+                sequence: sequence.to_native(),
+                nonce: *nonce,
+                ciphertext: ciphertext.as_ref().into(),
+            },
+            ArchivedJournalEntry::MemoryChunkV1(ArchivedJournalEntryMemoryChunkV1 {
+                hash,
+                compressed_data,
+            }) => Self::MemoryChunkV1 {
+                hash: *hash,
+                compressed_data: compressed_data.as_ref().into(),
+            },
+            ArchivedJournalEntry::UpdateMemoryRegionChunkedV1(
+                ArchivedJournalEntryUpdateMemoryRegionChunkedV1 { chunks, start, end },
+            ) => Self::UpdateMemoryRegionChunkedV1 {
+                region: (start.to_native())..(end.to_native()),
+                chunks: chunk_hashes(chunks.as_ref()),
+            },
             ArchivedJournalEntry::SetClockTimeV1(ArchivedJournalEntrySetClockTimeV1 {
                 clock_id,
                 time,
@@ -1219,3 +1290,12 @@ impl<'a> TryFrom<ArchivedJournalEntry<'a>> for JournalEntry<'a> {
         })
     }
 }
+
+/// The hashes of memory chunks are archived as one run of bytes
+fn chunk_hashes(data: &[u8]) -> Cow<'_, [[u8; 32]]> {
+    // SAFETY: `[u8; 32]` has the same alignment as `u8` and the length is
+    // rounded down to a whole number of hashes
+    Cow::Borrowed(unsafe {
+        std::slice::from_raw_parts(data.as_ptr() as *const [u8; 32], data.len() / 32)
+    })
+}
@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use lz4_flex::{block, decompress};
+
+use super::*;
+
+/// Chunks are never cut smaller than this, except at the end of a region
+const MIN_CHUNK_SIZE: usize = 2 * 1024;
+
+/// Chunks are always cut when they reach this size
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A chunk is cut when these bits of the rolling hash are all zero which
+/// gives chunks of 8KB on average. The high bits are used as they depend
+/// on the last 64 bytes rather than just the last few.
+const CHUNK_MASK: u64 = ((1 << 13) - 1) << 51;
+
+/// Random values that are mixed into the rolling hash for every byte
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64 with a fixed seed so that the boundaries are stable
+    let mut table = [0u64; 256];
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut i = 0;
+    while i < table.len() {
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Splits memory into chunks whose boundaries depend on the content
+/// rather than the offset, so that data which moves around in memory
+/// (or is written at different offsets) still produces the same chunks.
+pub fn memory_chunks(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let max = rest.len().min(MAX_CHUNK_SIZE);
+        let mut len = max;
+        let mut fingerprint = 0u64;
+        for (i, b) in rest[..max].iter().enumerate().skip(MIN_CHUNK_SIZE) {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[*b as usize]);
+            if fingerprint & CHUNK_MASK == 0 {
+                len = i + 1;
+                break;
+            }
+        }
+        let (chunk, remaining) = rest.split_at(len);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+/// Keeps the memory chunks of a journal so that the regions that
+/// reference them can be rebuilt
+#[derive(Debug, Default, Clone)]
+pub struct MemoryChunkStore {
+    chunks: HashMap<[u8; 32], Arc<[u8]>>,
+}
+
+impl MemoryChunkStore {
+    /// Stores a chunk in its compressed form
+    pub fn insert(&mut self, hash: [u8; 32], compressed_data: &[u8]) {
+        self.chunks.insert(hash, compressed_data.into());
+    }
+
+    /// Rebuilds the data of a region from its chunks
+    pub fn assemble(&self, region: &Range<u64>, chunks: &[[u8; 32]]) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity((region.end - region.start) as usize);
+        for hash in chunks {
+            let compressed_data = self.chunks.get(hash).ok_or_else(|| {
+                anyhow::format_err!(
+                    "memory chunk {} is missing from the journal",
+                    hex_hash(hash)
+                )
+            })?;
+            data.extend(zstd::bulk::decompress(compressed_data, MAX_CHUNK_SIZE)?);
+        }
+        if data.len() as u64 != region.end - region.start {
+            return Err(anyhow::format_err!(
+                "memory chunks hold {} bytes but the region {}..{} is {} bytes",
+                data.len(),
+                region.start,
+                region.end,
+                region.end - region.start
+            ));
+        }
+        Ok(data)
+    }
+}
+
+fn hex_hash(hash: &[u8; 32]) -> String {
+    hash[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Keeps track of the chunks that the latest update of every region
+/// references, in the same way as the [`CompactingJournal`] does, so that
+/// a chunk is stored again once compaction could have removed it.
+#[derive(Debug, Default)]
+struct ChunkRefs {
+    regions: HashMap<Range<u64>, Vec<[u8; 32]>>,
+    chunks: HashMap<[u8; 32], usize>,
+}
+
+impl ChunkRefs {
+    fn set_region(&mut self, region: Range<u64>, chunks: Vec<[u8; 32]>) {
+        for hash in chunks.iter() {
+            *self.chunks.entry(*hash).or_default() += 1;
+        }
+        for hash in self.regions.insert(region, chunks).unwrap_or_default() {
+            if let Some(refs) = self.chunks.get_mut(&hash) {
+                *refs -= 1;
+                if *refs == 0 {
+                    self.chunks.remove(&hash);
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, entry: &JournalEntry<'_>) {
+        match entry {
+            JournalEntry::UpdateMemoryRegionV1 { region, .. } => {
+                self.set_region(region.clone(), Vec::new());
+            }
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                self.set_region(region.clone(), chunks.to_vec());
+            }
+            // The compactor forgets the memory of previous runs
+            JournalEntry::InitModuleV1 { .. }
+            | JournalEntry::ProcessExitV1 { .. }
+            | JournalEntry::ClearEtherealV1 => {
+                self.regions.clear();
+                self.chunks.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Stores the memory regions written to the journal as chunks that are
+/// compressed with zstd and only written once, with the regions
+/// referencing the chunks by their hash. Snapshots then only cost as much
+/// as the data that actually changed, even when it moved around.
+///
+/// Reading the journal returns the records as they were stored.
+#[derive(Debug)]
+pub struct ChunkedMemoryJournal {
+    tx: ChunkedMemoryJournalTx,
+    rx: Box<DynReadableJournal>,
+}
+
+#[derive(Debug)]
+pub struct ChunkedMemoryJournalTx {
+    inner: Box<DynWritableJournal>,
+    level: i32,
+    refs: Mutex<ChunkRefs>,
+}
+
+impl ChunkedMemoryJournal {
+    pub fn new<J>(inner: J) -> anyhow::Result<Self>
+    where
+        J: Journal,
+    {
+        // Learn which chunks are already in the journal
+        let mut refs = ChunkRefs::default();
+        let existing = inner.as_restarted()?;
+        while let Some(record) = existing.read()? {
+            refs.apply(&record.record);
+        }
+        drop(existing);
+
+        let (tx, rx) = inner.split();
+        Ok(Self {
+            tx: ChunkedMemoryJournalTx {
+                inner: tx,
+                level: zstd::DEFAULT_COMPRESSION_LEVEL,
+                refs: Mutex::new(refs),
+            },
+            rx,
+        })
+    }
+
+    /// Sets the zstd compression level of the chunks (1 to 22)
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.tx.level = level;
+        self
+    }
+
+    pub fn into_split(self) -> (ChunkedMemoryJournalTx, Box<DynReadableJournal>) {
+        (self.tx, self.rx)
+    }
+}
+
+impl WritableJournal for ChunkedMemoryJournalTx {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        let mut refs = self.refs.lock().unwrap();
+        let JournalEntry::UpdateMemoryRegionV1 {
+            region,
+            compressed_data,
+        } = entry
+        else {
+            refs.apply(&entry);
+            return self.inner.write(entry);
+        };
+
+        let (uncompressed_size, compressed_data) = block::uncompressed_size(&compressed_data)?;
+        let data = decompress(compressed_data, uncompressed_size)?;
+
+        let mut record_start = None;
+        let mut hashes = Vec::new();
+        for chunk in memory_chunks(&data) {
+            let hash: [u8; 32] = blake3::hash(chunk).into();
+            if !refs.chunks.contains_key(&hash) && !hashes.contains(&hash) {
+                let res = self.inner.write(JournalEntry::MemoryChunkV1 {
+                    hash,
+                    compressed_data: zstd::bulk::compress(chunk, self.level)?.into(),
+                })?;
+                record_start.get_or_insert(res.record_start);
+            }
+            hashes.push(hash);
+        }
+
+        let res = self
+            .inner
+            .write(JournalEntry::UpdateMemoryRegionChunkedV1 {
+                region: region.clone(),
+                chunks: hashes.as_slice().into(),
+            })?;
+        refs.set_region(region, hashes);
+        Ok(LogWriteResult {
+            record_start: record_start.unwrap_or(res.record_start),
+            record_end: res.record_end,
+        })
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.inner.rollback()
+    }
+}
+
+impl WritableJournal for ChunkedMemoryJournal {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        self.tx.write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.tx.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.tx.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.tx.rollback()
+    }
+}
+
+impl ReadableJournal for ChunkedMemoryJournal {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.rx.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.rx.as_restarted()
+    }
+}
+
+impl Journal for ChunkedMemoryJournal {
+    fn split(self) -> (Box<DynWritableJournal>, Box<DynReadableJournal>) {
+        (Box::new(self.tx), self.rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory that does not compress or repeat itself
+    fn random_data(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn update(offset: u64, data: &[u8]) -> JournalEntry<'static> {
+        JournalEntry::UpdateMemoryRegionV1 {
+            region: offset..offset + data.len() as u64,
+            compressed_data: lz4_flex::compress_prepend_size(data).into(),
+        }
+    }
+
+    fn read_all(journal: &impl ReadableJournal) -> Vec<JournalEntry<'static>> {
+        let mut ret = Vec::new();
+        while let Some(record) = journal.read().unwrap() {
+            ret.push(record.into_inner().into_owned());
+        }
+        ret
+    }
+
+    fn count_chunks(entries: &[JournalEntry<'_>]) -> usize {
+        entries
+            .iter()
+            .filter(|e| matches!(e, JournalEntry::MemoryChunkV1 { .. }))
+            .count()
+    }
+
+    #[test]
+    pub fn test_chunk_boundaries_follow_content() {
+        let data = random_data(1, 512 * 1024);
+        let chunks = memory_chunks(&data).collect::<Vec<_>>();
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_CHUNK_SIZE));
+        assert!(
+            chunks[..chunks.len() - 1]
+                .iter()
+                .all(|c| c.len() >= MIN_CHUNK_SIZE)
+        );
+
+        // Inserting bytes at the start only changes the first chunk
+        let mut shifted = vec![0xAA; 100];
+        shifted.extend_from_slice(&data);
+        let shifted_chunks = memory_chunks(&shifted).collect::<Vec<_>>();
+        assert_eq!(chunks[1..], shifted_chunks[1..]);
+    }
+
+    #[test]
+    pub fn test_chunked_memory_restores_the_same_state() {
+        let first = random_data(2, 100_000);
+        let second = random_data(3, 30_000);
+        let entries = vec![
+            update(0, &first),
+            update(200_000, &second),
+            update(0, &second),
+        ];
+
+        let journal = ChunkedMemoryJournal::new(BufferedJournal::default()).unwrap();
+        let plain = JournalState::new();
+        for entry in entries {
+            journal.write(entry.clone()).unwrap();
+            plain.write(entry).unwrap();
+        }
+
+        let records = read_all(&journal);
+        assert!(
+            !records
+                .iter()
+                .any(|e| matches!(e, JournalEntry::UpdateMemoryRegionV1 { .. }))
+        );
+        let chunked = JournalState::new();
+        for record in records {
+            chunked.write(record).unwrap();
+        }
+        assert_eq!(plain.diff(&chunked), Vec::new());
+    }
+
+    #[test]
+    pub fn test_chunked_memory_deduplicates() {
+        let data = random_data(4, 256 * 1024);
+        let journal = ChunkedMemoryJournal::new(BufferedJournal::default()).unwrap();
+        journal.write(update(0, &data)).unwrap();
+        let first = count_chunks(&read_all(&journal.as_restarted().unwrap()));
+
+        // The same memory at another offset with a few bytes changed
+        let mut moved = data.clone();
+        moved[1000] ^= 0xFF;
+        journal.write(update(1024 * 1024, &moved)).unwrap();
+        let records = read_all(&journal.as_restarted().unwrap());
+        assert!(count_chunks(&records) - first <= 2);
+
+        // Journals that are opened again remember their chunks
+        let buffer = BufferedJournal::default();
+        for record in records {
+            buffer.write(record).unwrap();
+        }
+        let journal = ChunkedMemoryJournal::new(buffer).unwrap();
+        journal.write(update(2048 * 1024, &data)).unwrap();
+        let records = read_all(&journal);
+        assert_eq!(
+            count_chunks(&records),
+            count_chunks(&records[..records.len() - 1])
+        );
+    }
+
+    #[test]
+    pub fn test_compaction_removes_unreferenced_chunks() {
+        let old = random_data(5, 64 * 1024);
+        let new = random_data(6, 64 * 1024);
+        let kept = random_data(7, 16 * 1024);
+
+        let journal = ChunkedMemoryJournal::new(BufferedJournal::default()).unwrap();
+        journal.write(update(0, &old)).unwrap();
+        journal.write(update(100_000, &kept)).unwrap();
+        journal.write(update(0, &new)).unwrap();
+        let records = read_all(&journal);
+
+        let mut compacting = CompactingJournal::new(BufferedJournal::default()).unwrap();
+        for record in records {
+            compacting.write(record).unwrap();
+        }
+        compacting.compact_to(BufferedJournal::default()).unwrap();
+        let compacted = read_all(&compacting.as_restarted().unwrap());
+
+        let mut store = MemoryChunkStore::default();
+        let mut referenced = Vec::new();
+        for record in compacted.iter() {
+            match record {
+                JournalEntry::MemoryChunkV1 {
+                    hash,
+                    compressed_data,
+                } => store.insert(*hash, compressed_data),
+                JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                    referenced.extend_from_slice(chunks);
+                    let data = store.assemble(region, chunks).unwrap();
+                    assert!(data == new || data == kept);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(count_chunks(&compacted), store.chunks.len());
+        assert!(store.chunks.keys().all(|hash| referenced.contains(hash)));
+        for chunk in memory_chunks(&old) {
+            let hash: [u8; 32] = blake3::hash(chunk).into();
+            assert!(!store.chunks.contains_key(&hash));
+        }
+    }
+}
@@ -73,6 +73,11 @@ struct State {
     descriptor_seed: u64,
     // We maintain a memory map of the events that are significant
     memory_map: HashMap<MemoryRange, usize>,
+    // Chunks referenced by the chunked memory events in the memory map
+    memory_map_chunks: HashMap<usize, Vec<[u8; 32]>>,
+    // Last event that stored each memory chunk, chunks are only retained
+    // while a memory event in the memory map references them
+    memory_chunks: HashMap<[u8; 32], usize>,
     // List of all the snapshots
     snapshots: Vec<usize>,
     // Last tty event thats been set
@@ -170,6 +175,16 @@ impl State {
         {
             filter.add_event_to_whitelist(event_index);
         }
+        for hash in self
+            .memory_map
+            .values()
+            .filter_map(|e| self.memory_map_chunks.get(e))
+            .flatten()
+        {
+            if let Some(event_index) = self.memory_chunks.get(hash) {
+                filter.add_event_to_whitelist(*event_index);
+            }
+        }
         for d in self
             .create_directory
             .values()
@@ -301,6 +316,7 @@ impl State {
         self.accepted_sockets.clear();
         self.event_descriptors.clear();
         self.memory_map.clear();
+        self.memory_map_chunks.clear();
         self.open_pipes.clear();
         self.open_sockets.clear();
         self.snapshots.clear();
@@ -357,6 +373,8 @@ impl CompactingJournal {
             init_module: None,
             snapshots: Default::default(),
             memory_map: Default::default(),
+            memory_map_chunks: Default::default(),
+            memory_chunks: Default::default(),
             thread_map: Default::default(),
             staged_thread_map: Default::default(),
             open_sockets: Default::default(),
@@ -536,7 +554,18 @@ impl WritableJournal for CompactingJournalTx {
 
         match &entry {
             JournalEntry::UpdateMemoryRegionV1 { region, .. } => {
-                state.memory_map.insert(region.clone().into(), event_index);
+                if let Some(old) = state.memory_map.insert(region.clone().into(), event_index) {
+                    state.memory_map_chunks.remove(&old);
+                }
+            }
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                if let Some(old) = state.memory_map.insert(region.clone().into(), event_index) {
+                    state.memory_map_chunks.remove(&old);
+                }
+                state.memory_map_chunks.insert(event_index, chunks.to_vec());
+            }
+            JournalEntry::MemoryChunkV1 { hash, .. } => {
+                state.memory_chunks.insert(*hash, event_index);
             }
             JournalEntry::SetThreadV1 { id, .. } => {
                 state.staged_thread_map.insert(*id, event_index);
@@ -796,7 +825,13 @@ impl WritableJournal for CompactingJournalTx {
             JournalEntry::ClearEtherealV1 => {
                 state.clear_run_sub_events();
             }
-            JournalEntry::SetClockTimeV1 { .. }
+            // The results of non-deterministic calls are only needed to replay
+            // a run from its very start, which compaction rules out anyway
+            JournalEntry::NonDeterministicCallV1 { .. } => {}
+            // Encrypted entries can not be compacted as their contents are
+            // unknown, compaction should instead happen before the encryption
+            JournalEntry::EncryptedV1 { .. }
+            | JournalEntry::SetClockTimeV1 { .. }
             | JournalEntry::PortAddAddrV1 { .. }
             | JournalEntry::PortDelAddrV1 { .. }
             | JournalEntry::PortAddrClearV1
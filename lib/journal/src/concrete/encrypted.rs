@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use ring::{
+    aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use rkyv::{
+    rancor::Strategy,
+    ser::{Serializer, Writer, allocator::Arena, sharing::Share},
+    util::AlignedVec,
+};
+
+use super::*;
+
+/// Size of the header that precedes the archived entry in the plaintext of
+/// an encrypted record, which keeps the entry aligned.
+const PLAINTEXT_HEADER_LEN: usize = 8;
+
+/// A key used by the [`EncryptedJournal`] to encrypt and authenticate
+/// the records of a journal.
+///
+/// The identifier of the key is stored in the clear next to every record
+/// so that the right key can be picked when the journal is read, which
+/// allows keys to be rotated.
+#[derive(Clone)]
+pub struct JournalKey {
+    id: Arc<str>,
+    key: LessSafeKey,
+}
+
+impl std::fmt::Debug for JournalKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JournalKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JournalKey {
+    /// Length of the keys in bytes
+    pub const LEN: usize = 32;
+
+    pub fn new(id: impl Into<String>, key: &[u8]) -> anyhow::Result<Self> {
+        let id: String = id.into();
+        if id.is_empty() {
+            anyhow::bail!("the identifier of a journal key can not be empty");
+        }
+        if key.len() != Self::LEN {
+            anyhow::bail!(
+                "journal keys must be {} bytes long (key_id={id}, len={})",
+                Self::LEN,
+                key.len()
+            );
+        }
+        let key = UnboundKey::new(&CHACHA20_POLY1305, key)
+            .map_err(|_| anyhow::format_err!("invalid journal key (key_id={id})"))?;
+        Ok(Self {
+            id: id.into(),
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// The additional data that is authenticated along with a record, which
+/// prevents records from being moved around or attributed to another key
+fn record_aad(key_id: &str, sequence: u64) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(8 + key_id.len());
+    aad.extend_from_slice(&sequence.to_be_bytes());
+    aad.extend_from_slice(key_id.as_bytes());
+    aad
+}
+
+/// Encrypts every record written to the inner journal with an AEAD cipher
+/// (ChaCha20-Poly1305) and decrypts them again when they are read.
+///
+/// Each record is numbered, and the number is authenticated along with the
+/// record, which means that any record which has been tampered with, cut
+/// short, removed or reordered is detected when the journal is read. Only
+/// records which are dropped from the very end of the journal can not be
+/// detected, as the journal then looks like it was never written past that
+/// point.
+///
+/// The encrypted journal should be placed below any [`CompactingJournal`]
+/// or [`FilteredJournal`] as they can not make sense of encrypted records.
+#[derive(Debug)]
+pub struct EncryptedJournal {
+    tx: EncryptedJournalTx,
+    rx: EncryptedJournalRx,
+}
+
+#[derive(Debug)]
+pub struct EncryptedJournalTx {
+    inner: Box<DynWritableJournal>,
+    key: JournalKey,
+    rng: SystemRandom,
+    next_sequence: Mutex<u64>,
+}
+
+#[derive(Debug)]
+pub struct EncryptedJournalRx {
+    inner: Box<DynReadableJournal>,
+    keys: HashMap<Arc<str>, JournalKey>,
+    next_sequence: Mutex<u64>,
+}
+
+impl EncryptedJournal {
+    /// Creates a journal that encrypts its records with `key`, which is
+    /// also used to decrypt them.
+    ///
+    /// New records are appended after the records that already exist in
+    /// the inner journal.
+    pub fn new<J>(inner: J, key: JournalKey) -> anyhow::Result<Self>
+    where
+        J: Journal,
+    {
+        // Find the number of the next record
+        let mut next_sequence = 0;
+        let existing = inner.as_restarted()?;
+        while let Some(record) = existing.read()? {
+            if let JournalEntry::EncryptedV1 { sequence, .. } = record.record {
+                next_sequence = sequence + 1;
+            }
+        }
+        drop(existing);
+
+        let (tx, rx) = inner.split();
+        let mut keys = HashMap::new();
+        keys.insert(key.id.clone(), key.clone());
+        Ok(Self {
+            tx: EncryptedJournalTx {
+                inner: tx,
+                key,
+                rng: SystemRandom::new(),
+                next_sequence: Mutex::new(next_sequence),
+            },
+            rx: EncryptedJournalRx {
+                inner: rx,
+                keys,
+                next_sequence: Mutex::new(0),
+            },
+        })
+    }
+
+    /// Adds a key which is only used to decrypt records, for instance
+    /// the records written before the key was rotated.
+    pub fn with_decryption_key(mut self, key: JournalKey) -> Self {
+        self.rx.keys.insert(key.id.clone(), key);
+        self
+    }
+
+    pub fn into_split(self) -> (EncryptedJournalTx, EncryptedJournalRx) {
+        (self.tx, self.rx)
+    }
+}
+
+impl WritableJournal for EncryptedJournalTx {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        // Archive the entry in the same way as the log file would, preceded
+        // by its record type
+        let record_type = entry.archive_record_type();
+        let mut arena = Arena::new();
+        let mut serializer =
+            Serializer::new(AlignedVec::<16>::new(), arena.acquire(), Share::new());
+        {
+            let serializer = Strategy::<_, rkyv::rancor::Error>::wrap(&mut serializer);
+            let mut header = [0u8; PLAINTEXT_HEADER_LEN];
+            header[0..2].copy_from_slice(&(record_type as u16).to_be_bytes());
+            serializer.write(&header)?;
+            entry.serialize_archive(serializer)?;
+        }
+        let mut data = serializer.into_writer().to_vec();
+
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow::format_err!("failed to generate a nonce for a journal record"))?;
+
+        // The lock is held until the record is written so that the records
+        // end up in the same order as their sequence numbers
+        let mut next_sequence = self.next_sequence.lock().unwrap();
+        let sequence = *next_sequence;
+        self.key
+            .key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(record_aad(&self.key.id, sequence)),
+                &mut data,
+            )
+            .map_err(|_| anyhow::format_err!("failed to encrypt journal record"))?;
+
+        let res = self.inner.write(JournalEntry::EncryptedV1 {
+            key_id: self.key.id().into(),
+            sequence,
+            nonce,
+            ciphertext: data.into(),
+        })?;
+        *next_sequence += 1;
+        Ok(res)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.inner.rollback()
+    }
+}
+
+impl ReadableJournal for EncryptedJournalRx {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        let mut next_sequence = self.next_sequence.lock().unwrap();
+        let Some(res) = self.inner.read()? else {
+            return Ok(None);
+        };
+
+        let (key_id, sequence, nonce, ciphertext) = match res.record {
+            JournalEntry::EncryptedV1 {
+                key_id,
+                sequence,
+                nonce,
+                ciphertext,
+            } => (key_id, sequence, nonce, ciphertext),
+            entry => {
+                return Err(anyhow::format_err!(
+                    "journal record at {} is not encrypted ({})",
+                    res.record_start,
+                    entry.archive_record_type() as u16
+                ));
+            }
+        };
+        let key = self.keys.get(key_id.as_ref()).ok_or_else(|| {
+            anyhow::format_err!(
+                "journal record at {} is encrypted with an unknown key (key_id={key_id})",
+                res.record_start
+            )
+        })?;
+
+        // The plaintext is decrypted into an aligned buffer as the archived
+        // entry is accessed in place
+        let mut data = AlignedVec::<16>::with_capacity(ciphertext.len());
+        data.extend_from_slice(&ciphertext);
+        let plaintext = key
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(record_aad(&key_id, sequence)),
+                &mut data,
+            )
+            .map_err(|_| {
+                anyhow::format_err!(
+                    "journal record at {} failed authentication, it has been tampered with or truncated",
+                    res.record_start
+                )
+            })?;
+
+        // Only now that the sequence number is authenticated can it be trusted
+        if sequence != *next_sequence {
+            return Err(anyhow::format_err!(
+                "journal record at {} is out of order (sequence={sequence}, expected={}), records have been removed or reordered",
+                res.record_start,
+                *next_sequence
+            ));
+        }
+
+        if plaintext.len() < PLAINTEXT_HEADER_LEN {
+            return Err(anyhow::format_err!(
+                "journal record at {} is too short",
+                res.record_start
+            ));
+        }
+        let record_type = u16::from_be_bytes([plaintext[0], plaintext[1]]);
+        let record_type: JournalEntryRecordType = record_type.try_into().map_err(|_| {
+            anyhow::format_err!(
+                "journal record at {} has an unknown type ({record_type})",
+                res.record_start
+            )
+        })?;
+
+        // SAFETY: the record was authenticated, which means it was archived
+        // by an `EncryptedJournalTx` holding the same key
+        let record =
+            unsafe { record_type.deserialize_archive(&plaintext[PLAINTEXT_HEADER_LEN..])? }
+                .into_owned();
+        *next_sequence += 1;
+
+        Ok(Some(LogReadResult {
+            record_start: res.record_start,
+            record_end: res.record_end,
+            record,
+        }))
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        Ok(Box::new(EncryptedJournalRx {
+            inner: self.inner.as_restarted()?,
+            keys: self.keys.clone(),
+            next_sequence: Mutex::new(0),
+        }))
+    }
+}
+
+impl WritableJournal for EncryptedJournal {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        self.tx.write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.tx.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.tx.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.tx.rollback()
+    }
+}
+
+impl ReadableJournal for EncryptedJournal {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.rx.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.rx.as_restarted()
+    }
+}
+
+impl Journal for EncryptedJournal {
+    fn split(self) -> (Box<DynWritableJournal>, Box<DynReadableJournal>) {
+        (Box::new(self.tx), Box::new(self.rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> JournalKey {
+        JournalKey::new(id, &[byte; JournalKey::LEN]).unwrap()
+    }
+
+    fn entries() -> Vec<JournalEntry<'static>> {
+        vec![
+            JournalEntry::CreatePipeV1 {
+                read_fd: 1,
+                write_fd: 2,
+            },
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..16,
+                compressed_data: vec![0xAB; 16].into(),
+            },
+            JournalEntry::PortAddrClearV1,
+        ]
+    }
+
+    fn read_all(journal: &impl ReadableJournal) -> anyhow::Result<Vec<JournalEntry<'static>>> {
+        let mut ret = Vec::new();
+        while let Some(record) = journal.read()? {
+            ret.push(record.into_inner().into_owned());
+        }
+        Ok(ret)
+    }
+
+    /// Writes the entries to an encrypted journal and returns the
+    /// encrypted records
+    fn encrypted_records() -> Vec<JournalEntry<'static>> {
+        let buffer = BufferedJournal::default();
+        let journal = EncryptedJournal::new(buffer, key("a", 1)).unwrap();
+        for entry in entries() {
+            journal.write(entry).unwrap();
+        }
+        let (_, rx) = journal.into_split();
+        read_all(&rx.inner).unwrap()
+    }
+
+    fn decrypt(records: Vec<JournalEntry<'static>>) -> anyhow::Result<Vec<JournalEntry<'static>>> {
+        let buffer = BufferedJournal::default();
+        for record in records {
+            buffer.write(record).unwrap();
+        }
+        let journal = EncryptedJournal::new(buffer, key("b", 2))?.with_decryption_key(key("a", 1));
+        read_all(&journal)
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let records = encrypted_records();
+        assert!(
+            records
+                .iter()
+                .all(|r| matches!(r, JournalEntry::EncryptedV1 { .. }))
+        );
+        assert_eq!(decrypt(records).unwrap(), entries());
+    }
+
+    #[test]
+    fn test_encrypted_detects_tampering() {
+        let mut records = encrypted_records();
+        if let JournalEntry::EncryptedV1 { ciphertext, .. } = &mut records[1] {
+            ciphertext.to_mut()[3] ^= 1;
+        }
+        assert!(decrypt(records).is_err());
+
+        let mut records = encrypted_records();
+        if let JournalEntry::EncryptedV1 { ciphertext, .. } = &mut records[1] {
+            ciphertext.to_mut().pop();
+        }
+        assert!(decrypt(records).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_detects_missing_records() {
+        let mut records = encrypted_records();
+        records.remove(1);
+        assert!(decrypt(records).is_err());
+
+        let mut records = encrypted_records();
+        records.swap(0, 1);
+        assert!(decrypt(records).is_err());
+
+        let mut records = encrypted_records();
+        records.insert(
+            1,
+            JournalEntry::CreatePipeV1 {
+                read_fd: 3,
+                write_fd: 4,
+            },
+        );
+        assert!(decrypt(records).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_requires_key() {
+        let buffer = BufferedJournal::default();
+        for record in encrypted_records() {
+            buffer.write(record).unwrap();
+        }
+        let journal = EncryptedJournal::new(buffer, key("b", 2)).unwrap();
+        assert!(journal.read().is_err());
+    }
+}
@@ -241,7 +241,8 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
             | JournalEntry::ProcessExitV1 { .. }
             | JournalEntry::EpollCreateV1 { .. }
             | JournalEntry::EpollCtlV1 { .. }
-            | JournalEntry::TtySetV1 { .. } => {
+            | JournalEntry::TtySetV1 { .. }
+            | JournalEntry::NonDeterministicCallV1 { .. } => {
                 if self.config.filter_core {
                     return Ok(LogWriteResult {
                         record_start: 0,
@@ -250,7 +251,8 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
                 }
                 entry
             }
-            JournalEntry::ClearEtherealV1 => entry,
+            // Encrypted entries can not be told apart
+            JournalEntry::ClearEtherealV1 | JournalEntry::EncryptedV1 { .. } => entry,
             JournalEntry::SetThreadV1 { .. } | JournalEntry::CloseThreadV1 { .. } => {
                 if self.config.filter_threads {
                     return Ok(LogWriteResult {
@@ -260,7 +262,9 @@ impl<W: WritableJournal> WritableJournal for FilteredJournalTx<W> {
                 }
                 entry
             }
-            JournalEntry::UpdateMemoryRegionV1 { .. } => {
+            JournalEntry::UpdateMemoryRegionV1 { .. }
+            | JournalEntry::MemoryChunkV1 { .. }
+            | JournalEntry::UpdateMemoryRegionChunkedV1 { .. } => {
                 if self.config.filter_memory {
                     return Ok(LogWriteResult {
                         record_start: 0,
@@ -332,6 +332,15 @@ impl ReadableJournal for LogFileJournalRx {
             };
             let record_start = *buffer_pos as u64;
 
+            // A record that was cut short can not be read
+            if buffer_ptr.len() < header.record_size as usize {
+                return Err(anyhow::format_err!(
+                    "journal record at {record_start} is truncated ({} of {} bytes)",
+                    buffer_ptr.len(),
+                    header.record_size
+                ));
+            }
+
             // Move the buffer position forward past the record
             let entry = &buffer_ptr[..(header.record_size as usize)];
             buffer_ptr.advance(header.record_size as usize);
@@ -6,11 +6,15 @@ mod archived_from;
 mod auto_consistent;
 mod boxed;
 mod buffered;
+#[cfg(feature = "chunked-memory")]
+mod chunked;
 mod compacting;
 #[cfg(feature = "log-file")]
 mod compacting_log_file;
 mod compacting_transaction;
 mod counting;
+#[cfg(feature = "encryption")]
+mod encrypted;
 mod filter;
 #[cfg(feature = "log-file")]
 mod log_file;
@@ -19,6 +23,8 @@ mod null;
 mod pipe;
 mod printing;
 mod recombined;
+mod remote;
+mod state;
 #[cfg(test)]
 mod tests;
 mod transaction;
@@ -31,11 +37,15 @@ pub use aligned_cow_vec::*;
 pub use archived::*;
 pub use auto_consistent::*;
 pub use buffered::*;
+#[cfg(feature = "chunked-memory")]
+pub use chunked::*;
 pub use compacting::*;
 #[cfg(feature = "log-file")]
 pub use compacting_log_file::*;
 pub use compacting_transaction::*;
 pub use counting::*;
+#[cfg(feature = "encryption")]
+pub use encrypted::*;
 pub use filter::*;
 #[cfg(feature = "log-file")]
 pub use log_file::*;
@@ -44,5 +54,7 @@ pub use null::*;
 pub use pipe::*;
 pub use printing::*;
 pub use recombined::*;
+pub use remote::*;
+pub use state::*;
 pub use transaction::*;
 pub use unsupported::*;
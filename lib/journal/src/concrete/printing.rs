@@ -354,6 +354,50 @@ impl fmt::Display for JournalEntry<'_> {
             JournalEntry::SnapshotV1 { when, trigger } => {
                 write!(f, "snapshot (when={when:?}, trigger={trigger:?})")
             }
+            JournalEntry::NonDeterministicCallV1 {
+                call,
+                errno,
+                result,
+            } => write!(
+                f,
+                "non-deterministic-call (call={:?}, errno={}, result.len={})",
+                call,
+                errno,
+                result.len()
+            ),
+            JournalEntry::EncryptedV1 {
+                key_id,
+                sequence,
+                ciphertext,
+                ..
+            } => write!(
+                f,
+                "encrypted (key_id={}, sequence={}, ciphertext.len={})",
+                key_id,
+                sequence,
+                ciphertext.len()
+            ),
+            JournalEntry::MemoryChunkV1 {
+                hash,
+                compressed_data,
+            } => write!(
+                f,
+                "memory-chunk (hash={}, compressed.len={})",
+                hex_hash(hash),
+                compressed_data.len()
+            ),
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => write!(
+                f,
+                "memory-update-chunked (start={}, end={}, chunks={})",
+                region.start,
+                region.end,
+                chunks.len()
+            ),
         }
     }
 }
+
+/// Short form of a hash that is enough to tell memory chunks apart
+fn hex_hash(hash: &[u8; 32]) -> String {
+    hash[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,1146 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Version of the replication protocol, which is exchanged when the
+/// connection is established
+const PROTOCOL_VERSION: u32 = 2;
+
+/// Largest frame that will be accepted from the peer
+const MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+/// Time to wait between attempts to connect to the receiver
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time to wait for the peer to accept the connection and say hello
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after which the sender pings the receiver when it has no records
+/// to send, so that both ends can tell a quiet connection from a dead one
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time after which a connection the peer sent nothing on is dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Frames sent by the [`RemoteJournal`] to the [`RemoteJournalReceiver`]
+#[derive(Debug, Serialize, Deserialize)]
+enum RemoteJournalRequest {
+    /// Sent as soon as the connection is established, the receiver only
+    /// accepts records from senders that know its token
+    Hello { version: u32, token: String },
+    /// A record of the journal along with its index in the journal
+    Record {
+        offset: u64,
+        entry: JournalEntry<'static>,
+    },
+    /// Sent when there were no records to send for a while, the receiver
+    /// answers with an acknowledgement
+    Ping,
+    /// The journal was closed and no more records will be sent
+    Close,
+}
+
+/// Frames sent by the [`RemoteJournalReceiver`] back to the [`RemoteJournal`]
+#[derive(Debug, Serialize, Deserialize)]
+enum RemoteJournalResponse {
+    /// Sent once the sender is authenticated, the records are (re)sent
+    /// starting at `next_offset`
+    Hello { version: u32, next_offset: u64 },
+    /// All the records before `next_offset` were written to the journal
+    /// of the receiver
+    Ack { next_offset: u64 },
+}
+
+/// Writes a length delimited frame in the same way as the remote
+/// networking client and server do
+fn write_frame<T: Serialize>(stream: &mut TcpStream, frame: &T) -> anyhow::Result<()> {
+    let data = bincode::serialize(frame)?;
+    if data.len() > MAX_FRAME_LEN {
+        return Err(anyhow::format_err!(
+            "journal frame is too large ({} bytes)",
+            data.len()
+        ));
+    }
+    stream.write_all(&(data.len() as u32).to_be_bytes())?;
+    stream.write_all(&data)?;
+    Ok(())
+}
+
+fn read_frame<T: for<'de> Deserialize<'de>>(stream: &mut TcpStream) -> anyhow::Result<T> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow::format_err!(
+            "journal frame is too large ({len} bytes)"
+        ));
+    }
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    Ok(bincode::deserialize(&data)?)
+}
+
+/// Compares two tokens in a time that doesn't depend on where they differ
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn check_token(token: &str) -> anyhow::Result<()> {
+    if token.is_empty() {
+        return Err(anyhow::format_err!("the journal token must not be empty"));
+    }
+    Ok(())
+}
+
+/// Counts the records in a journal so that new records get the right offset
+fn count_records(journal: &impl ReadableJournal) -> anyhow::Result<u64> {
+    let existing = journal.as_restarted()?;
+    let mut count = 0;
+    while existing.read()?.is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The remote journal writes records to an inner journal and streams them
+/// to a [`RemoteJournalReceiver`] over TCP so that a standby can be kept
+/// in sync with the process writing the journal.
+///
+/// Records are identified by their index in the inner journal. When the
+/// connection drops it is re-established in the background and the
+/// receiver is sent whatever records it missed, which are read back from
+/// the inner journal.
+///
+/// The sender authenticates with a token shared with the receiver. The
+/// records are not encrypted, so the connection must only go through a
+/// trusted network.
+#[derive(Debug)]
+pub struct RemoteJournal {
+    tx: RemoteJournalTx,
+    rx: Box<DynReadableJournal>,
+}
+
+#[derive(Debug, Default)]
+struct SenderState {
+    /// Number of records written to the inner journal
+    written: u64,
+    /// Number of records the receiver acknowledged
+    acknowledged: u64,
+    /// Records that are waiting to be sent while the receiver is connected
+    pending: VecDeque<(u64, JournalEntry<'static>)>,
+    /// Set while connected, new records are only queued when this is set
+    connected: bool,
+    /// Set when the journal is dropped
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct SenderShared {
+    addr: SocketAddr,
+    token: String,
+    inner: Box<DynWritableJournal>,
+    state: Mutex<SenderState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+pub struct RemoteJournalTx {
+    shared: Arc<SenderShared>,
+    ack_timeout: Option<Duration>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RemoteJournal {
+    /// Creates a journal that writes its records to `inner` and replicates
+    /// them to the receiver listening on `addr`, which must have been
+    /// created with the same `token`.
+    ///
+    /// Records that already exist in the inner journal are also sent to the
+    /// receiver if it does not have them yet.
+    pub fn new<J>(inner: J, addr: SocketAddr, token: impl Into<String>) -> anyhow::Result<Self>
+    where
+        J: Journal + Send + Sync + 'static,
+    {
+        let token = token.into();
+        check_token(&token)?;
+        let written = count_records(&inner)?;
+        let (tx, rx) = inner.split();
+        let history = rx.as_restarted()?;
+
+        let shared = Arc::new(SenderShared {
+            addr,
+            token,
+            inner: tx,
+            state: Mutex::new(SenderState {
+                written,
+                ..Default::default()
+            }),
+            condvar: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("journal-replication".to_string())
+                .spawn(move || shared.run(history))?
+        };
+
+        Ok(Self {
+            tx: RemoteJournalTx {
+                shared,
+                ack_timeout: None,
+                thread: Some(thread),
+            },
+            rx,
+        })
+    }
+
+    /// Makes `flush` wait until the receiver has acknowledged all the
+    /// records that were written, failing if it does not do so in time
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.tx.ack_timeout.replace(timeout);
+        self
+    }
+
+    /// Returns the number of records the receiver has acknowledged
+    pub fn acknowledged(&self) -> u64 {
+        self.tx.acknowledged()
+    }
+
+    pub fn into_split(self) -> (RemoteJournalTx, Box<DynReadableJournal>) {
+        (self.tx, self.rx)
+    }
+}
+
+impl RemoteJournalTx {
+    /// Returns the number of records the receiver has acknowledged
+    pub fn acknowledged(&self) -> u64 {
+        self.shared.state.lock().unwrap().acknowledged
+    }
+
+    /// Waits for the receiver to acknowledge all the records that were
+    /// written, returning the acknowledged and written counts on timeout.
+    /// Unless `reconnect` is set it stops waiting when the receiver is not
+    /// connected.
+    fn wait_for_acks(&self, timeout: Duration, reconnect: bool) -> Result<(), (u64, u64)> {
+        let state = self.shared.state.lock().unwrap();
+        let (state, _) = self
+            .shared
+            .condvar
+            .wait_timeout_while(state, timeout, |state| {
+                (reconnect || state.connected) && state.acknowledged < state.written
+            })
+            .unwrap();
+        if state.acknowledged < state.written {
+            return Err((state.acknowledged, state.written));
+        }
+        Ok(())
+    }
+}
+
+impl SenderShared {
+    /// Keeps (re)connecting to the receiver and streaming the records
+    /// to it until the journal is closed
+    fn run(self: Arc<Self>, history: Box<DynReadableJournal>) {
+        loop {
+            if self.state.lock().unwrap().closed {
+                return;
+            }
+            match self.replicate(history.as_ref()) {
+                Ok(()) => return,
+                Err(err) => {
+                    tracing::debug!(addr = %self.addr, "journal replication interrupted - {err}");
+                }
+            }
+
+            // Wait a bit before trying again, unless the journal is closed
+            let state = self.state.lock().unwrap();
+            let _ = self
+                .condvar
+                .wait_timeout_while(state, RECONNECT_INTERVAL, |state| !state.closed)
+                .unwrap();
+        }
+    }
+
+    /// Connects to the receiver and streams the records to it, returning
+    /// successfully only when the journal was closed
+    fn replicate(self: &Arc<Self>, history: &DynReadableJournal) -> anyhow::Result<()> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        write_frame(
+            &mut stream,
+            &RemoteJournalRequest::Hello {
+                version: PROTOCOL_VERSION,
+                token: self.token.clone(),
+            },
+        )?;
+        let next_offset = match read_frame(&mut stream)? {
+            RemoteJournalResponse::Hello {
+                version: PROTOCOL_VERSION,
+                next_offset,
+            } => next_offset,
+            RemoteJournalResponse::Hello { version, .. } => {
+                return Err(anyhow::format_err!(
+                    "the receiver uses an unsupported protocol version ({version})"
+                ));
+            }
+            frame => {
+                return Err(anyhow::format_err!(
+                    "the receiver sent an unexpected frame ({frame:?})"
+                ));
+            }
+        };
+        stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        tracing::debug!(addr = %self.addr, next_offset, "connected to the journal receiver");
+
+        // From now on new records are queued, everything before them is
+        // read back from the inner journal
+        let (history, catch_up_until) = {
+            let mut state = self.state.lock().unwrap();
+            if next_offset > state.written {
+                return Err(anyhow::format_err!(
+                    "the receiver has more records ({next_offset}) than this journal ({})",
+                    state.written
+                ));
+            }
+            self.inner.flush()?;
+            state.connected = true;
+            state.pending.clear();
+            state.acknowledged = state.acknowledged.max(next_offset);
+            (history.as_restarted()?, state.written)
+        };
+
+        // The acknowledgements are read on their own thread
+        let ret = AckReader::spawn(self.clone(), stream.try_clone()?).and_then(|_acks| {
+            self.stream_records(&mut stream, history.as_ref(), next_offset, catch_up_until)
+        });
+
+        let mut state = self.state.lock().unwrap();
+        state.connected = false;
+        state.pending.clear();
+        ret
+    }
+
+    fn stream_records(
+        &self,
+        stream: &mut TcpStream,
+        history: &DynReadableJournal,
+        next_offset: u64,
+        catch_up_until: u64,
+    ) -> anyhow::Result<()> {
+        let mut offset = 0;
+        while offset < catch_up_until {
+            let record = history.read()?.ok_or_else(|| {
+                anyhow::format_err!("the journal ended at record {offset} of {catch_up_until}")
+            })?;
+            if offset >= next_offset {
+                let entry = record.into_inner().into_owned();
+                write_frame(stream, &RemoteJournalRequest::Record { offset, entry })?;
+            }
+            offset += 1;
+        }
+
+        loop {
+            let next = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if let Some((offset, entry)) = state.pending.pop_front() {
+                        break Some(RemoteJournalRequest::Record { offset, entry });
+                    }
+                    if state.closed {
+                        break None;
+                    }
+                    if !state.connected {
+                        return Err(anyhow::format_err!("the receiver closed the connection"));
+                    }
+                    let (next, wait) = self
+                        .condvar
+                        .wait_timeout(state, HEARTBEAT_INTERVAL)
+                        .unwrap();
+                    state = next;
+                    if wait.timed_out() && state.pending.is_empty() {
+                        break Some(RemoteJournalRequest::Ping);
+                    }
+                }
+            };
+            let Some(request) = next else {
+                write_frame(stream, &RemoteJournalRequest::Close)?;
+                stream.flush()?;
+                stream.shutdown(Shutdown::Write).ok();
+                return Ok(());
+            };
+            write_frame(stream, &request)?;
+        }
+    }
+}
+
+/// Reads the acknowledgements sent by the receiver, the connection is
+/// closed when this is dropped
+struct AckReader {
+    stream: TcpStream,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AckReader {
+    fn spawn(shared: Arc<SenderShared>, mut stream: TcpStream) -> anyhow::Result<Self> {
+        let reader = stream.try_clone()?;
+        let thread = std::thread::Builder::new()
+            .name("journal-replication-acks".to_string())
+            .spawn(move || {
+                while let Ok(RemoteJournalResponse::Ack { next_offset }) =
+                    read_frame::<RemoteJournalResponse>(&mut stream)
+                {
+                    let mut state = shared.state.lock().unwrap();
+                    state.acknowledged = state.acknowledged.max(next_offset);
+                    shared.condvar.notify_all();
+                }
+
+                // Wakes up the sender so that it reconnects
+                let mut state = shared.state.lock().unwrap();
+                state.connected = false;
+                shared.condvar.notify_all();
+            })?;
+        Ok(Self {
+            stream: reader,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for AckReader {
+    fn drop(&mut self) {
+        self.stream.shutdown(Shutdown::Both).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for RemoteJournalTx {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.closed = true;
+            self.shared.condvar.notify_all();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl WritableJournal for RemoteJournalTx {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        let is_exit = matches!(entry, JournalEntry::ProcessExitV1 { .. });
+
+        // The lock is held while the record is written so that the records
+        // are queued in the same order as they are in the inner journal
+        let mut state = self.shared.state.lock().unwrap();
+        let res = if state.connected {
+            let entry = entry.into_owned();
+            let res = self.shared.inner.write(entry.clone())?;
+            let offset = state.written;
+            state.pending.push_back((offset, entry));
+            self.shared.condvar.notify_all();
+            res
+        } else {
+            self.shared.inner.write(entry)?
+        };
+        state.written += 1;
+        drop(state);
+
+        // The process may exit straight after this record, so the standby
+        // is given a chance to receive it first
+        if is_exit {
+            self.wait_for_acks(self.ack_timeout.unwrap_or(CONNECT_TIMEOUT), false)
+                .ok();
+        }
+        Ok(res)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.shared.inner.flush()?;
+
+        let Some(timeout) = self.ack_timeout else {
+            return Ok(());
+        };
+        if let Err((acknowledged, written)) = self.wait_for_acks(timeout, true) {
+            return Err(anyhow::format_err!(
+                "the journal receiver at {} has only acknowledged {} of {} records",
+                self.shared.addr,
+                acknowledged,
+                written
+            ));
+        }
+        Ok(())
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.shared.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.shared.inner.rollback()
+    }
+}
+
+impl WritableJournal for RemoteJournal {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        self.tx.write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.tx.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.tx.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.tx.rollback()
+    }
+}
+
+impl ReadableJournal for RemoteJournal {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.rx.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.rx.as_restarted()
+    }
+}
+
+impl Journal for RemoteJournal {
+    fn split(self) -> (Box<DynWritableJournal>, Box<DynReadableJournal>) {
+        (Box::new(self.tx), self.rx)
+    }
+}
+
+/// The receiver accepts connections from a [`RemoteJournal`] and writes
+/// the records it is sent to an inner journal, which makes it a replica
+/// of the journal of the sender.
+///
+/// Only senders that know the token of the receiver are accepted, and a
+/// sender that connects replaces the previous one, whose connection may
+/// have gone stale. The records are not encrypted, so the receiver must
+/// not listen on an address that is exposed outside of a trusted network.
+///
+/// When following, the receiver can also be read as a journal to apply
+/// the records to a warm standby as they arrive. Reading returns the
+/// records that are already in the inner journal followed by the ones
+/// that are received, and only ends once the sender closes its journal,
+/// the sender has been disconnected for longer than the failover timeout
+/// or the receiver is promoted, which also happens as soon as the standby
+/// writes to it.
+#[derive(Debug)]
+pub struct RemoteJournalReceiver {
+    shared: Arc<ReceiverShared>,
+    rx: RemoteJournalReceiverRx,
+}
+
+#[derive(Debug)]
+struct ReceiverState {
+    /// Index of the next record that will be written to the inner journal
+    next_offset: u64,
+    /// Records that were received but not read yet (only kept when following)
+    received: VecDeque<JournalEntry<'static>>,
+    /// The connected sender along with its connection number
+    sender: Option<(u64, TcpStream)>,
+    /// Number of senders that connected, which tells them apart
+    connections: u64,
+    /// When the sender was last disconnected
+    disconnected_at: Instant,
+    /// Set once the sender closed its journal or the receiver was promoted
+    finished: bool,
+    /// How long the sender may be disconnected before the standby takes over
+    failover_timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct ReceiverShared {
+    inner: Box<DynWritableJournal>,
+    token: String,
+    follow: bool,
+    state: Mutex<ReceiverState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+pub struct RemoteJournalReceiverRx {
+    shared: Arc<ReceiverShared>,
+    /// Records that were in the inner journal before anything was received
+    existing: Mutex<(u64, Box<DynReadableJournal>)>,
+    offset: Mutex<u64>,
+}
+
+impl RemoteJournalReceiver {
+    /// Creates a receiver that replicates the records it is sent into
+    /// `inner`, continuing after the records that are already there.
+    ///
+    /// Only the senders that were created with the same `token` are
+    /// accepted. If `follow` is set the records that are received are also
+    /// kept until they are read back from the receiver.
+    pub fn new<J>(inner: J, follow: bool, token: impl Into<String>) -> anyhow::Result<Self>
+    where
+        J: Journal,
+    {
+        let token = token.into();
+        check_token(&token)?;
+        let next_offset = count_records(&inner)?;
+        let (tx, rx) = inner.split();
+        let rx = rx.as_restarted()?;
+
+        let shared = Arc::new(ReceiverShared {
+            inner: tx,
+            token,
+            follow,
+            state: Mutex::new(ReceiverState {
+                next_offset,
+                received: Default::default(),
+                sender: None,
+                connections: 0,
+                disconnected_at: Instant::now(),
+                finished: false,
+                failover_timeout: None,
+            }),
+            condvar: Condvar::new(),
+        });
+        Ok(Self {
+            rx: RemoteJournalReceiverRx {
+                shared: shared.clone(),
+                existing: Mutex::new((next_offset, rx)),
+                offset: Mutex::new(0),
+            },
+            shared,
+        })
+    }
+
+    /// Stops following once the sender has been disconnected for longer
+    /// than `timeout`, which lets the standby take over. The timer starts
+    /// when the receiver starts listening.
+    pub fn with_failover_timeout(self, timeout: Duration) -> Self {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .failover_timeout
+            .replace(timeout);
+        self
+    }
+
+    /// Accepts connections from senders on a background thread, a new
+    /// sender replacing the one that is connected
+    pub fn listen(&self, listener: TcpListener) -> anyhow::Result<SocketAddr> {
+        let addr = listener.local_addr()?;
+        self.shared.state.lock().unwrap().disconnected_at = Instant::now();
+
+        let shared = self.shared.clone();
+        std::thread::Builder::new()
+            .name("journal-receiver".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if shared.state.lock().unwrap().finished {
+                        return;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            tracing::debug!("failed to accept a journal sender - {err}");
+                            continue;
+                        }
+                    };
+                    let peer = stream.peer_addr().ok();
+                    let shared = shared.clone();
+                    let spawned = std::thread::Builder::new()
+                        .name("journal-receiver-conn".to_string())
+                        .spawn(move || {
+                            if let Err(err) = shared.serve(stream) {
+                                tracing::debug!(?peer, "journal sender disconnected - {err}");
+                            }
+                        });
+                    if let Err(err) = spawned {
+                        tracing::debug!(?peer, "failed to serve a journal sender - {err}");
+                    }
+                }
+            })?;
+        Ok(addr)
+    }
+
+    /// Returns the number of records in the inner journal
+    pub fn received(&self) -> u64 {
+        self.shared.state.lock().unwrap().next_offset
+    }
+
+    /// Stops accepting records from the sender so that the standby can
+    /// take over, reading from the receiver ends after the records that
+    /// were already received
+    pub fn promote(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.finished = true;
+        self.shared.condvar.notify_all();
+    }
+
+    pub fn into_split(self) -> (Box<DynWritableJournal>, RemoteJournalReceiverRx) {
+        (Box::new(ReceiverTx(self.shared)), self.rx)
+    }
+}
+
+impl ReceiverShared {
+    fn serve(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        match read_frame(&mut stream)? {
+            RemoteJournalRequest::Hello {
+                version: PROTOCOL_VERSION,
+                token,
+            } if tokens_match(&token, &self.token) => {}
+            RemoteJournalRequest::Hello {
+                version: PROTOCOL_VERSION,
+                ..
+            } => {
+                return Err(anyhow::format_err!("the sender sent the wrong token"));
+            }
+            RemoteJournalRequest::Hello { version, .. } => {
+                return Err(anyhow::format_err!(
+                    "the sender uses an unsupported protocol version ({version})"
+                ));
+            }
+            _ => {
+                return Err(anyhow::format_err!(
+                    "the sender sent records before saying hello"
+                ));
+            }
+        }
+        stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+
+        let (id, next_offset) = self.connect(stream.try_clone()?)?;
+        let ret = write_frame(
+            &mut stream,
+            &RemoteJournalResponse::Hello {
+                version: PROTOCOL_VERSION,
+                next_offset,
+            },
+        )
+        .and_then(|()| self.receive(id, &mut stream));
+        self.disconnect(id);
+        ret
+    }
+
+    /// Makes `stream` the connection of the sender, dropping the previous
+    /// one, and returns its connection number and the next expected offset
+    fn connect(&self, stream: TcpStream) -> anyhow::Result<(u64, u64)> {
+        let mut state = self.state.lock().unwrap();
+        if state.finished {
+            return Err(anyhow::format_err!(
+                "the receiver no longer accepts records"
+            ));
+        }
+        state.connections += 1;
+        let id = state.connections;
+        if let Some((_, previous)) = state.sender.replace((id, stream)) {
+            tracing::debug!("a new journal sender replaces the connected one");
+            previous.shutdown(Shutdown::Both).ok();
+        }
+        self.condvar.notify_all();
+        Ok((id, state.next_offset))
+    }
+
+    fn disconnect(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(state.sender, Some((current, _)) if current == id) {
+            state.sender = None;
+            state.disconnected_at = Instant::now();
+            self.condvar.notify_all();
+        }
+    }
+
+    fn receive(&self, id: u64, stream: &mut TcpStream) -> anyhow::Result<()> {
+        loop {
+            let (offset, entry) = match read_frame(stream)? {
+                RemoteJournalRequest::Record { offset, entry } => (offset, entry),
+                RemoteJournalRequest::Ping => {
+                    let next_offset = self.state.lock().unwrap().next_offset;
+                    write_frame(stream, &RemoteJournalResponse::Ack { next_offset })?;
+                    continue;
+                }
+                RemoteJournalRequest::Close => {
+                    self.inner.flush()?;
+                    let mut state = self.state.lock().unwrap();
+                    state.finished = true;
+                    self.condvar.notify_all();
+                    return Ok(());
+                }
+                RemoteJournalRequest::Hello { .. } => {
+                    return Err(anyhow::format_err!("the sender said hello twice"));
+                }
+            };
+
+            let next_offset = {
+                let mut state = self.state.lock().unwrap();
+                if state.finished {
+                    return Err(anyhow::format_err!(
+                        "the receiver no longer accepts records"
+                    ));
+                }
+                if !matches!(state.sender, Some((current, _)) if current == id) {
+                    return Err(anyhow::format_err!("the sender was replaced by a new one"));
+                }
+                if offset > state.next_offset {
+                    return Err(anyhow::format_err!(
+                        "the sender skipped records {} to {}",
+                        state.next_offset,
+                        offset - 1
+                    ));
+                }
+
+                // Records that were already received are sent again when
+                // the sender reconnects and can be ignored
+                if offset == state.next_offset {
+                    if self.follow {
+                        self.inner.write(entry.clone())?;
+                        state.received.push_back(entry);
+                        self.condvar.notify_all();
+                    } else {
+                        self.inner.write(entry)?;
+                    }
+                    self.inner.flush()?;
+                    state.next_offset += 1;
+                }
+                state.next_offset
+            };
+            write_frame(stream, &RemoteJournalResponse::Ack { next_offset })?;
+        }
+    }
+}
+
+impl RemoteJournalReceiverRx {
+    /// Returns true once all the records have been read and no more will
+    /// be received
+    fn is_finished(state: &mut ReceiverState) -> bool {
+        if state.finished {
+            return true;
+        }
+        if let Some(timeout) = state.failover_timeout {
+            if state.sender.is_none() && state.disconnected_at.elapsed() >= timeout {
+                tracing::debug!("journal sender is gone, the standby is taking over");
+                state.finished = true;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl ReadableJournal for RemoteJournalReceiverRx {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        {
+            let mut existing = self.existing.lock().unwrap();
+            let (remaining, rx) = &mut *existing;
+            if *remaining > 0 {
+                if let Some(res) = rx.read()? {
+                    *remaining -= 1;
+                    return Ok(Some(LogReadResult {
+                        record_start: res.record_start,
+                        record_end: res.record_end,
+                        record: res.record.into_owned(),
+                    }));
+                }
+                *remaining = 0;
+            }
+        }
+        if !self.shared.follow {
+            return Ok(None);
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        let record = loop {
+            if let Some(record) = state.received.pop_front() {
+                break record;
+            }
+            if Self::is_finished(&mut state) {
+                return Ok(None);
+            }
+            state = match (state.failover_timeout, state.sender.is_some()) {
+                (Some(timeout), false) => {
+                    let remaining = timeout.saturating_sub(state.disconnected_at.elapsed());
+                    self.shared
+                        .condvar
+                        .wait_timeout(state, remaining)
+                        .unwrap()
+                        .0
+                }
+                _ => self.shared.condvar.wait(state).unwrap(),
+            };
+        };
+
+        let mut offset = self.offset.lock().unwrap();
+        let record_start = *offset;
+        *offset += record.estimate_size() as u64;
+        Ok(Some(LogReadResult {
+            record_start,
+            record_end: *offset,
+            record,
+        }))
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        Err(anyhow::format_err!(
+            "the records received from a remote journal can only be read once"
+        ))
+    }
+}
+
+/// Writes to the inner journal of the receiver, which is how the standby
+/// carries on with the journal once it has taken over
+#[derive(Debug)]
+struct ReceiverTx(Arc<ReceiverShared>);
+
+impl WritableJournal for ReceiverTx {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        // Once the standby writes its own records it has taken over, so
+        // nothing more is accepted from the sender
+        let mut state = self.0.state.lock().unwrap();
+        if !state.finished {
+            tracing::debug!("journal written to by the standby, it is taking over");
+            state.finished = true;
+            self.0.condvar.notify_all();
+        }
+        let res = self.0.inner.write(entry)?;
+        state.next_offset += 1;
+        Ok(res)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.0.inner.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.0.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.0.inner.rollback()
+    }
+}
+
+impl WritableJournal for RemoteJournalReceiver {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        ReceiverTx(self.shared.clone()).write(entry)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.shared.inner.flush()
+    }
+
+    fn commit(&self) -> anyhow::Result<usize> {
+        self.shared.inner.commit()
+    }
+
+    fn rollback(&self) -> anyhow::Result<usize> {
+        self.shared.inner.rollback()
+    }
+}
+
+impl ReadableJournal for RemoteJournalReceiver {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        self.rx.read()
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        self.rx.as_restarted()
+    }
+}
+
+impl Journal for RemoteJournalReceiver {
+    fn split(self) -> (Box<DynWritableJournal>, Box<DynReadableJournal>) {
+        let (tx, rx) = self.into_split();
+        (tx, Box::new(rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "s3cr3t";
+
+    fn entries() -> Vec<JournalEntry<'static>> {
+        vec![
+            JournalEntry::CreatePipeV1 {
+                read_fd: 1,
+                write_fd: 2,
+            },
+            JournalEntry::UpdateMemoryRegionV1 {
+                region: 0..16,
+                compressed_data: vec![0xAB; 16].into(),
+            },
+            JournalEntry::PortAddrClearV1,
+            JournalEntry::CloseFileDescriptorV1 { fd: 3 },
+        ]
+    }
+
+    fn read_all(journal: &impl ReadableJournal) -> anyhow::Result<Vec<JournalEntry<'static>>> {
+        let mut ret = Vec::new();
+        while let Some(record) = journal.read()? {
+            ret.push(record.into_inner().into_owned());
+        }
+        Ok(ret)
+    }
+
+    fn journal_with(entries: &[JournalEntry<'static>]) -> BufferedJournal {
+        let journal = BufferedJournal::default();
+        for entry in entries {
+            journal.write(entry.clone()).unwrap();
+        }
+        journal
+    }
+
+    fn listen(receiver: &RemoteJournalReceiver) -> SocketAddr {
+        receiver
+            .listen(TcpListener::bind("127.0.0.1:0").unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_remote_replicates_records() {
+        let replica = BufferedJournal::default();
+        let view = replica.as_restarted().unwrap();
+        let receiver = RemoteJournalReceiver::new(replica, false, TOKEN).unwrap();
+        let addr = listen(&receiver);
+
+        let journal = RemoteJournal::new(BufferedJournal::default(), addr, TOKEN)
+            .unwrap()
+            .with_ack_timeout(Duration::from_secs(10));
+        for entry in entries() {
+            journal.write(entry).unwrap();
+        }
+        journal.flush().unwrap();
+
+        assert_eq!(journal.acknowledged(), 4);
+        assert_eq!(read_all(&view).unwrap(), entries());
+    }
+
+    #[test]
+    fn test_remote_resumes_from_acknowledged_offset() {
+        let all = entries();
+
+        // The replica already has the first records and the journal
+        // has all but the last
+        let replica = journal_with(&all[..2]);
+        let view = replica.as_restarted().unwrap();
+        let receiver = RemoteJournalReceiver::new(replica, false, TOKEN).unwrap();
+        let addr = listen(&receiver);
+
+        let journal = RemoteJournal::new(journal_with(&all[..3]), addr, TOKEN)
+            .unwrap()
+            .with_ack_timeout(Duration::from_secs(10));
+        journal.write(all[3].clone()).unwrap();
+        journal.flush().unwrap();
+
+        assert_eq!(receiver.received(), 4);
+        assert_eq!(read_all(&view).unwrap(), all);
+    }
+
+    #[test]
+    fn test_remote_follower_reads_until_closed() {
+        let all = entries();
+        let receiver = RemoteJournalReceiver::new(journal_with(&all[..1]), true, TOKEN).unwrap();
+        let addr = listen(&receiver);
+
+        let journal = RemoteJournal::new(journal_with(&all[..1]), addr, TOKEN)
+            .unwrap()
+            .with_ack_timeout(Duration::from_secs(10));
+        for entry in all[1..].iter() {
+            journal.write(entry.clone()).unwrap();
+        }
+        journal.flush().unwrap();
+
+        // Dropping the journal tells the receiver that the stream ended
+        drop(journal);
+
+        assert_eq!(read_all(&receiver).unwrap(), all);
+    }
+
+    #[test]
+    fn test_remote_follower_fails_over() {
+        let receiver = RemoteJournalReceiver::new(BufferedJournal::default(), true, TOKEN)
+            .unwrap()
+            .with_failover_timeout(Duration::from_millis(100));
+        listen(&receiver);
+
+        // Nothing ever connects so the standby takes over
+        let start = Instant::now();
+        assert!(receiver.read().unwrap().is_none());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_remote_rejects_the_wrong_token() {
+        let receiver =
+            RemoteJournalReceiver::new(BufferedJournal::default(), false, TOKEN).unwrap();
+        let addr = listen(&receiver);
+
+        let journal = RemoteJournal::new(BufferedJournal::default(), addr, "guess")
+            .unwrap()
+            .with_ack_timeout(Duration::from_millis(500));
+        journal.write(entries().remove(0)).unwrap();
+
+        assert!(journal.flush().is_err());
+        assert_eq!(receiver.received(), 0);
+    }
+
+    #[test]
+    fn test_remote_new_sender_replaces_a_stale_one() {
+        let replica = BufferedJournal::default();
+        let view = replica.as_restarted().unwrap();
+        let receiver = RemoteJournalReceiver::new(replica, false, TOKEN).unwrap();
+        let addr = listen(&receiver);
+
+        // A sender that went quiet without closing its connection
+        let mut stale = TcpStream::connect(addr).unwrap();
+        write_frame(
+            &mut stale,
+            &RemoteJournalRequest::Hello {
+                version: PROTOCOL_VERSION,
+                token: TOKEN.to_string(),
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            read_frame(&mut stale).unwrap(),
+            RemoteJournalResponse::Hello { .. }
+        ));
+
+        let journal = RemoteJournal::new(BufferedJournal::default(), addr, TOKEN)
+            .unwrap()
+            .with_ack_timeout(Duration::from_secs(10));
+        for entry in entries() {
+            journal.write(entry).unwrap();
+        }
+        journal.flush().unwrap();
+
+        assert_eq!(read_all(&view).unwrap(), entries());
+        // The receiver closed the stale connection
+        stale.set_read_timeout(Some(CONNECT_TIMEOUT)).unwrap();
+        let mut buf = [0u8; 1];
+        match stale.read(&mut buf) {
+            Ok(read) => assert_eq!(read, 0),
+            Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset),
+        }
+    }
+}
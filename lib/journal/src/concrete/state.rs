@@ -0,0 +1,748 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    net::SocketAddr,
+    ops::Range,
+    sync::Mutex,
+};
+
+use lz4_flex::{block, decompress};
+use wasmer_wasix_types::wasi;
+
+use super::*;
+
+/// Granularity at which the memory of two states is compared
+const MEMORY_CHUNK_SIZE: u64 = 4096;
+
+/// Reconstructs the state of a process (its files, memory, threads and
+/// sockets) from the records written to it, so that the state at two
+/// points of a journal, or of two journals, can be compared.
+#[derive(Debug, Default)]
+pub struct JournalState {
+    inner: Mutex<JournalStateInner>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct JournalStateInner {
+    records: u64,
+    /// Paths of the file descriptors that refer to files or directories
+    fds: HashMap<u32, String>,
+    files: BTreeMap<String, FileState>,
+    memory: BTreeMap<u64, Vec<u8>>,
+    threads: BTreeMap<u32, u64>,
+    sockets: BTreeMap<u32, SocketState>,
+    #[cfg(feature = "chunked-memory")]
+    memory_chunks: MemoryChunkStore,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FileState {
+    Directory,
+    File(Vec<u8>),
+    Symlink(String),
+}
+
+impl std::fmt::Display for FileState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Directory => write!(f, "directory"),
+            Self::File(data) => write!(f, "{} bytes", data.len()),
+            Self::Symlink(target) => write!(f, "symlink to {target}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SocketState {
+    ty: Option<String>,
+    bound: Option<SocketAddr>,
+    listening: bool,
+    local: Option<SocketAddr>,
+    peer: Option<SocketAddr>,
+}
+
+impl std::fmt::Display for SocketState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ty.as_deref().unwrap_or("socket"))?;
+        if self.listening {
+            write!(f, " listening")?;
+        }
+        if let Some(addr) = self.bound.as_ref() {
+            write!(f, " bound={addr}")?;
+        }
+        if let Some(addr) = self.local.as_ref() {
+            write!(f, " local={addr}")?;
+        }
+        if let Some(addr) = self.peer.as_ref() {
+            write!(f, " peer={addr}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A difference between two [`JournalState`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalStateChange {
+    FileAdded {
+        path: String,
+        file: String,
+    },
+    FileRemoved {
+        path: String,
+    },
+    FileChanged {
+        path: String,
+        old: String,
+        new: String,
+    },
+    MemoryChanged {
+        region: Range<u64>,
+    },
+    ThreadAdded {
+        id: u32,
+    },
+    ThreadRemoved {
+        id: u32,
+    },
+    ThreadChanged {
+        id: u32,
+    },
+    SocketAdded {
+        fd: u32,
+        socket: String,
+    },
+    SocketRemoved {
+        fd: u32,
+        socket: String,
+    },
+    SocketChanged {
+        fd: u32,
+        old: String,
+        new: String,
+    },
+}
+
+impl JournalStateChange {
+    /// The part of the state that changed, which is one of `files`,
+    /// `memory`, `threads` or `sockets`
+    pub fn section(&self) -> &'static str {
+        match self {
+            Self::FileAdded { .. } | Self::FileRemoved { .. } | Self::FileChanged { .. } => "files",
+            Self::MemoryChanged { .. } => "memory",
+            Self::ThreadAdded { .. } | Self::ThreadRemoved { .. } | Self::ThreadChanged { .. } => {
+                "threads"
+            }
+            Self::SocketAdded { .. } | Self::SocketRemoved { .. } | Self::SocketChanged { .. } => {
+                "sockets"
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for JournalStateChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FileAdded { path, file } => write!(f, "+ file {path} ({file})"),
+            Self::FileRemoved { path } => write!(f, "- file {path}"),
+            Self::FileChanged { path, old, new } => write!(f, "~ file {path} ({old} -> {new})"),
+            Self::MemoryChanged { region } => write!(
+                f,
+                "~ memory {:#x}..{:#x} ({} bytes)",
+                region.start,
+                region.end,
+                region.end - region.start
+            ),
+            Self::ThreadAdded { id } => write!(f, "+ thread {id}"),
+            Self::ThreadRemoved { id } => write!(f, "- thread {id}"),
+            Self::ThreadChanged { id } => write!(f, "~ thread {id}"),
+            Self::SocketAdded { fd, socket } => write!(f, "+ socket fd={fd} ({socket})"),
+            Self::SocketRemoved { fd, socket } => write!(f, "- socket fd={fd} ({socket})"),
+            Self::SocketChanged { fd, old, new } => {
+                write!(f, "~ socket fd={fd} ({old} -> {new})")
+            }
+        }
+    }
+}
+
+/// Joins `path` onto `base` and removes the `.` and `..` components
+fn normalize_path(base: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let full = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{base}/{path}")
+    };
+    for part in full.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn socket_type(af: wasi::Addressfamily, ty: wasi::Socktype) -> String {
+    let af = match af {
+        wasi::Addressfamily::Unspec => "unspec",
+        wasi::Addressfamily::Inet4 => "inet4",
+        wasi::Addressfamily::Inet6 => "inet6",
+        wasi::Addressfamily::Unix => "unix",
+    };
+    let ty = match ty {
+        wasi::Socktype::Unknown => "unknown",
+        wasi::Socktype::Stream => "stream",
+        wasi::Socktype::Dgram => "dgram",
+        wasi::Socktype::Raw => "raw",
+        wasi::Socktype::Seqpacket => "seqpacket",
+    };
+    format!("{af}/{ty}")
+}
+
+fn hash_of(data: &[&[u8]]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl JournalStateInner {
+    fn resolve(&self, fd: u32, path: &str) -> String {
+        let base = self.fds.get(&fd).map(String::as_str).unwrap_or("/");
+        normalize_path(base, path)
+    }
+
+    fn file_mut(&mut self, fd: u32) -> Option<&mut Vec<u8>> {
+        let path = self.fds.get(&fd)?;
+        match self.files.get_mut(path) {
+            Some(FileState::File(data)) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn write_memory(&mut self, region: Range<u64>, data: &[u8]) {
+        let mut offset = region.start;
+        let mut data = data;
+        while !data.is_empty() {
+            let chunk = offset / MEMORY_CHUNK_SIZE;
+            let start = (offset % MEMORY_CHUNK_SIZE) as usize;
+            let len = data.len().min(MEMORY_CHUNK_SIZE as usize - start);
+            let page = self
+                .memory
+                .entry(chunk)
+                .or_insert_with(|| vec![0u8; MEMORY_CHUNK_SIZE as usize]);
+            page[start..start + len].copy_from_slice(&data[..len]);
+            offset += len as u64;
+            data = &data[len..];
+        }
+    }
+
+    fn remove_tree(&mut self, path: &str) {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        self.files
+            .retain(|p, _| p != path && !p.starts_with(prefix.as_str()));
+    }
+
+    fn rename_tree(&mut self, from: &str, to: &str) {
+        self.remove_tree(to);
+        let prefix = format!("{}/", from.trim_end_matches('/'));
+        let moved: Vec<_> = self
+            .files
+            .keys()
+            .filter(|p| *p == from || p.starts_with(prefix.as_str()))
+            .cloned()
+            .collect();
+        for path in moved {
+            let state = self.files.remove(&path).unwrap();
+            let new_path = format!("{to}{}", &path[from.len()..]);
+            self.files.insert(new_path, state);
+        }
+        for path in self.fds.values_mut() {
+            if path == from || path.starts_with(prefix.as_str()) {
+                *path = format!("{to}{}", &path[from.len()..]);
+            }
+        }
+    }
+
+    fn apply(&mut self, entry: &JournalEntry<'_>) -> anyhow::Result<()> {
+        self.records += 1;
+        match entry {
+            JournalEntry::InitModuleV1 { .. } | JournalEntry::ProcessExitV1 { .. } => {
+                self.memory.clear();
+                self.threads.clear();
+            }
+            JournalEntry::UpdateMemoryRegionV1 {
+                region,
+                compressed_data,
+            } => {
+                let (uncompressed_size, compressed_data) =
+                    block::uncompressed_size(compressed_data)?;
+                let data = decompress(compressed_data, uncompressed_size)?;
+                self.write_memory(region.clone(), &data);
+            }
+            #[cfg(feature = "chunked-memory")]
+            JournalEntry::MemoryChunkV1 {
+                hash,
+                compressed_data,
+            } => {
+                self.memory_chunks.insert(*hash, compressed_data);
+            }
+            #[cfg(feature = "chunked-memory")]
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                let data = self.memory_chunks.assemble(region, chunks)?;
+                self.write_memory(region.clone(), &data);
+            }
+            JournalEntry::SetThreadV1 {
+                id,
+                call_stack,
+                memory_stack,
+                store_data,
+                ..
+            } => {
+                self.threads
+                    .insert(*id, hash_of(&[call_stack, memory_stack, store_data]));
+            }
+            JournalEntry::CloseThreadV1 { id, .. } => {
+                self.threads.remove(id);
+            }
+            JournalEntry::OpenFileDescriptorV1 {
+                fd,
+                dirfd,
+                path,
+                o_flags,
+                ..
+            }
+            | JournalEntry::OpenFileDescriptorV2 {
+                fd,
+                dirfd,
+                path,
+                o_flags,
+                ..
+            } => {
+                let path = self.resolve(*dirfd, path);
+                if o_flags.contains(wasi::Oflags::DIRECTORY) {
+                    self.files
+                        .entry(path.clone())
+                        .or_insert(FileState::Directory);
+                } else if o_flags.contains(wasi::Oflags::CREATE) || self.files.contains_key(&path) {
+                    let file = self
+                        .files
+                        .entry(path.clone())
+                        .or_insert_with(|| FileState::File(Vec::new()));
+                    if let (FileState::File(data), true) =
+                        (file, o_flags.contains(wasi::Oflags::TRUNC))
+                    {
+                        data.clear();
+                    }
+                }
+                self.fds.insert(*fd, path);
+            }
+            JournalEntry::FileDescriptorWriteV1 {
+                fd, offset, data, ..
+            } => {
+                if let Some(file) = self.file_mut(*fd) {
+                    let end = *offset as usize + data.len();
+                    if file.len() < end {
+                        file.resize(end, 0);
+                    }
+                    file[*offset as usize..end].copy_from_slice(data);
+                }
+            }
+            JournalEntry::FileDescriptorSetSizeV1 { fd, st_size } => {
+                if let Some(file) = self.file_mut(*fd) {
+                    file.resize(*st_size as usize, 0);
+                }
+            }
+            JournalEntry::FileDescriptorAllocateV1 { fd, offset, len } => {
+                if let Some(file) = self.file_mut(*fd) {
+                    let end = (*offset + *len) as usize;
+                    if file.len() < end {
+                        file.resize(end, 0);
+                    }
+                }
+            }
+            JournalEntry::CloseFileDescriptorV1 { fd } => {
+                self.fds.remove(fd);
+                self.sockets.remove(fd);
+            }
+            JournalEntry::RenumberFileDescriptorV1 { old_fd, new_fd } => {
+                if let Some(path) = self.fds.remove(old_fd) {
+                    self.fds.insert(*new_fd, path);
+                }
+                if let Some(socket) = self.sockets.remove(old_fd) {
+                    self.sockets.insert(*new_fd, socket);
+                }
+            }
+            JournalEntry::DuplicateFileDescriptorV1 {
+                original_fd,
+                copied_fd,
+            }
+            | JournalEntry::DuplicateFileDescriptorV2 {
+                original_fd,
+                copied_fd,
+                ..
+            } => {
+                if let Some(path) = self.fds.get(original_fd).cloned() {
+                    self.fds.insert(*copied_fd, path);
+                }
+                if let Some(socket) = self.sockets.get(original_fd).cloned() {
+                    self.sockets.insert(*copied_fd, socket);
+                }
+            }
+            JournalEntry::CreateDirectoryV1 { fd, path } => {
+                let path = self.resolve(*fd, path);
+                self.files.insert(path, FileState::Directory);
+            }
+            JournalEntry::RemoveDirectoryV1 { fd, path }
+            | JournalEntry::UnlinkFileV1 { fd, path } => {
+                let path = self.resolve(*fd, path);
+                self.remove_tree(&path);
+            }
+            JournalEntry::PathRenameV1 {
+                old_fd,
+                old_path,
+                new_fd,
+                new_path,
+            } => {
+                let from = self.resolve(*old_fd, old_path);
+                let to = self.resolve(*new_fd, new_path);
+                self.rename_tree(&from, &to);
+            }
+            JournalEntry::CreateHardLinkV1 {
+                old_fd,
+                old_path,
+                new_fd,
+                new_path,
+                ..
+            } => {
+                let from = self.resolve(*old_fd, old_path);
+                let to = self.resolve(*new_fd, new_path);
+                if let Some(file) = self.files.get(&from).cloned() {
+                    self.files.insert(to, file);
+                }
+            }
+            JournalEntry::CreateSymbolicLinkV1 {
+                old_path,
+                fd,
+                new_path,
+            } => {
+                let path = self.resolve(*fd, new_path);
+                self.files
+                    .insert(path, FileState::Symlink(old_path.to_string()));
+            }
+            JournalEntry::SocketOpenV1 { af, ty, fd, .. } => {
+                self.sockets.insert(
+                    *fd,
+                    SocketState {
+                        ty: Some(socket_type(*af, *ty)),
+                        ..Default::default()
+                    },
+                );
+            }
+            JournalEntry::SocketPairV1 { fd1, fd2 } => {
+                for fd in [fd1, fd2] {
+                    self.sockets.insert(
+                        *fd,
+                        SocketState {
+                            ty: Some("pair".to_string()),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            JournalEntry::SocketBindV1 { fd, addr } => {
+                self.sockets.entry(*fd).or_default().bound = Some(*addr);
+            }
+            JournalEntry::SocketListenV1 { fd, .. } => {
+                self.sockets.entry(*fd).or_default().listening = true;
+            }
+            JournalEntry::SocketConnectedV1 {
+                fd,
+                local_addr,
+                peer_addr,
+            } => {
+                let socket = self.sockets.entry(*fd).or_default();
+                socket.local = Some(*local_addr);
+                socket.peer = Some(*peer_addr);
+            }
+            JournalEntry::SocketAcceptedV1 {
+                listen_fd,
+                fd,
+                local_addr,
+                peer_addr,
+                ..
+            } => {
+                let ty = self.sockets.get(listen_fd).and_then(|s| s.ty.clone());
+                self.sockets.insert(
+                    *fd,
+                    SocketState {
+                        ty,
+                        local: Some(*local_addr),
+                        peer: Some(*peer_addr),
+                        ..Default::default()
+                    },
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl JournalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of records that were applied to the state
+    pub fn records(&self) -> u64 {
+        self.inner.lock().unwrap().records
+    }
+
+    /// Returns the changes that turn this state into `other`
+    pub fn diff(&self, other: &JournalState) -> Vec<JournalStateChange> {
+        let a = self.inner.lock().unwrap().clone();
+        let b = other.inner.lock().unwrap().clone();
+        let mut changes = Vec::new();
+
+        // Files
+        for (path, file) in a.files.iter() {
+            match b.files.get(path) {
+                None => changes.push(JournalStateChange::FileRemoved { path: path.clone() }),
+                Some(other) if other != file => changes.push(JournalStateChange::FileChanged {
+                    path: path.clone(),
+                    old: file.to_string(),
+                    new: other.to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (path, file) in b.files.iter() {
+            if !a.files.contains_key(path) {
+                changes.push(JournalStateChange::FileAdded {
+                    path: path.clone(),
+                    file: file.to_string(),
+                });
+            }
+        }
+
+        // Memory, where adjacent chunks are merged into a single region
+        let zero = vec![0u8; MEMORY_CHUNK_SIZE as usize];
+        let mut chunks: Vec<u64> = a.memory.keys().chain(b.memory.keys()).copied().collect();
+        chunks.sort_unstable();
+        chunks.dedup();
+        let mut region: Option<Range<u64>> = None;
+        for chunk in chunks {
+            let left = a.memory.get(&chunk).unwrap_or(&zero);
+            let right = b.memory.get(&chunk).unwrap_or(&zero);
+            if left == right {
+                continue;
+            }
+            let start = chunk * MEMORY_CHUNK_SIZE;
+            let end = start + MEMORY_CHUNK_SIZE;
+            region = match region.take() {
+                Some(r) if r.end == start => Some(r.start..end),
+                Some(r) => {
+                    changes.push(JournalStateChange::MemoryChanged { region: r });
+                    Some(start..end)
+                }
+                None => Some(start..end),
+            };
+        }
+        if let Some(region) = region {
+            changes.push(JournalStateChange::MemoryChanged { region });
+        }
+
+        // Threads
+        for (id, hash) in a.threads.iter() {
+            match b.threads.get(id) {
+                None => changes.push(JournalStateChange::ThreadRemoved { id: *id }),
+                Some(other) if other != hash => {
+                    changes.push(JournalStateChange::ThreadChanged { id: *id })
+                }
+                Some(_) => {}
+            }
+        }
+        for id in b.threads.keys() {
+            if !a.threads.contains_key(id) {
+                changes.push(JournalStateChange::ThreadAdded { id: *id });
+            }
+        }
+
+        // Sockets
+        for (fd, socket) in a.sockets.iter() {
+            match b.sockets.get(fd) {
+                None => changes.push(JournalStateChange::SocketRemoved {
+                    fd: *fd,
+                    socket: socket.to_string(),
+                }),
+                Some(other) if other != socket => changes.push(JournalStateChange::SocketChanged {
+                    fd: *fd,
+                    old: socket.to_string(),
+                    new: other.to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (fd, socket) in b.sockets.iter() {
+            if !a.sockets.contains_key(fd) {
+                changes.push(JournalStateChange::SocketAdded {
+                    fd: *fd,
+                    socket: socket.to_string(),
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+impl ReadableJournal for JournalState {
+    fn read(&self) -> anyhow::Result<Option<LogReadResult<'_>>> {
+        Ok(None)
+    }
+
+    fn as_restarted(&self) -> anyhow::Result<Box<DynReadableJournal>> {
+        Ok(Box::<JournalState>::default())
+    }
+}
+
+impl WritableJournal for JournalState {
+    fn write<'a>(&'a self, entry: JournalEntry<'a>) -> anyhow::Result<LogWriteResult> {
+        let estimated_size = entry.estimate_size() as u64;
+        self.inner.lock().unwrap().apply(&entry)?;
+        Ok(LogWriteResult {
+            record_start: 0,
+            record_end: estimated_size,
+        })
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: Vec<JournalEntry<'static>>) -> JournalState {
+        let state = JournalState::new();
+        for entry in entries {
+            state.write(entry).unwrap();
+        }
+        state
+    }
+
+    fn open(fd: u32, path: &str) -> JournalEntry<'static> {
+        JournalEntry::OpenFileDescriptorV1 {
+            fd,
+            dirfd: 3,
+            dirflags: 0,
+            path: path.to_string().into(),
+            o_flags: wasi::Oflags::CREATE,
+            fs_rights_base: wasi::Rights::all(),
+            fs_rights_inheriting: wasi::Rights::all(),
+            fs_flags: wasi::Fdflags::empty(),
+        }
+    }
+
+    fn write(fd: u32, offset: u64, data: &[u8]) -> JournalEntry<'static> {
+        JournalEntry::FileDescriptorWriteV1 {
+            fd,
+            offset,
+            data: data.to_vec().into(),
+            is_64bit: false,
+        }
+    }
+
+    fn memory(offset: u64, data: &[u8]) -> JournalEntry<'static> {
+        JournalEntry::UpdateMemoryRegionV1 {
+            region: offset..offset + data.len() as u64,
+            compressed_data: lz4_flex::compress_prepend_size(data).into(),
+        }
+    }
+
+    #[test]
+    fn test_diff_files_and_memory() {
+        let common = vec![
+            open(10, "a.txt"),
+            write(10, 0, b"hello"),
+            open(11, "/tmp/../b.txt"),
+            memory(0, &[1u8; 100]),
+        ];
+        let a = state(common.clone());
+        let mut more = common;
+        more.extend([
+            write(10, 5, b" world"),
+            JournalEntry::UnlinkFileV1 {
+                fd: 3,
+                path: "/b.txt".into(),
+            },
+            open(12, "c.txt"),
+            memory(8192, &[2u8; 5000]),
+            memory(30000, &[3u8; 1]),
+        ]);
+        let b = state(more);
+
+        assert_eq!(a.diff(&a), vec![]);
+        assert_eq!(
+            a.diff(&b),
+            vec![
+                JournalStateChange::FileChanged {
+                    path: "/a.txt".to_string(),
+                    old: "5 bytes".to_string(),
+                    new: "11 bytes".to_string(),
+                },
+                JournalStateChange::FileRemoved {
+                    path: "/b.txt".to_string(),
+                },
+                JournalStateChange::FileAdded {
+                    path: "/c.txt".to_string(),
+                    file: "0 bytes".to_string(),
+                },
+                JournalStateChange::MemoryChanged {
+                    region: 8192..16384
+                },
+                JournalStateChange::MemoryChanged {
+                    region: 28672..32768
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_threads_and_sockets() {
+        let a = state(vec![
+            JournalEntry::SocketOpenV1 {
+                af: wasi::Addressfamily::Inet4,
+                ty: wasi::Socktype::Stream,
+                pt: wasi::SockProto::Tcp,
+                fd: 5,
+            },
+            JournalEntry::CloseThreadV1 {
+                id: 1,
+                exit_code: None,
+            },
+        ]);
+        let b = state(vec![
+            JournalEntry::SocketOpenV1 {
+                af: wasi::Addressfamily::Inet4,
+                ty: wasi::Socktype::Stream,
+                pt: wasi::SockProto::Tcp,
+                fd: 5,
+            },
+            JournalEntry::SocketListenV1 { fd: 5, backlog: 10 },
+        ]);
+        assert_eq!(
+            a.diff(&b),
+            vec![JournalStateChange::SocketChanged {
+                fd: 5,
+                old: "inet4/stream".to_string(),
+                new: "inet4/stream listening".to_string(),
+            }]
+        );
+        assert_eq!(b.records(), 2);
+    }
+}
@@ -635,6 +635,56 @@ pub fn test_record_snapshot() {
     });
 }
 
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_non_deterministic_call() {
+    run_test(JournalEntry::NonDeterministicCallV1 {
+        call: NonDeterministicCall::ClockTimeGet {
+            clock_id: wasi::Snapshot0Clockid::Monotonic,
+        },
+        errno: wasi::Errno::Success,
+        result: 1234567890u64.to_le_bytes().to_vec().into(),
+    });
+    run_test(JournalEntry::NonDeterministicCallV1 {
+        call: NonDeterministicCall::SockRecv { fd: 12 },
+        errno: wasi::Errno::Connreset,
+        result: Vec::new().into(),
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_encrypted() {
+    run_test(JournalEntry::EncryptedV1 {
+        key_id: "key-1".into(),
+        sequence: 7,
+        nonce: [3u8; 12],
+        ciphertext: vec![42u8; 99].into(),
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_memory_chunk() {
+    run_test(JournalEntry::MemoryChunkV1 {
+        hash: [9u8; 32],
+        compressed_data: vec![7u8; 321].into(),
+    });
+}
+
+#[tracing_test::traced_test]
+#[test]
+pub fn test_record_update_memory_chunked() {
+    run_test(JournalEntry::UpdateMemoryRegionChunkedV1 {
+        region: 4096..73728,
+        chunks: vec![[1u8; 32], [2u8; 32], [1u8; 32]].into(),
+    });
+    run_test(JournalEntry::UpdateMemoryRegionChunkedV1 {
+        region: 0..0,
+        chunks: Vec::new().into(),
+    });
+}
+
 #[tracing_test::traced_test]
 #[test]
 pub fn test_record_alignment() {
@@ -728,4 +778,14 @@ pub fn test_record_alignment() {
     assert_eq!(std::mem::align_of::<JournalEntrySocketSetOptTimeV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntrySocketShutdownV1>(), 8);
     assert_eq!(std::mem::align_of::<JournalEntrySnapshotV1>(), 8);
+    assert_eq!(
+        std::mem::align_of::<JournalEntryNonDeterministicCallV1>(),
+        8
+    );
+    assert_eq!(std::mem::align_of::<JournalEntryEncryptedV1>(), 8);
+    assert_eq!(std::mem::align_of::<JournalEntryMemoryChunkV1>(), 8);
+    assert_eq!(
+        std::mem::align_of::<JournalEntryUpdateMemoryRegionChunkedV1>(),
+        8
+    );
 }
@@ -5,9 +5,9 @@ use std::time::{Duration, SystemTime};
 use std::{borrow::Cow, ops::Range};
 use virtual_net::{IpCidr, StreamSecurity};
 use wasmer_wasix_types::wasi::{
-    Addressfamily, Advice, EpollCtl, EpollEventCtl, EventFdFlags, ExitCode, Fdflags, Fdflagsext,
-    FileDelta, Filesize, Fstflags, LookupFlags, Oflags, Rights, SiFlags, Snapshot0Clockid,
-    SockProto, Sockoption, Socktype, Timestamp, Tty, Whence,
+    Addressfamily, Advice, EpollCtl, EpollEventCtl, Errno, EventFdFlags, ExitCode, Fdflags,
+    Fdflagsext, FileDelta, Filesize, Fstflags, LookupFlags, Oflags, Rights, SiFlags,
+    Snapshot0Clockid, SockProto, Sockoption, Socktype, Timestamp, Tty, Whence,
 };
 use wasmer_wasix_types::wasix::{ThreadStartType, WasiMemoryLayout};
 
@@ -75,6 +75,18 @@ pub enum SocketOptTimeType {
     Linger,
 }
 
+/// Identifies a call whose result depends on the world outside of the
+/// WASM process, and hence must be recorded for a run to be replayed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NonDeterministicCall {
+    RandomGet,
+    ClockTimeGet { clock_id: Snapshot0Clockid },
+    FdRead { fd: Fd },
+    SockRecv { fd: Fd },
+    PollOneoff,
+}
+
 /// Represents a log entry in a snapshot log stream that represents the total
 /// state of a WASM process at a point in time.
 #[allow(clippy::large_enum_variant)]
@@ -393,6 +405,41 @@ pub enum JournalEntry<'a> {
         when: SystemTime,
         trigger: SnapshotTrigger,
     },
+    /// The result of a non-deterministic call, which is fed back to the
+    /// process when the journal is replayed
+    NonDeterministicCallV1 {
+        call: NonDeterministicCall,
+        errno: Errno,
+        #[debug(ignore)]
+        #[serde(with = "base64")]
+        result: Cow<'a, [u8]>,
+    },
+    /// Another entry which was encrypted by an [`EncryptedJournal`](crate::EncryptedJournal),
+    /// along with the identifier of the key that encrypted it
+    EncryptedV1 {
+        key_id: Cow<'a, str>,
+        sequence: u64,
+        nonce: [u8; 12],
+        #[debug(ignore)]
+        #[serde(with = "base64")]
+        ciphertext: Cow<'a, [u8]>,
+    },
+    /// A chunk of memory that is stored once and then referenced by
+    /// [`UpdateMemoryRegionChunkedV1`](JournalEntry::UpdateMemoryRegionChunkedV1)
+    /// entries, identified by the blake3 hash of its data and compressed
+    /// with zstd
+    MemoryChunkV1 {
+        hash: [u8; 32],
+        #[debug(ignore)]
+        #[serde(with = "base64")]
+        compressed_data: Cow<'a, [u8]>,
+    },
+    /// Updates a region of memory with the data of the chunks, in order
+    UpdateMemoryRegionChunkedV1 {
+        region: Range<u64>,
+        #[debug(ignore)]
+        chunks: Cow<'a, [[u8; 32]]>,
+    },
 }
 
 impl JournalEntry<'_> {
@@ -772,6 +819,39 @@ impl JournalEntry<'_> {
             }
             Self::SocketShutdownV1 { fd, how } => JournalEntry::SocketShutdownV1 { fd, how },
             Self::SnapshotV1 { when, trigger } => JournalEntry::SnapshotV1 { when, trigger },
+            Self::NonDeterministicCallV1 {
+                call,
+                errno,
+                result,
+            } => JournalEntry::NonDeterministicCallV1 {
+                call,
+                errno,
+                result: result.into_owned().into(),
+            },
+            Self::EncryptedV1 {
+                key_id,
+                sequence,
+                nonce,
+                ciphertext,
+            } => JournalEntry::EncryptedV1 {
+                key_id: key_id.into_owned().into(),
+                sequence,
+                nonce,
+                ciphertext: ciphertext.into_owned().into(),
+            },
+            Self::MemoryChunkV1 {
+                hash,
+                compressed_data,
+            } => JournalEntry::MemoryChunkV1 {
+                hash,
+                compressed_data: compressed_data.into_owned().into(),
+            },
+            Self::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                JournalEntry::UpdateMemoryRegionChunkedV1 {
+                    region,
+                    chunks: chunks.into_owned().into(),
+                }
+            }
         }
     }
 
@@ -856,6 +936,16 @@ impl JournalEntry<'_> {
             JournalEntry::SocketSetOptTimeV1 { .. } => base_size,
             JournalEntry::SocketShutdownV1 { .. } => base_size,
             JournalEntry::SnapshotV1 { .. } => base_size,
+            JournalEntry::NonDeterministicCallV1 { result, .. } => base_size + result.len(),
+            JournalEntry::EncryptedV1 {
+                key_id, ciphertext, ..
+            } => base_size + key_id.len() + ciphertext.len(),
+            JournalEntry::MemoryChunkV1 {
+                compressed_data, ..
+            } => base_size + compressed_data.len(),
+            JournalEntry::UpdateMemoryRegionChunkedV1 { chunks, .. } => {
+                base_size + std::mem::size_of_val(chunks.as_ref())
+            }
         }
     }
 }
@@ -0,0 +1,133 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use wasmer_wasix_types::wasi::Errno;
+
+use super::*;
+
+/// The recorded result of a non-deterministic call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCall {
+    pub call: NonDeterministicCall,
+    pub errno: Errno,
+    pub result: Vec<u8>,
+}
+
+/// Feeds the results of the non-deterministic calls recorded in a journal
+/// back to a process, so that a previous run can be reproduced exactly.
+///
+/// The calls must be made in the same order as they were recorded, as soon
+/// as the process makes a different call the run has diverged and all the
+/// following calls are performed for real.
+#[derive(Debug, Default)]
+pub struct NonDeterministicReplay {
+    state: Mutex<NonDeterministicReplayState>,
+}
+
+#[derive(Debug, Default)]
+struct NonDeterministicReplayState {
+    calls: VecDeque<RecordedCall>,
+    diverged: bool,
+}
+
+impl NonDeterministicReplay {
+    pub fn new(calls: impl IntoIterator<Item = RecordedCall>) -> Self {
+        Self {
+            state: Mutex::new(NonDeterministicReplayState {
+                calls: calls.into_iter().collect(),
+                diverged: false,
+            }),
+        }
+    }
+
+    /// Reads all the non-deterministic calls recorded in a journal
+    pub fn from_journal(journal: &DynReadableJournal) -> anyhow::Result<Self> {
+        let mut calls = Vec::new();
+        while let Some(record) = journal.read()? {
+            if let JournalEntry::NonDeterministicCallV1 {
+                call,
+                errno,
+                result,
+            } = record.into_inner()
+            {
+                calls.push(RecordedCall {
+                    call,
+                    errno,
+                    result: result.into_owned(),
+                });
+            }
+        }
+        Ok(Self::new(calls))
+    }
+
+    /// Returns the recorded result of `call`, or `None` if it is to be
+    /// performed for real.
+    pub fn next(&self, call: NonDeterministicCall) -> Option<RecordedCall> {
+        let mut state = self.state.lock().unwrap();
+        let next = state.calls.front()?;
+        if next.call != call {
+            tracing::warn!(
+                expected = ?next.call,
+                actual = ?call,
+                remaining = state.calls.len(),
+                "the process diverged from the recorded run, replay has stopped"
+            );
+            state.calls.clear();
+            state.diverged = true;
+            return None;
+        }
+        state.calls.pop_front()
+    }
+
+    /// Number of recorded calls that are still to be replayed
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().calls.len()
+    }
+
+    /// Returns true if the process made a call that was not recorded
+    pub fn has_diverged(&self) -> bool {
+        self.state.lock().unwrap().diverged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_get(result: &[u8]) -> RecordedCall {
+        RecordedCall {
+            call: NonDeterministicCall::RandomGet,
+            errno: Errno::Success,
+            result: result.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_replay_stops_on_divergence() {
+        let replay = NonDeterministicReplay::new([
+            random_get(&[1, 2, 3]),
+            RecordedCall {
+                call: NonDeterministicCall::FdRead { fd: 0 },
+                errno: Errno::Again,
+                result: Vec::new(),
+            },
+            random_get(&[4, 5, 6]),
+        ]);
+
+        assert_eq!(
+            replay.next(NonDeterministicCall::RandomGet),
+            Some(random_get(&[1, 2, 3]))
+        );
+        assert_eq!(
+            replay
+                .next(NonDeterministicCall::FdRead { fd: 0 })
+                .map(|c| c.errno),
+            Some(Errno::Again)
+        );
+        assert!(!replay.has_diverged());
+
+        assert_eq!(replay.next(NonDeterministicCall::PollOneoff), None);
+        assert!(replay.has_diverged());
+        assert_eq!(replay.remaining(), 0);
+        assert_eq!(replay.next(NonDeterministicCall::RandomGet), None);
+    }
+}
@@ -9,3 +9,27 @@ pub fn copy_journal<R: ReadableJournal, W: WritableJournal>(
     }
     Ok(())
 }
+
+/// Copies the records of a journal up to and including the record at index
+/// `last` (the first record being at index zero), or all of them if there
+/// is no `last` record. Returns the number of records that were copied.
+pub fn copy_journal_until<R: ReadableJournal, W: WritableJournal>(
+    from: &R,
+    to: &W,
+    last: Option<u64>,
+) -> anyhow::Result<u64> {
+    let mut copied = 0;
+    while last.is_none_or(|last| copied <= last) {
+        let Some(record) = from.read()? else {
+            break;
+        };
+        to.write(record.into_inner())?;
+        copied += 1;
+    }
+    if let Some(last) = last {
+        if copied <= last {
+            anyhow::bail!("the journal has {copied} records, there is no record {last}");
+        }
+    }
+    Ok(copied)
+}
@@ -0,0 +1,506 @@
+//! `call_depth` is a middleware for putting a limit on the depth of
+//! nested WebAssembly function calls. The WebAssembly instance
+//! execution is stopped with [`TrapCode::CallDepthExceeded`] when the
+//! limit is reached.
+//!
+//! Unlike the stack overflow detection of the runtime, which depends on
+//! the size of the native stack and on the size of the frames emitted by
+//! each compiler, the limit is counted in WebAssembly calls. It is thus
+//! reproducible across compilers and platforms.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{BlockType as WpTypeOrFuncType, Operator};
+use wasmer::{
+    AsStoreMut, ExportIndex, GlobalInit, GlobalType, Instance, LocalFunctionIndex, Mutability,
+    Type,
+    sys::{FunctionMiddleware, MiddlewareError, MiddlewareReaderState, ModuleMiddleware},
+};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
+
+/// The module-level call depth middleware.
+///
+/// Every function increments a depth counter on entry, and decrements
+/// it when it returns. A call which would make the depth exceed the
+/// configured limit traps with [`TrapCode::CallDepthExceeded`].
+///
+/// The counter is not restored when the execution traps, or when an
+/// exception unwinds through a function: use [`reset_call_depth`] before
+/// calling into an instance again in those cases.
+///
+/// # Panic
+///
+/// An instance of `CallDepth` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global index to store the depth. Attempts to use a `CallDepth`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::sys::CompilerConfig;
+/// use wasmer_middlewares::CallDepth;
+///
+/// fn create_call_depth_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Allow at most 1000 nested WebAssembly calls.
+///     let call_depth = Arc::new(CallDepth::new(1000));
+///
+///     compiler_config.push_middleware(call_depth);
+/// }
+/// ```
+pub struct CallDepth {
+    /// Maximum number of nested calls.
+    limit: u32,
+
+    /// The global indexes for the current depth and for the branch
+    /// condition scratch value.
+    global_indexes: Mutex<Option<(GlobalIndex, GlobalIndex)>>,
+}
+
+/// The function-level call depth middleware.
+pub struct FunctionCallDepth {
+    /// Maximum number of nested calls.
+    limit: u32,
+
+    /// The global index for the current depth.
+    global_index: GlobalIndex,
+
+    /// The global index holding the condition of a branch out of the
+    /// function body while the depth is adjusted.
+    scratch_index: GlobalIndex,
+
+    /// Whether the function entry check has been emitted.
+    entered: bool,
+
+    /// Nesting level of the blocks in the function body, the function
+    /// body itself included.
+    block_depth: usize,
+}
+
+impl CallDepth {
+    /// Creates a `CallDepth` middleware allowing at most `limit` nested calls.
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl fmt::Debug for CallDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallDepth")
+            .field("limit", &self.limit)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for CallDepth {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        let (global_index, scratch_index) = self.global_indexes.lock().unwrap().unwrap();
+        Box::new(FunctionCallDepth {
+            limit: self.limit,
+            global_index,
+            scratch_index,
+            entered: false,
+            block_depth: 1,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!(
+                "CallDepth::transform_module_info: Attempting to use a `CallDepth` middleware from multiple modules."
+            );
+        }
+
+        // Append a global for the current depth and initialize it.
+        let depth_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_call_depth".to_string(),
+            ExportIndex::Global(depth_global_index),
+        );
+
+        // Append a private global holding branch conditions.
+        let scratch_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        *global_indexes = Some((depth_global_index, scratch_global_index));
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FunctionCallDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionCallDepth")
+            .field("limit", &self.limit)
+            .field("global_index", &self.global_index)
+            .field("scratch_index", &self.scratch_index)
+            .field("block_depth", &self.block_depth)
+            .finish()
+    }
+}
+
+impl FunctionCallDepth {
+    /// Operators adding `delta` to the current depth.
+    fn add_depth<'a>(&self, delta: i32) -> [Operator<'a>; 4] {
+        let global_index = self.global_index.as_u32();
+        [
+            Operator::GlobalGet { global_index },
+            Operator::I32Const { value: delta },
+            Operator::I32Add,
+            Operator::GlobalSet { global_index },
+        ]
+    }
+
+    /// Whether a branch with `relative_depth` targets the function body,
+    /// i.e. returns from the function.
+    fn exits(&self, relative_depth: u32) -> bool {
+        relative_depth as usize + 1 == self.block_depth
+    }
+
+    /// Stores the `i32` condition on top of the stack in the scratch
+    /// global, and subtracts from the depth the `i32` value computed by
+    /// `taken`, which may read the scratch global. The condition is then
+    /// put back on the stack.
+    fn sub_depth_if<'a>(&self, state: &mut MiddlewareReaderState<'a>, taken: &[Operator<'a>]) {
+        let global_index = self.global_index.as_u32();
+        let scratch_index = self.scratch_index.as_u32();
+        state.extend(&[
+            Operator::GlobalSet {
+                global_index: scratch_index,
+            },
+            Operator::GlobalGet { global_index },
+        ]);
+        state.extend(taken);
+        state.extend(&[
+            Operator::I32Sub,
+            Operator::GlobalSet { global_index },
+            Operator::GlobalGet {
+                global_index: scratch_index,
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionCallDepth {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            // if unsigned(globals[depth_index]) >= self.limit { throw(); }
+            // globals[depth_index] += 1;
+            self.entered = true;
+            state.extend(&[
+                Operator::GlobalGet {
+                    global_index: self.global_index.as_u32(),
+                },
+                Operator::I32Const {
+                    value: self.limit as i32,
+                },
+                Operator::I32GeU,
+                Operator::If {
+                    blockty: WpTypeOrFuncType::Empty,
+                },
+            ]);
+            state.push_trap(TrapCode::CallDepthExceeded);
+            state.push_operator(Operator::End);
+            state.extend(&self.add_depth(1));
+        }
+
+        match operator {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. }
+            | Operator::TryTable { .. } => self.block_depth += 1,
+            Operator::End | Operator::Delegate { .. } => {
+                self.block_depth -= 1;
+                if self.block_depth == 0 {
+                    // The final `end` of the function body returns.
+                    state.extend(&self.add_depth(-1));
+                }
+            }
+            Operator::Br { relative_depth } if self.exits(relative_depth) => {
+                state.extend(&self.add_depth(-1));
+            }
+            Operator::BrIf { relative_depth } if self.exits(relative_depth) => {
+                // globals[depth_index] -= (condition != 0);
+                let scratch_index = self.scratch_index.as_u32();
+                self.sub_depth_if(
+                    state,
+                    &[
+                        Operator::GlobalGet {
+                            global_index: scratch_index,
+                        },
+                        Operator::I32Eqz,
+                        Operator::I32Eqz,
+                    ],
+                );
+            }
+            Operator::BrTable { ref targets } => {
+                // globals[depth_index] -= (the selected target exits);
+                let scratch_index = self.scratch_index.as_u32();
+                let mut taken = vec![Operator::I32Const { value: 0 }];
+                for (index, target) in targets.targets().enumerate() {
+                    let target =
+                        target.map_err(|e| MiddlewareError::new("call_depth", e.to_string()))?;
+                    if self.exits(target) {
+                        taken.extend([
+                            Operator::GlobalGet {
+                                global_index: scratch_index,
+                            },
+                            Operator::I32Const {
+                                value: index as i32,
+                            },
+                            Operator::I32Eq,
+                            Operator::I32Or,
+                        ]);
+                    }
+                }
+                if self.exits(targets.default()) {
+                    taken.extend([
+                        Operator::GlobalGet {
+                            global_index: scratch_index,
+                        },
+                        Operator::I32Const {
+                            value: targets.len() as i32,
+                        },
+                        Operator::I32GeU,
+                        Operator::I32Or,
+                    ]);
+                }
+                if taken.len() > 1 {
+                    self.sub_depth_if(state, &taken);
+                }
+            }
+            Operator::BrOnNull { relative_depth }
+            | Operator::BrOnNonNull { relative_depth }
+            | Operator::BrOnCast { relative_depth, .. }
+            | Operator::BrOnCastFail { relative_depth, .. }
+                if self.exits(relative_depth) =>
+            {
+                // The condition is a reference, which can't be kept aside.
+                return Err(MiddlewareError::new(
+                    "call_depth",
+                    "reference branches out of the function body are not supported",
+                ));
+            }
+            Operator::Return
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::ReturnCallRef { .. } => {
+                // The callee of a tail call takes the place of the caller.
+                state.extend(&self.add_depth(-1));
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the current call depth of an [`Instance`].
+///
+/// Outside of any call, the depth is 0, unless the last call trapped.
+///
+/// # Panic
+///
+/// The [`Instance`] must have been processed with the [`CallDepth`]
+/// middleware at compile time, otherwise this will panic.
+pub fn get_call_depth(ctx: &mut impl AsStoreMut, instance: &Instance) -> u32 {
+    let depth: i32 = instance
+        .exports
+        .get_global("wasmer_call_depth")
+        .expect("Can't get `wasmer_call_depth` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_call_depth` from Instance has wrong type");
+
+    depth as u32
+}
+
+/// Reset the call depth of an [`Instance`] to 0.
+///
+/// This must be called after a trap, before calling into the instance
+/// again, as the functions which were interrupted did not decrement
+/// the depth.
+///
+/// # Panic
+///
+/// The [`Instance`] must have been processed with the [`CallDepth`]
+/// middleware at compile time, otherwise this will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance, RuntimeError};
+/// use wasmer_middlewares::call_depth::reset_call_depth;
+///
+/// fn recover(store: &mut impl AsStoreMut, instance: &Instance, result: Result<(), RuntimeError>) {
+///     if result.is_err() {
+///         reset_call_depth(store, instance);
+///     }
+/// }
+/// ```
+pub fn reset_call_depth(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    instance
+        .exports
+        .get_global("wasmer_call_depth")
+        .expect("Can't get `wasmer_call_depth` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_call_depth` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::sys::EngineBuilder;
+    use wasmer::{
+        Module, Store, TypedFunction, imports,
+        sys::{CompilerConfig, Cranelift},
+        wat2wasm,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"(module
+            (func $rec (export "rec") (param $n i32) (result i32)
+                (if (result i32) (i32.eqz (local.get $n))
+                    (then (i32.const 0))
+                    (else
+                        (i32.add
+                            (call $rec (i32.sub (local.get $n) (i32.const 1)))
+                            (i32.const 1)))))
+            (func $early (export "early") (param $n i32) (result i32)
+                (block
+                    (br_if 0 (local.get $n))
+                    (return (i32.const 7)))
+                (call $rec (local.get $n)))
+            (func (export "br") (param $n i32) (result i32)
+                (local.get $n)
+                (br 0))
+            (func (export "br_if") (param $n i32) (result i32)
+                (block
+                    (br_if 1 (i32.const 1) (local.get $n))
+                    (drop))
+                (i32.const 2))
+            (func (export "br_table") (param $n i32) (result i32)
+                (block (result i32)
+                    (br_table 0 1 0 (i32.const 3) (local.get $n)))
+                (i32.const 2)
+                (i32.add)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn instantiate(limit: u32) -> (Store, Instance) {
+        let call_depth = Arc::new(CallDepth::new(limit));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(call_depth);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        (store, instance)
+    }
+
+    #[test]
+    fn depth_is_restored_on_return() {
+        let (mut store, instance) = instantiate(16);
+        assert_eq!(get_call_depth(&mut store, &instance), 0);
+
+        let rec: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("rec")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert_eq!(rec.call(&mut store, 15).unwrap(), 15);
+        assert_eq!(get_call_depth(&mut store, &instance), 0);
+
+        let early: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("early")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert_eq!(early.call(&mut store, 0).unwrap(), 7);
+        assert_eq!(early.call(&mut store, 3).unwrap(), 3);
+        assert_eq!(get_call_depth(&mut store, &instance), 0);
+    }
+
+    #[test]
+    fn trap_when_limit_is_exceeded() {
+        let (mut store, instance) = instantiate(16);
+        let rec: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("rec")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // 17 nested calls: `rec(16)` down to `rec(0)`.
+        let error = rec.call(&mut store, 16).unwrap_err();
+        assert_eq!(error.to_trap(), Some(TrapCode::CallDepthExceeded));
+        assert_eq!(get_call_depth(&mut store, &instance), 16);
+
+        reset_call_depth(&mut store, &instance);
+        assert_eq!(rec.call(&mut store, 15).unwrap(), 15);
+        assert_eq!(get_call_depth(&mut store, &instance), 0);
+    }
+
+    #[test]
+    fn depth_is_restored_on_branch_out_of_the_body() {
+        let (mut store, instance) = instantiate(4);
+        let get = |store: &Store, name: &str| -> TypedFunction<i32, i32> {
+            instance
+                .exports
+                .get_function(name)
+                .unwrap()
+                .typed(store)
+                .unwrap()
+        };
+        let br = get(&store, "br");
+        let br_if = get(&store, "br_if");
+        let br_table = get(&store, "br_table");
+
+        // More calls than the limit, which would trap if a branch to the
+        // function body label didn't decrement the depth.
+        for n in 0..8 {
+            assert_eq!(br.call(&mut store, n).unwrap(), n);
+            assert_eq!(br_if.call(&mut store, n % 2).unwrap(), 2 - n % 2);
+            assert_eq!(
+                br_table.call(&mut store, n % 3).unwrap(),
+                if n % 3 == 1 { 3 } else { 5 }
+            );
+            assert_eq!(get_call_depth(&mut store, &instance), 0);
+        }
+    }
+}
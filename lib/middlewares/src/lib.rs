@@ -1,8 +1,12 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod call_depth;
 pub mod metering;
+pub mod profiling;
 
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use call_depth::CallDepth;
 pub use metering::Metering;
+pub use profiling::Profiling;
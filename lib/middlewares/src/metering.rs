@@ -3,6 +3,11 @@
 //! operators executed. The WebAssembly instance execution is stopped
 //! when the limit is reached.
 //!
+//! In [resumable](Metering::resumable) mode, the execution is instead
+//! handed to the host when the limit is reached, which can refill the
+//! points and let the execution continue where it stopped. This allows
+//! long-running computations to be time-sliced.
+//!
 //! # Example
 //!
 //! [See the `metering` detailed and complete
@@ -13,11 +18,11 @@ use std::fmt;
 use std::sync::{Arc, Mutex};
 use wasmer::wasmparser::{BlockType as WpTypeOrFuncType, Operator};
 use wasmer::{
-    AsStoreMut, ExportIndex, GlobalInit, GlobalType, Instance, LocalFunctionIndex, Mutability,
-    Type,
+    AsStoreMut, ExportIndex, Function, FunctionType, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, Mutability, TableType, Type, Value,
     sys::{FunctionMiddleware, MiddlewareError, MiddlewareReaderState, ModuleMiddleware},
 };
-use wasmer_types::{GlobalIndex, ModuleInfo};
+use wasmer_types::{GlobalIndex, ModuleInfo, SignatureIndex, TableIndex};
 
 #[derive(Clone)]
 struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex);
@@ -38,6 +43,23 @@ impl MeteringGlobalIndexes {
     }
 }
 
+/// Indexes of the table holding the host function which is called when points
+/// are exhausted in resumable mode, and of the signature of that function.
+#[derive(Clone, Debug)]
+struct MeteringYieldIndexes(TableIndex, SignatureIndex);
+
+impl MeteringYieldIndexes {
+    /// The table index in the current module for the yield handler.
+    fn table(&self) -> TableIndex {
+        self.0
+    }
+
+    /// The signature index in the current module of the yield handler.
+    fn signature(&self) -> SignatureIndex {
+        self.1
+    }
+}
+
 impl fmt::Debug for MeteringGlobalIndexes {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MeteringGlobalIndexes")
@@ -88,8 +110,14 @@ pub struct Metering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
+    /// Whether exhausting the points hands the execution to the host instead of trapping.
+    resumable: bool,
+
     /// The global indexes for metering points.
     global_indexes: Mutex<Option<MeteringGlobalIndexes>>,
+
+    /// The indexes for the yield handler, in resumable mode.
+    yield_indexes: Mutex<Option<MeteringYieldIndexes>>,
 }
 
 /// The function-level metering middleware.
@@ -100,6 +128,9 @@ pub struct FunctionMetering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// The global indexes for metering points.
     global_indexes: MeteringGlobalIndexes,
 
+    /// The indexes for the yield handler, in resumable mode.
+    yield_indexes: Option<MeteringYieldIndexes>,
+
     /// Accumulated cost of the current basic block.
     accumulated_cost: u64,
 }
@@ -120,6 +151,9 @@ pub enum MeteringPoints {
     /// The execution was terminated because the metering points were
     /// exhausted.  You can recover from this state by setting the
     /// points via [`set_remaining_points`] and restart the execution.
+    ///
+    /// In resumable mode, this is also the state observed by the yield
+    /// handler, until it refills the points.
     Exhausted,
 }
 
@@ -133,9 +167,33 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> Metering<F> {
         Self {
             initial_limit,
             cost_function: Arc::new(cost_function),
+            resumable: false,
             global_indexes: Mutex::new(None),
+            yield_indexes: Mutex::new(None),
         }
     }
+
+    /// Makes the exhaustion of the metering points resumable.
+    ///
+    /// Instead of trapping right away, an instance running out of points
+    /// calls the host function installed with [`set_yield_handler`]. The
+    /// handler can refill the points with [`set_remaining_points`], in which
+    /// case the execution resumes where it stopped, or leave them exhausted,
+    /// in which case the instance traps as it would without this mode.
+    /// Instances without a yield handler always trap.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use wasmer::wasmparser::Operator;
+    /// use wasmer_middlewares::Metering;
+    ///
+    /// let metering = Metering::new(1_000, |_: &Operator| -> u64 { 1 }).resumable();
+    /// ```
+    pub fn resumable(mut self) -> Self {
+        self.resumable = true;
+        self
+    }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
@@ -143,7 +201,9 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
         f.debug_struct("Metering")
             .field("initial_limit", &self.initial_limit)
             .field("cost_function", &"<function>")
+            .field("resumable", &self.resumable)
             .field("global_indexes", &self.global_indexes)
+            .field("yield_indexes", &self.yield_indexes)
             .finish()
     }
 }
@@ -154,6 +214,7 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
         Box::new(FunctionMetering {
             cost_function: self.cost_function.clone(),
             global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+            yield_indexes: self.yield_indexes.lock().unwrap().clone(),
             accumulated_cost: 0,
         })
     }
@@ -201,6 +262,29 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
             points_exhausted_global_index,
         ));
 
+        if self.resumable {
+            // Append a table holding the yield handler, which is installed by the
+            // host after instantiation, and the signature of the handler.
+            let yield_table_index =
+                module_info
+                    .tables
+                    .push(TableType::new(Type::FuncRef, 1, Some(1)));
+
+            module_info.exports.insert(
+                "wasmer_metering_yield_handler".to_string(),
+                ExportIndex::Table(yield_table_index),
+            );
+
+            let yield_signature_index = module_info
+                .signatures
+                .push(FunctionType::new(vec![], vec![]));
+
+            *self.yield_indexes.lock().unwrap() = Some(MeteringYieldIndexes(
+                yield_table_index,
+                yield_signature_index,
+            ));
+        }
+
         Ok(())
     }
 }
@@ -246,10 +330,27 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionMetering<F> {
         f.debug_struct("FunctionMetering")
             .field("cost_function", &"<function>")
             .field("global_indexes", &self.global_indexes)
+            .field("yield_indexes", &self.yield_indexes)
             .finish()
     }
 }
 
+impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMetering<F> {
+    /// Operators checking whether the remaining points are lower than the
+    /// accumulated cost.
+    fn points_below_cost<'a>(&self) -> [Operator<'a>; 3] {
+        [
+            Operator::GlobalGet {
+                global_index: self.global_indexes.remaining_points().as_u32(),
+            },
+            Operator::I64Const {
+                value: self.accumulated_cost as i64,
+            },
+            Operator::I64LtU,
+        ]
+    }
+}
+
 impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMetering<F> {
     fn feed<'a>(
         &mut self,
@@ -263,15 +364,9 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
 
         // Finalize the cost of the previous basic block and perform necessary checks.
         if is_accounting(&operator) && self.accumulated_cost > 0 {
+            // if unsigned(globals[remaining_points_index]) < unsigned(self.accumulated_cost) { throw(); }
+            state.extend(&self.points_below_cost());
             state.extend(&[
-                // if unsigned(globals[remaining_points_index]) < unsigned(self.accumulated_cost) { throw(); }
-                Operator::GlobalGet {
-                    global_index: self.global_indexes.remaining_points().as_u32(),
-                },
-                Operator::I64Const {
-                    value: self.accumulated_cost as i64,
-                },
-                Operator::I64LtU,
                 Operator::If {
                     blockty: WpTypeOrFuncType::Empty,
                 },
@@ -279,7 +374,38 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
                 Operator::GlobalSet {
                     global_index: self.global_indexes.points_exhausted().as_u32(),
                 },
-                Operator::Unreachable,
+            ]);
+            if let Some(yield_indexes) = &self.yield_indexes {
+                // if tables[yield_table_index][0] != null { tables[yield_table_index][0](); }
+                // if unsigned(globals[remaining_points_index]) < unsigned(self.accumulated_cost) { throw(); }
+                let table_index = yield_indexes.table().as_u32();
+                state.extend(&[
+                    Operator::I32Const { value: 0 },
+                    Operator::TableGet { table: table_index },
+                    Operator::RefIsNull,
+                    Operator::I32Eqz,
+                    Operator::If {
+                        blockty: WpTypeOrFuncType::Empty,
+                    },
+                    Operator::I32Const { value: 0 },
+                    Operator::CallIndirect {
+                        type_index: yield_indexes.signature().as_u32(),
+                        table_index,
+                    },
+                    Operator::End,
+                ]);
+                state.extend(&self.points_below_cost());
+                state.extend(&[
+                    Operator::If {
+                        blockty: WpTypeOrFuncType::Empty,
+                    },
+                    Operator::Unreachable,
+                    Operator::End,
+                ]);
+            } else {
+                state.extend(&[Operator::Unreachable]);
+            }
+            state.extend(&[
                 Operator::End,
                 // globals[remaining_points_index] -= self.accumulated_cost;
                 Operator::GlobalGet {
@@ -391,6 +517,57 @@ pub fn set_remaining_points(ctx: &mut impl AsStoreMut, instance: &Instance, poin
         .expect("Can't set `wasmer_metering_points_exhausted` in Instance");
 }
 
+/// Install the host function to call when the metering points of an
+/// [`Instance`] are exhausted.
+///
+/// The handler takes no parameters and returns nothing. It is called
+/// while the points are [`MeteringPoints::Exhausted`], and the execution
+/// resumes if it refilled them via [`set_remaining_points`]. Otherwise,
+/// or if it returns an error, the execution is stopped.
+///
+/// # Panic
+///
+/// The given [`Instance`] must have been processed with a
+/// [resumable](Metering::resumable) [`Metering`] middleware at compile
+/// time, and the handler must have the right type, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Function, FunctionEnv, FunctionEnvMut, Instance};
+/// use wasmer_middlewares::metering::{set_remaining_points, set_yield_handler};
+///
+/// /// Give `instance` 1000 more points every time it runs out of them.
+/// fn time_slice(store: &mut impl AsStoreMut, instance: &Instance) {
+///     let env = FunctionEnv::new(store, instance.clone());
+///     let handler = Function::new_typed_with_env(
+///         store,
+///         &env,
+///         |mut env: FunctionEnvMut<Instance>| {
+///             let instance = env.data().clone();
+///             set_remaining_points(&mut env, &instance, 1000);
+///         },
+///     );
+///
+///     set_yield_handler(store, instance, &handler);
+/// }
+/// ```
+pub fn set_yield_handler(ctx: &mut impl AsStoreMut, instance: &Instance, handler: &Function) {
+    assert_eq!(
+        handler.ty(ctx),
+        FunctionType::new(vec![], vec![]),
+        "The yield handler must take no parameters and return nothing"
+    );
+
+    instance
+        .exports
+        .get_table("wasmer_metering_yield_handler")
+        .expect("Can't get `wasmer_metering_yield_handler` from Instance")
+        .set(ctx, 0, Value::FuncRef(Some(handler.clone())))
+        .expect("Can't set `wasmer_metering_yield_handler` in Instance");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -398,7 +575,7 @@ mod tests {
     use std::sync::Arc;
     use wasmer::sys::EngineBuilder;
     use wasmer::{
-        Module, Store, TypedFunction, imports,
+        FunctionEnv, FunctionEnvMut, Module, Store, TypedFunction, imports,
         sys::{CompilerConfig, Cranelift},
         wat2wasm,
     };
@@ -619,4 +796,120 @@ mod tests {
             MeteringPoints::Exhausted
         );
     }
+
+    /// State of the yield handler in tests: the instance to refill, the
+    /// number of times the handler was called, and how many of those calls
+    /// refill the points.
+    struct YieldState {
+        instance: Option<Instance>,
+        calls: u32,
+        refills: u32,
+    }
+
+    fn yield_handler(mut env: FunctionEnvMut<YieldState>) {
+        let state = env.data_mut();
+        state.calls += 1;
+        if state.calls <= state.refills {
+            let instance = state.instance.clone().unwrap();
+            assert_eq!(
+                get_remaining_points(&mut env, &instance),
+                MeteringPoints::Exhausted
+            );
+            set_remaining_points(&mut env, &instance, 4);
+        }
+    }
+
+    fn resumable_instance(refills: u32) -> (Store, Instance, FunctionEnv<YieldState>) {
+        let metering = Arc::new(Metering::new(10, cost_function).resumable());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let env = FunctionEnv::new(
+            &mut store,
+            YieldState {
+                instance: Some(instance.clone()),
+                calls: 0,
+                refills,
+            },
+        );
+        let handler = Function::new_typed_with_env(&mut store, &env, yield_handler);
+        set_yield_handler(&mut store, &instance, &handler);
+        (store, instance, env)
+    }
+
+    #[test]
+    fn resumable_metering_yields_to_host() {
+        let (mut store, instance, env) = resumable_instance(u32::MAX);
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // The first two calls use the initial points, the next ones each
+        // run out of points and are resumed after the handler refills them.
+        for _ in 0..5 {
+            assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+        }
+        assert_eq!(env.as_ref(&store).calls, 3);
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(0)
+        );
+    }
+
+    #[test]
+    fn resumable_metering_traps_without_refill() {
+        let (mut store, instance, env) = resumable_instance(2);
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        for _ in 0..4 {
+            add_one.call(&mut store, 1).unwrap();
+        }
+
+        // The handler doesn't refill the points anymore.
+        assert!(add_one.call(&mut store, 1).is_err());
+        assert_eq!(env.as_ref(&store).calls, 3);
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Exhausted
+        );
+
+        // The instance can still be resumed by the host.
+        set_remaining_points(&mut store, &instance, 4);
+        add_one.call(&mut store, 1).unwrap();
+    }
+
+    #[test]
+    fn resumable_metering_traps_without_handler() {
+        let metering = Arc::new(Metering::new(10, cost_function).resumable());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        add_one.call(&mut store, 1).unwrap();
+        add_one.call(&mut store, 1).unwrap();
+        assert!(add_one.call(&mut store, 1).is_err());
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Exhausted
+        );
+    }
 }
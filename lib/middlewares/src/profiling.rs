@@ -0,0 +1,831 @@
+//! `profiling` is a middleware for attributing the cost of the
+//! executed operators to the functions that executed them, so that
+//! the functions dominating the cost of an execution can be found.
+//!
+//! The cost is measured in points by a cost function, like with the
+//! [`Metering`](crate::Metering) middleware. Optionally, the number
+//! of direct calls between each caller and callee is recorded too,
+//! which is used to attribute the points of each function to the
+//! call paths that lead to it when writing [folded
+//! stacks](ProfilingReport::write_folded).
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{BinaryReader, FunctionBody, Operator};
+use wasmer::{
+    AsStoreMut, ExportIndex, GlobalInit, GlobalType, Instance, LocalFunctionIndex, Mutability,
+    Type,
+    sys::{
+        FunctionBodyData, FunctionMiddleware, MiddlewareError, MiddlewareReaderState,
+        ModuleMiddleware,
+    },
+};
+use wasmer_types::{FunctionIndex, GlobalIndex, ModuleInfo, entity::PrimaryMap};
+
+use crate::metering::is_accounting;
+
+/// The name of the custom section recording the call edges of a profiled
+/// module, as pairs of little-endian caller and callee function indexes.
+/// The count of the calls of the edge at position `n` is exported as
+/// `wasmer_profiling_call_edge_{n}`.
+const CALL_EDGES_SECTION: &str = "wasmer_profiling_call_edges";
+
+/// The module-level state of the profiling middleware.
+#[derive(Debug)]
+struct ProfilingState {
+    /// The number of imported functions, which are not profiled.
+    num_imported_functions: usize,
+
+    /// The global indexes for the points and call counts of each local function.
+    functions: Vec<(GlobalIndex, GlobalIndex)>,
+
+    /// The global index for the call count of each recorded call edge.
+    call_edges: HashMap<(FunctionIndex, FunctionIndex), GlobalIndex>,
+}
+
+/// An error reading the profile accumulated by an [`Instance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfilingError {
+    /// The module of the instance has not been compiled with the
+    /// profiling middleware.
+    NotProfiled,
+    /// The call edges recorded in the module are malformed.
+    MalformedCallEdges,
+    /// A counter of the profile is not exported by the instance as an
+    /// `i64` global.
+    MissingCounter(String),
+}
+
+impl fmt::Display for ProfilingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotProfiled => write!(
+                f,
+                "the module has not been compiled with the profiling middleware"
+            ),
+            Self::MalformedCallEdges => {
+                write!(f, "the `{CALL_EDGES_SECTION}` section is malformed")
+            }
+            Self::MissingCounter(name) => write!(f, "the instance has no `{name}` counter"),
+        }
+    }
+}
+
+impl std::error::Error for ProfilingError {}
+
+/// The module-level profiling middleware.
+///
+/// # Panic
+///
+/// An instance of `Profiling` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global indexes to store the profile. Attempts to use a `Profiling`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::{wasmparser::Operator, sys::CompilerConfig};
+/// use wasmer_middlewares::Profiling;
+///
+/// type CostFunction = fn(&Operator) -> u64;
+///
+/// fn create_profiling_middleware(
+///     compiler_config: &mut dyn CompilerConfig,
+/// ) -> Arc<Profiling<CostFunction>> {
+///     // Let's define a dummy cost function,
+///     // which counts 1 for all operators.
+///     fn cost_function(_operator: &Operator) -> u64 { 1 }
+///
+///     // Let's create the profiling middleware, recording up to
+///     // 1024 distinct call edges.
+///     let profiling = Arc::new(
+///         Profiling::new(cost_function as CostFunction).with_call_edges(1024),
+///     );
+///
+///     // Finally, let's push the middleware, and keep it to read the
+///     // profile of the instances.
+///     compiler_config.push_middleware(profiling.clone());
+///     profiling
+/// }
+/// ```
+pub struct Profiling<F: Fn(&Operator) -> u64 + Send + Sync> {
+    /// Function that maps each operator to a cost in "points".
+    cost_function: Arc<F>,
+
+    /// Maximum number of distinct call edges recorded.
+    max_call_edges: usize,
+
+    /// The module-level profiling state.
+    state: Arc<Mutex<Option<ProfilingState>>>,
+}
+
+/// The function-level profiling middleware.
+pub struct FunctionProfiling<F: Fn(&Operator) -> u64 + Send + Sync> {
+    /// Function that maps each operator to a cost in "points".
+    cost_function: Arc<F>,
+
+    /// The global index for the points of the profiled function.
+    points_global: GlobalIndex,
+
+    /// The global index for the call count of the profiled function, until
+    /// the entry of the function is instrumented.
+    calls_global: Option<GlobalIndex>,
+
+    /// The global index for the call count of each recorded callee.
+    call_edges: HashMap<FunctionIndex, GlobalIndex>,
+
+    /// Accumulated cost of the current basic block.
+    accumulated_cost: u64,
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> Profiling<F> {
+    /// Creates a `Profiling` middleware, which doesn't record call edges.
+    pub fn new(cost_function: F) -> Self {
+        Self {
+            cost_function: Arc::new(cost_function),
+            max_call_edges: 0,
+            state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Records the number of direct calls between each caller and callee,
+    /// for up to `max_call_edges` distinct pairs of functions. Calls between
+    /// further pairs, and indirect calls, are not recorded.
+    ///
+    /// The pairs are taken in the order of the index of the caller, then
+    /// of the callee, so the same module always records the same pairs.
+    pub fn with_call_edges(mut self, max_call_edges: usize) -> Self {
+        self.max_call_edges = max_call_edges;
+        self
+    }
+
+    /// Reads the profile accumulated by an [`Instance`].
+    ///
+    /// The layout of the profile is read from the module of the
+    /// [`Instance`], which must have been compiled with this middleware.
+    pub fn report(
+        &self,
+        ctx: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> Result<ProfilingReport, ProfilingError> {
+        let call_edges = read_call_edges(instance)?;
+        let info = instance.module().info();
+
+        let functions = (0..info.functions.len() - info.num_imported_functions)
+            .map(|local_index| {
+                let index =
+                    FunctionIndex::from_u32((info.num_imported_functions + local_index) as u32);
+                Ok(FunctionProfile {
+                    index,
+                    name: info.function_names.get(&index).cloned(),
+                    points: get_counter(
+                        ctx,
+                        instance,
+                        &format!("wasmer_profiling_points_{local_index}"),
+                    )?,
+                    calls: get_counter(
+                        ctx,
+                        instance,
+                        &format!("wasmer_profiling_calls_{local_index}"),
+                    )?,
+                })
+            })
+            .collect::<Result<_, ProfilingError>>()?;
+
+        let call_edges = call_edges
+            .into_iter()
+            .enumerate()
+            .map(|(position, (caller, callee))| {
+                Ok(CallEdge {
+                    caller,
+                    callee,
+                    calls: get_counter(
+                        ctx,
+                        instance,
+                        &format!("wasmer_profiling_call_edge_{position}"),
+                    )?,
+                })
+            })
+            .collect::<Result<_, ProfilingError>>()?;
+
+        Ok(ProfilingReport {
+            functions,
+            call_edges,
+            function_names: info.function_names.clone(),
+        })
+    }
+
+    /// Resets the profile accumulated by an [`Instance`].
+    ///
+    /// The layout of the profile is read from the module of the
+    /// [`Instance`], which must have been compiled with this middleware.
+    pub fn reset(
+        &self,
+        ctx: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> Result<(), ProfilingError> {
+        let num_call_edges = read_call_edges(instance)?.len();
+        let info = instance.module().info();
+
+        let names = (0..info.functions.len() - info.num_imported_functions)
+            .flat_map(|local_index| {
+                [
+                    format!("wasmer_profiling_points_{local_index}"),
+                    format!("wasmer_profiling_calls_{local_index}"),
+                ]
+            })
+            .chain(
+                (0..num_call_edges)
+                    .map(|position| format!("wasmer_profiling_call_edge_{position}")),
+            );
+        for name in names {
+            instance
+                .exports
+                .get_global(&name)
+                .ok()
+                .and_then(|global| global.set(ctx, 0i64.into()).ok())
+                .ok_or(ProfilingError::MissingCounter(name))?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Profiling<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profiling")
+            .field("cost_function", &"<function>")
+            .field("max_call_edges", &self.max_call_edges)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Profiling<F> {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let state = self.state.lock().unwrap();
+        let state = state.as_ref().unwrap();
+        let (points_global, calls_global) = state.functions[local_function_index.as_u32() as usize];
+        let function_index = FunctionIndex::from_u32(
+            state.num_imported_functions as u32 + local_function_index.as_u32(),
+        );
+
+        Box::new(FunctionProfiling {
+            cost_function: self.cost_function.clone(),
+            points_global,
+            calls_global: Some(calls_global),
+            call_edges: state
+                .call_edges
+                .iter()
+                .filter(|((caller, _), _)| *caller == function_index)
+                .map(|((_, callee), global_index)| (*callee, *global_index))
+                .collect(),
+            accumulated_cost: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place, assigning the globals of
+    /// the call edges found in the function bodies. This is called before
+    /// application on functions begins.
+    fn transform_module(
+        &self,
+        module_info: &mut ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<(), MiddlewareError> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.is_some() {
+            panic!(
+                "Profiling::transform_module: Attempting to use a `Profiling` middleware from multiple modules."
+            );
+        }
+
+        let mut push_counter = |name: String| {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info
+                .exports
+                .insert(name, ExportIndex::Global(global_index));
+            global_index
+        };
+
+        // Append globals for the points and call count of each local function.
+        let num_local_functions = module_info.functions.len() - module_info.num_imported_functions;
+        let functions = (0..num_local_functions)
+            .map(|local_index| {
+                (
+                    push_counter(format!("wasmer_profiling_points_{local_index}")),
+                    push_counter(format!("wasmer_profiling_calls_{local_index}")),
+                )
+            })
+            .collect();
+
+        // Append globals for the first call edges, ordered by caller and
+        // callee, and record them in the module to read them back.
+        let mut call_edges = Vec::new();
+        if self.max_call_edges > 0 {
+            call_edges = find_call_edges(module_info.num_imported_functions, function_bodies)?
+                .into_iter()
+                .take(self.max_call_edges)
+                .collect();
+        }
+        let call_edges = call_edges
+            .into_iter()
+            .enumerate()
+            .map(|(position, edge)| {
+                (
+                    edge,
+                    push_counter(format!("wasmer_profiling_call_edge_{position}")),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let section = call_edges
+            .iter()
+            .flat_map(|((caller, callee), _)| {
+                [caller.as_u32().to_le_bytes(), callee.as_u32().to_le_bytes()]
+            })
+            .flatten()
+            .collect::<Vec<u8>>();
+        let section_index = module_info
+            .custom_sections_data
+            .push(section.into_boxed_slice());
+        module_info
+            .custom_sections
+            .insert(CALL_EDGES_SECTION.to_string(), section_index);
+
+        *state = Some(ProfilingState {
+            num_imported_functions: module_info.num_imported_functions,
+            functions,
+            call_edges: call_edges.into_iter().collect(),
+        });
+
+        Ok(())
+    }
+}
+
+/// The distinct pairs of caller and callee of the direct calls in the
+/// bodies of the local functions, in order.
+fn find_call_edges(
+    num_imported_functions: usize,
+    function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+) -> Result<BTreeSet<(FunctionIndex, FunctionIndex)>, MiddlewareError> {
+    let error =
+        |e: wasmer::wasmparser::BinaryReaderError| MiddlewareError::new("profiling", e.to_string());
+
+    let mut call_edges = BTreeSet::new();
+    for (local_index, body) in function_bodies.iter() {
+        let caller = FunctionIndex::from_u32(num_imported_functions as u32 + local_index.as_u32());
+        let mut reader = FunctionBody::new(BinaryReader::new(body.data, body.module_offset))
+            .get_operators_reader()
+            .map_err(error)?;
+        while !reader.eof() {
+            if let Operator::Call { function_index } | Operator::ReturnCall { function_index } =
+                reader.read().map_err(error)?
+            {
+                call_edges.insert((caller, FunctionIndex::from_u32(function_index)));
+            }
+        }
+    }
+    Ok(call_edges)
+}
+
+/// Reads the call edges recorded in the module of an [`Instance`].
+fn read_call_edges(
+    instance: &Instance,
+) -> Result<Vec<(FunctionIndex, FunctionIndex)>, ProfilingError> {
+    let section = instance
+        .module()
+        .custom_sections(CALL_EDGES_SECTION)
+        .next()
+        .ok_or(ProfilingError::NotProfiled)?;
+    if section.len() % 8 != 0 {
+        return Err(ProfilingError::MalformedCallEdges);
+    }
+    Ok(section
+        .chunks_exact(8)
+        .map(|edge| {
+            let index = |bytes: &[u8]| {
+                FunctionIndex::from_u32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            };
+            (index(&edge[..4]), index(&edge[4..]))
+        })
+        .collect())
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionProfiling<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionProfiling")
+            .field("cost_function", &"<function>")
+            .field("points_global", &self.points_global)
+            .field("calls_global", &self.calls_global)
+            .field("call_edges", &self.call_edges)
+            .finish()
+    }
+}
+
+/// Operators incrementing the counter in the given global by `value`.
+fn increment<'a>(global_index: GlobalIndex, value: u64) -> [Operator<'a>; 4] {
+    [
+        Operator::GlobalGet {
+            global_index: global_index.as_u32(),
+        },
+        Operator::I64Const {
+            value: value as i64,
+        },
+        Operator::I64Add,
+        Operator::GlobalSet {
+            global_index: global_index.as_u32(),
+        },
+    ]
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionProfiling<F> {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // Count the call to the function on its entry.
+        if let Some(calls_global) = self.calls_global.take() {
+            state.extend(&increment(calls_global, 1));
+        }
+
+        // Get the cost of the current operator, and add it to the accumulator,
+        // like the `Metering` middleware does.
+        self.accumulated_cost += (self.cost_function)(&operator);
+
+        // Attribute the cost of the previous basic block to the function.
+        if is_accounting(&operator) && self.accumulated_cost > 0 {
+            state.extend(&increment(self.points_global, self.accumulated_cost));
+            self.accumulated_cost = 0;
+        }
+
+        // Count the direct calls from the function to the callee.
+        if let Operator::Call { function_index } | Operator::ReturnCall { function_index } =
+            operator
+        {
+            let callee = FunctionIndex::from_u32(function_index);
+            if let Some(call_edge_global) = self.call_edges.get(&callee) {
+                state.extend(&increment(*call_edge_global, 1));
+            }
+        }
+
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Reads the counter exported under the given name.
+fn get_counter(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    name: &str,
+) -> Result<u64, ProfilingError> {
+    let value: i64 = instance
+        .exports
+        .get_global(name)
+        .ok()
+        .and_then(|global| global.get(ctx).try_into().ok())
+        .ok_or_else(|| ProfilingError::MissingCounter(name.to_string()))?;
+    Ok(value as u64)
+}
+
+/// The profile of a local function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// The index of the function.
+    pub index: FunctionIndex,
+    /// The name of the function, if the module has a name section.
+    pub name: Option<String>,
+    /// The points consumed by the function itself, excluding its callees.
+    pub points: u64,
+    /// The number of calls to the function.
+    pub calls: u64,
+}
+
+/// The number of direct calls from a caller to a callee.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallEdge {
+    /// The index of the calling function.
+    pub caller: FunctionIndex,
+    /// The index of the called function.
+    pub callee: FunctionIndex,
+    /// The number of calls.
+    pub calls: u64,
+}
+
+/// The profile accumulated by an [`Instance`], as read by
+/// [`Profiling::report`].
+#[derive(Debug, Clone)]
+pub struct ProfilingReport {
+    /// The profile of each local function.
+    pub functions: Vec<FunctionProfile>,
+    /// The recorded call edges.
+    pub call_edges: Vec<CallEdge>,
+    /// The names of the functions, from the name section.
+    function_names: HashMap<FunctionIndex, String>,
+}
+
+impl ProfilingReport {
+    /// Writes the profile in the folded stacks format, with one line per
+    /// call path giving the points consumed by its last function, which
+    /// can be rendered by flamegraph tools.
+    ///
+    /// The points of a function are attributed to its callers in proportion
+    /// of the number of calls they made to it, as the call paths themselves
+    /// are not recorded. The points of calls which are not recorded, like
+    /// calls from the host or indirect calls, are attributed to call paths
+    /// starting at the function.
+    pub fn write_folded(&self, mut out: impl Write) -> io::Result<()> {
+        let mut callers: HashMap<FunctionIndex, Vec<&CallEdge>> = HashMap::new();
+        for edge in &self.call_edges {
+            callers.entry(edge.callee).or_default().push(edge);
+        }
+        let calls: HashMap<FunctionIndex, u64> =
+            self.functions.iter().map(|f| (f.index, f.calls)).collect();
+
+        let mut stacks: Vec<(String, u64)> = Vec::new();
+        for function in &self.functions {
+            if function.points == 0 {
+                continue;
+            }
+            let mut path = vec![function.index];
+            self.fold(
+                &callers,
+                &calls,
+                &mut path,
+                function.points as f64,
+                &mut stacks,
+            );
+        }
+
+        // Merge the identical stacks, and sort them for a stable output.
+        let mut merged: HashMap<String, u64> = HashMap::new();
+        for (stack, points) in stacks {
+            *merged.entry(stack).or_default() += points;
+        }
+        let mut merged: Vec<_> = merged.into_iter().collect();
+        merged.sort();
+        for (stack, points) in merged {
+            writeln!(out, "{stack} {points}")?;
+        }
+        Ok(())
+    }
+
+    /// Attributes `points` of the call path `path`, given from callee to
+    /// caller, to the paths extending it with the callers of its first
+    /// function.
+    fn fold(
+        &self,
+        callers: &HashMap<FunctionIndex, Vec<&CallEdge>>,
+        calls: &HashMap<FunctionIndex, u64>,
+        path: &mut Vec<FunctionIndex>,
+        points: f64,
+        stacks: &mut Vec<(String, u64)>,
+    ) {
+        /// The maximum length of a folded call path.
+        const MAX_DEPTH: usize = 64;
+
+        let function = *path.last().unwrap();
+        let total_calls = calls.get(&function).copied().unwrap_or(0) as f64;
+        let mut remaining = points;
+        if total_calls > 0.0 && path.len() < MAX_DEPTH {
+            let on_path: HashSet<_> = path.iter().copied().collect();
+            for edge in callers.get(&function).into_iter().flatten() {
+                // Recursive calls are attributed to the outermost call.
+                if edge.calls == 0 || on_path.contains(&edge.caller) {
+                    continue;
+                }
+                let share = points * (edge.calls as f64 / total_calls).min(1.0);
+                if share >= 1.0 {
+                    path.push(edge.caller);
+                    self.fold(callers, calls, path, share, stacks);
+                    path.pop();
+                    remaining -= share;
+                }
+            }
+        }
+
+        let remaining = remaining.round();
+        if remaining >= 1.0 {
+            let stack = path
+                .iter()
+                .rev()
+                .map(|index| self.frame_name(*index))
+                .collect::<Vec<_>>()
+                .join(";");
+            stacks.push((stack, remaining as u64));
+        }
+    }
+
+    /// The name of a function in a folded stack.
+    fn frame_name(&self, index: FunctionIndex) -> String {
+        match self.function_names.get(&index) {
+            Some(name) => name.replace([';', ' ', '\n'], "_"),
+            None => format!("func{}", index.as_u32()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::sys::EngineBuilder;
+    use wasmer::{
+        Module, Store, TypedFunction, imports,
+        sys::{CompilerConfig, Cranelift},
+        wat2wasm,
+    };
+
+    type CostFunction = fn(&Operator) -> u64;
+
+    fn cost_function(operator: &Operator) -> u64 {
+        match operator {
+            Operator::LocalGet { .. } | Operator::I32Const { .. } => 1,
+            Operator::I32Add { .. } => 2,
+            _ => 0,
+        }
+    }
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"(module
+            (func $work (param $value i32) (result i32)
+                local.get $value
+                i32.const 1
+                i32.add)
+            (func $leaf (result i32)
+                i32.const 40
+                i32.const 2
+                i32.add)
+            (func $main (result i32)
+                i32.const 1
+                call $work
+                call $work
+                call $leaf
+                i32.add)
+            (export "main" (func $main))
+        )"#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn instantiate(
+        profiling: Profiling<CostFunction>,
+    ) -> (Store, Instance, Arc<Profiling<CostFunction>>) {
+        let profiling = Arc::new(profiling);
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(profiling.clone());
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        (store, instance, profiling)
+    }
+
+    fn call_main(store: &mut Store, instance: &Instance) {
+        let main: TypedFunction<(), i32> = instance
+            .exports
+            .get_function("main")
+            .unwrap()
+            .typed(store)
+            .unwrap();
+        assert_eq!(main.call(store).unwrap(), 45);
+    }
+
+    #[test]
+    fn points_are_attributed_to_functions() {
+        let (mut store, instance, profiling) =
+            instantiate(Profiling::new(cost_function as CostFunction));
+        call_main(&mut store, &instance);
+
+        let report = profiling.report(&mut store, &instance).unwrap();
+        let profile: Vec<_> = report
+            .functions
+            .iter()
+            .map(|f| (f.name.as_deref().unwrap(), f.points, f.calls))
+            .collect();
+        assert_eq!(
+            profile,
+            vec![("work", 8, 2), ("leaf", 4, 1), ("main", 3, 1)]
+        );
+        assert!(report.call_edges.is_empty());
+
+        // The profile accumulates over calls, until it is reset.
+        call_main(&mut store, &instance);
+        let report = profiling.report(&mut store, &instance).unwrap();
+        assert_eq!(report.functions[0].points, 16);
+
+        profiling.reset(&mut store, &instance).unwrap();
+        let report = profiling.report(&mut store, &instance).unwrap();
+        assert!(
+            report
+                .functions
+                .iter()
+                .all(|f| f.points == 0 && f.calls == 0)
+        );
+    }
+
+    #[test]
+    fn call_edges_are_recorded() {
+        let (mut store, instance, profiling) =
+            instantiate(Profiling::new(cost_function as CostFunction).with_call_edges(8));
+        call_main(&mut store, &instance);
+
+        let report = profiling.report(&mut store, &instance).unwrap();
+        let edges: Vec<_> = report
+            .call_edges
+            .iter()
+            .map(|e| (e.caller.as_u32(), e.callee.as_u32(), e.calls))
+            .collect();
+        assert_eq!(edges, vec![(2, 0, 2), (2, 1, 1)]);
+
+        let mut folded = Vec::new();
+        report.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main 3\nmain;leaf 4\nmain;work 8\n"
+        );
+    }
+
+    #[test]
+    fn call_edges_are_limited() {
+        let (mut store, instance, profiling) =
+            instantiate(Profiling::new(cost_function as CostFunction).with_call_edges(1));
+        call_main(&mut store, &instance);
+
+        // The first call edge, by caller and then callee, is recorded.
+        let report = profiling.report(&mut store, &instance).unwrap();
+        let edges: Vec<_> = report
+            .call_edges
+            .iter()
+            .map(|e| (e.caller.as_u32(), e.callee.as_u32(), e.calls))
+            .collect();
+        assert_eq!(edges, vec![(2, 0, 2)]);
+
+        // The points of the callee whose calls are not recorded are
+        // attributed to a call path starting at it.
+        let mut folded = Vec::new();
+        report.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "leaf 4\nmain 3\nmain;work 8\n"
+        );
+    }
+
+    #[test]
+    fn call_edges_are_stored_in_the_module() {
+        let (mut store, instance, _profiling) =
+            instantiate(Profiling::new(cost_function as CostFunction).with_call_edges(8));
+
+        // The profile of an instance of the deserialized module can be read
+        // without the middleware state.
+        let bytes = instance.module().serialize().unwrap();
+        let module = unsafe { Module::deserialize(&store, bytes) }.unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        call_main(&mut store, &instance);
+
+        let report = Profiling::new(cost_function as CostFunction)
+            .report(&mut store, &instance)
+            .unwrap();
+        let edges: Vec<_> = report
+            .call_edges
+            .iter()
+            .map(|e| (e.caller.as_u32(), e.callee.as_u32(), e.calls))
+            .collect();
+        assert_eq!(edges, vec![(2, 0, 2), (2, 1, 1)]);
+    }
+
+    #[test]
+    fn report_fails_without_profiling() {
+        let mut store = Store::default();
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let profiling = Profiling::new(cost_function as CostFunction);
+        assert_eq!(
+            profiling.report(&mut store, &instance).unwrap_err(),
+            ProfilingError::NotProfiled
+        );
+        assert_eq!(
+            profiling.reset(&mut store, &instance).unwrap_err(),
+            ProfilingError::NotProfiled
+        );
+    }
+}
@@ -101,6 +101,25 @@ pub enum MemoryError {
     Generic(String),
 }
 
+/// An error while taking or restoring a snapshot of an instance.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// The snapshot cannot be restored into this instance, either because it
+    /// was taken from another module or because the instance has grown past it.
+    #[error("incompatible snapshot: {0}")]
+    Incompatible(String),
+    /// A reference that is neither null nor a function of the module.
+    #[error("cannot snapshot {0}, only null references and functions of the module are supported")]
+    UnsupportedReference(String),
+    /// A memory could not be grown to the size of the snapshot.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+    /// The backend does not support snapshots.
+    #[error("instance snapshots are not supported by this backend")]
+    Unsupported,
+}
+
 /// An ImportError.
 ///
 /// Note: this error is not standard to WebAssembly, but it's
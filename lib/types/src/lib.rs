@@ -59,6 +59,7 @@ mod memory;
 mod module;
 mod module_hash;
 mod serialize;
+mod snapshot;
 mod stack;
 mod store_id;
 mod table;
@@ -72,7 +73,8 @@ mod vmoffsets;
 
 pub use error::{
     CompileError, DeserializeError, ImportError, MemoryError, MiddlewareError,
-    ParseCpuFeatureError, PreInstantiationError, SerializeError, WasmError, WasmResult,
+    ParseCpuFeatureError, PreInstantiationError, SerializeError, SnapshotError, WasmError,
+    WasmResult,
 };
 
 /// The entity module, with common helpers for Rust structures
@@ -88,7 +90,9 @@ pub use crate::initializers::{
     DataInitializerLike, DataInitializerLocation, DataInitializerLocationLike,
     OwnedDataInitializer, TableInitializer,
 };
-pub use crate::memory::{Memory32, Memory64, MemorySize};
+pub use crate::memory::{
+    DirtyPageTracking, DirtyRanges, Memory32, Memory64, MemoryEpoch, MemorySize,
+};
 pub use crate::module::{ExportsIterator, ImportKey, ImportsIterator, ModuleInfo};
 pub use crate::module_hash::{HashAlgorithm, ModuleHash};
 pub use crate::types::{
@@ -104,6 +108,7 @@ pub use crate::libcalls::LibCall;
 pub use crate::memory::MemoryStyle;
 pub use crate::table::TableStyle;
 pub use serialize::MetadataHeader;
+pub use snapshot::{InstanceSnapshot, SnapshotValue};
 // TODO: OnCalledAction is needed for asyncify. It will be refactored with https://github.com/wasmerio/wasmer/issues/3451
 pub use crate::stack::{FrameInfo, SourceLoc, TrapInformation};
 pub use crate::store_id::StoreId;
@@ -46,6 +46,41 @@ impl MemoryStyle {
     }
 }
 
+/// How the pages of a memory that are written to are tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirtyPageTracking {
+    /// The pages are write-protected and the operating system records
+    /// which of them were written to (`userfaultfd` on Linux).
+    WriteProtect,
+    /// Every page is hashed and compared against the hash it had when the
+    /// memory was last checked.
+    Hashing,
+}
+
+/// A point in time of the dirty page tracking of a memory.
+///
+/// The epoch returned with a set of [`DirtyRanges`] is passed back to get
+/// the ranges that were written to after them. Several consumers can each
+/// keep their own epoch. Epochs that came from another memory count as
+/// [`MemoryEpoch::START`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryEpoch(pub u64);
+
+impl MemoryEpoch {
+    /// The epoch dirty page tracking was enabled at, every page of the
+    /// memory is dirty since then.
+    pub const START: Self = Self(0);
+}
+
+/// The parts of a memory that were written to since an epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirtyRanges {
+    /// Byte ranges that were written to, in order and without overlaps.
+    pub ranges: Vec<std::ops::Range<u64>>,
+    /// Epoch to pass to get the ranges written to after these ones.
+    pub epoch: MemoryEpoch,
+}
+
 /// Trait for the `Memory32` and `Memory64` marker types.
 ///
 /// This allows code to be generic over 32-bit and 64-bit memories.
@@ -0,0 +1,74 @@
+//! Snapshots of the state of an instance.
+
+use crate::error::{DeserializeError, SerializeError};
+use crate::{DataIndex, ElemIndex, FunctionIndex, ModuleHash};
+use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
+
+/// The mutable state of an instance, captured so that it can be restored
+/// into a fresh instance of the same module.
+///
+/// Only the state defined by the module itself is captured. Imported
+/// memories, tables and globals belong to the embedder, which has to save
+/// and restore them separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+pub struct InstanceSnapshot {
+    /// Hash of the module the snapshot was taken from, if it was hashed.
+    pub module_hash: Option<ModuleHash>,
+    /// Contents of the memories defined by the module, in order.
+    pub memories: Vec<Vec<u8>>,
+    /// Values of the globals defined by the module, in order. Immutable
+    /// globals are `None` as instantiation gives them their value.
+    pub globals: Vec<Option<SnapshotValue>>,
+    /// Elements of the tables defined by the module, in order.
+    pub tables: Vec<Vec<Option<FunctionIndex>>>,
+    /// Passive data segments that were dropped.
+    pub dropped_data: Vec<DataIndex>,
+    /// Passive element segments that were dropped.
+    pub dropped_elements: Vec<ElemIndex>,
+}
+
+/// The value of a global in an [`InstanceSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+pub enum SnapshotValue {
+    /// The raw bytes of a numeric or vector value.
+    Bytes([u8; 16]),
+    /// A null reference or a function of the module.
+    Ref(Option<FunctionIndex>),
+}
+
+impl InstanceSnapshot {
+    /// Serialize the snapshot into bytes.
+    pub fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        rkyv::to_bytes::<rkyv::rancor::Error>(self)
+            .map(|v| v.into_vec())
+            .map_err(|e| SerializeError::Generic(e.to_string()))
+    }
+
+    /// Deserialize a snapshot from bytes produced by [`Self::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let archived = rkyv::access::<ArchivedInstanceSnapshot, rkyv::rancor::Error>(bytes)
+            .map_err(|e| DeserializeError::CorruptedBinary(e.to_string()))?;
+        rkyv::deserialize::<_, rkyv::rancor::Error>(archived)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("{e:?}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let snapshot = InstanceSnapshot {
+            module_hash: Some(ModuleHash::xxhash_from_bytes([7; 8])),
+            memories: vec![vec![1, 2, 3], vec![]],
+            globals: vec![None, Some(SnapshotValue::Bytes([9; 16]))],
+            tables: vec![vec![None, Some(FunctionIndex::from_u32(2))]],
+            dropped_data: vec![DataIndex::from_u32(1)],
+            dropped_elements: vec![],
+        };
+        let bytes = snapshot.serialize().unwrap();
+        assert_eq!(InstanceSnapshot::deserialize(&bytes).unwrap(), snapshot);
+        assert!(InstanceSnapshot::deserialize(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
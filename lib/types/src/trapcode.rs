@@ -63,6 +63,9 @@ pub enum TrapCode {
 
     /// An exception was thrown but it was left uncaught.
     UncaughtException = 11,
+
+    /// The maximum call depth configured by a middleware was exceeded.
+    CallDepthExceeded = 12,
 }
 
 impl TrapCode {
@@ -81,6 +84,7 @@ impl TrapCode {
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unaligned atomic access",
             Self::UncaughtException => "uncaught exception",
+            Self::CallDepthExceeded => "call depth exceeded",
         }
     }
 }
@@ -100,6 +104,7 @@ impl Display for TrapCode {
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unalign_atom",
             Self::UncaughtException => "uncaught_exception",
+            Self::CallDepthExceeded => "call_depth",
         };
         f.write_str(identifier)
     }
@@ -121,6 +126,7 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(Self::BadConversionToInteger),
             "unreachable" => Ok(Self::UnreachableCodeReached),
             "unalign_atom" => Ok(Self::UnalignedAtomic),
+            "call_depth" => Ok(Self::CallDepthExceeded),
             _ => Err(()),
         }
     }
@@ -144,7 +150,7 @@ mod tests {
     use super::*;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 11] = [
+    const CODES: [TrapCode; 12] = [
         TrapCode::StackOverflow,
         TrapCode::HeapAccessOutOfBounds,
         TrapCode::HeapMisaligned,
@@ -156,6 +162,7 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::UnalignedAtomic,
+        TrapCode::CallDepthExceeded,
     ];
 
     #[test]
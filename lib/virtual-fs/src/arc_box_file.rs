@@ -123,6 +123,23 @@ impl VirtualFile for ArcBoxFile {
         let inner = self.inner.lock().unwrap();
         inner.get_special_fd()
     }
+    fn lock(&mut self, lock: crate::FileLock) -> crate::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.lock(lock)
+    }
+    fn conflicting_lock(&self, lock: &crate::FileLock) -> crate::Result<Option<crate::FileLock>> {
+        let inner = self.inner.lock().unwrap();
+        inner.conflicting_lock(lock)
+    }
+    fn poll_lock_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        lock: &crate::FileLock,
+    ) -> Poll<crate::Result<()>> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = Pin::new(inner.as_mut());
+        inner.poll_lock_ready(cx, lock)
+    }
     fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         let mut inner = self.inner.lock().unwrap();
         let inner = Pin::new(inner.as_mut());
@@ -50,6 +50,7 @@ impl FileSystem for EmptyFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                ..Default::default()
             })
         } else {
             Err(FsError::EntryNotFound)
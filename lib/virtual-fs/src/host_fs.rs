@@ -1,6 +1,6 @@
 use crate::{
-    DirEntry, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir, Result,
-    VirtualFile,
+    DirEntry, FileLock, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
+    Result, VirtualFile, Watcher,
 };
 use bytes::{Buf, Bytes};
 use futures::future::BoxFuture;
@@ -216,6 +216,49 @@ impl crate::FileSystem for FileSystem {
         fs::remove_file(path).map_err(Into::into)
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+            fs::Permissions::from_mode(mode & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let permissions = {
+            let mut permissions = fs::metadata(&path)?.permissions();
+            permissions.set_readonly(mode & 0o222 == 0);
+            permissions
+        };
+
+        fs::set_permissions(path, permissions).map_err(Into::into)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        #[cfg(unix)]
+        {
+            let path = self.prepare_path(path);
+            std::os::unix::fs::chown(path, uid, gid).map_err(Into::into)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (path, uid, gid);
+            Err(FsError::Unsupported)
+        }
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<Watcher> {
+        #[cfg(target_os = "linux")]
+        {
+            host_watch::watch(&self.root, &self.prepare_path(path), recursive)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (path, recursive);
+            Err(FsError::Unsupported)
+        }
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_> {
         OpenOptions::new(self)
     }
@@ -267,6 +310,30 @@ impl TryInto<Metadata> for std::fs::Metadata {
                 (false, false, false, false)
             }
         };
+        let (mode, uid, gid, ino, nlink, dev) = {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                (
+                    self.mode() & 0o7777,
+                    self.uid(),
+                    self.gid(),
+                    self.ino(),
+                    self.nlink(),
+                    self.dev(),
+                )
+            }
+            #[cfg(not(unix))]
+            {
+                let mode = match (filetype.is_dir(), self.permissions().readonly()) {
+                    (true, false) => 0o755,
+                    (true, true) => 0o555,
+                    (false, false) => 0o644,
+                    (false, true) => 0o444,
+                };
+                (mode, 0, 0, 0, 1, 0)
+            }
+        };
 
         Ok(Metadata {
             ft: FileType {
@@ -291,6 +358,12 @@ impl TryInto<Metadata> for std::fs::Metadata {
                 .and_then(|time| time.duration_since(UNIX_EPOCH).map_err(io::Error::other))
                 .map_or(0, |time| time.as_nanos() as u64),
             len: self.len(),
+            mode,
+            uid,
+            gid,
+            ino,
+            nlink,
+            dev,
         })
     }
 }
@@ -349,6 +422,14 @@ pub struct File {
     pub host_path: PathBuf,
     #[cfg(feature = "enable-serde")]
     flags: u16,
+    /// Waits for the conflicting host locks to be released.
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    lock_wait: Option<LockWait>,
+    /// The owners which took advisory locks through this file. The host
+    /// doesn't know about owners, so unlocking on behalf of any other
+    /// owner is a no-op rather than a release of their locks.
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    lock_owners: Vec<u64>,
 }
 
 #[cfg(feature = "enable-serde")]
@@ -395,6 +476,8 @@ impl<'de> Deserialize<'de> for File {
                     inner_std: inner,
                     host_path,
                     flags,
+                    lock_wait: None,
+                    lock_owners: Vec::new(),
                 })
             }
 
@@ -434,6 +517,8 @@ impl<'de> Deserialize<'de> for File {
                     inner_std: inner,
                     host_path,
                     flags,
+                    lock_wait: None,
+                    lock_owners: Vec::new(),
                 })
             }
         }
@@ -479,6 +564,8 @@ impl File {
             host_path,
             #[cfg(feature = "enable-serde")]
             flags: _flags,
+            lock_wait: None,
+            lock_owners: Vec::new(),
         }
     }
 
@@ -488,6 +575,307 @@ impl File {
     }
 }
 
+/// A background task polling for the host locks that conflict with a
+/// lock to be released.
+#[derive(Debug)]
+struct LockWait {
+    task: tokio::task::JoinHandle<io::Result<()>>,
+    /// Dropped together with the file, which stops the task.
+    _alive: Arc<()>,
+}
+
+/// Advisory locks are mapped to open file description locks on Linux,
+/// and to whole file `flock` locks on the other unixes. Both are owned by
+/// the host file rather than by the lock owner.
+#[cfg(unix)]
+mod host_lock {
+    use super::*;
+    use crate::LockKind;
+    use std::os::fd::AsRawFd;
+
+    #[cfg(target_os = "linux")]
+    fn to_flock(lock: &FileLock) -> libc::flock {
+        // SAFETY: `flock` is a plain C struct, all zeroes is a valid value.
+        let mut fl: libc::flock = unsafe { std::mem::zeroed() };
+        fl.l_type = match lock.kind {
+            LockKind::Shared => libc::F_RDLCK,
+            LockKind::Exclusive => libc::F_WRLCK,
+            LockKind::Unlock => libc::F_UNLCK,
+        } as _;
+        fl.l_whence = libc::SEEK_SET as _;
+        fl.l_start = lock.start as _;
+        fl.l_len = lock.len as _;
+        fl
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn set(file: &fs::File, lock: &FileLock) -> Result<()> {
+        let fl = to_flock(lock);
+        // SAFETY: the file descriptor is valid while `file` is borrowed.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &fl) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EAGAIN) | Some(libc::EACCES) => Err(FsError::WouldBlock),
+            _ => Err(err.into()),
+        }
+    }
+
+    /// Only whole file locks are supported, through `flock`.
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn set(file: &fs::File, lock: &FileLock) -> Result<()> {
+        if lock.start != 0 || lock.len != 0 {
+            return Err(FsError::Unsupported);
+        }
+        let op = match lock.kind {
+            LockKind::Shared => libc::LOCK_SH,
+            LockKind::Exclusive => libc::LOCK_EX,
+            LockKind::Unlock => libc::LOCK_UN,
+        };
+        // SAFETY: the file descriptor is valid while `file` is borrowed.
+        if unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EWOULDBLOCK) => Err(FsError::WouldBlock),
+            _ => Err(err.into()),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn get(file: &fs::File, lock: &FileLock) -> io::Result<Option<FileLock>> {
+        let mut fl = to_flock(lock);
+        // SAFETY: the file descriptor is valid while `file` is borrowed.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_GETLK, &mut fl) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let kind = match fl.l_type as i32 {
+            libc::F_RDLCK => LockKind::Shared,
+            libc::F_WRLCK => LockKind::Exclusive,
+            _ => return Ok(None),
+        };
+        Ok(Some(FileLock {
+            kind,
+            start: fl.l_start as u64,
+            len: fl.l_len as u64,
+            // The owner of a host lock is not known.
+            owner: 0,
+        }))
+    }
+
+    /// Whether `lock` may be taken on `file`.
+    #[cfg(target_os = "linux")]
+    pub(super) fn is_free(file: &fs::File, lock: &FileLock) -> io::Result<bool> {
+        Ok(get(file, lock)?.is_none())
+    }
+
+    /// Whether `lock` may be taken on `file`. `flock` can't test for a
+    /// conflicting lock without taking the lock, so it is taken here, on
+    /// the same open file description as the waiting handle, which then
+    /// takes it again as a no-op.
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn is_free(file: &fs::File, lock: &FileLock) -> io::Result<bool> {
+        match set(file, lock) {
+            Ok(()) => Ok(true),
+            Err(FsError::WouldBlock) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Watches are backed by inotify, with a thread per watcher that reads the
+/// host events and reports them with paths relative to the root of the
+/// file system. The thread stops once the watcher is dropped.
+#[cfg(target_os = "linux")]
+mod host_watch {
+    use super::*;
+    use crate::{WatchEvent, WatchEventKind, WatchRegistry};
+    use std::collections::HashMap;
+    use std::ffi::{CString, OsStr};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    const MASK: u32 = libc::IN_CREATE
+        | libc::IN_MODIFY
+        | libc::IN_DELETE
+        | libc::IN_DELETE_SELF
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO;
+
+    /// How often the thread checks whether the watcher was dropped.
+    const POLL_INTERVAL_MS: i32 = 200;
+
+    struct Inotify {
+        fd: OwnedFd,
+        root: PathBuf,
+        watched: PathBuf,
+        recursive: bool,
+        /// The host directories (or file) of the watch descriptors.
+        dirs: HashMap<i32, PathBuf>,
+    }
+
+    impl Inotify {
+        fn add(&mut self, path: &Path) -> io::Result<()> {
+            let c_path = CString::new(path.as_os_str().as_bytes())?;
+            // SAFETY: the file descriptor is owned and the path is a valid C string.
+            let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), MASK) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.dirs.insert(wd, path.to_path_buf());
+
+            if self.recursive && fs::symlink_metadata(path)?.is_dir() {
+                for entry in fs::read_dir(path)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        self.add(&entry.path())?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn virtual_path(&self, path: &Path) -> PathBuf {
+            Path::new("/").join(path.strip_prefix(&self.root).unwrap_or(path))
+        }
+
+        /// Keeps the watched directories up to date when one is renamed.
+        fn rename_dirs(&mut self, from: &Path, to: &Path) {
+            for dir in self.dirs.values_mut() {
+                if let Ok(rest) = dir.strip_prefix(from) {
+                    *dir = to.join(rest);
+                }
+            }
+        }
+    }
+
+    pub(super) fn watch(root: &Path, path: &Path, recursive: bool) -> Result<Watcher> {
+        // SAFETY: plain system call, the result is checked below.
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: the file descriptor was just created and is not owned elsewhere.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut inotify = Inotify {
+            fd,
+            root: root.to_path_buf(),
+            watched: path.to_path_buf(),
+            recursive,
+            dirs: HashMap::new(),
+        };
+        inotify.add(path)?;
+
+        let registry = WatchRegistry::new();
+        let watcher = registry.watch(&inotify.virtual_path(path), recursive);
+        std::thread::Builder::new()
+            .name("host-fs-watch".to_string())
+            .spawn(move || run(inotify, registry))?;
+        Ok(watcher)
+    }
+
+    fn run(mut inotify: Inotify, registry: WatchRegistry) {
+        let mut buf = vec![0u8; 64 * 1024];
+        while registry.is_watched() {
+            let mut pollfd = libc::pollfd {
+                fd: inotify.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `pollfd` is a valid array of one element.
+            if unsafe { libc::poll(&mut pollfd, 1, POLL_INTERVAL_MS) } <= 0 {
+                continue;
+            }
+            // SAFETY: the buffer is valid for writes of its whole length.
+            let read = unsafe {
+                libc::read(
+                    inotify.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if read <= 0 {
+                continue;
+            }
+            dispatch(&mut inotify, &registry, &buf[..read as usize]);
+        }
+    }
+
+    fn dispatch(inotify: &mut Inotify, registry: &WatchRegistry, mut buf: &[u8]) {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+        // Both halves of a rename are read together, paired by their cookie.
+        let mut moved_from: HashMap<u32, (PathBuf, bool)> = HashMap::new();
+        while buf.len() >= HEADER {
+            // SAFETY: the kernel writes whole events, and the header was
+            // checked to be in bounds.
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const _) };
+            let name_len = (event.len as usize).min(buf.len() - HEADER);
+            let name = &buf[HEADER..HEADER + name_len];
+            let name = name.split(|b| *b == 0).next().unwrap_or_default();
+            buf = &buf[HEADER + name_len..];
+
+            if event.mask & libc::IN_IGNORED != 0 {
+                inotify.dirs.remove(&event.wd);
+                continue;
+            }
+            let Some(dir) = inotify.dirs.get(&event.wd) else {
+                continue;
+            };
+            let host_path = if name.is_empty() {
+                dir.clone()
+            } else {
+                dir.join(OsStr::from_bytes(name))
+            };
+            let is_dir = event.mask & libc::IN_ISDIR != 0;
+            let path = inotify.virtual_path(&host_path);
+
+            if event.mask & libc::IN_CREATE != 0 {
+                if is_dir && inotify.recursive {
+                    inotify.add(&host_path).ok();
+                }
+                registry.notify(WatchEvent::new(WatchEventKind::Create, path, is_dir));
+            } else if event.mask & libc::IN_MODIFY != 0 {
+                registry.notify(WatchEvent::new(WatchEventKind::Modify, path, is_dir));
+            } else if event.mask & libc::IN_DELETE != 0 {
+                registry.notify(WatchEvent::new(WatchEventKind::Remove, path, is_dir));
+            } else if event.mask & libc::IN_DELETE_SELF != 0 {
+                // Other entries are reported by their parent directory.
+                if host_path == inotify.watched {
+                    registry.notify(WatchEvent::new(WatchEventKind::Remove, path, is_dir));
+                }
+            } else if event.mask & libc::IN_MOVED_FROM != 0 {
+                moved_from.insert(event.cookie, (host_path, is_dir));
+            } else if event.mask & libc::IN_MOVED_TO != 0 {
+                match moved_from.remove(&event.cookie) {
+                    Some((from, is_dir)) => {
+                        inotify.rename_dirs(&from, &host_path);
+                        let from = inotify.virtual_path(&from);
+                        registry.notify(WatchEvent::rename(from, path, is_dir));
+                    }
+                    // Moved in from outside of the watched directories.
+                    None => {
+                        if is_dir && inotify.recursive {
+                            inotify.add(&host_path).ok();
+                        }
+                        registry.notify(WatchEvent::new(WatchEventKind::Create, path, is_dir));
+                    }
+                }
+            }
+        }
+
+        // Moved out of the watched directories.
+        for (from, is_dir) in moved_from.into_values() {
+            let path = inotify.virtual_path(&from);
+            registry.notify(WatchEvent::new(WatchEventKind::Remove, path, is_dir));
+        }
+    }
+}
+
 //#[cfg_attr(feature = "enable-serde", typetag::serde)]
 #[async_trait::async_trait]
 impl VirtualFile for File {
@@ -542,6 +930,70 @@ impl VirtualFile for File {
         None
     }
 
+    #[cfg(unix)]
+    fn lock(&mut self, lock: FileLock) -> Result<()> {
+        use crate::LockKind;
+
+        if lock.kind == LockKind::Unlock && !self.lock_owners.contains(&lock.owner) {
+            return Ok(());
+        }
+        host_lock::set(&self.inner_std, &lock)?;
+        if lock.kind != LockKind::Unlock {
+            if !self.lock_owners.contains(&lock.owner) {
+                self.lock_owners.push(lock.owner);
+            }
+        } else if lock.start == 0 && lock.len == 0 {
+            self.lock_owners.retain(|owner| *owner != lock.owner);
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn conflicting_lock(&self, lock: &FileLock) -> Result<Option<FileLock>> {
+        host_lock::get(&self.inner_std, lock).map_err(Into::into)
+    }
+
+    #[cfg(unix)]
+    fn poll_lock_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        lock: &FileLock,
+    ) -> Poll<Result<()>> {
+        if self.lock_wait.is_none() {
+            if host_lock::is_free(&self.inner_std, lock)? {
+                return Poll::Ready(Ok(()));
+            }
+
+            // The host has no way to get notified when a lock is released,
+            // so poll for it from a blocking thread.
+            let file = self.inner_std.try_clone()?;
+            let lock = *lock;
+            let alive = Arc::new(());
+            let weak = Arc::downgrade(&alive);
+            let task = self.handle.spawn_blocking(move || {
+                while weak.strong_count() > 0 && !host_lock::is_free(&file, &lock)? {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Ok(())
+            });
+            self.lock_wait = Some(LockWait {
+                task,
+                _alive: alive,
+            });
+        }
+
+        let wait = self.lock_wait.as_mut().unwrap();
+        let res = match Pin::new(&mut wait.task).poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.lock_wait.take();
+        match res {
+            Ok(res) => Poll::Ready(res.map_err(Into::into)),
+            Err(_) => Poll::Ready(Err(FsError::UnknownError)),
+        }
+    }
+
     fn poll_read_ready(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         let cursor = match self.inner_std.stream_position() {
             Ok(a) => a,
@@ -1454,4 +1906,176 @@ mod tests {
             panic!("next: {s:?}");
         }
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_permissions_and_ownership() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("foo.txt"), b"").unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+
+        assert_eq!(fs.set_permissions(Path::new("/foo.txt"), 0o640), Ok(()));
+
+        let metadata = fs.metadata(Path::new("/foo.txt")).unwrap();
+        let host = std::fs::metadata(temp.path().join("foo.txt")).unwrap();
+        assert_eq!(metadata.mode(), 0o640);
+        assert_eq!(host.mode() & 0o7777, 0o640);
+        assert_eq!(metadata.ino(), host.ino());
+        assert_eq!(metadata.dev(), host.dev());
+        assert_eq!(metadata.nlink(), 1);
+        assert_eq!((metadata.uid(), metadata.gid()), (host.uid(), host.gid()));
+
+        // Changing to the current owner is always allowed
+        assert_eq!(
+            fs.chown(Path::new("/foo.txt"), Some(host.uid()), Some(host.gid())),
+            Ok(())
+        );
+        assert_eq!(
+            fs.set_permissions(Path::new("/missing.txt"), 0o640),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_advisory_locks_map_to_host_locks() {
+        use crate::{FileLock, LockKind};
+
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let open = || {
+            fs.new_open_options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(Path::new("/foo.txt"))
+                .unwrap()
+        };
+        let mut first = open();
+        let mut second = open();
+
+        let range = FileLock {
+            kind: LockKind::Exclusive,
+            start: 10,
+            len: 10,
+            owner: 1,
+        };
+        assert_eq!(first.lock(range), Ok(()));
+        let conflict = second
+            .conflicting_lock(&FileLock::whole_file(LockKind::Shared, 2))
+            .unwrap()
+            .expect("the lock is held on the host");
+        assert_eq!(
+            (conflict.kind, conflict.start, conflict.len),
+            (LockKind::Exclusive, 10, 10)
+        );
+        assert_eq!(
+            second.lock(FileLock::whole_file(LockKind::Shared, 2)),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            second.lock(FileLock {
+                start: 20,
+                ..FileLock::whole_file(LockKind::Exclusive, 2)
+            }),
+            Ok(())
+        );
+
+        // Waiting for a lock ends once the conflicting lock is released
+        let wanted = FileLock {
+            len: 20,
+            ..FileLock::whole_file(LockKind::Shared, 3)
+        };
+        let mut third = open();
+        assert_eq!(third.lock(wanted), Err(FsError::WouldBlock));
+        let unlock = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            first.lock(FileLock::whole_file(LockKind::Unlock, 1))
+        });
+        futures::future::poll_fn(|cx| {
+            std::pin::Pin::new(third.as_mut()).poll_lock_ready(cx, &wanted)
+        })
+        .await
+        .unwrap();
+        assert_eq!(unlock.join().unwrap(), Ok(()));
+        assert_eq!(third.lock(wanted), Ok(()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unlocking_for_another_owner_keeps_host_locks() {
+        use crate::{FileLock, LockKind};
+
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        let open = || {
+            fs.new_open_options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(Path::new("/foo.txt"))
+                .unwrap()
+        };
+        let mut shared = open();
+        let mut other = open();
+
+        // `fd2 = dup(fd1); flock(fd1, LOCK_EX); close(fd2)`: closing a
+        // descriptor releases the locks of the process, which has none.
+        assert_eq!(
+            shared.lock(FileLock::whole_file(LockKind::Exclusive, 1)),
+            Ok(())
+        );
+        assert_eq!(
+            shared.lock(FileLock::whole_file(LockKind::Unlock, 2)),
+            Ok(())
+        );
+        assert_eq!(
+            other.lock(FileLock::whole_file(LockKind::Shared, 3)),
+            Err(FsError::WouldBlock)
+        );
+
+        assert_eq!(
+            shared.lock(FileLock::whole_file(LockKind::Unlock, 1)),
+            Ok(())
+        );
+        assert_eq!(
+            other.lock(FileLock::whole_file(LockKind::Shared, 3)),
+            Ok(())
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watch_reports_host_changes() {
+        use crate::{WatchEvent, WatchEventKind};
+
+        let temp = TempDir::new().unwrap();
+        let fs = FileSystem::new(Handle::current(), temp.path()).expect("get filesystem");
+        fs.create_dir(Path::new("/foo")).unwrap();
+
+        let mut watcher = fs.watch(Path::new("/"), true).unwrap();
+        std::fs::write(temp.path().join("foo/bar.txt"), b"hello").unwrap();
+        std::fs::rename(temp.path().join("foo/bar.txt"), temp.path().join("baz.txt")).unwrap();
+        std::fs::remove_file(temp.path().join("baz.txt")).unwrap();
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.len() < 4 && std::time::Instant::now() < deadline {
+            match watcher.next_event() {
+                Some(event) => events.push(event),
+                None => std::thread::sleep(std::time::Duration::from_millis(10)),
+            }
+        }
+        assert_eq!(
+            events,
+            vec![
+                WatchEvent::new(WatchEventKind::Create, "/foo/bar.txt", false),
+                WatchEvent::new(WatchEventKind::Modify, "/foo/bar.txt", false),
+                WatchEvent::rename("/foo/bar.txt", "/baz.txt", false),
+                WatchEvent::new(WatchEventKind::Remove, "/baz.txt", false),
+            ]
+        );
+    }
 }
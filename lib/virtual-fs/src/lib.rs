@@ -49,6 +49,8 @@ mod trace_fs;
 mod webc_volume_fs;
 
 pub mod limiter;
+mod lock;
+mod watch;
 
 pub use arc_box_file::*;
 pub use arc_file::*;
@@ -60,6 +62,7 @@ pub use cow_file::*;
 pub use dual_write_file::*;
 pub use empty_fs::*;
 pub use filesystems::FileSystems;
+pub use lock::*;
 pub use null_file::*;
 pub use overlay_fs::OverlayFileSystem;
 pub use passthru_fs::*;
@@ -69,6 +72,7 @@ pub use static_file::StaticFile;
 pub use tmp_fs::*;
 pub use trace_fs::TraceFileSystem;
 pub use union_fs::*;
+pub use watch::*;
 #[cfg(feature = "webc-fs")]
 pub use webc_volume_fs::WebcVolumeFileSystem;
 pub use zero_file::*;
@@ -98,6 +102,28 @@ pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata>;
     fn remove_file(&self, path: &Path) -> Result<()>;
 
+    /// Changes the permission bits of an entry, as `chmod` does. Only the
+    /// lower 12 bits of `mode` (permissions, setuid, setgid and sticky) are
+    /// used.
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        let _ = (path, mode);
+        Err(FsError::Unsupported)
+    }
+
+    /// Changes the owner and group of an entry, as `chown` does. A `None`
+    /// leaves the corresponding id unchanged.
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let _ = (path, uid, gid);
+        Err(FsError::Unsupported)
+    }
+
+    /// Watches `path` for changes. Unless `recursive` is set, only the
+    /// changes to `path` itself and to its direct children are reported.
+    fn watch(&self, path: &Path, recursive: bool) -> Result<Watcher> {
+        let _ = (path, recursive);
+        Err(FsError::Unsupported)
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_>;
 
     fn mount(&self, name: String, path: &Path, fs: Box<dyn FileSystem + Send + Sync>)
@@ -153,6 +179,18 @@ where
         (**self).remove_file(path)
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        (**self).set_permissions(path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        (**self).chown(path, uid, gid)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<Watcher> {
+        (**self).watch(path, recursive)
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_> {
         (**self).new_open_options()
     }
@@ -422,6 +460,34 @@ pub trait VirtualFile:
         None
     }
 
+    /// Takes or releases an advisory lock on the file without blocking.
+    ///
+    /// Returns [`FsError::WouldBlock`] if another owner holds a
+    /// conflicting lock, in which case [`VirtualFile::poll_lock_ready`]
+    /// can be used to wait for it to be released.
+    #[allow(unused_variables)]
+    fn lock(&mut self, lock: FileLock) -> crate::Result<()> {
+        Err(FsError::Unsupported)
+    }
+
+    /// Returns a lock held by another owner that prevents `lock` from
+    /// being taken, if there is one.
+    #[allow(unused_variables)]
+    fn conflicting_lock(&self, lock: &FileLock) -> crate::Result<Option<FileLock>> {
+        Err(FsError::Unsupported)
+    }
+
+    /// Polls until `lock` may be taken, i.e. until the conflicting locks
+    /// held by other owners have been released
+    #[allow(unused_variables)]
+    fn poll_lock_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        lock: &FileLock,
+    ) -> Poll<crate::Result<()>> {
+        Poll::Ready(Err(FsError::Unsupported))
+    }
+
     /// Polls the file for when there is data to be read
     fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>>;
 
@@ -675,6 +741,18 @@ pub struct Metadata {
     pub created: u64,
     pub modified: u64,
     pub len: u64,
+    /// The permission bits of the entry, without the file type bits.
+    pub mode: u32,
+    /// The id of the user owning the entry.
+    pub uid: u32,
+    /// The id of the group owning the entry.
+    pub gid: u32,
+    /// The inode number of the entry, or 0 if the file system has none.
+    pub ino: u64,
+    /// The number of hard links to the entry.
+    pub nlink: u64,
+    /// The id of the device holding the entry.
+    pub dev: u64,
 }
 
 impl Metadata {
@@ -705,6 +783,30 @@ impl Metadata {
     pub fn len(&self) -> u64 {
         self.len
     }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    pub fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
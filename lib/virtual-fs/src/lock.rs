@@ -0,0 +1,281 @@
+//! Advisory file locks, as used by `flock` and `fcntl(F_SETLK)`.
+//!
+//! Locks are advisory: they never prevent reads or writes, they only
+//! conflict with other locks. A lock belongs to an owner, which is an
+//! opaque number chosen by the caller (for example an open file
+//! description for `flock`, or a process for `fcntl`). Locks of the same
+//! owner never conflict with each other, and taking a new lock over a
+//! range already held by the same owner replaces it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::{FsError, Result};
+
+/// The kind of an advisory lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockKind {
+    /// Any number of owners may hold a shared lock over the same bytes.
+    Shared,
+    /// Only one owner may hold an exclusive lock over the same bytes.
+    Exclusive,
+    /// Releases the locks held over a range of bytes.
+    Unlock,
+}
+
+/// An advisory lock over a range of bytes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileLock {
+    pub kind: LockKind,
+    /// Offset of the first locked byte.
+    pub start: u64,
+    /// Number of locked bytes, where `0` means up to the end of the file
+    /// no matter how much it grows.
+    pub len: u64,
+    /// The owner of the lock.
+    pub owner: u64,
+}
+
+impl FileLock {
+    /// Creates a lock over the whole file.
+    pub fn whole_file(kind: LockKind, owner: u64) -> Self {
+        Self {
+            kind,
+            start: 0,
+            len: 0,
+            owner,
+        }
+    }
+
+    /// Offset right after the last locked byte.
+    pub fn end(&self) -> u64 {
+        if self.len == 0 {
+            u64::MAX
+        } else {
+            self.start.saturating_add(self.len)
+        }
+    }
+
+    /// Returns whether both locks cover at least one common byte.
+    pub fn overlaps(&self, other: &FileLock) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    /// Returns whether this lock can not be taken while `other` is held.
+    pub fn conflicts_with(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.kind != LockKind::Unlock
+            && other.kind != LockKind::Unlock
+            && (self.kind == LockKind::Exclusive || other.kind == LockKind::Exclusive)
+            && self.overlaps(other)
+    }
+}
+
+/// Keeps track of the advisory locks held on a set of files, each
+/// identified by a number such as its inode.
+///
+/// Cloning a `LockManager` returns a handle to the same set of locks.
+#[derive(Debug, Clone, Default)]
+pub struct LockManager {
+    files: Arc<Mutex<HashMap<u64, FileLocks>>>,
+}
+
+#[derive(Debug, Default)]
+struct FileLocks {
+    locks: Vec<FileLock>,
+    waiters: Vec<Waker>,
+}
+
+impl LockManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes or releases a lock on `file` without blocking.
+    ///
+    /// Returns [`FsError::WouldBlock`] if the lock conflicts with a lock
+    /// of another owner. Any lock the owner already holds over the range
+    /// is replaced, which allows upgrading, downgrading and splitting
+    /// locks.
+    pub fn lock(&self, file: u64, lock: FileLock) -> Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let entry = files.entry(file).or_default();
+        if entry.locks.iter().any(|held| lock.conflicts_with(held)) {
+            return Err(FsError::WouldBlock);
+        }
+
+        let mut locks = Vec::with_capacity(entry.locks.len() + 2);
+        for held in entry.locks.drain(..) {
+            if held.owner != lock.owner || !held.overlaps(&lock) {
+                locks.push(held);
+                continue;
+            }
+            // Keep the parts of the held lock outside of the new range.
+            if held.start < lock.start {
+                locks.push(FileLock {
+                    len: lock.start - held.start,
+                    ..held
+                });
+            }
+            if lock.end() < held.end() {
+                locks.push(FileLock {
+                    start: lock.end(),
+                    len: if held.len == 0 {
+                        0
+                    } else {
+                        held.end() - lock.end()
+                    },
+                    ..held
+                });
+            }
+        }
+        if lock.kind != LockKind::Unlock {
+            locks.push(lock);
+        }
+        entry.locks = locks;
+
+        // Releasing or downgrading a lock may unblock waiting owners.
+        for waker in entry.waiters.drain(..) {
+            waker.wake();
+        }
+        if entry.locks.is_empty() {
+            files.remove(&file);
+        }
+        Ok(())
+    }
+
+    /// Returns a lock of another owner that prevents `lock` from being
+    /// taken, if there is one.
+    pub fn conflicting(&self, file: u64, lock: &FileLock) -> Option<FileLock> {
+        let files = self.files.lock().unwrap();
+        files
+            .get(&file)?
+            .locks
+            .iter()
+            .find(|held| lock.conflicts_with(held))
+            .copied()
+    }
+
+    /// Polls until `lock` no longer conflicts with the locks held on
+    /// `file`. The lock still has to be taken with [`LockManager::lock`],
+    /// which may fail again if another owner took a conflicting lock in
+    /// the meantime.
+    pub fn poll_lock_ready(&self, file: u64, lock: &FileLock, cx: &mut Context<'_>) -> Poll<()> {
+        let mut files = self.files.lock().unwrap();
+        let Some(entry) = files.get_mut(&file) else {
+            return Poll::Ready(());
+        };
+        if !entry.locks.iter().any(|held| lock.conflicts_with(held)) {
+            return Poll::Ready(());
+        }
+        if !entry.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            entry.waiters.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Releases all the locks `owner` holds on `file`.
+    pub fn unlock_all(&self, file: u64, owner: u64) {
+        // Unlocking never conflicts, so this can not fail.
+        let _ = self.lock(file, FileLock::whole_file(LockKind::Unlock, owner));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(kind: LockKind, start: u64, len: u64, owner: u64) -> FileLock {
+        FileLock {
+            kind,
+            start,
+            len,
+            owner,
+        }
+    }
+
+    #[test]
+    fn shared_and_exclusive_locks() {
+        let manager = LockManager::new();
+        manager.lock(1, lock(LockKind::Shared, 0, 0, 1)).unwrap();
+        manager.lock(1, lock(LockKind::Shared, 0, 0, 2)).unwrap();
+        assert_eq!(
+            manager.lock(1, lock(LockKind::Exclusive, 10, 10, 3)),
+            Err(FsError::WouldBlock)
+        );
+        // Other files are not affected.
+        manager.lock(2, lock(LockKind::Exclusive, 0, 0, 3)).unwrap();
+
+        manager.unlock_all(1, 1);
+        manager.unlock_all(1, 2);
+        manager
+            .lock(1, lock(LockKind::Exclusive, 10, 10, 3))
+            .unwrap();
+        assert_eq!(
+            manager.conflicting(1, &lock(LockKind::Shared, 0, 0, 1)),
+            Some(lock(LockKind::Exclusive, 10, 10, 3))
+        );
+    }
+
+    #[test]
+    fn byte_range_locks_are_split() {
+        let manager = LockManager::new();
+        manager
+            .lock(1, lock(LockKind::Exclusive, 0, 100, 1))
+            .unwrap();
+        manager.lock(1, lock(LockKind::Unlock, 40, 20, 1)).unwrap();
+
+        assert!(
+            manager
+                .conflicting(1, &lock(LockKind::Exclusive, 40, 20, 2))
+                .is_none()
+        );
+        assert!(
+            manager
+                .conflicting(1, &lock(LockKind::Shared, 39, 1, 2))
+                .is_some()
+        );
+        assert!(
+            manager
+                .conflicting(1, &lock(LockKind::Shared, 60, 0, 2))
+                .is_some()
+        );
+
+        // Downgrading part of the range lets other owners share it.
+        manager.lock(1, lock(LockKind::Shared, 60, 10, 1)).unwrap();
+        manager.lock(1, lock(LockKind::Shared, 60, 10, 2)).unwrap();
+        assert_eq!(
+            manager.lock(1, lock(LockKind::Shared, 70, 1, 2)),
+            Err(FsError::WouldBlock)
+        );
+    }
+
+    #[test]
+    fn waiters_are_woken_when_locks_are_released() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::task::Wake;
+
+        struct CountingWaker(AtomicUsize);
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let manager = LockManager::new();
+        let wanted = lock(LockKind::Exclusive, 0, 0, 2);
+        manager.lock(1, lock(LockKind::Shared, 0, 0, 1)).unwrap();
+        assert!(manager.poll_lock_ready(1, &wanted, &mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        manager.unlock_all(1, 1);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(manager.poll_lock_ready(1, &wanted, &mut cx).is_ready());
+        manager.lock(1, wanted).unwrap();
+    }
+}
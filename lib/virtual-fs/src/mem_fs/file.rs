@@ -11,7 +11,7 @@ use self::offloaded_file::OffloadWrite;
 
 use super::*;
 use crate::limiter::TrackedVec;
-use crate::{CopyOnWriteFile, FsError, Result, VirtualFile};
+use crate::{CopyOnWriteFile, FileLock, FsError, Result, VirtualFile, WatchEvent, WatchEventKind};
 use std::cmp;
 use std::convert::TryInto;
 use std::fmt;
@@ -34,6 +34,9 @@ pub(super) struct FileHandle {
     append_mode: bool,
     cursor: u64,
     arc_file: Option<Result<Box<dyn VirtualFile + Send + Sync + 'static>>>,
+    /// The owners which took advisory locks through this handle, so that
+    /// their locks are released when it is closed.
+    lock_owners: Vec<u64>,
 }
 
 impl Clone for FileHandle {
@@ -46,6 +49,7 @@ impl Clone for FileHandle {
             append_mode: self.append_mode,
             cursor: self.cursor,
             arc_file: None,
+            lock_owners: Vec::new(),
         }
     }
 }
@@ -67,6 +71,7 @@ impl FileHandle {
             append_mode,
             cursor,
             arc_file: None,
+            lock_owners: Vec::new(),
         }
     }
 
@@ -102,6 +107,19 @@ impl FileHandle {
     }
 }
 
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if self.lock_owners.is_empty() {
+            return;
+        }
+        if let Ok(fs) = self.filesystem.inner.read() {
+            for owner in self.lock_owners.drain(..) {
+                fs.locks.unlock_all(self.inode as u64, owner);
+            }
+        }
+    }
+}
+
 impl VirtualFile for FileHandle {
     fn last_accessed(&self) -> u64 {
         let fs = match self.filesystem.inner.read() {
@@ -264,12 +282,18 @@ impl VirtualFile for FileHandle {
         {
             // Write lock.
             let mut fs = filesystem.inner.write().map_err(|_| FsError::Lock)?;
+            let path = fs.watched_path_of_inode(inode_of_file);
 
             // Remove the file from the storage.
             fs.storage.remove(inode_of_file);
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
+
+            if let Some(path) = path {
+                fs.watchers
+                    .notify(WatchEvent::new(WatchEventKind::Remove, path, false));
+            }
         }
 
         Ok(())
@@ -327,6 +351,7 @@ impl VirtualFile for FileHandle {
                         created: src.created_time(),
                         modified: src.last_modified(),
                         len: src.size(),
+                        ..inode.metadata().clone()
                     };
 
                     *inode = Node::CustomFile(CustomFileNode {
@@ -359,6 +384,7 @@ impl VirtualFile for FileHandle {
                         created: 1,
                         modified: 1,
                         len: src.len() as u64,
+                        ..inode.metadata().clone()
                     };
 
                     *inode = Node::ReadOnlyFile(ReadOnlyFileNode {
@@ -374,6 +400,33 @@ impl VirtualFile for FileHandle {
         })
     }
 
+    fn lock(&mut self, lock: FileLock) -> Result<()> {
+        let fs = self.filesystem.inner.read().map_err(|_| FsError::Lock)?;
+        fs.locks.lock(self.inode as u64, lock)?;
+        drop(fs);
+
+        if !self.lock_owners.contains(&lock.owner) {
+            self.lock_owners.push(lock.owner);
+        }
+        Ok(())
+    }
+
+    fn conflicting_lock(&self, lock: &FileLock) -> Result<Option<FileLock>> {
+        let fs = self.filesystem.inner.read().map_err(|_| FsError::Lock)?;
+        Ok(fs.locks.conflicting(self.inode as u64, lock))
+    }
+
+    fn poll_lock_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        lock: &FileLock,
+    ) -> Poll<Result<()>> {
+        let fs = self.filesystem.inner.read().map_err(|_| FsError::Lock)?;
+        fs.locks
+            .poll_lock_ready(self.inode as u64, lock, cx)
+            .map(Ok)
+    }
+
     fn poll_read_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         if !self.readable {
             return Poll::Ready(Err(io::Error::new(
@@ -699,6 +752,47 @@ mod test_virtual_file {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_advisory_locks() {
+        use crate::{FileLock, FsError, LockKind};
+
+        let fs = FileSystem::default();
+        let open = || {
+            fs.new_open_options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(path!("/foo.txt"))
+                .expect("failed to open the file")
+        };
+        let mut first = open();
+        let mut second = open();
+
+        first
+            .lock(FileLock::whole_file(LockKind::Exclusive, 1))
+            .unwrap();
+        assert_eq!(
+            second.lock(FileLock::whole_file(LockKind::Shared, 2)),
+            Err(FsError::WouldBlock)
+        );
+        assert_eq!(
+            second
+                .conflicting_lock(&FileLock::whole_file(LockKind::Shared, 2))
+                .unwrap(),
+            Some(FileLock::whole_file(LockKind::Exclusive, 1))
+        );
+
+        // Closing the handle releases the locks taken through it.
+        drop(first);
+        second
+            .lock(FileLock::whole_file(LockKind::Shared, 2))
+            .unwrap();
+        assert_eq!(
+            open().lock(FileLock::whole_file(LockKind::Shared, 3)),
+            Ok(())
+        );
+    }
 }
 
 impl AsyncRead for FileHandle {
@@ -946,7 +1040,7 @@ impl AsyncWrite for FileHandle {
                 .map_err(|_| io::Error::other("failed to acquire a write lock"))?;
 
             let inode = fs.storage.get_mut(self.inode);
-            match inode {
+            let bytes_written = match inode {
                 Some(Node::File(node)) => {
                     let bytes_written = node.file.write(buf, &mut cursor)?;
                     node.metadata.len = node.file.len().try_into().unwrap();
@@ -1004,7 +1098,9 @@ impl AsyncWrite for FileHandle {
                         format!("inode `{}` doesn't match a file", self.inode),
                     )));
                 }
-            }
+            };
+            fs.notify_modified(self.inode);
+            bytes_written
         };
         self.cursor = cursor;
         Poll::Ready(Ok(bytes_written))
@@ -1077,6 +1173,11 @@ impl AsyncWrite for FileHandle {
             }
         };
         self.cursor = cursor;
+        if let Poll::Ready(Ok(1..)) = ret {
+            if let Ok(fs) = self.filesystem.inner.read() {
+                fs.notify_modified(self.inode);
+            }
+        }
         ret
     }
 
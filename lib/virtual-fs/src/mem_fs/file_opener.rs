@@ -1,6 +1,8 @@
 use super::filesystem::InodeResolution;
 use super::*;
-use crate::{FileType, FsError, Metadata, OpenOptionsConfig, Result, VirtualFile};
+use crate::{
+    FileType, FsError, Metadata, OpenOptionsConfig, Result, VirtualFile, WatchEvent, WatchEventKind,
+};
 use shared_buffer::OwnedBuffer;
 use std::path::Path;
 use tracing::*;
@@ -49,6 +51,9 @@ impl FileSystem {
                             created: time,
                             modified: time,
                             len: file_len,
+                            mode: DEFAULT_FILE_MODE,
+                            nlink: 1,
+                            ..Default::default()
                         }
                     },
                 }));
@@ -109,6 +114,9 @@ impl FileSystem {
                             created: time,
                             modified: time,
                             len: 0,
+                            mode: DEFAULT_FILE_MODE,
+                            nlink: 1,
+                            ..Default::default()
                         }
                     }
                 };
@@ -194,6 +202,9 @@ impl FileSystem {
                                 created: time,
                                 modified: time,
                                 len: 0,
+                                mode: DEFAULT_FILE_MODE,
+                                nlink: 1,
+                                ..Default::default()
                             }
                         },
                     }));
@@ -265,6 +276,9 @@ impl FileSystem {
                     created: time,
                     modified: time,
                     len: 0,
+                    mode: DEFAULT_FILE_MODE,
+                    nlink: 1,
+                    ..Default::default()
                 }
             },
         }));
@@ -476,6 +490,10 @@ impl crate::FileOpener for FileSystem {
                     _ => return Err(FsError::NotAFile),
                 }
 
+                if truncate {
+                    fs.notify_modified(inode_of_file);
+                }
+
                 inode_of_file
             }
 
@@ -486,6 +504,7 @@ impl crate::FileOpener for FileSystem {
                 // Write lock.
                 let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
+                let inode_of_file = fs.storage.vacant_entry().key();
                 let metadata = {
                     let time = time();
                     Metadata {
@@ -497,9 +516,11 @@ impl crate::FileOpener for FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        mode: DEFAULT_FILE_MODE,
+                        nlink: 1,
+                        ..Default::default()
                     }
                 };
-                let inode_of_file = fs.storage.vacant_entry().key();
 
                 // We might be in optimized mode
                 let file = match fs.backing_offload.clone() {
@@ -534,6 +555,11 @@ impl crate::FileOpener for FileSystem {
                 // Adding the new directory to its parent.
                 fs.add_child_to_node(inode_of_parent, inode_of_file)?;
 
+                if let Some(path) = fs.watched_path_of_inode(inode_of_file) {
+                    fs.watchers
+                        .notify(WatchEvent::new(WatchEventKind::Create, path, false));
+                }
+
                 inode_of_file
             }
 
@@ -3,7 +3,10 @@
 use self::offloaded_file::OffloadBackingStore;
 
 use super::*;
-use crate::{DirEntry, FileType, FsError, Metadata, OpenOptions, ReadDir, Result};
+use crate::{
+    DirEntry, FileType, FsError, LockManager, Metadata, OpenOptions, ReadDir, Result, WatchEvent,
+    WatchEventKind, WatchRegistry, Watcher,
+};
 use futures::future::{BoxFuture, Either};
 use slab::Slab;
 use std::collections::VecDeque;
@@ -11,7 +14,7 @@ use std::convert::identity;
 use std::ffi::OsString;
 use std::fmt;
 use std::path::{Component, Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, atomic::Ordering};
 
 /// The in-memory file system!
 ///
@@ -239,6 +242,9 @@ impl FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        mode: DEFAULT_DIR_MODE,
+                        nlink: 1,
+                        ..Default::default()
                     }
                 },
             }));
@@ -295,7 +301,7 @@ impl crate::FileSystem for FileSystem {
 
                         entry_path
                     },
-                    metadata: Ok(node.metadata().clone()),
+                    metadata: guard.metadata_of(node.inode()),
                 })
                 .collect(),
 
@@ -314,7 +320,7 @@ impl crate::FileSystem for FileSystem {
             return Err(FsError::AlreadyExists);
         }
 
-        let (inode_of_parent, name_of_directory) = {
+        let (inode_of_parent, name_of_directory, canonical_path) = {
             // Read lock.
             let guard = self.inner.read().map_err(|_| FsError::Lock)?;
 
@@ -341,7 +347,7 @@ impl crate::FileSystem for FileSystem {
                 }
             };
 
-            (inode_of_parent, name_of_directory)
+            (inode_of_parent, name_of_directory, path)
         };
 
         if self.read_dir(path).is_ok() {
@@ -370,6 +376,9 @@ impl crate::FileSystem for FileSystem {
                         created: time,
                         modified: time,
                         len: 0,
+                        mode: DEFAULT_DIR_MODE,
+                        nlink: 1,
+                        ..Default::default()
                     }
                 },
             }));
@@ -381,13 +390,19 @@ impl crate::FileSystem for FileSystem {
 
             // Adding the new directory to its parent.
             fs.add_child_to_node(inode_of_parent, inode_of_directory)?;
+
+            fs.watchers.notify(WatchEvent::new(
+                WatchEventKind::Create,
+                canonical_path,
+                true,
+            ));
         }
 
         Ok(())
     }
 
     fn remove_dir(&self, path: &Path) -> Result<()> {
-        let (inode_of_parent, position, inode_of_directory) = {
+        let (inode_of_parent, position, inode_of_directory, canonical_path) = {
             // Read lock.
             let guard = self.inner.read().map_err(|_| FsError::Lock)?;
 
@@ -422,7 +437,7 @@ impl crate::FileSystem for FileSystem {
                     DirectoryMustBeEmpty::Yes,
                 )?;
 
-            (inode_of_parent, position, inode_of_directory)
+            (inode_of_parent, position, inode_of_directory, path)
         };
 
         let inode_of_directory = match inode_of_directory {
@@ -441,6 +456,12 @@ impl crate::FileSystem for FileSystem {
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
+
+            fs.watchers.notify(WatchEvent::new(
+                WatchEventKind::Remove,
+                canonical_path,
+                true,
+            ));
         }
 
         Ok(())
@@ -451,7 +472,14 @@ impl crate::FileSystem for FileSystem {
             let name_of_to;
 
             // Read lock.
-            let (name_of_from, inode_of_from_parent, name_of_to, inode_of_to_parent) = {
+            let (
+                name_of_from,
+                inode_of_from_parent,
+                name_of_to,
+                inode_of_to_parent,
+                canonical_from,
+                canonical_to,
+            ) = {
                 let fs = self.inner.read().map_err(|_| FsError::Lock)?;
 
                 let from = fs.canonicalize_without_inode(from)?;
@@ -489,6 +517,8 @@ impl crate::FileSystem for FileSystem {
                     inode_of_from_parent,
                     name_of_to,
                     inode_of_to_parent,
+                    from,
+                    to,
                 )
             };
 
@@ -569,6 +599,16 @@ impl crate::FileSystem for FileSystem {
                                 _ => return Err(FsError::UnknownError),
                             }
                         }
+
+                        let is_dir = matches!(
+                            fs.storage.get(inode),
+                            Some(Node::Directory(_) | Node::ArcDirectory(_))
+                        );
+                        fs.watchers.notify(WatchEvent::rename(
+                            canonical_from,
+                            canonical_to,
+                            is_dir,
+                        ));
                     }
 
                     Ok(())
@@ -609,12 +649,7 @@ impl crate::FileSystem for FileSystem {
         // Read lock.
         let guard = self.inner.read().map_err(|_| FsError::Lock)?;
         match guard.inode_of(path)? {
-            InodeResolution::Found(inode) => Ok(guard
-                .storage
-                .get(inode)
-                .ok_or(FsError::UnknownError)?
-                .metadata()
-                .clone()),
+            InodeResolution::Found(inode) => guard.metadata_of(inode),
             InodeResolution::Redirect(fs, path) => {
                 drop(guard);
                 fs.metadata(path.as_path())
@@ -626,12 +661,7 @@ impl crate::FileSystem for FileSystem {
         // Read lock.
         let guard = self.inner.read().map_err(|_| FsError::Lock)?;
         match guard.inode_of(path)? {
-            InodeResolution::Found(inode) => Ok(guard
-                .storage
-                .get(inode)
-                .ok_or(FsError::UnknownError)?
-                .metadata()
-                .clone()),
+            InodeResolution::Found(inode) => guard.metadata_of(inode),
             InodeResolution::Redirect(fs, path) => {
                 drop(guard);
                 fs.symlink_metadata(path.as_path())
@@ -639,8 +669,68 @@ impl crate::FileSystem for FileSystem {
         }
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of(path)? {
+            InodeResolution::Found(inode) => {
+                let node = guard.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+                node.metadata_mut().mode = mode & 0o7777;
+                Ok(())
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.set_permissions(path.as_path(), mode)
+            }
+        }
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        // Write lock.
+        let mut guard = self.inner.write().map_err(|_| FsError::Lock)?;
+        match guard.inode_of(path)? {
+            InodeResolution::Found(inode) => {
+                let metadata = guard
+                    .storage
+                    .get_mut(inode)
+                    .ok_or(FsError::UnknownError)?
+                    .metadata_mut();
+                if let Some(uid) = uid {
+                    metadata.uid = uid;
+                }
+                if let Some(gid) = gid {
+                    metadata.gid = gid;
+                }
+                Ok(())
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.chown(path.as_path(), uid, gid)
+            }
+        }
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<Watcher> {
+        // Read lock.
+        let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+        let (canonical_path, inode) = guard.canonicalize(path)?;
+        match inode {
+            InodeResolution::Found(_) => Ok(guard.watchers.watch(&canonical_path, recursive)),
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                // Report the paths relative to where the file system is mounted.
+                let depth = path.components().count().saturating_sub(1);
+                let mut mount_point = canonical_path;
+                for _ in 0..depth {
+                    mount_point.pop();
+                }
+                Ok(fs.watch(&path, recursive)?.prefixed(&mount_point))
+            }
+        }
+    }
+
     fn remove_file(&self, path: &Path) -> Result<()> {
-        let (inode_of_parent, position, inode_of_file) = {
+        let (inode_of_parent, position, inode_of_file, canonical_path) = {
             // Read lock.
             let guard = self.inner.read().map_err(|_| FsError::Lock)?;
 
@@ -670,7 +760,7 @@ impl crate::FileSystem for FileSystem {
                 guard.as_parent_get_position_and_inode_of_file(inode_of_parent, &name_of_file)?;
 
             match maybe_position_and_inode_of_file {
-                Some((position, inode_of_file)) => (inode_of_parent, position, inode_of_file),
+                Some((position, inode_of_file)) => (inode_of_parent, position, inode_of_file, path),
                 None => return Err(FsError::EntryNotFound),
             }
         };
@@ -691,6 +781,12 @@ impl crate::FileSystem for FileSystem {
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
+
+            fs.watchers.notify(WatchEvent::new(
+                WatchEventKind::Remove,
+                canonical_path,
+                false,
+            ));
         }
 
         Ok(())
@@ -725,6 +821,12 @@ pub(super) struct FileSystemInner {
     pub(super) storage: Slab<Node>,
     pub(super) backing_offload: Option<OffloadBackingStore>,
     pub(super) limiter: Option<crate::limiter::DynFsMemoryLimiter>,
+    /// The advisory locks held on the files, by inode.
+    pub(super) locks: LockManager,
+    /// The watchers notified of the changes to the file system.
+    pub(super) watchers: WatchRegistry,
+    /// The device id reported for the entries of this file system.
+    pub(super) dev: u64,
 }
 
 #[derive(Debug)]
@@ -746,6 +848,67 @@ impl InodeResolution {
 }
 
 impl FileSystemInner {
+    /// Get the path of an inode by walking up its parents, but only when
+    /// the file system is watched since this is not cheap.
+    pub(super) fn watched_path_of_inode(&self, inode: Inode) -> Option<PathBuf> {
+        if !self.watchers.is_watched() {
+            return None;
+        }
+
+        let mut names = Vec::new();
+        let mut current = inode;
+        while current != ROOT_INODE {
+            names.push(self.storage.get(current)?.name().to_os_string());
+            current = self.storage.iter().find_map(|(parent, node)| match node {
+                Node::Directory(DirectoryNode { children, .. }) if children.contains(&current) => {
+                    Some(parent)
+                }
+                _ => None,
+            })?;
+        }
+        Some(
+            std::iter::once(OsString::from("/"))
+                .chain(names.into_iter().rev())
+                .collect(),
+        )
+    }
+
+    /// Notify the watchers that the contents of a file changed.
+    pub(super) fn notify_modified(&self, inode: Inode) {
+        if let Some(path) = self.watched_path_of_inode(inode) {
+            self.watchers
+                .notify(WatchEvent::new(WatchEventKind::Modify, path, false));
+        }
+    }
+
+    /// Get the metadata of a node. Inode numbers start at 1 and are only
+    /// unique within the device of this file system. The link count of a
+    /// directory covers its `.` entry and the `..` entries of its
+    /// subdirectories.
+    pub(super) fn metadata_of(&self, inode: Inode) -> Result<Metadata> {
+        let node = self.storage.get(inode).ok_or(FsError::UnknownError)?;
+        let mut metadata = node.metadata().clone();
+        metadata.ino = inode as u64 + 1;
+        metadata.dev = self.dev;
+        match node {
+            Node::Directory(DirectoryNode { children, .. }) => {
+                let subdirectories = children
+                    .iter()
+                    .filter(|child| {
+                        matches!(
+                            self.storage.get(**child),
+                            Some(Node::Directory(_) | Node::ArcDirectory(_))
+                        )
+                    })
+                    .count();
+                metadata.nlink = 2 + subdirectories as u64;
+            }
+            Node::ArcDirectory(_) => metadata.nlink = metadata.nlink.max(2),
+            _ => {}
+        }
+        Ok(metadata)
+    }
+
     /// Get the inode associated to a path if it exists.
     pub(super) fn inode_of(&self, path: &Path) -> Result<InodeResolution> {
         // SAFETY: The root node always exists, so it's safe to unwrap here.
@@ -1122,6 +1285,9 @@ impl Default for FileSystemInner {
                 created: time,
                 modified: time,
                 len: 0,
+                mode: DEFAULT_DIR_MODE,
+                nlink: 1,
+                ..Default::default()
             },
         }));
 
@@ -1129,6 +1295,9 @@ impl Default for FileSystemInner {
             storage: slab,
             backing_offload: None,
             limiter: None,
+            locks: LockManager::default(),
+            watchers: WatchRegistry::default(),
+            dev: NEXT_DEV.fetch_add(1, Ordering::Relaxed),
         }
     }
 }
@@ -1597,7 +1766,8 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             }) if accessed == created && created == modified && modified > 0
         ));
 
@@ -1614,7 +1784,8 @@ mod test_filesystem {
                 accessed,
                 created,
                 modified,
-                len: 0
+                len: 0,
+                ..
             } if accessed == created && created == modified && modified > 0
         ));
 
@@ -1630,7 +1801,8 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
                     accessed == foo_metadata.accessed &&
                     created == foo_metadata.created &&
@@ -1646,7 +1818,8 @@ mod test_filesystem {
                     accessed,
                     created,
                     modified,
-                    len: 0
+                    len: 0,
+                    ..
                 }) if
                     accessed <= foo_metadata.accessed &&
                     created <= foo_metadata.created &&
@@ -1658,6 +1831,44 @@ mod test_filesystem {
         );
     }
 
+    #[tokio::test]
+    async fn test_permissions_and_ownership() {
+        let fs = FileSystem::default();
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()));
+        assert!(
+            fs.new_open_options()
+                .write(true)
+                .create_new(true)
+                .open(path!("/foo/bar.txt"))
+                .is_ok()
+        );
+
+        let dir = fs.metadata(path!("/foo")).unwrap();
+        let file = fs.metadata(path!("/foo/bar.txt")).unwrap();
+        assert_eq!((dir.mode(), dir.nlink()), (0o755, 2));
+        assert_eq!((file.mode(), file.nlink()), (0o644, 1));
+        assert_ne!(dir.ino(), file.ino());
+
+        let root = fs.metadata(path!("/")).unwrap();
+        assert_eq!(root.nlink(), 3, "the subdirectory links to the root");
+        assert!(![root.ino(), dir.ino(), file.ino()].contains(&0));
+
+        let other = FileSystem::default();
+        assert_ne!(other.metadata(path!("/")).unwrap().dev(), root.dev());
+        assert_eq!(file.dev(), root.dev());
+
+        assert_eq!(fs.set_permissions(path!("/foo/bar.txt"), 0o100600), Ok(()));
+        assert_eq!(fs.chown(path!("/foo/bar.txt"), Some(1000), None), Ok(()));
+        assert_eq!(fs.chown(path!("/foo/bar.txt"), None, Some(100)), Ok(()));
+
+        let file = fs.metadata(path!("/foo/bar.txt")).unwrap();
+        assert_eq!((file.mode(), file.uid(), file.gid()), (0o600, 1000, 100));
+        assert_eq!(
+            fs.set_permissions(path!("/foo/baz.txt"), 0o600),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn test_remove_file() {
         let fs = FileSystem::default();
@@ -1971,4 +2182,47 @@ mod test_filesystem {
 
         assert_eq!(buf, b"a");
     }
+
+    #[tokio::test]
+    async fn test_watch() {
+        use crate::{WatchEvent, WatchEventKind};
+        use tokio::io::AsyncWriteExt;
+
+        let fs = FileSystem::default();
+        fs.create_dir(path!("/foo")).unwrap();
+        assert_eq!(
+            fs.watch(path!("/missing"), false).map(|_| ()),
+            Err(FsError::EntryNotFound)
+        );
+
+        let mut watcher = fs.watch(path!("/foo"), false).unwrap();
+        let mut recursive = fs.watch(path!("/"), true).unwrap();
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap();
+        file.write_all(b"hello").await.unwrap();
+        fs.rename(path!("/foo/bar.txt"), path!("/foo/baz.txt"))
+            .await
+            .unwrap();
+        fs.create_dir(path!("/foo/qux")).unwrap();
+        fs.create_dir(path!("/foo/qux/quux")).unwrap();
+        fs.remove_file(path!("/foo/baz.txt")).unwrap();
+
+        let events: Vec<_> = std::iter::from_fn(|| watcher.next_event()).collect();
+        assert_eq!(
+            events,
+            vec![
+                WatchEvent::new(WatchEventKind::Create, "/foo/bar.txt", false),
+                WatchEvent::new(WatchEventKind::Modify, "/foo/bar.txt", false),
+                WatchEvent::rename("/foo/bar.txt", "/foo/baz.txt", false),
+                WatchEvent::new(WatchEventKind::Create, "/foo/qux", true),
+                WatchEvent::new(WatchEventKind::Remove, "/foo/baz.txt", false),
+            ]
+        );
+        assert_eq!(std::iter::from_fn(|| recursive.next_event()).count(), 6);
+    }
 }
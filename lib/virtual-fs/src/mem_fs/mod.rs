@@ -25,6 +25,16 @@ use self::offloaded_file::OffloadedFile;
 type Inode = usize;
 const ROOT_INODE: Inode = 0;
 
+/// Permission bits of newly created files.
+const DEFAULT_FILE_MODE: u32 = 0o644;
+/// Permission bits of newly created directories.
+const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// Device id of the next in-memory file system. Every instance gets its own
+/// device, as instances number their inodes independently. The ids start
+/// high up so that they do not clash with the devices of the host.
+static NEXT_DEV: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1 << 48);
+
 #[derive(Debug)]
 struct FileNode {
     inode: Inode,
@@ -13,8 +13,8 @@ use replace_with::replace_with_or_abort;
 use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
 
 use crate::{
-    FileOpener, FileSystem, FileSystems, FsError, Metadata, OpenOptions, OpenOptionsConfig,
-    ReadDir, VirtualFile, ops,
+    FileLock, FileOpener, FileSystem, FileSystems, FsError, Metadata, OpenOptions,
+    OpenOptionsConfig, ReadDir, VirtualFile, WatchEvent, WatchEventKind, Watcher, ops,
 };
 
 /// A primary filesystem and chain of secondary filesystems that are overlayed
@@ -105,6 +105,56 @@ where
 
         Err(FsError::EntryNotFound)
     }
+
+    /// Makes sure `path` is in the primary so that its metadata can be
+    /// changed, copying it from the secondaries along with its permissions
+    /// and ownership if needed.
+    fn copy_up(&self, path: &Path) -> Result<(), FsError> {
+        // Whiteout files can not be changed, they are just markers
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        match self.primary.metadata(path) {
+            Ok(_) => return Ok(()),
+            Err(e) if should_continue(e) => {}
+            Err(e) => return Err(e),
+        }
+
+        // There might be a whiteout, search for this
+        if ops::has_white_out(&self.primary, path) {
+            return Err(FsError::EntryNotFound);
+        }
+
+        for fs in self.secondaries.filesystems() {
+            let metadata = match fs.metadata(path) {
+                Ok(metadata) => metadata,
+                Err(e) if should_continue(e) => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(parent) = path.parent() {
+                ops::create_dir_all(&self.primary, parent)?;
+            }
+            if metadata.is_dir() {
+                self.primary.create_dir(path)?;
+            } else {
+                futures::executor::block_on(ops::copy_reference(fs, &self.primary, path))?;
+            }
+
+            // The secondary might not know about permissions or ownership,
+            // and the primary might not store them, so this is best effort.
+            if metadata.mode() != 0 {
+                self.primary.set_permissions(path, metadata.mode()).ok();
+            }
+            self.primary
+                .chown(path, Some(metadata.uid()), Some(metadata.gid()))
+                .ok();
+            return Ok(());
+        }
+
+        Err(FsError::EntryNotFound)
+    }
 }
 
 impl<P, S> FileSystem for OverlayFileSystem<P, S>
@@ -411,6 +461,42 @@ where
         self.permission_error_or_not_found(path)
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), FsError> {
+        self.copy_up(path)?;
+        self.primary.set_permissions(path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<(), FsError> {
+        self.copy_up(path)?;
+        self.primary.chown(path, uid, gid)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<Watcher, FsError> {
+        // Watch the path in every layer it exists in, the secondaries may
+        // change underneath the overlay when they are shared.
+        let mut watchers = Vec::new();
+        let mut error = FsError::EntryNotFound;
+        let primary = self
+            .primary
+            .watch(path, recursive)
+            .map(|w| w.filter_map(hide_white_outs));
+        let secondaries = self
+            .secondaries
+            .filesystems()
+            .into_iter()
+            .map(|fs| fs.watch(path, recursive));
+        for watcher in std::iter::once(primary).chain(secondaries) {
+            match watcher {
+                Ok(watcher) => watchers.push(watcher),
+                Err(FsError::Unsupported) => error = FsError::Unsupported,
+                Err(e) if should_continue(e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        watchers.into_iter().reduce(Watcher::merge).ok_or(error)
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_> {
         OpenOptions::new(self)
     }
@@ -562,6 +648,9 @@ where
         readable: bool,
         append: bool,
         new_size: Option<u64>,
+        // Locks taken on the original file, which are taken again on
+        // the copy once it has been made
+        locks: Vec<FileLock>,
     }
     enum CowState {
         // The original file is still open and can be accessed for all
@@ -817,6 +906,11 @@ where
                     state
                 }
             });
+            if let CowState::Copied(file) = &mut self.state {
+                for lock in self.locks.drain(..) {
+                    file.lock(lock).ok();
+                }
+            }
             ret
         }
 
@@ -911,6 +1005,26 @@ where
             Err(FsError::PermissionDenied)
         }
 
+        fn lock(&mut self, lock: FileLock) -> crate::Result<()> {
+            self.state.as_mut().lock(lock)?;
+            if !matches!(self.state, CowState::Copied(_)) {
+                self.locks.push(lock);
+            }
+            Ok(())
+        }
+
+        fn conflicting_lock(&self, lock: &FileLock) -> crate::Result<Option<FileLock>> {
+            self.state.as_ref().conflicting_lock(lock)
+        }
+
+        fn poll_lock_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            lock: &FileLock,
+        ) -> Poll<crate::Result<()>> {
+            Pin::new(self.state.as_mut()).poll_lock_ready(cx, lock)
+        }
+
         fn poll_read_ready(
             mut self: Pin<&mut Self>,
             cx: &mut std::task::Context<'_>,
@@ -1083,6 +1197,7 @@ where
         readable: conf.read,
         append: conf.append,
         new_size: None,
+        locks: Vec::new(),
     }))
 }
 
@@ -1115,6 +1230,19 @@ where
     }
 }
 
+/// Reports the creation of a whiteout in the primary as the removal of the
+/// entry it hides.
+fn hide_white_outs(mut event: WatchEvent) -> Option<WatchEvent> {
+    if let Some(path) = ops::is_white_out(&event.path) {
+        if event.kind != WatchEventKind::Create {
+            return None;
+        }
+        event.kind = WatchEventKind::Remove;
+        event.path = path;
+    }
+    Some(event)
+}
+
 fn should_continue(e: FsError) -> bool {
     // HACK: We shouldn't really be ignoring FsError::BaseNotDirectory, but
     // it's needed because the mem_fs::FileSystem doesn't return
@@ -1181,6 +1309,36 @@ mod tests {
         assert!(ops::exists(&overlay.secondaries[0], third));
     }
 
+    #[tokio::test]
+    async fn watch_reports_changes_of_all_layers() {
+        use crate::{WatchEvent, WatchEventKind};
+
+        let primary = MemFS::default();
+        let secondary = MemFS::default();
+        ops::touch(&secondary, "/file.txt").unwrap();
+        let fs = OverlayFileSystem::new(primary, [secondary]);
+
+        let mut watcher = fs.watch(Path::new("/"), true).unwrap();
+        fs.remove_file(Path::new("/file.txt")).unwrap();
+        ops::touch(&fs, "/new.txt").unwrap();
+        ops::touch(&fs.secondaries[0], "/other.txt").unwrap();
+
+        let events: Vec<_> = std::iter::from_fn(|| watcher.next_event()).collect();
+        assert_eq!(
+            events,
+            vec![
+                // The whiteout hiding the file is reported as its removal
+                WatchEvent::new(WatchEventKind::Remove, "/file.txt", false),
+                WatchEvent::new(WatchEventKind::Create, "/new.txt", false),
+                WatchEvent::new(WatchEventKind::Create, "/other.txt", false),
+            ]
+        );
+        assert_eq!(
+            fs.watch(Path::new("/missing"), false).map(|_| ()),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn open_files() {
         let primary = MemFS::default();
@@ -1337,6 +1495,48 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn changing_permissions_copies_secondary_files_up() {
+        let primary = MemFS::default();
+        let secondary = MemFS::default();
+        ops::create_dir_all(&secondary, "/secondary").unwrap();
+        ops::write(&secondary, "/secondary/file.txt", b"Hello, World!")
+            .await
+            .unwrap();
+        secondary
+            .chown(Path::new("/secondary/file.txt"), Some(1000), Some(1000))
+            .unwrap();
+
+        let fs = OverlayFileSystem::new(primary, [secondary]);
+
+        fs.set_permissions(Path::new("/secondary/file.txt"), 0o600)
+            .unwrap();
+
+        let metadata = fs.metadata(Path::new("/secondary/file.txt")).unwrap();
+        assert_eq!(metadata.mode(), 0o600);
+        assert_eq!((metadata.uid(), metadata.gid()), (1000, 1000));
+        assert_eq!(
+            ops::read_to_string(&fs, "/secondary/file.txt")
+                .await
+                .unwrap(),
+            "Hello, World!"
+        );
+
+        // The secondary is left untouched
+        assert!(ops::is_file(&fs.primary, "/secondary/file.txt"));
+        let metadata = fs.secondaries[0]
+            .metadata(Path::new("/secondary/file.txt"))
+            .unwrap();
+        assert_eq!(metadata.mode(), 0o644);
+
+        fs.chown(Path::new("/secondary"), Some(0), Some(0)).unwrap();
+        assert!(ops::is_dir(&fs.primary, "/secondary"));
+        assert_eq!(
+            fs.chown(Path::new("/missing"), Some(0), None),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn open_secondary_fs_without_cow() {
         let primary = MemFS::default();
@@ -230,6 +230,7 @@ fn transform_into_read_dir(path: &Path, fs_entries: &[FsEntry<'_>]) -> crate::Re
                 created: 0,
                 modified: 0,
                 len: e.get_len(),
+                ..Default::default()
             }),
         })
         .collect();
@@ -320,6 +321,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: fs_entry.get_len(),
+                ..Default::default()
             })
         } else if let Some(_fs) = self.volumes.values().find_map(|v| v.read_dir(&path).ok()) {
             Ok(Metadata {
@@ -328,6 +330,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                ..Default::default()
             })
         } else {
             self.memory.metadata(Path::new(&path))
@@ -363,6 +366,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: fs_entry.get_len(),
+                ..Default::default()
             })
         } else if self
             .volumes
@@ -376,6 +380,7 @@ impl FileSystem for StaticFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                ..Default::default()
             })
         } else {
             self.memory.symlink_metadata(Path::new(&path))
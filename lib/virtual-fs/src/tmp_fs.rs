@@ -93,6 +93,18 @@ impl FileSystem for TmpFileSystem {
         self.fs.remove_file(path)
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.fs.set_permissions(path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.fs.chown(path, uid, gid)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> Result<crate::Watcher> {
+        self.fs.watch(path, recursive)
+    }
+
     fn new_open_options(&self) -> OpenOptions<'_> {
         self.fs.new_open_options()
     }
@@ -83,6 +83,26 @@ where
         self.0.remove_file(path)
     }
 
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn set_permissions(&self, path: &std::path::Path, mode: u32) -> crate::Result<()> {
+        self.0.set_permissions(path, mode)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn chown(
+        &self,
+        path: &std::path::Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> crate::Result<()> {
+        self.0.chown(path, uid, gid)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), err)]
+    fn watch(&self, path: &std::path::Path, recursive: bool) -> crate::Result<crate::Watcher> {
+        self.0.watch(path, recursive)
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     fn new_open_options(&self) -> crate::OpenOptions<'_> {
         crate::OpenOptions::new(self)
@@ -158,6 +178,25 @@ impl VirtualFile for TraceFile {
         self.file.unlink()
     }
 
+    #[tracing::instrument(level = "trace", skip(self), fields(path=%self.path.display()), err)]
+    fn lock(&mut self, lock: crate::FileLock) -> crate::Result<()> {
+        self.file.lock(lock)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self), fields(path=%self.path.display()), err)]
+    fn conflicting_lock(&self, lock: &crate::FileLock) -> crate::Result<Option<crate::FileLock>> {
+        self.file.conflicting_lock(lock)
+    }
+
+    #[tracing::instrument(level = "trace", skip_all, fields(path=%self.path.display()))]
+    fn poll_lock_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        lock: &crate::FileLock,
+    ) -> Poll<crate::Result<()>> {
+        Pin::new(&mut *self.file).poll_lock_ready(cx, lock)
+    }
+
     #[tracing::instrument(level = "trace", skip_all, fields(path=%self.path.display()))]
     fn poll_read_ready(
         mut self: Pin<&mut Self>,
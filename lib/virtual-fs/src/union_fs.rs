@@ -115,6 +115,7 @@ impl FileSystem for UnionFileSystem {
                         created: 0,
                         modified: 0,
                         len: 0,
+                        ..Default::default()
                     }),
                 })
                 .collect::<Vec<_>>();
@@ -202,6 +203,7 @@ impl FileSystem for UnionFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                ..Default::default()
             })
         } else {
             match self.find_mount(path.to_owned()) {
@@ -220,6 +222,7 @@ impl FileSystem for UnionFileSystem {
                 created: 0,
                 modified: 0,
                 len: 0,
+                ..Default::default()
             })
         } else {
             match self.find_mount(path.to_owned()) {
@@ -240,6 +243,32 @@ impl FileSystem for UnionFileSystem {
             }
         }
     }
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        match self.find_mount(path.to_owned()) {
+            Some((_, path, fs)) => fs.set_permissions(&path, mode),
+            _ => Err(FsError::EntryNotFound),
+        }
+    }
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let path = self.prepare_path(path);
+
+        match self.find_mount(path.to_owned()) {
+            Some((_, path, fs)) => fs.chown(&path, uid, gid),
+            _ => Err(FsError::EntryNotFound),
+        }
+    }
+    fn watch(&self, path: &Path, recursive: bool) -> Result<crate::Watcher> {
+        let path = self.prepare_path(path);
+
+        match self.find_mount(path.to_owned()) {
+            Some((mount, path, fs)) => Ok(fs
+                .watch(&path, recursive)?
+                .prefixed(&Path::new("/").join(mount))),
+            _ => Err(FsError::EntryNotFound),
+        }
+    }
     fn new_open_options(&self) -> OpenOptions<'_> {
         OpenOptions::new(self)
     }
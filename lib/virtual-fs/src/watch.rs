@@ -0,0 +1,412 @@
+//! Change notifications for file systems.
+//!
+//! A file system which supports watching keeps a [`WatchRegistry`] and
+//! reports every change it makes through [`WatchRegistry::notify`]. The
+//! registry hands the events over to the [`Watcher`]s interested in the
+//! changed path, which queue them until they are consumed.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+use virtual_mio::{InterestHandler, InterestType};
+
+/// Maximum number of events queued by a watcher, any further event is
+/// dropped until the queue is drained.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// The kind of change reported by a [`WatchEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchEventKind {
+    /// An entry was created.
+    Create,
+    /// The contents of a file were modified.
+    Modify,
+    /// An entry was removed.
+    Remove,
+    /// An entry was moved to [`WatchEvent::target`].
+    Rename,
+}
+
+/// A change made to a file system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    /// The path of the changed entry.
+    pub path: PathBuf,
+    /// The new path of a renamed entry.
+    pub target: Option<PathBuf>,
+    /// Whether the changed entry is a directory.
+    pub is_dir: bool,
+}
+
+impl WatchEvent {
+    pub fn new(kind: WatchEventKind, path: impl Into<PathBuf>, is_dir: bool) -> Self {
+        Self {
+            kind,
+            path: path.into(),
+            target: None,
+            is_dir,
+        }
+    }
+
+    pub fn rename(from: impl Into<PathBuf>, to: impl Into<PathBuf>, is_dir: bool) -> Self {
+        Self {
+            kind: WatchEventKind::Rename,
+            path: from.into(),
+            target: Some(to.into()),
+            is_dir,
+        }
+    }
+}
+
+/// Dispatches the changes made to a file system to its watchers.
+///
+/// Cloning a `WatchRegistry` returns a handle to the same set of watchers.
+#[derive(Debug, Clone, Default)]
+pub struct WatchRegistry {
+    watchers: Arc<Mutex<Vec<Weak<WatchQueue>>>>,
+}
+
+impl WatchRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a watcher for the changes to `path`, and to its children
+    /// (or all its descendants if `recursive` is set).
+    pub fn watch(&self, path: &Path, recursive: bool) -> Watcher {
+        let queue = Arc::new(WatchQueue {
+            path: path.to_path_buf(),
+            recursive,
+            state: Default::default(),
+        });
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|w| w.strong_count() > 0);
+        watchers.push(Arc::downgrade(&queue));
+
+        Watcher {
+            sources: vec![WatchSource {
+                queue,
+                prefix: None,
+                filter: None,
+            }],
+            handler: None,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Returns whether anything is watching the file system, which allows
+    /// skipping the work needed to report a change.
+    pub fn is_watched(&self) -> bool {
+        let watchers = self.watchers.lock().unwrap();
+        watchers.iter().any(|w| w.strong_count() > 0)
+    }
+
+    /// Reports a change to the watchers interested in it.
+    pub fn notify(&self, event: WatchEvent) {
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|watcher| match watcher.upgrade() {
+            Some(queue) => {
+                if queue.matches(&event.path)
+                    || event.target.as_deref().is_some_and(|t| queue.matches(t))
+                {
+                    queue.push(event.clone());
+                }
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+#[derive(Debug)]
+struct WatchQueue {
+    path: PathBuf,
+    recursive: bool,
+    state: Mutex<WatchQueueState>,
+}
+
+#[derive(Debug, Default)]
+struct WatchQueueState {
+    events: VecDeque<WatchEvent>,
+    overflowed: bool,
+    waker: Option<Waker>,
+    handler: Option<SharedInterestHandler>,
+}
+
+type SharedInterestHandler = Arc<Mutex<Box<dyn InterestHandler>>>;
+
+impl WatchQueue {
+    fn matches(&self, path: &Path) -> bool {
+        if self.recursive {
+            path.starts_with(&self.path)
+        } else {
+            path == self.path || path.parent() == Some(self.path.as_path())
+        }
+    }
+
+    fn push(&self, event: WatchEvent) {
+        let mut state = self.state.lock().unwrap();
+        if state.events.len() >= MAX_QUEUED_EVENTS {
+            state.overflowed = true;
+            return;
+        }
+        state.events.push_back(event);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        if let Some(handler) = state.handler.as_ref() {
+            handler
+                .lock()
+                .unwrap()
+                .push_interest(InterestType::Readable);
+        }
+    }
+}
+
+type EventFilter = Arc<dyn Fn(WatchEvent) -> Option<WatchEvent> + Send + Sync>;
+
+struct WatchSource {
+    queue: Arc<WatchQueue>,
+    /// Prepended to the paths of the events, for watchers of file systems
+    /// which are mounted somewhere else.
+    prefix: Option<PathBuf>,
+    /// Rewrites or drops the events before they are prefixed.
+    filter: Option<EventFilter>,
+}
+
+impl fmt::Debug for WatchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchSource")
+            .field("queue", &self.queue)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl WatchSource {
+    fn map(&self, mut event: WatchEvent) -> Option<WatchEvent> {
+        if let Some(filter) = self.filter.as_ref() {
+            event = filter(event)?;
+        }
+        if let Some(prefix) = self.prefix.as_deref() {
+            event.path = join_prefix(prefix, &event.path);
+            event.target = event.target.map(|t| join_prefix(prefix, &t));
+        }
+        Some(event)
+    }
+
+    /// Pops the next event, registering the waker of `cx` if there is
+    /// none.
+    fn pop(&self, cx: Option<&mut Context<'_>>) -> Option<WatchEvent> {
+        let mut state = self.queue.state.lock().unwrap();
+        while let Some(event) = state.events.pop_front() {
+            if let Some(event) = self.map(event) {
+                return Some(event);
+            }
+        }
+        if let Some(cx) = cx {
+            state.waker = Some(cx.waker().clone());
+        }
+        None
+    }
+}
+
+fn join_prefix(prefix: &Path, path: &Path) -> PathBuf {
+    prefix.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Receives the changes made to a watched path, see
+/// [`crate::FileSystem::watch`].
+///
+/// The watch stops when the watcher is dropped.
+pub struct Watcher {
+    sources: Vec<WatchSource>,
+    handler: Option<SharedInterestHandler>,
+    /// Resources which are needed for as long as the watch lasts.
+    guards: Vec<Box<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("sources", &self.sources)
+            .finish()
+    }
+}
+
+impl Watcher {
+    /// Combines two watchers, so that the events of both are received.
+    pub fn merge(mut self, other: Watcher) -> Self {
+        if let Some(handler) = self.handler.as_ref() {
+            for source in other.sources.iter() {
+                source.queue.state.lock().unwrap().handler = Some(handler.clone());
+            }
+        }
+        self.sources.extend(other.sources);
+        self.guards.extend(other.guards);
+        self
+    }
+
+    /// Prepends `prefix` to the paths of the events, which is used when
+    /// the watched file system is mounted at `prefix`.
+    pub fn prefixed(mut self, prefix: &Path) -> Self {
+        for source in self.sources.iter_mut() {
+            source.prefix = Some(match source.prefix.take() {
+                Some(inner) => join_prefix(prefix, &inner),
+                None => prefix.to_path_buf(),
+            });
+        }
+        self
+    }
+
+    /// Rewrites the events with `filter`, dropping those for which it
+    /// returns `None`.
+    pub fn filter_map(
+        mut self,
+        filter: impl Fn(WatchEvent) -> Option<WatchEvent> + Send + Sync + 'static,
+    ) -> Self {
+        let filter: EventFilter = Arc::new(filter);
+        for source in self.sources.iter_mut() {
+            source.filter = Some(match source.filter.take() {
+                Some(inner) => {
+                    let filter = filter.clone();
+                    Arc::new(move |event| filter(inner(event)?))
+                }
+                None => filter.clone(),
+            });
+        }
+        self
+    }
+
+    /// Keeps `guard` alive for as long as the watcher.
+    pub fn with_guard(mut self, guard: impl Any + Send + Sync) -> Self {
+        self.guards.push(Box::new(guard));
+        self
+    }
+
+    /// Returns the next queued event, if any.
+    pub fn next_event(&mut self) -> Option<WatchEvent> {
+        self.sources.iter().find_map(|source| source.pop(None))
+    }
+
+    /// Waits for the next event.
+    pub fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<WatchEvent> {
+        for source in self.sources.iter() {
+            if let Some(event) = source.pop(Some(cx)) {
+                return Poll::Ready(event);
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Returns whether events have been dropped because too many were
+    /// queued, and resets the flag.
+    pub fn take_overflowed(&mut self) -> bool {
+        let mut overflowed = false;
+        for source in self.sources.iter() {
+            let mut state = source.queue.state.lock().unwrap();
+            overflowed |= std::mem::take(&mut state.overflowed);
+        }
+        overflowed
+    }
+
+    /// Sets the handler notified as `Readable` when events are queued.
+    pub fn set_interest_handler(&mut self, handler: Box<dyn InterestHandler>) {
+        let handler = Arc::new(Mutex::new(handler));
+        let mut has_events = false;
+        for source in self.sources.iter() {
+            let mut state = source.queue.state.lock().unwrap();
+            state.handler = Some(handler.clone());
+            has_events |= !state.events.is_empty();
+        }
+        if has_events {
+            handler
+                .lock()
+                .unwrap()
+                .push_interest(InterestType::Readable);
+        }
+        self.handler = Some(handler);
+    }
+
+    pub fn remove_interest_handler(&mut self) {
+        for source in self.sources.iter() {
+            source.queue.state.lock().unwrap().handler = None;
+        }
+        self.handler = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_filtered_by_path() {
+        let registry = WatchRegistry::new();
+        assert!(!registry.is_watched());
+
+        let mut dir = registry.watch(Path::new("/dir"), false);
+        let mut all = registry.watch(Path::new("/"), true);
+        assert!(registry.is_watched());
+
+        registry.notify(WatchEvent::new(
+            WatchEventKind::Create,
+            "/dir/file.txt",
+            false,
+        ));
+        registry.notify(WatchEvent::new(
+            WatchEventKind::Modify,
+            "/dir/sub/file.txt",
+            false,
+        ));
+        registry.notify(WatchEvent::rename("/other.txt", "/dir/moved.txt", false));
+
+        assert_eq!(
+            dir.next_event(),
+            Some(WatchEvent::new(
+                WatchEventKind::Create,
+                "/dir/file.txt",
+                false
+            ))
+        );
+        assert_eq!(
+            dir.next_event(),
+            Some(WatchEvent::rename("/other.txt", "/dir/moved.txt", false))
+        );
+        assert_eq!(dir.next_event(), None);
+        assert_eq!(std::iter::from_fn(|| all.next_event()).count(), 3);
+
+        drop(dir);
+        drop(all);
+        assert!(!registry.is_watched());
+    }
+
+    #[test]
+    fn merged_and_prefixed_watchers() {
+        let first = WatchRegistry::new();
+        let second = WatchRegistry::new();
+        let mut watcher = first.watch(Path::new("/"), true).merge(
+            second
+                .watch(Path::new("/"), true)
+                .prefixed(Path::new("/mnt")),
+        );
+
+        first.notify(WatchEvent::new(WatchEventKind::Remove, "/a", true));
+        second.notify(WatchEvent::new(WatchEventKind::Create, "/b", false));
+
+        assert_eq!(
+            watcher.next_event(),
+            Some(WatchEvent::new(WatchEventKind::Remove, "/a", true))
+        );
+        assert_eq!(
+            watcher.next_event(),
+            Some(WatchEvent::new(WatchEventKind::Create, "/mnt/b", false))
+        );
+    }
+}
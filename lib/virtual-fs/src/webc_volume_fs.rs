@@ -461,6 +461,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 6148,
+                    ..Default::default()
                 }),
             },
             DirEntry {
@@ -474,6 +475,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 0,
+                    ..Default::default()
                 }),
             },
             DirEntry {
@@ -487,6 +489,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 4694941,
+                    ..Default::default()
                 }),
             },
             DirEntry {
@@ -500,6 +503,7 @@ mod tests {
                     created: 0,
                     modified,
                     len: 0,
+                    ..Default::default()
                 }),
             },
         ];
@@ -524,6 +528,7 @@ mod tests {
             created: 0,
             modified,
             len: 4694941,
+            ..Default::default()
         };
         assert_eq!(
             fs.metadata("/lib/python.wasm".as_ref()).unwrap(),
@@ -550,6 +555,7 @@ mod tests {
                 created: 0,
                 modified,
                 len: 0,
+                ..Default::default()
             },
         );
         assert_eq!(
@@ -0,0 +1,787 @@
+//! Records the traffic of a [`VirtualNetworking`] into a pcapng file.
+//!
+//! Stream and datagram sockets only see payloads, so the IP, TCP and UDP
+//! headers of the recorded packets are synthesized from the endpoints of
+//! the sockets. TCP sequence numbers are relative to the start of each
+//! connection and a handshake is synthesized when it is opened. Raw
+//! sockets are recorded as Ethernet frames, ICMP and Unix sockets are not
+//! recorded.
+
+mod pcapng;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use self::pcapng::{
+    INTERFACE_ETHERNET, INTERFACE_IP, MAX_PAYLOAD, PcapngWriter, TCP_ACK, TCP_FIN, TCP_PSH,
+    TCP_SYN, tcp_packet, udp_packet,
+};
+use crate::{
+    DynVirtualNetworking, InterestHandler, IpCidr, IpRoute, Result, SocketStatus, StreamSecurity,
+    VirtualConnectedSocket, VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource,
+    VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket,
+    VirtualUdpSocket, VirtualUnixDatagramSocket,
+};
+
+/// Shared handle on the capture file
+#[derive(Clone)]
+struct Capture {
+    writer: Arc<Mutex<PcapngWriter>>,
+}
+
+impl std::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+impl Capture {
+    fn write(&self, interface: u32, data: &[u8], original_len: usize) {
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writer.write_packet(interface, data, original_len) {
+            tracing::debug!(%err, "failed to write to the network capture");
+        }
+    }
+
+    fn frame(&self, data: &[u8]) {
+        self.write(INTERFACE_ETHERNET, data, data.len());
+    }
+
+    fn datagram(&self, src: SocketAddr, dst: SocketAddr, payload: &[u8]) {
+        let captured = &payload[..payload.len().min(MAX_PAYLOAD)];
+        let packet = udp_packet(src, dst, captured);
+        let original_len = packet.len() + payload.len() - captured.len();
+        self.write(INTERFACE_IP, &packet, original_len);
+    }
+}
+
+/// Networking that records the traffic of the sockets it hands out into a
+/// pcapng file, on top of any other networking
+#[derive(Debug, Clone)]
+pub struct CapturingNetworking {
+    inner: DynVirtualNetworking,
+    capture: Capture,
+}
+
+impl CapturingNetworking {
+    /// Records the traffic of `inner` into `out`
+    pub fn new(inner: DynVirtualNetworking, out: impl Write + Send + 'static) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            capture: Capture {
+                writer: Arc::new(Mutex::new(PcapngWriter::new(Box::new(out))?)),
+            },
+        })
+    }
+
+    /// Records the traffic of `inner` into a new file at `path`
+    pub fn create(inner: DynVirtualNetworking, path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(inner, File::create(path)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for CapturingNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let inner = self.inner.bind_raw().await?;
+        Ok(Box::new(CapturingRawSocket {
+            inner,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let inner = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(CapturingTcpListener {
+            inner,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let inner = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(CapturingUdpSocket {
+            inner,
+            capture: self.capture.clone(),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let inner = self.inner.connect_tcp(addr, peer).await?;
+        Ok(Box::new(CapturingTcpSocket::new(
+            inner,
+            self.capture.clone(),
+            peer,
+            true,
+        )))
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+#[derive(Debug)]
+struct CapturingTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    capture: Capture,
+}
+
+impl VirtualIoSource for CapturingTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for CapturingTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (socket, peer) = self.inner.try_accept()?;
+        let socket = CapturingTcpSocket::new(socket, self.capture.clone(), peer, false);
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// The synthesized TCP connection of a stream socket
+#[derive(Debug)]
+struct TcpFlow {
+    local: SocketAddr,
+    peer: SocketAddr,
+    local_seq: u32,
+    peer_seq: u32,
+    local_fin: bool,
+    peer_fin: bool,
+}
+
+impl TcpFlow {
+    fn segment(&mut self, capture: &Capture, outgoing: bool, flags: u8, payload: &[u8]) {
+        let (src, dst, seq, ack) = if outgoing {
+            (self.local, self.peer, self.local_seq, self.peer_seq)
+        } else {
+            (self.peer, self.local, self.peer_seq, self.local_seq)
+        };
+        let packet = tcp_packet(src, dst, seq, ack, flags, payload);
+        capture.write(INTERFACE_IP, &packet, packet.len());
+
+        let mut len = payload.len() as u32;
+        if flags & (TCP_SYN | TCP_FIN) != 0 {
+            len += 1;
+        }
+        if outgoing {
+            self.local_seq = self.local_seq.wrapping_add(len);
+        } else {
+            self.peer_seq = self.peer_seq.wrapping_add(len);
+        }
+    }
+
+    fn data(&mut self, capture: &Capture, outgoing: bool, data: &[u8]) {
+        for chunk in data.chunks(MAX_PAYLOAD) {
+            self.segment(capture, outgoing, TCP_PSH | TCP_ACK, chunk);
+        }
+    }
+
+    fn fin(&mut self, capture: &Capture, outgoing: bool) {
+        let done = if outgoing {
+            &mut self.local_fin
+        } else {
+            &mut self.peer_fin
+        };
+        if !std::mem::replace(done, true) {
+            self.segment(capture, outgoing, TCP_FIN | TCP_ACK, &[]);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CapturingTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    capture: Capture,
+    flow: TcpFlow,
+}
+
+impl CapturingTcpSocket {
+    fn new(
+        inner: Box<dyn VirtualTcpSocket + Sync>,
+        capture: Capture,
+        peer: SocketAddr,
+        outgoing: bool,
+    ) -> Self {
+        let unspecified = match peer.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let local = inner
+            .addr_local()
+            .unwrap_or(SocketAddr::new(unspecified, 0));
+        let mut flow = TcpFlow {
+            local,
+            peer,
+            local_seq: 0,
+            peer_seq: 0,
+            local_fin: false,
+            peer_fin: false,
+        };
+        flow.segment(&capture, outgoing, TCP_SYN, &[]);
+        flow.segment(&capture, !outgoing, TCP_SYN | TCP_ACK, &[]);
+        flow.segment(&capture, outgoing, TCP_ACK, &[]);
+        Self {
+            inner,
+            capture,
+            flow,
+        }
+    }
+}
+
+impl Drop for CapturingTcpSocket {
+    fn drop(&mut self) {
+        self.flow.fin(&self.capture, true);
+    }
+}
+
+impl VirtualIoSource for CapturingTcpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for CapturingTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for CapturingTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let amt = self.inner.try_send(data)?;
+        self.flow.data(&self.capture, true, &data[..amt]);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()?;
+        self.flow.fin(&self.capture, true);
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let amt = self.inner.try_recv(buf, peek)?;
+        if peek {
+            return Ok(amt);
+        }
+        if amt == 0 {
+            self.flow.fin(&self.capture, false);
+        } else {
+            // SAFETY: the inner socket initialized the first `amt` bytes
+            let data = unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+            self.flow.data(&self.capture, false, data);
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for CapturingTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.inner.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)?;
+        if how != Shutdown::Read {
+            self.flow.fin(&self.capture, true);
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+struct CapturingUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    capture: Capture,
+}
+
+impl CapturingUdpSocket {
+    fn local(&self, peer: SocketAddr) -> SocketAddr {
+        self.inner.addr_local().unwrap_or_else(|_| match peer {
+            SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+        })
+    }
+}
+
+impl VirtualIoSource for CapturingUdpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for CapturingUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectionlessSocket for CapturingUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let amt = self.inner.try_send_to(data, addr)?;
+        self.capture.datagram(self.local(addr), addr, &data[..amt]);
+        Ok(amt)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let (amt, addr) = self.inner.try_recv_from(buf, peek)?;
+        if !peek {
+            // SAFETY: the inner socket initialized the first `amt` bytes
+            let data = unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+            self.capture.datagram(addr, self.local(addr), data);
+        }
+        Ok((amt, addr))
+    }
+}
+
+impl VirtualUdpSocket for CapturingUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+#[derive(Debug)]
+struct CapturingRawSocket {
+    inner: Box<dyn VirtualRawSocket + Sync>,
+    capture: Capture,
+}
+
+impl VirtualIoSource for CapturingRawSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for CapturingRawSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualRawSocket for CapturingRawSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let amt = self.inner.try_send(data)?;
+        self.capture.frame(&data[..amt]);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let amt = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            // SAFETY: the inner socket initialized the first `amt` bytes
+            let data = unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+            self.capture.frame(data);
+        }
+        Ok(amt)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LoopbackNetworking, VirtualConnectedSocketExt, VirtualTcpListenerExt};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Splits a pcapng file into its blocks
+    fn blocks(data: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut blocks = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let block_type = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+            let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+            assert_eq!(&rest[len - 4..len], &rest[4..8]);
+            blocks.push((block_type, &rest[8..len - 4]));
+            rest = &rest[len..];
+        }
+        blocks
+    }
+
+    #[tokio::test]
+    async fn captures_loopback_tcp() {
+        let loopback = LoopbackNetworking::new();
+        let out = SharedBuf::default();
+        let net = CapturingNetworking::new(Arc::new(loopback.clone()), out.clone()).unwrap();
+
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let mut client = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        client.send(b"ping").await.unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 4);
+        server.send(b"pong!").await.unwrap();
+        drop(server);
+
+        let data = out.0.lock().unwrap().clone();
+        let blocks = blocks(&data);
+        assert_eq!(blocks[0].0, 0x0A0D_0D0A);
+        assert_eq!(blocks[1].0, 1);
+        assert_eq!(blocks[2].0, 1);
+
+        // Handshake, the two payloads and the FIN of the server
+        let packets = blocks[3..]
+            .iter()
+            .map(|(block_type, body)| {
+                assert_eq!(*block_type, 6);
+                let captured = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+                &body[20..20 + captured]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(packets.len(), 6);
+        assert_eq!(&packets[3][40..], b"ping");
+        assert_eq!(&packets[4][40..], b"pong!");
+        assert_eq!(packets[5][33], TCP_FIN | TCP_ACK);
+
+        // The request flows from the client to the listening port
+        assert_eq!(u16::from_be_bytes([packets[3][22], packets[3][23]]), 8080);
+        assert_eq!(u16::from_be_bytes([packets[4][20], packets[4][21]]), 8080);
+    }
+}
@@ -0,0 +1,240 @@
+//! Writes pcapng files and synthesizes the IP, TCP and UDP headers of the
+//! payloads seen by the sockets.
+
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+
+/// Interface of the packets synthesized for stream and datagram sockets
+pub(super) const INTERFACE_IP: u32 = 0;
+/// Interface of the frames of raw sockets
+pub(super) const INTERFACE_ETHERNET: u32 = 1;
+
+pub(super) const TCP_FIN: u8 = 0x01;
+pub(super) const TCP_SYN: u8 = 0x02;
+pub(super) const TCP_PSH: u8 = 0x08;
+pub(super) const TCP_ACK: u8 = 0x10;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// Largest payload that fits in a synthesized packet along with its headers
+pub(super) const MAX_PAYLOAD: usize = 65_000;
+
+fn pad4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Writes the blocks of a pcapng section
+pub(super) struct PcapngWriter {
+    out: Box<dyn Write + Send>,
+}
+
+impl PcapngWriter {
+    /// Writes the section header along with the interfaces the packets are
+    /// recorded on
+    pub fn new(mut out: Box<dyn Write + Send>) -> io::Result<Self> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut out, BLOCK_SECTION_HEADER, &body)?;
+
+        for (linktype, name) in [(LINKTYPE_RAW, "ip"), (LINKTYPE_ETHERNET, "raw")] {
+            let mut body = Vec::new();
+            body.extend_from_slice(&linktype.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            body.extend_from_slice(&0u32.to_le_bytes());
+            write_option(&mut body, OPT_IF_NAME, name.as_bytes());
+            write_option(&mut body, OPT_END, &[]);
+            write_block(&mut out, BLOCK_INTERFACE_DESCRIPTION, &body)?;
+        }
+        out.flush()?;
+        Ok(Self { out })
+    }
+
+    /// Records a packet, `original_len` is larger than the data when the
+    /// packet was truncated
+    pub fn write_packet(
+        &mut self,
+        interface: u32,
+        data: &[u8],
+        original_len: usize,
+    ) -> io::Result<()> {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let mut body = Vec::with_capacity(20 + pad4(data.len()));
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(micros as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(original_len as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize(pad4(body.len()), 0);
+        write_block(&mut self.out, BLOCK_ENHANCED_PACKET, &body)?;
+        self.out.flush()
+    }
+}
+
+fn write_block(out: &mut dyn Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total_len = (12 + body.len()) as u32;
+    let mut block = Vec::with_capacity(total_len as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total_len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&total_len.to_le_bytes());
+    out.write_all(&block)
+}
+
+fn write_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(pad4(body.len()), 0);
+}
+
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    for chunk in chunks {
+        for pair in chunk.chunks(2) {
+            let word = match pair {
+                [hi, lo] => u16::from_be_bytes([*hi, *lo]),
+                [hi] => u16::from_be_bytes([*hi, 0]),
+                _ => unreachable!(),
+            };
+            sum += word as u32;
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Wraps a TCP or UDP segment into an IP packet. Mixed address families are
+/// both written as IPv6 addresses.
+fn ip_packet(
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    mut segment: Vec<u8>,
+    csum_at: usize,
+) -> Vec<u8> {
+    let len = segment.len();
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut pseudo = Vec::with_capacity(12);
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&[0, protocol]);
+            pseudo.extend_from_slice(&(len as u16).to_be_bytes());
+            let csum = checksum(&[&pseudo, &segment]);
+            segment[csum_at..csum_at + 2].copy_from_slice(&csum.to_be_bytes());
+
+            let mut packet = Vec::with_capacity(20 + len);
+            packet.extend_from_slice(&[0x45, 0]);
+            packet.extend_from_slice(&((20 + len) as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            let csum = checksum(&[&packet]);
+            packet[10..12].copy_from_slice(&csum.to_be_bytes());
+            packet.extend_from_slice(&segment);
+            packet
+        }
+        (src, dst) => {
+            let to_v6 = |ip: IpAddr| match ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                IpAddr::V6(ip) => ip,
+            };
+            let (src, dst) = (to_v6(src), to_v6(dst));
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&(len as u32).to_be_bytes());
+            pseudo.extend_from_slice(&[0, 0, 0, protocol]);
+            let csum = checksum(&[&pseudo, &segment]);
+            segment[csum_at..csum_at + 2].copy_from_slice(&csum.to_be_bytes());
+
+            let mut packet = Vec::with_capacity(40 + len);
+            packet.extend_from_slice(&[0x60, 0, 0, 0]);
+            packet.extend_from_slice(&(len as u16).to_be_bytes());
+            packet.extend_from_slice(&[protocol, 64]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            packet.extend_from_slice(&segment);
+            packet
+        }
+    }
+}
+
+/// Synthesizes a TCP segment, `payload` must not be larger than
+/// [`MAX_PAYLOAD`]
+pub(super) fn tcp_packet(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.extend_from_slice(&[5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    segment.extend_from_slice(payload);
+    ip_packet(src.ip(), dst.ip(), IPPROTO_TCP, segment, 16)
+}
+
+/// Synthesizes a UDP datagram, `payload` must not be larger than
+/// [`MAX_PAYLOAD`]
+pub(super) fn udp_packet(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(8 + payload.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    segment.extend_from_slice(&[0, 0]);
+    segment.extend_from_slice(payload);
+    ip_packet(src.ip(), dst.ip(), IPPROTO_UDP, segment, 6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthesized_checksums_verify() {
+        let src: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        let dst: SocketAddr = "10.0.0.2:80".parse().unwrap();
+        let packet = tcp_packet(src, dst, 1, 1, TCP_PSH | TCP_ACK, b"hello");
+        assert_eq!(packet.len(), 20 + 20 + 5);
+        assert_eq!(checksum(&[&packet[..20]]), 0);
+
+        let mut pseudo = Vec::new();
+        pseudo.extend_from_slice(&packet[12..20]);
+        pseudo.extend_from_slice(&[0, IPPROTO_TCP, 0, 25]);
+        assert_eq!(checksum(&[&pseudo, &packet[20..]]), 0);
+
+        let v6: SocketAddr = "[::1]:53".parse().unwrap();
+        let packet = udp_packet(v6, src, b"odd");
+        assert_eq!(packet[0] >> 4, 6);
+        assert_eq!(packet.len(), 40 + 8 + 3);
+    }
+}
@@ -0,0 +1,652 @@
+//! Injects faults into the traffic of a [`VirtualNetworking`] and shapes it.
+//!
+//! A [`FaultRule`] describes the faults to apply and which traffic they
+//! apply to, the traffic is matched with the syntax of
+//! [`Ruleset`](crate::ruleset::Ruleset) where the sockets and domains that
+//! the ruleset allows are the ones affected by the rule.
+//!
+//! ## Rule Specification
+//! ```text
+//! <faults>[@<ruleset>]
+//!
+//! <faults>: <fault> | <fault>,<faults>
+//! <fault>:
+//!     latency=<duration>      delays the data received by the guest
+//!     jitter=<duration>       varies the latency by up to this much
+//!     bandwidth=<rate>        caps each direction, in bytes per second
+//!     loss=<probability>      drops UDP datagrams
+//!     reset=<probability>     resets a TCP connection on a send or receive
+//!     dns-fail[=<probability>]  fails DNS queries
+//!     partition               cuts the traffic off entirely
+//!
+//! <duration>: <number>us | <number>ms | <number>s
+//! <rate>: <number> | <number>k | <number>m | <number>g
+//! ```
+//!
+//! For example, the following adds 100ms of latency to the traffic with
+//! `10.0.0.0/8` and drops a tenth of its datagrams:
+//! ```text
+//! latency=100ms,jitter=20ms,loss=0.1@ipv4:allow=10.0.0.0/8:*
+//! ```
+//!
+//! Rules without a ruleset apply to all the traffic. When several rules
+//! match, the first one wins. The random decisions come from a seeded
+//! generator so that a run can be reproduced by using the same seed.
+
+mod socket;
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use virtual_mio::InterestType;
+
+use self::socket::{FaultyTcpListener, FaultyTcpSocket, FaultyUdpSocket};
+use crate::ruleset::{Direction, RuleParseError, Ruleset};
+use crate::shaping::{Bucket, Delay, Notify};
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket,
+};
+
+/// Represents the errors that could happen while parsing a [`FaultRule`]
+#[derive(Debug, thiserror::Error)]
+pub enum FaultParseError {
+    #[error("unknown fault: {0}")]
+    UnknownFault(String),
+    #[error("invalid duration: {0}")]
+    InvalidDuration(String),
+    #[error("invalid rate: {0}")]
+    InvalidRate(String),
+    #[error("invalid probability: {0}, it must be between 0 and 1")]
+    InvalidProbability(String),
+    #[error("invalid ruleset: {0}")]
+    Ruleset(#[from] RuleParseError),
+}
+
+/// Whether the traffic matched by a rule is cut off, this can be changed
+/// while the sockets are open
+#[derive(Debug, Default)]
+struct Partition {
+    active: AtomicBool,
+    /// Sockets that are stalled by the partition
+    stalled: Mutex<Vec<Weak<Notify>>>,
+}
+
+impl Partition {
+    fn stall(&self, notify: &Arc<Notify>) {
+        let mut stalled = self.stalled.lock().unwrap();
+        stalled.retain(|s| s.strong_count() > 0);
+        if !stalled.iter().any(|s| s.as_ptr() == Arc::as_ptr(notify)) {
+            stalled.push(Arc::downgrade(notify));
+        }
+    }
+
+    fn set(&self, active: bool) {
+        self.active.store(active, Ordering::SeqCst);
+        if !active {
+            let stalled = std::mem::take(&mut *self.stalled.lock().unwrap());
+            for notify in stalled.iter().filter_map(Weak::upgrade) {
+                notify.notify(InterestType::Readable);
+                notify.notify(InterestType::Writable);
+            }
+        }
+    }
+}
+
+/// The faults applied to the traffic that matches a ruleset
+#[derive(Debug, Clone, Default)]
+pub struct FaultRule {
+    matcher: Option<Ruleset>,
+    latency: Duration,
+    jitter: Duration,
+    bandwidth: Option<u64>,
+    loss: f64,
+    reset: f64,
+    dns_failure: f64,
+    partition: Arc<Partition>,
+}
+
+impl FaultRule {
+    /// Creates a rule that applies to all the traffic and has no faults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the rule to the sockets and domains that `ruleset` allows
+    pub fn with_matcher(mut self, ruleset: Ruleset) -> Self {
+        self.matcher = Some(ruleset);
+        self
+    }
+
+    /// Delays the data that the guest receives
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Varies the latency by up to `jitter` in either direction
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Caps the traffic of each direction to `bytes_per_sec`
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Drops UDP datagrams with the given probability
+    pub fn with_loss(mut self, probability: f64) -> Self {
+        self.loss = probability;
+        self
+    }
+
+    /// Resets TCP connections on a send or receive with the given probability
+    pub fn with_resets(mut self, probability: f64) -> Self {
+        self.reset = probability;
+        self
+    }
+
+    /// Fails DNS queries with the given probability
+    pub fn with_dns_failures(mut self, probability: f64) -> Self {
+        self.dns_failure = probability;
+        self
+    }
+
+    /// Cuts the matching traffic off
+    pub fn with_partition(self, partitioned: bool) -> Self {
+        self.set_partitioned(partitioned);
+        self
+    }
+
+    /// Starts or heals the partition, which is shared by all the clones of
+    /// this rule
+    pub fn set_partitioned(&self, partitioned: bool) {
+        self.partition.set(partitioned);
+    }
+
+    pub fn is_partitioned(&self) -> bool {
+        self.partition.active.load(Ordering::SeqCst)
+    }
+
+    fn matches_socket(&self, addr: SocketAddr, dir: Direction) -> bool {
+        self.matcher
+            .as_ref()
+            .is_none_or(|ruleset| ruleset.allows_socket(addr, dir))
+    }
+
+    fn matches_domain(&self, domain: &str) -> bool {
+        self.matcher
+            .as_ref()
+            .is_none_or(|ruleset| ruleset.allows_domain(domain))
+    }
+}
+
+fn parse_duration(s: &str) -> std::result::Result<Duration, FaultParseError> {
+    let err = || FaultParseError::InvalidDuration(s.to_string());
+    let (value, unit) = if let Some(value) = s.strip_suffix("us") {
+        (value, 1e-6)
+    } else if let Some(value) = s.strip_suffix("ms") {
+        (value, 1e-3)
+    } else if let Some(value) = s.strip_suffix('s') {
+        (value, 1.0)
+    } else {
+        return Err(err());
+    };
+    let value = value.parse::<f64>().map_err(|_| err())?;
+    Duration::try_from_secs_f64(value * unit).map_err(|_| err())
+}
+
+fn parse_rate(s: &str) -> std::result::Result<u64, FaultParseError> {
+    let (value, unit) = match s.char_indices().last() {
+        Some((idx, 'k' | 'K')) => (&s[..idx], 1_000),
+        Some((idx, 'm' | 'M')) => (&s[..idx], 1_000_000),
+        Some((idx, 'g' | 'G')) => (&s[..idx], 1_000_000_000),
+        _ => (s, 1),
+    };
+    match value.parse::<u64>() {
+        Ok(value) if value > 0 => Ok(value * unit),
+        _ => Err(FaultParseError::InvalidRate(s.to_string())),
+    }
+}
+
+fn parse_probability(s: &str) -> std::result::Result<f64, FaultParseError> {
+    match s.parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(FaultParseError::InvalidProbability(s.to_string())),
+    }
+}
+
+impl FromStr for FaultRule {
+    type Err = FaultParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let (faults, matcher) = match s.split_once('@') {
+            Some((faults, ruleset)) => (faults, Some(Ruleset::from_str(ruleset)?)),
+            None => (s.as_str(), None),
+        };
+
+        let mut rule = FaultRule {
+            matcher,
+            ..Default::default()
+        };
+        for fault in faults.split(',').filter(|f| !f.is_empty()) {
+            let (name, value) = match fault.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (fault, None),
+            };
+            match (name, value) {
+                ("latency", Some(v)) => rule.latency = parse_duration(v)?,
+                ("jitter", Some(v)) => rule.jitter = parse_duration(v)?,
+                ("bandwidth", Some(v)) => rule.bandwidth = Some(parse_rate(v)?),
+                ("loss", Some(v)) => rule.loss = parse_probability(v)?,
+                ("reset", Some(v)) => rule.reset = parse_probability(v)?,
+                ("dns-fail", Some(v)) => rule.dns_failure = parse_probability(v)?,
+                ("dns-fail", None) => rule.dns_failure = 1.0,
+                ("partition", None) => rule.set_partitioned(true),
+                _ => return Err(FaultParseError::UnknownFault(fault.to_string())),
+            }
+        }
+        Ok(rule)
+    }
+}
+
+/// The rules applied by a [`FaultyNetworking`] along with the seed of its
+/// random decisions
+#[derive(Debug, Clone, Default)]
+pub struct NetworkFaults {
+    rules: Vec<FaultRule>,
+    seed: u64,
+}
+
+impl NetworkFaults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule, which is only used for the traffic that the previous
+    /// rules do not match
+    pub fn with_rule(mut self, rule: FaultRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn rules(&self) -> &[FaultRule] {
+        &self.rules
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// Deterministic random numbers (splitmix64)
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..1`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+
+    fn latency(&mut self, rule: &FaultRule) -> Duration {
+        if rule.jitter.is_zero() {
+            return rule.latency;
+        }
+        let offset = rule.jitter.mul_f64(2.0 * self.next_f64());
+        (rule.latency + offset).saturating_sub(rule.jitter)
+    }
+}
+
+/// A rule along with the bandwidth left to the traffic it matches
+#[derive(Debug)]
+struct ActiveRule {
+    rule: FaultRule,
+    upload: Option<Mutex<Bucket>>,
+    download: Option<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct FaultState {
+    rules: Vec<Arc<ActiveRule>>,
+    seed: u64,
+    /// Used to give each socket its own random numbers
+    sockets: AtomicU64,
+    rng: Mutex<Rng>,
+}
+
+impl FaultState {
+    fn rule_for(&self, addr: SocketAddr, dir: Direction) -> Option<Arc<ActiveRule>> {
+        self.rules
+            .iter()
+            .find(|active| active.rule.matches_socket(addr, dir))
+            .cloned()
+    }
+
+    fn socket_rng(&self) -> Rng {
+        let socket = self.sockets.fetch_add(1, Ordering::Relaxed);
+        Rng(self.seed ^ socket.wrapping_mul(0xD1B5_4A32_D192_ED03))
+    }
+}
+
+/// Networking that injects faults into the traffic of another networking
+/// and shapes it, as described by [`NetworkFaults`]
+#[derive(Debug, Clone)]
+pub struct FaultyNetworking {
+    inner: DynVirtualNetworking,
+    state: Arc<FaultState>,
+}
+
+impl FaultyNetworking {
+    pub fn new(inner: DynVirtualNetworking, faults: NetworkFaults) -> Self {
+        let rules = faults
+            .rules
+            .into_iter()
+            .map(|rule| {
+                Arc::new(ActiveRule {
+                    upload: rule.bandwidth.map(|rate| Mutex::new(Bucket::new(rate))),
+                    download: rule.bandwidth.map(|rate| Mutex::new(Bucket::new(rate))),
+                    rule,
+                })
+            })
+            .collect();
+        Self {
+            inner,
+            state: Arc::new(FaultState {
+                rules,
+                seed: faults.seed,
+                sockets: AtomicU64::new(0),
+                rng: Mutex::new(Rng(faults.seed)),
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for FaultyNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let inner = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(FaultyTcpListener::new(inner, self.state.clone())))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let inner = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(FaultyUdpSocket::new(inner, self.state.clone())))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let Some(rule) = self.state.rule_for(peer, Direction::Outbound) else {
+            return self.inner.connect_tcp(addr, peer).await;
+        };
+        if rule.rule.is_partitioned() {
+            return Err(NetworkError::TimedOut);
+        }
+        let latency = self.state.rng.lock().unwrap().latency(&rule.rule);
+        Delay::new(latency).await;
+
+        let inner = self.inner.connect_tcp(addr, peer).await?;
+        let socket = FaultyTcpSocket::new(inner, self.state.clone(), Some(rule));
+        Ok(Box::new(socket))
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        let rule = self
+            .state
+            .rules
+            .iter()
+            .find(|active| active.rule.matches_domain(host));
+        if let Some(rule) = rule {
+            let (latency, fail) = {
+                let mut rng = self.state.rng.lock().unwrap();
+                (rng.latency(&rule.rule), rng.chance(rule.rule.dns_failure))
+            };
+            Delay::new(latency).await;
+            if fail || rule.rule.is_partitioned() {
+                tracing::debug!(%host, "injected dns failure");
+                return Err(NetworkError::TimedOut);
+            }
+        }
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{LoopbackNetworking, VirtualConnectedSocketExt, VirtualTcpListenerExt};
+
+    #[test]
+    fn parse_fault_rules() {
+        let rule: FaultRule =
+            "latency=100ms, jitter=1.5ms, bandwidth=64k, loss=0.25, dns-fail @ ipv4:allow=10.0.0.0/8:*"
+                .parse()
+                .unwrap();
+        assert_eq!(rule.latency, Duration::from_millis(100));
+        assert_eq!(rule.jitter, Duration::from_micros(1500));
+        assert_eq!(rule.bandwidth, Some(64_000));
+        assert_eq!(rule.loss, 0.25);
+        assert_eq!(rule.dns_failure, 1.0);
+        assert!(!rule.is_partitioned());
+        assert!(rule.matches_socket("10.1.2.3:80".parse().unwrap(), Direction::Outbound));
+        assert!(!rule.matches_socket("192.168.1.1:80".parse().unwrap(), Direction::Outbound));
+
+        let rule: FaultRule = "partition".parse().unwrap();
+        assert!(rule.is_partitioned());
+        assert!(rule.matches_domain("example.com"));
+
+        assert!("loss=2".parse::<FaultRule>().is_err());
+        assert!("latency=10".parse::<FaultRule>().is_err());
+        assert!("teleport".parse::<FaultRule>().is_err());
+        assert!("reset=0.1@ipv4:maybe=*:*".parse::<FaultRule>().is_err());
+    }
+
+    #[test]
+    fn seeded_decisions_are_reproducible() {
+        let rule = FaultRule::new()
+            .with_latency(Duration::from_millis(50))
+            .with_jitter(Duration::from_millis(10));
+        let sample = |seed| {
+            let mut rng = Rng(seed);
+            (0..32)
+                .map(|_| (rng.latency(&rule), rng.chance(0.5)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+        assert!(sample(7).iter().all(|(latency, _)| {
+            *latency >= Duration::from_millis(40) && *latency <= Duration::from_millis(60)
+        }));
+    }
+
+    #[tokio::test]
+    async fn latency_resets_and_partitions() {
+        let loopback = LoopbackNetworking::new();
+        let slow = FaultRule::new().with_latency(Duration::from_millis(100));
+        let net = FaultyNetworking::new(
+            Arc::new(loopback.clone()),
+            NetworkFaults::new().with_rule(slow.clone()),
+        );
+
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let mut client = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        // The data only shows up once the latency has passed
+        let start = Instant::now();
+        client.send(b"ping").await.unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 4);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // A partition stalls the connection until it heals
+        slow.set_partitioned(true);
+        client.send(b"pong").await.unwrap();
+        assert_eq!(
+            server.try_recv(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        slow.set_partitioned(false);
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 4);
+
+        let net = FaultyNetworking::new(
+            Arc::new(loopback.clone()),
+            NetworkFaults::new().with_rule(FaultRule::new().with_resets(1.0)),
+        );
+        let addr: SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let _client = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        assert_eq!(
+            server.try_send(b"hello").unwrap_err(),
+            NetworkError::ConnectionReset
+        );
+        assert_eq!(
+            server.try_recv(&mut buf, false).unwrap_err(),
+            NetworkError::ConnectionReset
+        );
+    }
+}
@@ -0,0 +1,620 @@
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use virtual_mio::InterestType;
+
+use super::{ActiveRule, FaultState, Rng};
+use crate::ruleset::Direction;
+use crate::shaping::{Bucket, ForwardingHandler, Waking};
+use crate::{
+    InterestHandler, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket, copy_to_uninit,
+};
+
+/// Data read from the inner socket is held back until its latency passed,
+/// up to this much of it
+const MAX_DELAYED_BYTES: usize = 1024 * 1024;
+/// Datagrams that are held back beyond this are dropped
+const MAX_DELAYED_DATAGRAMS: usize = 1024;
+const READ_CHUNK: usize = 64 * 1024;
+
+fn take(bucket: &Option<std::sync::Mutex<Bucket>>, amt: usize) -> usize {
+    match bucket {
+        Some(bucket) => bucket.lock().unwrap().take(amt),
+        None => amt,
+    }
+}
+
+fn refund(bucket: &Option<std::sync::Mutex<Bucket>>, amt: usize) {
+    if let Some(bucket) = bucket {
+        bucket.lock().unwrap().refund(amt);
+    }
+}
+
+/// Returns when the bucket has tokens again, if it is out of them
+fn exhausted(bucket: &Option<std::sync::Mutex<Bucket>>) -> Option<Instant> {
+    let mut bucket = bucket.as_ref()?.lock().unwrap();
+    bucket.is_empty().then(|| bucket.ready_at())
+}
+
+#[derive(Debug)]
+pub(super) struct FaultyTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    state: Arc<FaultState>,
+}
+
+impl FaultyTcpListener {
+    pub fn new(inner: Box<dyn VirtualTcpListener + Sync>, state: Arc<FaultState>) -> Self {
+        Self { inner, state }
+    }
+}
+
+impl VirtualIoSource for FaultyTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for FaultyTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        loop {
+            let (socket, peer) = self.inner.try_accept()?;
+            let rule = self.state.rule_for(peer, Direction::Inbound);
+            if rule.as_ref().is_some_and(|rule| rule.rule.is_partitioned()) {
+                tracing::debug!(%peer, "dropped a connection across a partition");
+                continue;
+            }
+            let socket = FaultyTcpSocket::new(socket, self.state.clone(), rule);
+            return Ok((Box::new(socket), peer));
+        }
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+#[derive(Debug)]
+enum Segment {
+    Data(Vec<u8>),
+    Eof,
+}
+
+#[derive(Debug)]
+pub(super) struct FaultyTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    rule: Option<Arc<ActiveRule>>,
+    rng: Rng,
+    waking: Waking,
+    /// Data received from the peer along with when the guest gets to see it
+    delayed: VecDeque<(Instant, Segment)>,
+    delayed_bytes: usize,
+    eof: bool,
+    reset: bool,
+}
+
+impl FaultyTcpSocket {
+    pub fn new(
+        inner: Box<dyn VirtualTcpSocket + Sync>,
+        state: Arc<FaultState>,
+        rule: Option<Arc<ActiveRule>>,
+    ) -> Self {
+        Self {
+            inner,
+            rule,
+            rng: state.socket_rng(),
+            waking: Waking::new(),
+            delayed: VecDeque::new(),
+            delayed_bytes: 0,
+            eof: false,
+            reset: false,
+        }
+    }
+
+    /// Fails once the connection was reset and stalls it while it is
+    /// partitioned
+    fn check(&mut self) -> Result<()> {
+        if self.reset {
+            return Err(NetworkError::ConnectionReset);
+        }
+        if let Some(rule) = self.rule.as_ref() {
+            if rule.rule.is_partitioned() {
+                rule.rule.partition.stall(&self.waking.notify);
+                return Err(NetworkError::WouldBlock);
+            }
+        }
+        Ok(())
+    }
+
+    fn maybe_reset(&mut self, probability: f64) -> Result<()> {
+        if self.rng.chance(probability) {
+            tracing::debug!("injected a connection reset");
+            self.reset = true;
+            self.delayed.clear();
+            self.inner.close().ok();
+            return Err(NetworkError::ConnectionReset);
+        }
+        Ok(())
+    }
+
+    /// Moves what the inner socket received into the delayed data
+    fn pull(&mut self, rule: &ActiveRule) -> Result<()> {
+        let mut buf = vec![MaybeUninit::<u8>::uninit(); READ_CHUNK];
+        while !self.eof && self.delayed_bytes < MAX_DELAYED_BYTES {
+            let segment = match self.inner.try_recv(&mut buf, false) {
+                Ok(0) => {
+                    self.eof = true;
+                    Segment::Eof
+                }
+                Ok(amt) => {
+                    self.delayed_bytes += amt;
+                    // SAFETY: the inner socket initialized the first `amt` bytes
+                    let data =
+                        unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+                    Segment::Data(data.to_vec())
+                }
+                Err(NetworkError::WouldBlock) => break,
+                Err(err) if self.delayed.is_empty() => return Err(err),
+                Err(_) => break,
+            };
+            // The stream keeps its order whatever the jitter
+            let mut at = Instant::now() + self.rng.latency(&rule.rule);
+            if let Some((last, _)) = self.delayed.back() {
+                at = at.max(*last);
+            }
+            self.delayed.push_back((at, segment));
+        }
+        Ok(())
+    }
+}
+
+impl VirtualIoSource for FaultyTcpSocket {
+    fn remove_handler(&mut self) {
+        self.waking.notify.remove_handler();
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if let Some(rule) = self.rule.clone() {
+            if rule.rule.is_partitioned() {
+                rule.rule.partition.stall(&self.waking.notify);
+                self.waking.notify.register(cx.waker());
+                return Poll::Pending;
+            }
+            if let Some(at) = exhausted(&rule.download) {
+                self.waking.notify.register(cx.waker());
+                self.waking.at(at, InterestType::Readable);
+                return Poll::Pending;
+            }
+            match self.delayed.front() {
+                Some((at, segment)) if *at <= Instant::now() => {
+                    return Poll::Ready(Ok(match segment {
+                        Segment::Data(data) => data.len(),
+                        Segment::Eof => 0,
+                    }));
+                }
+                Some((at, _)) => {
+                    let at = *at;
+                    self.waking.notify.register(cx.waker());
+                    self.waking.at(at, InterestType::Readable);
+                    return Poll::Pending;
+                }
+                None => {}
+            }
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if let Some(rule) = self.rule.clone() {
+            if rule.rule.is_partitioned() {
+                rule.rule.partition.stall(&self.waking.notify);
+                self.waking.notify.register(cx.waker());
+                return Poll::Pending;
+            }
+            if let Some(at) = exhausted(&rule.upload) {
+                self.waking.notify.register(cx.waker());
+                self.waking.at(at, InterestType::Writable);
+                return Poll::Pending;
+            }
+        }
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for FaultyTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.waking.notify.set_handler(handler);
+        let forward = ForwardingHandler(self.waking.notify.clone());
+        self.inner.set_handler(Box::new(forward))
+    }
+}
+
+impl VirtualConnectedSocket for FaultyTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.check()?;
+        let Some(rule) = self.rule.clone() else {
+            return self.inner.try_send(data);
+        };
+        self.maybe_reset(rule.rule.reset)?;
+
+        let allowed = take(&rule.upload, data.len());
+        if allowed == 0 && !data.is_empty() {
+            if let Some(at) = exhausted(&rule.upload) {
+                self.waking.at(at, InterestType::Writable);
+            }
+            return Err(NetworkError::WouldBlock);
+        }
+        match self.inner.try_send(&data[..allowed]) {
+            Ok(amt) => {
+                refund(&rule.upload, allowed - amt);
+                Ok(amt)
+            }
+            Err(err) => {
+                refund(&rule.upload, allowed);
+                Err(err)
+            }
+        }
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.check()?;
+        let Some(rule) = self.rule.clone() else {
+            return self.inner.try_recv(buf, peek);
+        };
+        self.pull(&rule)?;
+
+        let at = match self.delayed.front() {
+            None => return Err(NetworkError::WouldBlock),
+            Some((_, Segment::Eof)) => return Ok(0),
+            Some((at, Segment::Data(_))) => *at,
+        };
+        if at > Instant::now() {
+            self.waking.at(at, InterestType::Readable);
+            return Err(NetworkError::WouldBlock);
+        }
+        if !peek {
+            self.maybe_reset(rule.rule.reset)?;
+        }
+
+        let Some((_, Segment::Data(data))) = self.delayed.front_mut() else {
+            unreachable!()
+        };
+        let amt = take(&rule.download, buf.len().min(data.len()));
+        if amt == 0 && !buf.is_empty() {
+            if let Some(at) = exhausted(&rule.download) {
+                self.waking.at(at, InterestType::Readable);
+            }
+            return Err(NetworkError::WouldBlock);
+        }
+        copy_to_uninit(buf, &data[..amt]);
+        if peek {
+            refund(&rule.download, amt);
+        } else {
+            data.drain(..amt);
+            self.delayed_bytes -= amt;
+            if data.is_empty() {
+                self.delayed.pop_front();
+            }
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for FaultyTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.inner.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.reset || self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct FaultyUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    state: Arc<FaultState>,
+    rng: Rng,
+    waking: Waking,
+    /// Datagrams received along with when the guest gets to see them, the
+    /// jitter is free to reorder them
+    delayed: Vec<(Instant, Vec<u8>, SocketAddr)>,
+}
+
+impl FaultyUdpSocket {
+    pub fn new(inner: Box<dyn VirtualUdpSocket + Sync>, state: Arc<FaultState>) -> Self {
+        Self {
+            inner,
+            rng: state.socket_rng(),
+            state,
+            waking: Waking::new(),
+            delayed: Vec::new(),
+        }
+    }
+
+    /// Moves what the inner socket received into the delayed datagrams,
+    /// dropping the ones that are lost along the way
+    fn pull(&mut self) -> Result<()> {
+        let mut buf = vec![MaybeUninit::<u8>::uninit(); READ_CHUNK];
+        while self.delayed.len() < MAX_DELAYED_DATAGRAMS {
+            let (amt, addr) = match self.inner.try_recv_from(&mut buf, false) {
+                Ok(ret) => ret,
+                Err(NetworkError::WouldBlock) => break,
+                Err(err) if self.delayed.is_empty() => return Err(err),
+                Err(_) => break,
+            };
+            let mut at = Instant::now();
+            if let Some(rule) = self.state.rule_for(addr, Direction::Inbound) {
+                if rule.rule.is_partitioned()
+                    || self.rng.chance(rule.rule.loss)
+                    || take(&rule.download, amt) < amt
+                {
+                    tracing::trace!(%addr, "dropped a received datagram");
+                    continue;
+                }
+                at += self.rng.latency(&rule.rule);
+            }
+            // SAFETY: the inner socket initialized the first `amt` bytes
+            let data = unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+            self.delayed.push((at, data.to_vec(), addr));
+        }
+        Ok(())
+    }
+
+    /// The datagram that the guest gets to see next
+    fn next(&self) -> Option<(usize, Instant)> {
+        self.delayed
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (at, _, _))| *at)
+            .map(|(idx, (at, _, _))| (idx, *at))
+    }
+}
+
+impl VirtualIoSource for FaultyUdpSocket {
+    fn remove_handler(&mut self) {
+        self.waking.notify.remove_handler();
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.next() {
+            Some((idx, at)) if at <= Instant::now() => Poll::Ready(Ok(self.delayed[idx].1.len())),
+            Some((_, at)) => {
+                self.waking.notify.register(cx.waker());
+                self.waking.at(at, InterestType::Readable);
+                Poll::Pending
+            }
+            None => self.inner.poll_read_ready(cx),
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for FaultyUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.waking.notify.set_handler(handler);
+        let forward = ForwardingHandler(self.waking.notify.clone());
+        self.inner.set_handler(Box::new(forward))
+    }
+}
+
+impl VirtualConnectionlessSocket for FaultyUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        if let Some(rule) = self.state.rule_for(addr, Direction::Outbound) {
+            // Like on a real network, the sender does not learn about the loss
+            if rule.rule.is_partitioned() || self.rng.chance(rule.rule.loss) {
+                tracing::trace!(%addr, "dropped a sent datagram");
+                return Ok(data.len());
+            }
+            let allowed = take(&rule.upload, data.len());
+            if allowed < data.len() {
+                refund(&rule.upload, allowed);
+                tracing::trace!(%addr, "dropped a sent datagram over the bandwidth");
+                return Ok(data.len());
+            }
+            return self.inner.try_send_to(data, addr).inspect_err(|_| {
+                refund(&rule.upload, allowed);
+            });
+        }
+        self.inner.try_send_to(data, addr)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        if self.state.rules.is_empty() {
+            return self.inner.try_recv_from(buf, peek);
+        }
+        self.pull()?;
+
+        let (idx, at) = self.next().ok_or(NetworkError::WouldBlock)?;
+        if at > Instant::now() {
+            self.waking.at(at, InterestType::Readable);
+            return Err(NetworkError::WouldBlock);
+        }
+        let (_, data, addr) = &self.delayed[idx];
+        let (amt, addr) = (copy_to_uninit(buf, data), *addr);
+        if !peek {
+            self.delayed.remove(idx);
+        }
+        Ok((amt, addr))
+    }
+}
+
+impl VirtualUdpSocket for FaultyUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
@@ -1,26 +1,38 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![allow(clippy::multiple_bound_locations)]
+pub mod capture;
 #[cfg(feature = "remote")]
 pub mod client;
 pub mod composite;
+pub mod fault;
 #[cfg(feature = "host-net")]
 pub mod host;
 pub mod loopback;
 pub mod meta;
+pub mod metering;
 pub mod ruleset;
 #[cfg(feature = "remote")]
 pub mod rx_tx;
 #[cfg(feature = "remote")]
 pub mod server;
+mod shaping;
 pub mod tcp_pair;
 #[cfg(feature = "tokio")]
 #[cfg(test)]
 mod tests;
+pub mod unix;
+#[cfg(feature = "userspace-net")]
+pub mod userspace;
 
+pub use capture::CapturingNetworking;
 #[cfg(feature = "remote")]
 pub use client::{RemoteNetworkingClient, RemoteNetworkingClientDriver};
 pub use composite::CompositeTcpListener;
+pub use fault::{FaultRule, FaultyNetworking, NetworkFaults};
 pub use loopback::LoopbackNetworking;
+pub use metering::{
+    MeteredNetworking, NetworkMeter, NetworkQuota, NetworkSnapshot, NetworkUsage, QuotaAction,
+};
 use pin_project_lite::pin_project;
 #[cfg(feature = "rkyv")]
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
@@ -33,6 +45,7 @@ use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::net::Shutdown;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
@@ -43,6 +56,9 @@ use thiserror::Error;
 use tokio::io::AsyncRead;
 #[cfg(feature = "tokio")]
 use tokio::io::AsyncWrite;
+pub use unix::UnixNetworking;
+#[cfg(feature = "userspace-net")]
+pub use userspace::{UserspaceNetworking, VirtualLan};
 
 pub use bytes::Bytes;
 pub use bytes::BytesMut;
@@ -210,6 +226,27 @@ pub trait VirtualNetworking: fmt::Debug + Send + Sync + 'static {
         Err(NetworkError::Unsupported)
     }
 
+    /// Listens for connections on a Unix stream socket bound to a path.
+    /// Unix streams use the same traits as TCP streams, their IP addresses
+    /// are unspecified
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Opens a connection to the Unix stream socket bound to a path
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Opens a Unix datagram socket bound to a path, or an unnamed one that
+    /// can only send datagrams when no path is given
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
     /// Performs DNS resolution for a specific hostname
     async fn resolve(
         &self,
@@ -769,6 +806,27 @@ pub trait VirtualUdpSocket:
     fn addr_peer(&self) -> Result<Option<SocketAddr>>;
 }
 
+/// Unix datagram sockets send and receive datagrams addressed by the path
+/// of the socket they are sent to
+pub trait VirtualUnixDatagramSocket: VirtualIoSource + fmt::Debug + Send + Sync + 'static {
+    /// Returns the path this socket is bound to, if it is not unnamed
+    fn addr_local(&self) -> Result<Option<PathBuf>>;
+
+    /// Sets the handler that will receive the interest events of this socket
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()>;
+
+    /// Sends a datagram to the socket bound to a specific path
+    fn try_send_to(&mut self, data: &[u8], path: &Path) -> Result<usize>;
+
+    /// Recv a datagram from the socket along with the path of its sender,
+    /// which is `None` when the sender is unnamed
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>)>;
+}
+
 #[derive(Debug, Default)]
 pub struct UnsupportedVirtualNetworking {}
 
@@ -942,3 +1000,12 @@ pub fn net_error_into_io_err(net_error: NetworkError) -> std::io::Error {
         }
     }
 }
+
+/// Copies as much of `src` as fits into `dst` and returns the amount copied
+pub(crate) fn copy_to_uninit(dst: &mut [MaybeUninit<u8>], src: &[u8]) -> usize {
+    let amt = dst.len().min(src.len());
+    for (dst, src) in dst.iter_mut().zip(&src[..amt]) {
+        dst.write(*src);
+    }
+    amt
+}
@@ -0,0 +1,588 @@
+//! Accounts for the traffic of a [`VirtualNetworking`] and enforces quotas
+//! on it.
+//!
+//! A [`NetworkMeter`] counts the bytes, packets and connections of the
+//! sockets opened through the [`MeteredNetworking`] instances that share it.
+//! The sockets can be attributed to a process so that the usage is also
+//! broken down per process, and [`NetworkMeter::snapshot`] returns all the
+//! counters at once so that the host can scrape them.
+
+mod socket;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use self::socket::{
+    MeteredRawSocket, MeteredTcpListener, MeteredTcpSocket, MeteredUdpSocket, SocketMeter,
+};
+use crate::shaping::Bucket;
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualUnixDatagramSocket,
+};
+
+/// Window over which the connection rate is measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// What happens to the traffic once a byte quota is used up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaAction {
+    /// Sends or receives fail with [`NetworkError::PermissionDenied`]
+    #[default]
+    Deny,
+    /// The traffic is slowed down to this many bytes per second
+    Throttle { bytes_per_sec: u64 },
+}
+
+/// Limits on the usage of a [`NetworkMeter`], the connection limits deny
+/// new connections while the byte limits apply [`QuotaAction`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkQuota {
+    pub max_active_connections: Option<u64>,
+    pub max_connections_per_sec: Option<u64>,
+    pub max_tx_bytes: Option<u64>,
+    pub max_rx_bytes: Option<u64>,
+    pub exceeded: QuotaAction,
+}
+
+/// Usage of the network, packets are only counted for datagram and raw
+/// sockets
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkUsage {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    /// TCP connections that are currently open
+    pub active_connections: u64,
+    /// TCP connections that were opened, including the closed ones
+    pub total_connections: u64,
+    /// TCP connections that were refused by the quota
+    pub denied_connections: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketKind {
+    Tcp,
+    Udp,
+    Raw,
+}
+
+/// Usage of a socket that is currently open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketUsage {
+    pub id: u64,
+    pub kind: SocketKind,
+    /// Process that opened the socket
+    pub process: Option<u32>,
+    pub local: Option<SocketAddr>,
+    pub peer: Option<SocketAddr>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+/// All the counters of a [`NetworkMeter`] at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkSnapshot {
+    pub total: NetworkUsage,
+    /// Connections opened during the last second
+    pub connection_rate: u64,
+    /// Usage of each process, including the sockets they already closed
+    pub processes: BTreeMap<u32, NetworkUsage>,
+    pub sockets: Vec<SocketUsage>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    rx_bytes: AtomicU64,
+    tx_bytes: AtomicU64,
+    rx_packets: AtomicU64,
+    tx_packets: AtomicU64,
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+    denied_connections: AtomicU64,
+}
+
+impl Counters {
+    fn usage(&self) -> NetworkUsage {
+        NetworkUsage {
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            denied_connections: self.denied_connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Rx,
+    Tx,
+}
+
+#[derive(Debug)]
+struct SocketEntry {
+    kind: SocketKind,
+    process: Option<u32>,
+    local: Option<SocketAddr>,
+    peer: Option<SocketAddr>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug)]
+struct MeterState {
+    quota: NetworkQuota,
+    total: Counters,
+    processes: Mutex<BTreeMap<u32, Arc<Counters>>>,
+    sockets: Mutex<BTreeMap<u64, SocketEntry>>,
+    next_socket: AtomicU64,
+    /// When the connections of the last [`RATE_WINDOW`] were opened
+    recent_connections: Mutex<VecDeque<Instant>>,
+    rx_throttle: Option<Mutex<Bucket>>,
+    tx_throttle: Option<Mutex<Bucket>>,
+}
+
+impl MeterState {
+    fn process(&self, pid: u32) -> Arc<Counters> {
+        self.processes
+            .lock()
+            .unwrap()
+            .entry(pid)
+            .or_default()
+            .clone()
+    }
+
+    fn recent_connections(&self) -> std::sync::MutexGuard<'_, VecDeque<Instant>> {
+        let mut recent = self.recent_connections.lock().unwrap();
+        let now = Instant::now();
+        while recent
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) >= RATE_WINDOW)
+        {
+            recent.pop_front();
+        }
+        recent
+    }
+
+    /// Counts a new connection, unless the quota refuses it
+    fn admit(&self, process: Option<&Counters>) -> Result<()> {
+        let mut recent = self.recent_connections();
+        let active = self.total.active_connections.load(Ordering::SeqCst);
+        let refused = self
+            .quota
+            .max_active_connections
+            .is_some_and(|max| active >= max)
+            || self
+                .quota
+                .max_connections_per_sec
+                .is_some_and(|max| recent.len() as u64 >= max);
+        let counters = std::iter::once(&self.total).chain(process);
+        if refused {
+            counters.for_each(|c| {
+                c.denied_connections.fetch_add(1, Ordering::SeqCst);
+            });
+            return Err(NetworkError::PermissionDenied);
+        }
+        recent.push_back(Instant::now());
+        counters.for_each(|c| {
+            c.active_connections.fetch_add(1, Ordering::SeqCst);
+            c.total_connections.fetch_add(1, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    fn release(&self, process: Option<&Counters>) {
+        for c in std::iter::once(&self.total).chain(process) {
+            c.active_connections.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn over_quota(&self, flow: Flow) -> bool {
+        let (used, max) = match flow {
+            Flow::Rx => (&self.total.rx_bytes, self.quota.max_rx_bytes),
+            Flow::Tx => (&self.total.tx_bytes, self.quota.max_tx_bytes),
+        };
+        max.is_some_and(|max| used.load(Ordering::Relaxed) >= max)
+    }
+
+    fn throttle(&self, flow: Flow) -> Option<&Mutex<Bucket>> {
+        match flow {
+            Flow::Rx => self.rx_throttle.as_ref(),
+            Flow::Tx => self.tx_throttle.as_ref(),
+        }
+    }
+}
+
+/// Accounts for the traffic of the [`MeteredNetworking`] instances it is
+/// given to, cloning it shares the counters
+#[derive(Debug, Clone)]
+pub struct NetworkMeter {
+    state: Arc<MeterState>,
+}
+
+impl Default for NetworkMeter {
+    fn default() -> Self {
+        Self::new(NetworkQuota::default())
+    }
+}
+
+impl NetworkMeter {
+    pub fn new(quota: NetworkQuota) -> Self {
+        let throttle = match quota.exceeded {
+            QuotaAction::Throttle { bytes_per_sec } => Some(bytes_per_sec.max(1)),
+            QuotaAction::Deny => None,
+        };
+        Self {
+            state: Arc::new(MeterState {
+                quota,
+                total: Counters::default(),
+                processes: Default::default(),
+                sockets: Default::default(),
+                next_socket: AtomicU64::new(0),
+                recent_connections: Default::default(),
+                rx_throttle: throttle.map(|rate| Mutex::new(Bucket::new(rate))),
+                tx_throttle: throttle.map(|rate| Mutex::new(Bucket::new(rate))),
+            }),
+        }
+    }
+
+    pub fn quota(&self) -> &NetworkQuota {
+        &self.state.quota
+    }
+
+    /// Usage of all the sockets, including the ones already closed
+    pub fn usage(&self) -> NetworkUsage {
+        self.state.total.usage()
+    }
+
+    /// Usage of the sockets of a process, including the ones already closed
+    pub fn process_usage(&self, pid: u32) -> Option<NetworkUsage> {
+        let processes = self.state.processes.lock().unwrap();
+        processes.get(&pid).map(|counters| counters.usage())
+    }
+
+    pub fn snapshot(&self) -> NetworkSnapshot {
+        let processes = self.state.processes.lock().unwrap();
+        let sockets = self.state.sockets.lock().unwrap();
+        NetworkSnapshot {
+            total: self.state.total.usage(),
+            connection_rate: self.state.recent_connections().len() as u64,
+            processes: processes
+                .iter()
+                .map(|(pid, counters)| (*pid, counters.usage()))
+                .collect(),
+            sockets: sockets
+                .iter()
+                .map(|(id, entry)| {
+                    let usage = entry.counters.usage();
+                    SocketUsage {
+                        id: *id,
+                        kind: entry.kind,
+                        process: entry.process,
+                        local: entry.local,
+                        peer: entry.peer,
+                        rx_bytes: usage.rx_bytes,
+                        tx_bytes: usage.tx_bytes,
+                        rx_packets: usage.rx_packets,
+                        tx_packets: usage.tx_packets,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Networking that accounts for the traffic of another networking in a
+/// [`NetworkMeter`] and enforces its quota
+#[derive(Debug, Clone)]
+pub struct MeteredNetworking {
+    inner: DynVirtualNetworking,
+    meter: NetworkMeter,
+    process: Option<u32>,
+}
+
+impl MeteredNetworking {
+    pub fn new(inner: DynVirtualNetworking, meter: NetworkMeter) -> Self {
+        Self {
+            inner,
+            meter,
+            process: None,
+        }
+    }
+
+    /// Attributes the sockets opened through this networking to a process
+    pub fn with_process(mut self, pid: u32) -> Self {
+        self.process = Some(pid);
+        self
+    }
+
+    pub fn meter(&self) -> &NetworkMeter {
+        &self.meter
+    }
+
+    fn process(&self) -> Option<(u32, Arc<Counters>)> {
+        self.process.map(|pid| (pid, self.meter.state.process(pid)))
+    }
+
+    fn socket_meter(
+        &self,
+        process: Option<(u32, Arc<Counters>)>,
+        kind: SocketKind,
+        local: Option<SocketAddr>,
+        peer: Option<SocketAddr>,
+    ) -> SocketMeter {
+        SocketMeter::new(self.meter.state.clone(), process, kind, local, peer)
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for MeteredNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let inner = self.inner.bind_raw().await?;
+        let meter = self.socket_meter(self.process(), SocketKind::Raw, None, None);
+        Ok(Box::new(MeteredRawSocket::new(inner, meter)))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let inner = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(MeteredTcpListener::new(
+            inner,
+            self.meter.state.clone(),
+            self.process(),
+        )))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let inner = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        let local = inner.addr_local().ok();
+        let meter = self.socket_meter(self.process(), SocketKind::Udp, local, None);
+        Ok(Box::new(MeteredUdpSocket::new(inner, meter)))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let process = self.process();
+        let counters = process.as_ref().map(|(_, counters)| counters.as_ref());
+        if let Err(err) = self.meter.state.admit(counters) {
+            tracing::debug!(%peer, "connection refused by the network quota");
+            return Err(err);
+        }
+        let inner = match self.inner.connect_tcp(addr, peer).await {
+            Ok(inner) => inner,
+            Err(err) => {
+                self.meter.state.release(counters);
+                return Err(err);
+            }
+        };
+        let local = inner.addr_local().ok();
+        let meter = SocketMeter::connection(self.meter.state.clone(), process, local, peer);
+        Ok(Box::new(MeteredTcpSocket::new(inner, meter)))
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner.listen_unix(path).await
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.inner.connect_unix(path).await
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        self.inner.bind_unix_datagram(path).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+
+    use super::*;
+    use crate::{LoopbackNetworking, VirtualConnectedSocketExt, VirtualTcpListenerExt};
+
+    #[tokio::test]
+    async fn counts_traffic_per_process() {
+        let loopback = LoopbackNetworking::new();
+        let meter = NetworkMeter::default();
+        let net = MeteredNetworking::new(Arc::new(loopback.clone()), meter.clone()).with_process(7);
+
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let mut client = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        let (mut server, peer) = listener.accept().await.unwrap();
+
+        client.send(b"hello").await.unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(server.recv(&mut buf, true).await.unwrap(), 5);
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 5);
+        server.send(b"bye").await.unwrap();
+
+        let usage = meter.process_usage(7).unwrap();
+        assert_eq!(usage.rx_bytes, 5);
+        assert_eq!(usage.tx_bytes, 3);
+        assert_eq!(usage.active_connections, 1);
+        assert_eq!(meter.usage(), usage);
+
+        let snapshot = meter.snapshot();
+        assert_eq!(snapshot.connection_rate, 1);
+        assert_eq!(snapshot.sockets.len(), 1);
+        let socket = &snapshot.sockets[0];
+        assert_eq!(socket.kind, SocketKind::Tcp);
+        assert_eq!(socket.process, Some(7));
+        assert_eq!(socket.peer, Some(peer));
+        assert_eq!((socket.rx_bytes, socket.tx_bytes), (5, 3));
+
+        // Closed sockets are gone from the snapshot but stay in the totals
+        drop(server);
+        let snapshot = meter.snapshot();
+        assert!(snapshot.sockets.is_empty());
+        assert_eq!(snapshot.processes[&7].active_connections, 0);
+        assert_eq!(snapshot.processes[&7].total_connections, 1);
+        assert_eq!(snapshot.processes[&7].rx_bytes, 5);
+    }
+
+    #[tokio::test]
+    async fn quotas_deny_connections_and_traffic() {
+        let loopback = LoopbackNetworking::new();
+        let meter = NetworkMeter::new(NetworkQuota {
+            max_active_connections: Some(1),
+            max_tx_bytes: Some(4),
+            ..Default::default()
+        });
+        let net = MeteredNetworking::new(Arc::new(loopback.clone()), meter.clone());
+
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut listener = net.listen_tcp(addr, false, false, false).await.unwrap();
+        let _first = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        // The second connection is over the limit and gets dropped
+        let _second = loopback
+            .loopback_connect_to("127.0.0.1:0".parse().unwrap(), addr)
+            .unwrap();
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+        assert_eq!(meter.usage().denied_connections, 1);
+        assert_eq!(meter.usage().active_connections, 1);
+
+        // The send that crosses the quota goes through, the next ones fail
+        assert_eq!(server.try_send(b"hello").unwrap(), 5);
+        assert_eq!(
+            server.try_send(b"again").unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        assert_eq!(meter.usage().tx_bytes, 5);
+    }
+}
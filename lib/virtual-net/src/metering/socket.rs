@@ -0,0 +1,575 @@
+use std::mem::MaybeUninit;
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use virtual_mio::InterestType;
+
+use super::{Counters, Flow, MeterState, SocketEntry, SocketKind};
+use crate::shaping::{ForwardingHandler, Waking};
+use crate::{
+    InterestHandler, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualRawSocket, VirtualSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Accounts for the traffic of a single socket, it is removed from the
+/// meter when dropped
+#[derive(Debug)]
+pub(super) struct SocketMeter {
+    state: Arc<MeterState>,
+    id: u64,
+    counters: Arc<Counters>,
+    process: Option<Arc<Counters>>,
+    /// Whether the socket holds one of the active connections
+    connection: bool,
+    waking: Waking,
+}
+
+impl SocketMeter {
+    pub fn new(
+        state: Arc<MeterState>,
+        process: Option<(u32, Arc<Counters>)>,
+        kind: SocketKind,
+        local: Option<SocketAddr>,
+        peer: Option<SocketAddr>,
+    ) -> Self {
+        let id = state.next_socket.fetch_add(1, Ordering::Relaxed);
+        let counters = Arc::new(Counters::default());
+        let (pid, process) = process.unzip();
+        state.sockets.lock().unwrap().insert(
+            id,
+            SocketEntry {
+                kind,
+                process: pid,
+                local,
+                peer,
+                counters: counters.clone(),
+            },
+        );
+        Self {
+            state,
+            id,
+            counters,
+            process,
+            connection: false,
+            waking: Waking::new(),
+        }
+    }
+
+    /// Meter of a TCP connection that was already admitted
+    pub fn connection(
+        state: Arc<MeterState>,
+        process: Option<(u32, Arc<Counters>)>,
+        local: Option<SocketAddr>,
+        peer: SocketAddr,
+    ) -> Self {
+        let mut meter = Self::new(state, process, SocketKind::Tcp, local, Some(peer));
+        meter.connection = true;
+        meter
+    }
+
+    pub fn record(&self, flow: Flow, amt: usize, packet: bool) {
+        let counters = [&self.state.total, &*self.counters]
+            .into_iter()
+            .chain(self.process.as_deref());
+        for c in counters {
+            let (bytes, packets) = match flow {
+                Flow::Rx => (&c.rx_bytes, &c.rx_packets),
+                Flow::Tx => (&c.tx_bytes, &c.tx_packets),
+            };
+            bytes.fetch_add(amt as u64, Ordering::Relaxed);
+            if packet {
+                packets.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if self.state.over_quota(flow) {
+            if let Some(bucket) = self.state.throttle(flow) {
+                bucket.lock().unwrap().consume(amt);
+            }
+        }
+    }
+
+    /// Fails when the quota of the flow is used up, either for good or
+    /// until the throttling lets more traffic through
+    pub fn check(&mut self, flow: Flow) -> Result<()> {
+        if !self.state.over_quota(flow) {
+            return Ok(());
+        }
+        let Some(bucket) = self.state.throttle(flow) else {
+            return Err(NetworkError::PermissionDenied);
+        };
+        let mut bucket = bucket.lock().unwrap();
+        if bucket.is_empty() {
+            let at = bucket.ready_at();
+            drop(bucket);
+            let interest = match flow {
+                Flow::Rx => InterestType::Readable,
+                Flow::Tx => InterestType::Writable,
+            };
+            self.waking.at(at, interest);
+            return Err(NetworkError::WouldBlock);
+        }
+        Ok(())
+    }
+
+    /// Whether the socket is held back by the throttling, in which case the
+    /// task is woken up once it is not anymore
+    pub fn poll_throttled(&mut self, flow: Flow, cx: &mut Context<'_>) -> bool {
+        self.waking.notify.register(cx.waker());
+        matches!(self.check(flow), Err(NetworkError::WouldBlock))
+    }
+
+    pub fn set_handler(
+        &self,
+        handler: Box<dyn InterestHandler + Send + Sync>,
+    ) -> Box<dyn InterestHandler + Send + Sync> {
+        self.waking.notify.set_handler(handler);
+        Box::new(ForwardingHandler(self.waking.notify.clone()))
+    }
+
+    pub fn remove_handler(&self) {
+        self.waking.notify.remove_handler();
+    }
+}
+
+impl Drop for SocketMeter {
+    fn drop(&mut self) {
+        self.state.sockets.lock().unwrap().remove(&self.id);
+        if self.connection {
+            self.state.release(self.process.as_deref());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct MeteredTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    state: Arc<MeterState>,
+    process: Option<(u32, Arc<Counters>)>,
+}
+
+impl MeteredTcpListener {
+    pub fn new(
+        inner: Box<dyn VirtualTcpListener + Sync>,
+        state: Arc<MeterState>,
+        process: Option<(u32, Arc<Counters>)>,
+    ) -> Self {
+        Self {
+            inner,
+            state,
+            process,
+        }
+    }
+}
+
+impl VirtualIoSource for MeteredTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for MeteredTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        loop {
+            let (socket, peer) = self.inner.try_accept()?;
+            let counters = self.process.as_ref().map(|(_, c)| c.as_ref());
+            if self.state.admit(counters).is_err() {
+                tracing::debug!(%peer, "connection refused by the network quota");
+                continue;
+            }
+            let local = socket.addr_local().ok();
+            let meter =
+                SocketMeter::connection(self.state.clone(), self.process.clone(), local, peer);
+            return Ok((Box::new(MeteredTcpSocket::new(socket, meter)), peer));
+        }
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct MeteredTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    meter: SocketMeter,
+}
+
+impl MeteredTcpSocket {
+    pub fn new(inner: Box<dyn VirtualTcpSocket + Sync>, meter: SocketMeter) -> Self {
+        Self { inner, meter }
+    }
+}
+
+impl VirtualIoSource for MeteredTcpSocket {
+    fn remove_handler(&mut self) {
+        self.meter.remove_handler();
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Rx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Tx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for MeteredTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let forward = self.meter.set_handler(handler);
+        self.inner.set_handler(forward)
+    }
+}
+
+impl VirtualConnectedSocket for MeteredTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.meter.check(Flow::Tx)?;
+        let amt = self.inner.try_send(data)?;
+        self.meter.record(Flow::Tx, amt, false);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.meter.check(Flow::Rx)?;
+        let amt = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            self.meter.record(Flow::Rx, amt, false);
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for MeteredTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, reuse: bool) -> Result<()> {
+        self.inner.set_nodelay(reuse)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.inner.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct MeteredUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    meter: SocketMeter,
+}
+
+impl MeteredUdpSocket {
+    pub fn new(inner: Box<dyn VirtualUdpSocket + Sync>, meter: SocketMeter) -> Self {
+        Self { inner, meter }
+    }
+}
+
+impl VirtualIoSource for MeteredUdpSocket {
+    fn remove_handler(&mut self) {
+        self.meter.remove_handler();
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Rx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Tx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for MeteredUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let forward = self.meter.set_handler(handler);
+        self.inner.set_handler(forward)
+    }
+}
+
+impl VirtualConnectionlessSocket for MeteredUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.meter.check(Flow::Tx)?;
+        let amt = self.inner.try_send_to(data, addr)?;
+        self.meter.record(Flow::Tx, amt, true);
+        Ok(amt)
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        self.meter.check(Flow::Rx)?;
+        let (amt, addr) = self.inner.try_recv_from(buf, peek)?;
+        if !peek {
+            self.meter.record(Flow::Rx, amt, true);
+        }
+        Ok((amt, addr))
+    }
+}
+
+impl VirtualUdpSocket for MeteredUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct MeteredRawSocket {
+    inner: Box<dyn VirtualRawSocket + Sync>,
+    meter: SocketMeter,
+}
+
+impl MeteredRawSocket {
+    pub fn new(inner: Box<dyn VirtualRawSocket + Sync>, meter: SocketMeter) -> Self {
+        Self { inner, meter }
+    }
+}
+
+impl VirtualIoSource for MeteredRawSocket {
+    fn remove_handler(&mut self) {
+        self.meter.remove_handler();
+        self.inner.remove_handler()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Rx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if self.meter.poll_throttled(Flow::Tx, cx) {
+            return Poll::Pending;
+        }
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for MeteredRawSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let forward = self.meter.set_handler(handler);
+        self.inner.set_handler(forward)
+    }
+}
+
+impl VirtualRawSocket for MeteredRawSocket {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.meter.check(Flow::Tx)?;
+        let amt = self.inner.try_send(data)?;
+        self.meter.record(Flow::Tx, amt, true);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.meter.check(Flow::Rx)?;
+        let amt = self.inner.try_recv(buf, peek)?;
+        if !peek {
+            self.meter.record(Flow::Rx, amt, true);
+        }
+        Ok(amt)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
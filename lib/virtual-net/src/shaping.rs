@@ -0,0 +1,306 @@
+//! Building blocks shared by the networking wrappers that shape the
+//! traffic: token buckets and a timer that wakes sockets up once delayed
+//! data or bandwidth becomes available.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use virtual_mio::InterestType;
+
+use crate::InterestHandler;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct Timer {
+    queue: Mutex<BinaryHeap<Reverse<Scheduled>>>,
+    cond: Condvar,
+    seq: AtomicU64,
+}
+
+struct Scheduled {
+    at: Instant,
+    seq: u64,
+    task: Task,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+/// The timer is a thread that is shared by every networking of the process
+/// that shapes its traffic
+fn timer() -> &'static Timer {
+    static TIMER: LazyLock<&'static Timer> = LazyLock::new(|| {
+        let timer: &'static Timer = Box::leak(Box::new(Timer {
+            queue: Mutex::new(BinaryHeap::new()),
+            cond: Condvar::new(),
+            seq: AtomicU64::new(0),
+        }));
+        std::thread::Builder::new()
+            .name("virtual-net-shaping".to_string())
+            .spawn(move || timer.run())
+            .expect("failed to spawn the network shaping timer");
+        timer
+    });
+    *TIMER
+}
+
+impl Timer {
+    fn run(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let mut due = Vec::new();
+            while queue.peek().is_some_and(|Reverse(next)| next.at <= now) {
+                due.push(queue.pop().unwrap().0.task);
+            }
+            if !due.is_empty() {
+                drop(queue);
+                due.into_iter().for_each(|task| task());
+                queue = self.queue.lock().unwrap();
+                continue;
+            }
+            queue = match queue.peek() {
+                Some(Reverse(next)) => {
+                    let timeout = next.at.saturating_duration_since(now);
+                    self.cond.wait_timeout(queue, timeout).unwrap().0
+                }
+                None => self.cond.wait(queue).unwrap(),
+            };
+        }
+    }
+
+    fn schedule(&self, at: Instant, task: Task) {
+        let seq = self.seq.fetch_add(1, atomic::Ordering::Relaxed);
+        self.queue
+            .lock()
+            .unwrap()
+            .push(Reverse(Scheduled { at, seq, task }));
+        self.cond.notify_one();
+    }
+}
+
+/// Where the readiness of a socket is reported, it stands in for the
+/// handler of the guest so that readiness can also be raised by the timer
+#[derive(Debug, Default)]
+pub(crate) struct Notify {
+    handler: Mutex<Option<Box<dyn InterestHandler + Send + Sync>>>,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Notify {
+    pub fn set_handler(&self, handler: Box<dyn InterestHandler + Send + Sync>) {
+        self.handler.lock().unwrap().replace(handler);
+    }
+
+    pub fn remove_handler(&self) {
+        self.handler.lock().unwrap().take();
+    }
+
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    pub fn notify(&self, interest: InterestType) {
+        if let Some(handler) = self.handler.lock().unwrap().as_mut() {
+            handler.push_interest(interest);
+        }
+        let wakers = std::mem::take(&mut *self.wakers.lock().unwrap());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Raises `interest` on the socket at `at`, unless it was dropped by then
+    pub fn notify_at(self: &Arc<Self>, at: Instant, interest: InterestType) {
+        let notify = Arc::downgrade(self);
+        timer().schedule(
+            at,
+            Box::new(move || {
+                if let Some(notify) = notify.upgrade() {
+                    notify.notify(interest);
+                }
+            }),
+        );
+    }
+}
+
+/// Schedules the wake ups of a socket, without piling up timers for the
+/// same deadline
+#[derive(Debug)]
+pub(crate) struct Waking {
+    pub notify: Arc<Notify>,
+    read_at: Option<Instant>,
+    write_at: Option<Instant>,
+}
+
+impl Waking {
+    pub fn new() -> Self {
+        Self {
+            notify: Arc::new(Notify::default()),
+            read_at: None,
+            write_at: None,
+        }
+    }
+
+    pub fn at(&mut self, at: Instant, interest: InterestType) {
+        let scheduled = match interest {
+            InterestType::Writable => &mut self.write_at,
+            _ => &mut self.read_at,
+        };
+        let now = Instant::now();
+        if scheduled.is_some_and(|s| s > now && s <= at) {
+            return;
+        }
+        *scheduled = Some(at);
+        self.notify.notify_at(at, interest);
+    }
+}
+
+/// Handler given to the inner socket, which forwards to the guest
+#[derive(Debug)]
+pub(crate) struct ForwardingHandler(pub Arc<Notify>);
+
+impl InterestHandler for ForwardingHandler {
+    fn push_interest(&mut self, interest: InterestType) {
+        self.0.notify(interest);
+    }
+
+    fn pop_interest(&mut self, interest: InterestType) -> bool {
+        self.0
+            .handler
+            .lock()
+            .unwrap()
+            .as_mut()
+            .is_some_and(|handler| handler.pop_interest(interest))
+    }
+
+    fn has_interest(&self, interest: InterestType) -> bool {
+        self.0
+            .handler
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handler| handler.has_interest(interest))
+    }
+}
+
+/// Future that completes once a duration has elapsed
+pub(crate) struct Delay {
+    state: Arc<Mutex<(bool, Option<Waker>)>>,
+}
+
+impl Delay {
+    pub fn new(duration: Duration) -> Self {
+        let state = Arc::new(Mutex::new((duration.is_zero(), None::<Waker>)));
+        if !duration.is_zero() {
+            let weak: Weak<Mutex<(bool, Option<Waker>)>> = Arc::downgrade(&state);
+            timer().schedule(
+                Instant::now() + duration,
+                Box::new(move || {
+                    if let Some(state) = weak.upgrade() {
+                        let mut state = state.lock().unwrap();
+                        state.0 = true;
+                        if let Some(waker) = state.1.take() {
+                            waker.wake();
+                        }
+                    }
+                }),
+            );
+        }
+        Self { state }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 {
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Token bucket that caps the bandwidth of a direction, it holds up to a
+/// second worth of traffic
+#[derive(Debug)]
+pub(crate) struct Bucket {
+    rate: u64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        self.last = now;
+    }
+
+    /// Takes up to `amt` bytes worth of tokens
+    pub fn take(&mut self, amt: usize) -> usize {
+        self.refill();
+        let amt = amt.min(self.tokens as usize);
+        self.tokens -= amt as f64;
+        amt
+    }
+
+    pub fn refund(&mut self, amt: usize) {
+        self.tokens = (self.tokens + amt as f64).min(self.rate as f64);
+    }
+
+    /// Takes `amt` bytes worth of tokens even when that is more than the
+    /// bucket holds, the debt is paid back before the bucket refills
+    pub fn consume(&mut self, amt: usize) {
+        self.refill();
+        self.tokens -= amt as f64;
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.refill();
+        self.tokens < 1.0
+    }
+
+    /// When a reasonable amount of traffic can go through again
+    pub fn ready_at(&self) -> Instant {
+        let wanted = (self.rate as f64).min(4096.0);
+        let missing = (wanted - self.tokens).max(0.0);
+        self.last + Duration::from_secs_f64(missing / self.rate as f64)
+    }
+}
@@ -0,0 +1,828 @@
+//! Unix domain sockets that connect guests with each other in-process, and
+//! that can optionally be passed through to real Unix sockets on the host.
+
+use std::collections::{HashMap, VecDeque};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(all(feature = "host-net", unix))]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use bytes::Bytes;
+#[cfg(all(feature = "host-net", unix))]
+use tokio::runtime::Handle;
+use virtual_mio::InterestType;
+
+use crate::tcp_pair::TcpSocketHalf;
+use crate::{
+    DynVirtualNetworking, InterestHandler, IpCidr, IpRoute, NetworkError, Result, StreamSecurity,
+    VirtualIcmpSocket, VirtualIoSource, VirtualNetworking, VirtualRawSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket, VirtualUnixDatagramSocket, copy_to_uninit,
+};
+
+const DEFAULT_MAX_BUFFER_SIZE: usize = 1_048_576;
+
+/// Number of datagrams a socket queues before senders have to wait
+const MAX_QUEUED_DATAGRAMS: usize = 256;
+
+/// Unix sockets are not addressed by IP, the streams report this address
+fn unspecified_addr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+}
+
+#[derive(Debug, Default)]
+struct UnixNamespace {
+    listeners: HashMap<PathBuf, Weak<Mutex<UnixListenerState>>>,
+    datagrams: HashMap<PathBuf, Weak<Mutex<UnixDatagramState>>>,
+}
+
+impl UnixNamespace {
+    /// Forgets the sockets that were closed and checks whether a live socket
+    /// is still bound to `path`
+    fn check_unbound(&mut self, path: &Path) -> Result<()> {
+        self.listeners.retain(|_, state| state.strong_count() > 0);
+        self.datagrams.retain(|_, state| state.strong_count() > 0);
+        if self.listeners.contains_key(path) || self.datagrams.contains_key(path) {
+            return Err(NetworkError::AddressInUse);
+        }
+        Ok(())
+    }
+}
+
+/// Directories of the guest whose Unix sockets are real sockets on the host
+#[cfg(all(feature = "host-net", unix))]
+#[derive(Debug, Clone)]
+struct HostDirs {
+    dirs: Vec<(PathBuf, PathBuf)>,
+    handle: Handle,
+}
+
+#[cfg(all(feature = "host-net", unix))]
+impl HostDirs {
+    fn host_path(&self, path: &Path) -> Result<Option<PathBuf>> {
+        for (guest_dir, host_dir) in self.dirs.iter() {
+            if let Ok(rest) = path.strip_prefix(guest_dir) {
+                if rest
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_)))
+                {
+                    return Err(NetworkError::PermissionDenied);
+                }
+                return Ok(Some(host_dir.join(rest)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn guest_path(&self, path: &Path) -> Option<PathBuf> {
+        self.dirs.iter().find_map(|(guest_dir, host_dir)| {
+            path.strip_prefix(host_dir)
+                .ok()
+                .map(|rest| guest_dir.join(rest))
+        })
+    }
+}
+
+/// A Unix socket bound on the host, which is removed again when the guest
+/// closes the socket so that the path can be bound again
+#[cfg(all(feature = "host-net", unix))]
+#[derive(Debug)]
+struct HostBinding {
+    path: PathBuf,
+    task: tokio::task::AbortHandle,
+}
+
+#[cfg(all(feature = "host-net", unix))]
+impl Drop for HostBinding {
+    fn drop(&mut self) {
+        self.task.abort();
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Copies the bytes of a host Unix stream to and from the guest half of a
+/// stream until either side closes it
+#[cfg(all(feature = "host-net", unix))]
+async fn pump_host_stream(stream: tokio::net::UnixStream, half: TcpSocketHalf) {
+    use tokio::io::AsyncWriteExt;
+
+    let (mut host_rx, mut host_tx) = stream.into_split();
+    let (mut guest_tx, mut guest_rx) = half.split();
+    let inbound = async move {
+        tokio::io::copy(&mut host_rx, &mut guest_tx).await.ok();
+        guest_tx.close().ok();
+    };
+    let outbound = async move {
+        tokio::io::copy(&mut guest_rx, &mut host_tx).await.ok();
+        host_tx.shutdown().await.ok();
+    };
+    futures_util::future::join(inbound, outbound).await;
+}
+
+/// Adds Unix domain sockets to another networking implementation, which
+/// keeps handling everything that is addressed by IP.
+///
+/// Sockets are bound in a namespace that is shared by all the clones of this
+/// networking, so guests using it can talk to each other. Sockets bound
+/// under a directory passed to [`UnixNetworking::with_host_dir`] are real
+/// Unix sockets on the host instead.
+#[derive(Debug, Clone)]
+pub struct UnixNetworking {
+    inner: DynVirtualNetworking,
+    namespace: Arc<Mutex<UnixNamespace>>,
+    #[cfg(all(feature = "host-net", unix))]
+    host: Option<Arc<HostDirs>>,
+}
+
+impl UnixNetworking {
+    pub fn new(inner: DynVirtualNetworking) -> Self {
+        Self {
+            inner,
+            namespace: Default::default(),
+            #[cfg(all(feature = "host-net", unix))]
+            host: None,
+        }
+    }
+
+    /// Passes the sockets bound under `guest_dir` through to real Unix
+    /// sockets under `host_dir`, so that guests can serve and reach host
+    /// processes. Must be called from within a Tokio runtime.
+    ///
+    /// Unnamed datagram sockets can send to the host but will not receive
+    /// the replies, as the host has no path to send them to.
+    #[cfg(all(feature = "host-net", unix))]
+    pub fn with_host_dir(
+        mut self,
+        guest_dir: impl Into<PathBuf>,
+        host_dir: impl Into<PathBuf>,
+    ) -> Self {
+        let mut host = self
+            .host
+            .take()
+            .map(Arc::unwrap_or_clone)
+            .unwrap_or_else(|| HostDirs {
+                dirs: Vec::new(),
+                handle: Handle::current(),
+            });
+        host.dirs.push((guest_dir.into(), host_dir.into()));
+        self.host = Some(Arc::new(host));
+        self
+    }
+
+    #[cfg(all(feature = "host-net", unix))]
+    fn host_path(&self, path: &Path) -> Result<Option<(&HostDirs, PathBuf)>> {
+        let Some(host) = self.host.as_deref() else {
+            return Ok(None);
+        };
+        Ok(host.host_path(path)?.map(|host_path| (host, host_path)))
+    }
+
+    #[cfg(all(feature = "host-net", unix))]
+    fn listen_host(
+        &self,
+        host: &HostDirs,
+        host_path: PathBuf,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = std::os::unix::net::UnixListener::bind(&host_path)
+            .map_err(crate::io_err_into_net_error)?;
+        listener
+            .set_nonblocking(true)
+            .map_err(crate::io_err_into_net_error)?;
+        let listener = {
+            let _guard = host.handle.enter();
+            tokio::net::UnixListener::from_std(listener).map_err(crate::io_err_into_net_error)?
+        };
+
+        let state = Arc::new(Mutex::new(UnixListenerState::default()));
+        let weak = Arc::downgrade(&state);
+        let task = host.handle.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Some(state) = weak.upgrade() else {
+                    break;
+                };
+                let half = state.lock().unwrap().connect();
+                tokio::spawn(pump_host_stream(stream, half));
+            }
+        });
+        Ok(Box::new(UnixListener {
+            state,
+            _host_binding: Some(HostBinding {
+                path: host_path,
+                task: task.abort_handle(),
+            }),
+        }))
+    }
+
+    #[cfg(all(feature = "host-net", unix))]
+    fn bind_host_datagram(
+        &self,
+        socket: &mut UnixDatagramSocket,
+        host_path: PathBuf,
+    ) -> Result<()> {
+        let host = self.host.clone().expect("the path was mapped to the host");
+        let datagram = std::os::unix::net::UnixDatagram::bind(&host_path)
+            .map_err(crate::io_err_into_net_error)?;
+        datagram
+            .set_nonblocking(true)
+            .map_err(crate::io_err_into_net_error)?;
+        let datagram = {
+            let _guard = host.handle.enter();
+            Arc::new(
+                tokio::net::UnixDatagram::from_std(datagram)
+                    .map_err(crate::io_err_into_net_error)?,
+            )
+        };
+
+        let weak = Arc::downgrade(&socket.state);
+        let receiver = datagram.clone();
+        let dirs = host.clone();
+        let task = host.handle.spawn(async move {
+            let mut buf = vec![0u8; u16::MAX as usize];
+            while let Ok((amt, from)) = receiver.recv_from(&mut buf).await {
+                let Some(state) = weak.upgrade() else {
+                    break;
+                };
+                let from = from.as_pathname().and_then(|from| dirs.guest_path(from));
+                let mut state = state.lock().unwrap();
+                if state.queue.len() < MAX_QUEUED_DATAGRAMS {
+                    state.push(Bytes::copy_from_slice(&buf[..amt]), from);
+                } else {
+                    tracing::trace!("dropping a datagram from the host, the queue is full");
+                }
+            }
+        });
+        socket.host_socket = Some(datagram);
+        socket._host_binding = Some(HostBinding {
+            path: host_path,
+            task: task.abort_handle(),
+        });
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for UnixNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.inner.bind_udp(addr, reuse_port, reuse_addr).await
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.inner.connect_tcp(addr, peer).await
+    }
+
+    async fn listen_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        #[cfg(all(feature = "host-net", unix))]
+        if let Some((host, host_path)) = self.host_path(path)? {
+            return self.listen_host(host, host_path);
+        }
+
+        let mut namespace = self.namespace.lock().unwrap();
+        namespace.check_unbound(path)?;
+        let state = Arc::new(Mutex::new(UnixListenerState::default()));
+        namespace
+            .listeners
+            .insert(path.to_path_buf(), Arc::downgrade(&state));
+        Ok(Box::new(UnixListener {
+            state,
+            #[cfg(all(feature = "host-net", unix))]
+            _host_binding: None,
+        }))
+    }
+
+    async fn connect_unix(&self, path: &Path) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        #[cfg(all(feature = "host-net", unix))]
+        if let Some((host, host_path)) = self.host_path(path)? {
+            let stream = host
+                .handle
+                .spawn(tokio::net::UnixStream::connect(host_path))
+                .await
+                .map_err(|_| NetworkError::IOError)?
+                .map_err(crate::io_err_into_net_error)?;
+            let (half, guest) = TcpSocketHalf::channel(
+                DEFAULT_MAX_BUFFER_SIZE,
+                unspecified_addr(),
+                unspecified_addr(),
+            );
+            host.handle.spawn(pump_host_stream(stream, half));
+            return Ok(Box::new(guest));
+        }
+
+        let listener = self
+            .namespace
+            .lock()
+            .unwrap()
+            .listeners
+            .get(path)
+            .and_then(Weak::upgrade)
+            .ok_or(NetworkError::ConnectionRefused)?;
+        let half = listener.lock().unwrap().connect();
+        Ok(Box::new(half))
+    }
+
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>> {
+        let socket = UnixDatagramSocket {
+            path: path.map(Path::to_path_buf),
+            state: Default::default(),
+            namespace: self.namespace.clone(),
+            #[cfg(all(feature = "host-net", unix))]
+            host: self.host.clone(),
+            #[cfg(all(feature = "host-net", unix))]
+            host_socket: None,
+            #[cfg(all(feature = "host-net", unix))]
+            _host_binding: None,
+        };
+        let Some(path) = path else {
+            return Ok(Box::new(socket));
+        };
+
+        #[cfg(all(feature = "host-net", unix))]
+        if let Some((_, host_path)) = self.host_path(path)? {
+            let mut socket = socket;
+            self.bind_host_datagram(&mut socket, host_path)?;
+            return Ok(Box::new(socket));
+        }
+
+        let mut namespace = self.namespace.lock().unwrap();
+        namespace.check_unbound(path)?;
+        namespace
+            .datagrams
+            .insert(path.to_path_buf(), Arc::downgrade(&socket.state));
+        Ok(Box::new(socket))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+#[derive(Debug, Default)]
+struct UnixListenerState {
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    backlog: VecDeque<TcpSocketHalf>,
+    wakers: Vec<Waker>,
+}
+
+impl UnixListenerState {
+    /// Queues a new connection and returns the half of the connecting side
+    fn connect(&mut self) -> TcpSocketHalf {
+        let (half1, half2) = TcpSocketHalf::channel(
+            DEFAULT_MAX_BUFFER_SIZE,
+            unspecified_addr(),
+            unspecified_addr(),
+        );
+        self.backlog.push_back(half1);
+        if let Some(handler) = self.handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+        self.wakers.drain(..).for_each(|w| w.wake());
+        half2
+    }
+}
+
+/// Listens for connections to a Unix stream socket
+#[derive(Debug)]
+pub struct UnixListener {
+    state: Arc<Mutex<UnixListenerState>>,
+    #[cfg(all(feature = "host-net", unix))]
+    _host_binding: Option<HostBinding>,
+}
+
+impl VirtualIoSource for UnixListener {
+    fn remove_handler(&mut self) {
+        self.state.lock().unwrap().handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.backlog.is_empty() {
+            return Poll::Ready(Ok(state.backlog.len()));
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for UnixListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let next = self.state.lock().unwrap().backlog.pop_front();
+        match next {
+            Some(next) => Ok((Box::new(next), unspecified_addr())),
+            None => Err(NetworkError::WouldBlock),
+        }
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.backlog.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        state.handler.replace(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(unspecified_addr())
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(64)
+    }
+}
+
+#[derive(Debug, Default)]
+struct UnixDatagramState {
+    queue: VecDeque<(Bytes, Option<PathBuf>)>,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+    /// Sockets whose datagrams did not fit in the queue, they are told
+    /// once there is room again
+    blocked: Vec<Weak<Mutex<UnixDatagramState>>>,
+    /// Room was made while no handler was registered
+    writable: bool,
+}
+
+impl UnixDatagramState {
+    fn push(&mut self, data: Bytes, from: Option<PathBuf>) {
+        self.queue.push_back((data, from));
+        if let Some(handler) = self.handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+        self.wakers.drain(..).for_each(|w| w.wake());
+    }
+
+    fn notify_writable(&mut self) {
+        match self.handler.as_mut() {
+            Some(handler) => handler.push_interest(InterestType::Writable),
+            None => self.writable = true,
+        }
+    }
+}
+
+/// A Unix datagram socket, bound to a path or unnamed
+#[derive(Debug)]
+pub struct UnixDatagramSocket {
+    path: Option<PathBuf>,
+    state: Arc<Mutex<UnixDatagramState>>,
+    namespace: Arc<Mutex<UnixNamespace>>,
+    #[cfg(all(feature = "host-net", unix))]
+    host: Option<Arc<HostDirs>>,
+    #[cfg(all(feature = "host-net", unix))]
+    host_socket: Option<Arc<tokio::net::UnixDatagram>>,
+    #[cfg(all(feature = "host-net", unix))]
+    _host_binding: Option<HostBinding>,
+}
+
+impl UnixDatagramSocket {
+    /// Sends a datagram to a socket on the host, from the host socket this
+    /// socket is bound to so that replies find their way back
+    #[cfg(all(feature = "host-net", unix))]
+    fn try_send_to_host(&mut self, data: &[u8], host_path: &Path) -> Result<usize> {
+        let ret = match self.host_socket.as_ref() {
+            Some(socket) => socket.try_send_to(data, host_path),
+            None => std::os::unix::net::UnixDatagram::unbound()
+                .and_then(|socket| socket.send_to(data, host_path)),
+        };
+        ret.map_err(crate::io_err_into_net_error)
+    }
+}
+
+impl VirtualIoSource for UnixDatagramSocket {
+    fn remove_handler(&mut self) {
+        self.state.lock().unwrap().handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((data, _)) = state.queue.front() {
+            return Poll::Ready(Ok(data.len()));
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(DEFAULT_MAX_BUFFER_SIZE))
+    }
+}
+
+impl VirtualUnixDatagramSocket for UnixDatagramSocket {
+    fn addr_local(&self) -> Result<Option<PathBuf>> {
+        Ok(self.path.clone())
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.queue.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        if std::mem::take(&mut state.writable) {
+            handler.push_interest(InterestType::Writable);
+        }
+        state.handler.replace(handler);
+        Ok(())
+    }
+
+    fn try_send_to(&mut self, data: &[u8], path: &Path) -> Result<usize> {
+        #[cfg(all(feature = "host-net", unix))]
+        if let Some(host) = self.host.clone() {
+            if let Some(host_path) = host.host_path(path)? {
+                return self.try_send_to_host(data, &host_path);
+            }
+        }
+
+        let peer = self
+            .namespace
+            .lock()
+            .unwrap()
+            .datagrams
+            .get(path)
+            .and_then(Weak::upgrade)
+            .ok_or(NetworkError::ConnectionRefused)?;
+        let mut peer = peer.lock().unwrap();
+        if peer.queue.len() >= MAX_QUEUED_DATAGRAMS {
+            peer.blocked.push(Arc::downgrade(&self.state));
+            return Err(NetworkError::WouldBlock);
+        }
+        peer.push(Bytes::copy_from_slice(data), self.path.clone());
+        Ok(data.len())
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>)> {
+        let mut state = self.state.lock().unwrap();
+        let next = if peek {
+            state.queue.front().cloned()
+        } else {
+            state.queue.pop_front()
+        };
+        let Some((data, from)) = next else {
+            return Err(NetworkError::WouldBlock);
+        };
+        let blocked = if peek {
+            Vec::new()
+        } else {
+            std::mem::take(&mut state.blocked)
+        };
+        drop(state);
+
+        for sender in blocked.iter().filter_map(Weak::upgrade) {
+            sender.lock().unwrap().notify_writable();
+        }
+
+        Ok((copy_to_uninit(buf, &data), from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UnsupportedVirtualNetworking, VirtualConnectedSocketExt, VirtualTcpListenerExt};
+
+    fn networking() -> UnixNetworking {
+        UnixNetworking::new(Arc::new(UnsupportedVirtualNetworking::default()))
+    }
+
+    async fn recv(socket: &mut Box<dyn VirtualTcpSocket + Sync>, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        while data.len() < len {
+            let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+            let amt = socket.recv(&mut buf, false).await.unwrap();
+            assert!(amt > 0, "the stream was closed");
+            data.extend(buf[..amt].iter().map(|b| unsafe { b.assume_init() }));
+        }
+        data
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_in_process() {
+        let networking = networking();
+        let path = Path::new("/run/test.sock");
+
+        let mut listener = networking.listen_unix(path).await.unwrap();
+        assert_eq!(
+            networking.listen_unix(path).await.unwrap_err(),
+            NetworkError::AddressInUse
+        );
+        assert_eq!(
+            networking
+                .connect_unix(Path::new("/run/other.sock"))
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+
+        let mut client = networking.connect_unix(path).await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        client.send(b"ping").await.unwrap();
+        assert_eq!(recv(&mut server, 4).await, b"ping");
+        server.send(b"pong").await.unwrap();
+        assert_eq!(recv(&mut client, 4).await, b"pong");
+
+        drop(listener);
+        networking.listen_unix(path).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn datagram_in_process() {
+        let networking = networking();
+        let server_path = Path::new("/run/server.sock");
+        let client_path = Path::new("/run/client.sock");
+
+        let mut server = networking
+            .bind_unix_datagram(Some(server_path))
+            .await
+            .unwrap();
+        let mut client = networking
+            .bind_unix_datagram(Some(client_path))
+            .await
+            .unwrap();
+        let mut unnamed = networking.bind_unix_datagram(None).await.unwrap();
+        assert_eq!(unnamed.addr_local().unwrap(), None);
+
+        let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+        assert_eq!(
+            server.try_recv_from(&mut buf, false).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        client.try_send_to(b"hello", server_path).unwrap();
+        unnamed.try_send_to(b"anonymous", server_path).unwrap();
+
+        let (amt, from) = server.try_recv_from(&mut buf, true).unwrap();
+        assert_eq!((amt, from.as_deref()), (5, Some(client_path)));
+        let (amt, from) = server.try_recv_from(&mut buf, false).unwrap();
+        assert_eq!((amt, from.as_deref()), (5, Some(client_path)));
+        let data: Vec<u8> = buf[..amt]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        assert_eq!(data, b"hello");
+
+        let (amt, from) = server.try_recv_from(&mut buf, false).unwrap();
+        assert_eq!((amt, from), (9, None));
+
+        server.try_send_to(b"reply", client_path).unwrap();
+        let (amt, from) = client.try_recv_from(&mut buf, false).unwrap();
+        assert_eq!((amt, from.as_deref()), (5, Some(server_path)));
+
+        drop(server);
+        assert_eq!(
+            client.try_send_to(b"gone", server_path).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+    }
+
+    #[cfg(all(feature = "host-net", unix))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_host_passthrough() {
+        use std::io::{Read, Write};
+
+        let host_dir =
+            std::env::temp_dir().join(format!("virtual-net-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&host_dir).unwrap();
+        let networking = networking().with_host_dir("/host", &host_dir);
+
+        let mut listener = networking
+            .listen_unix(Path::new("/host/guest.sock"))
+            .await
+            .unwrap();
+        let host_path = host_dir.join("guest.sock");
+        let client = tokio::task::spawn_blocking(move || {
+            let mut stream = std::os::unix::net::UnixStream::connect(host_path).unwrap();
+            stream.write_all(b"from the host").unwrap();
+            let mut buf = [0u8; 14];
+            stream.read_exact(&mut buf).unwrap();
+            buf
+        });
+
+        let (mut socket, _) = listener.accept().await.unwrap();
+        assert_eq!(recv(&mut socket, 13).await, b"from the host");
+        socket.send(b"from the guest").await.unwrap();
+        assert_eq!(&client.await.unwrap(), b"from the guest");
+
+        drop(listener);
+        assert!(!host_dir.join("guest.sock").exists());
+        std::fs::remove_dir_all(&host_dir).ok();
+    }
+}
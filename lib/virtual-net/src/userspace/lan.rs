@@ -0,0 +1,213 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem::MaybeUninit;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use virtual_mio::InterestType;
+
+use super::UserspaceNetworking;
+use crate::{
+    InterestHandler, NetworkError, Result, SocketStatus, VirtualIoSource, VirtualRawSocket,
+    VirtualSocket, copy_to_uninit,
+};
+
+/// Frames waiting on a port beyond this are dropped, like a switch would
+const MAX_QUEUED_FRAMES: usize = 1024;
+
+/// Largest frame a port accepts, a jumbo IP packet plus its Ethernet header
+pub(super) const MAX_FRAME_SIZE: usize = 9216 + 14;
+
+type Mac = [u8; 6];
+
+fn is_unicast(mac: &Mac) -> bool {
+    mac[0] & 0x01 == 0
+}
+
+#[derive(Debug, Default)]
+struct LanPortState {
+    frames: VecDeque<Vec<u8>>,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+    promiscuous: bool,
+}
+
+impl LanPortState {
+    fn deliver(&mut self, frame: &[u8]) {
+        if self.frames.len() >= MAX_QUEUED_FRAMES {
+            tracing::trace!("virtual lan port is full, dropping a frame");
+            return;
+        }
+        self.frames.push_back(frame.to_vec());
+        if let Some(handler) = self.handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+        self.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+#[derive(Debug, Default)]
+struct LanState {
+    ports: HashMap<u64, LanPortState>,
+    macs: HashMap<Mac, u64>,
+    next_port: u64,
+}
+
+/// An Ethernet switch that lives inside the process.
+///
+/// Every [`LanPort`] plugged into it can exchange frames with the other
+/// ports, which lets several [`UserspaceNetworking`] stacks form a private
+/// network without TUN devices or elevated privileges.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualLan {
+    state: Arc<Mutex<LanState>>,
+}
+
+impl VirtualLan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plugs a new port into the switch
+    pub fn port(&self) -> LanPort {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_port;
+        state.next_port += 1;
+        state.ports.insert(id, LanPortState::default());
+        LanPort {
+            lan: self.state.clone(),
+            id,
+            ttl: 64,
+        }
+    }
+
+    /// Creates a userspace network stack that is plugged into a new port
+    pub fn networking(&self) -> UserspaceNetworking {
+        UserspaceNetworking::new(Box::new(self.port()))
+    }
+}
+
+/// A port of a [`VirtualLan`], which sends and receives raw Ethernet frames
+#[derive(Debug)]
+pub struct LanPort {
+    lan: Arc<Mutex<LanState>>,
+    id: u64,
+    ttl: u32,
+}
+
+impl LanPort {
+    fn with_state<T>(&self, f: impl FnOnce(&mut LanPortState) -> T) -> T {
+        let mut lan = self.lan.lock().unwrap();
+        f(lan.ports.get_mut(&self.id).expect("port is plugged in"))
+    }
+}
+
+impl Drop for LanPort {
+    fn drop(&mut self) {
+        let mut lan = self.lan.lock().unwrap();
+        lan.ports.remove(&self.id);
+        lan.macs.retain(|_, port| *port != self.id);
+    }
+}
+
+impl VirtualIoSource for LanPort {
+    fn remove_handler(&mut self) {
+        self.with_state(|port| port.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with_state(|port| {
+            if let Some(frame) = port.frames.front() {
+                return Poll::Ready(Ok(frame.len()));
+            }
+            if !port.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                port.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(MAX_FRAME_SIZE))
+    }
+}
+
+impl VirtualSocket for LanPort {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.ttl)
+    }
+
+    fn addr_local(&self) -> Result<std::net::SocketAddr> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.with_state(|port| {
+            if !port.frames.is_empty() {
+                handler.push_interest(InterestType::Readable);
+            }
+            port.handler.replace(handler);
+        });
+        Ok(())
+    }
+}
+
+impl VirtualRawSocket for LanPort {
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        if data.len() < 14 || data.len() > MAX_FRAME_SIZE {
+            return Err(NetworkError::InvalidInput);
+        }
+        let dst: Mac = data[0..6].try_into().unwrap();
+        let src: Mac = data[6..12].try_into().unwrap();
+
+        let mut lan = self.lan.lock().unwrap();
+        if is_unicast(&src) {
+            lan.macs.insert(src, self.id);
+        }
+        let target = is_unicast(&dst)
+            .then(|| lan.macs.get(&dst).copied())
+            .flatten();
+        for (id, port) in lan.ports.iter_mut() {
+            let wanted = match target {
+                Some(target) => *id == target || port.promiscuous,
+                None => true,
+            };
+            if *id != self.id && wanted {
+                port.deliver(data);
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.with_state(|port| {
+            let frame = port.frames.front().ok_or(NetworkError::WouldBlock)?;
+            let amt = copy_to_uninit(buf, frame);
+            if !peek {
+                port.frames.pop_front();
+            }
+            Ok(amt)
+        })
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.with_state(|port| port.promiscuous = promiscuous);
+        Ok(())
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        Ok(self.with_state(|port| port.promiscuous))
+    }
+}
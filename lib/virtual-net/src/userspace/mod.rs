@@ -0,0 +1,1064 @@
+//! A [`VirtualNetworking`] implementation backed by the `smoltcp` userspace
+//! TCP/IP stack.
+//!
+//! Sockets are turned into Ethernet frames that are exchanged over a
+//! [`VirtualRawSocket`], such as a port of a [`VirtualLan`], so that several
+//! sandboxes can form a private network inside one process. Each stack has
+//! its own addresses and routing table, which are configured with
+//! [`VirtualNetworking::ip_add`], [`VirtualNetworking::route_add`] or
+//! [`VirtualNetworking::dhcp_acquire`]. Only IPv4 is supported.
+//!
+//! A background thread drives the stack, it exits shortly after the
+//! networking and all of its sockets are dropped.
+
+mod lan;
+mod socket;
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Route, Routes, SocketHandle};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium};
+use smoltcp::socket::{
+    Dhcpv4Event, Dhcpv4Socket, TcpSocket, TcpSocketBuffer, TcpState, UdpPacketMetadata, UdpSocket,
+    UdpSocketBuffer,
+};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpEndpoint};
+use virtual_mio::InterestType;
+
+pub use self::lan::{LanPort, VirtualLan};
+pub use self::socket::{UserspaceTcpListener, UserspaceTcpSocket, UserspaceUdpSocket};
+use crate::{
+    InterestHandler, IpCidr, IpRoute, NetworkError, Result, VirtualNetworking, VirtualRawSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Size of the send and receive buffers of each TCP socket
+const TCP_BUFFER_SIZE: usize = 65536;
+/// Number of connections that can be in the middle of a handshake on a
+/// listening port
+const TCP_BACKLOG: usize = 4;
+/// Size of the send and receive buffers of each UDP socket
+const UDP_BUFFER_SIZE: usize = 65536;
+/// Number of datagrams each UDP socket can queue
+const UDP_PACKETS: usize = 64;
+/// Frames read from the device before the stack is polled again
+const MAX_FRAMES_PER_POLL: usize = 256;
+/// Longest the driver sleeps before it checks whether the stack is gone
+const MAX_IDLE: Duration = Duration::from_secs(1);
+/// How long [`VirtualNetworking::dhcp_acquire`] waits for a lease
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+const EPHEMERAL_PORTS: std::ops::RangeInclusive<u16> = 49152..=65535;
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+fn to_ipv4(ip: IpAddr) -> Result<Ipv4Addr> {
+    match ip {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => Err(NetworkError::Unsupported),
+    }
+}
+
+fn to_endpoint(addr: SocketAddr) -> Result<IpEndpoint> {
+    let ip = to_ipv4(addr.ip())?;
+    let ip = if ip.is_unspecified() {
+        IpAddress::Unspecified
+    } else {
+        ip.into()
+    };
+    Ok(IpEndpoint::new(ip, addr.port()))
+}
+
+fn from_endpoint(endpoint: IpEndpoint) -> SocketAddr {
+    let ip = match endpoint.addr {
+        IpAddress::Unspecified => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        ip => ip.into(),
+    };
+    SocketAddr::new(ip, endpoint.port)
+}
+
+fn to_cidr(cidr: &IpCidr) -> Result<smoltcp::wire::IpCidr> {
+    Ok(smoltcp::wire::IpCidr::new(
+        to_ipv4(cidr.ip)?.into(),
+        cidr.prefix,
+    ))
+}
+
+fn to_instant(time: Option<Duration>) -> Option<Instant> {
+    time.map(|time| Instant::from_micros(time.as_micros() as i64))
+}
+
+pub(crate) fn to_network_error(err: smoltcp::Error) -> NetworkError {
+    match err {
+        smoltcp::Error::Exhausted => NetworkError::WouldBlock,
+        smoltcp::Error::Illegal => NetworkError::InvalidInput,
+        smoltcp::Error::Unaddressable => NetworkError::AddressNotAvailable,
+        smoltcp::Error::Finished => NetworkError::ConnectionAborted,
+        smoltcp::Error::Truncated => NetworkError::InvalidInput,
+        _ => NetworkError::IOError,
+    }
+}
+
+/// The smoltcp device, the driver moves the frames between its queues and
+/// the [`VirtualRawSocket`] of the stack.
+#[derive(Debug, Default)]
+struct FrameDevice {
+    rx: VecDeque<Vec<u8>>,
+    tx: VecDeque<Vec<u8>>,
+}
+
+struct FrameRxToken(Vec<u8>);
+
+struct FrameTxToken<'a>(&'a mut VecDeque<Vec<u8>>);
+
+impl smoltcp::phy::RxToken for FrameRxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.0)
+    }
+}
+
+impl smoltcp::phy::TxToken for FrameTxToken<'_> {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame)?;
+        self.0.push_back(frame);
+        Ok(ret)
+    }
+}
+
+impl<'a> Device<'a> for FrameDevice {
+    type RxToken = FrameRxToken;
+    type TxToken = FrameTxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let frame = self.rx.pop_front()?;
+        Some((FrameRxToken(frame), FrameTxToken(&mut self.tx)))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(FrameTxToken(&mut self.tx))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = 1514;
+        caps
+    }
+}
+
+/// Wakes up the driver of a stack
+#[derive(Debug, Default)]
+struct Wake {
+    pending: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Wake {
+    fn notify(&self) {
+        *self.pending.lock().unwrap() = true;
+        self.condvar.notify_one();
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let pending = self.pending.lock().unwrap();
+        let (mut pending, _) = self
+            .condvar
+            .wait_timeout_while(pending, timeout, |pending| !*pending)
+            .unwrap();
+        *pending = false;
+    }
+}
+
+/// Wakes up the driver when the device receives frames
+#[derive(Debug)]
+struct WakeHandler(Arc<Wake>);
+
+impl InterestHandler for WakeHandler {
+    fn push_interest(&mut self, _interest: InterestType) {
+        self.0.notify();
+    }
+
+    fn pop_interest(&mut self, _interest: InterestType) -> bool {
+        false
+    }
+
+    fn has_interest(&self, _interest: InterestType) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+enum EntryKind {
+    Stream(SocketHandle),
+    Listener(Vec<SocketHandle>),
+    Datagram(SocketHandle),
+}
+
+/// A socket handed out by the stack along with whoever waits on it
+#[derive(Debug)]
+struct Entry {
+    kind: EntryKind,
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Readiness {
+    readable: bool,
+    writable: bool,
+    closed: bool,
+}
+
+impl Entry {
+    fn new(kind: EntryKind) -> Self {
+        Self {
+            kind,
+            handler: None,
+            wakers: Vec::new(),
+        }
+    }
+
+    fn notify(&mut self, readiness: Readiness) {
+        if let Some(handler) = self.handler.as_mut() {
+            if readiness.readable {
+                handler.push_interest(InterestType::Readable);
+            }
+            if readiness.writable {
+                handler.push_interest(InterestType::Writable);
+            }
+            if readiness.closed {
+                handler.push_interest(InterestType::Closed);
+            }
+        }
+        self.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+/// Whether a socket of a listening backlog completed its handshake
+fn is_accepted(socket: &TcpSocket) -> bool {
+    !matches!(
+        socket.state(),
+        TcpState::Listen | TcpState::SynReceived | TcpState::Closed
+    )
+}
+
+#[derive(Debug)]
+struct Dhcp {
+    handle: SocketHandle,
+    cidr: Option<IpCidr>,
+    waiters: Vec<Waker>,
+}
+
+struct StackState {
+    iface: Interface<'static, FrameDevice>,
+    device: Box<dyn VirtualRawSocket + Sync>,
+    mac: [u8; 6],
+    ips: Vec<IpCidr>,
+    routes: Vec<IpRoute>,
+    entries: HashMap<u64, Entry>,
+    next_id: u64,
+    /// Streams that were dropped but still need to exchange a FIN
+    closing: Vec<(SocketHandle, Option<u16>)>,
+    tcp_ports: HashMap<u16, usize>,
+    udp_ports: HashMap<u16, usize>,
+    next_port: u16,
+    dhcp: Option<Dhcp>,
+}
+
+impl StackState {
+    fn add_entry(&mut self, kind: EntryKind) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(id, Entry::new(kind));
+        id
+    }
+
+    fn entry(&mut self, id: u64) -> &mut Entry {
+        self.entries
+            .get_mut(&id)
+            .expect("socket belongs to the stack")
+    }
+
+    fn tcp(&mut self, handle: SocketHandle) -> &mut TcpSocket<'static> {
+        self.iface.get_socket::<TcpSocket>(handle)
+    }
+
+    fn udp(&mut self, handle: SocketHandle) -> &mut UdpSocket<'static> {
+        self.iface.get_socket::<UdpSocket>(handle)
+    }
+
+    fn readiness(iface: &mut Interface<'static, FrameDevice>, kind: &EntryKind) -> Readiness {
+        match kind {
+            EntryKind::Stream(handle) => {
+                let socket = iface.get_socket::<TcpSocket>(*handle);
+                Readiness {
+                    readable: socket.can_recv() || !socket.may_recv(),
+                    writable: socket.can_send(),
+                    closed: socket.state() == TcpState::Closed,
+                }
+            }
+            EntryKind::Listener(backlog) => Readiness {
+                readable: backlog
+                    .iter()
+                    .any(|handle| is_accepted(iface.get_socket::<TcpSocket>(*handle))),
+                ..Default::default()
+            },
+            EntryKind::Datagram(handle) => {
+                let socket = iface.get_socket::<UdpSocket>(*handle);
+                Readiness {
+                    readable: socket.can_recv(),
+                    writable: socket.can_send(),
+                    closed: false,
+                }
+            }
+        }
+    }
+
+    /// Returns the readiness of a socket and stores the waker if it is not
+    /// ready yet
+    fn poll_ready(
+        &mut self,
+        id: u64,
+        waker: &Waker,
+        ready: impl FnOnce(&mut Interface<'static, FrameDevice>, &EntryKind) -> Option<usize>,
+    ) -> Poll<Result<usize>> {
+        let entry = self
+            .entries
+            .get_mut(&id)
+            .expect("socket belongs to the stack");
+        if let Some(amt) = ready(&mut self.iface, &entry.kind) {
+            return Poll::Ready(Ok(amt));
+        }
+        if !entry.wakers.iter().any(|w| w.will_wake(waker)) {
+            entry.wakers.push(waker.clone());
+        }
+        Poll::Pending
+    }
+
+    fn set_handler(&mut self, id: u64, mut handler: Box<dyn InterestHandler + Send + Sync>) {
+        let entry = self
+            .entries
+            .get_mut(&id)
+            .expect("socket belongs to the stack");
+        let readiness = Self::readiness(&mut self.iface, &entry.kind);
+        if readiness.readable {
+            handler.push_interest(InterestType::Readable);
+        }
+        if readiness.writable {
+            handler.push_interest(InterestType::Writable);
+        }
+        entry.handler.replace(handler);
+    }
+
+    fn claim_port(&mut self, udp: bool, port: u16, shared: bool) -> Result<u16> {
+        let ports = if udp {
+            &mut self.udp_ports
+        } else {
+            &mut self.tcp_ports
+        };
+        let port = if port == 0 {
+            let mut found = None;
+            for _ in EPHEMERAL_PORTS {
+                let port = self.next_port;
+                self.next_port = if port == *EPHEMERAL_PORTS.end() {
+                    *EPHEMERAL_PORTS.start()
+                } else {
+                    port + 1
+                };
+                if !ports.contains_key(&port) {
+                    found = Some(port);
+                    break;
+                }
+            }
+            found.ok_or(NetworkError::AddressInUse)?
+        } else if ports.contains_key(&port) && !shared {
+            return Err(NetworkError::AddressInUse);
+        } else {
+            port
+        };
+        *ports.entry(port).or_default() += 1;
+        Ok(port)
+    }
+
+    fn release_port(&mut self, udp: bool, port: u16) {
+        let ports = if udp {
+            &mut self.udp_ports
+        } else {
+            &mut self.tcp_ports
+        };
+        if let Some(count) = ports.get_mut(&port) {
+            *count -= 1;
+            if *count == 0 {
+                ports.remove(&port);
+            }
+        }
+    }
+
+    /// Checks that a local address belongs to the interface
+    fn check_local(&self, addr: SocketAddr) -> Result<()> {
+        let ip = to_ipv4(addr.ip())?;
+        if ip.is_unspecified() || self.ips.iter().any(|cidr| cidr.ip == addr.ip()) {
+            Ok(())
+        } else {
+            Err(NetworkError::AddressNotAvailable)
+        }
+    }
+
+    fn listen_socket(&mut self, endpoint: IpEndpoint) -> Result<SocketHandle> {
+        let mut socket = TcpSocket::new(
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        );
+        socket.listen(endpoint).map_err(to_network_error)?;
+        Ok(self.iface.add_socket(socket))
+    }
+
+    fn sync_ips(&mut self) {
+        let ips = self
+            .ips
+            .iter()
+            .filter_map(|cidr| to_cidr(cidr).ok())
+            .collect::<Vec<_>>();
+        self.iface.update_ip_addrs(|addrs| *addrs = ips.into());
+    }
+
+    fn sync_routes(&mut self) {
+        let routes = self
+            .routes
+            .iter()
+            .filter_map(|route| {
+                let cidr = to_cidr(&route.cidr).ok()?;
+                let route = Route {
+                    via_router: to_ipv4(route.via_router).ok()?.into(),
+                    preferred_until: to_instant(route.preferred_until),
+                    expires_at: to_instant(route.expires_at),
+                };
+                Some((cidr, route))
+            })
+            .collect::<Vec<_>>();
+        self.iface.routes_mut().update(|map| {
+            *map = BTreeMap::new().into();
+            for (cidr, route) in routes {
+                let _ = map.insert(cidr, route);
+            }
+        });
+    }
+
+    fn set_gateway(&mut self, ip: IpAddr) {
+        self.routes.retain(|route| route.cidr.prefix != 0);
+        self.routes.push(IpRoute {
+            cidr: IpCidr {
+                ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                prefix: 0,
+            },
+            via_router: ip,
+            preferred_until: None,
+            expires_at: None,
+        });
+    }
+
+    /// Applies the configuration changes of the DHCP client
+    fn poll_dhcp(&mut self) {
+        let Some(dhcp) = self.dhcp.as_mut() else {
+            return;
+        };
+        let event = self.iface.get_socket::<Dhcpv4Socket>(dhcp.handle).poll();
+        let old = dhcp.cidr.take();
+        match event {
+            None => {
+                dhcp.cidr = old;
+                dhcp.waiters.drain(..).for_each(Waker::wake);
+                return;
+            }
+            Some(Dhcpv4Event::Configured(config)) => {
+                let cidr = IpCidr {
+                    ip: IpAddr::V4(config.address.address().into()),
+                    prefix: config.address.prefix_len(),
+                };
+                dhcp.cidr = Some(cidr);
+                self.ips.retain(|ip| Some(*ip) != old);
+                if !self.ips.contains(&cidr) {
+                    self.ips.push(cidr);
+                }
+                if let Some(router) = config.router {
+                    self.set_gateway(IpAddr::V4(router.into()));
+                }
+            }
+            Some(Dhcpv4Event::Deconfigured) => {
+                self.ips.retain(|ip| Some(*ip) != old);
+            }
+        }
+        self.sync_ips();
+        self.sync_routes();
+        if let Some(dhcp) = self.dhcp.as_mut() {
+            dhcp.waiters.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+/// The state of a userspace stack shared by its networking, its sockets
+/// and its driver
+pub(crate) struct Stack {
+    state: Mutex<StackState>,
+    wake: Arc<Wake>,
+}
+
+impl std::fmt::Debug for Stack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stack").finish_non_exhaustive()
+    }
+}
+
+impl Stack {
+    fn lock(&self) -> std::sync::MutexGuard<'_, StackState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Asks the driver to poll the stack, for instance after data was queued
+    fn notify(&self) {
+        self.wake.notify();
+    }
+
+    /// Moves frames between the device and the interface, then lets the
+    /// sockets know about their readiness. Returns when the stack wants to
+    /// be polled again.
+    fn pump(&self) -> Option<Duration> {
+        let mut state = self.lock();
+        let state = &mut *state;
+
+        let mut buf = vec![MaybeUninit::<u8>::uninit(); lan::MAX_FRAME_SIZE];
+        let mut backlogged = false;
+        for n in 0..=MAX_FRAMES_PER_POLL {
+            match state.device.try_recv(&mut buf, false) {
+                Ok(amt) if amt > 0 => {
+                    if n == MAX_FRAMES_PER_POLL {
+                        backlogged = true;
+                    }
+                    // SAFETY: `try_recv` initialized the first `amt` bytes
+                    let frame =
+                        unsafe { std::mem::transmute::<&[MaybeUninit<u8>], &[u8]>(&buf[..amt]) };
+                    state.iface.device_mut().rx.push_back(frame.to_vec());
+                }
+                _ => break,
+            }
+        }
+
+        let now = Instant::now();
+        let changed = state.iface.poll(now).unwrap_or_else(|err| {
+            tracing::trace!(%err, "userspace stack poll failed");
+            true
+        });
+        state.poll_dhcp();
+
+        while let Some(frame) = state.iface.device_mut().tx.pop_front() {
+            if let Err(err) = state.device.try_send(&frame) {
+                tracing::trace!(%err, "userspace stack dropped a frame");
+            }
+        }
+
+        let mut closed = Vec::new();
+        state.closing.retain(|(handle, port)| {
+            let socket = state.iface.get_socket::<TcpSocket>(*handle);
+            let done = matches!(socket.state(), TcpState::Closed | TcpState::TimeWait);
+            if done {
+                closed.push((*handle, *port));
+            }
+            !done
+        });
+        for (handle, port) in closed {
+            state.iface.remove_socket(handle);
+            if let Some(port) = port {
+                state.release_port(false, port);
+            }
+        }
+
+        if changed {
+            for entry in state.entries.values_mut() {
+                let readiness = StackState::readiness(&mut state.iface, &entry.kind);
+                entry.notify(readiness);
+            }
+        }
+
+        if backlogged {
+            return Some(Duration::ZERO);
+        }
+        state.iface.poll_delay(now).map(Duration::from)
+    }
+}
+
+fn drive(stack: Weak<Stack>, wake: Arc<Wake>) {
+    loop {
+        let Some(stack) = stack.upgrade() else {
+            return;
+        };
+        let delay = stack.pump();
+        drop(stack);
+        wake.wait(delay.map_or(MAX_IDLE, |delay| delay.min(MAX_IDLE)));
+    }
+}
+
+/// Networking backed by a userspace TCP/IP stack that exchanges Ethernet
+/// frames over a [`VirtualRawSocket`]
+#[derive(Debug, Clone)]
+pub struct UserspaceNetworking {
+    stack: Arc<Stack>,
+}
+
+impl UserspaceNetworking {
+    /// Creates a stack with a random MAC address and no IP address, that
+    /// sends and receives its frames on `device`
+    pub fn new(mut device: Box<dyn VirtualRawSocket + Sync>) -> Self {
+        let wake = Arc::new(Wake::default());
+        if let Err(err) = device.set_handler(Box::new(WakeHandler(wake.clone()))) {
+            tracing::warn!(%err, "failed to watch the device of the userspace stack");
+        }
+
+        // Locally administered unicast address
+        let mut mac: [u8; 6] = random_u64().to_le_bytes()[..6].try_into().unwrap();
+        mac[0] = (mac[0] & 0xfe) | 0x02;
+
+        let iface = InterfaceBuilder::new(FrameDevice::default(), vec![])
+            .hardware_addr(EthernetAddress(mac).into())
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(Vec::new())
+            .routes(Routes::new(BTreeMap::new()))
+            .random_seed(random_u64())
+            .finalize();
+
+        let stack = Arc::new(Stack {
+            state: Mutex::new(StackState {
+                iface,
+                device,
+                mac,
+                ips: Vec::new(),
+                routes: Vec::new(),
+                entries: HashMap::new(),
+                next_id: 0,
+                closing: Vec::new(),
+                tcp_ports: HashMap::new(),
+                udp_ports: HashMap::new(),
+                next_port: *EPHEMERAL_PORTS.start(),
+                dhcp: None,
+            }),
+            wake: wake.clone(),
+        });
+
+        let weak = Arc::downgrade(&stack);
+        std::thread::Builder::new()
+            .name("userspace-net".to_string())
+            .spawn(move || drive(weak, wake))
+            .expect("failed to spawn the userspace network driver");
+
+        Self { stack }
+    }
+
+    /// Sends the SYN of an outgoing connection
+    fn start_connect(&self, addr: SocketAddr, peer: SocketAddr) -> Result<UserspaceTcpSocket> {
+        let remote = to_endpoint(peer)?;
+        if !remote.is_specified() {
+            return Err(NetworkError::AddressNotAvailable);
+        }
+        let mut state = self.stack.lock();
+        state.check_local(addr)?;
+        let port = state.claim_port(false, addr.port(), false)?;
+        let local = to_endpoint(SocketAddr::new(addr.ip(), port))?;
+
+        let socket = TcpSocket::new(
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        );
+        let handle = state.iface.add_socket(socket);
+        let (socket, cx) = state.iface.get_socket_and_context::<TcpSocket>(handle);
+        if let Err(err) = socket.connect(cx, remote, local) {
+            state.iface.remove_socket(handle);
+            state.release_port(false, port);
+            return Err(to_network_error(err));
+        }
+        let id = state.add_entry(EntryKind::Stream(handle));
+        drop(state);
+        self.stack.notify();
+
+        Ok(UserspaceTcpSocket::new(
+            self.stack.clone(),
+            id,
+            handle,
+            Some(port),
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for UserspaceNetworking {
+    /// Runs a DHCP client on the device until it obtains a lease, the lease
+    /// is then renewed in the background
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        {
+            let mut state = self.stack.lock();
+            if state.dhcp.is_none() {
+                let handle = state.iface.add_socket(Dhcpv4Socket::new());
+                state.dhcp = Some(Dhcp {
+                    handle,
+                    cidr: None,
+                    waiters: Vec::new(),
+                });
+            }
+        }
+        self.stack.notify();
+
+        let deadline = std::time::Instant::now() + DHCP_TIMEOUT;
+        std::future::poll_fn(|cx| {
+            let mut state = self.stack.lock();
+            let dhcp = state.dhcp.as_mut().expect("DHCP client was started");
+            if let Some(cidr) = dhcp.cidr {
+                return Poll::Ready(Ok(vec![cidr.ip]));
+            }
+            if std::time::Instant::now() >= deadline {
+                return Poll::Ready(Err(NetworkError::TimedOut));
+            }
+            dhcp.waiters.push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let ipv4 = to_ipv4(ip)?;
+        if prefix > 32 || ipv4.is_unspecified() || ipv4.is_broadcast() || ipv4.is_multicast() {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut state = self.stack.lock();
+        let cidr = IpCidr { ip, prefix };
+        if !state.ips.contains(&cidr) {
+            state.ips.push(cidr);
+            state.sync_ips();
+        }
+        Ok(())
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let mut state = self.stack.lock();
+        state.ips.retain(|cidr| cidr.ip != ip);
+        state.sync_ips();
+        Ok(())
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        let mut state = self.stack.lock();
+        state.ips.clear();
+        state.sync_ips();
+        Ok(())
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        Ok(self.stack.lock().ips.clone())
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        Ok(self.stack.lock().mac)
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        to_ipv4(ip)?;
+        let mut state = self.stack.lock();
+        state.set_gateway(ip);
+        state.sync_routes();
+        Ok(())
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        to_cidr(&cidr)?;
+        to_ipv4(via_router)?;
+        if cidr.prefix > 32 {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut state = self.stack.lock();
+        state.routes.retain(|route| route.cidr != cidr);
+        state.routes.push(IpRoute {
+            cidr,
+            via_router,
+            preferred_until,
+            expires_at,
+        });
+        state.sync_routes();
+        Ok(())
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        let mut state = self.stack.lock();
+        state.routes.retain(|route| route.cidr.ip != cidr);
+        state.sync_routes();
+        Ok(())
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        let mut state = self.stack.lock();
+        state.routes.clear();
+        state.sync_routes();
+        Ok(())
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        Ok(self.stack.lock().routes.clone())
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        if only_v6 {
+            return Err(NetworkError::Unsupported);
+        }
+        let mut state = self.stack.lock();
+        state.check_local(addr)?;
+        let port = state.claim_port(false, addr.port(), reuse_port || reuse_addr)?;
+        let addr = SocketAddr::new(addr.ip(), port);
+        let endpoint = to_endpoint(addr)?;
+
+        let mut backlog = Vec::with_capacity(TCP_BACKLOG);
+        for _ in 0..TCP_BACKLOG {
+            match state.listen_socket(endpoint) {
+                Ok(handle) => backlog.push(handle),
+                Err(err) => {
+                    for handle in backlog {
+                        state.iface.remove_socket(handle);
+                    }
+                    state.release_port(false, port);
+                    return Err(err);
+                }
+            }
+        }
+        let id = state.add_entry(EntryKind::Listener(backlog));
+        drop(state);
+        self.stack.notify();
+
+        Ok(Box::new(UserspaceTcpListener::new(
+            self.stack.clone(),
+            id,
+            addr,
+        )))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let mut state = self.stack.lock();
+        state.check_local(addr)?;
+        let port = state.claim_port(true, addr.port(), reuse_port || reuse_addr)?;
+        let endpoint = to_endpoint(SocketAddr::new(addr.ip(), port))?;
+
+        let mut socket = UdpSocket::new(
+            UdpSocketBuffer::new(
+                vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+            UdpSocketBuffer::new(
+                vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+        );
+        if let Err(err) = socket.bind(endpoint) {
+            state.release_port(true, port);
+            return Err(to_network_error(err));
+        }
+        let handle = state.iface.add_socket(socket);
+        let id = state.add_entry(EntryKind::Datagram(handle));
+        drop(state);
+
+        Ok(Box::new(UserspaceUdpSocket::new(
+            self.stack.clone(),
+            id,
+            handle,
+            port,
+        )))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        // Dropping the socket cleans it up if the connection fails or the
+        // caller gives up
+        let mut socket = self.start_connect(addr, peer)?;
+        std::future::poll_fn(|cx| socket.poll_connected(cx)).await?;
+        Ok(Box::new(socket))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use super::*;
+    use crate::{VirtualConnectedSocketExt, VirtualConnectionlessSocketExt, VirtualTcpListenerExt};
+
+    const A: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const B: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    async fn lan() -> (UserspaceNetworking, UserspaceNetworking) {
+        let lan = VirtualLan::new();
+        let a = lan.networking();
+        let b = lan.networking();
+        a.ip_add(A, 24).await.unwrap();
+        b.ip_add(B, 24).await.unwrap();
+        (a, b)
+    }
+
+    async fn recv_all(socket: &mut Box<dyn VirtualTcpSocket + Sync>, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [MaybeUninit::uninit(); 1024];
+        while data.len() < len {
+            let amt = socket.recv(&mut buf, false).await.unwrap();
+            assert_ne!(amt, 0, "unexpected EOF");
+            data.extend(buf[..amt].iter().map(|b| unsafe { b.assume_init() }));
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn tcp_over_virtual_lan() {
+        let (a, b) = lan().await;
+
+        let mut listener = a
+            .listen_tcp(SocketAddr::new(A, 8080), false, false, false)
+            .await
+            .unwrap();
+        let mut client = b
+            .connect_tcp(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                SocketAddr::new(A, 8080),
+            )
+            .await
+            .unwrap();
+        let (mut server, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer, client.addr_local().unwrap());
+        assert_eq!(server.addr_peer().unwrap(), peer);
+        assert_eq!(peer.ip(), B);
+
+        let request = vec![7u8; 100_000];
+        let mut sent = 0;
+        let writer = async {
+            while sent < request.len() {
+                sent += client.send(&request[sent..]).await.unwrap();
+            }
+            client
+        };
+        let (mut client, received) = tokio::join!(writer, recv_all(&mut server, request.len()));
+        assert_eq!(received, request);
+
+        server.send(b"pong").await.unwrap();
+        assert_eq!(recv_all(&mut client, 4).await, b"pong");
+
+        client.close().unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(server.recv(&mut buf, false).await.unwrap(), 0);
+
+        let refused = b
+            .connect_tcp(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                SocketAddr::new(A, 9090),
+            )
+            .await;
+        assert!(matches!(refused, Err(NetworkError::ConnectionRefused)));
+    }
+
+    #[tokio::test]
+    async fn udp_over_virtual_lan() {
+        let (a, b) = lan().await;
+
+        let mut server = a
+            .bind_udp(SocketAddr::new(A, 5353), false, false)
+            .await
+            .unwrap();
+        let mut client = b
+            .bind_udp(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+        let client_port = client.addr_local().unwrap().port();
+        assert!(EPHEMERAL_PORTS.contains(&client_port));
+
+        client
+            .send_to(b"hello", SocketAddr::new(A, 5353))
+            .await
+            .unwrap();
+        let mut buf = [MaybeUninit::uninit(); 16];
+        let (amt, from) = server.recv_from(&mut buf, false).await.unwrap();
+        assert_eq!(amt, 5);
+        assert_eq!(from, SocketAddr::new(B, client_port));
+
+        assert!(matches!(
+            a.bind_udp(SocketAddr::new(A, 5353), false, false).await,
+            Err(NetworkError::AddressInUse)
+        ));
+    }
+
+    #[tokio::test]
+    async fn addresses_and_routes() {
+        let net = VirtualLan::new().networking();
+        let mac = net.mac().await.unwrap();
+        assert_eq!(mac[0] & 0x03, 0x02);
+
+        net.ip_add(A, 24).await.unwrap();
+        assert_eq!(
+            net.ip_list().await.unwrap(),
+            vec![IpCidr { ip: A, prefix: 24 }]
+        );
+        assert!(matches!(
+            net.ip_add(IpAddr::V4(Ipv4Addr::BROADCAST), 32).await,
+            Err(NetworkError::InvalidInput)
+        ));
+        assert!(matches!(
+            net.listen_tcp(SocketAddr::new(B, 80), false, false, false)
+                .await,
+            Err(NetworkError::AddressNotAvailable)
+        ));
+
+        net.gateway_set(B).await.unwrap();
+        let cidr = IpCidr {
+            ip: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)),
+            prefix: 16,
+        };
+        net.route_add(cidr, B, None, None).await.unwrap();
+        let routes = net.route_list().await.unwrap();
+        assert_eq!(routes.len(), 2);
+        assert!(routes.iter().all(|route| route.via_router == B));
+
+        net.route_remove(cidr.ip).await.unwrap();
+        assert_eq!(net.route_list().await.unwrap().len(), 1);
+        net.ip_clear().await.unwrap();
+        assert!(net.ip_list().await.unwrap().is_empty());
+    }
+}
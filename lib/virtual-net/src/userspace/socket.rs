@@ -0,0 +1,566 @@
+use std::mem::MaybeUninit;
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::{TcpSocket, TcpState};
+
+use super::{EntryKind, Stack, from_endpoint, is_accepted, to_endpoint, to_network_error};
+use crate::{
+    InterestHandler, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket, copy_to_uninit,
+};
+
+/// Keep-alive interval of TCP sockets that asked for one
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(75);
+
+/// A listening port of a [`super::UserspaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserspaceTcpListener {
+    stack: Arc<Stack>,
+    id: u64,
+    addr: SocketAddr,
+    ttl: u8,
+}
+
+impl UserspaceTcpListener {
+    pub(super) fn new(stack: Arc<Stack>, id: u64, addr: SocketAddr) -> Self {
+        Self {
+            stack,
+            id,
+            addr,
+            ttl: 64,
+        }
+    }
+}
+
+impl Drop for UserspaceTcpListener {
+    fn drop(&mut self) {
+        let mut state = self.stack.lock();
+        if let Some(entry) = state.entries.remove(&self.id) {
+            if let EntryKind::Listener(backlog) = entry.kind {
+                for handle in backlog {
+                    state.iface.remove_socket(handle);
+                }
+            }
+        }
+        state.release_port(false, self.addr.port());
+    }
+}
+
+impl VirtualIoSource for UserspaceTcpListener {
+    fn remove_handler(&mut self) {
+        self.stack.lock().entry(self.id).handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.stack
+            .lock()
+            .poll_ready(self.id, cx.waker(), |iface, kind| match kind {
+                EntryKind::Listener(backlog) => {
+                    let amt = backlog
+                        .iter()
+                        .filter(|handle| is_accepted(iface.get_socket::<TcpSocket>(**handle)))
+                        .count();
+                    (amt > 0).then_some(amt)
+                }
+                _ => None,
+            })
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for UserspaceTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let listen = to_endpoint(self.addr)?;
+        let mut guard = self.stack.lock();
+        let state = &mut *guard;
+        let EntryKind::Listener(backlog) = &mut state.entry(self.id).kind else {
+            unreachable!("listeners are registered as such");
+        };
+        let mut backlog = std::mem::take(backlog);
+
+        let accepted = backlog
+            .iter()
+            .position(|handle| is_accepted(state.iface.get_socket::<TcpSocket>(*handle)));
+        let ret = match accepted {
+            Some(index) => {
+                let handle = backlog.remove(index);
+                let peer = from_endpoint(state.tcp(handle).remote_endpoint());
+                match state.listen_socket(listen) {
+                    Ok(replacement) => backlog.push(replacement),
+                    Err(err) => tracing::warn!(%err, "failed to refill the TCP backlog"),
+                }
+                let id = state.add_entry(EntryKind::Stream(handle));
+                Ok((id, handle, peer))
+            }
+            None => Err(NetworkError::WouldBlock),
+        };
+        state.entry(self.id).kind = EntryKind::Listener(backlog);
+        drop(guard);
+
+        let (id, handle, peer) = ret?;
+        self.stack.notify();
+        let socket = UserspaceTcpSocket::new(self.stack.clone(), id, handle, None);
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.lock().set_handler(self.id, handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(self.ttl)
+    }
+}
+
+/// A TCP connection of a [`super::UserspaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserspaceTcpSocket {
+    stack: Arc<Stack>,
+    id: u64,
+    handle: SocketHandle,
+    /// Local port claimed by this socket, accepted sockets share the port
+    /// of their listener
+    port: Option<u16>,
+    linger: Option<Duration>,
+    dontroute: bool,
+}
+
+impl UserspaceTcpSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: u64, handle: SocketHandle, port: Option<u16>) -> Self {
+        Self {
+            stack,
+            id,
+            handle,
+            port,
+            linger: None,
+            dontroute: false,
+        }
+    }
+
+    fn with_socket<T>(&self, f: impl FnOnce(&mut TcpSocket<'static>) -> T) -> T {
+        f(self.stack.lock().tcp(self.handle))
+    }
+
+    /// Resolves once the handshake of an outgoing connection is over
+    pub(super) fn poll_connected(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut state = self.stack.lock();
+        match state.tcp(self.handle).state() {
+            TcpState::SynSent | TcpState::SynReceived => {
+                let entry = state.entry(self.id);
+                if !entry.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    entry.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            TcpState::Closed => Poll::Ready(Err(NetworkError::ConnectionRefused)),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl Drop for UserspaceTcpSocket {
+    fn drop(&mut self) {
+        let mut state = self.stack.lock();
+        state.entries.remove(&self.id);
+        state.tcp(self.handle).close();
+        state.closing.push((self.handle, self.port));
+        drop(state);
+        self.stack.notify();
+    }
+}
+
+impl VirtualIoSource for UserspaceTcpSocket {
+    fn remove_handler(&mut self) {
+        self.stack.lock().entry(self.id).handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let handle = self.handle;
+        self.stack
+            .lock()
+            .poll_ready(self.id, cx.waker(), |iface, _| {
+                let socket = iface.get_socket::<TcpSocket>(handle);
+                if socket.can_recv() {
+                    Some(socket.recv_queue())
+                } else {
+                    (!socket.may_recv()).then_some(0)
+                }
+            })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let handle = self.handle;
+        self.stack
+            .lock()
+            .poll_ready(self.id, cx.waker(), |iface, _| {
+                let socket = iface.get_socket::<TcpSocket>(handle);
+                if socket.can_send() {
+                    Some(socket.send_capacity() - socket.send_queue())
+                } else {
+                    (!socket.may_send()).then_some(0)
+                }
+            })
+    }
+}
+
+impl VirtualSocket for UserspaceTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = u8::try_from(ttl).map_err(|_| NetworkError::InvalidInput)?;
+        self.with_socket(|socket| socket.set_hop_limit(Some(ttl)));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.with_socket(|socket| socket.hop_limit()).unwrap_or(64) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(from_endpoint(
+            self.with_socket(|socket| socket.local_endpoint()),
+        ))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(match self.with_socket(|socket| socket.state()) {
+            TcpState::Closed | TcpState::TimeWait => SocketStatus::Closed,
+            TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => SocketStatus::Opening,
+            _ => SocketStatus::Opened,
+        })
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.lock().set_handler(self.id, handler);
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for UserspaceTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.linger)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let ret = self.with_socket(|socket| {
+            if !socket.may_send() {
+                return Err(match socket.state() {
+                    TcpState::SynSent | TcpState::SynReceived => NetworkError::NotConnected,
+                    _ => NetworkError::BrokenPipe,
+                });
+            }
+            match socket.send_slice(data) {
+                Ok(0) if !data.is_empty() => Err(NetworkError::WouldBlock),
+                Ok(amt) => Ok(amt),
+                Err(err) => Err(to_network_error(err)),
+            }
+        });
+        if ret.is_ok() {
+            self.stack.notify();
+        }
+        ret
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.with_socket(|socket| socket.close());
+        self.stack.notify();
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let ret = self.with_socket(|socket| {
+            if !socket.can_recv() {
+                return if socket.may_recv() {
+                    Err(NetworkError::WouldBlock)
+                } else {
+                    Ok(0)
+                };
+            }
+            if peek {
+                let data = socket.peek(buf.len()).map_err(to_network_error)?;
+                Ok(copy_to_uninit(buf, data))
+            } else {
+                socket
+                    .recv(|data| {
+                        let amt = copy_to_uninit(buf, data);
+                        (amt, amt)
+                    })
+                    .map_err(to_network_error)
+            }
+        });
+        // Reading opens the receive window, which the peer has to hear about
+        if matches!(ret, Ok(amt) if amt > 0 && !peek) {
+            self.stack.notify();
+        }
+        ret
+    }
+}
+
+impl VirtualTcpSocket for UserspaceTcpSocket {
+    /// The buffers are allocated when the socket is created, their size can
+    /// not be changed afterwards
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.with_socket(|socket| socket.recv_capacity()))
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.with_socket(|socket| socket.send_capacity()))
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.with_socket(|socket| socket.set_nagle_enabled(!nodelay));
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(!self.with_socket(|socket| socket.nagle_enabled()))
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        let interval = keepalive.then(|| KEEPALIVE_INTERVAL.into());
+        self.with_socket(|socket| socket.set_keep_alive(interval));
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(self.with_socket(|socket| socket.keep_alive()).is_some())
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.dontroute = dontroute;
+        Ok(())
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Ok(self.dontroute)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        let endpoint = self.with_socket(|socket| socket.remote_endpoint());
+        if !endpoint.is_specified() {
+            return Err(NetworkError::NotConnected);
+        }
+        Ok(from_endpoint(endpoint))
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        // Only the peer can close the receiving half of a connection
+        if how != Shutdown::Read {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        !self.with_socket(|socket| socket.is_open())
+    }
+}
+
+/// A UDP socket of a [`super::UserspaceNetworking`] stack
+#[derive(Debug)]
+pub struct UserspaceUdpSocket {
+    stack: Arc<Stack>,
+    id: u64,
+    handle: SocketHandle,
+    port: u16,
+    broadcast: bool,
+    multicast_loop_v4: bool,
+    multicast_loop_v6: bool,
+    multicast_ttl_v4: u32,
+}
+
+impl UserspaceUdpSocket {
+    pub(super) fn new(stack: Arc<Stack>, id: u64, handle: SocketHandle, port: u16) -> Self {
+        Self {
+            stack,
+            id,
+            handle,
+            port,
+            broadcast: false,
+            multicast_loop_v4: true,
+            multicast_loop_v6: true,
+            multicast_ttl_v4: 1,
+        }
+    }
+}
+
+impl Drop for UserspaceUdpSocket {
+    fn drop(&mut self) {
+        let mut state = self.stack.lock();
+        state.entries.remove(&self.id);
+        state.iface.remove_socket(self.handle);
+        state.release_port(true, self.port);
+    }
+}
+
+impl VirtualIoSource for UserspaceUdpSocket {
+    fn remove_handler(&mut self) {
+        self.stack.lock().entry(self.id).handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let handle = self.handle;
+        self.stack
+            .lock()
+            .poll_ready(self.id, cx.waker(), |iface, _| {
+                let socket = iface.get_socket::<smoltcp::socket::UdpSocket>(handle);
+                socket.can_recv().then_some(1)
+            })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let handle = self.handle;
+        self.stack
+            .lock()
+            .poll_ready(self.id, cx.waker(), |iface, _| {
+                let socket = iface.get_socket::<smoltcp::socket::UdpSocket>(handle);
+                socket.can_send().then(|| socket.payload_send_capacity())
+            })
+    }
+}
+
+impl VirtualSocket for UserspaceUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = u8::try_from(ttl).map_err(|_| NetworkError::InvalidInput)?;
+        self.stack.lock().udp(self.handle).set_hop_limit(Some(ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.stack.lock().udp(self.handle).hop_limit().unwrap_or(64) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(from_endpoint(self.stack.lock().udp(self.handle).endpoint()))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stack.lock().set_handler(self.id, handler);
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for UserspaceUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let endpoint = to_endpoint(addr)?;
+        self.stack
+            .lock()
+            .udp(self.handle)
+            .send_slice(data, endpoint)
+            .map_err(to_network_error)?;
+        self.stack.notify();
+        Ok(data.len())
+    }
+
+    fn try_recv_from(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr)> {
+        let mut state = self.stack.lock();
+        let socket = state.udp(self.handle);
+        let (amt, endpoint) = if peek {
+            let (data, endpoint) = socket.peek().map_err(to_network_error)?;
+            (copy_to_uninit(buf, data), *endpoint)
+        } else {
+            let (data, endpoint) = socket.recv().map_err(to_network_error)?;
+            (copy_to_uninit(buf, data), endpoint)
+        };
+        Ok((amt, from_endpoint(endpoint)))
+    }
+}
+
+impl VirtualUdpSocket for UserspaceUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.multicast_loop_v4 = val;
+        Ok(())
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(self.multicast_loop_v4)
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.multicast_loop_v6 = val;
+        Ok(())
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Ok(self.multicast_loop_v6)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.multicast_ttl_v4 = ttl;
+        Ok(())
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(self.multicast_ttl_v4)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,462 @@
+//! Tracking of the pages of linear memories that are written to.
+//!
+//! On Linux the pages are write-protected with `userfaultfd` in its
+//! asynchronous mode, where the kernel resolves the write faults itself and
+//! only records that the page was written. The `PAGEMAP_SCAN` ioctl then
+//! reports the written pages and protects them again in a single step, so
+//! writes made by other threads while the memory is scanned are never lost.
+//!
+//! Where that is not available (older kernels, other platforms or when the
+//! process is not allowed to use `userfaultfd`) every page is hashed
+//! instead and compared against its hash from the previous scan.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use wasmer_types::{DirtyPageTracking, DirtyRanges, MemoryEpoch, MemoryError};
+
+/// Every tracker counts its epochs from a different base, so that epochs
+/// which came from another memory are never mistaken for its own
+static NEXT_TRACKER: AtomicU64 = AtomicU64::new(1);
+
+/// Keeps track of the epoch at which every page of a memory last changed.
+pub(crate) struct DirtyPageTracker {
+    method: Method,
+    page_size: usize,
+    /// Start of the memory, which changes when the memory moves as it grows
+    base: usize,
+    /// Epoch at which every accessible page was last seen changing
+    page_epochs: Vec<u64>,
+    /// Epoch that the changes found by the next scan belong to
+    epoch: u64,
+}
+
+enum Method {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    WriteProtect(uffd::WriteProtect),
+    Hashing(Vec<u64>),
+}
+
+impl Method {
+    fn new(_base: usize, _reserved: usize) -> Self {
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if let Some(wp) = uffd::WriteProtect::new(_base, _reserved) {
+            return Self::WriteProtect(wp);
+        }
+        Self::Hashing(Vec::new())
+    }
+}
+
+impl std::fmt::Debug for DirtyPageTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirtyPageTracker")
+            .field("tracking", &self.tracking())
+            .field("pages", &self.page_epochs.len())
+            .field("epoch", &self.epoch)
+            .finish()
+    }
+}
+
+impl DirtyPageTracker {
+    /// Starts tracking the memory at `base`, of which `len` bytes are
+    /// accessible out of the `reserved` bytes that are mapped.
+    pub(crate) fn new(base: *mut u8, reserved: usize, len: usize) -> Result<Self, MemoryError> {
+        let base = base as usize;
+        let mut ret = Self {
+            method: Method::new(base, reserved),
+            page_size: region::page::size(),
+            base,
+            page_epochs: Vec::new(),
+            epoch: NEXT_TRACKER.fetch_add(1, Ordering::Relaxed) << 32,
+        };
+        // Everything that is in the memory already belongs to the first epoch
+        ret.scan(base as *mut u8, reserved, len)?;
+        ret.epoch += 1;
+        Ok(ret)
+    }
+
+    pub(crate) fn tracking(&self) -> DirtyPageTracking {
+        match self.method {
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            Method::WriteProtect(_) => DirtyPageTracking::WriteProtect,
+            Method::Hashing(_) => DirtyPageTracking::Hashing,
+        }
+    }
+
+    /// Returns the ranges of the memory that were written to since `since`
+    /// and starts a new epoch.
+    pub(crate) fn dirty_ranges_since(
+        &mut self,
+        base: *mut u8,
+        reserved: usize,
+        len: usize,
+        since: MemoryEpoch,
+    ) -> Result<DirtyRanges, MemoryError> {
+        self.scan(base, reserved, len)?;
+
+        // Epochs this tracker did not hand out yet belong to another memory
+        let since = if since.0 > self.epoch { 0 } else { since.0 };
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for (page, epoch) in self.page_epochs.iter().enumerate() {
+            if *epoch < since {
+                continue;
+            }
+            let start = (page * self.page_size) as u64;
+            let end = (len as u64).min(start + self.page_size as u64);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        self.epoch += 1;
+        Ok(DirtyRanges {
+            ranges,
+            epoch: MemoryEpoch(self.epoch),
+        })
+    }
+
+    /// Marks every page that changed since the last scan with the current epoch
+    fn scan(&mut self, base: *mut u8, reserved: usize, len: usize) -> Result<(), MemoryError> {
+        let pages = len.div_ceil(self.page_size);
+
+        // When the memory moved (as it grew) tracking starts over on the
+        // new mapping and all of it counts as changed
+        if base as usize != self.base {
+            self.method = Method::new(base as usize, reserved);
+            self.base = base as usize;
+            self.page_epochs.fill(self.epoch);
+        }
+        self.page_epochs.resize(pages, self.epoch);
+
+        match &mut self.method {
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            Method::WriteProtect(wp) => {
+                let page_epochs = &mut self.page_epochs;
+                let (base, epoch, page_size) = (self.base, self.epoch, self.page_size);
+                wp.scan(base, base + pages * page_size, |range| {
+                    let first = (range.start - base) / page_size;
+                    let last = (range.end - base).div_ceil(page_size);
+                    page_epochs[first..last.min(pages)].fill(epoch);
+                })
+                .map_err(|err| MemoryError::Region(err.to_string()))?;
+            }
+            Method::Hashing(hashes) => {
+                hashes.resize(pages, 0);
+                let data = unsafe { std::slice::from_raw_parts(base, pages * self.page_size) };
+                for ((page, hash), epoch) in data
+                    .chunks(self.page_size)
+                    .zip(hashes.iter_mut())
+                    .zip(self.page_epochs.iter_mut())
+                {
+                    let new_hash = hash_page(page);
+                    if *hash != new_hash {
+                        *hash = new_hash;
+                        *epoch = self.epoch;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cheap hash of the contents of a page, which only has to tell whether
+/// the page changed
+fn hash_page(page: &[u8]) -> u64 {
+    let mut words = page.chunks_exact(8);
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for word in words.by_ref() {
+        let word = u64::from_ne_bytes(word.try_into().unwrap());
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+    for byte in words.remainder() {
+        hash = (hash.rotate_left(5) ^ *byte as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+    hash
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod uffd {
+    use std::fs::File;
+    use std::io;
+    use std::ops::Range;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const UFFD_USER_MODE_ONLY: libc::c_int = 1;
+    const UFFD_API: u64 = 0xAA;
+    const UFFD_FEATURE_WP_HUGETLBFS_SHMEM: u64 = 1 << 12;
+    const UFFD_FEATURE_WP_UNPOPULATED: u64 = 1 << 13;
+    const UFFD_FEATURE_WP_ASYNC: u64 = 1 << 15;
+    const UFFDIO_API: u32 = 0xc018_aa3f;
+    const UFFDIO_REGISTER: u32 = 0xc020_aa00;
+    const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+    const PAGEMAP_SCAN: u32 = 0xc060_6610;
+    const PM_SCAN_WP_MATCHING: u64 = 1 << 0;
+    const PM_SCAN_CHECK_WPASYNC: u64 = 1 << 1;
+    const PAGE_IS_WRITTEN: u64 = 1 << 1;
+
+    #[repr(C)]
+    struct UffdioApi {
+        api: u64,
+        features: u64,
+        ioctls: u64,
+    }
+
+    #[repr(C)]
+    struct UffdioRegister {
+        start: u64,
+        len: u64,
+        mode: u64,
+        ioctls: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct PageRegion {
+        start: u64,
+        end: u64,
+        categories: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct PmScanArg {
+        size: u64,
+        flags: u64,
+        start: u64,
+        end: u64,
+        walk_end: u64,
+        vec: u64,
+        vec_len: u64,
+        max_pages: u64,
+        category_inverted: u64,
+        category_mask: u64,
+        category_anyof_mask: u64,
+        return_mask: u64,
+    }
+
+    /// Memory that is write-protected with a `userfaultfd`, which stays
+    /// registered for as long as the file descriptor is open
+    pub(super) struct WriteProtect {
+        _uffd: OwnedFd,
+        pagemap: File,
+    }
+
+    impl WriteProtect {
+        /// Registers the mapping, or returns `None` if the kernel or the
+        /// mapping does not support asynchronous write protection
+        pub(super) fn new(base: usize, reserved: usize) -> Option<Self> {
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_userfaultfd,
+                    libc::O_CLOEXEC | libc::O_NONBLOCK | UFFD_USER_MODE_ONLY,
+                )
+            };
+            if fd < 0 {
+                return None;
+            }
+            let uffd = unsafe { OwnedFd::from_raw_fd(fd as _) };
+
+            let mut api = UffdioApi {
+                api: UFFD_API,
+                features: UFFD_FEATURE_WP_ASYNC
+                    | UFFD_FEATURE_WP_UNPOPULATED
+                    | UFFD_FEATURE_WP_HUGETLBFS_SHMEM,
+                ioctls: 0,
+            };
+            if unsafe { libc::ioctl(uffd.as_raw_fd(), UFFDIO_API as libc::Ioctl, &mut api) } != 0 {
+                return None;
+            }
+
+            let mut register = UffdioRegister {
+                start: base as u64,
+                len: reserved as u64,
+                mode: UFFDIO_REGISTER_MODE_WP,
+                ioctls: 0,
+            };
+            if unsafe {
+                libc::ioctl(
+                    uffd.as_raw_fd(),
+                    UFFDIO_REGISTER as libc::Ioctl,
+                    &mut register,
+                )
+            } != 0
+            {
+                return None;
+            }
+
+            let pagemap = File::open("/proc/self/pagemap").ok()?;
+            Some(Self {
+                _uffd: uffd,
+                pagemap,
+            })
+        }
+
+        /// Calls `f` with every range between `start` and `end` that was
+        /// written to and protects those ranges again
+        pub(super) fn scan(
+            &self,
+            start: usize,
+            end: usize,
+            mut f: impl FnMut(Range<usize>),
+        ) -> io::Result<()> {
+            let mut regions = [PageRegion::default(); 64];
+            let mut start = start as u64;
+            let end = end as u64;
+            while start < end {
+                let mut arg = PmScanArg {
+                    size: std::mem::size_of::<PmScanArg>() as u64,
+                    flags: PM_SCAN_WP_MATCHING | PM_SCAN_CHECK_WPASYNC,
+                    start,
+                    end,
+                    vec: regions.as_mut_ptr() as u64,
+                    vec_len: regions.len() as u64,
+                    category_mask: PAGE_IS_WRITTEN,
+                    return_mask: PAGE_IS_WRITTEN,
+                    ..Default::default()
+                };
+                let found = unsafe {
+                    libc::ioctl(
+                        self.pagemap.as_raw_fd(),
+                        PAGEMAP_SCAN as libc::Ioctl,
+                        &mut arg,
+                    )
+                };
+                if found < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                for region in &regions[..found as usize] {
+                    f(region.start as usize..region.end as usize);
+                }
+                start = arg.walk_end;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LinearMemory, VMOwnedMemory, VMSharedMemory};
+    use wasmer_types::{MemoryStyle, MemoryType, Pages, WASM_PAGE_SIZE};
+
+    fn write(memory: &dyn LinearMemory, offset: usize, data: &[u8]) {
+        unsafe { memory.initialize_with_data(offset, data).unwrap() }
+    }
+
+    fn pages(memory: &mut dyn LinearMemory, since: MemoryEpoch) -> (Vec<Range<u64>>, MemoryEpoch) {
+        let dirty = memory.dirty_ranges_since(since).unwrap();
+        let page_size = region::page::size() as u64;
+        let ranges = dirty
+            .ranges
+            .iter()
+            .map(|r| r.start / page_size..r.end.div_ceil(page_size))
+            .collect();
+        (ranges, dirty.epoch)
+    }
+
+    fn check(memory: &mut dyn LinearMemory, moves_on_grow: bool) {
+        let page_size = region::page::size();
+        let size = memory.size().bytes().0 as u64 / page_size as u64;
+        memory.enable_dirty_tracking().unwrap();
+
+        // Everything is dirty since tracking started
+        let (ranges, epoch) = pages(memory, MemoryEpoch::START);
+        assert_eq!(ranges, vec![0..size]);
+
+        // Epochs of other memories do not hide any writes
+        let (ranges, _) = pages(memory, MemoryEpoch(u64::MAX));
+        assert_eq!(ranges, vec![0..size]);
+
+        let (ranges, epoch) = pages(memory, epoch);
+        assert_eq!(ranges, vec![]);
+
+        write(memory, page_size * 3 + 10, b"hello");
+        write(memory, page_size * 7 - 1, b"ab");
+        let (ranges, second) = pages(memory, epoch);
+        assert_eq!(ranges, vec![3..4, 6..8]);
+
+        // Consumers with an older epoch still see the older writes
+        write(memory, page_size * 12, b"x");
+        let (ranges, _) = pages(memory, epoch);
+        assert_eq!(ranges, vec![3..4, 6..8, 12..13]);
+        let (ranges, third) = pages(memory, second);
+        assert_eq!(ranges, vec![12..13]);
+
+        // Growing the memory makes the new pages dirty, and all of them
+        // when the memory had to move
+        memory.grow(Pages(1)).unwrap();
+        let new_size = memory.size().bytes().0 as u64 / page_size as u64;
+        let (ranges, _) = pages(memory, third);
+        if moves_on_grow {
+            assert_eq!(ranges, vec![0..new_size]);
+        } else {
+            assert_eq!(ranges, vec![size..new_size]);
+        }
+    }
+
+    #[test]
+    fn owned_memory_tracks_dirty_pages() {
+        let ty = MemoryType::new(2, Some(4), false);
+        let style = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+        let mut memory = VMOwnedMemory::new(&ty, &style).unwrap();
+        check(&mut memory, true);
+    }
+
+    #[test]
+    fn shared_memory_tracks_dirty_pages() {
+        let ty = MemoryType::new(2, Some(4), true);
+        let style = MemoryStyle::Static {
+            bound: Pages(4),
+            offset_guard_size: WASM_PAGE_SIZE as u64,
+        };
+        let mut memory = VMSharedMemory::new(&ty, &style).unwrap();
+        check(&mut memory, false);
+    }
+
+    #[test]
+    fn hashing_finds_changed_pages() {
+        let page_size = region::page::size();
+        let mut data = vec![0u8; page_size * 4];
+        let mut tracker = DirtyPageTracker {
+            method: Method::Hashing(Vec::new()),
+            page_size,
+            base: data.as_mut_ptr() as usize,
+            page_epochs: Vec::new(),
+            epoch: 0,
+        };
+        let base = data.as_mut_ptr();
+        tracker.scan(base, data.len(), data.len()).unwrap();
+        tracker.epoch += 1;
+
+        let dirty = tracker
+            .dirty_ranges_since(base, data.len(), data.len(), MemoryEpoch(1))
+            .unwrap();
+        assert_eq!(dirty.ranges, vec![]);
+
+        unsafe { *base.add(page_size * 2 + 5) = 1 };
+        let dirty = tracker
+            .dirty_ranges_since(base, data.len(), data.len(), dirty.epoch)
+            .unwrap();
+        let page_size = page_size as u64;
+        assert_eq!(dirty.ranges, vec![page_size * 2..page_size * 3]);
+    }
+}
@@ -7,6 +7,7 @@
 //! how it is allocated and deallocated.
 
 mod allocator;
+mod snapshot;
 
 use crate::export::VMExtern;
 use crate::imports::Imports;
@@ -20,7 +21,7 @@ use crate::vmcontext::{
     VMTrampoline, memory_copy, memory_fill, memory32_atomic_check32, memory32_atomic_check64,
 };
 use crate::{FunctionBodyPtr, MaybeInstanceOwned, TrapHandlerFn, VMTag, wasmer_call_trampoline};
-use crate::{LinearMemory, NotifyLocation};
+use crate::{LinearMemory, MemoryImage, NotifyLocation};
 use crate::{VMConfig, VMFuncRef, VMFunction, VMGlobal, VMMemory, VMTable};
 pub use allocator::InstanceAllocator;
 use memoffset::offset_of;
@@ -438,7 +439,7 @@ impl Instance {
             .memories
             .get(memory_index)
             .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()));
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Grow imported memory by the specified amount of pages.
@@ -459,7 +460,7 @@ impl Instance {
     {
         let import = self.imported_memory(memory_index);
         let mem = import.handle;
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Returns the number of allocated wasm pages.
@@ -515,7 +516,7 @@ impl Instance {
             .tables
             .get(table_index)
             .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Grow table by the specified amount of elements.
@@ -530,7 +531,7 @@ impl Instance {
     ) -> Option<u32> {
         let import = self.imported_table(table_index);
         let table = import.handle;
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Get table element by index.
@@ -1198,6 +1199,28 @@ impl VMInstance {
         Ok(())
     }
 
+    /// Maps `image` copy-on-write over the local memory `index`, in place
+    /// of the data initializers of that memory.
+    ///
+    /// Returns `false` if the memory does not support memory images, in
+    /// which case its data initializers must be applied as usual.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call immediately after instantiation, before
+    /// [`Self::finish_instantiation`].
+    pub unsafe fn initialize_memory_with_image(
+        &mut self,
+        index: LocalMemoryIndex,
+        image: &MemoryImage,
+    ) -> Result<bool, MemoryError> {
+        unsafe {
+            self.instance_mut()
+                .get_local_vmmemory_mut(index)
+                .initialize_with_image(image)
+        }
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     pub fn vmctx(&self) -> &VMContext {
         self.instance().vmctx()
@@ -0,0 +1,315 @@
+//! Capturing the mutable state of an instance and restoring it into a
+//! fresh instance of the same module.
+
+use super::{Instance, VMInstance};
+use crate::VMFuncRef;
+use crate::table::TableElement;
+use std::collections::HashMap;
+use std::ptr::NonNull;
+use std::slice;
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    FunctionIndex, InstanceSnapshot, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex,
+    Mutability, Pages, RawValue, SnapshotError, SnapshotValue, Type, WASM_PAGE_SIZE,
+};
+
+impl Instance {
+    /// Maps the func refs this instance hands out back to their function
+    /// index.
+    fn func_ref_indices(&self) -> HashMap<VMFuncRef, FunctionIndex> {
+        let mut indices = HashMap::new();
+        for (index, func_ref) in self.imported_funcrefs.iter() {
+            indices.entry(VMFuncRef(*func_ref)).or_insert(index);
+        }
+        for (local_index, func_ref) in self.funcrefs.iter() {
+            indices.insert(
+                VMFuncRef(NonNull::from(func_ref)),
+                self.module.func_index(local_index),
+            );
+        }
+        indices
+    }
+
+    /// Captures the memories, mutable globals, tables and dropped segments
+    /// defined by the module of this instance.
+    pub(crate) fn snapshot(&self) -> Result<InstanceSnapshot, SnapshotError> {
+        let func_refs = self.func_ref_indices();
+        let func_index = |func_ref: Option<VMFuncRef>| {
+            func_ref
+                .map(|func_ref| {
+                    func_refs.get(&func_ref).copied().ok_or_else(|| {
+                        SnapshotError::UnsupportedReference(
+                            "a function of another instance".to_string(),
+                        )
+                    })
+                })
+                .transpose()
+        };
+
+        let memories = self
+            .memories
+            .keys()
+            .map(|index| {
+                let memory = self.memory(index);
+                unsafe { slice::from_raw_parts(memory.base, memory.current_length) }.to_vec()
+            })
+            .collect();
+
+        let globals = self
+            .globals
+            .values()
+            .map(|handle| {
+                let global = handle.get(self.context());
+                let ty = global.ty();
+                if ty.mutability == Mutability::Const {
+                    return Ok(None);
+                }
+                let raw = unsafe { global.vmglobal().as_ref().val };
+                let value = match ty.ty {
+                    Type::FuncRef => {
+                        SnapshotValue::Ref(func_index(unsafe { VMFuncRef::from_raw(raw) })?)
+                    }
+                    ty if ty.is_ref() => {
+                        if unsafe { raw.externref } != 0 {
+                            return Err(SnapshotError::UnsupportedReference(format!(
+                                "a non-null {ty}"
+                            )));
+                        }
+                        SnapshotValue::Ref(None)
+                    }
+                    _ => SnapshotValue::Bytes(unsafe { raw.bytes }),
+                };
+                Ok(Some(value))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let tables = self
+            .tables
+            .values()
+            .map(|handle| {
+                let table = handle.get(self.context());
+                (0..table.size())
+                    .map(|index| match table.get(index) {
+                        Some(TableElement::FuncRef(func_ref)) => func_index(func_ref),
+                        Some(TableElement::ExternRef(Some(_))) => Err(
+                            SnapshotError::UnsupportedReference("a non-null externref".to_string()),
+                        ),
+                        Some(TableElement::ExternRef(None)) | None => Ok(None),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+
+        let passive_data = self.passive_data.borrow();
+        let mut dropped_data = self
+            .module
+            .passive_data
+            .keys()
+            .filter(|index| !passive_data.contains_key(index))
+            .copied()
+            .collect::<Vec<_>>();
+        dropped_data.sort();
+
+        // Empty element segments are never kept, dropping them is a no-op.
+        let passive_elements = self.passive_elements.borrow();
+        let mut dropped_elements = self
+            .module
+            .passive_elements
+            .iter()
+            .filter(|(index, segment)| !segment.is_empty() && !passive_elements.contains_key(index))
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+        dropped_elements.sort();
+
+        Ok(InstanceSnapshot {
+            module_hash: self.module.hash(),
+            memories,
+            globals,
+            tables,
+            dropped_data,
+            dropped_elements,
+        })
+    }
+
+    /// Checks that `snapshot` can be restored into this instance, before
+    /// anything is modified.
+    fn check_snapshot(&self, snapshot: &InstanceSnapshot) -> Result<(), SnapshotError> {
+        let incompatible = |message: String| Err(SnapshotError::Incompatible(message));
+
+        if let (Some(expected), Some(found)) = (self.module.hash(), snapshot.module_hash) {
+            if expected != found {
+                return incompatible(format!(
+                    "taken from module {found}, this instance is of module {expected}"
+                ));
+            }
+        }
+        if snapshot.memories.len() != self.memories.len()
+            || snapshot.globals.len() != self.globals.len()
+            || snapshot.tables.len() != self.tables.len()
+        {
+            return incompatible(format!(
+                "{} memories, {} globals and {} tables, the module defines {}, {} and {}",
+                snapshot.memories.len(),
+                snapshot.globals.len(),
+                snapshot.tables.len(),
+                self.memories.len(),
+                self.globals.len(),
+                self.tables.len(),
+            ));
+        }
+
+        for (index, data) in snapshot.memories.iter().enumerate() {
+            let current_length = self.memory(LocalMemoryIndex::new(index)).current_length;
+            if data.len() % WASM_PAGE_SIZE != 0 || data.len() < current_length {
+                return incompatible(format!(
+                    "memory {index} has {} bytes, the instance has {current_length}",
+                    data.len()
+                ));
+            }
+        }
+
+        for (index, value) in snapshot.globals.iter().enumerate() {
+            let handle = self.globals[LocalGlobalIndex::new(index)];
+            let ty = handle.get(self.context()).ty();
+            let matches = match value {
+                None => ty.mutability == Mutability::Const,
+                Some(_) if ty.mutability == Mutability::Const => false,
+                Some(SnapshotValue::Bytes(_)) => ty.ty.is_num(),
+                Some(SnapshotValue::Ref(None)) => ty.ty.is_ref(),
+                Some(SnapshotValue::Ref(Some(_))) => ty.ty == Type::FuncRef,
+            };
+            if !matches {
+                return incompatible(format!("global {index} is a {ty}, not {value:?}"));
+            }
+        }
+
+        for (index, elements) in snapshot.tables.iter().enumerate() {
+            let handle = self.tables[LocalTableIndex::new(index)];
+            let table = handle.get(self.context());
+            if elements.len() < table.size() as usize {
+                return incompatible(format!(
+                    "table {index} has {} elements, the instance has {}",
+                    elements.len(),
+                    table.size()
+                ));
+            }
+            if table.ty().ty != Type::FuncRef && elements.iter().any(Option::is_some) {
+                return incompatible(format!("table {index} does not hold functions"));
+            }
+        }
+
+        let functions = self.module.functions.len();
+        let indices = snapshot
+            .globals
+            .iter()
+            .filter_map(|value| match value {
+                Some(SnapshotValue::Ref(index)) => *index,
+                _ => None,
+            })
+            .chain(snapshot.tables.iter().flatten().filter_map(|index| *index));
+        for index in indices {
+            if index.index() >= functions {
+                return incompatible(format!(
+                    "function {} does not exist in the module",
+                    index.as_u32()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restores a snapshot taken by [`Self::snapshot`] into this instance.
+    pub(crate) fn restore(&mut self, snapshot: &InstanceSnapshot) -> Result<(), SnapshotError> {
+        self.check_snapshot(snapshot)?;
+
+        for (index, data) in snapshot.memories.iter().enumerate() {
+            let index = LocalMemoryIndex::new(index);
+            let current_length = self.memory(index).current_length;
+            if data.len() > current_length {
+                let delta = (data.len() - current_length) / WASM_PAGE_SIZE;
+                self.memory_grow(index, Pages(delta as u32))?;
+            }
+            let memory = self.memory(index);
+            if memory.current_length != data.len() {
+                return Err(SnapshotError::Incompatible(format!(
+                    "memory {} grew to {} bytes instead of {}",
+                    index.index(),
+                    memory.current_length,
+                    data.len()
+                )));
+            }
+            unsafe { slice::from_raw_parts_mut(memory.base, data.len()) }.copy_from_slice(data);
+        }
+
+        for (index, value) in snapshot.globals.iter().enumerate() {
+            let raw = match *value {
+                None => continue,
+                Some(SnapshotValue::Bytes(bytes)) => RawValue { bytes },
+                Some(SnapshotValue::Ref(function)) => function
+                    .and_then(|function| self.func_ref(function))
+                    .map_or_else(RawValue::default, VMFuncRef::into_raw),
+            };
+            unsafe {
+                (*self.global_ptr(LocalGlobalIndex::new(index)).as_ptr()).val = raw;
+            }
+        }
+
+        for (index, elements) in snapshot.tables.iter().enumerate() {
+            let index = LocalTableIndex::new(index);
+            let holds_functions = self.get_local_table(index).ty().ty == Type::FuncRef;
+            let null = if holds_functions {
+                TableElement::FuncRef(None)
+            } else {
+                TableElement::ExternRef(None)
+            };
+            let size = self.get_local_table(index).size();
+            let len = elements.len() as u32;
+            if len > size && self.table_grow(index, len - size, null.clone()).is_none() {
+                return Err(SnapshotError::Incompatible(format!(
+                    "table {} could not grow to {len} elements",
+                    index.index()
+                )));
+            }
+            for (element_index, function) in elements.iter().enumerate() {
+                let element = match function {
+                    Some(function) => TableElement::FuncRef(self.func_ref(*function)),
+                    None => null.clone(),
+                };
+                self.table_set(index, element_index as u32, element)
+                    .expect("table was grown to the snapshot size");
+            }
+        }
+
+        for index in &snapshot.dropped_data {
+            self.data_drop(*index);
+        }
+        for index in &snapshot.dropped_elements {
+            self.elem_drop(*index);
+        }
+
+        Ok(())
+    }
+}
+
+impl VMInstance {
+    /// Captures the mutable state defined by the module of this instance:
+    /// its memories, mutable globals, tables and dropped passive segments.
+    ///
+    /// Function references are recorded as function indices, so only null
+    /// references and functions of this instance (local or imported) can be
+    /// captured. Imported memories, tables and globals are not included.
+    pub fn snapshot(&self) -> Result<InstanceSnapshot, SnapshotError> {
+        self.instance().snapshot()
+    }
+
+    /// Restores a snapshot taken by [`Self::snapshot`] into this instance,
+    /// which should be a fresh instance of the same module.
+    ///
+    /// Memories and tables are grown to the size they had in the snapshot.
+    /// The instance is left untouched if the snapshot does not match its
+    /// module.
+    pub fn restore(&mut self, snapshot: &InstanceSnapshot) -> Result<(), SnapshotError> {
+        self.instance_mut().restore(snapshot)
+    }
+}
@@ -14,6 +14,7 @@
 )]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod dirty_pages;
 mod exception_ref;
 mod export;
 mod extern_ref;
@@ -21,8 +22,11 @@ mod function_env;
 mod global;
 mod imports;
 mod instance;
+mod limiter;
 mod memory;
+mod memory_image;
 mod mmap;
+mod pooling;
 mod probestack;
 mod sig_registry;
 mod store;
@@ -42,11 +46,14 @@ pub use crate::function_env::VMFunctionEnvironment;
 pub use crate::global::*;
 pub use crate::imports::Imports;
 pub use crate::instance::{InstanceAllocator, VMInstance};
+pub use crate::limiter::{LimitedMemory, LimitedTable, ResourceLimiter};
 pub use crate::memory::{
     LinearMemory, NotifyLocation, VMMemory, VMOwnedMemory, VMSharedMemory,
     initialize_memory_with_data,
 };
+pub use crate::memory_image::MemoryImage;
 pub use crate::mmap::{Mmap, MmapType};
+pub use crate::pooling::{InstanceSlot, PoolingAllocator, PoolingConfig};
 pub use crate::probestack::PROBESTACK;
 pub use crate::sig_registry::SignatureRegistry;
 pub use crate::store::{InternalStoreHandle, MaybeInstanceOwned, StoreHandle, StoreObjects};
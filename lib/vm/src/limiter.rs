@@ -0,0 +1,70 @@
+//! Store-level limits on the growth of memories and tables.
+
+use crate::{InternalStoreHandle, VMMemory, VMTable};
+use std::fmt;
+use wasmer_types::{MemoryError, MemoryIndex, Pages, TableIndex};
+
+/// Identifies the memory a [`ResourceLimiter`] is consulted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitedMemory {
+    /// A memory of the store.
+    Store(InternalStoreHandle<VMMemory>),
+    /// A local memory of an instance being created, by its index in the
+    /// module, before it is added to the store.
+    Instantiating(MemoryIndex),
+}
+
+/// Identifies the table a [`ResourceLimiter`] is consulted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitedTable {
+    /// A table of the store.
+    Store(InternalStoreHandle<VMTable>),
+    /// A local table of an instance being created, by its index in the
+    /// module, before it is added to the store.
+    Instantiating(TableIndex),
+}
+
+/// Used by hosts to limit the memories and tables of all the instances of
+/// a store.
+///
+/// A limiter is installed on the [`StoreObjects`](crate::StoreObjects) of
+/// a store, and is consulted whenever a memory or a table of the store is
+/// about to grow, either by a `memory.grow`/`table.grow` instruction or
+/// by the host. It is also consulted for the initial size of the memories
+/// and tables of every new instance, as a growth from zero.
+///
+/// Denying a growth is not a trap: `memory.grow` and `table.grow` return
+/// -1, and the corresponding host calls return an error.
+pub trait ResourceLimiter: fmt::Debug + Send + Sync {
+    /// Called when `memory` is about to grow from `current` to `desired`
+    /// pages. `maximum` is the maximum declared by the memory type, if any.
+    ///
+    /// Returns whether the growth is allowed.
+    fn memory_growing(
+        &mut self,
+        memory: LimitedMemory,
+        current: Pages,
+        desired: Pages,
+        maximum: Option<Pages>,
+    ) -> bool;
+
+    /// Called when `table` is about to grow from `current` to `desired`
+    /// elements. `maximum` is the maximum declared by the table type, if any.
+    ///
+    /// Returns whether the growth is allowed.
+    fn table_growing(
+        &mut self,
+        table: LimitedTable,
+        current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> bool;
+
+    /// Called when a growth of `memory` allowed by [`Self::memory_growing`]
+    /// failed anyway, for example because it exceeds the memory maximum.
+    fn memory_grow_failed(&mut self, _memory: LimitedMemory, _error: &MemoryError) {}
+
+    /// Called when a growth of `table` allowed by [`Self::table_growing`]
+    /// failed anyway.
+    fn table_grow_failed(&mut self, _table: LimitedTable) {}
+}
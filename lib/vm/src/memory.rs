@@ -5,6 +5,8 @@
 //!
 //! `Memory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
+use crate::dirty_pages::DirtyPageTracker;
+use crate::memory_image::MemoryImage;
 use crate::mmap::MmapType;
 use crate::threadconditions::ThreadConditions;
 pub use crate::threadconditions::{NotifyLocation, WaiterError};
@@ -18,7 +20,10 @@ use std::rc::Rc;
 use std::slice;
 use std::sync::RwLock;
 use std::time::Duration;
-use wasmer_types::{Bytes, MemoryError, MemoryStyle, MemoryType, Pages, WASM_PAGE_SIZE};
+use wasmer_types::{
+    Bytes, DirtyPageTracking, DirtyRanges, MemoryEpoch, MemoryError, MemoryStyle, MemoryType,
+    Pages, WASM_PAGE_SIZE,
+};
 
 // The memory mapped area
 #[derive(Debug)]
@@ -27,8 +32,12 @@ struct WasmMmap {
     alloc: Mmap,
     // The current logical size in wasm pages of this linear memory.
     size: Pages,
+    // Whether the allocation maps a backing file.
+    file_backed: bool,
     /// The owned memory definition used by the generated code
     vm_memory_definition: MaybeInstanceOwned<VMMemoryDefinition>,
+    // Tracks the pages that are written to, once it is enabled.
+    dirty_pages: Option<DirtyPageTracker>,
 }
 
 impl WasmMmap {
@@ -103,6 +112,7 @@ impl WasmMmap {
             new_mmap.as_mut_slice()[..copy_len].copy_from_slice(&self.alloc.as_slice()[..copy_len]);
 
             self.alloc = new_mmap;
+            self.file_backed = false;
         } else if delta_bytes > 0 {
             // Make the newly allocated pages accessible.
             self.alloc
@@ -142,6 +152,35 @@ impl WasmMmap {
         Ok(())
     }
 
+    /// Starts tracking the pages that are written to
+    fn enable_dirty_tracking(&mut self) -> Result<DirtyPageTracking, MemoryError> {
+        if let Some(tracker) = self.dirty_pages.as_ref() {
+            return Ok(tracker.tracking());
+        }
+        let tracker = DirtyPageTracker::new(
+            self.alloc.as_mut_ptr(),
+            self.alloc.len(),
+            self.size.bytes().0,
+        )?;
+        let tracking = tracker.tracking();
+        self.dirty_pages = Some(tracker);
+        Ok(tracking)
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`
+    fn dirty_ranges_since(&mut self, epoch: MemoryEpoch) -> Result<DirtyRanges, MemoryError> {
+        let base = self.alloc.as_mut_ptr();
+        let reserved = self.alloc.len();
+        let len = self.size.bytes().0;
+        let tracker =
+            self.dirty_pages
+                .as_mut()
+                .ok_or_else(|| MemoryError::UnsupportedOperation {
+                    message: "dirty page tracking is not enabled for this memory".to_string(),
+                })?;
+        tracker.dirty_ranges_since(base, reserved, len, epoch)
+    }
+
     /// Copies the memory
     /// (in this case it performs a copy-on-write to save memory)
     pub fn copy(&mut self) -> Result<Self, MemoryError> {
@@ -160,6 +199,8 @@ impl WasmMmap {
             ))),
             alloc,
             size: self.size,
+            file_backed: false,
+            dirty_pages: None,
         })
     }
 }
@@ -324,6 +365,7 @@ impl VMOwnedMemory {
             let mapped_pages = memory.minimum;
             let mapped_bytes = mapped_pages.bytes();
 
+            let file_backed = backing_file.is_some();
             let mut alloc =
                 Mmap::accessible_reserved(mapped_bytes.0, request_bytes, backing_file, memory_type)
                     .map_err(MemoryError::Region)?;
@@ -351,6 +393,8 @@ impl VMOwnedMemory {
                 },
                 alloc,
                 size: Bytes::from(mem_length).try_into().unwrap(),
+                file_backed,
+                dirty_pages: None,
             };
 
             Ok(Self {
@@ -425,6 +469,16 @@ impl LinearMemory for VMOwnedMemory {
         self.mmap.vm_memory_definition.as_ptr()
     }
 
+    /// Starts tracking the pages of the memory that are written to
+    fn enable_dirty_tracking(&mut self) -> Result<DirtyPageTracking, MemoryError> {
+        self.mmap.enable_dirty_tracking()
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`
+    fn dirty_ranges_since(&mut self, epoch: MemoryEpoch) -> Result<DirtyRanges, MemoryError> {
+        self.mmap.dirty_ranges_since(epoch)
+    }
+
     /// Owned memory can not be cloned (this will always return None)
     fn try_clone(&self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         Err(MemoryError::MemoryNotShared)
@@ -435,6 +489,20 @@ impl LinearMemory for VMOwnedMemory {
         let forked = Self::copy(self)?;
         Ok(Box::new(forked))
     }
+
+    /// Maps the image copy-on-write over this memory, unless the memory
+    /// is backed by a file or is too small to hold the image.
+    #[cfg(target_os = "linux")]
+    unsafe fn initialize_with_image(&mut self, image: &MemoryImage) -> Result<bool, MemoryError> {
+        if self.mmap.file_backed || image.offset() + image.len() > self.mmap.size.bytes().0 {
+            return Ok(false);
+        }
+        self.mmap
+            .alloc
+            .map_file_private(image.offset(), image.len(), image.fd())
+            .map_err(MemoryError::Region)?;
+        Ok(true)
+    }
 }
 
 /// A shared linear memory instance.
@@ -581,6 +649,18 @@ impl LinearMemory for VMSharedMemory {
         guard.vm_memory_definition.as_ptr()
     }
 
+    /// Starts tracking the pages of the memory that are written to
+    fn enable_dirty_tracking(&mut self) -> Result<DirtyPageTracking, MemoryError> {
+        let mut guard = self.mmap.write().unwrap();
+        guard.enable_dirty_tracking()
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`
+    fn dirty_ranges_since(&mut self, epoch: MemoryEpoch) -> Result<DirtyRanges, MemoryError> {
+        let mut guard = self.mmap.write().unwrap();
+        guard.dirty_ranges_since(epoch)
+    }
+
     /// Shared memory can always be cloned
     fn try_clone(&self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         Ok(Box::new(self.clone()))
@@ -679,11 +759,26 @@ impl LinearMemory for VMMemory {
         self.0.try_clone()
     }
 
+    /// Starts tracking the pages of the memory that are written to
+    fn enable_dirty_tracking(&mut self) -> Result<DirtyPageTracking, MemoryError> {
+        self.0.enable_dirty_tracking()
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`
+    fn dirty_ranges_since(&mut self, epoch: MemoryEpoch) -> Result<DirtyRanges, MemoryError> {
+        self.0.dirty_ranges_since(epoch)
+    }
+
     /// Initialize memory with data
     unsafe fn initialize_with_data(&self, start: usize, data: &[u8]) -> Result<(), Trap> {
         unsafe { self.0.initialize_with_data(start, data) }
     }
 
+    /// Initialize memory with an image
+    unsafe fn initialize_with_image(&mut self, image: &MemoryImage) -> Result<bool, MemoryError> {
+        unsafe { self.0.initialize_with_image(image) }
+    }
+
     /// Copies this memory to a new memory
     fn copy(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         self.0.copy()
@@ -831,6 +926,25 @@ where
     /// Attempts to clone this memory (if its clonable)
     fn try_clone(&self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError>;
 
+    /// Starts tracking the pages of the memory that are written to, and
+    /// returns how they are tracked. Calling it again has no effect.
+    fn enable_dirty_tracking(&mut self) -> Result<DirtyPageTracking, MemoryError> {
+        Err(MemoryError::UnsupportedOperation {
+            message: "enable_dirty_tracking() is not supported".to_string(),
+        })
+    }
+
+    /// Returns the ranges of the memory that were written to since `epoch`,
+    /// along with the epoch to pass to get the ranges written after them.
+    ///
+    /// Pages count as written when their contents may have changed, and
+    /// pages added by growing the memory always count as written.
+    fn dirty_ranges_since(&mut self, _epoch: MemoryEpoch) -> Result<DirtyRanges, MemoryError> {
+        Err(MemoryError::UnsupportedOperation {
+            message: "dirty_ranges_since() is not supported".to_string(),
+        })
+    }
+
     #[doc(hidden)]
     /// # Safety
     /// This function is unsafe because WebAssembly specification requires that data is always set at initialization time.
@@ -843,6 +957,18 @@ where
         }
     }
 
+    /// Maps `image` copy-on-write over this memory, replacing its contents.
+    ///
+    /// Returns `false` if this memory does not support memory images, in
+    /// which case the data has to be written with `initialize_with_data`.
+    ///
+    /// # Safety
+    /// Like `initialize_with_data`, this must only be called at
+    /// initialization time, before the memory is used.
+    unsafe fn initialize_with_image(&mut self, _image: &MemoryImage) -> Result<bool, MemoryError> {
+        Ok(false)
+    }
+
     /// Copies this memory to a new memory
     fn copy(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError>;
 
@@ -0,0 +1,112 @@
+//! Copy-on-write images of the initial contents of linear memories.
+//!
+//! A [`MemoryImage`] holds the contents written by the active data
+//! segments of a module into one of its memories. Instead of copying the
+//! data segments into every new instance, memories which support it map
+//! the image copy-on-write, so that instantiating a module with a lot of
+//! static data only touches the pages the instance actually writes to.
+
+use std::fmt;
+#[cfg(target_os = "linux")]
+use std::fs::File;
+
+/// The initial contents of a linear memory, ready to be mapped
+/// copy-on-write into new memories.
+///
+/// Images are backed by a `memfd` on Linux, and are not supported on
+/// other platforms.
+pub struct MemoryImage {
+    /// The file holding the contents of the image.
+    #[cfg(target_os = "linux")]
+    file: File,
+    /// Offset of the image in the linear memory, aligned to the host page size.
+    offset: usize,
+    /// Length of the image, aligned to the host page size.
+    len: usize,
+}
+
+impl fmt::Debug for MemoryImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryImage")
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl MemoryImage {
+    /// Creates the image of a memory initialized with `segments`, each
+    /// made of the offset it is written at and its data. Segments are
+    /// applied in order, so later segments overwrite earlier ones.
+    ///
+    /// Returns `None` if there is no data to write or if memory images
+    /// are not supported on this platform.
+    #[cfg(target_os = "linux")]
+    pub fn new(segments: &[(usize, &[u8])]) -> Result<Option<Self>, String> {
+        use std::os::fd::FromRawFd;
+        use std::os::unix::fs::FileExt;
+
+        let Some(start) = segments
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(offset, _)| *offset)
+            .min()
+        else {
+            return Ok(None);
+        };
+        let end = segments
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(offset, data)| offset + data.len())
+            .max()
+            .unwrap_or(start);
+
+        let page_size = region::page::size();
+        let offset = start - start % page_size;
+        let len = end.next_multiple_of(page_size) - offset;
+
+        let fd = unsafe { libc::memfd_create(c"wasmer_memory_image".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len(len as u64).map_err(|e| e.to_string())?;
+        for (segment_offset, data) in segments.iter().filter(|(_, data)| !data.is_empty()) {
+            file.write_all_at(data, (segment_offset - offset) as u64)
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(Some(Self { file, offset, len }))
+    }
+
+    /// Creates the image of a memory initialized with `segments`.
+    ///
+    /// Memory images are not supported on this platform, so this always
+    /// returns `None`.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_segments: &[(usize, &[u8])]) -> Result<Option<Self>, String> {
+        Ok(None)
+    }
+
+    /// Returns the offset of the image in the linear memory.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the length of the image, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the image is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the file descriptor of the file holding the image.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn fd(&self) -> std::os::fd::RawFd {
+        use std::os::fd::AsRawFd;
+        self.file.as_raw_fd()
+    }
+}
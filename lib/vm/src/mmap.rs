@@ -269,6 +269,39 @@ impl Mmap {
             .map_err(|e| e.to_string())
     }
 
+    /// Map the first `len` bytes of the file `fd` copy-on-write over the
+    /// memory starting at `start`, replacing its contents.
+    /// `start` and `len` must be native page-size multiples and describe a range within
+    /// `self`'s accessible memory.
+    #[cfg(target_os = "linux")]
+    pub fn map_file_private(
+        &mut self,
+        start: usize,
+        len: usize,
+        fd: std::os::fd::RawFd,
+    ) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.total_size);
+        assert_le!(start, self.total_size - len);
+
+        let ptr = unsafe {
+            libc::mmap(
+                (self.ptr + start) as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_FIXED,
+                fd,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
     /// Make the memory starting at `start` and extending for `len` bytes accessible.
     /// `start` and `len` must be native page-size multiples and describe a range within
     /// `self`'s reserved memory.
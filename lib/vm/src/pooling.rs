@@ -0,0 +1,759 @@
+//! Pooling allocation of the memories and tables of instances.
+//!
+//! A [`PoolingAllocator`] reserves the address space of all the linear
+//! memories it can hand out when it is created, and splits it into fixed
+//! size slots. The pool has a sub-pool of slots for each kind of object,
+//! memories and tables, and a number of [`InstanceSlot`]s bounding the
+//! instances alive at the same time. Instances take an instance slot, and
+//! their memories and tables take a slot of their kind, which is given
+//! back when they are dropped: the pages a memory touched are released to
+//! the OS, but the address space stays reserved, so that instantiating
+//! does not need any `mmap` call.
+
+use crate::mmap::{Mmap, MmapType};
+use crate::store::MaybeInstanceOwned;
+use crate::table::RawTableElement;
+use crate::threadconditions::ThreadConditions;
+use crate::vmcontext::VMMemoryDefinition;
+use crate::{LinearMemory, VMMemory, VMOwnedMemory, VMTable, VMTableDefinition};
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use wasmer_types::{MemoryError, MemoryStyle, MemoryType, Pages, TableStyle, TableType};
+
+/// Configuration of a [`PoolingAllocator`].
+#[derive(Debug, Clone)]
+pub struct PoolingConfig {
+    /// Maximum number of instances which can be alive at the same time.
+    pub max_instances: u32,
+
+    /// Maximum number of local memories of an instance.
+    pub max_memories_per_instance: u32,
+
+    /// Maximum size of a memory, in wasm pages. This is the size of the
+    /// address space reserved for each memory.
+    pub max_memory_pages: Pages,
+
+    /// Size in bytes of the guard region reserved after each memory.
+    pub memory_offset_guard_size: u64,
+
+    /// Maximum number of local tables of an instance.
+    pub max_tables_per_instance: u32,
+
+    /// Maximum number of elements of a table.
+    pub max_table_elements: u32,
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            max_instances: 1000,
+            max_memories_per_instance: 1,
+            max_memory_pages: Pages(160),
+            memory_offset_guard_size: 0x1_0000,
+            max_tables_per_instance: 1,
+            max_table_elements: 10_000,
+        }
+    }
+}
+
+/// An allocator handing out pre-reserved slots for the memories and tables
+/// of instances, up to a fixed number of instances alive at the same time.
+///
+/// The pool has `max_instances * max_memories_per_instance` memory slots
+/// and `max_instances * max_tables_per_instance` table slots, shared by
+/// the instances and by the memories and tables created on their own.
+///
+/// Cloning a `PoolingAllocator` gives another handle to the same pool.
+#[derive(Clone)]
+pub struct PoolingAllocator {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    config: PoolingConfig,
+    /// The address space of all the memory slots.
+    memories: Mmap,
+    /// Size in bytes of a memory slot, guard region included.
+    memory_slot_size: usize,
+    /// Indexes of the instance slots which are not in use.
+    free_instances: Mutex<Vec<u32>>,
+    /// Indexes of the memory slots which are not in use. The slots whose
+    /// pages could not be released are retired, and never given back.
+    free_memories: Mutex<Vec<u32>>,
+    /// Indexes of the table slots which are not in use.
+    free_tables: Mutex<Vec<u32>>,
+    /// Table storage retained by each table slot, to be reused by the next
+    /// table using it.
+    table_storage: Vec<Mutex<Vec<RawTableElement>>>,
+}
+
+// The raw table elements retained in the pool are never dereferenced, and
+// the memory slots are only accessed through the memories using them.
+unsafe impl Send for PoolInner {}
+unsafe impl Sync for PoolInner {}
+
+impl fmt::Debug for PoolingAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolingAllocator")
+            .field("config", &self.inner.config)
+            .field("available_slots", &self.available_slots())
+            .field("available_memory_slots", &self.available_memory_slots())
+            .field("available_table_slots", &self.available_table_slots())
+            .finish()
+    }
+}
+
+impl PoolingAllocator {
+    /// Creates a pool, reserving the address space of all its memories.
+    pub fn new(config: PoolingConfig) -> Result<Self, String> {
+        let page_size = region::page::size();
+        let memory_slot_size = config
+            .max_memory_pages
+            .bytes()
+            .0
+            .checked_add(config.memory_offset_guard_size as usize)
+            .map(|size| size.next_multiple_of(page_size))
+            .ok_or_else(|| "the memory slot size overflows".to_string())?;
+        let num_memories = config
+            .max_instances
+            .checked_mul(config.max_memories_per_instance)
+            .ok_or_else(|| "the number of memory slots overflows".to_string())?;
+        let num_tables = config
+            .max_instances
+            .checked_mul(config.max_tables_per_instance)
+            .ok_or_else(|| "the number of table slots overflows".to_string())?;
+        let total_size = memory_slot_size
+            .checked_mul(num_memories as usize)
+            .ok_or_else(|| "the size of the memory pool overflows".to_string())?;
+        let memories = Mmap::accessible_reserved(0, total_size, None, MmapType::Private)?;
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                memories,
+                memory_slot_size,
+                free_instances: Mutex::new((0..config.max_instances).rev().collect()),
+                free_memories: Mutex::new((0..num_memories).rev().collect()),
+                free_tables: Mutex::new((0..num_tables).rev().collect()),
+                table_storage: (0..num_tables).map(|_| Mutex::new(Vec::new())).collect(),
+                config,
+            }),
+        })
+    }
+
+    /// Returns the configuration of this pool.
+    pub fn config(&self) -> &PoolingConfig {
+        &self.inner.config
+    }
+
+    /// Returns the number of instance slots which are not in use.
+    pub fn available_slots(&self) -> usize {
+        self.inner.free_instances.lock().unwrap().len()
+    }
+
+    /// Returns the number of memory slots which are not in use.
+    pub fn available_memory_slots(&self) -> usize {
+        self.inner.free_memories.lock().unwrap().len()
+    }
+
+    /// Returns the number of table slots which are not in use.
+    pub fn available_table_slots(&self) -> usize {
+        self.inner.free_tables.lock().unwrap().len()
+    }
+
+    /// Takes a slot for the memories and tables of a new instance.
+    ///
+    /// The slot is given back to the pool once it is dropped, along with
+    /// all the memories and tables allocated from it.
+    pub fn allocate_instance(&self) -> Result<InstanceSlot, String> {
+        let index = self
+            .inner
+            .free_instances
+            .lock()
+            .unwrap()
+            .pop()
+            .ok_or_else(|| {
+                format!(
+                    "all the {} instance slots of the pool are in use",
+                    self.inner.config.max_instances
+                )
+            })?;
+        Ok(InstanceSlot {
+            inner: Arc::new(SlotInner {
+                pool: self.inner.clone(),
+                index,
+                memories: Mutex::new(0),
+                tables: Mutex::new(0),
+            }),
+        })
+    }
+
+    /// Creates a memory in a memory slot of the pool, outside of any
+    /// instance slot, with metadata owned by a VM and pointed to by
+    /// `vm_memory_location`.
+    ///
+    /// See [`InstanceSlot::memory_from_definition`] for the memories which
+    /// fit in a slot.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn memory_from_definition(
+        &self,
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        unsafe { PooledMemory::allocate(&self.inner, None, memory, style, vm_memory_location) }
+    }
+
+    /// Creates a table in a table slot of the pool, outside of any instance
+    /// slot, with metadata owned by a VM and pointed to by
+    /// `vm_table_location`.
+    ///
+    /// # Safety
+    /// - `vm_table_location` must point to a valid location in VM memory.
+    pub unsafe fn table_from_definition(
+        &self,
+        table: &TableType,
+        style: &TableStyle,
+        vm_table_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        unsafe { pooled_table(&self.inner, None, table, style, vm_table_location) }
+    }
+}
+
+/// A slot of a [`PoolingAllocator`], holding the memories and tables of
+/// one instance.
+#[derive(Clone)]
+pub struct InstanceSlot {
+    inner: Arc<SlotInner>,
+}
+
+struct SlotInner {
+    pool: Arc<PoolInner>,
+    index: u32,
+    /// Number of memories allocated for this slot.
+    memories: Mutex<u32>,
+    /// Number of tables allocated for this slot.
+    tables: Mutex<u32>,
+}
+
+impl fmt::Debug for InstanceSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceSlot")
+            .field("index", &self.inner.index)
+            .finish()
+    }
+}
+
+impl Drop for SlotInner {
+    fn drop(&mut self) {
+        self.pool.free_instances.lock().unwrap().push(self.index);
+    }
+}
+
+impl InstanceSlot {
+    /// Creates the next memory of this slot, with metadata owned by a VM
+    /// and pointed to by `vm_memory_location`.
+    ///
+    /// Static memories must have a bound and a guard fitting in a memory
+    /// slot of the pool. Dynamic memories can grow up to the size of the
+    /// slot, minus their guard.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn memory_from_definition(
+        &self,
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let pool = &self.inner.pool;
+        {
+            let mut memories = self.inner.memories.lock().unwrap();
+            if *memories >= pool.config.max_memories_per_instance {
+                return Err(MemoryError::Generic(format!(
+                    "an instance of the pool can have at most {} memories",
+                    pool.config.max_memories_per_instance
+                )));
+            }
+            *memories += 1;
+        }
+        unsafe {
+            PooledMemory::allocate(pool, Some(self.clone()), memory, style, vm_memory_location)
+        }
+    }
+
+    /// Creates the next table of this slot, with metadata owned by a VM
+    /// and pointed to by `vm_table_location`.
+    ///
+    /// # Safety
+    /// - `vm_table_location` must point to a valid location in VM memory.
+    pub unsafe fn table_from_definition(
+        &self,
+        table: &TableType,
+        style: &TableStyle,
+        vm_table_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        let pool = &self.inner.pool;
+        {
+            let mut tables = self.inner.tables.lock().unwrap();
+            if *tables >= pool.config.max_tables_per_instance {
+                return Err(format!(
+                    "an instance of the pool can have at most {} tables",
+                    pool.config.max_tables_per_instance
+                ));
+            }
+            *tables += 1;
+        }
+        unsafe { pooled_table(pool, Some(self.clone()), table, style, vm_table_location) }
+    }
+}
+
+/// Creates a table in a table slot of `pool`.
+///
+/// # Safety
+/// - `vm_table_location` must point to a valid location in VM memory.
+unsafe fn pooled_table(
+    pool: &Arc<PoolInner>,
+    instance: Option<InstanceSlot>,
+    table: &TableType,
+    style: &TableStyle,
+    vm_table_location: NonNull<VMTableDefinition>,
+) -> Result<VMTable, String> {
+    if table.minimum > pool.config.max_table_elements {
+        return Err(format!(
+            "a table of {} elements does not fit in the table slots of the pool",
+            table.minimum
+        ));
+    }
+    let index = pool.free_tables.lock().unwrap().pop().ok_or_else(|| {
+        format!(
+            "all the {} table slots of the pool are in use",
+            pool.table_storage.len()
+        )
+    })?;
+    let slot = TableSlot {
+        pool: pool.clone(),
+        index,
+        _instance: instance,
+    };
+
+    let storage = std::mem::take(&mut *pool.table_storage[index as usize].lock().unwrap());
+    let storage = if storage.capacity() == 0 {
+        Vec::with_capacity(pool.config.max_table_elements as usize)
+    } else {
+        storage
+    };
+    unsafe {
+        VMTable::from_definition_pooled(
+            table,
+            style,
+            vm_table_location,
+            storage,
+            pool.config.max_table_elements,
+            slot,
+        )
+    }
+}
+
+/// A table slot of a [`PoolingAllocator`], given back to the pool when it
+/// is dropped.
+pub(crate) struct TableSlot {
+    pool: Arc<PoolInner>,
+    index: u32,
+    /// The instance slot the table belongs to, if any, kept in use while
+    /// the table is alive.
+    _instance: Option<InstanceSlot>,
+}
+
+impl fmt::Debug for TableSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableSlot")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl TableSlot {
+    /// Gives the slot back to the pool, with the storage of the dropped
+    /// table to be reused by the next table of the slot.
+    pub(crate) fn recycle(self, mut storage: Vec<RawTableElement>) {
+        storage.clear();
+        *self.pool.table_storage[self.index as usize].lock().unwrap() = storage;
+    }
+}
+
+impl Drop for TableSlot {
+    fn drop(&mut self) {
+        self.pool.free_tables.lock().unwrap().push(self.index);
+    }
+}
+
+/// A memory slot of a [`PoolingAllocator`], given back to the pool when it
+/// is dropped, unless it is retired.
+struct MemorySlot {
+    pool: Arc<PoolInner>,
+    index: u32,
+    /// Whether the slot must not be reused.
+    retired: bool,
+    /// The instance slot the memory belongs to, if any, kept in use while
+    /// the memory is alive.
+    _instance: Option<InstanceSlot>,
+}
+
+impl fmt::Debug for MemorySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySlot")
+            .field("index", &self.index)
+            .field("retired", &self.retired)
+            .finish()
+    }
+}
+
+impl Drop for MemorySlot {
+    fn drop(&mut self) {
+        if !self.retired {
+            self.pool.free_memories.lock().unwrap().push(self.index);
+        }
+    }
+}
+
+/// A linear memory allocated from a slot of a [`PoolingAllocator`].
+///
+/// The memory can grow in place up to the bound of its style. When it is
+/// dropped, its pages are released and made inaccessible again.
+#[derive(Debug)]
+struct PooledMemory {
+    /// The slot this memory was allocated from, kept in use while the
+    /// memory is alive.
+    slot: MemorySlot,
+    /// Start of the memory slot.
+    base: *mut u8,
+    /// Number of bytes of the slot which are accessible.
+    accessible_bytes: usize,
+    /// Maximum number of pages the memory can grow to in this slot.
+    bound: Pages,
+    /// The WebAssembly linear memory description.
+    memory: MemoryType,
+    /// The style of the memory, always static.
+    style: MemoryStyle,
+    /// The memory definition used by the generated code.
+    vm_memory_definition: MaybeInstanceOwned<VMMemoryDefinition>,
+}
+
+unsafe impl Send for PooledMemory {}
+unsafe impl Sync for PooledMemory {}
+
+impl PooledMemory {
+    /// Creates a memory in a memory slot of `pool`.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    unsafe fn allocate(
+        pool: &Arc<PoolInner>,
+        instance: Option<InstanceSlot>,
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let (bound, offset_guard_size) = match *style {
+            MemoryStyle::Static {
+                bound,
+                offset_guard_size,
+            } => (bound, offset_guard_size),
+            MemoryStyle::Dynamic { offset_guard_size } => {
+                (pool.config.max_memory_pages, offset_guard_size)
+            }
+        };
+        if bound.bytes().0 + offset_guard_size as usize > pool.memory_slot_size {
+            return Err(MemoryError::Generic(format!(
+                "a memory of {} pages with a {offset_guard_size} bytes guard does not fit in the memory slots of the pool",
+                bound.0
+            )));
+        }
+        if memory.minimum > bound {
+            return Err(MemoryError::MinimumMemoryTooLarge {
+                min_requested: memory.minimum,
+                max_allowed: bound,
+            });
+        }
+
+        let index = pool.free_memories.lock().unwrap().pop().ok_or_else(|| {
+            MemoryError::Generic("all the memory slots of the pool are in use".to_string())
+        })?;
+        let base = unsafe {
+            (pool.memories.as_ptr() as *mut u8).add(index as usize * pool.memory_slot_size)
+        };
+
+        let mut pooled = Self {
+            slot: MemorySlot {
+                pool: pool.clone(),
+                index,
+                retired: false,
+                _instance: instance,
+            },
+            base,
+            accessible_bytes: 0,
+            bound,
+            memory: *memory,
+            style: *style,
+            vm_memory_definition: unsafe {
+                let mut ptr = vm_memory_location;
+                let md = ptr.as_mut();
+                md.base = base;
+                md.current_length = 0;
+                MaybeInstanceOwned::Instance(vm_memory_location)
+            },
+        };
+        pooled.grow(memory.minimum)?;
+        Ok(VMMemory(Box::new(pooled)))
+    }
+
+    /// Releases the pages of this memory and makes them inaccessible.
+    fn release(&mut self) -> Result<(), String> {
+        if self.accessible_bytes == 0 {
+            return Ok(());
+        }
+        unsafe {
+            #[cfg(target_os = "linux")]
+            if libc::madvise(self.base as _, self.accessible_bytes, libc::MADV_DONTNEED) != 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+            #[cfg(not(target_os = "linux"))]
+            std::ptr::write_bytes(self.base, 0, self.accessible_bytes);
+
+            region::protect(self.base, self.accessible_bytes, region::Protection::NONE)
+                .map_err(|e| e.to_string())?;
+        }
+        self.accessible_bytes = 0;
+        Ok(())
+    }
+}
+
+impl Drop for PooledMemory {
+    fn drop(&mut self) {
+        // The slot must not be reused with the contents of this memory, so
+        // it is taken out of the pool if they can't be released.
+        if let Err(e) = self.release() {
+            tracing::error!(
+                slot = self.slot.index,
+                "failed to release a pooled memory, retiring its slot: {e}"
+            );
+            self.slot.retired = true;
+        }
+    }
+}
+
+impl LinearMemory for PooledMemory {
+    fn ty(&self) -> MemoryType {
+        let mut ty = self.memory;
+        ty.minimum = self.size();
+        ty
+    }
+
+    fn size(&self) -> Pages {
+        let length = unsafe { self.vm_memory_definition.as_ptr().as_ref().current_length };
+        Pages((length / wasmer_types::WASM_PAGE_SIZE) as u32)
+    }
+
+    fn style(&self) -> MemoryStyle {
+        self.style
+    }
+
+    fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+        let prev_pages = self.size();
+        let could_not_grow = MemoryError::CouldNotGrow {
+            current: prev_pages,
+            attempted_delta: delta,
+        };
+        if delta.0 == 0 {
+            return Ok(prev_pages);
+        }
+        let new_pages = prev_pages
+            .checked_add(delta)
+            .ok_or(could_not_grow.clone())?;
+        if new_pages > self.bound || self.memory.maximum.is_some_and(|max| new_pages > max) {
+            return Err(could_not_grow);
+        }
+
+        let new_bytes = new_pages.bytes().0;
+        if new_bytes > self.accessible_bytes {
+            unsafe {
+                region::protect(
+                    self.base.add(self.accessible_bytes),
+                    new_bytes - self.accessible_bytes,
+                    region::Protection::READ_WRITE,
+                )
+            }
+            .map_err(|e| MemoryError::Region(e.to_string()))?;
+            self.accessible_bytes = new_bytes;
+        }
+        unsafe {
+            self.vm_memory_definition.as_ptr().as_mut().current_length = new_bytes;
+        }
+        Ok(prev_pages)
+    }
+
+    fn grow_at_least(&mut self, min_size: u64) -> Result<(), MemoryError> {
+        let current = self.size();
+        let cur_size = current.bytes().0 as u64;
+        if cur_size < min_size {
+            let growth_pages = (min_size - cur_size).div_ceil(wasmer_types::WASM_PAGE_SIZE as u64);
+            self.grow(Pages(growth_pages as u32))?;
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), MemoryError> {
+        self.release().map_err(MemoryError::Region)?;
+        unsafe {
+            self.vm_memory_definition.as_ptr().as_mut().current_length = 0;
+        }
+        Ok(())
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.vm_memory_definition.as_ptr()
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
+        Err(MemoryError::MemoryNotShared)
+    }
+
+    fn copy(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
+        // Copies leave the pool, in a memory owned by the host.
+        let copy = VMOwnedMemory::new(&self.ty(), &self.style)?;
+        let length = self.size().bytes().0;
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.base, copy.vmmemory().as_mut().base, length);
+        }
+        Ok(Box::new(copy))
+    }
+
+    fn thread_conditions(&self) -> Option<&ThreadConditions> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::UnsafeCell;
+    use wasmer_types::{TableType, Type};
+
+    fn allocator() -> PoolingAllocator {
+        PoolingAllocator::new(PoolingConfig {
+            max_instances: 2,
+            max_memory_pages: Pages(2),
+            max_table_elements: 10,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn objects_take_a_slot_of_their_kind() {
+        let allocator = allocator();
+        let mut memory_definition = VMMemoryDefinition {
+            base: std::ptr::null_mut(),
+            current_length: 0,
+        };
+        let mut table_definition = VMTableDefinition {
+            base: std::ptr::null_mut(),
+            current_elements: 0,
+        };
+        let memory_type = MemoryType::new(1, None, false);
+        let memory_style = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+        let table_type = TableType::new(Type::FuncRef, 1, None);
+        let table_style = TableStyle::CallerChecksSignature;
+
+        // Memories and tables created on their own take no instance slot.
+        let memory = unsafe {
+            allocator.memory_from_definition(
+                &memory_type,
+                &memory_style,
+                NonNull::from(&mut memory_definition),
+            )
+        }
+        .unwrap();
+        assert_eq!(allocator.available_slots(), 2);
+        assert_eq!(allocator.available_memory_slots(), 1);
+        assert_eq!(allocator.available_table_slots(), 2);
+        let table = unsafe {
+            allocator.table_from_definition(
+                &table_type,
+                &table_style,
+                NonNull::from(&mut table_definition),
+            )
+        }
+        .unwrap();
+        assert_eq!(allocator.available_slots(), 2);
+        assert_eq!(allocator.available_table_slots(), 1);
+        drop((memory, table));
+        assert_eq!(allocator.available_memory_slots(), 2);
+        assert_eq!(allocator.available_table_slots(), 2);
+
+        // The objects of an instance keep its slot in use.
+        let slot = allocator.allocate_instance().unwrap();
+        let memory = unsafe {
+            slot.memory_from_definition(
+                &memory_type,
+                &memory_style,
+                NonNull::from(&mut memory_definition),
+            )
+        }
+        .unwrap();
+        assert!(
+            unsafe {
+                slot.memory_from_definition(
+                    &memory_type,
+                    &memory_style,
+                    NonNull::from(&mut memory_definition),
+                )
+            }
+            .is_err()
+        );
+        drop(slot);
+        assert_eq!(allocator.available_slots(), 1);
+        assert_eq!(allocator.available_memory_slots(), 1);
+        drop(memory);
+        assert_eq!(allocator.available_slots(), 2);
+        assert_eq!(allocator.available_memory_slots(), 2);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn memories_which_cannot_be_released_retire_their_slot() {
+        let allocator = allocator();
+        let pool = &allocator.inner;
+        let index = pool.free_memories.lock().unwrap().pop().unwrap();
+
+        // `madvise` fails on an address which is not page aligned.
+        let memory = PooledMemory {
+            slot: MemorySlot {
+                pool: pool.clone(),
+                index,
+                retired: false,
+                _instance: None,
+            },
+            base: unsafe { (pool.memories.as_ptr() as *mut u8).add(1) },
+            accessible_bytes: 1,
+            bound: Pages(1),
+            memory: MemoryType::new(1, None, false),
+            style: MemoryStyle::Dynamic {
+                offset_guard_size: 0,
+            },
+            vm_memory_definition: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(
+                VMMemoryDefinition {
+                    base: std::ptr::null_mut(),
+                    current_length: 0,
+                },
+            ))),
+        };
+        drop(memory);
+        assert_eq!(allocator.available_memory_slots(), 1);
+    }
+}
@@ -1,10 +1,10 @@
 use crate::{
-    VMExceptionObj, VMExternObj, VMFunction, VMFunctionEnvironment, VMGlobal, VMInstance, VMMemory,
-    VMTable, VMTag,
+    LimitedMemory, LimitedTable, LinearMemory, ResourceLimiter, TableElement, VMExceptionObj,
+    VMExternObj, VMFunction, VMFunctionEnvironment, VMGlobal, VMInstance, VMMemory, VMTable, VMTag,
 };
 use core::slice::Iter;
 use std::{cell::UnsafeCell, fmt, marker::PhantomData, num::NonZeroUsize, ptr::NonNull};
-use wasmer_types::StoreId;
+use wasmer_types::{MemoryError, Pages, StoreId, WASM_PAGE_SIZE};
 
 /// Trait to represent an object managed by a context. This is implemented on
 /// the VM types managed by the context.
@@ -54,6 +54,7 @@ pub struct StoreObjects {
     exceptions: Vec<VMExceptionObj>,
     tags: Vec<VMTag>,
     function_environments: Vec<VMFunctionEnvironment>,
+    limiter: Option<Box<dyn ResourceLimiter>>,
 }
 
 impl StoreObjects {
@@ -82,6 +83,7 @@ impl StoreObjects {
             function_environments,
             exceptions,
             tags,
+            limiter: None,
         }
     }
 
@@ -114,6 +116,120 @@ impl StoreObjects {
         }
     }
 
+    /// Sets the resource limiter consulted when the memories and tables of
+    /// this store grow, replacing the previous one.
+    pub fn set_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        self.limiter = limiter;
+    }
+
+    /// Returns the resource limiter of this store, if any.
+    pub fn limiter_mut(&mut self) -> Option<&mut (dyn ResourceLimiter + 'static)> {
+        self.limiter.as_deref_mut()
+    }
+
+    /// Grows a memory of this store by `delta` pages, if the resource
+    /// limiter allows it.
+    ///
+    /// Returns the previous size of the memory.
+    pub fn grow_memory(
+        &mut self,
+        handle: InternalStoreHandle<VMMemory>,
+        delta: Pages,
+    ) -> Result<Pages, MemoryError> {
+        let memory = &mut self.memories[handle.index() - 1];
+        let Some(limiter) = self.limiter.as_deref_mut() else {
+            return memory.grow(delta);
+        };
+
+        let current = memory.size();
+        if delta.0 > 0 {
+            let desired = Pages(current.0.saturating_add(delta.0));
+            if !limiter.memory_growing(
+                LimitedMemory::Store(handle),
+                current,
+                desired,
+                memory.ty().maximum,
+            ) {
+                return Err(MemoryError::CouldNotGrow {
+                    current,
+                    attempted_delta: delta,
+                });
+            }
+        }
+        memory
+            .grow(delta)
+            .inspect_err(|e| limiter.memory_grow_failed(LimitedMemory::Store(handle), e))
+    }
+
+    /// Grows a memory of this store to at least `min_size` bytes, if the
+    /// resource limiter allows it.
+    pub fn grow_memory_at_least(
+        &mut self,
+        handle: InternalStoreHandle<VMMemory>,
+        min_size: u64,
+    ) -> Result<(), MemoryError> {
+        let memory = &mut self.memories[handle.index() - 1];
+        let Some(limiter) = self.limiter.as_deref_mut() else {
+            return memory.grow_at_least(min_size);
+        };
+
+        let current = memory.size();
+        if current.bytes().0 as u64 >= min_size {
+            return Ok(());
+        }
+        let desired_pages = min_size.div_ceil(WASM_PAGE_SIZE as u64);
+        let desired = Pages(u32::try_from(desired_pages).unwrap_or(u32::MAX));
+        if !limiter.memory_growing(
+            LimitedMemory::Store(handle),
+            current,
+            desired,
+            memory.ty().maximum,
+        ) {
+            return Err(MemoryError::CouldNotGrow {
+                current,
+                attempted_delta: Pages(desired.0 - current.0),
+            });
+        }
+        memory
+            .grow_at_least(min_size)
+            .inspect_err(|e| limiter.memory_grow_failed(LimitedMemory::Store(handle), e))
+    }
+
+    /// Grows a table of this store by `delta` elements, if the resource
+    /// limiter allows it.
+    ///
+    /// Returns the previous size of the table, or `None` if it could not
+    /// grow.
+    pub fn grow_table(
+        &mut self,
+        handle: InternalStoreHandle<VMTable>,
+        delta: u32,
+        init_value: TableElement,
+    ) -> Option<u32> {
+        let table = &mut self.tables[handle.index() - 1];
+        let Some(limiter) = self.limiter.as_deref_mut() else {
+            return table.grow(delta, init_value);
+        };
+
+        if delta > 0 {
+            let current = table.size();
+            let desired = current.saturating_add(delta);
+            if !limiter.table_growing(
+                LimitedTable::Store(handle),
+                current,
+                desired,
+                table.ty().maximum,
+            ) {
+                return None;
+            }
+        }
+        let result = table.grow(delta, init_value);
+        if result.is_none() {
+            limiter.table_grow_failed(LimitedTable::Store(handle));
+        }
+        result
+    }
+
     /// Return an immutable iterator over all globals
     pub fn iter_globals(&self) -> Iter<'_, VMGlobal> {
         self.globals.iter()
@@ -263,6 +379,11 @@ impl<T> PartialEq for InternalStoreHandle<T> {
     }
 }
 impl<T> Eq for InternalStoreHandle<T> {}
+impl<T> std::hash::Hash for InternalStoreHandle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.idx.hash(state);
+    }
+}
 
 impl<T: StoreObject> InternalStoreHandle<T> {
     /// Moves the given object into a context and returns a handle to it.
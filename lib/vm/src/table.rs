@@ -8,6 +8,7 @@
 use crate::Trap;
 use crate::VMExternRef;
 use crate::VMFuncRef;
+use crate::pooling::TableSlot;
 use crate::store::MaybeInstanceOwned;
 use crate::vmcontext::VMTableDefinition;
 use std::cell::UnsafeCell;
@@ -78,6 +79,8 @@ pub struct VMTable {
     /// Our chosen implementation style.
     style: TableStyle,
     vm_table_definition: MaybeInstanceOwned<VMTableDefinition>,
+    /// The pool slot the storage of this table was taken from, if any.
+    pool_slot: Option<TableSlot>,
 }
 
 impl VMTable {
@@ -86,7 +89,7 @@ impl VMTable {
     /// This creates a `Table` with metadata owned by a VM, pointed to by
     /// `vm_table_location`: this can be used to create a local table.
     pub fn new(table: &TableType, style: &TableStyle) -> Result<Self, String> {
-        unsafe { Self::new_inner(table, style, None) }
+        unsafe { Self::new_inner(table, style, None, None) }
     }
 
     /// Returns the size of the table
@@ -106,7 +109,33 @@ impl VMTable {
         style: &TableStyle,
         vm_table_location: NonNull<VMTableDefinition>,
     ) -> Result<Self, String> {
-        unsafe { Self::new_inner(table, style, Some(vm_table_location)) }
+        unsafe { Self::new_inner(table, style, Some(vm_table_location), None) }
+    }
+
+    /// Create a new table with VM owned metadata, reusing the `storage`
+    /// of a pool slot, which is given back to the slot when the table is
+    /// dropped.
+    ///
+    /// The table cannot grow past `max_elements`.
+    ///
+    /// # Safety
+    /// - `vm_table_location` must point to a valid location in VM memory.
+    pub(crate) unsafe fn from_definition_pooled(
+        table: &TableType,
+        style: &TableStyle,
+        vm_table_location: NonNull<VMTableDefinition>,
+        storage: Vec<RawTableElement>,
+        max_elements: u32,
+        slot: TableSlot,
+    ) -> Result<Self, String> {
+        unsafe {
+            Self::new_inner(
+                table,
+                style,
+                Some(vm_table_location),
+                Some((storage, max_elements, slot)),
+            )
+        }
     }
 
     /// Create a new `Table` with either self-owned or VM owned metadata.
@@ -114,6 +143,7 @@ impl VMTable {
         table: &TableType,
         style: &TableStyle,
         vm_table_location: Option<NonNull<VMTableDefinition>>,
+        pooled: Option<(Vec<RawTableElement>, u32, TableSlot)>,
     ) -> Result<Self, String> {
         unsafe {
             match table.ty {
@@ -134,12 +164,25 @@ impl VMTable {
             }
             let table_minimum = usize::try_from(table.minimum)
                 .map_err(|_| "Table minimum is bigger than usize".to_string())?;
-            let mut vec = vec![RawTableElement::default(); table_minimum];
+            let (mut vec, maximum, pool_slot) = match pooled {
+                Some((mut storage, max_elements, slot)) => {
+                    storage.resize(table_minimum, RawTableElement::default());
+                    let maximum = table
+                        .maximum
+                        .map_or(max_elements, |max| max.min(max_elements));
+                    (storage, Some(maximum), Some(slot))
+                }
+                None => (
+                    vec![RawTableElement::default(); table_minimum],
+                    table.maximum,
+                    None,
+                ),
+            };
             let base = vec.as_mut_ptr();
             match style {
                 TableStyle::CallerChecksSignature => Ok(Self {
                     vec,
-                    maximum: table.maximum,
+                    maximum,
                     table: *table,
                     style: style.clone(),
                     vm_table_definition: if let Some(table_loc) = vm_table_location {
@@ -156,6 +199,7 @@ impl VMTable {
                             current_elements: table_minimum as _,
                         })))
                     },
+                    pool_slot,
                 }),
             }
         }
@@ -350,3 +394,11 @@ impl VMTable {
         Ok(())
     }
 }
+
+impl Drop for VMTable {
+    fn drop(&mut self) {
+        if let Some(slot) = self.pool_slot.take() {
+            slot.recycle(std::mem::take(&mut self.vec));
+        }
+    }
+}
@@ -133,6 +133,7 @@ unsafe fn process_illegal_op(addr: usize) -> Option<TrapCode> {
                 8 => Some(TrapCode::BadConversionToInteger),
                 9 => Some(TrapCode::UnreachableCodeReached),
                 10 => Some(TrapCode::UnalignedAtomic),
+                12 => Some(TrapCode::CallDepthExceeded),
                 _ => None,
             },
         }
@@ -5,9 +5,9 @@ use std::mem::MaybeUninit;
 use wasmer::{FromToNativeWasmType, MemorySize, ValueType};
 
 use super::{
-    Errno, ErrnoSignal, EventFdReadwrite, Eventtype, Fd, JoinStatusType, ProcSpawnFdOp, Signal,
-    SignalDisposition, Snapshot0SubscriptionClock, SubscriptionClock, SubscriptionFsReadwrite,
-    Userdata,
+    Errno, ErrnoSignal, EventFdReadwrite, Eventtype, Fd, Filesize, JoinStatusType, Pid,
+    ProcSpawnFdOp, Signal, SignalDisposition, Snapshot0SubscriptionClock, SubscriptionClock,
+    SubscriptionFsReadwrite, Userdata,
 };
 
 /// Thread local key
@@ -516,3 +516,165 @@ unsafe impl<M: MemorySize> ValueType for ProcSpawnFdOp<M> {
     #[inline]
     fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
 }
+
+#[doc = " Kind of an advisory file lock."]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, num_enum :: TryFromPrimitive, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum Locktype {
+    #[doc = " Release the locks held over the range."]
+    Unlock,
+    #[doc = " A shared (read) lock, which may be held by several owners at once."]
+    Shared,
+    #[doc = " An exclusive (write) lock, which may be held by a single owner."]
+    Exclusive,
+    #[doc = " Unknown."]
+    Unknown,
+}
+impl core::fmt::Debug for Locktype {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Locktype::Unlock => f.debug_tuple("F_UNLCK").finish(),
+            Locktype::Shared => f.debug_tuple("F_RDLCK").finish(),
+            Locktype::Exclusive => f.debug_tuple("F_WRLCK").finish(),
+            Locktype::Unknown => f.debug_tuple("Unknown").finish(),
+        }
+    }
+}
+unsafe impl ValueType for Locktype {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+unsafe impl wasmer::FromToNativeWasmType for Locktype {
+    type Native = i32;
+
+    fn to_native(self) -> Self::Native {
+        self as i32
+    }
+
+    fn from_native(n: Self::Native) -> Self {
+        match n {
+            0 => Self::Unlock,
+            1 => Self::Shared,
+            2 => Self::Exclusive,
+
+            q => {
+                tracing::debug!("could not serialize number {q} to enum Locktype");
+                Self::Unknown
+            }
+        }
+    }
+
+    fn is_from_store(&self, _store: &impl wasmer::AsStoreRef) -> bool {
+        false
+    }
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " Flags for taking advisory file locks."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct Lockflags : u16 {
+        #[doc = " Wait for conflicting locks to be released instead of failing"]
+        #[doc = " with `EAGAIN` (`F_SETLKW` or `flock` without `LOCK_NB`)."]
+        const WAIT = 1 << 0;
+        #[doc = " The lock is owned by the calling process, like the locks of"]
+        #[doc = " `fcntl(F_SETLK)`, instead of by the open file description like"]
+        #[doc = " the locks of `flock`."]
+        const PROCESS = 1 << 1;
+    }
+}
+
+unsafe impl wasmer::FromToNativeWasmType for Lockflags {
+    type Native = i32;
+
+    fn to_native(self) -> Self::Native {
+        self.bits() as i32
+    }
+
+    fn from_native(n: Self::Native) -> Self {
+        Self::from_bits_truncate(n as u16)
+    }
+
+    fn is_from_store(&self, _store: &impl wasmer::AsStoreRef) -> bool {
+        false
+    }
+}
+
+#[doc = " An advisory lock held on a file, as returned by `F_GETLK`."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Lockinfo {
+    #[doc = " The kind of the lock, which is `unlock` when no lock conflicts."]
+    pub type_: Locktype,
+    #[doc = " The process holding the lock, or zero if the lock is not owned by"]
+    #[doc = " a process."]
+    pub pid: Pid,
+    #[doc = " Offset of the first locked byte."]
+    pub start: Filesize,
+    #[doc = " Number of locked bytes, zero meaning up to the end of the file."]
+    pub len: Filesize,
+}
+unsafe impl ValueType for Lockinfo {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " The changes reported by a file system watch, and the flags of the"]
+    #[doc = " events read from it. The values match the masks of `inotify`."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct Watchmask : u32 {
+        #[doc = " The contents of a file were modified."]
+        const MODIFY = 0x2;
+        #[doc = " The metadata of an entry changed."]
+        const ATTRIB = 0x4;
+        #[doc = " An entry was moved out of the watched directory."]
+        const MOVED_FROM = 0x40;
+        #[doc = " An entry was moved into the watched directory."]
+        const MOVED_TO = 0x80;
+        #[doc = " An entry was created in the watched directory."]
+        const CREATE = 0x100;
+        #[doc = " An entry was removed from the watched directory."]
+        const DELETE = 0x200;
+        #[doc = " The watched entry itself was removed."]
+        const DELETE_SELF = 0x400;
+        #[doc = " Events were dropped because too many were queued."]
+        const Q_OVERFLOW = 0x4000;
+        #[doc = " The entry of the event is a directory."]
+        const ISDIR = 0x4000_0000;
+    }
+}
+
+unsafe impl wasmer::FromToNativeWasmType for Watchmask {
+    type Native = i32;
+
+    fn to_native(self) -> Self::Native {
+        self.bits() as i32
+    }
+
+    fn from_native(n: Self::Native) -> Self {
+        Self::from_bits_truncate(n as u32)
+    }
+
+    fn is_from_store(&self, _store: &impl wasmer::AsStoreRef) -> bool {
+        false
+    }
+}
+
+#[doc = " The permissions and ownership of a file, which complement its `filestat`."]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Filestatext {
+    #[doc = " Permission bits of the file, including the setuid, setgid and"]
+    #[doc = " sticky bits but not the file type."]
+    pub st_mode: u32,
+    #[doc = " User ID of the owner of the file."]
+    pub st_uid: u32,
+    #[doc = " Group ID of the owner of the file."]
+    pub st_gid: u32,
+}
+unsafe impl ValueType for Filestatext {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
@@ -14,7 +14,8 @@ use wasmer_wasix_types::wasi::{EpollType, Fd as WasiFd, Fdflags, Fdflagsext, Fil
 use crate::net::socket::InodeSocket;
 
 use super::{
-    InodeGuard, InodeValFilePollGuard, InodeValFilePollGuardMode, InodeWeakGuard, NotificationInner,
+    FsWatchInner, InodeGuard, InodeValFilePollGuard, InodeValFilePollGuardMode, InodeWeakGuard,
+    NotificationInner,
 };
 
 #[derive(Debug, Clone)]
@@ -123,6 +124,9 @@ impl Drop for EpollJoinGuard {
             InodeValFilePollGuardMode::EventNotifications(inner) => {
                 inner.remove_interest_handler();
             }
+            InodeValFilePollGuardMode::FsWatch(inner) => {
+                inner.remove_interest_handler();
+            }
             InodeValFilePollGuardMode::DuplexPipe { pipe } => {
                 let inner = pipe.write().unwrap();
                 inner.remove_interest_handler();
@@ -220,4 +224,8 @@ pub enum Kind {
     EventNotifications {
         inner: Arc<NotificationInner>,
     },
+    /// The watches of the changes made to the file system
+    FsWatch {
+        inner: Arc<FsWatchInner>,
+    },
 }
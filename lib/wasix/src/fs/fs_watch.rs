@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    task::{Poll, Waker},
+};
+
+use virtual_fs::{WatchEvent, WatchEventKind, Watcher};
+use virtual_mio::{InterestHandler, InterestType};
+use wasmer_wasix_types::wasi::{Errno, Watchmask};
+
+/// Size of the header of an event, which is followed by its name.
+const EVENT_HEADER_LEN: usize = 16;
+
+/// A set of watches whose events are read from a file descriptor, in the
+/// same format as the events of `inotify`.
+#[derive(Debug)]
+pub struct FsWatchInner {
+    state: Mutex<FsWatchState>,
+    /// Kept apart from the state as it is signaled by the watchers while
+    /// their queues are locked.
+    signal: Mutex<FsWatchSignal>,
+}
+
+#[derive(Debug)]
+struct FsWatchState {
+    next_wd: i32,
+    /// Used to pair the two events of a rename.
+    next_cookie: u32,
+    watches: BTreeMap<i32, FsWatch>,
+    /// Encoded events waiting to be read.
+    pending: VecDeque<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct FsWatch {
+    watcher: Watcher,
+    path: PathBuf,
+    mask: Watchmask,
+}
+
+#[derive(Debug, Default)]
+struct FsWatchSignal {
+    /// All the registered wakers
+    wakers: VecDeque<Waker>,
+    /// InterestHandler for use with epoll
+    interest_handler: Option<Box<dyn InterestHandler>>,
+}
+
+impl FsWatchSignal {
+    fn add_waker(&mut self, waker: &Waker) {
+        if !self.wakers.iter().any(|a| a.will_wake(waker)) {
+            self.wakers.push_front(waker.clone());
+        }
+    }
+
+    fn wake_all(&mut self) {
+        while let Some(waker) = self.wakers.pop_front() {
+            waker.wake();
+        }
+        if let Some(handler) = self.interest_handler.as_mut() {
+            handler.push_interest(InterestType::Readable);
+        }
+    }
+}
+
+/// Forwards the events queued on a watcher to the file descriptor.
+#[derive(Debug)]
+struct FsWatchWaker(Weak<FsWatchInner>);
+
+impl InterestHandler for FsWatchWaker {
+    fn push_interest(&mut self, interest: InterestType) {
+        if let (InterestType::Readable, Some(inner)) = (interest, self.0.upgrade()) {
+            inner.signal.lock().unwrap().wake_all();
+        }
+    }
+
+    fn pop_interest(&mut self, _interest: InterestType) -> bool {
+        false
+    }
+
+    fn has_interest(&self, _interest: InterestType) -> bool {
+        false
+    }
+}
+
+impl Default for FsWatchInner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FsWatchInner {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(FsWatchState {
+                next_wd: 1,
+                next_cookie: 1,
+                watches: BTreeMap::new(),
+                pending: VecDeque::new(),
+            }),
+            signal: Mutex::new(FsWatchSignal::default()),
+        }
+    }
+
+    /// Adds a watch of `path`, returning its watch descriptor. Watching
+    /// a path again replaces the mask of its existing watch.
+    pub fn add(self: &Arc<Self>, path: &Path, mut watcher: Watcher, mask: Watchmask) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        if let Some((wd, watch)) = state.watches.iter_mut().find(|(_, w)| w.path == path) {
+            watch.mask = mask;
+            return *wd;
+        }
+
+        let wd = state.next_wd;
+        state.next_wd += 1;
+        watcher.set_interest_handler(Box::new(FsWatchWaker(Arc::downgrade(self))));
+        state.watches.insert(
+            wd,
+            FsWatch {
+                watcher,
+                path: path.to_path_buf(),
+                mask,
+            },
+        );
+        wd
+    }
+
+    /// Removes a watch, returning whether it existed.
+    pub fn remove(&self, wd: i32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.watches.remove(&wd).is_some()
+    }
+
+    /// Returns the number of bytes ready to be read.
+    pub fn poll(&self, waker: &Waker) -> Poll<usize> {
+        // The waker is registered first so that events queued while the
+        // watchers are drained are not missed.
+        self.signal.lock().unwrap().add_waker(waker);
+        match self.collect() {
+            0 => Poll::Pending,
+            len => Poll::Ready(len),
+        }
+    }
+
+    /// Reads as many whole events as fit in `buf`.
+    ///
+    /// Fails with `Errno::Inval` if the next event does not fit, like the
+    /// `read` of an `inotify` file descriptor, and with `Errno::Again` if
+    /// there are no events.
+    pub fn try_read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        self.collect();
+
+        let mut state = self.state.lock().unwrap();
+        let mut written = 0;
+        while let Some(event) = state.pending.front() {
+            if written + event.len() > buf.len() {
+                break;
+            }
+            buf[written..written + event.len()].copy_from_slice(event);
+            written += event.len();
+            state.pending.pop_front();
+        }
+        match written {
+            0 if state.pending.is_empty() => Err(Errno::Again),
+            0 => Err(Errno::Inval),
+            n => Ok(n),
+        }
+    }
+
+    /// Waits for events and reads as many as fit in `buf`.
+    pub fn read(&self, waker: &Waker, buf: &mut [u8]) -> Poll<Result<usize, Errno>> {
+        self.signal.lock().unwrap().add_waker(waker);
+        match self.try_read(buf) {
+            Err(Errno::Again) => Poll::Pending,
+            res => Poll::Ready(res),
+        }
+    }
+
+    pub fn set_interest_handler(&self, mut handler: Box<dyn InterestHandler>) {
+        if self.collect() > 0 {
+            handler.push_interest(InterestType::Readable);
+        }
+        let mut signal = self.signal.lock().unwrap();
+        signal.interest_handler.replace(handler);
+    }
+
+    pub fn remove_interest_handler(&self) -> Option<Box<dyn InterestHandler>> {
+        let mut signal = self.signal.lock().unwrap();
+        signal.interest_handler.take()
+    }
+
+    /// Encodes the events queued on the watchers, returning the number of
+    /// bytes ready to be read.
+    fn collect(&self) -> usize {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        for (wd, watch) in state.watches.iter_mut() {
+            if watch.watcher.take_overflowed() {
+                state
+                    .pending
+                    .push_back(encode(-1, Watchmask::Q_OVERFLOW, 0, None));
+            }
+            while let Some(event) = watch.watcher.next_event() {
+                let cookie = if event.kind == WatchEventKind::Rename {
+                    state.next_cookie = state.next_cookie.wrapping_add(1).max(1);
+                    state.next_cookie
+                } else {
+                    0
+                };
+                for (mask, name) in watch.translate(&event) {
+                    state
+                        .pending
+                        .push_back(encode(*wd, mask, cookie, name.as_deref()));
+                }
+            }
+        }
+        state.pending.iter().map(|e| e.len()).sum()
+    }
+}
+
+impl FsWatch {
+    /// Translates an event into the events reported for this watch, along
+    /// with the name of the entry relative to the watched directory.
+    fn translate(&self, event: &WatchEvent) -> Vec<(Watchmask, Option<PathBuf>)> {
+        let name_of = |path: &Path| -> Option<Option<PathBuf>> {
+            if path == self.path {
+                Some(None)
+            } else if path.parent() == Some(self.path.as_path()) {
+                Some(path.file_name().map(PathBuf::from))
+            } else {
+                None
+            }
+        };
+
+        let mut events = Vec::new();
+        match event.kind {
+            WatchEventKind::Create => {
+                events.extend(name_of(&event.path).map(|name| (Watchmask::CREATE, name)))
+            }
+            WatchEventKind::Modify => {
+                events.extend(name_of(&event.path).map(|name| (Watchmask::MODIFY, name)))
+            }
+            WatchEventKind::Remove => events.extend(name_of(&event.path).map(|name| match name {
+                Some(name) => (Watchmask::DELETE, Some(name)),
+                None => (Watchmask::DELETE_SELF, None),
+            })),
+            WatchEventKind::Rename => {
+                if let Some(Some(name)) = name_of(&event.path) {
+                    events.push((Watchmask::MOVED_FROM, Some(name)));
+                }
+                if let Some(Some(name)) = event.target.as_deref().and_then(name_of) {
+                    events.push((Watchmask::MOVED_TO, Some(name)));
+                }
+            }
+        }
+
+        events
+            .into_iter()
+            .filter(|(mask, _)| self.mask.intersects(*mask))
+            .map(|(mask, name)| {
+                let mask = if event.is_dir {
+                    mask | Watchmask::ISDIR
+                } else {
+                    mask
+                };
+                (mask, name)
+            })
+            .collect()
+    }
+}
+
+/// Encodes an event like a `struct inotify_event`, with the name padded
+/// with zeroes to a multiple of the size of the header.
+fn encode(wd: i32, mask: Watchmask, cookie: u32, name: Option<&Path>) -> Vec<u8> {
+    let name = name
+        .map(|n| n.to_string_lossy().into_owned().into_bytes())
+        .unwrap_or_default();
+    let len = if name.is_empty() {
+        0
+    } else {
+        (name.len() + 1).next_multiple_of(EVENT_HEADER_LEN)
+    };
+
+    let mut event = Vec::with_capacity(EVENT_HEADER_LEN + len);
+    event.extend_from_slice(&wd.to_le_bytes());
+    event.extend_from_slice(&mask.bits().to_le_bytes());
+    event.extend_from_slice(&cookie.to_le_bytes());
+    event.extend_from_slice(&(len as u32).to_le_bytes());
+    event.extend_from_slice(&name);
+    event.resize(EVENT_HEADER_LEN + len, 0);
+    event
+}
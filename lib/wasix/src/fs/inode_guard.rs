@@ -15,7 +15,7 @@ use wasmer_wasix_types::{
     wasi::{Errno, EventFdReadwrite, Eventrwflags, Subscription},
 };
 
-use super::{InodeGuard, Kind, notification::NotificationInner};
+use super::{InodeGuard, Kind, fs_watch::FsWatchInner, notification::NotificationInner};
 use crate::{
     net::socket::{InodeSocketInner, InodeSocketKind},
     state::{PollEvent, PollEventSet, WasiState, iterate_poll_events},
@@ -27,6 +27,7 @@ use crate::{
 pub(crate) enum InodeValFilePollGuardMode {
     File(Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>),
     EventNotifications(Arc<NotificationInner>),
+    FsWatch(Arc<FsWatchInner>),
     Socket { inner: Arc<InodeSocketInner> },
     PipeRx { rx: Arc<RwLock<Box<PipeRx>>> },
     PipeTx { tx: Arc<RwLock<Box<PipeTx>>> },
@@ -51,6 +52,7 @@ impl InodeValFilePollGuard {
             Kind::EventNotifications { inner, .. } => {
                 InodeValFilePollGuardMode::EventNotifications(inner.clone())
             }
+            Kind::FsWatch { inner } => InodeValFilePollGuardMode::FsWatch(inner.clone()),
             Kind::Socket { socket, .. } => InodeValFilePollGuardMode::Socket {
                 inner: socket.inner.clone(),
             },
@@ -89,6 +91,9 @@ impl std::fmt::Debug for InodeValFilePollGuard {
             InodeValFilePollGuardMode::EventNotifications { .. } => {
                 write!(f, "guard-notifications(fd={}, peb={})", self.fd, self.peb)
             }
+            InodeValFilePollGuardMode::FsWatch { .. } => {
+                write!(f, "guard-fs-watch(fd={}, peb={})", self.fd, self.peb)
+            }
             InodeValFilePollGuardMode::Socket { inner } => {
                 let inner = inner.protected.read().unwrap();
                 match &inner.kind {
@@ -112,6 +117,9 @@ impl std::fmt::Debug for InodeValFilePollGuard {
                     InodeSocketKind::Raw(..) => {
                         write!(f, "guard-raw-socket(fd={}, peb={})", self.fd, self.peb)
                     }
+                    InodeSocketKind::UnixDatagram { .. } => {
+                        write!(f, "guard-unix-datagram(fd={}, peb={})", self.fd, self.peb)
+                    }
                     _ => write!(f, "guard-socket(fd={}), peb={})", self.fd, self.peb),
                 }
             }
@@ -194,6 +202,7 @@ impl Future for InodeValFilePollGuardJoin {
                     file.poll_read_ready(cx)
                 }
                 InodeValFilePollGuardMode::EventNotifications(inner) => inner.poll(waker).map(Ok),
+                InodeValFilePollGuardMode::FsWatch(inner) => inner.poll(waker).map(Ok),
                 InodeValFilePollGuardMode::Socket { inner } => {
                     let mut guard = inner.protected.write().unwrap();
                     guard.poll_read_ready(cx)
@@ -293,6 +302,10 @@ impl Future for InodeValFilePollGuardJoin {
                     file.poll_write_ready(cx)
                 }
                 InodeValFilePollGuardMode::EventNotifications(inner) => inner.poll(waker).map(Ok),
+                InodeValFilePollGuardMode::FsWatch { .. } => Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Cannot write to a file system watch",
+                ))),
                 InodeValFilePollGuardMode::Socket { inner } => {
                     let mut guard = inner.protected.write().unwrap();
                     guard.poll_write_ready(cx)
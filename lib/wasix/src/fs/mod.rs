@@ -7,6 +7,7 @@
 
 mod fd;
 mod fd_list;
+mod fs_watch;
 mod inode_guard;
 mod notification;
 
@@ -38,12 +39,13 @@ use wasmer_config::package::PackageId;
 use wasmer_wasix_types::{
     types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
     wasi::{
-        Errno, Fd as WasiFd, Fdflags, Fdflagsext, Fdstat, Filesize, Filestat, Filetype,
-        Preopentype, Prestat, PrestatEnum, Rights, Socktype,
+        Errno, Fd as WasiFd, Fdflags, Fdflagsext, Fdstat, Filesize, Filestat, Filestatext,
+        Filetype, Preopentype, Prestat, PrestatEnum, Rights, Socktype,
     },
 };
 
 pub use self::fd::{EpollFd, EpollInterest, EpollJoinGuard, Fd, FdInner, InodeVal, Kind};
+pub use self::fs_watch::FsWatchInner;
 pub(crate) use self::inode_guard::{
     InodeValFilePollGuard, InodeValFilePollGuardJoin, InodeValFilePollGuardMode,
     InodeValFileReadGuard, InodeValFileWriteGuard, POLL_GUARD_MAX_RET, WasiStateFileGuard,
@@ -256,14 +258,19 @@ impl WasiInodes {
 
     /// adds another value to the inodes
     pub fn add_inode_val(&self, val: InodeVal) -> InodeGuard {
-        let val = Arc::new(val);
         let st_ino = {
             let guard = val.stat.read().unwrap();
             guard.st_ino
         };
+        self.add_inode_val_at(Inode(st_ino), val)
+    }
+
+    /// adds another value to the inodes under an inode number that differs
+    /// from the one reported in its filestat
+    pub fn add_inode_val_at(&self, ino: Inode, val: InodeVal) -> InodeGuard {
+        let val = Arc::new(val);
 
         let mut guard = self.protected.write().unwrap();
-        let ino = Inode(st_ino);
         guard.lookup.insert(ino, Arc::downgrade(&val));
 
         // every 100 calls we clear out dead weaks
@@ -441,6 +448,24 @@ impl FileSystem for WasiFsRoot {
             WasiFsRoot::Backing(fs) => fs.remove_file(path),
         }
     }
+    fn set_permissions(&self, path: &Path, mode: u32) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.set_permissions(path, mode),
+            WasiFsRoot::Backing(fs) => fs.set_permissions(path, mode),
+        }
+    }
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.chown(path, uid, gid),
+            WasiFsRoot::Backing(fs) => fs.chown(path, uid, gid),
+        }
+    }
+    fn watch(&self, path: &Path, recursive: bool) -> virtual_fs::Result<virtual_fs::Watcher> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.watch(path, recursive),
+            WasiFsRoot::Backing(fs) => fs.watch(path, recursive),
+        }
+    }
     fn new_open_options(&self) -> OpenOptions<'_> {
         match self {
             WasiFsRoot::Sandbox(fs) => fs.new_open_options(),
@@ -1146,7 +1171,8 @@ impl WasiFs {
                                         file.to_string_lossy().to_string().into(),
                                         Filestat {
                                             st_filetype: file_type,
-                                            st_ino: Inode::from_path(path_str).as_u64(),
+                                            st_dev: metadata.dev(),
+                                            st_ino: metadata.ino(),
                                             st_size: metadata.len(),
                                             st_ctim: metadata.created(),
                                             st_mtim: metadata.modified(),
@@ -1226,6 +1252,7 @@ impl WasiFs {
                     | Kind::PipeTx { .. }
                     | Kind::DuplexPipe { .. }
                     | Kind::EventNotifications { .. }
+                    | Kind::FsWatch { .. }
                     | Kind::Epoll { .. } => {
                         return Err(Errno::Notdir);
                     }
@@ -1457,8 +1484,13 @@ impl WasiFs {
 
     pub fn filestat_fd(&self, fd: WasiFd) -> Result<Filestat, Errno> {
         let inode = self.get_fd_inode(fd)?;
-        let guard = inode.stat.read().unwrap();
-        Ok(*guard.deref())
+        let mut stat = *inode.stat.read().unwrap().deref();
+        // The stored link count only counts the links made through the
+        // sandbox, so the links of the underlying entry are added in
+        if let Ok(md) = self.get_metadata_for_kind(inode.read().deref()) {
+            stat.st_nlink = stat.st_nlink.max(md.nlink());
+        }
+        Ok(stat)
     }
 
     pub fn fdstat(&self, fd: WasiFd) -> Result<Fdstat, Errno> {
@@ -1510,6 +1542,7 @@ impl WasiFs {
                 Kind::Socket { socket } => match &socket.inner.protected.read().unwrap().kind {
                     InodeSocketKind::TcpStream { .. } => Filetype::SocketStream,
                     InodeSocketKind::Raw { .. } => Filetype::SocketRaw,
+                    InodeSocketKind::UnixDatagram { .. } => Filetype::SocketDgram,
                     InodeSocketKind::PreSocket { props, .. } => match props.ty {
                         Socktype::Stream => Filetype::SocketStream,
                         Socktype::Dgram => Filetype::SocketDgram,
@@ -1611,7 +1644,10 @@ impl WasiFs {
         is_preopened: bool,
         name: String,
     ) -> Result<InodeGuard, Errno> {
-        let stat = self.get_stat_for_kind(&kind)?;
+        let mut stat = self.get_stat_for_kind(&kind)?;
+        // The link count of an inode only tracks the links made through
+        // this file system, as it decides when unlinking removes the file.
+        stat.st_nlink = 1;
         Ok(self.create_inode_with_stat(inodes, kind, is_preopened, name.into(), stat))
     }
 
@@ -1650,15 +1686,23 @@ impl WasiFs {
             _ => {}
         }
 
-        let st_ino = Inode::from_path(&name);
-        stat.st_ino = st_ino.as_u64();
+        // Inodes are keyed by their path in the sandbox, since file systems
+        // mounted next to each other can reuse the same inode numbers. The
+        // inode number of the underlying entry is only reported to guests.
+        let ino = Inode::from_path(&name);
+        if stat.st_ino == 0 {
+            stat.st_ino = ino.as_u64();
+        }
 
-        inodes.add_inode_val(InodeVal {
-            stat: RwLock::new(stat),
-            is_preopened,
-            name: RwLock::new(name),
-            kind: RwLock::new(kind),
-        })
+        inodes.add_inode_val_at(
+            ino,
+            InodeVal {
+                stat: RwLock::new(stat),
+                is_preopened,
+                name: RwLock::new(name),
+                kind: RwLock::new(kind),
+            },
+        )
     }
 
     pub fn create_fd(
@@ -2098,26 +2142,68 @@ impl WasiFs {
     }
 
     pub fn get_stat_for_kind(&self, kind: &Kind) -> Result<Filestat, Errno> {
+        if let Kind::File {
+            handle: Some(wf),
+            path,
+            ..
+        } = kind
+        {
+            let wf = wf.read().unwrap();
+            let md = self.root_fs.metadata(path).unwrap_or_default();
+            return Ok(Filestat {
+                st_filetype: Filetype::RegularFile,
+                st_dev: md.dev(),
+                st_ino: md.ino(),
+                st_nlink: md.nlink().max(1),
+                st_size: wf.size(),
+                st_atim: wf.last_accessed(),
+                st_mtim: wf.last_modified(),
+                st_ctim: wf.created_time(),
+            });
+        }
+        let md = self.get_metadata_for_kind(kind)?;
+        Ok(Filestat {
+            st_filetype: virtual_file_type_to_wasi_file_type(md.file_type()),
+            st_dev: md.dev(),
+            // File systems without inode numbers report 0, in which case
+            // the inode of the sandbox is used instead
+            st_ino: md.ino(),
+            // File systems which do not count links report none
+            st_nlink: md.nlink().max(1),
+            st_size: md.len(),
+            st_atim: md.accessed(),
+            st_mtim: md.modified(),
+            st_ctim: md.created(),
+        })
+    }
+
+    /// Returns the permission bits and ownership of an entry, which are
+    /// zero for entries that do not live on the file system
+    pub fn get_stat_ext_for_kind(&self, kind: &Kind) -> Result<Filestatext, Errno> {
         let md = match kind {
-            Kind::File { handle, path, .. } => match handle {
-                Some(wf) => {
-                    let wf = wf.read().unwrap();
-                    return Ok(Filestat {
-                        st_filetype: Filetype::RegularFile,
-                        st_ino: Inode::from_path(path.to_string_lossy().as_ref()).as_u64(),
-                        st_size: wf.size(),
-                        st_atim: wf.last_accessed(),
-                        st_mtim: wf.last_modified(),
-                        st_ctim: wf.created_time(),
-
-                        ..Filestat::default()
-                    });
-                }
-                None => self
-                    .root_fs
-                    .metadata(path)
-                    .map_err(fs_error_into_wasi_err)?,
-            },
+            Kind::File {
+                handle: Some(_),
+                path,
+                ..
+            } => self.root_fs.metadata(path).unwrap_or_default(),
+            Kind::File { .. } | Kind::Dir { .. } | Kind::Symlink { .. } => {
+                self.get_metadata_for_kind(kind)?
+            }
+            _ => return Ok(Filestatext::default()),
+        };
+        Ok(Filestatext {
+            st_mode: md.mode(),
+            st_uid: md.uid(),
+            st_gid: md.gid(),
+        })
+    }
+
+    fn get_metadata_for_kind(&self, kind: &Kind) -> Result<virtual_fs::Metadata, Errno> {
+        let md = match kind {
+            Kind::File { path, .. } => self
+                .root_fs
+                .metadata(path)
+                .map_err(fs_error_into_wasi_err)?,
             Kind::Dir { path, .. } => self
                 .root_fs
                 .metadata(path)
@@ -2156,14 +2242,7 @@ impl WasiFs {
             }
             _ => return Err(Errno::Io),
         };
-        Ok(Filestat {
-            st_filetype: virtual_file_type_to_wasi_file_type(md.file_type()),
-            st_size: md.len(),
-            st_atim: md.accessed(),
-            st_mtim: md.modified(),
-            st_ctim: md.created(),
-            ..Filestat::default()
-        })
+        Ok(md)
     }
 
     /// Closes an open FD, handling all details such as FD being preopen
@@ -2259,6 +2338,15 @@ impl FileSystem for FallbackFileSystem {
     fn remove_file(&self, _path: &Path) -> Result<(), FsError> {
         Self::fail();
     }
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn chown(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> Result<(), FsError> {
+        Self::fail();
+    }
+    fn watch(&self, _path: &Path, _recursive: bool) -> Result<virtual_fs::Watcher, FsError> {
+        Self::fail();
+    }
     fn new_open_options(&self) -> virtual_fs::OpenOptions<'_> {
         Self::fail();
     }
@@ -2341,3 +2429,101 @@ pub fn fs_error_into_wasi_err(fs_error: FsError) -> Errno {
         FsError::Unsupported => Errno::Notsup,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn filestats_describe_the_underlying_entry() {
+        let root = virtual_fs::tmp_fs::TmpFileSystem::new();
+        root.create_dir(Path::new("/dir")).unwrap();
+        root.new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(Path::new("/dir/file"))
+            .unwrap();
+        root.set_permissions(Path::new("/dir/file"), 0o600).unwrap();
+        root.chown(Path::new("/dir/file"), Some(1000), Some(100))
+            .unwrap();
+        let md = root.metadata(Path::new("/dir/file")).unwrap();
+
+        let inodes = WasiInodes::new();
+        let fs = WasiFs::new_with_preopen(
+            &inodes,
+            &[],
+            &["/".to_string()],
+            WasiFsRoot::Sandbox(Arc::new(root)),
+        )
+        .unwrap();
+
+        let dir = fs
+            .get_inode_at_path(&inodes, VIRTUAL_ROOT_FD, "/dir", true)
+            .unwrap();
+        let stat = fs.get_stat_for_kind(dir.read().deref()).unwrap();
+        assert_eq!(stat.st_filetype, Filetype::Directory);
+        assert_eq!(stat.st_nlink, 2);
+
+        let file = fs
+            .get_inode_at_path(&inodes, VIRTUAL_ROOT_FD, "/dir/file", true)
+            .unwrap();
+        let stat = fs.get_stat_for_kind(file.read().deref()).unwrap();
+        assert_eq!((stat.st_ino, stat.st_nlink), (md.ino(), 1));
+        assert_eq!(file.stat.read().unwrap().st_ino, md.ino());
+
+        let ext = fs.get_stat_ext_for_kind(file.read().deref()).unwrap();
+        assert_eq!(
+            ext,
+            Filestatext {
+                st_mode: 0o600,
+                st_uid: 1000,
+                st_gid: 100,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn mounts_reusing_inode_numbers_stay_apart() {
+        let root = virtual_fs::tmp_fs::TmpFileSystem::new();
+        for dir in ["/a", "/b"] {
+            let mount = virtual_fs::mem_fs::FileSystem::default();
+            mount
+                .new_open_options()
+                .write(true)
+                .create_new(true)
+                .open(Path::new("/file"))
+                .unwrap();
+            let mount: Arc<dyn FileSystem + Send + Sync> = Arc::new(mount);
+            root.mount(PathBuf::from(dir), &mount, PathBuf::from("/"))
+                .unwrap();
+        }
+
+        let inodes = WasiInodes::new();
+        let fs = WasiFs::new_with_preopen(
+            &inodes,
+            &[],
+            &["/".to_string()],
+            WasiFsRoot::Sandbox(Arc::new(root)),
+        )
+        .unwrap();
+
+        let a = fs
+            .get_inode_at_path(&inodes, VIRTUAL_ROOT_FD, "/a/file", true)
+            .unwrap();
+        let b = fs
+            .get_inode_at_path(&inodes, VIRTUAL_ROOT_FD, "/b/file", true)
+            .unwrap();
+        let (a_stat, b_stat) = (*a.stat.read().unwrap(), *b.stat.read().unwrap());
+        assert_eq!(a_stat.st_ino, b_stat.st_ino);
+        assert_ne!(a_stat.st_dev, b_stat.st_dev);
+
+        assert_ne!(a.ino(), b.ino());
+        let guard = inodes.protected.read().unwrap();
+        assert!(guard.lookup.contains_key(&a.ino()));
+        assert!(guard.lookup.contains_key(&b.ino()));
+        for reserved in [FS_STDIN_INO, FS_STDOUT_INO, FS_STDERR_INO, FS_ROOT_INO] {
+            assert_ne!(a.ino(), reserved);
+            assert_ne!(b.ino(), reserved);
+        }
+    }
+}
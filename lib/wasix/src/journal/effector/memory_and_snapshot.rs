@@ -78,16 +78,21 @@ impl JournalEffector {
             cur = end;
         }
 
-        // Next we examine the dirty page manager and filter out any pages
-        // that have not been explicitly written to (according to the
-        // PTE)
-        //
-        // # TODO
-        // https://docs.kernel.org/admin-guide/mm/soft-dirty.html
+        // Next we ask the memory which pages were written to since the
+        // last snapshot and filter out the regions that were not, memories
+        // that can not track their pages are checked in full
+        if let Some(dirty) = Self::dirty_memory_ranges(ctx, guard) {
+            let mut dirty = dirty.iter().peekable();
+            regions.retain(|region| {
+                while dirty.next_if(|d| d.end <= region.start).is_some() {}
+                dirty.peek().is_some_and(|d| d.start < region.end)
+            });
+        }
 
         // Now that we know all the regions that need to be saved we
         // enter a processing loop that dumps all the data to the log
         // file in an orderly manner.
+        let env = ctx.data();
         let memory = unsafe { env.memory_view(ctx) };
         let journal = ctx.data().active_journal()?;
 
@@ -194,6 +199,25 @@ impl JournalEffector {
         Ok(())
     }
 
+    /// Returns the ranges of memory that were written to since the last
+    /// snapshot, or `None` if the memory does not track its dirty pages
+    fn dirty_memory_ranges(
+        ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+        guard: &mut MutexGuard<'_, WasiProcessInner>,
+    ) -> Option<Vec<Range<u64>>> {
+        let (env, mut store) = ctx.data_and_store_mut();
+        let memory = unsafe { env.memory() };
+        if let Err(err) = memory.enable_dirty_tracking(&mut store) {
+            tracing::trace!("memory does not track dirty pages - {err}");
+            return None;
+        }
+        let dirty = memory
+            .dirty_ranges_since(&mut store, guard.snapshot_memory_epoch)
+            .ok()?;
+        guard.snapshot_memory_epoch = dirty.epoch;
+        Some(dirty.ranges)
+    }
+
     /// # Safety
     ///
     /// This function manipulates the memory of the process and thus must be executed
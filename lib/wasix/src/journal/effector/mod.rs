@@ -82,6 +82,8 @@ mod syscalls {
 #[cfg(feature = "journal")]
 mod memory_and_snapshot;
 #[cfg(feature = "journal")]
+mod non_deterministic_call;
+#[cfg(feature = "journal")]
 mod process_exit;
 #[cfg(feature = "journal")]
 mod save_event;
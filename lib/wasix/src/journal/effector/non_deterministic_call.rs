@@ -0,0 +1,147 @@
+use wasmer_wasix_types::{types::__wasi_iovec_t, wasi::Event};
+
+use super::*;
+use crate::syscalls::{EventResult, read_bytes};
+
+impl JournalEffector {
+    /// Returns true if the results of the non-deterministic calls are to be
+    /// saved to the journal, which is requested with the
+    /// [`SnapshotTrigger::NonDeterministicCall`] trigger
+    pub fn should_save_non_deterministic_calls(ctx: &FunctionEnvMut<'_, WasiEnv>) -> bool {
+        let env = ctx.data();
+        env.should_journal() && env.has_snapshot_trigger(SnapshotTrigger::NonDeterministicCall)
+    }
+
+    pub fn save_non_deterministic_call(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+        call: NonDeterministicCall,
+        res: Result<impl AsRef<[u8]>, Errno>,
+    ) -> anyhow::Result<()> {
+        if !Self::should_save_non_deterministic_calls(ctx) {
+            return Ok(());
+        }
+
+        let (errno, result) = match res.as_ref() {
+            Ok(result) => (Errno::Success, result.as_ref()),
+            Err(err) => (*err, &[][..]),
+        };
+        ctx.data()
+            .active_journal()?
+            .write(JournalEntry::NonDeterministicCallV1 {
+                call,
+                errno,
+                result: Cow::Borrowed(result),
+            })
+            .map_err(map_snapshot_err)?;
+        Ok(())
+    }
+
+    /// Saves the data that a read stored in the `iovs`
+    pub fn save_non_deterministic_read<M: MemorySize>(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+        call: NonDeterministicCall,
+        iovs: WasmPtr<__wasi_iovec_t<M>, M>,
+        iovs_len: M::Offset,
+        res: Result<usize, Errno>,
+    ) -> anyhow::Result<()> {
+        if !Self::should_save_non_deterministic_calls(ctx) {
+            return Ok(());
+        }
+        let read = match res {
+            Ok(read) => read,
+            Err(err) => return Self::save_non_deterministic_call(ctx, call, Err::<&[u8], _>(err)),
+        };
+
+        let env = ctx.data();
+        let memory = unsafe { env.memory_view(&ctx) };
+        let iovs_arr = iovs.slice(&memory, iovs_len)?;
+        let iovs_arr = iovs_arr.access().map_err(mem_error_to_wasi)?;
+
+        let mut data = Vec::with_capacity(read);
+        let mut remaining: M::Offset = TryFrom::<usize>::try_from(read).unwrap_or_default();
+        for iovs in iovs_arr.iter() {
+            let sub = iovs.buf_len.min(remaining);
+            if sub == M::ZERO {
+                continue;
+            }
+            remaining -= sub;
+
+            let buf = WasmPtr::<u8, M>::new(iovs.buf)
+                .slice(&memory, sub)
+                .map_err(mem_error_to_wasi)?
+                .access()
+                .map_err(mem_error_to_wasi)?;
+            data.extend_from_slice(buf.as_ref());
+        }
+        Self::save_non_deterministic_call(ctx, call, Ok(data))
+    }
+
+    pub fn save_poll_oneoff(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+        res: Result<&[Event], Errno>,
+    ) -> anyhow::Result<()> {
+        if !Self::should_save_non_deterministic_calls(ctx) {
+            return Ok(());
+        }
+
+        let data = match res {
+            Ok(events) => {
+                let events: Vec<_> = events.iter().map(EventResult::from_event).collect();
+                Ok(bincode::serialize(&events)?)
+            }
+            Err(err) => Err(err),
+        };
+        Self::save_non_deterministic_call(ctx, NonDeterministicCall::PollOneoff, data)
+    }
+
+    /// Returns the recorded result of a non-deterministic call, or `None`
+    /// if the call is to be made for real
+    pub fn replay_non_deterministic_call(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+        call: NonDeterministicCall,
+    ) -> Option<Result<Vec<u8>, Errno>> {
+        let recorded = ctx.data().non_deterministic_replay.as_ref()?.next(call)?;
+        tracing::trace!(?call, errno = %recorded.errno, "replaying non-deterministic call");
+        Some(match recorded.errno {
+            Errno::Success => Ok(recorded.result),
+            err => Err(err),
+        })
+    }
+
+    /// Stores the recorded data of a read in the `iovs`
+    pub fn replay_non_deterministic_read<M: MemorySize>(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+        call: NonDeterministicCall,
+        iovs: WasmPtr<__wasi_iovec_t<M>, M>,
+        iovs_len: M::Offset,
+    ) -> Option<Result<usize, Errno>> {
+        let data = match Self::replay_non_deterministic_call(ctx, call)? {
+            Ok(data) => data,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let env = ctx.data();
+        let memory = unsafe { env.memory_view(&ctx) };
+        Some(
+            iovs.slice(&memory, iovs_len)
+                .map_err(mem_error_to_wasi)
+                .and_then(|iovs_arr| read_bytes(&data[..], &memory, iovs_arr)),
+        )
+    }
+
+    pub fn replay_poll_oneoff(
+        ctx: &FunctionEnvMut<'_, WasiEnv>,
+    ) -> Option<Result<Vec<Event>, Errno>> {
+        let data = Self::replay_non_deterministic_call(ctx, NonDeterministicCall::PollOneoff)?;
+        Some(data.and_then(|data| {
+            let events: Vec<EventResult> = bincode::deserialize(&data).map_err(|err| {
+                tracing::warn!(
+                    "failed to decode the recorded events of poll_oneoff - {}",
+                    err
+                );
+                Errno::Io
+            })?;
+            Ok(events.into_iter().map(EventResult::into_event).collect())
+        }))
+    }
+}
@@ -17,6 +17,7 @@ use wasmer_wasix_types::{
 };
 
 pub mod socket;
+pub mod unix;
 
 #[allow(dead_code)]
 pub(crate) fn read_ip<M: MemorySize>(
@@ -228,6 +229,22 @@ pub(crate) fn write_ip_port<M: MemorySize>(
     Ok(())
 }
 
+/// Writes the address of a Unix socket, whose path does not fit in the
+/// address and is queried with `sock_addr_local_unix` and friends
+pub(crate) fn write_unix_addr<M: MemorySize>(
+    memory: &MemoryView,
+    ptr: WasmPtr<__wasi_addr_port_t, M>,
+) -> Result<(), Errno> {
+    let addr = __wasi_addr_port_t {
+        tag: Addressfamily::Unix,
+        _padding: 0,
+        u: __wasi_addr_port_u { octs: [0; 18] },
+    };
+    ptr.deref(memory)
+        .write(addr)
+        .map_err(crate::mem_error_to_wasi)
+}
+
 #[allow(dead_code)]
 pub(crate) fn read_route<M: MemorySize>(
     memory: &MemoryView,
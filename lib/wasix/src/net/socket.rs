@@ -3,6 +3,7 @@ use std::{
     io,
     mem::MaybeUninit,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
     pin::Pin,
     sync::{Arc, RwLock, RwLockWriteGuard},
     task::{Context, Poll},
@@ -14,7 +15,7 @@ use serde_derive::{Deserialize, Serialize};
 use virtual_mio::InterestHandler;
 use virtual_net::{
     NetworkError, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualTcpListener,
-    VirtualTcpSocket, VirtualUdpSocket, net_error_into_io_err,
+    VirtualTcpSocket, VirtualUdpSocket, VirtualUnixDatagramSocket, net_error_into_io_err,
 };
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::wasi::{Addressfamily, Errno, Rights, SockProto, Sockoption, Socktype};
@@ -74,6 +75,10 @@ pub enum InodeSocketKind {
         socket: Box<dyn VirtualUdpSocket + Sync>,
         peer: Option<SocketAddr>,
     },
+    UnixDatagram {
+        socket: Box<dyn VirtualUnixDatagramSocket + Sync>,
+        peer: Option<PathBuf>,
+    },
     RemoteSocket {
         props: SocketProperties,
         local_addr: SocketAddr,
@@ -199,6 +204,15 @@ impl From<wasmer_journal::SocketOptTimeType> for TimeType {
 //#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub(crate) struct InodeSocketProtected {
     pub kind: InodeSocketKind,
+    /// Set for Unix domain sockets, whose addresses are paths
+    pub unix: Option<UnixSocketPaths>,
+}
+
+/// The paths of a Unix domain socket, `None` while it is unnamed
+#[derive(Debug, Clone, Default)]
+pub struct UnixSocketPaths {
+    pub local: Option<PathBuf>,
+    pub peer: Option<PathBuf>,
 }
 
 #[derive(Debug)]
@@ -215,7 +229,21 @@ pub struct InodeSocket {
 
 impl InodeSocket {
     pub fn new(kind: InodeSocketKind) -> Self {
-        let protected = InodeSocketProtected { kind };
+        let unix = match &kind {
+            InodeSocketKind::PreSocket { props, .. } if props.family == Addressfamily::Unix => {
+                Some(UnixSocketPaths::default())
+            }
+            _ => None,
+        };
+        Self::new_with_unix(kind, unix)
+    }
+
+    pub fn new_unix(kind: InodeSocketKind, paths: UnixSocketPaths) -> Self {
+        Self::new_with_unix(kind, Some(paths))
+    }
+
+    fn new_with_unix(kind: InodeSocketKind, unix: Option<UnixSocketPaths>) -> Self {
+        let protected = InodeSocketProtected { kind, unix };
         Self {
             inner: Arc::new(InodeSocketInner {
                 protected: RwLock::new(protected),
@@ -401,6 +429,10 @@ impl InodeSocket {
             .flatten()
             .unwrap_or(Duration::from_secs(30));
 
+        if self.is_unix() {
+            return self.listen_unix(tasks, net, timeout).await;
+        }
+
         let socket = {
             let inner = self.inner.protected.read().unwrap();
             match &inner.kind {
@@ -470,6 +502,10 @@ impl InodeSocket {
                     tracing::warn!("wasi[?]::sock_listen - failed - not supported(udp-socket)");
                     return Err(Errno::Notsup);
                 }
+                InodeSocketKind::UnixDatagram { .. } => {
+                    tracing::warn!("wasi[?]::sock_listen - failed - not supported(unix-datagram)");
+                    return Err(Errno::Notsup);
+                }
             }
         };
 
@@ -561,6 +597,7 @@ impl InodeSocket {
             }
             InodeSocketKind::Icmp(_) => {}
             InodeSocketKind::UdpSocket { .. } => {}
+            InodeSocketKind::UnixDatagram { .. } => {}
             InodeSocketKind::Raw(_) => {}
             InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
             InodeSocketKind::RemoteSocket { .. } => {}
@@ -666,6 +703,7 @@ impl InodeSocket {
             InodeSocketKind::TcpListener { .. } => WasiSocketStatus::Opened,
             InodeSocketKind::TcpStream { .. } => WasiSocketStatus::Opened,
             InodeSocketKind::UdpSocket { .. } => WasiSocketStatus::Opened,
+            InodeSocketKind::UnixDatagram { .. } => WasiSocketStatus::Opened,
             InodeSocketKind::RemoteSocket { is_dead, .. } => match is_dead {
                 true => WasiSocketStatus::Closed,
                 false => WasiSocketStatus::Opened,
@@ -1144,6 +1182,13 @@ impl InodeSocket {
                                 Err(NetworkError::NotConnected)
                             }
                         }
+                        InodeSocketKind::UnixDatagram { socket, peer } => {
+                            if let Some(peer) = peer {
+                                socket.try_send_to(self.data, peer)
+                            } else {
+                                Err(NetworkError::NotConnected)
+                            }
+                        }
                         InodeSocketKind::PreSocket { .. } => {
                             return Poll::Ready(Err(Errno::Notconn));
                         }
@@ -1326,6 +1371,13 @@ impl InodeSocket {
                                 }
                             }
                         }
+                        InodeSocketKind::UnixDatagram { socket, peer } => {
+                            match socket.try_recv_from(self.data, peek) {
+                                Ok((amt, from)) if peer.is_none() || from == *peer => Ok(amt),
+                                Ok(_) => Err(NetworkError::WouldBlock),
+                                Err(err) => Err(err),
+                            }
+                        }
                         InodeSocketKind::RemoteSocket { is_dead, .. } => {
                             return match is_dead {
                                 true => Poll::Ready(Ok(0)),
@@ -1480,6 +1532,7 @@ impl InodeSocket {
             match &mut guard.kind {
                 InodeSocketKind::TcpStream { .. }
                 | InodeSocketKind::UdpSocket { .. }
+                | InodeSocketKind::UnixDatagram { .. }
                 | InodeSocketKind::Raw(..) => true,
                 InodeSocketKind::RemoteSocket { is_dead, .. } => !(*is_dead),
                 _ => false,
@@ -1496,6 +1549,7 @@ impl InodeSocketProtected {
             InodeSocketKind::TcpListener { socket, .. } => socket.remove_handler(),
             InodeSocketKind::TcpStream { socket, .. } => socket.remove_handler(),
             InodeSocketKind::UdpSocket { socket, .. } => socket.remove_handler(),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.remove_handler(),
             InodeSocketKind::Raw(socket) => socket.remove_handler(),
             InodeSocketKind::Icmp(socket) => socket.remove_handler(),
             InodeSocketKind::PreSocket { props, .. } => {
@@ -1512,6 +1566,7 @@ impl InodeSocketProtected {
             InodeSocketKind::TcpListener { socket, .. } => socket.poll_read_ready(cx),
             InodeSocketKind::TcpStream { socket, .. } => socket.poll_read_ready(cx),
             InodeSocketKind::UdpSocket { socket, .. } => socket.poll_read_ready(cx),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.poll_read_ready(cx),
             InodeSocketKind::Raw(socket) => socket.poll_read_ready(cx),
             InodeSocketKind::Icmp(socket) => socket.poll_read_ready(cx),
             InodeSocketKind::PreSocket { .. } => Poll::Pending,
@@ -1528,6 +1583,7 @@ impl InodeSocketProtected {
             InodeSocketKind::TcpListener { socket, .. } => socket.poll_write_ready(cx),
             InodeSocketKind::TcpStream { socket, .. } => socket.poll_write_ready(cx),
             InodeSocketKind::UdpSocket { socket, .. } => socket.poll_write_ready(cx),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.poll_write_ready(cx),
             InodeSocketKind::Raw(socket) => socket.poll_write_ready(cx),
             InodeSocketKind::Icmp(socket) => socket.poll_write_ready(cx),
             InodeSocketKind::PreSocket { .. } => Poll::Pending,
@@ -1547,6 +1603,7 @@ impl InodeSocketProtected {
             InodeSocketKind::TcpListener { socket, .. } => socket.set_handler(handler),
             InodeSocketKind::TcpStream { socket, .. } => socket.set_handler(handler),
             InodeSocketKind::UdpSocket { socket, .. } => socket.set_handler(handler),
+            InodeSocketKind::UnixDatagram { socket, .. } => socket.set_handler(handler),
             InodeSocketKind::Raw(socket) => socket.set_handler(handler),
             InodeSocketKind::Icmp(socket) => socket.set_handler(handler),
             InodeSocketKind::PreSocket { props, .. }
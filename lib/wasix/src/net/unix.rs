@@ -0,0 +1,681 @@
+//! Unix domain sockets, which are addressed by paths instead of IP
+//! addresses and are therefore driven by their own syscalls. Binding,
+//! connecting and addressing them is not recorded in the journal.
+
+use std::{
+    future::Future,
+    mem::MaybeUninit,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    task::Poll,
+    time::Duration,
+};
+
+use virtual_net::{NetworkError, VirtualNetworking};
+use wasmer::{MemoryView, WasmPtr};
+use wasmer_types::MemorySize;
+use wasmer_wasix_types::wasi::{Errno, Socktype};
+
+use super::socket::{InodeSocket, InodeSocketInner, InodeSocketKind, TimeType, UnixSocketPaths};
+use crate::{VirtualTaskManager, WasiEnv, net::net_error_into_wasi_err};
+
+/// Reads the path of a Unix socket from memory and resolves it against the
+/// current directory of the process
+pub(crate) fn read_unix_path<M: MemorySize>(
+    env: &WasiEnv,
+    memory: &MemoryView,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<PathBuf, Errno> {
+    let path = path
+        .read_utf8_string(memory, path_len)
+        .map_err(crate::mem_error_to_wasi)?;
+    let current_dir = env.state.fs.current_dir.lock().unwrap().clone();
+    resolve_unix_path(&current_dir, &path)
+}
+
+/// Writes the path of a Unix socket into a buffer, truncating it when the
+/// buffer is too small. The full length of the path is always returned,
+/// which is zero for unnamed sockets.
+pub(crate) fn write_unix_path<M: MemorySize>(
+    memory: &MemoryView,
+    path: Option<&Path>,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Result<(), Errno> {
+    let path = path
+        .map(|p| p.as_os_str().as_encoded_bytes())
+        .unwrap_or_default();
+    let buf_len: u64 = buf_len.into();
+    let amt = path.len().min(buf_len as usize);
+    if amt > 0 {
+        let amt_offset = M::Offset::try_from(amt).map_err(|_| Errno::Overflow)?;
+        buf.slice(memory, amt_offset)
+            .and_then(|slice| slice.write_slice(&path[..amt]))
+            .map_err(crate::mem_error_to_wasi)?;
+    }
+    let len = M::Offset::try_from(path.len()).map_err(|_| Errno::Overflow)?;
+    ret_len.write(memory, len).map_err(crate::mem_error_to_wasi)
+}
+
+/// Resolves the path of a Unix socket against the current directory.
+///
+/// Paths that start with a NUL byte are abstract names, which are not part
+/// of the file system and are used as is.
+pub(crate) fn resolve_unix_path(current_dir: &str, path: &str) -> Result<PathBuf, Errno> {
+    if path.is_empty() {
+        return Err(Errno::Inval);
+    }
+    if path.starts_with('\0') {
+        return Ok(PathBuf::from(path));
+    }
+
+    let mut resolved = PathBuf::from("/");
+    let path = Path::new(path);
+    if path.is_relative() {
+        resolved.push(current_dir);
+    }
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Ok(resolved)
+}
+
+/// Whether the path of a Unix socket is an abstract name rather than a
+/// path in the file system
+pub(crate) fn is_abstract_unix_path(path: &Path) -> bool {
+    path.as_os_str().as_encoded_bytes().first() == Some(&0)
+}
+
+impl InodeSocket {
+    /// Whether this socket is a Unix domain socket
+    pub fn is_unix(&self) -> bool {
+        self.inner.protected.read().unwrap().unix.is_some()
+    }
+
+    pub(crate) fn unix_paths(&self) -> Option<UnixSocketPaths> {
+        self.inner.protected.read().unwrap().unix.clone()
+    }
+
+    /// Returns the path this Unix socket is bound to, `None` when it is unnamed
+    pub fn unix_addr_local(&self) -> Result<Option<PathBuf>, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        let unix = inner.unix.as_ref().ok_or(Errno::Inval)?;
+        Ok(unix.local.clone())
+    }
+
+    /// Returns the path of the socket this Unix socket is connected to,
+    /// `None` when the peer is unnamed
+    pub fn unix_addr_peer(&self) -> Result<Option<PathBuf>, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        let unix = inner.unix.as_ref().ok_or(Errno::Inval)?;
+        match &inner.kind {
+            InodeSocketKind::TcpStream { .. } => Ok(unix.peer.clone()),
+            InodeSocketKind::UnixDatagram {
+                peer: Some(peer), ..
+            } => Ok(Some(peer.clone())),
+            _ => Err(Errno::Notconn),
+        }
+    }
+
+    /// Binds a Unix socket to a path. Stream sockets only remember the path
+    /// until they start listening, datagram sockets are opened right away.
+    pub async fn bind_unix(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        net: &dyn VirtualNetworking,
+        path: PathBuf,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let timeout = self
+            .opt_time(TimeType::BindTimeout)
+            .ok()
+            .flatten()
+            .unwrap_or(Duration::from_secs(30));
+
+        {
+            let mut inner = self.inner.protected.write().unwrap();
+            let inner = &mut *inner;
+            let unix = inner.unix.as_mut().ok_or(Errno::Inval)?;
+            match &inner.kind {
+                InodeSocketKind::PreSocket { .. } if unix.local.is_some() => {
+                    return Err(Errno::Inval);
+                }
+                InodeSocketKind::PreSocket { props, .. } => match props.ty {
+                    Socktype::Stream => {
+                        unix.local = Some(path);
+                        return Ok(None);
+                    }
+                    Socktype::Dgram => {}
+                    _ => return Err(Errno::Notsup),
+                },
+                _ => return Err(Errno::Inval),
+            }
+        }
+
+        tokio::select! {
+            socket = net.bind_unix_datagram(Some(&path)) => {
+                let socket = socket.map_err(net_error_into_wasi_err)?;
+                Ok(Some(InodeSocket::new_unix(
+                    InodeSocketKind::UnixDatagram { socket, peer: None },
+                    UnixSocketPaths { local: Some(path), peer: None },
+                )))
+            },
+            _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+        }
+    }
+
+    /// Opens an unnamed socket for a Unix datagram pre-socket, so that it can
+    /// send datagrams before it was bound
+    pub async fn auto_bind_unix(
+        &self,
+        net: &dyn VirtualNetworking,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        {
+            let inner = self.inner.protected.read().unwrap();
+            match &inner.kind {
+                InodeSocketKind::PreSocket { props, .. }
+                    if inner.unix.is_some() && props.ty == Socktype::Dgram => {}
+                _ => return Ok(None),
+            }
+        }
+
+        let socket = net
+            .bind_unix_datagram(None)
+            .await
+            .map_err(net_error_into_wasi_err)?;
+        Ok(Some(InodeSocket::new_unix(
+            InodeSocketKind::UnixDatagram { socket, peer: None },
+            UnixSocketPaths::default(),
+        )))
+    }
+
+    pub(super) async fn listen_unix(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        net: &dyn VirtualNetworking,
+        timeout: Duration,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let path = {
+            let inner = self.inner.protected.read().unwrap();
+            match (&inner.kind, inner.unix.as_ref()) {
+                (InodeSocketKind::PreSocket { props, .. }, Some(unix))
+                    if props.ty == Socktype::Stream =>
+                {
+                    unix.local.clone().ok_or_else(|| {
+                        tracing::warn!("wasi[?]::sock_listen - failed - unix path not bound");
+                        Errno::Inval
+                    })?
+                }
+                (InodeSocketKind::TcpListener { .. }, _) => {
+                    tracing::warn!(
+                        "wasi[?]::sock_listen - failed - already listening (unix-listener)"
+                    );
+                    return Err(Errno::Notsup);
+                }
+                _ => {
+                    tracing::warn!("wasi[?]::sock_listen - failed - not supported(unix)");
+                    return Err(Errno::Notsup);
+                }
+            }
+        };
+
+        tokio::select! {
+            socket = net.listen_unix(&path) => {
+                let socket = socket.map_err(net_error_into_wasi_err)?;
+                Ok(Some(InodeSocket::new_unix(
+                    InodeSocketKind::TcpListener {
+                        socket,
+                        accept_timeout: Some(timeout),
+                    },
+                    UnixSocketPaths { local: Some(path), peer: None },
+                )))
+            },
+            _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+        }
+    }
+
+    /// Connects a Unix stream socket to the listener bound to a path, or
+    /// sets the default destination of a Unix datagram socket
+    pub async fn connect_unix(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        net: &dyn VirtualNetworking,
+        path: PathBuf,
+        timeout: Option<Duration>,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let timeout = timeout.unwrap_or(Duration::from_secs(30));
+
+        let stream = {
+            let mut inner = self.inner.protected.write().unwrap();
+            let inner = &mut *inner;
+            let unix = inner.unix.as_ref().ok_or(Errno::Inval)?;
+            match &mut inner.kind {
+                InodeSocketKind::PreSocket { props, .. } => match props.ty {
+                    Socktype::Stream => Some((
+                        props.handler.take(),
+                        props.write_timeout,
+                        props.read_timeout,
+                        unix.local.clone(),
+                    )),
+                    Socktype::Dgram => None,
+                    _ => return Err(Errno::Notsup),
+                },
+                InodeSocketKind::UnixDatagram { peer, .. } => {
+                    peer.replace(path);
+                    return Ok(None);
+                }
+                _ => return Err(Errno::Notsup),
+            }
+        };
+
+        // Datagram sockets that were not bound yet are connected unnamed
+        let Some((handler, write_timeout, read_timeout, local)) = stream else {
+            let socket = tokio::select! {
+                res = net.bind_unix_datagram(None) => res.map_err(net_error_into_wasi_err)?,
+                _ = tasks.sleep_now(timeout) => return Err(Errno::Timedout)
+            };
+            return Ok(Some(InodeSocket::new_unix(
+                InodeSocketKind::UnixDatagram {
+                    socket,
+                    peer: Some(path),
+                },
+                UnixSocketPaths::default(),
+            )));
+        };
+
+        let mut socket = tokio::select! {
+            res = net.connect_unix(&path) => res.map_err(net_error_into_wasi_err)?,
+            _ = tasks.sleep_now(timeout) => return Err(Errno::Timedout)
+        };
+        if let Some(handler) = handler {
+            socket
+                .set_handler(handler)
+                .map_err(net_error_into_wasi_err)?;
+        }
+
+        Ok(Some(InodeSocket::new_unix(
+            InodeSocketKind::TcpStream {
+                socket,
+                write_timeout,
+                read_timeout,
+            },
+            UnixSocketPaths {
+                local,
+                peer: Some(path),
+            },
+        )))
+    }
+
+    pub async fn send_to_unix(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        buf: &[u8],
+        path: &Path,
+        timeout: Option<Duration>,
+        nonblocking: bool,
+    ) -> Result<usize, Errno> {
+        struct SocketSender<'a, 'b> {
+            inner: &'a InodeSocketInner,
+            data: &'b [u8],
+            path: &'b Path,
+            nonblocking: bool,
+            handler_registered: bool,
+        }
+        impl Drop for SocketSender<'_, '_> {
+            fn drop(&mut self) {
+                if self.handler_registered {
+                    let mut inner = self.inner.protected.write().unwrap();
+                    inner.remove_handler();
+                }
+            }
+        }
+        impl Future for SocketSender<'_, '_> {
+            type Output = Result<usize, Errno>;
+            fn poll(
+                mut self: Pin<&mut Self>,
+                cx: &mut std::task::Context<'_>,
+            ) -> Poll<Self::Output> {
+                loop {
+                    let mut inner = self.inner.protected.write().unwrap();
+                    let res = match &mut inner.kind {
+                        InodeSocketKind::UnixDatagram { socket, .. } => {
+                            socket.try_send_to(self.data, self.path)
+                        }
+                        InodeSocketKind::PreSocket { .. } => {
+                            return Poll::Ready(Err(Errno::Notconn));
+                        }
+                        _ => return Poll::Ready(Err(Errno::Notsup)),
+                    };
+                    return match res {
+                        Ok(amt) => Poll::Ready(Ok(amt)),
+                        Err(NetworkError::WouldBlock) if self.nonblocking => {
+                            Poll::Ready(Err(Errno::Again))
+                        }
+                        Err(NetworkError::WouldBlock) if !self.handler_registered => {
+                            inner
+                                .set_handler(cx.waker().into())
+                                .map_err(net_error_into_wasi_err)?;
+                            drop(inner);
+                            self.handler_registered = true;
+                            continue;
+                        }
+                        Err(NetworkError::WouldBlock) => Poll::Pending,
+                        Err(err) => Poll::Ready(Err(net_error_into_wasi_err(err))),
+                    };
+                }
+            }
+        }
+
+        let poller = SocketSender {
+            inner: &self.inner,
+            data: buf,
+            path,
+            nonblocking,
+            handler_registered: false,
+        };
+        if let Some(timeout) = timeout {
+            tokio::select! {
+                res = poller => res,
+                _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+            }
+        } else {
+            poller.await
+        }
+    }
+
+    pub async fn recv_from_unix(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        buf: &mut [MaybeUninit<u8>],
+        timeout: Option<Duration>,
+        nonblocking: bool,
+        peek: bool,
+    ) -> Result<(usize, Option<PathBuf>), Errno> {
+        struct SocketReceiver<'a, 'b> {
+            inner: &'a InodeSocketInner,
+            data: &'b mut [MaybeUninit<u8>],
+            nonblocking: bool,
+            peek: bool,
+            handler_registered: bool,
+        }
+        impl Drop for SocketReceiver<'_, '_> {
+            fn drop(&mut self) {
+                if self.handler_registered {
+                    let mut inner = self.inner.protected.write().unwrap();
+                    inner.remove_handler();
+                }
+            }
+        }
+        impl Future for SocketReceiver<'_, '_> {
+            type Output = Result<(usize, Option<PathBuf>), Errno>;
+            fn poll(
+                mut self: Pin<&mut Self>,
+                cx: &mut std::task::Context<'_>,
+            ) -> Poll<Self::Output> {
+                loop {
+                    let peek = self.peek;
+                    let mut inner = self.inner.protected.write().unwrap();
+                    let protected = &mut *inner;
+                    let res = match &mut protected.kind {
+                        InodeSocketKind::UnixDatagram { socket, .. } => {
+                            socket.try_recv_from(self.data, peek)
+                        }
+                        InodeSocketKind::TcpStream { socket, .. } => {
+                            let peer = protected.unix.as_ref().and_then(|u| u.peer.clone());
+                            socket.try_recv(self.data, peek).map(|amt| (amt, peer))
+                        }
+                        InodeSocketKind::PreSocket { .. } => {
+                            return Poll::Ready(Err(Errno::Notconn));
+                        }
+                        _ => return Poll::Ready(Err(Errno::Notsup)),
+                    };
+                    return match res {
+                        Ok(ret) => Poll::Ready(Ok(ret)),
+                        Err(NetworkError::WouldBlock) if self.nonblocking => {
+                            Poll::Ready(Err(Errno::Again))
+                        }
+                        Err(NetworkError::WouldBlock) if !self.handler_registered => {
+                            inner
+                                .set_handler(cx.waker().into())
+                                .map_err(net_error_into_wasi_err)?;
+                            drop(inner);
+                            self.handler_registered = true;
+                            continue;
+                        }
+                        Err(NetworkError::WouldBlock) => Poll::Pending,
+                        Err(err) => Poll::Ready(Err(net_error_into_wasi_err(err))),
+                    };
+                }
+            }
+        }
+
+        let poller = SocketReceiver {
+            inner: &self.inner,
+            data: buf,
+            nonblocking,
+            peek,
+            handler_registered: false,
+        };
+        if let Some(timeout) = timeout {
+            tokio::select! {
+                res = poller => res,
+                _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+            }
+        } else {
+            poller.await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use virtual_net::{UnixNetworking, UnsupportedVirtualNetworking};
+    use wasmer_wasix_types::wasi::{Addressfamily, SockProto};
+
+    use super::*;
+    use crate::{net::socket::SocketProperties, runtime::task_manager::tokio::TokioTaskManager};
+
+    fn networking() -> UnixNetworking {
+        UnixNetworking::new(Arc::new(UnsupportedVirtualNetworking::default()))
+    }
+
+    fn tasks() -> TokioTaskManager {
+        TokioTaskManager::new(tokio::runtime::Handle::current())
+    }
+
+    /// Opens a socket the way `sock_open` does
+    fn pre_socket(ty: Socktype) -> InodeSocket {
+        InodeSocket::new(InodeSocketKind::PreSocket {
+            props: SocketProperties {
+                family: Addressfamily::Unix,
+                ty,
+                pt: SockProto::Ip,
+                only_v6: false,
+                reuse_port: false,
+                reuse_addr: false,
+                no_delay: None,
+                keep_alive: None,
+                dont_route: None,
+                send_buf_size: None,
+                recv_buf_size: None,
+                write_timeout: None,
+                read_timeout: None,
+                accept_timeout: None,
+                connect_timeout: None,
+                handler: None,
+            },
+            addr: None,
+        })
+    }
+
+    fn init(buf: &[MaybeUninit<u8>]) -> Vec<u8> {
+        buf.iter().map(|b| unsafe { b.assume_init() }).collect()
+    }
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+    #[test]
+    fn paths_are_resolved_against_the_current_directory() {
+        assert_eq!(
+            resolve_unix_path("/home", "run/../app.sock"),
+            Ok(PathBuf::from("/home/app.sock"))
+        );
+        assert_eq!(
+            resolve_unix_path("/home", "/run/./app.sock"),
+            Ok(PathBuf::from("/run/app.sock"))
+        );
+        assert_eq!(
+            resolve_unix_path("/home", "\0abstract"),
+            Ok(PathBuf::from("\0abstract"))
+        );
+        assert!(is_abstract_unix_path(Path::new("\0abstract")));
+        assert_eq!(resolve_unix_path("/home", ""), Err(Errno::Inval));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streams_connect_to_listeners_bound_to_a_path() {
+        let (tasks, net) = (tasks(), networking());
+        let path = PathBuf::from("/run/server.sock");
+
+        let server = pre_socket(Socktype::Stream);
+        assert!(server.is_unix());
+        assert!(
+            server
+                .bind_unix(&tasks, &net, path.clone())
+                .await
+                .unwrap()
+                .is_none(),
+            "stream sockets only bind when they listen"
+        );
+        assert_eq!(
+            server.bind_unix(&tasks, &net, path.clone()).await.err(),
+            Some(Errno::Inval)
+        );
+        let listener = server.listen(&tasks, &net, 1).await.unwrap().unwrap();
+        assert_eq!(listener.unix_addr_local(), Ok(Some(path.clone())));
+
+        let client = pre_socket(Socktype::Stream)
+            .connect_unix(&tasks, &net, path.clone(), TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.unix_addr_local(), Ok(None));
+        assert_eq!(client.unix_addr_peer(), Ok(Some(path.clone())));
+
+        // `sock_accept` gives the accepted socket the path of the listener
+        let (accepted, _) = listener.accept(&tasks, false, TIMEOUT).await.unwrap();
+        let accepted = InodeSocket::new_unix(
+            InodeSocketKind::TcpStream {
+                socket: accepted,
+                write_timeout: None,
+                read_timeout: None,
+            },
+            UnixSocketPaths {
+                local: listener.unix_paths().unwrap().local,
+                peer: None,
+            },
+        );
+        assert_eq!(accepted.unix_addr_local(), Ok(Some(path.clone())));
+
+        assert_eq!(client.send(&tasks, b"ping", TIMEOUT, false).await, Ok(4));
+        let mut buf = [MaybeUninit::uninit(); 4];
+        let amt = accepted
+            .recv(&tasks, &mut buf, TIMEOUT, false, false)
+            .await
+            .unwrap();
+        assert_eq!(init(&buf[..amt]), b"ping");
+
+        assert_eq!(accepted.send(&tasks, b"pong", TIMEOUT, false).await, Ok(4));
+        let mut buf = [MaybeUninit::uninit(); 4];
+        let (amt, peer) = client
+            .recv_from_unix(&tasks, &mut buf, TIMEOUT, false, false)
+            .await
+            .unwrap();
+        assert_eq!(init(&buf[..amt]), b"pong");
+        assert_eq!(peer, Some(path));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn connecting_to_an_unbound_path_is_refused() {
+        let (tasks, net) = (tasks(), networking());
+        let err = pre_socket(Socktype::Stream)
+            .connect_unix(&tasks, &net, PathBuf::from("/run/none.sock"), TIMEOUT)
+            .await
+            .err();
+        assert_eq!(err, Some(Errno::Connrefused));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn datagrams_are_sent_between_paths() {
+        let (tasks, net) = (tasks(), networking());
+        let server_path = PathBuf::from("/run/server.sock");
+        let client_path = PathBuf::from("/run/client.sock");
+
+        let server = pre_socket(Socktype::Dgram)
+            .bind_unix(&tasks, &net, server_path.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let client = pre_socket(Socktype::Dgram)
+            .bind_unix(&tasks, &net, client_path.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(server.unix_addr_local(), Ok(Some(server_path.clone())));
+        assert_eq!(server.unix_addr_peer(), Err(Errno::Notconn));
+
+        let sent = client
+            .send_to_unix(&tasks, b"hello", &server_path, TIMEOUT, false)
+            .await;
+        assert_eq!(sent, Ok(5));
+        let mut buf = [MaybeUninit::uninit(); 16];
+        let (amt, from) = server
+            .recv_from_unix(&tasks, &mut buf, TIMEOUT, false, false)
+            .await
+            .unwrap();
+        assert_eq!(init(&buf[..amt]), b"hello");
+        assert_eq!(from, Some(client_path.clone()));
+
+        // Nothing is left to read, so a non-blocking receive fails
+        let res = server
+            .recv_from_unix(&tasks, &mut buf, TIMEOUT, true, false)
+            .await;
+        assert_eq!(res.err(), Some(Errno::Again));
+
+        // A pre-socket that was never bound sends from an unnamed socket
+        let unnamed = pre_socket(Socktype::Dgram)
+            .auto_bind_unix(&net)
+            .await
+            .unwrap()
+            .unwrap();
+        let sent = unnamed
+            .send_to_unix(&tasks, b"anon", &server_path, TIMEOUT, false)
+            .await;
+        assert_eq!(sent, Ok(4));
+        let (amt, from) = server
+            .recv_from_unix(&tasks, &mut buf, TIMEOUT, false, false)
+            .await
+            .unwrap();
+        assert_eq!(init(&buf[..amt]), b"anon");
+        assert_eq!(from, None);
+
+        // Connecting a datagram socket sets its default destination
+        assert!(
+            server
+                .connect_unix(&tasks, &net, client_path.clone(), TIMEOUT)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(server.unix_addr_peer(), Ok(Some(client_path)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{WasiEnv, WasiRuntimeError, journal::SnapshotTrigger};
+use crate::{Runtime, WasiEnv, WasiRuntimeError, journal::SnapshotTrigger};
 #[cfg(feature = "journal")]
 use crate::{WasiResult, journal::JournalEffector, syscalls::do_checkpoint_from_outside, unwind};
 use serde::{Deserialize, Serialize};
@@ -9,13 +9,14 @@ use std::{
     convert::TryInto,
     ops::Range,
     sync::{
-        Arc, Condvar, Mutex, MutexGuard, RwLock, Weak,
+        Arc, Condvar, Mutex, MutexGuard, OnceLock, RwLock, Weak,
         atomic::{AtomicU32, Ordering},
     },
     task::Waker,
     time::Duration,
 };
 use tracing::trace;
+use virtual_net::{DynVirtualNetworking, MeteredNetworking};
 use wasmer::FunctionEnvMut;
 use wasmer_types::ModuleHash;
 use wasmer_wasix_types::{
@@ -111,6 +112,9 @@ pub struct WasiProcess {
     /// the exponential backoff of CPU is halted (as in CPU
     /// is allowed to run freely)
     pub(crate) cpu_run_tokens: Arc<AtomicU32>,
+    /// Networking of the process, it is metered separately from the other
+    /// processes when the runtime has a network meter
+    pub(crate) networking: Arc<OnceLock<DynVirtualNetworking>>,
 }
 
 /// Represents a freeze of all threads to perform some action
@@ -183,6 +187,10 @@ pub struct WasiProcessInner {
     /// duplicate entries in the journal for memory that has not changed
     #[cfg(feature = "journal")]
     pub snapshot_memory_hash: HashMap<MemorySnapshotRegion, u64>,
+    /// Epoch of the dirty page tracking of the memory when the last
+    /// snapshot was taken
+    #[cfg(feature = "journal")]
+    pub snapshot_memory_epoch: wasmer::MemoryEpoch,
     /// Represents all the backoff properties for this process
     /// which will be used to determine if the CPU should be
     /// throttled or not
@@ -437,6 +445,8 @@ impl WasiProcess {
                 snapshot_on: Default::default(),
                 #[cfg(feature = "journal")]
                 snapshot_memory_hash: Default::default(),
+                #[cfg(feature = "journal")]
+                snapshot_memory_epoch: Default::default(),
                 disable_journaling_after_checkpoint: false,
                 stop_running_after_checkpoint: false,
                 backoff: WasiProcessCpuBackoff::new(max_cpu_backoff_time, max_cpu_cool_off_time),
@@ -469,11 +479,13 @@ impl WasiProcess {
             ),
             waiting,
             cpu_run_tokens: Arc::new(AtomicU32::new(0)),
+            networking: Default::default(),
         }
     }
 
     pub(super) fn set_pid(&mut self, pid: WasiProcessId) {
         self.pid = pid;
+        self.networking = Default::default();
     }
 
     /// Gets the process ID of this process
@@ -491,6 +503,21 @@ impl WasiProcess {
             .unwrap_or(WasiProcessId(0))
     }
 
+    /// Networking used by the process, which attributes its traffic to the
+    /// process when the runtime has a network meter
+    pub fn networking<'a>(
+        &'a self,
+        runtime: &'a (dyn Runtime + Send + Sync),
+    ) -> &'a DynVirtualNetworking {
+        let Some(meter) = runtime.network_meter() else {
+            return runtime.networking();
+        };
+        self.networking.get_or_init(|| {
+            let networking = MeteredNetworking::new(runtime.networking().clone(), meter.clone());
+            Arc::new(networking.with_process(self.pid.raw()))
+        })
+    }
+
     /// Gains access to the process internals
     // TODO: Make this private, all inner access should be exposed with methods.
     pub fn lock(&self) -> MutexGuard<'_, WasiProcessInner> {
@@ -1,13 +1,14 @@
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc};
 
 use bytes::Bytes;
 use futures::Future;
+use wasmer::{AsStoreMut, ExportError, Function, FunctionEnv, FunctionEnvMut, Instance, Value};
 use wasmer_wasix_types::{
     wasi::Errno,
     wasix::{ThreadStartType, WasiMemoryLayout},
 };
 
-use crate::os::task::thread::RewindResultType;
+use crate::{WasiEnv, WasiError, os::task::thread::RewindResultType, syscalls::__asyncify};
 
 /// Future that will be polled by asyncify methods
 #[doc(hidden)]
@@ -58,3 +59,111 @@ impl std::fmt::Debug for DeepSleepWork {
         )
     }
 }
+
+/// Future that will be polled when a metered guest runs out of fuel, which
+/// resolves to the number of points to resume the guest with, or `None` to
+/// stop it
+pub type FuelRefillFuture = dyn Future<Output = Option<u64>> + Send + Sync + 'static;
+
+/// Refills the metering points of the guests compiled with the resumable
+/// `Metering` middleware of `wasmer-middlewares` when they run out of them
+#[derive(Clone)]
+pub struct FuelRefill(Arc<dyn Fn() -> Pin<Box<FuelRefillFuture>> + Send + Sync + 'static>);
+
+impl FuelRefill {
+    pub fn new<F>(refill: F) -> Self
+    where
+        F: Fn() -> Pin<Box<FuelRefillFuture>> + Send + Sync + 'static,
+    {
+        Self(Arc::new(refill))
+    }
+
+    /// Returns the future resolving to the points of a guest which ran out of fuel
+    pub fn refill(&self) -> Pin<Box<FuelRefillFuture>> {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for FuelRefill {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "fuel-refill")
+    }
+}
+
+/// Creates the yield handler of guests compiled with the resumable `Metering`
+/// middleware of `wasmer-middlewares`, which is installed with its
+/// `set_yield_handler` function
+///
+/// When the guest runs out of fuel, it waits on its thread for `refill` to
+/// resolve. The guest is never unwound with asyncify in the meantime, as the
+/// metering code which calls the handler is added after the asyncify
+/// instrumentation of the guest and cannot be rewound. Signals and forced
+/// exits received while waiting are processed before the guest is resumed.
+pub fn fuel_yield_handler(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    refill: FuelRefill,
+) -> Function {
+    Function::new_typed_with_env(
+        store,
+        env,
+        move |mut ctx: FunctionEnvMut<'_, WasiEnv>| -> Result<(), WasiError> {
+            WasiEnv::do_pending_operations(&mut ctx)?;
+            let mut refilled = refill.refill();
+            let points = loop {
+                match __asyncify(&mut ctx, None, async {
+                    Ok::<_, Errno>((&mut refilled).await)
+                })? {
+                    Ok(points) => break points,
+                    // Signals arrived while waiting and were processed
+                    Err(_) => continue,
+                }
+            };
+
+            // Leaving the points exhausted stops the guest
+            let Some(points) = points else {
+                return Ok(());
+            };
+            let instance = ctx
+                .data()
+                .inner()
+                .main_module_instance_handles()
+                .instance
+                .clone();
+            for (name, value) in [
+                ("wasmer_metering_remaining_points", (points as i64).into()),
+                ("wasmer_metering_points_exhausted", 0i32.into()),
+            ] {
+                let res = match instance.exports.get_global(name) {
+                    Ok(global) => global.set(&mut ctx, value).map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                if let Err(err) = res {
+                    tracing::warn!("failed to refill the metering points - {}", err);
+                    return Err(WasiError::Exit(Errno::Noexec.into()));
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Installs the [`fuel_yield_handler`] of the runtime in an instance compiled
+/// with the resumable `Metering` middleware of `wasmer-middlewares`
+pub(crate) fn install_fuel_yield_handler(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    instance: &Instance,
+) -> Result<(), ExportError> {
+    let Ok(table) = instance.exports.get_table("wasmer_metering_yield_handler") else {
+        return Ok(());
+    };
+    let Some(refill) = env.as_ref(store).runtime().fuel_refill().cloned() else {
+        return Ok(());
+    };
+
+    let handler = fuel_yield_handler(store, env, refill);
+    table
+        .set(store, 0, Value::FuncRef(Some(handler)))
+        .map_err(|_| ExportError::IncompatibleType)
+}
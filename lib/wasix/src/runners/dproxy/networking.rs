@@ -1,6 +1,7 @@
 use std::sync::LazyLock;
 use std::{
     net::{IpAddr, SocketAddr},
+    path::Path,
     sync::{Arc, Mutex},
     task::{Context, Poll, Waker},
     time::Duration,
@@ -9,7 +10,7 @@ use std::{
 use virtual_net::{
     IpCidr, IpRoute, NetworkError, StreamSecurity, VirtualIcmpSocket, VirtualNetworking,
     VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
-    host::LocalNetworking, loopback::LoopbackNetworking,
+    VirtualUnixDatagramSocket, host::LocalNetworking, loopback::LoopbackNetworking,
 };
 
 #[derive(Debug, Default)]
@@ -210,6 +211,30 @@ impl VirtualNetworking for LocalWithLoopbackNetworking {
         self.inner_networking.connect_tcp(addr, peer).await
     }
 
+    /// Listens for connections on a Unix stream socket bound to a path
+    async fn listen_unix(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>, NetworkError> {
+        self.inner_networking.listen_unix(path).await
+    }
+
+    /// Opens a connection to the Unix stream socket bound to a path
+    async fn connect_unix(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>, NetworkError> {
+        self.inner_networking.connect_unix(path).await
+    }
+
+    /// Opens a Unix datagram socket bound to a path, or an unnamed one
+    async fn bind_unix_datagram(
+        &self,
+        path: Option<&Path>,
+    ) -> Result<Box<dyn VirtualUnixDatagramSocket + Sync>, NetworkError> {
+        self.inner_networking.bind_unix_datagram(path).await
+    }
+
     /// Performs DNS resolution for a specific hostname
     async fn resolve(
         &self,
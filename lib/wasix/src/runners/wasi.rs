@@ -5,6 +5,7 @@ use std::{path::PathBuf, sync::Arc};
 use anyhow::{Context, Error};
 use tracing::Instrument;
 use virtual_fs::{ArcBoxFile, FileSystem, TmpFileSystem, VirtualFile};
+use virtual_net::{FaultyNetworking, NetworkFaults};
 use wasmer::{Engine, Module};
 use wasmer_types::ModuleHash;
 use webc::metadata::{Command, annotations::Wasi};
@@ -13,9 +14,9 @@ use crate::{
     Runtime, WasiEnvBuilder, WasiError, WasiRuntimeError,
     bin_factory::BinaryPackage,
     capabilities::Capabilities,
-    journal::{DynJournal, DynReadableJournal, SnapshotTrigger},
+    journal::{DynJournal, DynReadableJournal, NonDeterministicReplay, SnapshotTrigger},
     runners::{MappedDirectory, MountedDirectory, wasi_common::CommonWasiOptions},
-    runtime::task_manager::VirtualTaskManagerExt,
+    runtime::{OverriddenRuntime, task_manager::VirtualTaskManagerExt},
 };
 
 use super::wasi_common::{MAPPED_CURRENT_DIR_DEFAULT_PATH, MappedCommand};
@@ -174,6 +175,13 @@ impl WasiRunner {
         self
     }
 
+    /// Injects faults into the networking of the runtime, see
+    /// [`virtual_net::fault`] for what they can do
+    pub fn with_network_faults(&mut self, faults: NetworkFaults) -> &mut Self {
+        self.wasi.network_faults = Some(faults);
+        self
+    }
+
     #[cfg(feature = "journal")]
     pub fn with_snapshot_trigger(&mut self, on: SnapshotTrigger) -> &mut Self {
         self.wasi.snapshot_on.push(on);
@@ -227,6 +235,15 @@ impl WasiRunner {
         self
     }
 
+    #[cfg(feature = "journal")]
+    pub fn with_non_deterministic_replay(
+        &mut self,
+        replay: Arc<NonDeterministicReplay>,
+    ) -> &mut Self {
+        self.wasi.non_deterministic_replay.replace(replay);
+        self
+    }
+
     pub fn with_stdin(&mut self, stdin: Box<dyn VirtualFile + Send + Sync>) -> &mut Self {
         self.stdin = Some(ArcBoxFile::new(stdin));
         self
@@ -279,8 +296,23 @@ impl WasiRunner {
 
         match runtime_or_engine {
             RuntimeOrEngine::Runtime(runtime) => {
+                let runtime = match &self.wasi.network_faults {
+                    Some(faults) => {
+                        let networking =
+                            FaultyNetworking::new(runtime.networking().clone(), faults.clone());
+                        Arc::new(
+                            OverriddenRuntime::new(runtime).with_networking(Arc::new(networking)),
+                        )
+                    }
+                    None => runtime,
+                };
                 builder.set_runtime(runtime);
             }
+            RuntimeOrEngine::Engine(_) if self.wasi.network_faults.is_some() => {
+                anyhow::bail!(
+                    "Network faults can only be injected into the networking of a runtime"
+                );
+            }
             RuntimeOrEngine::Engine(engine) => {
                 builder.set_engine(engine);
             }
@@ -383,6 +415,9 @@ impl WasiRunner {
 
             builder.with_stop_running_after_snapshot(self.wasi.stop_running_after_snapshot);
             builder.with_skip_stdio_during_bootstrap(self.wasi.skip_stdio_during_bootstrap);
+            if let Some(replay) = self.wasi.non_deterministic_replay.clone() {
+                builder.with_non_deterministic_replay(replay);
+            }
         }
 
         let env = builder.build()?;
@@ -483,6 +518,9 @@ impl WasiRunner {
             }
 
             builder.with_stop_running_after_snapshot(self.wasi.stop_running_after_snapshot);
+            if let Some(replay) = self.wasi.non_deterministic_replay.clone() {
+                builder.with_non_deterministic_replay(replay);
+            }
         }
 
         let env = builder.build()?;
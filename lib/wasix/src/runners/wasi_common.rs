@@ -8,13 +8,14 @@ use anyhow::{Context, Error};
 use futures::future::BoxFuture;
 use tokio::runtime::Handle;
 use virtual_fs::{FileSystem, FsError, OverlayFileSystem, RootFileSystemBuilder, TmpFileSystem};
+use virtual_net::NetworkFaults;
 use webc::metadata::annotations::Wasi as WasiAnnotation;
 
 use crate::{
     WasiEnvBuilder,
     bin_factory::BinaryPackage,
     capabilities::Capabilities,
-    journal::{DynJournal, DynReadableJournal, SnapshotTrigger},
+    journal::{DynJournal, DynReadableJournal, NonDeterministicReplay, SnapshotTrigger},
 };
 
 pub const MAPPED_CURRENT_DIR_DEFAULT_PATH: &str = "/home";
@@ -44,7 +45,9 @@ pub(crate) struct CommonWasiOptions {
     pub(crate) snapshot_interval: Option<std::time::Duration>,
     pub(crate) stop_running_after_snapshot: bool,
     pub(crate) skip_stdio_during_bootstrap: bool,
+    pub(crate) non_deterministic_replay: Option<Arc<NonDeterministicReplay>>,
     pub(crate) current_dir: Option<PathBuf>,
+    pub(crate) network_faults: Option<NetworkFaults>,
 }
 
 impl CommonWasiOptions {
@@ -366,6 +369,18 @@ impl<F: FileSystem> virtual_fs::FileSystem for RelativeOrAbsolutePathHack<F> {
         self.execute(path, |fs, p| fs.remove_file(p))
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> virtual_fs::Result<()> {
+        self.execute(path, |fs, p| fs.set_permissions(p, mode))
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> virtual_fs::Result<()> {
+        self.execute(path, |fs, p| fs.chown(p, uid, gid))
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> virtual_fs::Result<virtual_fs::Watcher> {
+        self.execute(path, |fs, p| fs.watch(p, recursive))
+    }
+
     fn new_open_options(&self) -> virtual_fs::OpenOptions<'_> {
         virtual_fs::OpenOptions::new(self)
     }
@@ -549,6 +564,9 @@ mod tests {
                         .and_then(unix_timestamp_nanos)
                         .unwrap_or(0),
                     len: contents.len() as u64,
+                    // Permissions, ownership and inode details come straight
+                    // from the host.
+                    ..metadata.clone().try_into().unwrap()
                 })
             }]
         );
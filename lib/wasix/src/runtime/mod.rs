@@ -16,14 +16,14 @@ use std::{
 };
 
 use futures::future::BoxFuture;
-use virtual_net::{DynVirtualNetworking, VirtualNetworking};
+use virtual_net::{DynVirtualNetworking, NetworkMeter, VirtualNetworking};
 use wasmer::{CompileError, Engine, Module, RuntimeError};
 use wasmer_wasix_types::wasi::ExitCode;
 
 #[cfg(feature = "journal")]
 use crate::journal::{DynJournal, DynReadableJournal};
 use crate::{
-    SpawnError, WasiTtyState,
+    FuelRefill, SpawnError, WasiTtyState,
     bin_factory::BinaryPackageCommand,
     http::{DynHttpClient, HttpClient},
     os::TtyBridge,
@@ -53,6 +53,19 @@ where
     /// Provides access to all the networking related functions such as sockets.
     fn networking(&self) -> &DynVirtualNetworking;
 
+    /// Accounts for the network traffic of each process, when set the
+    /// processes open their sockets through a metered networking.
+    fn network_meter(&self) -> Option<&NetworkMeter> {
+        None
+    }
+
+    /// Refills the metering points of the instances compiled with the resumable
+    /// `Metering` middleware of `wasmer-middlewares`, when set they wait for it
+    /// instead of trapping when they run out of points.
+    fn fuel_refill(&self) -> Option<&FuelRefill> {
+        None
+    }
+
     /// Retrieve the active [`VirtualTaskManager`].
     fn task_manager(&self) -> &Arc<dyn VirtualTaskManager>;
 
@@ -293,6 +306,8 @@ impl TtyBridge for DefaultTty {
 pub struct PluggableRuntime {
     pub rt: Arc<dyn VirtualTaskManager>,
     pub networking: DynVirtualNetworking,
+    pub network_meter: Option<NetworkMeter>,
+    pub fuel_refill: Option<FuelRefill>,
     pub http_client: Option<DynHttpClient>,
     pub package_loader: Arc<dyn PackageLoader + Send + Sync>,
     pub source: Arc<dyn Source + Send + Sync>,
@@ -315,6 +330,8 @@ impl PluggableRuntime {
                 let networking = Arc::new(virtual_net::UnsupportedVirtualNetworking::default());
             }
         }
+        // Unix sockets connect the instances sharing this runtime
+        let networking = Arc::new(virtual_net::UnixNetworking::new(networking));
         let http_client =
             crate::http::default_http_client().map(|client| Arc::new(client) as DynHttpClient);
 
@@ -331,6 +348,8 @@ impl PluggableRuntime {
         Self {
             rt,
             networking,
+            network_meter: None,
+            fuel_refill: None,
             http_client,
             engine: Default::default(),
             tty: None,
@@ -352,6 +371,16 @@ impl PluggableRuntime {
         self
     }
 
+    pub fn set_network_meter(&mut self, meter: NetworkMeter) -> &mut Self {
+        self.network_meter = Some(meter);
+        self
+    }
+
+    pub fn set_fuel_refill(&mut self, refill: FuelRefill) -> &mut Self {
+        self.fuel_refill = Some(refill);
+        self
+    }
+
     pub fn set_engine(&mut self, engine: Engine) -> &mut Self {
         self.engine = engine;
         self
@@ -409,6 +438,14 @@ impl Runtime for PluggableRuntime {
         &self.networking
     }
 
+    fn network_meter(&self) -> Option<&NetworkMeter> {
+        self.network_meter.as_ref()
+    }
+
+    fn fuel_refill(&self) -> Option<&FuelRefill> {
+        self.fuel_refill.as_ref()
+    }
+
     fn http_client(&self) -> Option<&DynHttpClient> {
         self.http_client.as_ref()
     }
@@ -464,6 +501,8 @@ pub struct OverriddenRuntime {
     inner: Arc<DynRuntime>,
     task_manager: Option<Arc<dyn VirtualTaskManager>>,
     networking: Option<DynVirtualNetworking>,
+    network_meter: Option<NetworkMeter>,
+    fuel_refill: Option<FuelRefill>,
     http_client: Option<DynHttpClient>,
     package_loader: Option<Arc<dyn PackageLoader + Send + Sync>>,
     source: Option<Arc<dyn Source + Send + Sync>>,
@@ -482,6 +521,8 @@ impl OverriddenRuntime {
             inner,
             task_manager: None,
             networking: None,
+            network_meter: None,
+            fuel_refill: None,
             http_client: None,
             package_loader: None,
             source: None,
@@ -505,6 +546,16 @@ impl OverriddenRuntime {
         self
     }
 
+    pub fn with_network_meter(mut self, meter: NetworkMeter) -> Self {
+        self.network_meter.replace(meter);
+        self
+    }
+
+    pub fn with_fuel_refill(mut self, refill: FuelRefill) -> Self {
+        self.fuel_refill.replace(refill);
+        self
+    }
+
     pub fn with_http_client(mut self, http_client: DynHttpClient) -> Self {
         self.http_client.replace(http_client);
         self
@@ -560,6 +611,22 @@ impl Runtime for OverriddenRuntime {
         }
     }
 
+    fn network_meter(&self) -> Option<&NetworkMeter> {
+        if let Some(meter) = self.network_meter.as_ref() {
+            Some(meter)
+        } else {
+            self.inner.network_meter()
+        }
+    }
+
+    fn fuel_refill(&self) -> Option<&FuelRefill> {
+        if let Some(refill) = self.fuel_refill.as_ref() {
+            Some(refill)
+        } else {
+            self.inner.fuel_refill()
+        }
+    }
+
     fn task_manager(&self) -> &Arc<dyn VirtualTaskManager> {
         if let Some(rt) = self.task_manager.as_ref() {
             rt
@@ -673,6 +673,34 @@ where
         self.inner.remove_file(&path)
     }
 
+    fn set_permissions(&self, path: &Path, mode: u32) -> virtual_fs::Result<()> {
+        let path = self.path(path)?;
+        self.inner.set_permissions(&path, mode)
+    }
+
+    fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> virtual_fs::Result<()> {
+        let path = self.path(path)?;
+        self.inner.chown(&path, uid, gid)
+    }
+
+    fn watch(&self, path: &Path, recursive: bool) -> virtual_fs::Result<virtual_fs::Watcher> {
+        let mapped = self.path(path)?;
+        let watcher = self.inner.watch(&mapped, recursive)?;
+
+        // Map the paths of the events back to the paths they were watched as.
+        let path = Path::new("/").join(path);
+        let unmap = move |p: PathBuf| match p.strip_prefix(&mapped) {
+            Ok(rest) if rest.as_os_str().is_empty() => path.clone(),
+            Ok(rest) => path.join(rest),
+            Err(_) => p,
+        };
+        Ok(watcher.filter_map(move |mut event| {
+            event.path = unmap(event.path);
+            event.target = event.target.map(&unmap);
+            Some(event)
+        }))
+    }
+
     fn new_open_options(&self) -> virtual_fs::OpenOptions<'_> {
         virtual_fs::OpenOptions::new(self)
     }
@@ -13,7 +13,7 @@ use wasmer::{AsStoreMut, Engine, Instance, Module};
 use wasmer_config::package::PackageId;
 
 #[cfg(feature = "journal")]
-use crate::journal::{DynJournal, DynReadableJournal, SnapshotTrigger};
+use crate::journal::{DynJournal, DynReadableJournal, NonDeterministicReplay, SnapshotTrigger};
 use crate::{
     Runtime, WasiEnv, WasiFunctionEnv, WasiRuntimeError, WasiThreadError,
     bin_factory::{BinFactory, BinaryPackage},
@@ -98,6 +98,9 @@ pub struct WasiEnvBuilder {
     #[cfg(feature = "journal")]
     pub(super) writable_journals: Vec<Arc<DynJournal>>,
 
+    #[cfg(feature = "journal")]
+    pub(super) non_deterministic_replay: Option<Arc<NonDeterministicReplay>>,
+
     pub(super) skip_stdio_during_bootstrap: bool,
 
     #[cfg(feature = "ctrlc")]
@@ -777,6 +780,14 @@ impl WasiEnvBuilder {
         self.skip_stdio_during_bootstrap = skip;
     }
 
+    /// Feeds the results of the non-deterministic calls recorded in a
+    /// previous run (see [`SnapshotTrigger::NonDeterministicCall`]) back to
+    /// the process, so that the run is reproduced exactly.
+    #[cfg(feature = "journal")]
+    pub fn with_non_deterministic_replay(&mut self, replay: Arc<NonDeterministicReplay>) {
+        self.non_deterministic_replay.replace(replay);
+    }
+
     /// Consumes the [`WasiEnvBuilder`] and produces a [`WasiEnvInit`], which
     /// can be used to construct a new [`WasiEnv`].
     ///
@@ -995,6 +1006,8 @@ impl WasiEnvBuilder {
             snapshot_on: self.snapshot_on,
             #[cfg(feature = "journal")]
             stop_running_after_snapshot: self.stop_running_after_snapshot,
+            #[cfg(feature = "journal")]
+            non_deterministic_replay: self.non_deterministic_replay,
             skip_stdio_during_bootstrap: self.skip_stdio_during_bootstrap,
         };
 
@@ -24,7 +24,7 @@ use wasmer_wasix_types::{
 use webc::metadata::annotations::Wasi;
 
 #[cfg(feature = "journal")]
-use crate::journal::{DynJournal, JournalEffector, SnapshotTrigger};
+use crate::journal::{DynJournal, JournalEffector, NonDeterministicReplay, SnapshotTrigger};
 use crate::{
     Runtime, VirtualTaskManager, WasiControlPlane, WasiEnvBuilder, WasiError, WasiFunctionEnv,
     WasiResult, WasiRuntimeError, WasiStateCreationError, WasiThreadError, WasiVFork,
@@ -78,6 +78,10 @@ pub struct WasiEnvInit {
     #[cfg(feature = "journal")]
     pub stop_running_after_snapshot: bool,
 
+    /// Results of non-deterministic calls that are fed back to the process
+    #[cfg(feature = "journal")]
+    pub non_deterministic_replay: Option<Arc<NonDeterministicReplay>>,
+
     /// Skip writes to stdout and stderr when bootstrapping from a journal
     pub skip_stdio_during_bootstrap: bool,
 }
@@ -121,6 +125,8 @@ impl WasiEnvInit {
             snapshot_on: self.snapshot_on.clone(),
             #[cfg(feature = "journal")]
             stop_running_after_snapshot: self.stop_running_after_snapshot,
+            #[cfg(feature = "journal")]
+            non_deterministic_replay: None,
             skip_stdio_during_bootstrap: self.skip_stdio_during_bootstrap,
         }
     }
@@ -170,6 +176,11 @@ pub struct WasiEnv {
     /// Should stdio be skipped when bootstrapping this module from an existing journal?
     pub skip_stdio_during_bootstrap: bool,
 
+    /// Results of non-deterministic calls recorded in a previous run, which
+    /// are returned instead of performing the calls
+    #[cfg(feature = "journal")]
+    pub(crate) non_deterministic_replay: Option<Arc<NonDeterministicReplay>>,
+
     /// Flag that indicates the cleanup of the environment is to be disabled
     /// (this is normally used so that the instance can be reused later on)
     pub(crate) disable_fs_cleanup: bool,
@@ -207,6 +218,8 @@ impl Clone for WasiEnv {
             enable_exponential_cpu_backoff: self.enable_exponential_cpu_backoff,
             replaying_journal: self.replaying_journal,
             skip_stdio_during_bootstrap: self.skip_stdio_during_bootstrap,
+            #[cfg(feature = "journal")]
+            non_deterministic_replay: self.non_deterministic_replay.clone(),
             disable_fs_cleanup: self.disable_fs_cleanup,
         }
     }
@@ -248,6 +261,9 @@ impl WasiEnv {
             enable_exponential_cpu_backoff: self.enable_exponential_cpu_backoff,
             replaying_journal: false,
             skip_stdio_during_bootstrap: self.skip_stdio_during_bootstrap,
+            // Forked processes do not record their calls either
+            #[cfg(feature = "journal")]
+            non_deterministic_replay: None,
             disable_fs_cleanup: self.disable_fs_cleanup,
         };
         Ok((new_env, handle))
@@ -384,6 +400,8 @@ impl WasiEnv {
             enable_journal: false,
             replaying_journal: false,
             skip_stdio_during_bootstrap: init.skip_stdio_during_bootstrap,
+            #[cfg(feature = "journal")]
+            non_deterministic_replay: init.non_deterministic_replay,
             enable_deep_sleep: init.capabilities.threading.enable_asynchronous_threading,
             enable_exponential_cpu_backoff: init
                 .capabilities
@@ -796,7 +814,7 @@ impl WasiEnv {
 
     /// Accesses the virtual networking implementation
     pub fn net(&self) -> &DynVirtualNetworking {
-        self.runtime.networking()
+        self.process.networking(self.runtime.as_ref())
     }
 
     /// Providers safe access to the initialized part of WasiEnv
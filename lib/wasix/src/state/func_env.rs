@@ -298,6 +298,8 @@ impl WasiFunctionEnv {
         }
         tracing::trace!("initializing with layout {:?}", self.data(store).layout);
 
+        crate::rewind::install_fuel_yield_handler(store, &self.env, &instance)?;
+
         Ok(())
     }
 
@@ -57,6 +57,27 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
                     self.action_update_compressed_memory(region, compressed_data, differ_ethereal)
                 }?;
             }
+            JournalEntry::MemoryChunkV1 {
+                hash,
+                compressed_data,
+            } => {
+                tracing::trace!(compressed_len = %compressed_data.len(), "Replay journal - MemoryChunk");
+                self.memory_chunks.insert(hash, &compressed_data);
+            }
+            JournalEntry::UpdateMemoryRegionChunkedV1 { region, chunks } => {
+                let data = self
+                    .memory_chunks
+                    .assemble(&region, &chunks)
+                    .map_err(anyhow_err_to_runtime_err)?;
+                let compressed_data = lz4_flex::compress_prepend_size(&data);
+                unsafe {
+                    self.action_update_compressed_memory(
+                        region,
+                        Cow::Owned(compressed_data),
+                        differ_ethereal,
+                    )
+                }?;
+            }
             JournalEntry::CloseThreadV1 { id, exit_code } => {
                 unsafe { self.action_close_thread(id, exit_code, differ_ethereal) }?;
             }
@@ -172,6 +193,18 @@ impl<'a> JournalSyscallPlayer<'a, '_> {
             JournalEntry::SnapshotV1 { when, trigger } => {
                 unsafe { self.action_snapshot(when, trigger, differ_ethereal) }?;
             }
+            // The results of non-deterministic calls are only used when
+            // replaying a run from its start, they do not change any state
+            JournalEntry::NonDeterministicCallV1 { call, .. } => {
+                tracing::trace!(?call, "Replay journal - NonDeterministicCall (skipped)");
+            }
+            // Encrypted entries must be decrypted by an `EncryptedJournal`
+            // before they reach the process
+            JournalEntry::EncryptedV1 { key_id, .. } => {
+                return Err(anyhow_err_to_runtime_err(anyhow::format_err!(
+                    "the journal is encrypted (key_id={key_id}) and no key was supplied to read it"
+                )));
+            }
             JournalEntry::SetClockTimeV1 { clock_id, time } => {
                 tracing::trace!(?clock_id, %time, "Replay journal - ClockTimeSet");
                 JournalEffector::apply_clock_time_set(&mut self.ctx, clock_id, time)
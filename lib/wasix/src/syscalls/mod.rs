@@ -33,6 +33,8 @@ use futures::{
 use tracing::instrument;
 pub use wasi::*;
 pub use wasix::*;
+#[cfg(feature = "journal")]
+use wasmer_journal::NonDeterministicCall;
 use wasmer_journal::SnapshotTrigger;
 use wasmer_wasix_types::wasix::ThreadStartType;
 
@@ -93,10 +95,11 @@ pub(crate) use self::types::{
     wasi::{
         Addressfamily, Advice, Clockid, Dircookie, Dirent, DlFlags, DlHandle, Errno, Event,
         EventFdReadwrite, Eventrwflags, Eventtype, ExitCode, Fd as WasiFd, Fdflags, Fdflagsext,
-        Fdstat, Filesize, Filestat, Filetype, Fstflags, Linkcount, Longsize, OptionFd, Pid,
-        Prestat, ProcSpawnFdOp, Rights, SignalDisposition, Snapshot0Clockid, Sockoption,
-        Sockstatus, Socktype, StackSnapshot, StdioMode as WasiStdioMode, Streamsecurity,
-        Subscription, SubscriptionFsReadwrite, Tid, Timestamp, TlKey, TlUser, TlVal, Tty, Whence,
+        Fdstat, Filesize, Filestat, Filestatext, Filetype, Fstflags, Linkcount, Lockflags,
+        Lockinfo, Locktype, Longsize, OptionFd, Pid, Prestat, ProcSpawnFdOp, Rights,
+        SignalDisposition, Snapshot0Clockid, Sockoption, Sockstatus, Socktype, StackSnapshot,
+        StdioMode as WasiStdioMode, Streamsecurity, Subscription, SubscriptionFsReadwrite, Tid,
+        Timestamp, TlKey, TlUser, TlVal, Tty, Watchmask, Whence,
     },
     *,
 };
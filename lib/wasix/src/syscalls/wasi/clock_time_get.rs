@@ -35,13 +35,41 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = unsafe { env.memory_view(&ctx) };
 
-    let mut t_out = wasi_try_ok!(platform_clock_time_get(clock_id, precision));
-    {
+    #[cfg(feature = "journal")]
+    if let Some(res) = JournalEffector::replay_non_deterministic_call(
+        &ctx,
+        NonDeterministicCall::ClockTimeGet { clock_id },
+    ) {
+        let data = wasi_try_ok!(res);
+        let t_out = wasi_try_ok!(
+            data.try_into()
+                .map(i64::from_le_bytes)
+                .map_err(|_| Errno::Io)
+        );
+        wasi_try_mem_ok!(time.write(&memory, t_out as Timestamp));
+        return Ok(Errno::Success);
+    }
+
+    let res = platform_clock_time_get(clock_id, precision).map(|mut t_out| {
         let guard = env.state.clock_offset.lock().unwrap();
         if let Some(offset) = guard.get(&clock_id) {
             t_out += *offset;
         }
-    };
+        t_out
+    });
+
+    #[cfg(feature = "journal")]
+    JournalEffector::save_non_deterministic_call(
+        &ctx,
+        NonDeterministicCall::ClockTimeGet { clock_id },
+        res.map(i64::to_le_bytes),
+    )
+    .map_err(|err| {
+        tracing::error!("failed to save clock_time_get event - {}", err);
+        WasiError::Exit(ExitCode::from(Errno::Fault))
+    })?;
+
+    let t_out = wasi_try_ok!(res);
     wasi_try_mem_ok!(time.write(&memory, t_out as Timestamp));
     Ok(Errno::Success)
 }
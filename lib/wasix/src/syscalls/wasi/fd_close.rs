@@ -37,6 +37,7 @@ pub fn fd_close(mut ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd) -> Result<Errn
             return Ok(e);
         }
     }
+    release_process_locks(env, fd);
     wasi_try_ok!(state.fs.close_fd(fd));
 
     #[cfg(feature = "journal")]
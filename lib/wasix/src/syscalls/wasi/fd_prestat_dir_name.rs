@@ -46,6 +46,7 @@ pub fn fd_prestat_dir_name<M: MemorySize>(
         | Kind::PipeTx { .. }
         | Kind::DuplexPipe { .. }
         | Kind::EventNotifications { .. }
+        | Kind::FsWatch { .. }
         | Kind::Epoll { .. } => Errno::Notdir,
     }
 }
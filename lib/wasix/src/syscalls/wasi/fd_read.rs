@@ -4,13 +4,16 @@ use virtual_fs::{AsyncReadExt, DeviceFile, ReadBuf};
 
 use super::*;
 use crate::{
-    fs::NotificationInner,
+    fs::{FsWatchInner, NotificationInner},
     journal::SnapshotTrigger,
     net::socket::TimeType,
     os::task::process::{MaybeCheckpointResult, WasiProcessCheckpoint, WasiProcessInner},
     syscalls::*,
 };
 
+/// Upper bound of the buffer events of a file system watch are read into.
+const MAX_FS_WATCH_READ: usize = 64 * 1024;
+
 /// ### `fd_read()`
 /// Read data from file descriptor
 /// Inputs:
@@ -49,9 +52,35 @@ pub fn fd_read<M: MemorySize>(
     ctx = wasi_try_ok!(maybe_backoff::<M>(ctx)?);
     if fd == DeviceFile::STDIN {
         ctx = wasi_try_ok!(maybe_snapshot_once::<M>(ctx, SnapshotTrigger::FirstStdin)?);
+
+        #[cfg(feature = "journal")]
+        if let Some(res) = JournalEffector::replay_non_deterministic_read(
+            &ctx,
+            NonDeterministicCall::FdRead { fd },
+            iovs,
+            iovs_len,
+        ) {
+            return fd_read_internal_handler(ctx, res, nread);
+        }
     }
 
     let res = fd_read_internal::<M>(&mut ctx, fd, iovs, iovs_len, offset, nread, true)?;
+
+    #[cfg(feature = "journal")]
+    if fd == DeviceFile::STDIN {
+        JournalEffector::save_non_deterministic_read(
+            &ctx,
+            NonDeterministicCall::FdRead { fd },
+            iovs,
+            iovs_len,
+            res,
+        )
+        .map_err(|err| {
+            tracing::error!("failed to save stdin read event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+    }
+
     fd_read_internal_handler(ctx, res, nread)
 }
 
@@ -435,6 +464,70 @@ pub(crate) fn fd_read_internal<M: MemorySize>(
                     let ret = wasi_try_ok_ok!(read_bytes(&reader[..], &memory, iovs_arr));
                     (ret, false)
                 }
+                Kind::FsWatch { inner } => {
+                    // Whole events are read at once, so they are first read
+                    // into a buffer as large as the I/O vectors
+                    let buf_len = {
+                        let memory = unsafe { env.memory_view(ctx) };
+                        let iovs_arr = wasi_try_mem_ok_ok!(iovs.slice(&memory, iovs_len));
+                        let iovs_arr = wasi_try_mem_ok_ok!(iovs_arr.access());
+                        iovs_arr
+                            .iter()
+                            .map(|iov| iov.buf_len.into() as usize)
+                            .sum::<usize>()
+                            .min(MAX_FS_WATCH_READ)
+                    };
+
+                    struct FsWatchPoller {
+                        inner: Arc<FsWatchInner>,
+                        buf: Option<Vec<u8>>,
+                        non_blocking: bool,
+                    }
+                    let poller = FsWatchPoller {
+                        inner: inner.clone(),
+                        buf: Some(vec![0u8; buf_len]),
+                        non_blocking: fd_flags.contains(Fdflags::NONBLOCK),
+                    };
+
+                    drop(guard);
+
+                    impl Future for FsWatchPoller {
+                        type Output = Result<Vec<u8>, Errno>;
+                        fn poll(
+                            mut self: Pin<&mut Self>,
+                            cx: &mut Context<'_>,
+                        ) -> Poll<Self::Output> {
+                            let mut buf = self.buf.take().unwrap_or_default();
+                            let res = if self.non_blocking {
+                                Poll::Ready(self.inner.try_read(&mut buf))
+                            } else {
+                                self.inner.read(cx.waker(), &mut buf)
+                            };
+                            match res {
+                                Poll::Ready(Ok(read)) => {
+                                    buf.truncate(read);
+                                    Poll::Ready(Ok(buf))
+                                }
+                                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                                Poll::Pending => {
+                                    self.buf = Some(buf);
+                                    Poll::Pending
+                                }
+                            }
+                        }
+                    }
+
+                    let res = __asyncify_light(env, None, poller)?.map_err(|err| match err {
+                        Errno::Timedout => Errno::Again,
+                        a => a,
+                    });
+                    let events = wasi_try_ok_ok!(res);
+
+                    let memory = unsafe { env.memory_view(ctx) };
+                    let iovs_arr = wasi_try_mem_ok_ok!(iovs.slice(&memory, iovs_len));
+                    let ret = wasi_try_ok_ok!(read_bytes(&events[..], &memory, iovs_arr));
+                    (ret, false)
+                }
                 Kind::Symlink { .. } | Kind::Epoll { .. } => {
                     return Ok(Err(Errno::Notsup));
                 }
@@ -114,6 +114,7 @@ pub fn fd_readdir<M: MemorySize>(
             | Kind::PipeTx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => return Ok(Errno::Notdir),
         }
     };
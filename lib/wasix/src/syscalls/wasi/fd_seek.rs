@@ -127,6 +127,7 @@ pub(crate) fn fd_seek_internal(
                 | Kind::PipeTx { .. }
                 | Kind::DuplexPipe { .. }
                 | Kind::EventNotifications { .. }
+                | Kind::FsWatch { .. }
                 | Kind::Epoll { .. } => {
                     // TODO: check this
                     return Ok(Err(Errno::Inval));
@@ -67,6 +67,7 @@ pub fn fd_sync(mut ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd) -> Result<Errno
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => return Ok(Errno::Inval),
         }
     }
@@ -79,14 +79,17 @@ pub(crate) fn path_filestat_get_internal(
         flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
     )?;
 
-    let st_ino = file_inode.ino().as_u64();
     let mut stat = if file_inode.is_preopened {
         *file_inode.stat.read().unwrap().deref()
     } else {
         let guard = file_inode.read();
         state.fs.get_stat_for_kind(guard.deref())?
     };
-    stat.st_ino = st_ino;
+    if stat.st_ino == 0 {
+        stat.st_ino = file_inode.ino().as_u64();
+    }
+    let st_nlink = file_inode.stat.read().unwrap().st_nlink;
+    stat.st_nlink = stat.st_nlink.max(st_nlink);
     Ok(stat)
 }
 
@@ -125,6 +125,7 @@ pub(crate) fn path_link_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => return Err(Errno::Notdir),
         }
     }
@@ -114,6 +114,7 @@ pub fn path_rename_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => return Ok(Errno::Inval),
             Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
                 debug!("fatal internal logic error: parent of inode is not a directory");
@@ -134,6 +135,7 @@ pub fn path_rename_internal(
             | Kind::PipeTx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => {
                 return Ok(Errno::Inval);
             }
@@ -202,7 +204,8 @@ pub fn path_rename_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::Epoll { .. }
-            | Kind::EventNotifications { .. } => {}
+            | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. } => {}
             Kind::Root { .. } => unreachable!("The root can not be moved"),
         }
     }
@@ -29,6 +29,22 @@ pub struct EventResult {
     pub inner: EventResultType,
 }
 impl EventResult {
+    pub fn from_event(event: &Event) -> Self {
+        Self {
+            userdata: event.userdata,
+            error: event.error,
+            type_: event.type_,
+            inner: match event.type_ {
+                Eventtype::Clock | Eventtype::Unknown => {
+                    EventResultType::Clock(unsafe { event.u.clock })
+                }
+                Eventtype::FdRead | Eventtype::FdWrite => {
+                    EventResultType::Fd(unsafe { event.u.fd_readwrite })
+                }
+            },
+        }
+    }
+
     pub fn into_event(self) -> Event {
         Event {
             userdata: self.userdata,
@@ -416,6 +432,32 @@ where
         }
     };
 
+    // Saves the triggered events so that they can be replayed later
+    #[cfg(feature = "journal")]
+    let process_events = |ctx: &FunctionEnvMut<'a, WasiEnv>, events: Vec<Event>| {
+        if let Err(err) = JournalEffector::save_poll_oneoff(ctx, Ok(&events)) {
+            tracing::error!("failed to save poll_oneoff event - {}", err);
+            return Errno::Fault;
+        }
+        process_events(ctx, events)
+    };
+
+    // When replaying a previous run the recorded events are returned
+    // instead of polling
+    #[cfg(feature = "journal")]
+    if !ctx
+        .data()
+        .thread
+        .has_rewind_of_type(HandleRewindType::ResultDriven)
+    {
+        if let Some(res) = JournalEffector::replay_poll_oneoff(&ctx) {
+            if let Ok(events) = res {
+                process_events(&ctx, events);
+            }
+            return Ok(Errno::Success);
+        }
+    }
+
     #[cfg(feature = "sys")]
     if env.capabilities.threading.enable_blocking_sleep && subs_len == 1 {
         // Here, `poll_oneoff` is merely in a sleeping state
@@ -471,6 +513,10 @@ where
                 // Otherwise process the error
                 Err(err) => {
                     tracing::warn!("failed to poll during deep sleep - {}", err);
+                    #[cfg(feature = "journal")]
+                    if let Err(err) = JournalEffector::save_poll_oneoff(ctx, Err(err)) {
+                        tracing::error!("failed to save poll_oneoff event - {}", err);
+                    }
                     err
                 }
             }
@@ -18,13 +18,43 @@ pub fn random_get<M: MemorySize>(
     let memory = unsafe { env.memory_view(&ctx) };
     let buf_len64: u64 = buf_len.into();
     let mut u8_buffer = vec![0; buf_len64 as usize];
-    let res = getrandom::getrandom(&mut u8_buffer);
+
+    #[cfg(feature = "journal")]
+    if let Some(res) =
+        JournalEffector::replay_non_deterministic_call(&ctx, NonDeterministicCall::RandomGet)
+    {
+        let data = wasi_try!(res);
+        if data.len() != u8_buffer.len() {
+            tracing::warn!(
+                recorded = data.len(),
+                requested = u8_buffer.len(),
+                "replayed random_get has a different length"
+            );
+            return Errno::Io;
+        }
+        let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
+        wasi_try_mem!(buf.write_slice(&data));
+        return Errno::Success;
+    }
+
+    let res = getrandom::getrandom(&mut u8_buffer).map_err(|_| Errno::Io);
+
+    #[cfg(feature = "journal")]
+    if let Err(err) = JournalEffector::save_non_deterministic_call(
+        &ctx,
+        NonDeterministicCall::RandomGet,
+        res.map(|_| &u8_buffer[..]),
+    ) {
+        tracing::error!("failed to save random_get event - {}", err);
+        return Errno::Fault;
+    }
+
     match res {
         Ok(()) => {
             let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
             wasi_try_mem!(buf.write_slice(&u8_buffer));
             Errno::Success
         }
-        Err(_) => Errno::Io,
+        Err(err) => err,
     }
 }
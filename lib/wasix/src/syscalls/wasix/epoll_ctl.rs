@@ -243,6 +243,7 @@ pub(super) fn register_epoll_handler(
             drop(inner);
         }
         InodeValFilePollGuardMode::EventNotifications(inner) => inner.set_interest_handler(handler),
+        InodeValFilePollGuardMode::FsWatch(inner) => inner.set_interest_handler(handler),
         InodeValFilePollGuardMode::DuplexPipe { pipe } => {
             let mut inner = pipe.write().unwrap();
             inner.set_interest_handler(handler);
@@ -0,0 +1,36 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_filestat_ext_get()`
+/// Get the permissions and ownership of an open file, which `fd_filestat_get`
+/// does not report
+///
+/// Input:
+/// - `Fd fd`
+///   The open file descriptor whose metadata will be read
+///
+/// Output:
+/// - `Filestatext *buf`
+///   Where the metadata from `fd` will be written
+#[instrument(level = "trace", skip_all, fields(%fd), ret)]
+pub fn fd_filestat_ext_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    buf: WasmPtr<Filestatext, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let fd_entry = wasi_try!(state.fs.get_fd(fd));
+    if !fd_entry.inner.rights.contains(Rights::FD_FILESTAT_GET) {
+        return Errno::Access;
+    }
+
+    let stat = {
+        let guard = fd_entry.inode.read();
+        wasi_try!(state.fs.get_stat_ext_for_kind(guard.deref()))
+    };
+
+    wasi_try_mem!(buf.deref(&memory).write(stat));
+
+    Errno::Success
+}
@@ -0,0 +1,149 @@
+use std::sync::RwLock;
+use std::task::Poll;
+
+use virtual_fs::{FileLock, LockKind, VirtualFile};
+
+use super::*;
+use crate::syscalls::*;
+
+/// Set on the owner of the locks owned by a process, so that they can not
+/// be mistaken with the locks owned by an open file description.
+const PROCESS_LOCK_OWNER: u64 = 1 << 63;
+
+type LockedHandle = Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>;
+
+/// ### `fd_lock()`
+/// Takes or releases an advisory lock over a range of bytes of a file,
+/// which is used to implement `flock` and `fcntl(F_SETLK)`
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor of the file to lock
+/// - `Locktype type_`
+///     The kind of lock to take, or `unlock` to release the locks
+/// - `Filesize start`
+///     Offset of the first byte to lock
+/// - `Filesize len`
+///     Number of bytes to lock, zero meaning up to the end of the file
+/// - `Lockflags flags`
+///     Whether to wait for conflicting locks to be released, and whether
+///     the lock is owned by the process or by the open file description
+/// Errors:
+/// - `Errno::Again`
+///     A conflicting lock is held and `WAIT` was not requested
+/// - `Errno::Badf`
+///     `fd` is not open for reading (resp. writing) when taking a process
+///     owned shared (resp. exclusive) lock
+#[instrument(level = "trace", skip_all, fields(%fd, ?type_, start, len, ?flags), ret)]
+pub fn fd_lock(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    type_: Locktype,
+    start: Filesize,
+    len: Filesize,
+    flags: Lockflags,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let (handle, lock) = wasi_try_ok!(lock_request(env, fd, type_, start, len, flags));
+
+    if !flags.contains(Lockflags::WAIT) || lock.kind == LockKind::Unlock {
+        let mut guard = handle.write().unwrap();
+        wasi_try_ok!(guard.lock(lock).map_err(fs_error_into_wasi_err));
+        return Ok(Errno::Success);
+    }
+
+    // The handle is only locked while polling, so that the owner of a
+    // conflicting lock which shares the handle can still release it
+    let work = std::future::poll_fn(move |cx| {
+        let mut guard = handle.write().unwrap();
+        match guard.lock(lock) {
+            Err(FsError::WouldBlock) => {}
+            res => return Poll::Ready(res.map_err(fs_error_into_wasi_err)),
+        }
+        match Pin::new(guard.as_mut()).poll_lock_ready(cx, &lock) {
+            Poll::Ready(Ok(())) => {
+                // Try again to take the lock
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(fs_error_into_wasi_err(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    });
+    wasi_try_ok!(__asyncify(&mut ctx, None, work)?);
+
+    Ok(Errno::Success)
+}
+
+/// Returns the handle of the file behind `fd` and the lock to take on it.
+pub(crate) fn lock_request(
+    env: &WasiEnv,
+    fd: WasiFd,
+    type_: Locktype,
+    start: Filesize,
+    len: Filesize,
+    flags: Lockflags,
+) -> Result<(LockedHandle, FileLock), Errno> {
+    let kind = match type_ {
+        Locktype::Unlock => LockKind::Unlock,
+        Locktype::Shared => LockKind::Shared,
+        Locktype::Exclusive => LockKind::Exclusive,
+        Locktype::Unknown => return Err(Errno::Inval),
+    };
+
+    let fd_entry = env.state.fs.get_fd(fd)?;
+    if flags.contains(Lockflags::PROCESS) {
+        let required = match kind {
+            LockKind::Shared => Rights::FD_READ,
+            LockKind::Exclusive => Rights::FD_WRITE,
+            LockKind::Unlock => Rights::empty(),
+        };
+        if !fd_entry.inner.rights.contains(required) {
+            return Err(Errno::Badf);
+        }
+    }
+
+    let handle = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.clone(),
+        Kind::Root { .. } | Kind::Dir { .. } => return Err(Errno::Isdir),
+        _ => return Err(Errno::Inval),
+    };
+
+    // Locks taken by `flock` are shared by all the descriptors referring
+    // to the same open file description, like the handle is
+    let owner = if flags.contains(Lockflags::PROCESS) {
+        PROCESS_LOCK_OWNER | env.pid().raw() as u64
+    } else {
+        Arc::as_ptr(&handle) as *const () as usize as u64
+    };
+    let lock = FileLock {
+        kind,
+        start,
+        len,
+        owner,
+    };
+    Ok((handle, lock))
+}
+
+/// Returns the process owning a lock, if it is owned by a process.
+pub(crate) fn lock_owner_pid(lock: &FileLock) -> Option<Pid> {
+    if lock.owner & PROCESS_LOCK_OWNER != 0 {
+        Some(lock.owner as Pid)
+    } else {
+        None
+    }
+}
+
+/// Releases the locks the process holds on the file behind `fd`, as
+/// closing any descriptor of a file releases the `fcntl` locks of the
+/// process on it.
+pub(crate) fn release_process_locks(env: &WasiEnv, fd: WasiFd) {
+    let flags = Lockflags::PROCESS;
+    if let Ok((handle, lock)) = lock_request(env, fd, Locktype::Unlock, 0, 0, flags) {
+        handle.write().unwrap().lock(lock).ok();
+    }
+}
@@ -0,0 +1,67 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_lock_get()`
+/// Finds an advisory lock which prevents a lock from being taken, which
+/// is used to implement `fcntl(F_GETLK)`
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor of the file to check
+/// - `Locktype type_`
+///     The kind of lock that would be taken
+/// - `Filesize start`
+///     Offset of the first byte that would be locked
+/// - `Filesize len`
+///     Number of bytes that would be locked, zero meaning up to the end
+///     of the file
+/// - `Lockflags flags`
+///     Whether the lock would be owned by the process or by the open file
+///     description
+/// Output:
+/// - `Lockinfo *ret`
+///     The conflicting lock, whose type is `unlock` if there is none
+#[instrument(level = "trace", skip_all, fields(%fd, ?type_, start, len, ?flags), ret)]
+pub fn fd_lock_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    type_: Locktype,
+    start: Filesize,
+    len: Filesize,
+    flags: Lockflags,
+    ret: WasmPtr<Lockinfo, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (handle, lock) = wasi_try!(lock_request(env, fd, type_, start, len, flags));
+    let conflict = {
+        let guard = handle.read().unwrap();
+        wasi_try!(
+            guard
+                .conflicting_lock(&lock)
+                .map_err(fs_error_into_wasi_err)
+        )
+    };
+
+    let info = match conflict {
+        Some(conflict) => Lockinfo {
+            type_: match conflict.kind {
+                virtual_fs::LockKind::Shared => Locktype::Shared,
+                virtual_fs::LockKind::Exclusive => Locktype::Exclusive,
+                virtual_fs::LockKind::Unlock => Locktype::Unlock,
+            },
+            pid: lock_owner_pid(&conflict).unwrap_or(0),
+            start: conflict.start,
+            len: conflict.len,
+        },
+        None => Lockinfo {
+            type_: Locktype::Unlock,
+            pid: 0,
+            start,
+            len,
+        },
+    };
+
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem!(ret.write(&memory, info));
+
+    Errno::Success
+}
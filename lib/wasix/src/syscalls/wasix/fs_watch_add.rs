@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fs_watch_add()`
+/// Watches the changes made to a file or to the entries of a directory,
+/// which is used to implement `inotify_add_watch`
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor returned by `fs_watch_create`
+/// - `Fd dirfd`
+///     The directory that `path` is relative to
+/// - `const char *path`
+///     String containing the path to watch
+/// - `u32 path_len`
+///     The length of the `path` string
+/// - `Watchmask mask`
+///     The changes to report
+/// Output:
+/// - `i32 *ret_wd`
+///     The watch descriptor identifying the events of this watch, which is
+///     the same if the path was already watched
+#[instrument(level = "trace", skip_all, fields(%fd, %dirfd, path = field::Empty, ?mask, ret_wd = field::Empty), ret)]
+pub fn fs_watch_add<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    dirfd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    mask: Watchmask,
+    ret_wd: WasmPtr<i32, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+    let path_str = unsafe { get_input_str!(&memory, path, path_len) };
+    Span::current().record("path", path_str.as_str());
+
+    let inner = match wasi_try!(state.fs.get_fd(fd)).inode.read().deref() {
+        Kind::FsWatch { inner } => inner.clone(),
+        _ => return Errno::Inval,
+    };
+
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, dirfd, &path_str, true));
+    let path = match inode.read().deref() {
+        Kind::File { path, .. } | Kind::Dir { path, .. } => path.clone(),
+        Kind::Root { .. } => PathBuf::from("/"),
+        _ => return Errno::Inval,
+    };
+
+    let watcher = wasi_try!(
+        state
+            .fs
+            .root_fs
+            .watch(&path, false)
+            .map_err(fs_error_into_wasi_err)
+    );
+    let wd = inner.add(&path, watcher, mask);
+
+    Span::current().record("ret_wd", wd);
+    wasi_try_mem!(ret_wd.write(&memory, wd));
+
+    Errno::Success
+}
@@ -0,0 +1,39 @@
+use super::*;
+use crate::{fs::FsWatchInner, syscalls::*};
+
+/// ### `fs_watch_create()`
+/// Creates a file descriptor which reports the changes made to the watched
+/// paths, which is used to implement `inotify_init`
+/// Output:
+/// - `Fd *ret_fd`
+///     The file descriptor the events are read from
+#[instrument(level = "trace", skip_all, fields(ret_fd = field::Empty), ret)]
+pub fn fs_watch_create<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    ret_fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let kind = Kind::FsWatch {
+        inner: Arc::new(FsWatchInner::new()),
+    };
+    let inode =
+        state
+            .fs
+            .create_inode_with_default_stat(inodes, kind, false, "fs_watch".to_string().into());
+    let rights = Rights::FD_READ | Rights::POLL_FD_READWRITE | Rights::FD_FDSTAT_SET_FLAGS;
+    let fd = wasi_try!(state.fs.create_fd(
+        rights,
+        rights,
+        Fdflags::empty(),
+        Fdflagsext::empty(),
+        0,
+        inode,
+    ));
+
+    Span::current().record("ret_fd", fd);
+    wasi_try_mem!(ret_fd.write(&memory, fd));
+
+    Errno::Success
+}
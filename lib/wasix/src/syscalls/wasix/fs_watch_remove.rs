@@ -0,0 +1,24 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fs_watch_remove()`
+/// Stops a watch added with `fs_watch_add`, which is used to implement
+/// `inotify_rm_watch`
+/// Inputs:
+/// - `Fd fd`
+///     The file descriptor returned by `fs_watch_create`
+/// - `i32 wd`
+///     The watch descriptor returned by `fs_watch_add`
+#[instrument(level = "trace", skip_all, fields(%fd, %wd), ret)]
+pub fn fs_watch_remove(ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd, wd: i32) -> Errno {
+    let env = ctx.data();
+    let inner = match wasi_try!(env.state.fs.get_fd(fd)).inode.read().deref() {
+        Kind::FsWatch { inner } => inner.clone(),
+        _ => return Errno::Inval,
+    };
+
+    if !inner.remove(wd) {
+        return Errno::Inval;
+    }
+    Errno::Success
+}
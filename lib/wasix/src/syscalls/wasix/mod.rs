@@ -13,11 +13,18 @@ mod epoll_wait;
 mod fd_dup2;
 mod fd_fdflags_get;
 mod fd_fdflags_set;
+mod fd_filestat_ext_get;
+mod fd_lock;
+mod fd_lock_get;
 mod fd_pipe;
+mod fs_watch_add;
+mod fs_watch_create;
+mod fs_watch_remove;
 mod futex_wait;
 mod futex_wake;
 mod futex_wake_all;
 mod getcwd;
+mod path_filestat_ext_get;
 mod path_open2;
 mod port_addr_add;
 mod port_addr_clear;
@@ -50,9 +57,13 @@ mod resolve;
 mod sched_yield;
 mod sock_accept;
 mod sock_addr_local;
+mod sock_addr_local_unix;
 mod sock_addr_peer;
+mod sock_addr_peer_unix;
 mod sock_bind;
+mod sock_bind_unix;
 mod sock_connect;
+mod sock_connect_unix;
 mod sock_get_opt_flag;
 mod sock_get_opt_size;
 mod sock_get_opt_time;
@@ -65,9 +76,11 @@ mod sock_open;
 mod sock_pair;
 mod sock_recv;
 mod sock_recv_from;
+mod sock_recv_from_unix;
 mod sock_send;
 mod sock_send_file;
 mod sock_send_to;
+mod sock_send_to_unix;
 mod sock_set_opt_flag;
 mod sock_set_opt_size;
 mod sock_set_opt_time;
@@ -100,11 +113,18 @@ pub use epoll_wait::*;
 pub use fd_dup2::*;
 pub use fd_fdflags_get::*;
 pub use fd_fdflags_set::*;
+pub use fd_filestat_ext_get::*;
+pub use fd_lock::*;
+pub use fd_lock_get::*;
 pub use fd_pipe::*;
+pub use fs_watch_add::*;
+pub use fs_watch_create::*;
+pub use fs_watch_remove::*;
 pub use futex_wait::*;
 pub use futex_wake::*;
 pub use futex_wake_all::*;
 pub use getcwd::*;
+pub use path_filestat_ext_get::*;
 pub use path_open2::*;
 pub use port_addr_add::*;
 pub use port_addr_clear::*;
@@ -137,9 +157,13 @@ pub use resolve::*;
 pub use sched_yield::*;
 pub use sock_accept::*;
 pub use sock_addr_local::*;
+pub use sock_addr_local_unix::*;
 pub use sock_addr_peer::*;
+pub use sock_addr_peer_unix::*;
 pub use sock_bind::*;
+pub use sock_bind_unix::*;
 pub use sock_connect::*;
+pub use sock_connect_unix::*;
 pub use sock_get_opt_flag::*;
 pub use sock_get_opt_size::*;
 pub use sock_get_opt_time::*;
@@ -152,9 +176,11 @@ pub use sock_open::*;
 pub use sock_pair::*;
 pub use sock_recv::*;
 pub use sock_recv_from::*;
+pub use sock_recv_from_unix::*;
 pub use sock_send::*;
 pub use sock_send_file::*;
 pub use sock_send_to::*;
+pub use sock_send_to_unix::*;
 pub use sock_set_opt_flag::*;
 pub use sock_set_opt_size::*;
 pub use sock_set_opt_time::*;
@@ -0,0 +1,53 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_filestat_ext_get()`
+/// Access the permissions and ownership of a file or directory, which
+/// `path_filestat_get` does not report
+/// Inputs:
+/// - `Fd fd`
+///     The directory that `path` is relative to
+/// - `LookupFlags flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// Output:
+/// - `Filestatext *buf`
+///     The location where the metadata will be stored
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_filestat_ext_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    buf: WasmPtr<Filestatext, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let path_string = unsafe { get_input_str!(&memory, path, path_len) };
+    Span::current().record("path", path_string.as_str());
+
+    let root_dir = wasi_try!(state.fs.get_fd(fd));
+    if !root_dir.inner.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Errno::Access;
+    }
+    let file_inode = wasi_try!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+
+    let stat = {
+        let guard = file_inode.read();
+        wasi_try!(state.fs.get_stat_ext_for_kind(guard.deref()))
+    };
+
+    wasi_try_mem!(buf.deref(&memory).write(stat));
+
+    Errno::Success
+}
@@ -284,6 +284,7 @@ pub(crate) fn path_open_internal(
             | Kind::PipeRx { .. }
             | Kind::DuplexPipe { .. }
             | Kind::EventNotifications { .. }
+            | Kind::FsWatch { .. }
             | Kind::Epoll { .. } => {}
             Kind::Symlink {
                 base_po_dir,
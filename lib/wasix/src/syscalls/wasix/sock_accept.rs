@@ -1,7 +1,10 @@
 use std::task::Waker;
 
 use super::*;
-use crate::{net::socket::TimeType, syscalls::*};
+use crate::{
+    net::socket::{TimeType, UnixSocketPaths},
+    syscalls::*,
+};
 
 /// ### `sock_accept()`
 /// Accept a new incoming connection.
@@ -100,12 +103,20 @@ pub fn sock_accept_v2<M: MemorySize>(
     let env = ctx.data();
     let (memory, state, _) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
     wasi_try_mem_ok!(ro_fd.write(&memory, fd));
-    wasi_try_ok!(crate::net::write_ip_port(
-        &memory,
-        ro_addr,
-        peer_addr.ip(),
-        peer_addr.port()
-    ));
+    let is_unix = wasi_try_ok!(__sock_actor(&mut ctx, fd, Rights::empty(), |socket, _| Ok(
+        socket.is_unix()
+    )));
+    let memory = unsafe { ctx.data().memory_view(&ctx) };
+    if is_unix {
+        wasi_try_ok!(crate::net::write_unix_addr(&memory, ro_addr));
+    } else {
+        wasi_try_ok!(crate::net::write_ip_port(
+            &memory,
+            ro_addr,
+            peer_addr.ip(),
+            peer_addr.port()
+        ));
+    }
 
     Ok(Errno::Success)
 }
@@ -121,7 +132,7 @@ pub(crate) fn sock_accept_internal(
     let inodes = &state.inodes;
 
     let tasks = env.tasks().clone();
-    let (child, local_addr, peer_addr, fd_flags) = wasi_try_ok_ok!(__sock_asyncify(
+    let (child, local_addr, peer_addr, fd_flags, unix) = wasi_try_ok_ok!(__sock_asyncify(
         env,
         sock,
         Rights::SOCK_ACCEPT,
@@ -136,19 +147,30 @@ pub(crate) fn sock_accept_internal(
                 .flatten()
                 .unwrap_or(Duration::from_secs(30));
             let local_addr = socket.addr_local()?;
+            let unix = socket.unix_paths();
             socket
                 .accept(tasks.deref(), nonblocking, Some(timeout))
                 .await
-                .map(|a| (a.0, local_addr, a.1, fd_flags))
+                .map(|a| (a.0, local_addr, a.1, fd_flags, unix))
         },
     ));
 
+    let child = InodeSocketKind::TcpStream {
+        socket: child,
+        write_timeout: None,
+        read_timeout: None,
+    };
     let kind = Kind::Socket {
-        socket: InodeSocket::new(InodeSocketKind::TcpStream {
-            socket: child,
-            write_timeout: None,
-            read_timeout: None,
-        }),
+        socket: match unix {
+            Some(listener) => InodeSocket::new_unix(
+                child,
+                UnixSocketPaths {
+                    local: listener.local,
+                    peer: None,
+                },
+            ),
+            None => InodeSocket::new(child),
+        },
     };
     let inode = state
         .fs
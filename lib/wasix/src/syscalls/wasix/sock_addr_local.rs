@@ -7,7 +7,8 @@ use crate::syscalls::*;
 /// Note: This is similar to `getsockname` in POSIX
 ///
 /// When successful, the contents of the output buffer consist of an IP address,
-/// either IP4 or IP6.
+/// either IP4 or IP6. Unix domain sockets only report their address family,
+/// their path is returned by `sock_addr_local_unix`.
 ///
 /// ## Parameters
 ///
@@ -22,12 +23,19 @@ pub fn sock_addr_local<M: MemorySize>(
         &mut ctx,
         sock,
         Rights::empty(),
-        |socket, _| socket.addr_local()
+        |socket, _| match socket.is_unix() {
+            true => Ok(None),
+            false => socket.addr_local().map(Some),
+        }
     ));
 
     Span::current().record("addr", format!("{addr:?}"));
 
     let memory = unsafe { ctx.data().memory_view(&ctx) };
+    let Some(addr) = addr else {
+        wasi_try!(crate::net::write_unix_addr(&memory, ret_addr));
+        return Errno::Success;
+    };
     wasi_try!(crate::net::write_ip_port(
         &memory,
         ret_addr,
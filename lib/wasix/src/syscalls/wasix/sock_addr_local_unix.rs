@@ -0,0 +1,45 @@
+use super::*;
+use crate::{net::unix::write_unix_path, syscalls::*};
+
+/// ### `sock_addr_local_unix()`
+/// Returns the path to which a Unix domain socket is bound.
+///
+/// Note: This is similar to `getsockname` in POSIX using PF_UNIX
+///
+/// The path is truncated when it does not fit in the buffer, its full
+/// length is returned either way and is zero for unnamed sockets.
+///
+/// ## Parameters
+///
+/// * `fd` - Socket that the path is bound to
+/// * `buf` - Buffer the path is written to
+///
+/// ## Return
+///
+/// Length of the path
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_addr_local_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Errno {
+    let path = wasi_try!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::empty(),
+        |socket, _| socket.unix_addr_local()
+    ));
+    Span::current().record("path", format!("{path:?}"));
+
+    let memory = unsafe { ctx.data().memory_view(&ctx) };
+    wasi_try!(write_unix_path(
+        &memory,
+        path.as_deref(),
+        buf,
+        buf_len,
+        ret_len
+    ));
+    Errno::Success
+}
@@ -0,0 +1,67 @@
+use super::*;
+use crate::{
+    net::unix::{is_abstract_unix_path, read_unix_path},
+    syscalls::*,
+};
+
+/// ### `sock_bind_unix()`
+/// Bind a Unix domain socket to a path
+/// Note: This is similar to `bind` in POSIX using PF_UNIX
+///
+/// Relative paths are resolved against the current directory. The socket
+/// is added to the file system at that path, which must not exist yet.
+/// Paths that start with a NUL byte are abstract names instead.
+///
+/// ## Parameters
+///
+/// * `fd` - File descriptor of the socket to be bind
+/// * `path` - Path to bind the socket to
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_bind_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let path = wasi_try_ok!(read_unix_path(env, &memory, path, path_len));
+    Span::current().record("path", format!("{path:?}"));
+
+    let in_fs = !is_abstract_unix_path(&path);
+    if in_fs && env.state.fs.root_fs.metadata(&path).is_ok() {
+        return Ok(Errno::Addrinuse);
+    }
+
+    let net = env.net().clone();
+    let tasks = env.tasks().clone();
+    let bind_path = path.clone();
+    wasi_try_ok!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_BIND,
+        move |socket, _| async move {
+            socket
+                .bind_unix(tasks.deref(), net.deref(), bind_path)
+                .await
+        }
+    ));
+
+    // Like on POSIX the socket shows up in the file system, though failing
+    // to add it (for instance when the directory is missing) is not an error
+    if in_fs {
+        ctx.data()
+            .state
+            .fs
+            .root_fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .ok();
+    }
+
+    Ok(Errno::Success)
+}
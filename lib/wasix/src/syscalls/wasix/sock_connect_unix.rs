@@ -0,0 +1,46 @@
+use super::*;
+use crate::{
+    net::{socket::TimeType, unix::read_unix_path},
+    syscalls::*,
+};
+
+/// ### `sock_connect_unix()`
+/// Initiate a connection on a Unix domain socket to the socket bound to a
+/// path
+///
+/// Note: This is similar to `connect` in POSIX using PF_UNIX
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `path` - Path of the socket to connect to
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty), ret)]
+pub fn sock_connect_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let path = wasi_try_ok!(read_unix_path(env, &memory, path, path_len));
+    Span::current().record("path", format!("{path:?}"));
+
+    let net = env.net().clone();
+    let tasks = env.tasks().clone();
+    wasi_try_ok!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_CONNECT,
+        move |socket, _| async move {
+            let timeout = socket.opt_time(TimeType::ConnectTimeout).ok().flatten();
+            socket
+                .connect_unix(tasks.deref(), net.deref(), path, timeout)
+                .await
+        }
+    ));
+
+    Ok(Errno::Success)
+}
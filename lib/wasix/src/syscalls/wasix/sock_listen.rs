@@ -28,8 +28,15 @@ pub fn sock_listen<M: MemorySize>(
 
     wasi_try_ok!(sock_listen_internal(&mut ctx, sock, backlog)?);
 
+    // Unix sockets are bound outside of the journal, so their listen
+    // could not be replayed
     #[cfg(feature = "journal")]
-    if ctx.data().enable_journal {
+    if ctx.data().enable_journal
+        && !__sock_actor(&mut ctx, sock, Rights::empty(), |socket, _| {
+            Ok(socket.is_unix())
+        })
+        .unwrap_or(false)
+    {
         JournalEffector::save_sock_listen(&mut ctx, sock, backlog).map_err(|err| {
             tracing::error!("failed to save sock_listen event - {}", err);
             WasiError::Exit(ExitCode::from(Errno::Fault))
@@ -40,6 +40,16 @@ pub fn sock_recv<M: MemorySize>(
         let pid = ctx.data().pid();
         let tid = ctx.data().tid();
 
+        #[cfg(feature = "journal")]
+        if let Some(res) = JournalEffector::replay_non_deterministic_read(
+            &ctx,
+            NonDeterministicCall::SockRecv { fd: sock },
+            ri_data,
+            ri_data_len,
+        ) {
+            return sock_recv_internal_handler(ctx, res, ro_data_len, ro_flags);
+        }
+
         let res = sock_recv_internal::<M>(
             &mut ctx,
             sock,
@@ -50,6 +60,19 @@ pub fn sock_recv<M: MemorySize>(
             ro_flags,
         )?;
 
+        #[cfg(feature = "journal")]
+        JournalEffector::save_non_deterministic_read(
+            &ctx,
+            NonDeterministicCall::SockRecv { fd: sock },
+            ri_data,
+            ri_data_len,
+            res,
+        )
+        .map_err(|err| {
+            tracing::error!("failed to save socket receive event - {}", err);
+            WasiError::Exit(ExitCode::from(Errno::Fault))
+        })?;
+
         sock_recv_internal_handler(ctx, res, ro_data_len, ro_flags)
     }
 }
@@ -0,0 +1,108 @@
+use std::mem::MaybeUninit;
+
+use super::*;
+use crate::{
+    net::{socket::TimeType, unix::write_unix_path},
+    syscalls::*,
+};
+
+/// ### `sock_recv_from_unix()`
+/// Receive a datagram and the path of its sender from a Unix domain socket.
+/// Note: This is similar to `recvfrom` in POSIX using PF_UNIX, though it also
+/// supports reading the data into multiple buffers in the manner of `readv`.
+///
+/// The path is truncated when it does not fit in the buffer, its full
+/// length is returned either way and is zero for unnamed senders.
+///
+/// ## Parameters
+///
+/// * `ri_data` - List of scatter/gather vectors to which to store data.
+/// * `ri_flags` - Message flags.
+/// * `path_buf` - Buffer the path of the sender is written to
+///
+/// ## Return
+///
+/// Number of bytes stored in ri_data, message flags and the length of the
+/// path of the sender.
+#[instrument(level = "trace", skip_all, fields(%sock, nread = field::Empty, peer = field::Empty), ret)]
+pub fn sock_recv_from_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ri_data: WasmPtr<__wasi_iovec_t<M>, M>,
+    ri_data_len: M::Offset,
+    ri_flags: RiFlags,
+    ro_data_len: WasmPtr<M::Offset, M>,
+    ro_flags: WasmPtr<RoFlags, M>,
+    path_buf: WasmPtr<u8, M>,
+    path_buf_len: M::Offset,
+    ret_path_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let iovs_arr = wasi_try_mem_ok!(ri_data.slice(&memory, ri_data_len));
+
+    let peek = (ri_flags & __WASI_SOCK_RECV_INPUT_PEEK) != 0;
+    let nonblocking_flag = (ri_flags & __WASI_SOCK_RECV_INPUT_DONT_WAIT) != 0;
+
+    let max_size = {
+        let mut max_size = 0usize;
+        for iovs in iovs_arr.iter() {
+            let iovs = wasi_try_mem_ok!(iovs.read());
+            let buf_len: usize = wasi_try_ok!(iovs.buf_len.try_into().map_err(|_| Errno::Overflow));
+            max_size += buf_len;
+        }
+        max_size
+    };
+
+    let (data, peer) = wasi_try_ok!(__sock_asyncify(
+        env,
+        sock,
+        Rights::SOCK_RECV_FROM,
+        |socket, fd| async move {
+            let nonblocking = nonblocking_flag || fd.inner.flags.contains(Fdflags::NONBLOCK);
+            let timeout = socket
+                .opt_time(TimeType::ReadTimeout)
+                .ok()
+                .flatten()
+                .unwrap_or(Duration::from_secs(30));
+
+            let mut buf = vec![MaybeUninit::<u8>::uninit(); max_size];
+            let (amt, peer) = socket
+                .recv_from_unix(
+                    env.tasks().deref(),
+                    &mut buf,
+                    Some(timeout),
+                    nonblocking,
+                    peek,
+                )
+                .await?;
+            let data: Vec<u8> = buf[..amt]
+                .iter()
+                .map(|b| unsafe { b.assume_init() })
+                .collect();
+            Ok((data, peer))
+        }
+    ));
+    Span::current()
+        .record("nread", data.len())
+        .record("peer", format!("{peer:?}"));
+
+    if !data.is_empty() {
+        wasi_try_ok!(read_bytes(&data[..], &memory, iovs_arr));
+    }
+    wasi_try_ok!(write_unix_path(
+        &memory,
+        peer.as_deref(),
+        path_buf,
+        path_buf_len,
+        ret_path_len
+    ));
+
+    let bytes_read: M::Offset = wasi_try_ok!(data.len().try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(ro_flags.write(&memory, 0));
+    wasi_try_mem_ok!(ro_data_len.write(&memory, bytes_read));
+
+    Ok(Errno::Success)
+}
@@ -193,7 +193,8 @@ pub(crate) fn sock_send_file_internal(
                             }
                             Kind::PipeTx { .. }
                             | Kind::Epoll { .. }
-                            | Kind::EventNotifications { .. } => {
+                            | Kind::EventNotifications { .. }
+                            | Kind::FsWatch { .. } => {
                                 return Ok(Err(Errno::Inval));
                             }
                             Kind::Dir { .. } | Kind::Root { .. } => {
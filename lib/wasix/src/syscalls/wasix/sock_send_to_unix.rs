@@ -0,0 +1,94 @@
+use super::*;
+use crate::{
+    net::{socket::TimeType, unix::read_unix_path},
+    syscalls::*,
+};
+
+/// ### `sock_send_to_unix()`
+/// Send a datagram on a Unix domain socket to the socket bound to a path.
+/// Note: This is similar to `sendto` in POSIX using PF_UNIX, though it also
+/// supports writing the data from multiple buffers in the manner of `writev`.
+///
+/// Sockets that were not bound are sending from an unnamed socket.
+///
+/// ## Parameters
+///
+/// * `si_data` - List of scatter/gather vectors to which to retrieve data
+/// * `si_flags` - Message flags.
+/// * `path` - Path of the socket to send the datagram to
+///
+/// ## Return
+///
+/// Number of bytes transmitted.
+#[instrument(level = "trace", skip_all, fields(%sock, path = field::Empty, nsent = field::Empty), ret)]
+pub fn sock_send_to_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    si_data: WasmPtr<__wasi_ciovec_t<M>, M>,
+    si_data_len: M::Offset,
+    si_flags: SiFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    ret_data_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    WasiEnv::do_pending_operations(&mut ctx)?;
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let path = wasi_try_ok!(read_unix_path(env, &memory, path, path_len));
+    Span::current().record("path", format!("{path:?}"));
+
+    // The buffers make up a single datagram
+    let data = {
+        let iovs_arr = wasi_try_mem_ok!(si_data.slice(&memory, si_data_len));
+        let iovs_arr = wasi_try_mem_ok!(iovs_arr.access());
+        let mut data = Vec::new();
+        for iovs in iovs_arr.iter() {
+            let buf =
+                wasi_try_mem_ok!(WasmPtr::<u8, M>::new(iovs.buf).slice(&memory, iovs.buf_len));
+            data.extend_from_slice(wasi_try_mem_ok!(buf.access()).as_ref());
+        }
+        data
+    };
+
+    let net = env.net().clone();
+    wasi_try_ok!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_SEND_TO,
+        move |socket, _| async move { socket.auto_bind_unix(net.deref()).await }
+    ));
+
+    let env = ctx.data();
+    let nonblocking_flag = (si_flags & __WASI_SOCK_SEND_INPUT_DONT_WAIT) != 0;
+    let bytes_written = wasi_try_ok!(__sock_asyncify(
+        env,
+        sock,
+        Rights::SOCK_SEND_TO,
+        |socket, fd| async move {
+            let nonblocking = nonblocking_flag || fd.inner.flags.contains(Fdflags::NONBLOCK);
+            let timeout = socket
+                .opt_time(TimeType::WriteTimeout)
+                .ok()
+                .flatten()
+                .unwrap_or(Duration::from_secs(30));
+            socket
+                .send_to_unix(
+                    env.tasks().deref(),
+                    &data,
+                    &path,
+                    Some(timeout),
+                    nonblocking,
+                )
+                .await
+        },
+    ));
+    Span::current().record("nsent", bytes_written);
+
+    let memory = unsafe { env.memory_view(&ctx) };
+    let bytes_written: M::Offset =
+        wasi_try_ok!(bytes_written.try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(ret_data_len.write(&memory, bytes_written));
+
+    Ok(Errno::Success)
+}
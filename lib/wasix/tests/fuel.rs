@@ -0,0 +1,175 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use virtual_fs::AsyncReadExt;
+use virtual_mio::InlineWaker;
+use wasmer::{
+    Engine, Module,
+    sys::{CompilerConfig, Cranelift, EngineBuilder},
+    wasmparser::Operator,
+};
+use wasmer_middlewares::Metering;
+use wasmer_types::ModuleHash;
+use wasmer_wasix::{
+    FuelRefill, Pipe, PluggableRuntime,
+    runners::wasi::{RuntimeOrEngine, WasiRunner},
+    runtime::task_manager::tokio::TokioTaskManager,
+};
+
+/// Counts to 1000 before writing "done", which takes 8 points per iteration.
+const GUEST: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 16) "done")
+
+    (func $main (export "_start")
+        (local $i i32)
+        (loop $again
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $again (i32.lt_u (local.get $i) (i32.const 1000)))
+        )
+
+        (i32.store (i32.const 0) (i32.const 16))
+        (i32.store (i32.const 4) (i32.const 4))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    )
+)
+"#;
+
+/// The same guest, exporting the functions of a guest instrumented with
+/// asyncify so that its threads are allowed to deep sleep. Its code is not
+/// instrumented, so unwinding it would leave it in an undefined state.
+const ASYNCIFIED_GUEST: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (global $state (mut i32) (i32.const 0))
+
+    (data (i32.const 16) "done")
+
+    (func $main (export "_start")
+        (local $i i32)
+        (loop $again
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $again (i32.lt_u (local.get $i) (i32.const 1000)))
+        )
+
+        (i32.store (i32.const 0) (i32.const 16))
+        (i32.store (i32.const 4) (i32.const 4))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    )
+
+    (func (export "asyncify_start_unwind") (param i32) (global.set $state (i32.const 1)))
+    (func (export "asyncify_stop_unwind") (global.set $state (i32.const 0)))
+    (func (export "asyncify_start_rewind") (param i32) (global.set $state (i32.const 2)))
+    (func (export "asyncify_stop_rewind") (global.set $state (i32.const 0)))
+    (func (export "asyncify_get_state") (result i32) (global.get $state))
+)
+"#;
+
+/// Runs the guest with 500 points, refilled with 500 points after `delay`
+/// at most `max_refills` times, and returns its output and the number of
+/// refills.
+fn run_metered(max_refills: u32) -> (anyhow::Result<()>, String, u32) {
+    run_metered_guest(GUEST, max_refills, Duration::from_millis(1))
+}
+
+fn run_metered_guest(
+    guest: &[u8],
+    max_refills: u32,
+    delay: Duration,
+) -> (anyhow::Result<()>, String, u32) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let _guard = runtime.handle().enter();
+
+    let mut compiler = Cranelift::default();
+    compiler.push_middleware(Arc::new(Metering::new(500, |_: &Operator| 1).resumable()));
+    let engine: Engine = EngineBuilder::new(compiler).into();
+    let module = Module::new(&engine, guest).unwrap();
+
+    let refills = Arc::new(AtomicU32::new(0));
+    let mut rt = PluggableRuntime::new(Arc::new(TokioTaskManager::new(runtime.handle().clone())));
+    rt.set_engine(engine);
+    rt.set_fuel_refill(FuelRefill::new({
+        let refills = refills.clone();
+        let handle = runtime.handle().clone();
+        move || {
+            let refills = refills.clone();
+            // The handler polls this on the guest thread, outside of tokio.
+            let delay = {
+                let _guard = handle.enter();
+                tokio::time::sleep(delay)
+            };
+            Box::pin(async move {
+                delay.await;
+                if refills.fetch_add(1, Ordering::SeqCst) < max_refills {
+                    Some(500)
+                } else {
+                    None
+                }
+            })
+        }
+    }));
+
+    let (stdout_tx, mut stdout_rx) = Pipe::channel();
+    let res = {
+        let mut runner = WasiRunner::new();
+        runner.with_stdout(Box::new(stdout_tx));
+        runner
+            .capabilities_mut()
+            .threading
+            .enable_asynchronous_threading = true;
+        runner.run_wasm(
+            RuntimeOrEngine::Runtime(Arc::new(rt)),
+            "fuel",
+            module,
+            ModuleHash::random(),
+        )
+    };
+
+    let mut stdout = String::new();
+    InlineWaker::block_on(stdout_rx.read_to_string(&mut stdout)).unwrap();
+    (res, stdout, refills.load(Ordering::SeqCst))
+}
+
+#[test]
+fn fuel_exhaustion_resumes_once_refilled() {
+    let (res, stdout, refills) = run_metered(u32::MAX);
+    res.unwrap();
+    assert_eq!(stdout, "done");
+    assert!(refills >= 15, "{refills} refills");
+}
+
+#[test]
+fn fuel_exhaustion_stops_without_refill() {
+    let (res, stdout, refills) = run_metered(3);
+    assert!(res.is_err());
+    assert_eq!(stdout, "");
+    assert_eq!(refills, 4);
+}
+
+#[test]
+fn fuel_exhaustion_waits_in_asyncified_guests() {
+    // Deep sleep would kick in after 50ms of waiting
+    let (res, stdout, refills) =
+        run_metered_guest(ASYNCIFIED_GUEST, u32::MAX, Duration::from_millis(100));
+    res.unwrap();
+    assert_eq!(stdout, "done");
+    assert!(refills >= 15, "{refills} refills");
+}
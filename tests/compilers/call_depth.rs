@@ -0,0 +1,45 @@
+use anyhow::Result;
+use wasmer_middlewares::CallDepth;
+use wasmer_middlewares::call_depth::{get_call_depth, reset_call_depth};
+
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_types::TrapCode;
+
+fn instantiate(mut config: crate::Config, limit: u32) -> Result<(Store, Instance)> {
+    config.middlewares.push(Arc::new(CallDepth::new(limit)));
+    let mut store = config.store();
+    let wat = r#"(module
+        (func $rec (export "rec") (param i32) (result i32)
+           (if (result i32) (i32.eqz (local.get 0))
+              (then (i32.const 0))
+              (else
+                 (i32.add
+                    (call $rec (i32.sub (local.get 0) (i32.const 1)))
+                    (i32.const 1)))))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    Ok((store, instance))
+}
+
+#[compiler_test(call_depth)]
+fn call_depth_ok(config: crate::Config) -> Result<()> {
+    let (mut store, instance) = instantiate(config, 8)?;
+    let rec: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "rec")?;
+    assert_eq!(rec.call(&mut store, 7)?, 7);
+    assert_eq!(get_call_depth(&mut store, &instance), 0);
+    Ok(())
+}
+
+#[compiler_test(call_depth)]
+fn call_depth_exceeded(config: crate::Config) -> Result<()> {
+    let (mut store, instance) = instantiate(config, 8)?;
+    let rec: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "rec")?;
+    let error = rec.call(&mut store, 8).unwrap_err();
+    assert_eq!(error.to_trap(), Some(TrapCode::CallDepthExceeded));
+
+    reset_call_depth(&mut store, &instance);
+    assert_eq!(rec.call(&mut store, 7)?, 7);
+    Ok(())
+}
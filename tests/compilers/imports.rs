@@ -514,3 +514,68 @@ fn instance_local_memory_lifetime(config: crate::Config) -> Result<()> {
 
     Ok(())
 }
+
+#[compiler_test(imports)]
+fn dynamic_function_with_multiple_results(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (type $t (func (param i32 i64) (result i64 f64 i32 f32)))
+        (import "host" "swap" (func $swap (type $t)))
+        (table 1 1 funcref)
+        (elem (i32.const 0) func $swap)
+
+        (func (export "call") (type $t)
+            local.get 0
+            local.get 1
+            call $swap)
+        (func (export "call_indirect") (type $t)
+            local.get 0
+            local.get 1
+            i32.const 0
+            call_indirect (type $t))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let swap = Function::new(
+        &mut store,
+        FunctionType::new(
+            vec![ValueType::I32, ValueType::I64],
+            vec![
+                ValueType::I64,
+                ValueType::F64,
+                ValueType::I32,
+                ValueType::F32,
+            ],
+        ),
+        |values| {
+            let (x, y) = (values[0].unwrap_i32(), values[1].unwrap_i64());
+            Ok(vec![
+                Value::I64(y),
+                Value::F64(x as f64),
+                Value::I32(x * 2),
+                Value::F32(y as f32 * 2.0),
+            ])
+        },
+    );
+    let imports = imports! {
+        "host" => {
+            "swap" => swap,
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    for name in ["call", "call_indirect"] {
+        let f = instance.exports.get_function(name)?;
+        let results = f.call(&mut store, &[Value::I32(3), Value::I64(4)])?;
+        assert_eq!(
+            &*results,
+            &[
+                Value::I64(4),
+                Value::F64(3.0),
+                Value::I32(6),
+                Value::F32(8.0)
+            ]
+        );
+    }
+
+    Ok(())
+}
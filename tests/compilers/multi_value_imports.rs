@@ -2,14 +2,12 @@
 //! This tests checks that the provided functions (both native and
 //! dynamic ones) work properly.
 
-use wasmer::*;
-
 macro_rules! mvr_test {
     ($test_name:ident, $( $result_type:ty ),* ) => {
         mod $test_name {
-            use wasmer::*;
+            use super::ExpectedExpr;
 
-            fn get_module(store: &Store) -> anyhow::Result<wasmer::Module> {
+            fn get_module(store: &wasmer::Store) -> anyhow::Result<wasmer::Module> {
                 let wat: String = r#"
   (type $type (func (param i32) (result
 "#.to_string() +
@@ -39,23 +37,25 @@ macro_rules! mvr_test {
             fn native(config: crate::Config) -> anyhow::Result<()> {
                 let mut store = config.store();
                 let module = get_module(&store)?;
+                let callback_fn = wasmer::Function::new_typed(&mut store, callback_fn);
                 let instance = wasmer::Instance::new(
+                    &mut store,
                     &module,
                     &wasmer::imports! {
                         "host" => {
-                            "callback_fn" => wasmer::Function::new_native(&store, callback_fn)
+                            "callback_fn" => callback_fn
                         }
                     }
                 )?;
                 let expected_value = vec![ $( <$result_type>::expected_val(1) ),* ].into_boxed_slice();
-                assert_eq!(instance.exports.get_function("test_call")?.call(&[wasmer::Val::I32(1)])?,
+                assert_eq!(instance.exports.get_function("test_call")?.call(&mut store, &[wasmer::Value::I32(1)])?,
                            expected_value);
-                assert_eq!(instance.exports.get_function("test_call_indirect")?.call(&[wasmer::Val::I32(1)])?,
+                assert_eq!(instance.exports.get_function("test_call_indirect")?.call(&mut store, &[wasmer::Value::I32(1)])?,
                            expected_value);
                 Ok(())
             }
 
-            fn dynamic_callback_fn(values: &[wasmer::Value]) -> anyhow::Result<Vec<wasmer::Val>, wasmer::RuntimeError> {
+            fn dynamic_callback_fn(values: &[wasmer::Value]) -> Result<Vec<wasmer::Value>, wasmer::RuntimeError> {
                 assert_eq!(values[0], wasmer::Value::I32(1));
                 Ok(vec![ $( <$result_type>::expected_val(1) ),* ])
             }
@@ -64,8 +64,9 @@ macro_rules! mvr_test {
             fn dynamic(config: crate::Config) -> anyhow::Result<()> {
                 let mut store = config.store();
                 let module = get_module(&store)?;
-                let callback_fn = wasmer::Function::new(&store, &wasmer::FunctionType::new(vec![wasmer::ValType::I32], vec![ $( <$result_type>::expected_valtype() ),* ]), dynamic_callback_fn);
+                let callback_fn = wasmer::Function::new(&mut store, &wasmer::FunctionType::new(vec![wasmer::Type::I32], vec![ $( <$result_type>::expected_valtype() ),* ]), dynamic_callback_fn);
                 let instance = wasmer::Instance::new(
+                    &mut store,
                     &module,
                     &wasmer::imports! {
                         "host" => {
@@ -74,9 +75,9 @@ macro_rules! mvr_test {
                     }
                 )?;
                 let expected_value = vec![ $( <$result_type>::expected_val(1) ),* ].into_boxed_slice();
-                assert_eq!(instance.exports.get_function("test_call")?.call(&[wasmer::Val::I32(1)])?,
+                assert_eq!(instance.exports.get_function("test_call")?.call(&mut store, &[wasmer::Value::I32(1)])?,
                            expected_value);
-                assert_eq!(instance.exports.get_function("test_call_indirect")?.call(&[wasmer::Val::I32(1)])?,
+                assert_eq!(instance.exports.get_function("test_call_indirect")?.call(&mut store, &[wasmer::Value::I32(1)])?,
                            expected_value);
                 Ok(())
             }
@@ -86,51 +87,51 @@ macro_rules! mvr_test {
 
 trait ExpectedExpr {
     fn expected_value(n: i32) -> Self;
-    fn expected_val(n: i32) -> wasmer::Val;
-    fn expected_valtype() -> wasmer::ValType;
+    fn expected_val(n: i32) -> wasmer::Value;
+    fn expected_valtype() -> wasmer::Type;
 }
 impl ExpectedExpr for i32 {
     fn expected_value(n: i32) -> i32 {
         n + 1
     }
-    fn expected_val(n: i32) -> wasmer::Val {
-        wasmer::Val::I32(Self::expected_value(n))
+    fn expected_val(n: i32) -> wasmer::Value {
+        wasmer::Value::I32(Self::expected_value(n))
     }
-    fn expected_valtype() -> wasmer::ValType {
-        wasmer::ValType::I32
+    fn expected_valtype() -> wasmer::Type {
+        wasmer::Type::I32
     }
 }
 impl ExpectedExpr for i64 {
     fn expected_value(n: i32) -> i64 {
         n as i64 + 2i64
     }
-    fn expected_val(n: i32) -> wasmer::Val {
-        wasmer::Val::I64(Self::expected_value(n))
+    fn expected_val(n: i32) -> wasmer::Value {
+        wasmer::Value::I64(Self::expected_value(n))
     }
-    fn expected_valtype() -> wasmer::ValType {
-        wasmer::ValType::I64
+    fn expected_valtype() -> wasmer::Type {
+        wasmer::Type::I64
     }
 }
 impl ExpectedExpr for f32 {
     fn expected_value(n: i32) -> f32 {
         n as f32 * 0.1
     }
-    fn expected_val(n: i32) -> wasmer::Val {
-        wasmer::Val::F32(Self::expected_value(n))
+    fn expected_val(n: i32) -> wasmer::Value {
+        wasmer::Value::F32(Self::expected_value(n))
     }
-    fn expected_valtype() -> wasmer::ValType {
-        wasmer::ValType::F32
+    fn expected_valtype() -> wasmer::Type {
+        wasmer::Type::F32
     }
 }
 impl ExpectedExpr for f64 {
     fn expected_value(n: i32) -> f64 {
         n as f64 * 0.12
     }
-    fn expected_val(n: i32) -> wasmer::Val {
-        wasmer::Val::F64(Self::expected_value(n))
+    fn expected_val(n: i32) -> wasmer::Value {
+        wasmer::Value::F64(Self::expected_value(n))
     }
-    fn expected_valtype() -> wasmer::ValType {
-        wasmer::ValType::F64
+    fn expected_valtype() -> wasmer::Type {
+        wasmer::Type::F64
     }
 }
 
@@ -37,9 +37,6 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
     if is_exception_handling {
         features.exceptions(true);
     }
-    if config.compiler == crate::Compiler::Singlepass {
-        features.multi_value(false);
-    }
     config.set_features(features);
     config.set_nan_canonicalization(try_nan_canonicalization);
 
@@ -66,14 +63,6 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
         // We allow this, so tests can be run properly for `simd_const` test.
         wast.allow_instantiation_failures(&["Validation error: multiple tables"]);
     }
-    if config.compiler == crate::Compiler::Singlepass {
-        // We don't support multivalue yet in singlepass
-        wast.allow_instantiation_failures(&[
-            "Validation error: invalid result arity: func type returns multiple values",
-            "Validation error: blocks, loops, and ifs may only produce a resulttype when multi-value is not enabled",
-            "Validation error: func type returns multiple values but the multi-value feature is not enabled",
-        ]);
-    }
     wast.fail_fast = false;
     let path = Path::new(wast_path);
     wast.run_file(path)